@@ -132,14 +132,15 @@ impl<'a> CanvasPaintState<'a> {
 
 impl GenericPathBuilder for azure_hl::PathBuilder {
     fn arc(
-        &self,
+        &mut self,
         origin: Point2D<f32>,
         radius: f32,
         start_angle: f32,
         end_angle: f32,
         anticlockwise: bool,
     ) {
-        self.arc(
+        azure_hl::PathBuilder::arc(
+            self,
             origin as Point2D<AzFloat>,
             radius as AzFloat,
             start_angle as AzFloat,
@@ -148,22 +149,23 @@ impl GenericPathBuilder for azure_hl::PathBuilder {
         );
     }
     fn bezier_curve_to(
-        &self,
+        &mut self,
         control_point1: &Point2D<f32>,
         control_point2: &Point2D<f32>,
         control_point3: &Point2D<f32>,
     ) {
-        self.bezier_curve_to(
+        azure_hl::PathBuilder::bezier_curve_to(
+            self,
             control_point1 as &Point2D<AzFloat>,
             control_point2 as &Point2D<AzFloat>,
             control_point3 as &Point2D<AzFloat>,
         );
     }
-    fn close(&self) {
-        self.close();
+    fn close(&mut self) {
+        azure_hl::PathBuilder::close(self);
     }
     fn ellipse(
-        &self,
+        &mut self,
         origin: Point2D<f32>,
         radius_x: f32,
         radius_y: f32,
@@ -172,7 +174,8 @@ impl GenericPathBuilder for azure_hl::PathBuilder {
         end_angle: f32,
         anticlockwise: bool,
     ) {
-        self.ellipse(
+        azure_hl::PathBuilder::ellipse(
+            self,
             origin as Point2D<AzFloat>,
             radius_x as AzFloat,
             radius_y as AzFloat,
@@ -186,30 +189,36 @@ impl GenericPathBuilder for azure_hl::PathBuilder {
         let AzPoint { x, y } = self.get_current_point();
         Point2D::new(x as f32, y as f32)
     }
-    fn line_to(&self, point: Point2D<f32>) {
-        self.line_to(point as Point2D<AzFloat>);
+    fn line_to(&mut self, point: Point2D<f32>) {
+        azure_hl::PathBuilder::line_to(self, point as Point2D<AzFloat>);
     }
-    fn move_to(&self, point: Point2D<f32>) {
-        self.move_to(point as Point2D<AzFloat>);
+    fn move_to(&mut self, point: Point2D<f32>) {
+        azure_hl::PathBuilder::move_to(self, point as Point2D<AzFloat>);
     }
-    fn quadratic_curve_to(&self, control_point: &Point2D<f32>, end_point: &Point2D<f32>) {
-        self.quadratic_curve_to(
+    fn quadratic_curve_to(&mut self, control_point: &Point2D<f32>, end_point: &Point2D<f32>) {
+        azure_hl::PathBuilder::quadratic_curve_to(
+            self,
             control_point as &Point2D<AzFloat>,
             end_point as &Point2D<AzFloat>,
         );
     }
-    fn finish(&self) -> Path {
-        Path::Azure(self.finish())
+    fn finish(&mut self) -> Path {
+        Path::Azure(azure_hl::PathBuilder::finish(self))
     }
 }
 
 impl GenericDrawTarget for azure_hl::DrawTarget {
-    fn clear_rect(&self, rect: &Rect<f32>) {
-        self.clear_rect(rect as &Rect<AzFloat>);
+    fn clear_rect(&mut self, rect: &Rect<f32>) {
+        azure_hl::DrawTarget::clear_rect(self, rect as &Rect<AzFloat>);
     }
 
-    fn copy_surface(&self, surface: SourceSurface, source: Rect<i32>, destination: Point2D<i32>) {
-        self.copy_surface(surface.into_azure(), source, destination);
+    fn copy_surface(
+        &mut self,
+        surface: SourceSurface,
+        source: Rect<i32>,
+        destination: Point2D<i32>,
+    ) {
+        azure_hl::DrawTarget::copy_surface(self, surface.into_azure(), source, destination);
     }
 
     fn create_gradient_stops(
@@ -243,7 +252,7 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
             .map(|s| SourceSurface::Azure(s))
     }
     fn draw_surface(
-        &self,
+        &mut self,
         surface: SourceSurface,
         dest: Rect<f64>,
         source: Rect<f64>,
@@ -256,7 +265,8 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
             draw_options.as_azure().composition,
             azure_hl::AntialiasMode::None,
         );
-        self.draw_surface(
+        azure_hl::DrawTarget::draw_surface(
+            self,
             surface.into_azure(),
             dest.to_azure_style(),
             source.to_azure_style(),
@@ -265,7 +275,7 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
         );
     }
     fn draw_surface_with_shadow(
-        &self,
+        &mut self,
         surface: SourceSurface,
         dest: &Point2D<f32>,
         color: &Color,
@@ -273,7 +283,8 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
         sigma: f32,
         operator: CompositionOp,
     ) {
-        self.draw_surface_with_shadow(
+        azure_hl::DrawTarget::draw_surface_with_shadow(
+            self,
             surface.into_azure(),
             dest as &Point2D<AzFloat>,
             color.as_azure(),
@@ -282,15 +293,22 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
             operator.into_azure(),
         );
     }
-    fn fill(&self, path: &Path, pattern: Pattern, draw_options: &DrawOptions) {
-        self.fill(
+    fn fill(&mut self, path: &Path, pattern: Pattern, draw_options: &DrawOptions) {
+        azure_hl::DrawTarget::fill(
+            self,
             path.as_azure(),
             pattern.as_azure().to_pattern_ref(),
             draw_options.as_azure(),
         );
     }
-    fn fill_rect(&self, rect: &Rect<f32>, pattern: Pattern, draw_options: Option<&DrawOptions>) {
-        self.fill_rect(
+    fn fill_rect(
+        &mut self,
+        rect: &Rect<f32>,
+        pattern: Pattern,
+        draw_options: Option<&DrawOptions>,
+    ) {
+        azure_hl::DrawTarget::fill_rect(
+            self,
             rect as &Rect<AzFloat>,
             pattern.as_azure().to_pattern_ref(),
             draw_options.map(|x| x.as_azure()),
@@ -306,26 +324,27 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
     fn get_transform(&self) -> Transform2D<f32> {
         self.get_transform() as Transform2D<f32>
     }
-    fn pop_clip(&self) {
-        self.pop_clip();
+    fn pop_clip(&mut self) {
+        azure_hl::DrawTarget::pop_clip(self);
     }
-    fn push_clip(&self, path: &Path) {
-        self.push_clip(path.as_azure());
+    fn push_clip(&mut self, path: &Path) {
+        azure_hl::DrawTarget::push_clip(self, path.as_azure());
     }
-    fn set_transform(&self, matrix: &Transform2D<f32>) {
-        self.set_transform(matrix as &Transform2D<AzFloat>);
+    fn set_transform(&mut self, matrix: &Transform2D<f32>) {
+        azure_hl::DrawTarget::set_transform(self, matrix as &Transform2D<AzFloat>);
     }
     fn snapshot(&self) -> SourceSurface {
         SourceSurface::Azure(self.snapshot())
     }
     fn stroke(
-        &self,
+        &mut self,
         path: &Path,
         pattern: Pattern,
         stroke_options: &StrokeOptions,
         draw_options: &DrawOptions,
     ) {
-        self.stroke(
+        azure_hl::DrawTarget::stroke(
+            self,
             path.as_azure(),
             pattern.as_azure().to_pattern_ref(),
             stroke_options.as_azure(),
@@ -333,7 +352,7 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
         );
     }
     fn stroke_line(
-        &self,
+        &mut self,
         start: Point2D<f32>,
         end: Point2D<f32>,
         pattern: Pattern,
@@ -354,7 +373,8 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
             stroke_options.mDashPattern,
         );
 
-        self.stroke_line(
+        azure_hl::DrawTarget::stroke_line(
+            self,
             start,
             end,
             pattern.as_azure().to_pattern_ref(),
@@ -363,13 +383,14 @@ impl GenericDrawTarget for azure_hl::DrawTarget {
         );
     }
     fn stroke_rect(
-        &self,
+        &mut self,
         rect: &Rect<f32>,
         pattern: Pattern,
         stroke_options: &StrokeOptions,
         draw_options: &DrawOptions,
     ) {
-        self.stroke_rect(
+        azure_hl::DrawTarget::stroke_rect(
+            self,
             rect as &Rect<AzFloat>,
             pattern.as_azure().to_pattern_ref(),
             stroke_options.as_azure(),
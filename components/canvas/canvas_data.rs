@@ -83,7 +83,7 @@ pub trait Backend {
 /// azure's and raqote's PathBuilder.
 pub trait GenericPathBuilder {
     fn arc(
-        &self,
+        &mut self,
         origin: Point2D<f32>,
         radius: f32,
         start_angle: f32,
@@ -91,14 +91,14 @@ pub trait GenericPathBuilder {
         anticlockwise: bool,
     );
     fn bezier_curve_to(
-        &self,
+        &mut self,
         control_point1: &Point2D<f32>,
         control_point2: &Point2D<f32>,
         control_point3: &Point2D<f32>,
     );
-    fn close(&self);
+    fn close(&mut self);
     fn ellipse(
-        &self,
+        &mut self,
         origin: Point2D<f32>,
         radius_x: f32,
         radius_y: f32,
@@ -108,31 +108,31 @@ pub trait GenericPathBuilder {
         anticlockwise: bool,
     );
     fn get_current_point(&self) -> Point2D<f32>;
-    fn line_to(&self, point: Point2D<f32>);
-    fn move_to(&self, point: Point2D<f32>);
-    fn quadratic_curve_to(&self, control_point: &Point2D<f32>, end_point: &Point2D<f32>);
-    fn finish(&self) -> Path;
+    fn line_to(&mut self, point: Point2D<f32>);
+    fn move_to(&mut self, point: Point2D<f32>);
+    fn quadratic_curve_to(&mut self, control_point: &Point2D<f32>, end_point: &Point2D<f32>);
+    fn finish(&mut self) -> Path;
 }
 
 /// A wrapper around a stored PathBuilder and an optional transformation that should be
 /// applied to any points to ensure they are in the matching device space.
 struct PathBuilderRef<'a> {
-    builder: &'a Box<dyn GenericPathBuilder>,
+    builder: &'a mut Box<dyn GenericPathBuilder>,
     transform: Transform2D<f32>,
 }
 
 impl<'a> PathBuilderRef<'a> {
-    fn line_to(&self, pt: &Point2D<f32>) {
+    fn line_to(&mut self, pt: &Point2D<f32>) {
         let pt = self.transform.transform_point(pt);
         self.builder.line_to(pt);
     }
 
-    fn move_to(&self, pt: &Point2D<f32>) {
+    fn move_to(&mut self, pt: &Point2D<f32>) {
         let pt = self.transform.transform_point(pt);
         self.builder.move_to(pt);
     }
 
-    fn rect(&self, rect: &Rect<f32>) {
+    fn rect(&mut self, rect: &Rect<f32>) {
         let (first, second, third, fourth) = (
             Point2D::new(rect.origin.x, rect.origin.y),
             Point2D::new(rect.origin.x + rect.size.width, rect.origin.y),
@@ -151,14 +151,14 @@ impl<'a> PathBuilderRef<'a> {
         self.builder.close();
     }
 
-    fn quadratic_curve_to(&self, cp: &Point2D<f32>, endpoint: &Point2D<f32>) {
+    fn quadratic_curve_to(&mut self, cp: &Point2D<f32>, endpoint: &Point2D<f32>) {
         self.builder.quadratic_curve_to(
             &self.transform.transform_point(cp),
             &self.transform.transform_point(endpoint),
         )
     }
 
-    fn bezier_curve_to(&self, cp1: &Point2D<f32>, cp2: &Point2D<f32>, endpoint: &Point2D<f32>) {
+    fn bezier_curve_to(&mut self, cp1: &Point2D<f32>, cp2: &Point2D<f32>, endpoint: &Point2D<f32>) {
         self.builder.bezier_curve_to(
             &self.transform.transform_point(cp1),
             &self.transform.transform_point(cp2),
@@ -166,14 +166,21 @@ impl<'a> PathBuilderRef<'a> {
         )
     }
 
-    fn arc(&self, center: &Point2D<f32>, radius: f32, start_angle: f32, end_angle: f32, ccw: bool) {
+    fn arc(
+        &mut self,
+        center: &Point2D<f32>,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        ccw: bool,
+    ) {
         let center = self.transform.transform_point(center);
         self.builder
             .arc(center, radius, start_angle, end_angle, ccw);
     }
 
     pub fn ellipse(
-        &self,
+        &mut self,
         center: &Point2D<f32>,
         radius_x: f32,
         radius_y: f32,
@@ -208,8 +215,13 @@ impl<'a> PathBuilderRef<'a> {
 // This defines required methods for DrawTarget of azure and raqote
 // The prototypes are derived from azure's methods.
 pub trait GenericDrawTarget {
-    fn clear_rect(&self, rect: &Rect<f32>);
-    fn copy_surface(&self, surface: SourceSurface, source: Rect<i32>, destination: Point2D<i32>);
+    fn clear_rect(&mut self, rect: &Rect<f32>);
+    fn copy_surface(
+        &mut self,
+        surface: SourceSurface,
+        source: Rect<i32>,
+        destination: Point2D<i32>,
+    );
     fn create_gradient_stops(
         &self,
         gradient_stops: Vec<GradientStop>,
@@ -228,7 +240,7 @@ pub trait GenericDrawTarget {
         stride: i32,
     ) -> Option<SourceSurface>;
     fn draw_surface(
-        &self,
+        &mut self,
         surface: SourceSurface,
         dest: Rect<f64>,
         source: Rect<f64>,
@@ -236,7 +248,7 @@ pub trait GenericDrawTarget {
         draw_options: &DrawOptions,
     );
     fn draw_surface_with_shadow(
-        &self,
+        &mut self,
         surface: SourceSurface,
         dest: &Point2D<f32>,
         color: &Color,
@@ -244,24 +256,39 @@ pub trait GenericDrawTarget {
         sigma: f32,
         operator: CompositionOp,
     );
-    fn fill(&self, path: &Path, pattern: Pattern, draw_options: &DrawOptions);
-    fn fill_rect(&self, rect: &Rect<f32>, pattern: Pattern, draw_options: Option<&DrawOptions>);
+    fn fill(&mut self, path: &Path, pattern: Pattern, draw_options: &DrawOptions);
+    fn fill_rect(&mut self, rect: &Rect<f32>, pattern: Pattern, draw_options: Option<&DrawOptions>);
+    /// Draw `text` starting at `origin`. Backends without text rendering draw
+    /// nothing.
+    fn fill_text(
+        &mut self,
+        text: &str,
+        origin: Point2D<f32>,
+        max_width: Option<f32>,
+        _pattern: Pattern,
+        _draw_options: &DrawOptions,
+    ) {
+        error!(
+            "Unimplemented canvas2d.fillText. Values received: {}, {}, {}, {:?}.",
+            text, origin.x, origin.y, max_width
+        );
+    }
     fn get_format(&self) -> SurfaceFormat;
     fn get_size(&self) -> Size2D<i32>;
     fn get_transform(&self) -> Transform2D<f32>;
-    fn pop_clip(&self);
-    fn push_clip(&self, path: &Path);
-    fn set_transform(&self, matrix: &Transform2D<f32>);
+    fn pop_clip(&mut self);
+    fn push_clip(&mut self, path: &Path);
+    fn set_transform(&mut self, matrix: &Transform2D<f32>);
     fn snapshot(&self) -> SourceSurface;
     fn stroke(
-        &self,
+        &mut self,
         path: &Path,
         pattern: Pattern,
         stroke_options: &StrokeOptions,
         draw_options: &DrawOptions,
     );
     fn stroke_line(
-        &self,
+        &mut self,
         start: Point2D<f32>,
         end: Point2D<f32>,
         pattern: Pattern,
@@ -269,7 +296,7 @@ pub trait GenericDrawTarget {
         draw_options: &DrawOptions,
     );
     fn stroke_rect(
-        &self,
+        &mut self,
         rect: &Rect<f32>,
         pattern: Pattern,
         stroke_options: &StrokeOptions,
@@ -291,14 +318,14 @@ pub enum GradientStop {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::AzGradientStop),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(raqote::GradientStop),
 }
 
 pub enum GradientStops {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::GradientStops),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(Vec<raqote::GradientStop>),
 }
 
 #[derive(Clone)]
@@ -306,7 +333,7 @@ pub enum Color {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::Color),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(raqote::SolidSource),
 }
 
 #[derive(Clone)]
@@ -314,7 +341,7 @@ pub enum CompositionOp {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::CompositionOp),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(raqote::BlendMode),
 }
 
 pub enum SurfaceFormat {
@@ -329,14 +356,14 @@ pub enum SourceSurface {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::SourceSurface),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(crate::raqote_backend::Surface),
 }
 
 pub enum Path {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::Path),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(raqote::Path),
 }
 
 #[derive(Clone)]
@@ -344,7 +371,7 @@ pub enum Pattern {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::Pattern),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(crate::raqote_backend::Pattern),
 }
 
 pub enum DrawSurfaceOptions {
@@ -359,7 +386,7 @@ pub enum DrawOptions {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::DrawOptions),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(()),
+    Raqote(raqote::DrawOptions),
 }
 
 #[derive(Clone)]
@@ -367,7 +394,7 @@ pub enum StrokeOptions<'a> {
     #[cfg(feature = "canvas2d-azure")]
     Azure(azure::azure_hl::StrokeOptions<'a>),
     #[cfg(feature = "canvas2d-raqote")]
-    Raqote(PhantomData<&'a ()>),
+    Raqote(raqote::StrokeStyle, PhantomData<&'a ()>),
 }
 
 #[derive(Clone, Copy)]
@@ -409,7 +436,7 @@ impl<'a> CanvasData<'a> {
         canvas_id: CanvasId,
     ) -> CanvasData<'a> {
         let backend = create_backend();
-        let draw_target = backend.create_drawtarget(size);;
+        let draw_target = backend.create_drawtarget(size);
         let webrender_api = webrender_api_sender.create_api();
         CanvasData {
             backend,
@@ -426,7 +453,7 @@ impl<'a> CanvasData<'a> {
     }
 
     pub fn draw_image(
-        &self,
+        &mut self,
         image_data: Vec<u8>,
         image_size: Size2D<f64>,
         dest_rect: Rect<f64>,
@@ -442,14 +469,15 @@ impl<'a> CanvasData<'a> {
             image_data.into()
         };
 
-        let writer = |draw_target: &dyn GenericDrawTarget| {
+        let draw_options = self.state.draw_options.clone();
+        let writer = move |draw_target: &mut dyn GenericDrawTarget| {
             write_image(
                 draw_target,
                 image_data,
                 source_rect.size,
                 dest_rect,
                 smoothing_enabled,
-                &self.state.draw_options,
+                &draw_options,
             );
         };
 
@@ -459,10 +487,9 @@ impl<'a> CanvasData<'a> {
                 Size2D::new(dest_rect.size.width as f32, dest_rect.size.height as f32),
             );
 
-            // TODO(pylbrecht) pass another closure for raqote
             self.draw_with_shadow(&rect, writer);
         } else {
-            writer(&*self.drawtarget);
+            writer(&mut *self.drawtarget);
         }
     }
 
@@ -478,14 +505,21 @@ impl<'a> CanvasData<'a> {
         }
     }
 
-    pub fn fill_text(&self, text: String, x: f64, y: f64, max_width: Option<f64>) {
-        error!(
-            "Unimplemented canvas2d.fillText. Values received: {}, {}, {}, {:?}.",
-            text, x, y, max_width
+    pub fn fill_text(&mut self, text: String, x: f64, y: f64, max_width: Option<f64>) {
+        if self.state.fill_style.is_zero_size_gradient() {
+            return; // Paint nothing if gradient size is zero.
+        }
+
+        self.drawtarget.fill_text(
+            &text,
+            Point2D::new(x as f32, y as f32),
+            max_width.map(|width| width as f32),
+            self.state.fill_style.clone(),
+            &self.state.draw_options,
         );
     }
 
-    pub fn fill_rect(&self, rect: &Rect<f32>) {
+    pub fn fill_rect(&mut self, rect: &Rect<f32>) {
         if self.state.fill_style.is_zero_size_gradient() {
             return; // Paint nothing if gradient size is zero.
         }
@@ -498,12 +532,10 @@ impl<'a> CanvasData<'a> {
         );
 
         if self.need_to_draw_shadow() {
-            self.draw_with_shadow(&draw_rect, |new_draw_target: &dyn GenericDrawTarget| {
-                new_draw_target.fill_rect(
-                    &draw_rect,
-                    self.state.fill_style.clone(),
-                    Some(&self.state.draw_options),
-                );
+            let fill_style = self.state.fill_style.clone();
+            let draw_options = self.state.draw_options.clone();
+            self.draw_with_shadow(&draw_rect, |new_draw_target: &mut dyn GenericDrawTarget| {
+                new_draw_target.fill_rect(&draw_rect, fill_style, Some(&draw_options));
             });
         } else {
             self.drawtarget.fill_rect(
@@ -514,23 +546,21 @@ impl<'a> CanvasData<'a> {
         }
     }
 
-    pub fn clear_rect(&self, rect: &Rect<f32>) {
+    pub fn clear_rect(&mut self, rect: &Rect<f32>) {
         self.drawtarget.clear_rect(rect);
     }
 
-    pub fn stroke_rect(&self, rect: &Rect<f32>) {
+    pub fn stroke_rect(&mut self, rect: &Rect<f32>) {
         if self.state.stroke_style.is_zero_size_gradient() {
             return; // Paint nothing if gradient size is zero.
         }
 
         if self.need_to_draw_shadow() {
-            self.draw_with_shadow(&rect, |new_draw_target: &dyn GenericDrawTarget| {
-                new_draw_target.stroke_rect(
-                    rect,
-                    self.state.stroke_style.clone(),
-                    &self.state.stroke_opts,
-                    &self.state.draw_options,
-                );
+            let stroke_style = self.state.stroke_style.clone();
+            let stroke_opts = self.state.stroke_opts.clone();
+            let draw_options = self.state.draw_options.clone();
+            self.draw_with_shadow(&rect, |new_draw_target: &mut dyn GenericDrawTarget| {
+                new_draw_target.stroke_rect(rect, stroke_style, &stroke_opts, &draw_options);
             });
         } else if rect.size.width == 0. || rect.size.height == 0. {
             self.drawtarget.stroke_line(
@@ -570,7 +600,7 @@ impl<'a> CanvasData<'a> {
 
         // If a user-space builder exists, create a finished path from it.
         let new_state = match *self.path_state.as_mut().unwrap() {
-            PathState::UserSpacePathBuilder(ref builder, ref mut transform) => {
+            PathState::UserSpacePathBuilder(ref mut builder, ref mut transform) => {
                 Some((builder.finish(), transform.take()))
             },
             PathState::DeviceSpacePathBuilder(..) | PathState::UserSpacePath(..) => None,
@@ -595,8 +625,8 @@ impl<'a> CanvasData<'a> {
 
         // If a device-space builder is present, create a user-space path from its
         // finished path by inverting the initial transformation.
-        let new_state = match self.path_state.as_ref().unwrap() {
-            PathState::DeviceSpacePathBuilder(ref builder) => {
+        let new_state = match self.path_state.as_mut().unwrap() {
+            PathState::DeviceSpacePathBuilder(ref mut builder) => {
                 let path = builder.finish();
                 let inverse = match self.drawtarget.get_transform().inverse() {
                     Some(m) => m,
@@ -605,7 +635,7 @@ impl<'a> CanvasData<'a> {
                         return;
                     },
                 };
-                let builder = path.transformed_copy_to_builder(&inverse);
+                let mut builder = path.transformed_copy_to_builder(&inverse);
                 Some(builder.finish())
             },
             PathState::UserSpacePathBuilder(..) | PathState::UserSpacePath(..) => None,
@@ -691,59 +721,39 @@ impl<'a> CanvasData<'a> {
             ));
         }
 
-        // Rust is not pleased by returning a reference to a builder in some branches
-        // and overwriting path_state in other ones. The following awkward use of duplicate
-        // matches works around the resulting borrow errors.
-        let new_state = {
-            match self.path_state.as_ref().unwrap() {
-                &PathState::UserSpacePathBuilder(_, None) |
-                &PathState::DeviceSpacePathBuilder(_) => None,
-                &PathState::UserSpacePathBuilder(ref builder, Some(ref transform)) => {
-                    let path = builder.finish();
-                    Some(PathState::DeviceSpacePathBuilder(
-                        path.transformed_copy_to_builder(transform),
-                    ))
-                },
-                &PathState::UserSpacePath(ref path, Some(ref transform)) => Some(
-                    PathState::DeviceSpacePathBuilder(path.transformed_copy_to_builder(transform)),
-                ),
-                &PathState::UserSpacePath(ref path, None) => Some(PathState::UserSpacePathBuilder(
-                    path.copy_to_builder(),
-                    None,
-                )),
-            }
-        };
-        match new_state {
-            // There's a new builder value that needs to be stored.
-            Some(state) => self.path_state = Some(state),
-            // There's an existing builder value that can be returned immediately.
-            None => match self.path_state.as_ref().unwrap() {
-                &PathState::UserSpacePathBuilder(ref builder, None) => {
-                    return PathBuilderRef {
-                        builder,
-                        transform: Transform2D::identity(),
-                    };
-                },
-                &PathState::DeviceSpacePathBuilder(ref builder) => {
-                    return PathBuilderRef {
-                        builder,
-                        transform: self.drawtarget.get_transform(),
-                    };
-                },
-                _ => unreachable!(),
+        let new_state = match self.path_state.as_mut().unwrap() {
+            &mut PathState::UserSpacePathBuilder(_, None) |
+            &mut PathState::DeviceSpacePathBuilder(_) => None,
+            &mut PathState::UserSpacePathBuilder(ref mut builder, Some(ref transform)) => {
+                let path = builder.finish();
+                Some(PathState::DeviceSpacePathBuilder(
+                    path.transformed_copy_to_builder(transform),
+                ))
             },
+            &mut PathState::UserSpacePath(ref path, Some(ref transform)) => Some(
+                PathState::DeviceSpacePathBuilder(path.transformed_copy_to_builder(transform)),
+            ),
+            &mut PathState::UserSpacePath(ref path, None) => Some(PathState::UserSpacePathBuilder(
+                path.copy_to_builder(),
+                None,
+            )),
+        };
+        if let Some(state) = new_state {
+            self.path_state = Some(state);
         }
 
-        match self.path_state.as_ref().unwrap() {
-            &PathState::UserSpacePathBuilder(ref builder, None) => PathBuilderRef {
+        match self.path_state.as_mut().unwrap() {
+            &mut PathState::UserSpacePathBuilder(ref mut builder, None) => PathBuilderRef {
                 builder,
                 transform: Transform2D::identity(),
             },
-            &PathState::DeviceSpacePathBuilder(ref builder) => PathBuilderRef {
+            &mut PathState::DeviceSpacePathBuilder(ref mut builder) => PathBuilderRef {
                 builder,
                 transform: self.drawtarget.get_transform(),
             },
-            &PathState::UserSpacePathBuilder(..) | &PathState::UserSpacePath(..) => unreachable!(),
+            &mut PathState::UserSpacePathBuilder(..) | &mut PathState::UserSpacePath(..) => {
+                unreachable!()
+            },
         }
     }
 
@@ -1024,7 +1034,7 @@ impl<'a> CanvasData<'a> {
     }
 
     fn create_draw_target_for_shadow(&self, source_rect: &Rect<f32>) -> Box<dyn GenericDrawTarget> {
-        let draw_target = self.drawtarget.create_similar_draw_target(
+        let mut draw_target = self.drawtarget.create_similar_draw_target(
             &Size2D::new(
                 source_rect.size.width as i32,
                 source_rect.size.height as i32,
@@ -1038,13 +1048,13 @@ impl<'a> CanvasData<'a> {
         draw_target
     }
 
-    fn draw_with_shadow<F>(&self, rect: &Rect<f32>, draw_shadow_source: F)
+    fn draw_with_shadow<F>(&mut self, rect: &Rect<f32>, draw_shadow_source: F)
     where
-        F: FnOnce(&dyn GenericDrawTarget),
+        F: FnOnce(&mut dyn GenericDrawTarget),
     {
         let shadow_src_rect = self.state.transform.transform_rect(rect);
-        let new_draw_target = self.create_draw_target_for_shadow(&shadow_src_rect);
-        draw_shadow_source(&*new_draw_target);
+        let mut new_draw_target = self.create_draw_target_for_shadow(&shadow_src_rect);
+        draw_shadow_source(&mut *new_draw_target);
         self.drawtarget.draw_surface_with_shadow(
             new_draw_target.snapshot(),
             &Point2D::new(
@@ -1116,7 +1126,7 @@ pub struct CanvasPaintState<'a> {
 /// dest_rect: Area of the destination target where the pixels will be copied
 /// smoothing_enabled: It determines if smoothing is applied to the image result
 fn write_image(
-    draw_target: &dyn GenericDrawTarget,
+    draw_target: &mut dyn GenericDrawTarget,
     image_data: Vec<u8>,
     image_size: Size2D<f64>,
     dest_rect: Rect<f64>,
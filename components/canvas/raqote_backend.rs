@@ -2,60 +2,107 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::canvas_data;
 use crate::canvas_data::{
     Backend, CanvasPaintState, Color, CompositionOp, DrawOptions, ExtendMode, Filter,
-    GenericDrawTarget, GenericPathBuilder, GradientStop, GradientStops, Path, Pattern,
-    SourceSurface, StrokeOptions, SurfaceFormat,
+    GenericDrawTarget, GenericPathBuilder, GradientStop, GradientStops, Path, SourceSurface,
+    StrokeOptions, SurfaceFormat,
 };
 use crate::canvas_paint_thread::AntialiasMode;
 use canvas_traits::canvas::*;
 use cssparser::RGBA;
 use euclid::{Point2D, Rect, Size2D, Transform2D, Vector2D};
+use font_kit::family_name::FamilyName;
+use font_kit::loaders::default::Font;
+use font_kit::properties::Properties;
+use font_kit::source::Source as _;
+use font_kit::source::SystemSource;
+use raqote::PathOp;
+use std::cmp::Ordering;
+use std::f32::consts::PI;
 use std::marker::PhantomData;
+use std::mem;
+use std::sync::Arc;
+
+/// The size canvas text is drawn at, as the paint thread only knows about the
+/// default `10px sans-serif` font.
+const FONT_SIZE: f32 = 10.;
+
+/// The number of line segments curves are flattened into when hit testing paths.
+const CURVE_STEPS: usize = 16;
+
+thread_local! {
+    static SANS_SERIF: Option<Font> = SystemSource::new()
+        .select_best_match(&[FamilyName::SansSerif], &Properties::new())
+        .ok()
+        .and_then(|handle| handle.load().ok());
+}
 
 pub struct RaqoteBackend;
 
 impl Backend for RaqoteBackend {
-    fn get_composition_op(&self, _opts: &DrawOptions) -> CompositionOp {
-        unimplemented!()
+    fn get_composition_op(&self, opts: &DrawOptions) -> CompositionOp {
+        CompositionOp::Raqote(opts.as_raqote().blend_mode)
     }
 
-    fn need_to_draw_shadow(&self, _color: &Color) -> bool {
-        unimplemented!()
+    fn need_to_draw_shadow(&self, color: &Color) -> bool {
+        color.as_raqote().a != 0
     }
 
-    fn size_from_pattern(&self, _rect: &Rect<f32>, _pattern: &Pattern) -> Option<Size2D<f32>> {
-        unimplemented!()
+    fn size_from_pattern(
+        &self,
+        rect: &Rect<f32>,
+        pattern: &canvas_data::Pattern,
+    ) -> Option<Size2D<f32>> {
+        match pattern.as_raqote() {
+            Pattern::Surface(ref pattern) => {
+                let surface_size = pattern.surface.size;
+                let size = match (pattern.repeat_x, pattern.repeat_y) {
+                    (true, true) => rect.size,
+                    (true, false) => Size2D::new(rect.size.width, surface_size.height as f32),
+                    (false, true) => Size2D::new(surface_size.width as f32, rect.size.height),
+                    (false, false) => {
+                        Size2D::new(surface_size.width as f32, surface_size.height as f32)
+                    },
+                };
+                Some(size)
+            },
+            Pattern::Color(..) | Pattern::LinearGradient(..) | Pattern::RadialGradient(..) => None,
+        }
     }
 
-    fn set_shadow_color<'a>(&mut self, _color: RGBA, _state: &mut CanvasPaintState<'a>) {
-        unimplemented!()
+    fn set_shadow_color<'a>(&mut self, color: RGBA, state: &mut CanvasPaintState<'a>) {
+        state.shadow_color = Color::Raqote(color.to_raqote_style());
     }
 
     fn set_fill_style<'a>(
         &mut self,
-        _style: FillOrStrokeStyle,
-        _state: &mut CanvasPaintState<'a>,
+        style: FillOrStrokeStyle,
+        state: &mut CanvasPaintState<'a>,
         _drawtarget: &dyn GenericDrawTarget,
     ) {
-        unimplemented!()
+        if let Some(pattern) = style.to_raqote_pattern() {
+            state.fill_style = canvas_data::Pattern::Raqote(pattern);
+        }
     }
 
     fn set_stroke_style<'a>(
         &mut self,
-        _style: FillOrStrokeStyle,
-        _state: &mut CanvasPaintState<'a>,
+        style: FillOrStrokeStyle,
+        state: &mut CanvasPaintState<'a>,
         _drawtarget: &dyn GenericDrawTarget,
     ) {
-        unimplemented!()
+        if let Some(pattern) = style.to_raqote_pattern() {
+            state.stroke_style = canvas_data::Pattern::Raqote(pattern);
+        }
     }
 
     fn set_global_composition<'a>(
         &mut self,
-        _op: CompositionOrBlending,
-        _state: &mut CanvasPaintState<'a>,
+        op: CompositionOrBlending,
+        state: &mut CanvasPaintState<'a>,
     ) {
-        unimplemented!()
+        state.draw_options.as_raqote_mut().blend_mode = op.to_raqote_style();
     }
 
     fn create_drawtarget(&self, size: Size2D<u64>) -> Box<dyn GenericDrawTarget> {
@@ -65,198 +112,1584 @@ impl Backend for RaqoteBackend {
         ))
     }
 
-    fn recreate_paint_state<'a>(&self, _state: &CanvasPaintState<'a>) -> CanvasPaintState<'a> {
-        CanvasPaintState::new(AntialiasMode::Default)
+    fn recreate_paint_state<'a>(&self, state: &CanvasPaintState<'a>) -> CanvasPaintState<'a> {
+        CanvasPaintState::new(AntialiasMode::from_raqote(
+            state.draw_options.as_raqote().antialias,
+        ))
     }
 }
 
 impl<'a> CanvasPaintState<'a> {
-    pub fn new(_antialias: AntialiasMode) -> CanvasPaintState<'a> {
+    pub fn new(antialias: AntialiasMode) -> CanvasPaintState<'a> {
+        let black = Pattern::Color(raqote::SolidSource {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        });
         CanvasPaintState {
-            draw_options: DrawOptions::Raqote(()),
-            fill_style: Pattern::Raqote(()),
-            stroke_style: Pattern::Raqote(()),
-            stroke_opts: StrokeOptions::Raqote(PhantomData),
+            draw_options: DrawOptions::Raqote(raqote::DrawOptions {
+                antialias: antialias.into_raqote(),
+                ..raqote::DrawOptions::new()
+            }),
+            fill_style: canvas_data::Pattern::Raqote(black.clone()),
+            stroke_style: canvas_data::Pattern::Raqote(black),
+            stroke_opts: StrokeOptions::Raqote(
+                raqote::StrokeStyle {
+                    width: 1.0,
+                    cap: raqote::LineCap::Butt,
+                    join: raqote::LineJoin::Miter,
+                    miter_limit: 10.0,
+                    dash_array: vec![],
+                    dash_offset: 0.0,
+                },
+                PhantomData,
+            ),
             transform: Transform2D::identity(),
             shadow_offset_x: 0.0,
             shadow_offset_y: 0.0,
             shadow_blur: 0.0,
-            shadow_color: Color::Raqote(()),
+            shadow_color: Color::Raqote(raqote::SolidSource {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }),
         }
     }
 }
 
+/// A snapshot of pixels in raqote's premultiplied ARGB format.
+#[derive(Clone)]
+pub struct Surface {
+    size: Size2D<i32>,
+    data: Arc<Vec<u32>>,
+}
+
+impl Surface {
+    /// Reads premultiplied BGRA bytes, `stride` bytes per row.
+    fn from_bgra(data: &[u8], size: Size2D<i32>, stride: i32) -> Option<Surface> {
+        if size.width < 0 || size.height < 0 || stride < size.width * 4 {
+            return None;
+        }
+        let (width, height, stride) = (size.width as usize, size.height as usize, stride as usize);
+        if height > 0 && data.len() < stride * (height - 1) + width * 4 {
+            return None;
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = &data[y * stride..y * stride + width * 4];
+            pixels.extend(
+                row.chunks(4)
+                    .map(|bgra| u32::from_le_bytes([bgra[0], bgra[1], bgra[2], bgra[3]])),
+            );
+        }
+        Some(Surface {
+            size,
+            data: Arc::new(pixels),
+        })
+    }
+
+    fn image(&self) -> raqote::Image {
+        raqote::Image {
+            width: self.size.width,
+            height: self.size.height,
+            data: &self.data,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Pattern {
+    /// A premultiplied color.
+    Color(raqote::SolidSource),
+    LinearGradient(LinearGradientPattern),
+    RadialGradient(RadialGradientPattern),
+    Surface(SurfacePattern),
+}
+
+#[derive(Clone)]
+pub struct LinearGradientPattern {
+    gradient: raqote::Gradient,
+    start: Point2D<f32>,
+    end: Point2D<f32>,
+}
+
+#[derive(Clone)]
+pub struct RadialGradientPattern {
+    gradient: raqote::Gradient,
+    center1: Point2D<f32>,
+    radius1: f32,
+    center2: Point2D<f32>,
+    radius2: f32,
+}
+
+#[derive(Clone)]
+pub struct SurfacePattern {
+    surface: Surface,
+    repeat_x: bool,
+    repeat_y: bool,
+}
+
 impl Pattern {
-    pub fn is_zero_size_gradient(&self) -> bool {
+    fn source(&self) -> raqote::Source {
         match *self {
-            Pattern::Raqote(()) => unimplemented!(),
+            Pattern::Color(color) => raqote::Source::Solid(color),
+            Pattern::LinearGradient(ref pattern) => raqote::Source::new_linear_gradient(
+                pattern.gradient.clone(),
+                pattern.start,
+                pattern.end,
+                raqote::Spread::Pad,
+            ),
+            Pattern::RadialGradient(ref pattern) => raqote::Source::new_two_circle_radial_gradient(
+                pattern.gradient.clone(),
+                pattern.center1,
+                pattern.radius1,
+                pattern.center2,
+                pattern.radius2,
+                raqote::Spread::Pad,
+            ),
+            Pattern::Surface(ref pattern) => {
+                let extend = if pattern.repeat_x || pattern.repeat_y {
+                    raqote::ExtendMode::Repeat
+                } else {
+                    raqote::ExtendMode::Pad
+                };
+                raqote::Source::Image(
+                    pattern.surface.image(),
+                    extend,
+                    raqote::FilterMode::Bilinear,
+                    Transform2D::identity(),
+                )
+            },
+        }
+    }
+
+    /// The user space area a pattern paints, if it doesn't repeat in both
+    /// directions. `bounds` is the canvas in user space.
+    fn clip_rect(&self, bounds: &Rect<f32>) -> Option<Rect<f32>> {
+        let pattern = match *self {
+            Pattern::Surface(ref pattern) if !(pattern.repeat_x && pattern.repeat_y) => pattern,
+            _ => return None,
+        };
+        let (x, width) = if pattern.repeat_x {
+            (bounds.origin.x, bounds.size.width)
+        } else {
+            (0., pattern.surface.size.width as f32)
+        };
+        let (y, height) = if pattern.repeat_y {
+            (bounds.origin.y, bounds.size.height)
+        } else {
+            (0., pattern.surface.size.height as f32)
+        };
+        Some(Rect::new(Point2D::new(x, y), Size2D::new(width, height)))
+    }
+}
+
+impl canvas_data::Pattern {
+    fn as_raqote(&self) -> &Pattern {
+        match self {
+            canvas_data::Pattern::Raqote(p) => p,
+        }
+    }
+
+    pub fn is_zero_size_gradient(&self) -> bool {
+        match *self.as_raqote() {
+            Pattern::LinearGradient(ref gradient) => gradient.start == gradient.end,
+            Pattern::RadialGradient(ref gradient) => {
+                gradient.center1 == gradient.center2 && gradient.radius1 == gradient.radius2
+            },
+            Pattern::Color(..) | Pattern::Surface(..) => false,
         }
     }
 }
 
 impl<'a> StrokeOptions<'a> {
-    pub fn set_line_width(&mut self, _val: f32) {
-        unimplemented!()
+    fn as_raqote(&self) -> &raqote::StrokeStyle {
+        match self {
+            StrokeOptions::Raqote(options, _) => options,
+        }
     }
-    pub fn set_miter_limit(&mut self, _val: f32) {
-        unimplemented!()
+    pub fn set_line_width(&mut self, val: f32) {
+        match self {
+            StrokeOptions::Raqote(options, _) => options.width = val,
+        }
     }
-    pub fn set_line_join(&mut self, _val: LineJoinStyle) {
-        unimplemented!()
+    pub fn set_miter_limit(&mut self, val: f32) {
+        match self {
+            StrokeOptions::Raqote(options, _) => options.miter_limit = val,
+        }
     }
-    pub fn set_line_cap(&mut self, _val: LineCapStyle) {
-        unimplemented!()
+    pub fn set_line_join(&mut self, val: LineJoinStyle) {
+        match self {
+            StrokeOptions::Raqote(options, _) => options.join = val.to_raqote_style(),
+        }
+    }
+    pub fn set_line_cap(&mut self, val: LineCapStyle) {
+        match self {
+            StrokeOptions::Raqote(options, _) => options.cap = val.to_raqote_style(),
+        }
     }
 }
 
 impl DrawOptions {
-    pub fn set_alpha(&mut self, _val: f32) {
+    fn as_raqote(&self) -> &raqote::DrawOptions {
+        match self {
+            DrawOptions::Raqote(options) => options,
+        }
+    }
+    fn as_raqote_mut(&mut self) -> &mut raqote::DrawOptions {
+        match self {
+            DrawOptions::Raqote(options) => options,
+        }
+    }
+    pub fn set_alpha(&mut self, val: f32) {
+        match self {
+            DrawOptions::Raqote(options) => options.alpha = val,
+        }
+    }
+}
+
+impl Color {
+    fn as_raqote(&self) -> &raqote::SolidSource {
+        match self {
+            Color::Raqote(color) => color,
+        }
+    }
+}
+
+impl CompositionOp {
+    fn into_raqote(self) -> raqote::BlendMode {
+        match self {
+            CompositionOp::Raqote(op) => op,
+        }
+    }
+}
+
+impl SourceSurface {
+    fn into_raqote(self) -> Surface {
+        match self {
+            SourceSurface::Raqote(surface) => surface,
+        }
+    }
+}
+
+impl GradientStop {
+    fn into_raqote(self) -> raqote::GradientStop {
         match self {
-            DrawOptions::Raqote(()) => unimplemented!(),
+            GradientStop::Raqote(stop) => stop,
+        }
+    }
+}
+
+impl AntialiasMode {
+    fn into_raqote(self) -> raqote::AntialiasMode {
+        match self {
+            AntialiasMode::Default => raqote::AntialiasMode::Gray,
+            AntialiasMode::None => raqote::AntialiasMode::None,
+        }
+    }
+
+    fn from_raqote(val: raqote::AntialiasMode) -> AntialiasMode {
+        match val {
+            raqote::AntialiasMode::None => AntialiasMode::None,
+            _ => AntialiasMode::Default,
+        }
+    }
+}
+
+impl Filter {
+    fn as_raqote(&self) -> raqote::FilterMode {
+        match *self {
+            Filter::Linear => raqote::FilterMode::Bilinear,
+            Filter::Point => raqote::FilterMode::Nearest,
         }
     }
 }
 
 impl Path {
+    fn as_raqote(&self) -> &raqote::Path {
+        match self {
+            Path::Raqote(p) => p,
+        }
+    }
+
     pub fn transformed_copy_to_builder(
         &self,
-        _transform: &Transform2D<f32>,
+        transform: &Transform2D<f32>,
     ) -> Box<dyn GenericPathBuilder> {
-        unimplemented!()
+        Box::new(PathBuilder::from_path(self.as_raqote(), transform))
     }
 
-    pub fn contains_point(&self, _x: f64, _y: f64, _path_transform: &Transform2D<f32>) -> bool {
-        unimplemented!()
+    pub fn contains_point(&self, x: f64, y: f64, path_transform: &Transform2D<f32>) -> bool {
+        let point = Point2D::new(x as f32, y as f32);
+        winding_number(self.as_raqote(), path_transform, point) != 0
     }
 
     pub fn copy_to_builder(&self) -> Box<dyn GenericPathBuilder> {
-        unimplemented!()
+        Box::new(PathBuilder::from_path(
+            self.as_raqote(),
+            &Transform2D::identity(),
+        ))
+    }
+}
+
+/// The winding number of `path`, transformed by `transform`, around `point`,
+/// with curves flattened into line segments.
+fn winding_number(path: &raqote::Path, transform: &Transform2D<f32>, point: Point2D<f32>) -> i32 {
+    let mut winding = 0;
+    let mut edge = |from: Point2D<f32>, to: Point2D<f32>| {
+        let side = (to.x - from.x) * (point.y - from.y) - (point.x - from.x) * (to.y - from.y);
+        if from.y <= point.y {
+            if to.y > point.y && side > 0. {
+                winding += 1;
+            }
+        } else if to.y <= point.y && side < 0. {
+            winding -= 1;
+        }
+    };
+
+    let (mut start, mut current) = (Point2D::zero(), Point2D::zero());
+    for op in &path.ops {
+        match *op {
+            PathOp::MoveTo(p) => {
+                edge(current, start);
+                start = transform.transform_point(&p);
+                current = start;
+            },
+            PathOp::LineTo(p) => {
+                let p = transform.transform_point(&p);
+                edge(current, p);
+                current = p;
+            },
+            PathOp::QuadTo(c, p) => {
+                let (from, c, p) = (
+                    current,
+                    transform.transform_point(&c),
+                    transform.transform_point(&p),
+                );
+                for step in 1..=CURVE_STEPS {
+                    let t = step as f32 / CURVE_STEPS as f32;
+                    let next = from.lerp(c, t).lerp(c.lerp(p, t), t);
+                    edge(current, next);
+                    current = next;
+                }
+            },
+            PathOp::CubicTo(c1, c2, p) => {
+                let (from, c1, c2, p) = (
+                    current,
+                    transform.transform_point(&c1),
+                    transform.transform_point(&c2),
+                    transform.transform_point(&p),
+                );
+                for step in 1..=CURVE_STEPS {
+                    let t = step as f32 / CURVE_STEPS as f32;
+                    let (a, b, c) = (from.lerp(c1, t), c1.lerp(c2, t), c2.lerp(p, t));
+                    let next = a.lerp(b, t).lerp(b.lerp(c, t), t);
+                    edge(current, next);
+                    current = next;
+                }
+            },
+            PathOp::Close => {
+                edge(current, start);
+                current = start;
+            },
+        }
+    }
+    edge(current, start);
+    winding
+}
+
+/// A raqote path builder that keeps track of the points canvas path
+/// operations need, and survives being finished.
+pub struct PathBuilder {
+    builder: raqote::PathBuilder,
+    current_point: Option<Point2D<f32>>,
+    subpath_start: Option<Point2D<f32>>,
+}
+
+impl PathBuilder {
+    fn new() -> PathBuilder {
+        PathBuilder {
+            builder: raqote::PathBuilder::new(),
+            current_point: None,
+            subpath_start: None,
+        }
+    }
+
+    fn from_path(path: &raqote::Path, transform: &Transform2D<f32>) -> PathBuilder {
+        let mut builder = PathBuilder::new();
+        for op in &path.ops {
+            match *op {
+                PathOp::MoveTo(p) => builder.move_to(transform.transform_point(&p)),
+                PathOp::LineTo(p) => builder.line_to(transform.transform_point(&p)),
+                PathOp::QuadTo(c, p) => builder.quadratic_curve_to(
+                    &transform.transform_point(&c),
+                    &transform.transform_point(&p),
+                ),
+                PathOp::CubicTo(c1, c2, p) => builder.bezier_curve_to(
+                    &transform.transform_point(&c1),
+                    &transform.transform_point(&c2),
+                    &transform.transform_point(&p),
+                ),
+                PathOp::Close => builder.close(),
+            }
+        }
+        builder
+    }
+
+    // https://html.spec.whatwg.org/multipage/#ensure-there-is-a-subpath
+    fn ensure_subpath(&mut self, point: &Point2D<f32>) {
+        if self.current_point.is_none() {
+            self.move_to(*point);
+        }
+    }
+}
+
+impl GenericPathBuilder for PathBuilder {
+    fn arc(
+        &mut self,
+        origin: Point2D<f32>,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        anticlockwise: bool,
+    ) {
+        self.ellipse(
+            origin,
+            radius,
+            radius,
+            0.,
+            start_angle,
+            end_angle,
+            anticlockwise,
+        );
+    }
+    fn bezier_curve_to(
+        &mut self,
+        control_point1: &Point2D<f32>,
+        control_point2: &Point2D<f32>,
+        control_point3: &Point2D<f32>,
+    ) {
+        self.ensure_subpath(control_point1);
+        self.builder.cubic_to(
+            control_point1.x,
+            control_point1.y,
+            control_point2.x,
+            control_point2.y,
+            control_point3.x,
+            control_point3.y,
+        );
+        self.current_point = Some(*control_point3);
+    }
+    fn close(&mut self) {
+        if let Some(start) = self.subpath_start {
+            self.builder.close();
+            // A closed subpath is followed by a new one at the same point.
+            self.move_to(start);
+        }
+    }
+    fn ellipse(
+        &mut self,
+        origin: Point2D<f32>,
+        radius_x: f32,
+        radius_y: f32,
+        rotation_angle: f32,
+        start_angle: f32,
+        end_angle: f32,
+        anticlockwise: bool,
+    ) {
+        // https://html.spec.whatwg.org/multipage/#dom-context-2d-ellipse
+        let sweep = end_angle - start_angle;
+        let sweep = if anticlockwise && -sweep >= 2. * PI {
+            -2. * PI
+        } else if !anticlockwise && sweep >= 2. * PI {
+            2. * PI
+        } else {
+            let sweep = sweep % (2. * PI);
+            if anticlockwise && sweep > 0. {
+                sweep - 2. * PI
+            } else if !anticlockwise && sweep < 0. {
+                sweep + 2. * PI
+            } else {
+                sweep
+            }
+        };
+
+        let (rotation_sin, rotation_cos) = rotation_angle.sin_cos();
+        let point = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let (x, y) = (radius_x * cos, radius_y * sin);
+            Point2D::new(
+                origin.x + x * rotation_cos - y * rotation_sin,
+                origin.y + x * rotation_sin + y * rotation_cos,
+            )
+        };
+        let tangent = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let (x, y) = (-radius_x * sin, radius_y * cos);
+            Vector2D::new(
+                x * rotation_cos - y * rotation_sin,
+                x * rotation_sin + y * rotation_cos,
+            )
+        };
+
+        let start = point(start_angle);
+        if self.current_point.is_some() {
+            self.line_to(start);
+        } else {
+            self.move_to(start);
+        }
+
+        // Approximate the arc with one cubic bezier per quarter turn at most.
+        let segments = (sweep.abs() / (PI / 2.)).ceil().max(1.);
+        let step = sweep / segments;
+        let k = 4. / 3. * (step / 4.).tan();
+        for segment in 0..segments as usize {
+            let from = start_angle + step * segment as f32;
+            let to = from + step;
+            self.bezier_curve_to(
+                &(point(from) + tangent(from) * k),
+                &(point(to) - tangent(to) * k),
+                &point(to),
+            );
+        }
+    }
+    fn get_current_point(&self) -> Point2D<f32> {
+        self.current_point.unwrap_or(Point2D::zero())
+    }
+    fn line_to(&mut self, point: Point2D<f32>) {
+        self.ensure_subpath(&point);
+        self.builder.line_to(point.x, point.y);
+        self.current_point = Some(point);
+    }
+    fn move_to(&mut self, point: Point2D<f32>) {
+        self.builder.move_to(point.x, point.y);
+        self.current_point = Some(point);
+        self.subpath_start = Some(point);
+    }
+    fn quadratic_curve_to(&mut self, control_point: &Point2D<f32>, end_point: &Point2D<f32>) {
+        self.ensure_subpath(control_point);
+        self.builder
+            .quad_to(control_point.x, control_point.y, end_point.x, end_point.y);
+        self.current_point = Some(*end_point);
+    }
+    fn finish(&mut self) -> Path {
+        let path = mem::replace(&mut self.builder, raqote::PathBuilder::new()).finish();
+        // Keep the builder usable by replaying the finished path into a new one.
+        *self = PathBuilder::from_path(&path, &Transform2D::identity());
+        Path::Raqote(path)
+    }
+}
+
+fn rect_path(rect: &Rect<f32>) -> raqote::Path {
+    let mut builder = raqote::PathBuilder::new();
+    builder.move_to(rect.origin.x, rect.origin.y);
+    builder.line_to(rect.max_x(), rect.origin.y);
+    builder.line_to(rect.max_x(), rect.max_y());
+    builder.line_to(rect.origin.x, rect.max_y());
+    builder.close();
+    builder.finish()
+}
+
+/// Whether a blend mode affects the destination outside of what is drawn,
+/// which raqote only composites where the drawing has coverage.
+fn is_unbounded(mode: raqote::BlendMode) -> bool {
+    match mode {
+        raqote::BlendMode::Src |
+        raqote::BlendMode::SrcIn |
+        raqote::BlendMode::SrcOut |
+        raqote::BlendMode::DstIn |
+        raqote::BlendMode::DstAtop => true,
+        _ => false,
+    }
+}
+
+/// Runs `draw` against `dt`, clipped to the user space `clip` if any.
+///
+/// Drawing with an unbounded blend mode happens in a transparent layer first,
+/// which is then composited over the whole target.
+fn composite<F>(
+    dt: &mut raqote::DrawTarget,
+    clip: Option<Rect<f32>>,
+    options: &raqote::DrawOptions,
+    draw: F,
+) where
+    F: FnOnce(&mut raqote::DrawTarget, &raqote::DrawOptions),
+{
+    let clipped = |dt: &mut raqote::DrawTarget, options: &raqote::DrawOptions| match clip {
+        Some(ref rect) => {
+            raqote::DrawTarget::push_clip(dt, &rect_path(rect));
+            draw(dt, options);
+            raqote::DrawTarget::pop_clip(dt);
+        },
+        None => draw(dt, options),
+    };
+
+    if !is_unbounded(options.blend_mode) {
+        return clipped(dt, options);
+    }
+
+    let transform = *raqote::DrawTarget::get_transform(dt);
+    let mut layer = raqote::DrawTarget::new(dt.width(), dt.height());
+    raqote::DrawTarget::set_transform(&mut layer, &transform);
+    let mut layer_options = options.clone();
+    layer_options.blend_mode = raqote::BlendMode::SrcOver;
+    clipped(&mut layer, &layer_options);
+
+    let mut composite_options = raqote::DrawOptions::new();
+    composite_options.blend_mode = options.blend_mode;
+    composite_options.antialias = raqote::AntialiasMode::None;
+    let image = raqote::Image {
+        width: layer.width(),
+        height: layer.height(),
+        data: layer.get_data(),
+    };
+    raqote::DrawTarget::set_transform(dt, &Transform2D::identity());
+    dt.draw_image_at(0., 0., &image, &composite_options);
+    raqote::DrawTarget::set_transform(dt, &transform);
+}
+
+/// The area of the canvas in user space, if the current transform is invertible.
+fn user_space_bounds(dt: &raqote::DrawTarget) -> Option<Rect<f32>> {
+    let inverse = raqote::DrawTarget::get_transform(dt).inverse()?;
+    Some(inverse.transform_rect(&Rect::new(
+        Point2D::zero(),
+        Size2D::new(dt.width() as f32, dt.height() as f32),
+    )))
+}
+
+/// Fills or strokes with `pattern` through `draw`, which receives the source to draw with.
+fn draw_pattern<F>(
+    dt: &mut raqote::DrawTarget,
+    pattern: &Pattern,
+    options: &raqote::DrawOptions,
+    draw: F,
+) where
+    F: FnOnce(&mut raqote::DrawTarget, &raqote::Source, &raqote::DrawOptions),
+{
+    let bounds = match user_space_bounds(dt) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let source = pattern.source();
+    composite(dt, pattern.clip_rect(&bounds), options, |dt, options| {
+        draw(dt, &source, options)
+    });
+}
+
+/// Blurs alpha values in place with a box blur of the given radius, horizontally
+/// and then vertically.
+fn box_blur(alpha: &mut [u8], width: usize, height: usize, radius: usize) {
+    let mut line = vec![0; width.max(height)];
+    for y in 0..height {
+        blur_line(alpha, y * width, 1, width, radius, &mut line);
+    }
+    for x in 0..width {
+        blur_line(alpha, x, width, height, radius, &mut line);
     }
 }
 
+fn blur_line(
+    data: &mut [u8],
+    start: usize,
+    stride: usize,
+    len: usize,
+    radius: usize,
+    line: &mut [u8],
+) {
+    let value = |data: &[u8], i: usize| data[start + i * stride] as u32;
+    let window = 2 * radius as u32 + 1;
+    let mut sum: u32 = (0..len.min(radius + 1)).map(|i| value(data, i)).sum();
+    for (i, blurred) in line[..len].iter_mut().enumerate() {
+        *blurred = (sum / window) as u8;
+        if i + radius + 1 < len {
+            sum += value(data, i + radius + 1);
+        }
+        if i >= radius {
+            sum -= value(data, i - radius);
+        }
+    }
+    for (i, blurred) in line[..len].iter().enumerate() {
+        data[start + i * stride] = *blurred;
+    }
+}
+
+/// Renders the shadow of `surface`: its alpha, blurred by a gaussian of
+/// standard deviation `sigma` and tinted with `color`. The result extends
+/// `margin` pixels past each side of the surface.
+fn shadow(surface: &Surface, color: &raqote::SolidSource, sigma: f32) -> (Vec<u32>, i32, i32) {
+    let margin = (sigma * 3.).ceil().max(0.) as i32;
+    let width = (surface.size.width + 2 * margin) as usize;
+    let height = (surface.size.height + 2 * margin) as usize;
+    let mut alpha = vec![0u8; width * height];
+    for y in 0..surface.size.height as usize {
+        for x in 0..surface.size.width as usize {
+            let pixel = surface.data[y * surface.size.width as usize + x];
+            alpha[(y + margin as usize) * width + x + margin as usize] = (pixel >> 24) as u8;
+        }
+    }
+
+    // Three box blurs approximate a gaussian blur, see
+    // https://www.w3.org/TR/filter-effects-1/#feGaussianBlurElement.
+    let box_size = (sigma * 3. * (2. * PI).sqrt() / 4. + 0.5).floor() as usize;
+    if box_size > 1 {
+        for _ in 0..3 {
+            box_blur(&mut alpha, width, height, box_size / 2);
+        }
+    }
+
+    let tint = |channel: u8, alpha: u8| (channel as u32 * alpha as u32 + 127) / 255;
+    let data = alpha
+        .into_iter()
+        .map(|a| {
+            tint(color.a, a) << 24 |
+                tint(color.r, a) << 16 |
+                tint(color.g, a) << 8 |
+                tint(color.b, a)
+        })
+        .collect();
+    (data, width as i32, height as i32)
+}
+
+fn to_f32_rect(rect: &Rect<f64>) -> Rect<f32> {
+    Rect::new(
+        Point2D::new(rect.origin.x as f32, rect.origin.y as f32),
+        Size2D::new(rect.size.width as f32, rect.size.height as f32),
+    )
+}
+
+fn bgra_bytes(data: &[u32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() * 4);
+    for pixel in data {
+        bytes.extend_from_slice(&pixel.to_le_bytes());
+    }
+    bytes
+}
+
 impl GenericDrawTarget for raqote::DrawTarget {
-    fn clear_rect(&self, _rect: &Rect<f32>) {
-        unimplemented!()
+    fn clear_rect(&mut self, rect: &Rect<f32>) {
+        let mut options = raqote::DrawOptions::new();
+        options.blend_mode = raqote::BlendMode::Src;
+        let transparent = raqote::Source::Solid(raqote::SolidSource {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        });
+        raqote::DrawTarget::fill(self, &rect_path(rect), &transparent, &options);
     }
 
     fn copy_surface(
-        &self,
-        _surface: SourceSurface,
-        _source: Rect<i32>,
-        _destination: Point2D<i32>,
+        &mut self,
+        surface: SourceSurface,
+        source: Rect<i32>,
+        destination: Point2D<i32>,
     ) {
-        unimplemented!()
+        let surface = surface.into_raqote();
+        let (width, height) = (self.width(), self.height());
+        let data = self.get_data_mut();
+        for y in 0..source.size.height {
+            let (source_y, dest_y) = (source.origin.y + y, destination.y + y);
+            if source_y < 0 || source_y >= surface.size.height || dest_y < 0 || dest_y >= height {
+                continue;
+            }
+            for x in 0..source.size.width {
+                let (source_x, dest_x) = (source.origin.x + x, destination.x + x);
+                if source_x < 0 || source_x >= surface.size.width || dest_x < 0 || dest_x >= width {
+                    continue;
+                }
+                data[(dest_y * width + dest_x) as usize] =
+                    surface.data[(source_y * surface.size.width + source_x) as usize];
+            }
+        }
     }
 
     fn create_gradient_stops(
         &self,
-        _gradient_stops: Vec<GradientStop>,
+        gradient_stops: Vec<GradientStop>,
         _extend_mode: ExtendMode,
     ) -> GradientStops {
-        unimplemented!()
+        GradientStops::Raqote(
+            gradient_stops
+                .into_iter()
+                .map(|stop| stop.into_raqote())
+                .collect(),
+        )
     }
 
     fn create_path_builder(&self) -> Box<dyn GenericPathBuilder> {
-        unimplemented!()
+        Box::new(PathBuilder::new())
     }
 
     fn create_similar_draw_target(
         &self,
-        _size: &Size2D<i32>,
+        size: &Size2D<i32>,
         _format: SurfaceFormat,
     ) -> Box<dyn GenericDrawTarget> {
-        unimplemented!()
+        Box::new(raqote::DrawTarget::new(size.width, size.height))
     }
     fn create_source_surface_from_data(
         &self,
-        _data: &[u8],
-        _size: Size2D<i32>,
-        _stride: i32,
+        data: &[u8],
+        size: Size2D<i32>,
+        stride: i32,
     ) -> Option<SourceSurface> {
-        unimplemented!()
+        Surface::from_bgra(data, size, stride).map(SourceSurface::Raqote)
     }
     fn draw_surface(
-        &self,
-        _surface: SourceSurface,
-        _dest: Rect<f64>,
-        _source: Rect<f64>,
-        _filter: Filter,
-        _draw_options: &DrawOptions,
+        &mut self,
+        surface: SourceSurface,
+        dest: Rect<f64>,
+        source: Rect<f64>,
+        filter: Filter,
+        draw_options: &DrawOptions,
     ) {
-        unimplemented!()
+        let surface = surface.into_raqote();
+        let (dest, source) = (to_f32_rect(&dest), to_f32_rect(&source));
+        if dest.size.width <= 0. || dest.size.height <= 0. {
+            return;
+        }
+        let transform = Transform2D::create_translation(-dest.origin.x, -dest.origin.y)
+            .post_scale(
+                source.size.width / dest.size.width,
+                source.size.height / dest.size.height,
+            )
+            .post_translate(source.origin.to_vector());
+        let image = raqote::Source::Image(
+            surface.image(),
+            raqote::ExtendMode::Pad,
+            filter.as_raqote(),
+            transform,
+        );
+        let mut options = draw_options.as_raqote().clone();
+        options.antialias = raqote::AntialiasMode::None;
+        composite(self, None, &options, |dt, options| {
+            raqote::DrawTarget::fill(dt, &rect_path(&dest), &image, options)
+        });
     }
     fn draw_surface_with_shadow(
-        &self,
-        _surface: SourceSurface,
-        _dest: &Point2D<f32>,
-        _color: &Color,
-        _offset: &Vector2D<f32>,
-        _sigma: f32,
-        _operator: CompositionOp,
+        &mut self,
+        surface: SourceSurface,
+        dest: &Point2D<f32>,
+        color: &Color,
+        offset: &Vector2D<f32>,
+        sigma: f32,
+        operator: CompositionOp,
     ) {
-        unimplemented!()
+        let surface = surface.into_raqote();
+        let (shadow, width, height) = shadow(&surface, color.as_raqote(), sigma);
+        let margin = (width - surface.size.width) as f32 / 2.;
+
+        let mut options = raqote::DrawOptions::new();
+        options.blend_mode = operator.into_raqote();
+        let transform = *raqote::DrawTarget::get_transform(self);
+        raqote::DrawTarget::set_transform(self, &Transform2D::identity());
+        let shadow = raqote::Image {
+            width,
+            height,
+            data: &shadow,
+        };
+        self.draw_image_at(
+            dest.x + offset.x - margin,
+            dest.y + offset.y - margin,
+            &shadow,
+            &options,
+        );
+        self.draw_image_at(dest.x, dest.y, &surface.image(), &options);
+        raqote::DrawTarget::set_transform(self, &transform);
     }
-    fn fill(&self, _path: &Path, _pattern: Pattern, _draw_options: &DrawOptions) {
-        unimplemented!()
+    fn fill(&mut self, path: &Path, pattern: canvas_data::Pattern, draw_options: &DrawOptions) {
+        draw_pattern(
+            self,
+            pattern.as_raqote(),
+            draw_options.as_raqote(),
+            |dt, source, options| raqote::DrawTarget::fill(dt, path.as_raqote(), source, options),
+        );
+    }
+    fn fill_rect(
+        &mut self,
+        rect: &Rect<f32>,
+        pattern: canvas_data::Pattern,
+        draw_options: Option<&DrawOptions>,
+    ) {
+        let default_options = raqote::DrawOptions::new();
+        let options = draw_options.map_or(&default_options, |options| options.as_raqote());
+        let path = rect_path(rect);
+        draw_pattern(self, pattern.as_raqote(), options, |dt, source, options| {
+            raqote::DrawTarget::fill(dt, &path, source, options)
+        });
     }
-    fn fill_rect(&self, _rect: &Rect<f32>, _pattern: Pattern, _draw_options: Option<&DrawOptions>) {
-        unimplemented!()
+    fn fill_text(
+        &mut self,
+        text: &str,
+        origin: Point2D<f32>,
+        max_width: Option<f32>,
+        pattern: canvas_data::Pattern,
+        draw_options: &DrawOptions,
+    ) {
+        SANS_SERIF.with(|font| {
+            let font = match *font {
+                Some(ref font) => font,
+                None => return warn!("No font available to draw canvas text with."),
+            };
+
+            let scale = FONT_SIZE / font.metrics().units_per_em as f32;
+            let mut ids = vec![];
+            let mut positions = vec![];
+            let mut width = 0.;
+            for character in text.chars() {
+                let id = match font.glyph_for_char(character) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                ids.push(id);
+                positions.push(Point2D::new(origin.x + width, origin.y));
+                width += font.advance(id).map_or(0., |advance| advance.x * scale);
+            }
+
+            // Text wider than the maximum width is squeezed horizontally to fit.
+            let transform = *raqote::DrawTarget::get_transform(self);
+            match max_width {
+                Some(max_width) if max_width <= 0. => return,
+                Some(max_width) if max_width < width => {
+                    let squeeze = Transform2D::create_translation(-origin.x, -origin.y)
+                        .post_scale(max_width / width, 1.)
+                        .post_translate(origin.to_vector())
+                        .post_mul(&transform);
+                    raqote::DrawTarget::set_transform(self, &squeeze);
+                },
+                _ => {},
+            }
+            draw_pattern(
+                self,
+                pattern.as_raqote(),
+                draw_options.as_raqote(),
+                |dt, source, options| {
+                    dt.draw_glyphs(font, FONT_SIZE, &ids, &positions, source, options)
+                },
+            );
+            raqote::DrawTarget::set_transform(self, &transform);
+        });
     }
     fn get_format(&self) -> SurfaceFormat {
-        unimplemented!()
+        SurfaceFormat::Raqote(())
     }
     fn get_size(&self) -> Size2D<i32> {
-        unimplemented!()
+        Size2D::new(self.width(), self.height())
     }
     fn get_transform(&self) -> Transform2D<f32> {
-        unimplemented!()
+        *raqote::DrawTarget::get_transform(self)
     }
-    fn pop_clip(&self) {
-        unimplemented!()
+    fn pop_clip(&mut self) {
+        raqote::DrawTarget::pop_clip(self);
     }
-    fn push_clip(&self, _path: &Path) {
-        unimplemented!()
+    fn push_clip(&mut self, path: &Path) {
+        raqote::DrawTarget::push_clip(self, path.as_raqote());
     }
-    fn set_transform(&self, _matrix: &Transform2D<f32>) {
-        unimplemented!()
+    fn set_transform(&mut self, matrix: &Transform2D<f32>) {
+        raqote::DrawTarget::set_transform(self, matrix);
     }
     fn snapshot(&self) -> SourceSurface {
-        unimplemented!()
+        SourceSurface::Raqote(Surface {
+            size: Size2D::new(self.width(), self.height()),
+            data: Arc::new(self.get_data().to_vec()),
+        })
     }
     fn stroke(
-        &self,
-        _path: &Path,
-        _pattern: Pattern,
-        _stroke_options: &StrokeOptions,
-        _draw_options: &DrawOptions,
+        &mut self,
+        path: &Path,
+        pattern: canvas_data::Pattern,
+        stroke_options: &StrokeOptions,
+        draw_options: &DrawOptions,
     ) {
-        unimplemented!()
+        let style = stroke_options.as_raqote();
+        draw_pattern(
+            self,
+            pattern.as_raqote(),
+            draw_options.as_raqote(),
+            |dt, source, options| {
+                raqote::DrawTarget::stroke(dt, path.as_raqote(), source, style, options)
+            },
+        );
     }
     fn stroke_line(
-        &self,
-        _start: Point2D<f32>,
-        _end: Point2D<f32>,
-        _pattern: Pattern,
-        _stroke_options: &StrokeOptions,
-        _draw_options: &DrawOptions,
+        &mut self,
+        start: Point2D<f32>,
+        end: Point2D<f32>,
+        pattern: canvas_data::Pattern,
+        stroke_options: &StrokeOptions,
+        draw_options: &DrawOptions,
     ) {
-        unimplemented!()
+        let mut style = stroke_options.as_raqote().clone();
+        style.cap = match style.join {
+            raqote::LineJoin::Round => raqote::LineCap::Round,
+            _ => raqote::LineCap::Butt,
+        };
+        let mut builder = raqote::PathBuilder::new();
+        builder.move_to(start.x, start.y);
+        builder.line_to(end.x, end.y);
+        let path = builder.finish();
+        draw_pattern(
+            self,
+            pattern.as_raqote(),
+            draw_options.as_raqote(),
+            |dt, source, options| raqote::DrawTarget::stroke(dt, &path, source, &style, options),
+        );
     }
     fn stroke_rect(
-        &self,
-        _rect: &Rect<f32>,
-        _pattern: Pattern,
-        _stroke_options: &StrokeOptions<'_>,
-        _draw_options: &DrawOptions,
+        &mut self,
+        rect: &Rect<f32>,
+        pattern: canvas_data::Pattern,
+        stroke_options: &StrokeOptions,
+        draw_options: &DrawOptions,
     ) {
-        unimplemented!()
+        let path = rect_path(rect);
+        let style = stroke_options.as_raqote();
+        draw_pattern(
+            self,
+            pattern.as_raqote(),
+            draw_options.as_raqote(),
+            |dt, source, options| raqote::DrawTarget::stroke(dt, &path, source, style, options),
+        );
     }
 
-    fn snapshot_data(&self, _f: &dyn Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
-        unimplemented!()
+    fn snapshot_data(&self, f: &dyn Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        f(&bgra_bytes(self.get_data()))
     }
 
     fn snapshot_data_owned(&self) -> Vec<u8> {
-        unimplemented!()
+        bgra_bytes(self.get_data())
+    }
+}
+
+pub trait ToRaqoteStyle {
+    type Target;
+    fn to_raqote_style(self) -> Self::Target;
+}
+
+impl ToRaqoteStyle for LineCapStyle {
+    type Target = raqote::LineCap;
+
+    fn to_raqote_style(self) -> raqote::LineCap {
+        match self {
+            LineCapStyle::Butt => raqote::LineCap::Butt,
+            LineCapStyle::Round => raqote::LineCap::Round,
+            LineCapStyle::Square => raqote::LineCap::Square,
+        }
+    }
+}
+
+impl ToRaqoteStyle for LineJoinStyle {
+    type Target = raqote::LineJoin;
+
+    fn to_raqote_style(self) -> raqote::LineJoin {
+        match self {
+            LineJoinStyle::Round => raqote::LineJoin::Round,
+            LineJoinStyle::Bevel => raqote::LineJoin::Bevel,
+            LineJoinStyle::Miter => raqote::LineJoin::Miter,
+        }
+    }
+}
+
+impl ToRaqoteStyle for CompositionStyle {
+    type Target = raqote::BlendMode;
+
+    fn to_raqote_style(self) -> raqote::BlendMode {
+        match self {
+            CompositionStyle::SrcIn => raqote::BlendMode::SrcIn,
+            CompositionStyle::SrcOut => raqote::BlendMode::SrcOut,
+            CompositionStyle::SrcOver => raqote::BlendMode::SrcOver,
+            CompositionStyle::SrcAtop => raqote::BlendMode::SrcAtop,
+            CompositionStyle::DestIn => raqote::BlendMode::DstIn,
+            CompositionStyle::DestOut => raqote::BlendMode::DstOut,
+            CompositionStyle::DestOver => raqote::BlendMode::DstOver,
+            CompositionStyle::DestAtop => raqote::BlendMode::DstAtop,
+            CompositionStyle::Copy => raqote::BlendMode::Src,
+            CompositionStyle::Lighter => raqote::BlendMode::Add,
+            CompositionStyle::Xor => raqote::BlendMode::Xor,
+        }
+    }
+}
+
+impl ToRaqoteStyle for BlendingStyle {
+    type Target = raqote::BlendMode;
+
+    fn to_raqote_style(self) -> raqote::BlendMode {
+        match self {
+            BlendingStyle::Multiply => raqote::BlendMode::Multiply,
+            BlendingStyle::Screen => raqote::BlendMode::Screen,
+            BlendingStyle::Overlay => raqote::BlendMode::Overlay,
+            BlendingStyle::Darken => raqote::BlendMode::Darken,
+            BlendingStyle::Lighten => raqote::BlendMode::Lighten,
+            BlendingStyle::ColorDodge => raqote::BlendMode::ColorDodge,
+            BlendingStyle::ColorBurn => raqote::BlendMode::ColorBurn,
+            BlendingStyle::HardLight => raqote::BlendMode::HardLight,
+            BlendingStyle::SoftLight => raqote::BlendMode::SoftLight,
+            BlendingStyle::Difference => raqote::BlendMode::Difference,
+            BlendingStyle::Exclusion => raqote::BlendMode::Exclusion,
+            BlendingStyle::Hue => raqote::BlendMode::Hue,
+            BlendingStyle::Saturation => raqote::BlendMode::Saturation,
+            BlendingStyle::Color => raqote::BlendMode::Color,
+            BlendingStyle::Luminosity => raqote::BlendMode::Luminosity,
+        }
+    }
+}
+
+impl ToRaqoteStyle for CompositionOrBlending {
+    type Target = raqote::BlendMode;
+
+    fn to_raqote_style(self) -> raqote::BlendMode {
+        match self {
+            CompositionOrBlending::Composition(op) => op.to_raqote_style(),
+            CompositionOrBlending::Blending(op) => op.to_raqote_style(),
+        }
+    }
+}
+
+impl ToRaqoteStyle for RGBA {
+    type Target = raqote::SolidSource;
+
+    fn to_raqote_style(self) -> raqote::SolidSource {
+        let premultiply = |channel: u8| ((channel as u32 * self.alpha as u32 + 127) / 255) as u8;
+        raqote::SolidSource {
+            r: premultiply(self.red),
+            g: premultiply(self.green),
+            b: premultiply(self.blue),
+            a: self.alpha,
+        }
+    }
+}
+
+fn to_raqote_gradient(stops: &[CanvasGradientStop]) -> raqote::Gradient {
+    let mut stops: Vec<raqote::GradientStop> = stops
+        .iter()
+        .map(|stop| raqote::GradientStop {
+            position: stop.offset as f32,
+            color: raqote::Color::new(
+                stop.color.alpha,
+                stop.color.red,
+                stop.color.green,
+                stop.color.blue,
+            ),
+        })
+        .collect();
+    // Stops at the same offset keep the order they were added in.
+    stops.sort_by(|a, b| {
+        a.position
+            .partial_cmp(&b.position)
+            .unwrap_or(Ordering::Equal)
+    });
+    raqote::Gradient { stops }
+}
+
+pub trait ToRaqotePattern {
+    fn to_raqote_pattern(&self) -> Option<Pattern>;
+}
+
+impl ToRaqotePattern for FillOrStrokeStyle {
+    fn to_raqote_pattern(&self) -> Option<Pattern> {
+        let transparent = raqote::SolidSource {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        Some(match *self {
+            FillOrStrokeStyle::Color(ref color) => Pattern::Color(color.to_raqote_style()),
+            // A gradient without any color stops paints transparent black.
+            FillOrStrokeStyle::LinearGradient(ref style) if style.stops.is_empty() => {
+                Pattern::Color(transparent)
+            },
+            FillOrStrokeStyle::RadialGradient(ref style) if style.stops.is_empty() => {
+                Pattern::Color(transparent)
+            },
+            FillOrStrokeStyle::LinearGradient(ref style) => {
+                Pattern::LinearGradient(LinearGradientPattern {
+                    gradient: to_raqote_gradient(&style.stops),
+                    start: Point2D::new(style.x0 as f32, style.y0 as f32),
+                    end: Point2D::new(style.x1 as f32, style.y1 as f32),
+                })
+            },
+            FillOrStrokeStyle::RadialGradient(ref style) => {
+                Pattern::RadialGradient(RadialGradientPattern {
+                    gradient: to_raqote_gradient(&style.stops),
+                    center1: Point2D::new(style.x0 as f32, style.y0 as f32),
+                    radius1: style.r0 as f32,
+                    center2: Point2D::new(style.x1 as f32, style.y1 as f32),
+                    radius2: style.r1 as f32,
+                })
+            },
+            FillOrStrokeStyle::Surface(ref style) => Pattern::Surface(SurfacePattern {
+                surface: Surface::from_bgra(
+                    &style.surface_data,
+                    style.surface_size.to_i32(),
+                    style.surface_size.width as i32 * 4,
+                )?,
+                repeat_x: style.repeat_x,
+                repeat_y: style.repeat_y,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(r: u8, g: u8, b: u8, a: u8) -> canvas_data::Pattern {
+        canvas_data::Pattern::Raqote(Pattern::Color(raqote::SolidSource { r, g, b, a }))
+    }
+
+    fn aliased(blend_mode: raqote::BlendMode) -> DrawOptions {
+        DrawOptions::Raqote(raqote::DrawOptions {
+            blend_mode,
+            antialias: raqote::AntialiasMode::None,
+            ..raqote::DrawOptions::new()
+        })
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect<f32> {
+        Rect::new(Point2D::new(x, y), Size2D::new(width, height))
+    }
+
+    fn pixel(dt: &raqote::DrawTarget, x: i32, y: i32) -> u32 {
+        dt.get_data()[(y * dt.width() + x) as usize]
+    }
+
+    const RED: u32 = 0xffff0000;
+    const GREEN: u32 = 0xff00ff00;
+
+    #[test]
+    fn fill_rect_paints_inside_the_rect_only() {
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        let options = aliased(raqote::BlendMode::SrcOver);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(1., 1., 2., 2.),
+            solid(0, 255, 0, 255),
+            Some(&options),
+        );
+        assert_eq!(pixel(&dt, 1, 1), GREEN);
+        assert_eq!(pixel(&dt, 2, 2), GREEN);
+        assert_eq!(pixel(&dt, 0, 0), 0);
+        assert_eq!(pixel(&dt, 3, 3), 0);
+    }
+
+    #[test]
+    fn unbounded_composition_operators_affect_the_whole_canvas() {
+        let background = aliased(raqote::BlendMode::SrcOver);
+
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(0., 0., 4., 4.),
+            solid(255, 0, 0, 255),
+            Some(&background),
+        );
+        let copy = aliased(raqote::BlendMode::Src);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(1., 1., 2., 2.),
+            solid(0, 255, 0, 255),
+            Some(&copy),
+        );
+        assert_eq!(pixel(&dt, 1, 1), GREEN);
+        assert_eq!(pixel(&dt, 0, 0), 0);
+
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(0., 0., 4., 4.),
+            solid(255, 0, 0, 255),
+            Some(&background),
+        );
+        let destination_in = aliased(raqote::BlendMode::DstIn);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(1., 1., 2., 2.),
+            solid(0, 255, 0, 255),
+            Some(&destination_in),
+        );
+        assert_eq!(pixel(&dt, 1, 1), RED);
+        assert_eq!(pixel(&dt, 0, 0), 0);
+    }
+
+    #[test]
+    fn bounded_composition_operators_leave_the_rest_of_the_canvas_alone() {
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        let options = aliased(raqote::BlendMode::SrcOver);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(0., 0., 4., 4.),
+            solid(255, 0, 0, 255),
+            Some(&options),
+        );
+        let destination_over = aliased(raqote::BlendMode::DstOver);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(1., 1., 2., 2.),
+            solid(0, 255, 0, 255),
+            Some(&destination_over),
+        );
+        assert_eq!(pixel(&dt, 1, 1), RED);
+        assert_eq!(pixel(&dt, 0, 0), RED);
+    }
+
+    #[test]
+    fn clear_rect_makes_the_rect_transparent() {
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        let options = aliased(raqote::BlendMode::SrcOver);
+        GenericDrawTarget::fill_rect(
+            &mut dt,
+            &rect(0., 0., 4., 4.),
+            solid(255, 0, 0, 255),
+            Some(&options),
+        );
+        GenericDrawTarget::clear_rect(&mut dt, &rect(0., 0., 2., 4.));
+        assert_eq!(pixel(&dt, 1, 1), 0);
+        assert_eq!(pixel(&dt, 2, 1), RED);
+    }
+
+    #[test]
+    fn non_repeating_surface_patterns_paint_the_surface_once() {
+        let surface = Surface {
+            size: Size2D::new(1, 1),
+            data: Arc::new(vec![GREEN]),
+        };
+        let pattern = canvas_data::Pattern::Raqote(Pattern::Surface(SurfacePattern {
+            surface,
+            repeat_x: false,
+            repeat_y: false,
+        }));
+        let mut dt = raqote::DrawTarget::new(3, 3);
+        let options = aliased(raqote::BlendMode::SrcOver);
+        GenericDrawTarget::fill_rect(&mut dt, &rect(0., 0., 3., 3.), pattern, Some(&options));
+        assert_eq!(pixel(&dt, 0, 0), GREEN);
+        assert_eq!(pixel(&dt, 1, 0), 0);
+        assert_eq!(pixel(&dt, 0, 1), 0);
+    }
+
+    #[test]
+    fn draw_surface_scales_the_source_to_the_destination() {
+        let surface = Surface {
+            size: Size2D::new(1, 1),
+            data: Arc::new(vec![GREEN]),
+        };
+        let mut dt = raqote::DrawTarget::new(4, 4);
+        GenericDrawTarget::draw_surface(
+            &mut dt,
+            SourceSurface::Raqote(surface),
+            Rect::new(Point2D::new(1., 1.), Size2D::new(2., 2.)),
+            Rect::new(Point2D::zero(), Size2D::new(1., 1.)),
+            Filter::Point,
+            &aliased(raqote::BlendMode::SrcOver),
+        );
+        assert_eq!(pixel(&dt, 1, 1), GREEN);
+        assert_eq!(pixel(&dt, 2, 2), GREEN);
+        assert_eq!(pixel(&dt, 0, 0), 0);
+        assert_eq!(pixel(&dt, 3, 3), 0);
+    }
+
+    #[test]
+    fn copy_surface_skips_pixels_out_of_bounds() {
+        let surface = Surface {
+            size: Size2D::new(2, 1),
+            data: Arc::new(vec![RED, GREEN]),
+        };
+        let mut dt = raqote::DrawTarget::new(2, 2);
+        GenericDrawTarget::copy_surface(
+            &mut dt,
+            SourceSurface::Raqote(surface),
+            Rect::new(Point2D::new(0, 0), Size2D::new(3, 2)),
+            Point2D::new(1, 1),
+        );
+        assert_eq!(dt.get_data(), &[0, 0, 0, RED]);
+    }
+
+    #[test]
+    fn fill_text_draws_nothing_without_room() {
+        let mut dt = raqote::DrawTarget::new(20, 20);
+        GenericDrawTarget::fill_text(
+            &mut dt,
+            "Hi",
+            Point2D::new(0., 15.),
+            Some(0.),
+            solid(0, 0, 0, 255),
+            &aliased(raqote::BlendMode::SrcOver),
+        );
+        assert!(dt.get_data().iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn winding_number_counts_the_subpaths_around_a_point() {
+        let square = rect_path(&rect(0., 0., 10., 10.));
+        let identity = Transform2D::identity();
+        assert_eq!(
+            winding_number(&square, &identity, Point2D::new(5., 5.)).abs(),
+            1
+        );
+        assert_eq!(winding_number(&square, &identity, Point2D::new(15., 5.)), 0);
+
+        let mut builder = raqote::PathBuilder::new();
+        for _ in 0..2 {
+            builder.move_to(0., 0.);
+            builder.line_to(10., 0.);
+            builder.line_to(10., 10.);
+            builder.line_to(0., 10.);
+            builder.close();
+        }
+        let twice = builder.finish();
+        assert_eq!(
+            winding_number(&twice, &identity, Point2D::new(5., 5.)).abs(),
+            2
+        );
+
+        let moved = Transform2D::create_translation(10., 0.);
+        assert_eq!(winding_number(&square, &moved, Point2D::new(5., 5.)), 0);
+        assert_eq!(
+            winding_number(&square, &moved, Point2D::new(15., 5.)).abs(),
+            1
+        );
+    }
+
+    #[test]
+    fn arcs_contain_their_center() {
+        let mut builder = PathBuilder::new();
+        builder.arc(Point2D::new(10., 10.), 5., 0., 2. * PI, false);
+        let path = builder.finish();
+        let identity = Transform2D::identity();
+        assert!(path.contains_point(10., 10., &identity));
+        assert!(path.contains_point(14., 10., &identity));
+        assert!(!path.contains_point(16., 10., &identity));
+        assert!(!path.contains_point(14., 14., &identity));
+    }
+
+    #[test]
+    fn path_builder_tracks_the_current_point() {
+        let mut builder = PathBuilder::new();
+        assert_eq!(builder.get_current_point(), Point2D::zero());
+        builder.line_to(Point2D::new(1., 1.));
+        builder.line_to(Point2D::new(5., 1.));
+        assert_eq!(builder.get_current_point(), Point2D::new(5., 1.));
+        builder.close();
+        assert_eq!(builder.get_current_point(), Point2D::new(1., 1.));
+
+        // A whole turn, in either direction, ends where it started.
+        let mut builder = PathBuilder::new();
+        builder.arc(Point2D::new(0., 0.), 2., 0., 2. * PI, false);
+        let end = builder.get_current_point();
+        assert!((end.x - 2.).abs() < 1e-4 && end.y.abs() < 1e-4);
+        builder.arc(Point2D::new(0., 0.), 2., 0., -2. * PI, true);
+        let end = builder.get_current_point();
+        assert!((end.x - 2.).abs() < 1e-4 && end.y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn path_builder_is_usable_after_being_finished() {
+        let mut builder = PathBuilder::new();
+        builder.move_to(Point2D::new(0., 0.));
+        builder.line_to(Point2D::new(1., 0.));
+        let first = builder.finish();
+        assert_eq!(first.as_raqote().ops.len(), 2);
+        assert_eq!(builder.get_current_point(), Point2D::new(1., 0.));
+        builder.line_to(Point2D::new(1., 1.));
+        assert_eq!(builder.finish().as_raqote().ops.len(), 3);
+    }
+
+    #[test]
+    fn blur_line_averages_over_the_window() {
+        let mut data = [0, 0, 0, 210, 0, 0, 0];
+        let mut line = [0; 7];
+        blur_line(&mut data, 0, 1, 7, 1, &mut line);
+        assert_eq!(data, [0, 0, 70, 70, 70, 0, 0]);
+
+        // Every other value, starting from the second one.
+        let mut data = [9, 0, 9, 90, 9, 0];
+        blur_line(&mut data, 1, 2, 3, 1, &mut line);
+        assert_eq!(data, [9, 30, 9, 30, 9, 30]);
+    }
+
+    #[test]
+    fn shadows_are_tinted_and_blurred_alpha() {
+        let surface = Surface {
+            size: Size2D::new(1, 1),
+            data: Arc::new(vec![GREEN]),
+        };
+        let color = raqote::SolidSource {
+            r: 128,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+        let (data, width, height) = shadow(&surface, &color, 0.);
+        assert_eq!((width, height), (1, 1));
+        assert_eq!(data, vec![0x80800000]);
+
+        let black = raqote::SolidSource {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let (data, width, height) = shadow(&surface, &black, 2.);
+        assert_eq!((width, height), (13, 13));
+        let alpha = |x: i32, y: i32| data[(y * width + x) as usize] >> 24;
+        assert!(alpha(6, 6) > 0 && alpha(6, 6) < 255);
+        assert_eq!(alpha(5, 6), alpha(7, 6));
+        assert_eq!(alpha(6, 5), alpha(6, 7));
+        assert!(alpha(6, 6) > alpha(4, 6));
+        assert_eq!(alpha(0, 0), 0);
+    }
+
+    #[test]
+    fn surfaces_are_read_from_bgra_rows() {
+        let bytes = [
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, //
+            9, 10, 11, 12, 13, 14, 15, 16,
+        ];
+        let surface = Surface::from_bgra(&bytes, Size2D::new(2, 2), 10).unwrap();
+        assert_eq!(
+            *surface.data,
+            vec![0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d]
+        );
+        assert_eq!(bgra_bytes(&surface.data[..1]), vec![1, 2, 3, 4]);
+
+        assert!(Surface::from_bgra(&bytes, Size2D::new(2, 2), 7).is_none());
+        assert!(Surface::from_bgra(&bytes[..17], Size2D::new(2, 2), 10).is_none());
+    }
+
+    #[test]
+    fn colors_are_premultiplied() {
+        let color = RGBA::new(255, 128, 0, 128).to_raqote_style();
+        assert_eq!((color.r, color.g, color.b, color.a), (128, 64, 0, 128));
+    }
+
+    #[test]
+    fn gradient_stops_are_sorted_by_offset() {
+        let stop = |offset: f64, red: u8| CanvasGradientStop {
+            offset,
+            color: RGBA::new(red, 0, 0, 255),
+        };
+        let gradient = to_raqote_gradient(&[stop(1., 1), stop(0., 2), stop(0.5, 3), stop(0.5, 4)]);
+        let order: Vec<_> = gradient
+            .stops
+            .iter()
+            .map(|stop| (stop.position, stop.color.r()))
+            .collect();
+        assert_eq!(order, vec![(0., 2), (0.5, 3), (0.5, 4), (1., 1)]);
+    }
+
+    #[test]
+    fn gradients_without_size_paint_nothing() {
+        let gradient = |x1: f32| {
+            canvas_data::Pattern::Raqote(Pattern::LinearGradient(LinearGradientPattern {
+                gradient: raqote::Gradient { stops: vec![] },
+                start: Point2D::zero(),
+                end: Point2D::new(x1, 0.),
+            }))
+        };
+        assert!(gradient(0.).is_zero_size_gradient());
+        assert!(!gradient(1.).is_zero_size_gradient());
     }
 }
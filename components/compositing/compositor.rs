@@ -31,7 +31,7 @@ use script_traits::{AnimationState, AnimationTickType, ConstellationMsg, LayoutC
 use script_traits::{
     MouseButton, MouseEventType, ScrollState, TouchEventType, TouchId, WheelDelta,
 };
-use script_traits::{UntrustedNodeAddress, WindowSizeData, WindowSizeType};
+use script_traits::{PrintSettings, UntrustedNodeAddress, WindowSizeData, WindowSizeType};
 use servo_geometry::{DeviceIndependentPixel, FramebufferUintLength};
 use std::collections::HashMap;
 use std::env;
@@ -270,6 +270,10 @@ enum CompositeTarget {
 
     /// Compose to a PNG, write it to disk, and then exit the browser (used for reftests)
     PngFile,
+
+    /// Compose as normal, print the document to a PDF, write it to disk, and then exit the
+    /// browser
+    PdfFile,
 }
 
 impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
@@ -283,6 +287,7 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
         device_pixels_per_px: Option<f32>,
     ) -> Self {
         let composite_target = match output_file {
+            Some(ref path) if path.ends_with(".pdf") => CompositeTarget::PdfFile,
             Some(_) => CompositeTarget::PngFile,
             None => CompositeTarget::Window,
         };
@@ -524,6 +529,37 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
                 }
             },
 
+            (
+                Msg::WebDriverMouseButtonEvent(mouse_event_type, mouse_button, x, y),
+                ShutdownState::NotShuttingDown,
+            ) => {
+                let point = self.device_point_from_css_point(x, y);
+                let event = match mouse_event_type {
+                    MouseEventType::Click => MouseWindowEvent::Click(mouse_button, point),
+                    MouseEventType::MouseDown => MouseWindowEvent::MouseDown(mouse_button, point),
+                    MouseEventType::MouseUp => MouseWindowEvent::MouseUp(mouse_button, point),
+                };
+                self.on_mouse_window_event_class(event);
+            },
+
+            (Msg::WebDriverMouseMoveEvent(x, y), ShutdownState::NotShuttingDown) => {
+                let point = self.device_point_from_css_point(x, y);
+                self.on_mouse_window_move_event_class(point);
+            },
+
+            (Msg::WebDriverWheelEvent(delta, x, y), ShutdownState::NotShuttingDown) => {
+                let point = self.device_point_from_css_point(x, y);
+                self.on_wheel_event(delta, point);
+                // Scroll locations are in device pixels, and their positive deltas scroll
+                // towards the top left, unlike those of wheels.
+                let scroll_delta =
+                    TypedVector2D::<f32, CSSPixel>::new(-delta.x as f32, -delta.y as f32) *
+                        self.device_pixels_per_page_px();
+                let scroll_location =
+                    ScrollLocation::Delta(LayoutVector2D::from_untyped(&scroll_delta.to_untyped()));
+                self.on_scroll_event(scroll_location, point.to_i32(), TouchEventType::Move);
+            },
+
             // When we are shutting_down, we need to avoid performing operations
             // such as Paint that may crash because we have begun tearing down
             // the rest of our resources.
@@ -1067,6 +1103,10 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
         self.page_zoom * self.hidpi_factor()
     }
 
+    fn device_point_from_css_point(&self, x: f32, y: f32) -> DevicePoint {
+        TypedPoint2D::<f32, CSSPixel>::new(x, y) * self.device_pixels_per_page_px()
+    }
+
     fn update_zoom_transform(&mut self) {
         let scale = self.device_pixels_per_page_px();
         self.scale = TypedScale::new(scale.get());
@@ -1244,7 +1284,9 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
         self.webrender.update();
 
         let wait_for_stable_image = match target {
-            CompositeTarget::WindowAndPng | CompositeTarget::PngFile => true,
+            CompositeTarget::WindowAndPng | CompositeTarget::PngFile | CompositeTarget::PdfFile => {
+                true
+            },
             CompositeTarget::Window => self.exit_after_load,
         };
 
@@ -1265,7 +1307,7 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
 
         let rt_info = match target {
             #[cfg(feature = "gl")]
-            CompositeTarget::Window => gl::RenderTargetInfo::default(),
+            CompositeTarget::Window | CompositeTarget::PdfFile => gl::RenderTargetInfo::default(),
             #[cfg(feature = "gl")]
             CompositeTarget::WindowAndPng | CompositeTarget::PngFile => gl::initialize_png(
                 &*self.window.gl(),
@@ -1376,6 +1418,15 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
                 );
                 None
             },
+            CompositeTarget::PdfFile => {
+                profile(
+                    ProfilerCategory::ImageSaving,
+                    None,
+                    self.time_profiler_chan.clone(),
+                    || self.save_pdf(),
+                );
+                None
+            },
             #[cfg(not(feature = "gl"))]
             _ => None,
         };
@@ -1393,6 +1444,41 @@ impl<Window: WindowMethods + ?Sized> IOCompositor<Window> {
         Ok(rv)
     }
 
+    /// Print the root pipeline to the output file, as a PDF document.
+    fn save_pdf(&self) {
+        let path = match self.output_file.as_ref() {
+            Some(path) => path,
+            None => return error!("No file specified."),
+        };
+        let pipeline = match self.root_pipeline.as_ref() {
+            Some(pipeline) => pipeline,
+            None => return error!("No document to print."),
+        };
+        let settings = PrintSettings {
+            background: true,
+            ..PrintSettings::default()
+        };
+        let (sender, receiver) = ipc::channel().unwrap();
+        if let Err(e) = pipeline
+            .layout_chan
+            .send(LayoutControlMsg::Print(settings, sender))
+        {
+            return warn!("Sending Print message to layout failed ({:?}).", e);
+        }
+        let pdf = match receiver.recv() {
+            Ok(Some(pdf)) => pdf,
+            _ => return error!("Failed to print {}.", path),
+        };
+        match File::create(path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(&pdf) {
+                    error!("Failed to save {} ({}).", path, e);
+                }
+            },
+            Err(e) => error!("Failed to create {} ({}).", path, e),
+        }
+    }
+
     fn composite_if_necessary(&mut self, reason: CompositingReason) {
         if self.composition_request == CompositionRequest::NoCompositingNecessary {
             if self.is_running_problem_test {
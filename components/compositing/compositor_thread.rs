@@ -15,6 +15,7 @@ use net_traits::image::base::Image;
 use profile_traits::mem;
 use profile_traits::time;
 use script_traits::{AnimationState, ConstellationMsg, EventResult};
+use script_traits::{MouseButton, MouseEventType, WheelDelta};
 use std::fmt::{Debug, Error, Formatter};
 use style_traits::viewport::ViewportConstraints;
 use webrender_api;
@@ -113,6 +114,16 @@ pub enum Msg {
     GetScreenSize(IpcSender<DeviceIntSize>),
     /// Get screen available size.
     GetScreenAvailSize(IpcSender<DeviceIntSize>),
+
+    /// Act as if the given mouse button was pressed, released or clicked at the given point
+    /// of the viewport, in CSS pixels, for WebDriver.
+    WebDriverMouseButtonEvent(MouseEventType, MouseButton, f32, f32),
+    /// Act as if the mouse was moved to the given point of the viewport, in CSS pixels, for
+    /// WebDriver.
+    WebDriverMouseMoveEvent(f32, f32),
+    /// Act as if the mouse wheel was turned at the given point of the viewport, in CSS
+    /// pixels, for WebDriver.
+    WebDriverWheelEvent(WheelDelta, f32, f32),
 }
 
 impl Debug for Msg {
@@ -135,6 +146,9 @@ impl Debug for Msg {
             Msg::GetClientWindow(..) => write!(f, "GetClientWindow"),
             Msg::GetScreenSize(..) => write!(f, "GetScreenSize"),
             Msg::GetScreenAvailSize(..) => write!(f, "GetScreenAvailSize"),
+            Msg::WebDriverMouseButtonEvent(..) => write!(f, "WebDriverMouseButtonEvent"),
+            Msg::WebDriverMouseMoveEvent(..) => write!(f, "WebDriverMouseMoveEvent"),
+            Msg::WebDriverWheelEvent(..) => write!(f, "WebDriverWheelEvent"),
         }
     }
 }
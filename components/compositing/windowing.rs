@@ -4,10 +4,13 @@
 
 //! Abstract windowing methods. The concrete implementations of these can be found in `platform/`.
 
-use embedder_traits::EventLoopWaker;
+use cookie::Cookie;
+use embedder_traits::{DownloadId, EventLoopWaker};
 use euclid::TypedScale;
 #[cfg(feature = "gl")]
 use gleam::gl;
+use hyper_serde::Serde;
+use ipc_channel::ipc::IpcSender;
 use keyboard_types::KeyboardEvent;
 use msg::constellation_msg::{PipelineId, TopLevelBrowsingContextId, TraversalDirection};
 use script_traits::{MouseButton, TouchEventType, TouchId, WheelDelta};
@@ -101,6 +104,25 @@ pub enum WindowEvent {
     CaptureWebRender,
     /// Toggle sampling profiler with the given sampling rate and max duration.
     ToggleSamplingProfiler(Duration, Duration),
+    /// Retrieve the cookies of the given domain and its subdomains, or all cookies.
+    GetCookies(Option<String>, IpcSender<Vec<Serde<Cookie<'static>>>>),
+    /// Store a cookie, as if it had been set by a response from the given URL.
+    AddCookie(ServoUrl, Serde<Cookie<'static>>),
+    /// Remove the cookies of the given domain and its subdomains, or all cookies.
+    DeleteCookies(Option<String>),
+    /// Create a named ephemeral session, with its own cookies, cache and storage,
+    /// none of which is ever written to disk.
+    NewEphemeralSession(String),
+    /// Create a new top level browsing context in the named ephemeral session.
+    NewBrowserInEphemeralSession(ServoUrl, TopLevelBrowsingContextId, String),
+    /// Close all browsers of the named ephemeral session, and discard all of its state.
+    DiscardEphemeralSession(String),
+    /// Stop fetching a download, keeping what was received so far.
+    PauseDownload(DownloadId),
+    /// Fetch the rest of a paused download.
+    ResumeDownload(DownloadId),
+    /// Stop a download, and remove what was received so far.
+    CancelDownload(DownloadId),
 }
 
 impl Debug for WindowEvent {
@@ -131,6 +153,17 @@ impl Debug for WindowEvent {
             WindowEvent::CaptureWebRender => write!(f, "CaptureWebRender"),
             WindowEvent::ToggleSamplingProfiler(..) => write!(f, "ToggleSamplingProfiler"),
             WindowEvent::ExitFullScreen(..) => write!(f, "ExitFullScreen"),
+            WindowEvent::GetCookies(..) => write!(f, "GetCookies"),
+            WindowEvent::AddCookie(..) => write!(f, "AddCookie"),
+            WindowEvent::DeleteCookies(..) => write!(f, "DeleteCookies"),
+            WindowEvent::NewEphemeralSession(..) => write!(f, "NewEphemeralSession"),
+            WindowEvent::NewBrowserInEphemeralSession(..) => {
+                write!(f, "NewBrowserInEphemeralSession")
+            },
+            WindowEvent::DiscardEphemeralSession(..) => write!(f, "DiscardEphemeralSession"),
+            WindowEvent::PauseDownload(..) => write!(f, "PauseDownload"),
+            WindowEvent::ResumeDownload(..) => write!(f, "ResumeDownload"),
+            WindowEvent::CancelDownload(..) => write!(f, "CancelDownload"),
         }
     }
 }
@@ -46,3 +46,13 @@ pub fn default_config_dir() -> Option<PathBuf> {
 pub fn default_config_dir() -> Option<PathBuf> {
     None
 }
+
+#[cfg(not(any(target_os = "android", feature = "uwp")))]
+pub fn default_download_dir() -> Option<PathBuf> {
+    ::dirs::download_dir()
+}
+
+#[cfg(any(target_os = "android", feature = "uwp"))]
+pub fn default_download_dir() -> Option<PathBuf> {
+    None
+}
@@ -134,6 +134,10 @@ pub struct Opts {
     /// remote WebDriver commands.
     pub webdriver_port: Option<u16>,
 
+    /// `None` to disable WebDriver BiDi or `Some` with a port number to start a WebSocket
+    /// server to listen to remote WebDriver BiDi commands.
+    pub webdriver_bidi_port: Option<u16>,
+
     /// The initial requested size of the window.
     pub initial_window_size: TypedSize2D<u32, DeviceIndependentPixel>,
 
@@ -576,6 +580,7 @@ pub fn default_opts() -> Opts {
         debugger_port: None,
         devtools_port: None,
         webdriver_port: None,
+        webdriver_bidi_port: None,
         initial_window_size: TypedSize2D::new(1024, 740),
         user_agent: default_user_agent_string(DEFAULT_USER_AGENT).into(),
         multiprocess: false,
@@ -619,7 +624,12 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
     let mut opts = Options::new();
     opts.optflag("c", "cpu", "CPU painting");
     opts.optflag("g", "gpu", "GPU painting");
-    opts.optopt("o", "output", "Output file", "output.png");
+    opts.optopt(
+        "o",
+        "output",
+        "Output file, printed on pages if it ends with .pdf",
+        "output.png",
+    );
     opts.optopt("s", "size", "Size of tiles", "512");
     opts.optopt("", "device-pixel-ratio", "Device pixels per px", "");
     opts.optflagopt(
@@ -706,6 +716,12 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
         "Start remote WebDriver server on port",
         "7000",
     );
+    opts.optflagopt(
+        "",
+        "webdriver-bidi",
+        "Start remote WebDriver BiDi server on port",
+        "7001",
+    );
     opts.optopt("", "resolution", "Set window resolution.", "1024x740");
     opts.optopt(
         "u",
@@ -944,6 +960,12 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
         })
     });
 
+    let webdriver_bidi_port = opt_match.opt_default("webdriver-bidi", "7001").map(|port| {
+        port.parse().unwrap_or_else(|err| {
+            args_fail(&format!("Error parsing option: --webdriver-bidi ({})", err))
+        })
+    });
+
     let initial_window_size = match opt_match.opt_str("resolution") {
         Some(res_string) => {
             let res: Vec<u32> = res_string
@@ -1019,6 +1041,7 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
         debugger_port: debugger_port,
         devtools_port: devtools_port,
         webdriver_port: webdriver_port,
+        webdriver_bidi_port: webdriver_bidi_port,
         initial_window_size: initial_window_size,
         user_agent: user_agent,
         multiprocess: opt_match.opt_present("M"),
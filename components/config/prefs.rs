@@ -158,6 +158,12 @@ mod gen {
                     },
                 },
             },
+            devtools: {
+                netmonitor: {
+                    #[serde(rename = "devtools.netmonitor.response-body-limit")]
+                    response_body_limit: i64,
+                },
+            },
             dom: {
                 bluetooth: {
                     enabled: bool,
@@ -456,12 +462,26 @@ mod gen {
                 }
             },
             network: {
+                downloads: {
+                    directory: String,
+                },
                 http_cache: {
                     #[serde(rename = "network.http-cache.disabled")]
                     disabled: bool,
+                    disk: {
+                        #[serde(rename = "network.http-cache.disk.enabled")]
+                        enabled: bool,
+                        #[serde(rename = "network.http-cache.disk.max-size")]
+                        max_size: i64,
+                    },
                 },
                 mime: {
                     sniff: bool,
+                },
+                proxy: {
+                    http: String,
+                    https: String,
+                    no_proxy: String,
                 }
             },
             session_history: {
@@ -94,7 +94,7 @@ use crate::browsingcontext::{
     AllBrowsingContextsIterator, BrowsingContext, FullyActiveBrowsingContextsIterator,
 };
 use crate::event_loop::EventLoop;
-use crate::network_listener::NetworkListener;
+use crate::network_listener::{NetworkListener, NetworkListenerMsg};
 use crate::pipeline::{InitialPipelineState, Pipeline};
 use crate::session_history::{
     JointSessionHistory, NeedsToReload, SessionHistoryChange, SessionHistoryDiff,
@@ -125,17 +125,29 @@ use layout_traits::LayoutThreadFactory;
 use log::{Level, LevelFilter, Log, Metadata, Record};
 use media::{GLPlayerThreads, WindowGLContext};
 use msg::constellation_msg::{BackgroundHangMonitorRegister, HangMonitorAlert, SamplerControlMsg};
+use msg::constellation_msg::{BroadcastChannelRouterId, MessagePortId, MessagePortRouterId};
 use msg::constellation_msg::{
     BrowsingContextGroupId, BrowsingContextId, HistoryStateId, PipelineId,
     TopLevelBrowsingContextId,
 };
 use msg::constellation_msg::{PipelineNamespace, PipelineNamespaceId, TraversalDirection};
+use net::fetch::methods::is_url_potentially_trustworthy;
+use net::resource_thread::new_ephemeral_resource_threads;
+use net_traits::cache_storage_thread::CacheStorageThreadMsg;
+use net_traits::csp::Violation;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
 use net_traits::pub_domains::reg_host;
 use net_traits::request::RequestBuilder;
 use net_traits::storage_thread::{StorageThreadMsg, StorageType};
-use net_traits::{self, FetchResponseMsg, IpcSend, ResourceThreads};
+use net_traits::{self, IpcSend, ResourceThreads};
+use net_traits::{CookieSource, CoreResourceMsg};
 use profile_traits::mem;
 use profile_traits::time;
+use script_traits::transferable::StructuredSerializedData;
+use script_traits::transferable::{BroadcastMsg, MessagePortMsg, PortMessageTask};
+use script_traits::webdriver_msg::WebDriverUserPromptType;
+use script_traits::webdriver_msg::{WebDriverBrowsingContextEvent, WebDriverWindowState};
+use script_traits::webdriver_msg::{WebDriverUserPromptAction, WebDriverUserPromptError};
 use script_traits::CompositorEvent::{MouseButtonEvent, MouseMoveEvent};
 use script_traits::MouseEventType;
 use script_traits::{webdriver_msg, LogEntry, ScriptToConstellationChan, ServiceWorkerMsg};
@@ -183,6 +195,9 @@ struct Browser {
 
     /// The joint session history for this browser.
     session_history: JointSessionHistory,
+
+    /// The name of the ephemeral session this browser belongs to, if any.
+    ephemeral_session: Option<String>,
 }
 
 /// A browsing context group.
@@ -203,6 +218,24 @@ struct BrowsingContextGroup {
     event_loops: HashMap<Host, Weak<EventLoop>>,
 }
 
+/// The state of a message port, as tracked by the constellation.
+enum TransferState {
+    /// The port is managed by the global behind the given router.
+    Managed(MessagePortRouterId),
+    /// The port is being transferred to another global, and the messages
+    /// sent to it in the meantime are buffered here.
+    TransferInProgress(VecDeque<PortMessageTask>),
+}
+
+/// The information the constellation keeps about a message port.
+struct MessagePortInfo {
+    /// Where the messages for this port should go.
+    state: TransferState,
+
+    /// The port this one is entangled with, if any.
+    entangled_with: Option<MessagePortId>,
+}
+
 /// The `Constellation` itself. In the servo browser, there is one
 /// constellation, which maintains all of the browser global data.
 /// In embedded applications, there may be more than one constellation,
@@ -249,10 +282,10 @@ pub struct Constellation<Message, LTF, STF> {
     layout_receiver: Receiver<Result<FromLayoutMsg, IpcError>>,
 
     /// A channel for network listener to send messages to the constellation.
-    network_listener_sender: Sender<(PipelineId, FetchResponseMsg)>,
+    network_listener_sender: Sender<(PipelineId, NetworkListenerMsg)>,
 
     /// A channel for the constellation to receive messages from network listener.
-    network_listener_receiver: Receiver<(PipelineId, FetchResponseMsg)>,
+    network_listener_receiver: Receiver<(PipelineId, NetworkListenerMsg)>,
 
     /// A channel for the constellation to receive messages from the compositor thread.
     compositor_receiver: Receiver<FromCompositorMsg>,
@@ -283,6 +316,11 @@ pub struct Constellation<Message, LTF, STF> {
     /// browsing.
     private_resource_threads: ResourceThreads,
 
+    /// Channels to the resource-related threads of every named ephemeral session.
+    /// These keep all their state in memory, isolated from the public and private
+    /// resource threads, and from each other.
+    ephemeral_sessions: HashMap<String, ResourceThreads>,
+
     /// A channel for the constellation to send messages to the font
     /// cache thread.
     font_cache_thread: FontCacheThread,
@@ -416,6 +454,18 @@ pub struct Constellation<Message, LTF, STF> {
 
     /// Application window's GL Context for Media player
     player_context: WindowGLContext,
+
+    /// The message ports known to the constellation.
+    message_ports: HashMap<MessagePortId, MessagePortInfo>,
+
+    /// The routers of the globals managing message ports.
+    message_port_routers: HashMap<MessagePortRouterId, IpcSender<MessagePortMsg>>,
+
+    /// The routers of the globals managing broadcast channels.
+    broadcast_routers: HashMap<BroadcastChannelRouterId, IpcSender<BroadcastMsg>>,
+
+    /// For each origin, the routers with at least one channel of a given name.
+    broadcast_channels: HashMap<ImmutableOrigin, HashMap<String, Vec<BroadcastChannelRouterId>>>,
 }
 
 /// State needed to construct a constellation.
@@ -475,6 +525,11 @@ pub struct InitialConstellationState {
 struct WebDriverData {
     load_channel: Option<(PipelineId, IpcSender<webdriver_msg::LoadStatus>)>,
     resize_channel: Option<IpcSender<WindowSizeData>>,
+    /// The user prompts that are held open until a WebDriver client handles them,
+    /// one per top-level browsing context.
+    user_prompts: HashMap<TopLevelBrowsingContextId, UserPrompt>,
+    /// Where to report what happens to browsing contexts, for WebDriver BiDi.
+    event_channel: Option<IpcSender<WebDriverBrowsingContextEvent>>,
 }
 
 impl WebDriverData {
@@ -482,6 +537,114 @@ impl WebDriverData {
         WebDriverData {
             load_channel: None,
             resize_channel: None,
+            user_prompts: HashMap::new(),
+            event_channel: None,
+        }
+    }
+
+    fn send_browsing_context_event(&self, event: WebDriverBrowsingContextEvent) {
+        if let Some(ref event_channel) = self.event_channel {
+            if let Err(e) = event_channel.send(event) {
+                warn!(
+                    "Failed to send browsing context event to WebDriver ({}).",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Keep the given embedder message open as a user prompt of the top-level browsing
+    /// context, instead of showing it to the user. Messages that aren't user prompts are
+    /// handed back to be forwarded to the embedder.
+    fn hold_user_prompt(
+        &mut self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
+        msg: EmbedderMsg,
+    ) -> Option<EmbedderMsg> {
+        let prompt = match msg {
+            EmbedderMsg::Alert(message, sender) => UserPrompt::Alert(message, sender),
+            EmbedderMsg::Confirm(message, sender) => UserPrompt::Confirm(message, sender),
+            EmbedderMsg::Prompt(message, default, sender) => {
+                UserPrompt::Prompt(message, default, sender)
+            },
+            EmbedderMsg::AllowUnload(sender) => UserPrompt::BeforeUnload(sender),
+            msg => return Some(msg),
+        };
+        match self.user_prompts.entry(top_level_browsing_context_id) {
+            Entry::Occupied(_) => {
+                // WebDriver only knows about one prompt at a time.
+                warn!(
+                    "Dismissing user prompt of {}, another one is already open.",
+                    top_level_browsing_context_id
+                );
+                prompt.handle(WebDriverUserPromptAction::Dismiss);
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(prompt);
+            },
+        }
+        None
+    }
+}
+
+/// A user prompt opened by script, which blocks the script thread until it is answered.
+/// https://w3c.github.io/webdriver/#dfn-user-prompt
+enum UserPrompt {
+    Alert(String, IpcSender<()>),
+    Confirm(String, IpcSender<bool>),
+    /// The message, the text that will be returned if the prompt is accepted, and the
+    /// sender for the reply.
+    Prompt(String, String, IpcSender<Option<String>>),
+    BeforeUnload(IpcSender<bool>),
+}
+
+impl UserPrompt {
+    fn prompt_type(&self) -> WebDriverUserPromptType {
+        match *self {
+            UserPrompt::Alert(..) => WebDriverUserPromptType::Alert,
+            UserPrompt::Confirm(..) => WebDriverUserPromptType::Confirm,
+            UserPrompt::Prompt(..) => WebDriverUserPromptType::Prompt,
+            UserPrompt::BeforeUnload(..) => WebDriverUserPromptType::BeforeUnload,
+        }
+    }
+
+    fn message(&self) -> Option<String> {
+        match *self {
+            UserPrompt::Alert(ref message, _) |
+            UserPrompt::Confirm(ref message, _) |
+            UserPrompt::Prompt(ref message, _, _) => Some(message.clone()),
+            UserPrompt::BeforeUnload(_) => None,
+        }
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-send-alert-text
+    fn set_text(&mut self, text: String) -> Result<(), WebDriverUserPromptError> {
+        match *self {
+            UserPrompt::Alert(..) | UserPrompt::Confirm(..) => {
+                Err(WebDriverUserPromptError::NotInteractable)
+            },
+            UserPrompt::Prompt(_, ref mut value, _) => {
+                *value = text;
+                Ok(())
+            },
+            UserPrompt::BeforeUnload(_) => Err(WebDriverUserPromptError::UnsupportedOperation),
+        }
+    }
+
+    /// Answer the prompt, unblocking the script thread that opened it.
+    fn handle(self, action: WebDriverUserPromptAction) {
+        let accept = action == WebDriverUserPromptAction::Accept;
+        let result = match self {
+            UserPrompt::Alert(_, sender) => sender.send(()),
+            UserPrompt::Confirm(_, sender) | UserPrompt::BeforeUnload(sender) => {
+                sender.send(accept)
+            },
+            UserPrompt::Prompt(_, value, sender) => {
+                sender.send(if accept { Some(value) } else { None })
+            },
+        };
+        if let Err(e) = result {
+            warn!("Failed to reply to user prompt ({}).", e);
         }
     }
 }
@@ -636,6 +799,23 @@ where
     mpsc_receiver
 }
 
+/// Ask the storage, IndexedDB and cache storage threads of a private or ephemeral session
+/// to exit. Their state only lives in memory, so there is no need to wait for them.
+fn exit_storage_threads(resource_threads: &ResourceThreads) {
+    let (sender, _) = ipc::channel().expect("Failed to create IPC channel!");
+    if let Err(e) = resource_threads.send(StorageThreadMsg::Exit(sender)) {
+        warn!("Exit storage thread failed ({})", e);
+    }
+    let (sender, _) = ipc::channel().expect("Failed to create IPC channel!");
+    if let Err(e) = resource_threads.send(IndexedDBThreadMsg::Exit(sender)) {
+        warn!("Exit indexeddb thread failed ({})", e);
+    }
+    let (sender, _) = ipc::channel().expect("Failed to create IPC channel!");
+    if let Err(e) = resource_threads.send(CacheStorageThreadMsg::Exit(sender)) {
+        warn!("Exit cache storage thread failed ({})", e);
+    }
+}
+
 impl<Message, LTF, STF> Constellation<Message, LTF, STF>
 where
     LTF: LayoutThreadFactory<Message = Message>,
@@ -723,6 +903,7 @@ where
                     bluetooth_thread: state.bluetooth_thread,
                     public_resource_threads: state.public_resource_threads,
                     private_resource_threads: state.private_resource_threads,
+                    ephemeral_sessions: HashMap::new(),
                     font_cache_thread: state.font_cache_thread,
                     swmanager_chan: None,
                     swmanager_receiver: swmanager_receiver,
@@ -767,6 +948,10 @@ where
                     enable_canvas_antialiasing,
                     glplayer_threads: state.glplayer_threads,
                     player_context: state.player_context,
+                    message_ports: HashMap::new(),
+                    message_port_routers: HashMap::new(),
+                    broadcast_routers: HashMap::new(),
+                    broadcast_channels: HashMap::new(),
                 };
 
                 constellation.run();
@@ -964,11 +1149,7 @@ where
             },
         };
 
-        let resource_threads = if is_private {
-            self.private_resource_threads.clone()
-        } else {
-            self.public_resource_threads.clone()
-        };
+        let resource_threads = self.resource_threads(top_level_browsing_context_id, is_private);
 
         let result = Pipeline::spawn::<Message, LTF, STF>(InitialPipelineState {
             id: pipeline_id,
@@ -1120,11 +1301,23 @@ where
             .insert(browsing_context_id, browsing_context);
 
         // If this context is a nested container, attach it to parent pipeline.
+        let mut parent_browsing_context_id = None;
         if let Some(parent_pipeline_id) = parent_pipeline_id {
             if let Some(parent) = self.pipelines.get_mut(&parent_pipeline_id) {
                 parent.add_child(browsing_context_id);
+                parent_browsing_context_id = Some(parent.browsing_context_id);
             }
         }
+
+        if let Some(pipeline) = self.pipelines.get(&pipeline_id) {
+            self.webdriver.send_browsing_context_event(
+                WebDriverBrowsingContextEvent::ContextCreated(
+                    browsing_context_id,
+                    parent_browsing_context_id,
+                    pipeline.url.clone(),
+                ),
+            );
+        }
     }
 
     fn add_pending_change(&mut self, change: SessionHistoryChange) {
@@ -1151,7 +1344,7 @@ where
             BackgroundHangMonitor(HangMonitorAlert),
             Compositor(FromCompositorMsg),
             Layout(FromLayoutMsg),
-            NetworkListener((PipelineId, FetchResponseMsg)),
+            NetworkListener((PipelineId, NetworkListenerMsg)),
             FromSWManager(SWManagerMsg),
         }
 
@@ -1227,8 +1420,15 @@ where
         }
     }
 
-    fn handle_request_from_network_listener(&mut self, message: (PipelineId, FetchResponseMsg)) {
-        let (id, message_) = message;
+    fn handle_request_from_network_listener(&mut self, message: (PipelineId, NetworkListenerMsg)) {
+        let (id, message_) = match message {
+            (id, NetworkListenerMsg::Fetch(message_)) => (id, message_),
+            // The response is downloaded instead of being loaded in the pipeline.
+            (id, NetworkListenerMsg::Download) => return self.handle_abort_load_url_msg(id),
+            (id, NetworkListenerMsg::CspViolations(violations)) => {
+                return self.handle_navigation_csp_violations(id, violations);
+            },
+        };
         let result = match self.pipelines.get(&id) {
             Some(pipeline) => {
                 let msg = ConstellationControlMsg::NavigationResponse(id, message_);
@@ -1243,6 +1443,23 @@ where
         }
     }
 
+    /// Violations found while navigating a nested browsing context are reported
+    /// to the document containing it, whose policies were checked.
+    fn handle_navigation_csp_violations(&mut self, id: PipelineId, violations: Vec<Violation>) {
+        let parent_pipeline_id = match self.parent_pipeline_id(id) {
+            Some(parent_pipeline_id) => parent_pipeline_id,
+            None => return,
+        };
+        let msg = ConstellationControlMsg::ReportCspViolations(parent_pipeline_id, violations);
+        let result = match self.pipelines.get(&parent_pipeline_id) {
+            Some(parent) => parent.event_loop.send(msg),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.handle_send_error(parent_pipeline_id, e);
+        }
+    }
+
     fn handle_request_from_swmanager(&mut self, message: SWManagerMsg) {
         match message {
             SWManagerMsg::OwnSender(sw_sender) => {
@@ -1363,7 +1580,32 @@ where
             // Create a new top level browsing context. Will use response_chan to return
             // the browsing context id.
             FromCompositorMsg::NewBrowser(url, top_level_browsing_context_id) => {
-                self.handle_new_top_level_browsing_context(url, top_level_browsing_context_id);
+                self.handle_new_top_level_browsing_context(
+                    url,
+                    top_level_browsing_context_id,
+                    None,
+                );
+            },
+            // Create a new top level browsing context in an ephemeral session.
+            FromCompositorMsg::NewBrowserInEphemeralSession(
+                url,
+                top_level_browsing_context_id,
+                name,
+            ) => {
+                if !self.ephemeral_sessions.contains_key(&name) {
+                    return warn!("Ephemeral session {} does not exist.", name);
+                }
+                self.handle_new_top_level_browsing_context(
+                    url,
+                    top_level_browsing_context_id,
+                    Some(name),
+                );
+            },
+            FromCompositorMsg::NewEphemeralSession(name) => {
+                self.handle_new_ephemeral_session(name);
+            },
+            FromCompositorMsg::DiscardEphemeralSession(name) => {
+                self.handle_discard_ephemeral_session(name);
             },
             // Close a top level browsing context.
             FromCompositorMsg::CloseBrowser(top_level_browsing_context_id) => {
@@ -1425,6 +1667,42 @@ where
             FromCompositorMsg::ExitFullScreen(top_level_browsing_context_id) => {
                 self.handle_exit_fullscreen_msg(top_level_browsing_context_id);
             },
+            FromCompositorMsg::GetCookies(domain, sender) => {
+                let msg = CoreResourceMsg::GetCookiesForDomain(domain, sender);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to get cookies ({})", e);
+                }
+            },
+            FromCompositorMsg::AddCookie(url, cookie) => {
+                let msg = CoreResourceMsg::SetCookieForUrl(url, cookie, CookieSource::HTTP);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to add cookie ({})", e);
+                }
+            },
+            FromCompositorMsg::DeleteCookies(domain) => {
+                let msg = CoreResourceMsg::RemoveCookiesForDomain(domain);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to delete cookies ({})", e);
+                }
+            },
+            FromCompositorMsg::PauseDownload(id) => {
+                let msg = CoreResourceMsg::PauseDownload(id);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to pause download ({})", e);
+                }
+            },
+            FromCompositorMsg::ResumeDownload(id) => {
+                let msg = CoreResourceMsg::ResumeDownload(id);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to resume download ({})", e);
+                }
+            },
+            FromCompositorMsg::CancelDownload(id) => {
+                let msg = CoreResourceMsg::CancelDownload(id);
+                if let Err(e) = self.public_resource_threads.send(msg) {
+                    warn!("Resource thread failed to cancel download ({})", e);
+                }
+            },
         }
     }
 
@@ -1446,8 +1724,17 @@ where
 
         match content {
             FromScriptMsg::ForwardToEmbedder(embedder_msg) => {
-                self.embedder_proxy
-                    .send((Some(source_top_ctx_id), embedder_msg));
+                // Under WebDriver, user prompts stay open until the client handles them.
+                let embedder_msg = if opts::get().webdriver_port.is_some() {
+                    self.webdriver
+                        .hold_user_prompt(source_top_ctx_id, embedder_msg)
+                } else {
+                    Some(embedder_msg)
+                };
+                if let Some(embedder_msg) = embedder_msg {
+                    self.embedder_proxy
+                        .send((Some(source_top_ctx_id), embedder_msg));
+                }
             },
             FromScriptMsg::PipelineExited => {
                 self.handle_pipeline_exited(source_pipeline_id);
@@ -1460,7 +1747,12 @@ where
             },
 
             FromScriptMsg::InitiateNavigateRequest(req_init, cancel_chan) => {
-                self.handle_navigate_request(source_pipeline_id, req_init, cancel_chan);
+                self.handle_navigate_request(
+                    source_top_ctx_id,
+                    source_pipeline_id,
+                    req_init,
+                    cancel_chan,
+                );
             },
             FromScriptMsg::ScriptLoadedURLInIFrame(load_info) => {
                 self.handle_script_loaded_url_in_iframe_msg(load_info);
@@ -1620,6 +1912,283 @@ where
                     new_value,
                 );
             },
+            FromScriptMsg::NewPipelineNamespace(sender) => {
+                if let Err(e) = sender.send(self.next_pipeline_namespace_id()) {
+                    warn!("Sending pipeline namespace failed ({}).", e);
+                }
+            },
+            FromScriptMsg::NewMessagePortRouter(router_id, sender) => {
+                self.message_port_routers.insert(router_id, sender);
+            },
+            FromScriptMsg::RemoveMessagePortRouter(router_id) => {
+                self.handle_remove_messageport_router(router_id);
+            },
+            FromScriptMsg::NewMessagePort(router_id, port_id) => {
+                self.message_ports.insert(
+                    port_id,
+                    MessagePortInfo {
+                        state: TransferState::Managed(router_id),
+                        entangled_with: None,
+                    },
+                );
+            },
+            FromScriptMsg::EntanglePorts(port1, port2) => {
+                self.handle_entangle_messageports(port1, port2);
+            },
+            FromScriptMsg::MessagePortShipped(port_id) => {
+                self.handle_messageport_shipped(port_id);
+            },
+            FromScriptMsg::CompleteMessagePortTransfer(router_id, port_id) => {
+                self.handle_complete_messageport_transfer(router_id, port_id);
+            },
+            FromScriptMsg::RemoveMessagePort(port_id) => {
+                self.handle_remove_messageport(port_id);
+            },
+            FromScriptMsg::RerouteMessagePort(port_id, task) => {
+                self.handle_reroute_messageport(port_id, task);
+            },
+            FromScriptMsg::NewBroadcastChannelRouter(router_id, sender, origin) => {
+                if self
+                    .check_origin_against_pipeline(&source_pipeline_id, &origin)
+                    .is_err()
+                {
+                    return warn!("Attempt to add broadcast router from an unexpected origin.");
+                }
+                self.broadcast_routers.insert(router_id, sender);
+            },
+            FromScriptMsg::RemoveBroadcastChannelRouter(router_id, origin) => {
+                self.handle_remove_broadcast_channel_router(router_id, origin);
+            },
+            FromScriptMsg::NewBroadcastChannelNameInRouter(router_id, channel_name, origin) => {
+                if self
+                    .check_origin_against_pipeline(&source_pipeline_id, &origin)
+                    .is_err()
+                {
+                    return warn!("Attempt to add channel name from an unexpected origin.");
+                }
+                self.handle_new_broadcast_channel_name_in_router(router_id, channel_name, origin);
+            },
+            FromScriptMsg::RemoveBroadcastChannelNameInRouter(router_id, channel_name, origin) => {
+                self.handle_remove_broadcast_channel_name_in_router(
+                    router_id,
+                    channel_name,
+                    origin,
+                );
+            },
+            FromScriptMsg::ScheduleBroadcast(router_id, message) => {
+                if self
+                    .check_origin_against_pipeline(&source_pipeline_id, &message.origin)
+                    .is_err()
+                {
+                    return warn!("Attempt to broadcast from an unexpected origin.");
+                }
+                self.handle_schedule_broadcast(router_id, message);
+            },
+        }
+    }
+
+    /// Check that the origin claimed by a message matches the one of the pipeline it came from.
+    fn check_origin_against_pipeline(
+        &self,
+        pipeline_id: &PipelineId,
+        origin: &ImmutableOrigin,
+    ) -> Result<(), ()> {
+        match self.pipelines.get(pipeline_id) {
+            Some(pipeline) if pipeline.url.origin() == *origin => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    fn handle_remove_messageport_router(&mut self, router_id: MessagePortRouterId) {
+        self.message_port_routers.remove(&router_id);
+        // The ports that were managed by this router went away with their global.
+        let removed: Vec<MessagePortId> = self
+            .message_ports
+            .iter()
+            .filter_map(|(port_id, info)| match info.state {
+                TransferState::Managed(id) if id == router_id => Some(*port_id),
+                _ => None,
+            })
+            .collect();
+        for port_id in removed {
+            self.handle_remove_messageport(port_id);
+        }
+    }
+
+    fn handle_entangle_messageports(&mut self, port1: MessagePortId, port2: MessagePortId) {
+        if let Some(info) = self.message_ports.get_mut(&port1) {
+            info.entangled_with = Some(port2);
+        } else {
+            warn!(
+                "Constellation asked to entangle unknown messageport: {}",
+                port1
+            );
+        }
+        if let Some(info) = self.message_ports.get_mut(&port2) {
+            info.entangled_with = Some(port1);
+        } else {
+            warn!(
+                "Constellation asked to entangle unknown messageport: {}",
+                port2
+            );
+        }
+    }
+
+    fn handle_messageport_shipped(&mut self, port_id: MessagePortId) {
+        match self.message_ports.get_mut(&port_id) {
+            Some(info) => {
+                if let TransferState::Managed(_) = info.state {
+                    info.state = TransferState::TransferInProgress(VecDeque::new());
+                }
+            },
+            None => warn!(
+                "Constellation asked to ship unknown messageport: {}",
+                port_id
+            ),
+        }
+    }
+
+    fn handle_complete_messageport_transfer(
+        &mut self,
+        router_id: MessagePortRouterId,
+        port_id: MessagePortId,
+    ) {
+        let buffer = match self.message_ports.get_mut(&port_id) {
+            Some(info) => match replace(&mut info.state, TransferState::Managed(router_id)) {
+                TransferState::TransferInProgress(buffer) => buffer,
+                TransferState::Managed(_) => {
+                    warn!("Completing the transfer of a messageport that wasn't shipped.");
+                    VecDeque::new()
+                },
+            },
+            None => {
+                return warn!(
+                    "Constellation asked to complete transfer of unknown messageport: {}",
+                    port_id
+                );
+            },
+        };
+        match self.message_port_routers.get(&router_id) {
+            Some(sender) => {
+                if let Err(e) = sender.send(MessagePortMsg::CompleteTransfer(port_id, buffer)) {
+                    warn!("Sending messageport transfer completion failed ({}).", e);
+                }
+            },
+            None => warn!("No messageport router for {}", router_id),
+        }
+    }
+
+    fn handle_remove_messageport(&mut self, port_id: MessagePortId) {
+        let entangled = match self.message_ports.remove(&port_id) {
+            Some(info) => info.entangled_with,
+            None => return,
+        };
+        if let Some(entangled_id) = entangled {
+            if let Some(info) = self.message_ports.get_mut(&entangled_id) {
+                info.entangled_with = None;
+            }
+        }
+    }
+
+    fn handle_reroute_messageport(&mut self, port_id: MessagePortId, task: PortMessageTask) {
+        let router_id = match self.message_ports.get_mut(&port_id) {
+            Some(info) => match info.state {
+                TransferState::Managed(router_id) => router_id,
+                TransferState::TransferInProgress(ref mut buffer) => {
+                    return buffer.push_back(task);
+                },
+            },
+            None => {
+                return warn!("Dropping message for unknown messageport: {}", port_id);
+            },
+        };
+        match self.message_port_routers.get(&router_id) {
+            Some(sender) => {
+                if let Err(e) = sender.send(MessagePortMsg::NewTask(port_id, task)) {
+                    warn!("Routing message to messageport {} failed ({}).", port_id, e);
+                }
+            },
+            None => warn!("No messageport router for {}", router_id),
+        }
+    }
+
+    fn handle_remove_broadcast_channel_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        origin: ImmutableOrigin,
+    ) {
+        self.broadcast_routers.remove(&router_id);
+        if let Entry::Occupied(mut entry) = self.broadcast_channels.entry(origin) {
+            for routers in entry.get_mut().values_mut() {
+                routers.retain(|id| *id != router_id);
+            }
+            entry.get_mut().retain(|_, routers| !routers.is_empty());
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    fn handle_new_broadcast_channel_name_in_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        channel_name: String,
+        origin: ImmutableOrigin,
+    ) {
+        let routers = self
+            .broadcast_channels
+            .entry(origin)
+            .or_insert_with(HashMap::new)
+            .entry(channel_name)
+            .or_insert_with(Vec::new);
+        if !routers.contains(&router_id) {
+            routers.push(router_id);
+        }
+    }
+
+    fn handle_remove_broadcast_channel_name_in_router(
+        &mut self,
+        router_id: BroadcastChannelRouterId,
+        channel_name: String,
+        origin: ImmutableOrigin,
+    ) {
+        if let Entry::Occupied(mut entry) = self.broadcast_channels.entry(origin) {
+            if let Some(routers) = entry.get_mut().get_mut(&channel_name) {
+                routers.retain(|id| *id != router_id);
+                if routers.is_empty() {
+                    entry.get_mut().remove(&channel_name);
+                }
+            }
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Broadcast a message to the same-origin channels of every other global.
+    /// The global that posted it takes care of its own channels.
+    fn handle_schedule_broadcast(
+        &mut self,
+        source_router_id: BroadcastChannelRouterId,
+        message: BroadcastMsg,
+    ) {
+        let routers = match self
+            .broadcast_channels
+            .get(&message.origin)
+            .and_then(|channels| channels.get(&message.channel_name))
+        {
+            Some(routers) => routers,
+            None => return,
+        };
+        for router_id in routers.iter().filter(|id| **id != source_router_id) {
+            match self.broadcast_routers.get(router_id) {
+                Some(sender) => {
+                    if let Err(e) = sender.send(message.clone()) {
+                        warn!("Broadcasting to router {} failed ({}).", router_id, e);
+                    }
+                },
+                None => warn!("No broadcast router for {}", router_id),
+            }
         }
     }
 
@@ -1750,6 +2319,9 @@ where
         let (core_sender, core_receiver) = ipc::channel().expect("Failed to create IPC channel!");
         let (storage_sender, storage_receiver) =
             ipc::channel().expect("Failed to create IPC channel!");
+        let (idb_sender, idb_receiver) = ipc::channel().expect("Failed to create IPC channel!");
+        let (cache_storage_sender, cache_storage_receiver) =
+            ipc::channel().expect("Failed to create IPC channel!");
 
         debug!("Exiting core resource threads.");
         if let Err(e) = self
@@ -1779,6 +2351,28 @@ where
             warn!("Exit storage thread failed ({})", e);
         }
 
+        debug!("Exiting indexeddb thread.");
+        if let Err(e) = self
+            .public_resource_threads
+            .send(IndexedDBThreadMsg::Exit(idb_sender))
+        {
+            warn!("Exit indexeddb thread failed ({})", e);
+        }
+
+        debug!("Exiting cache storage thread.");
+        if let Err(e) = self
+            .public_resource_threads
+            .send(CacheStorageThreadMsg::Exit(cache_storage_sender))
+        {
+            warn!("Exit cache storage thread failed ({})", e);
+        }
+
+        debug!("Exiting private and ephemeral storage threads.");
+        exit_storage_threads(&self.private_resource_threads);
+        for (_, resource_threads) in self.ephemeral_sessions.drain() {
+            exit_storage_threads(&resource_threads);
+        }
+
         debug!("Exiting bluetooth thread.");
         if let Err(e) = self.bluetooth_thread.send(BluetoothRequest::Exit) {
             warn!("Exit bluetooth thread failed ({})", e);
@@ -1832,6 +2426,12 @@ where
         if let Err(e) = storage_receiver.recv() {
             warn!("Exit storage thread failed ({})", e);
         }
+        if let Err(e) = idb_receiver.recv() {
+            warn!("Exit indexeddb thread failed ({})", e);
+        }
+        if let Err(e) = cache_storage_receiver.recv() {
+            warn!("Exit cache storage thread failed ({})", e);
+        }
 
         debug!("Asking compositor to complete shutdown.");
         self.compositor_proxy
@@ -2021,6 +2621,7 @@ where
         &mut self,
         url: ServoUrl,
         top_level_browsing_context_id: TopLevelBrowsingContextId,
+        ephemeral_session: Option<String>,
     ) {
         let window_size = self.window_size.initial_viewport;
         let pipeline_id = PipelineId::new();
@@ -2032,7 +2633,7 @@ where
         let browsing_context_id = BrowsingContextId::from(top_level_browsing_context_id);
         let load_data = LoadData::new(url, None, None, None);
         let sandbox = IFrameSandboxState::IFrameUnsandboxed;
-        let is_private = false;
+        let is_private = ephemeral_session.is_some();
         let is_visible = true;
 
         // Register this new top-level browsing context id as a browser and set
@@ -2042,6 +2643,7 @@ where
             Browser {
                 focused_browsing_context_id: browsing_context_id,
                 session_history: JointSessionHistory::new(),
+                ephemeral_session,
             },
         );
 
@@ -2083,6 +2685,14 @@ where
         &mut self,
         top_level_browsing_context_id: TopLevelBrowsingContextId,
     ) {
+        // Unblock the script thread before asking it to exit.
+        if let Some(prompt) = self
+            .webdriver
+            .user_prompts
+            .remove(&top_level_browsing_context_id)
+        {
+            prompt.handle(WebDriverUserPromptAction::Dismiss);
+        }
         let browsing_context_id = BrowsingContextId::from(top_level_browsing_context_id);
         self.close_browsing_context(browsing_context_id, ExitPipelineMode::Normal);
         self.browsers.remove(&top_level_browsing_context_id);
@@ -2101,6 +2711,41 @@ where
             .remove(&browsing_context.bc_group_id);
     }
 
+    fn handle_new_ephemeral_session(&mut self, name: String) {
+        if self.ephemeral_sessions.contains_key(&name) {
+            return warn!("Ephemeral session {} already exists.", name);
+        }
+        // The resource thread handles the session from now on, without us waiting for it.
+        let (resource_threads, receiver) = new_ephemeral_resource_threads();
+        let msg = CoreResourceMsg::NewEphemeralSession(receiver);
+        if let Err(e) = self.public_resource_threads.send(msg) {
+            return warn!("Resource thread failed to create ephemeral session ({})", e);
+        }
+        self.ephemeral_sessions.insert(name, resource_threads);
+    }
+
+    /// Close every browser of an ephemeral session, and drop all of its state.
+    fn handle_discard_ephemeral_session(&mut self, name: String) {
+        let resource_threads = match self.ephemeral_sessions.remove(&name) {
+            Some(resource_threads) => resource_threads,
+            None => return warn!("Ephemeral session {} does not exist.", name),
+        };
+        let browser_ids: Vec<TopLevelBrowsingContextId> = self
+            .browsers
+            .iter()
+            .filter(|&(_, browser)| browser.ephemeral_session.as_ref() == Some(&name))
+            .map(|(&id, _)| id)
+            .collect();
+        for top_level_browsing_context_id in browser_ids {
+            self.handle_close_top_level_browsing_context(top_level_browsing_context_id);
+        }
+        let (sender, _) = ipc::channel().expect("Failed to create IPC channel!");
+        if let Err(e) = resource_threads.send(CoreResourceMsg::Exit(sender)) {
+            warn!("Exit resource thread failed ({})", e);
+        }
+        exit_storage_threads(&resource_threads);
+    }
+
     fn handle_iframe_size_msg(&mut self, iframe_sizes: Vec<IFrameSizeMsg>) {
         for IFrameSizeMsg { data, type_ } in iframe_sizes {
             let window_size = WindowSizeData {
@@ -2152,22 +2797,80 @@ where
         }
     }
 
+    /// The resource threads used by the pipelines of a top-level browsing context:
+    /// those of its ephemeral session, if any, or else the private or public ones.
+    fn resource_threads(
+        &self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
+        is_private: bool,
+    ) -> ResourceThreads {
+        let ephemeral_session = self
+            .browsers
+            .get(&top_level_browsing_context_id)
+            .and_then(|browser| browser.ephemeral_session.as_ref())
+            .and_then(|name| self.ephemeral_sessions.get(name));
+        match ephemeral_session {
+            Some(resource_threads) => resource_threads.clone(),
+            None if is_private => self.private_resource_threads.clone(),
+            None => self.public_resource_threads.clone(),
+        }
+    }
+
     fn handle_navigate_request(
         &self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
         id: PipelineId,
         request_builder: RequestBuilder,
         cancel_chan: IpcReceiver<()>,
     ) {
+        let ancestor_urls = self.ancestor_urls(id);
+        let request_builder = request_builder
+            .has_trustworthy_ancestor_origin(
+                ancestor_urls.iter().any(is_url_potentially_trustworthy),
+            )
+            .top_level_browsing_context_id(Some(top_level_browsing_context_id));
+        let is_private = self
+            .pipelines
+            .get(&id)
+            .and_then(|pipeline| self.browsing_contexts.get(&pipeline.browsing_context_id))
+            .map_or(false, |browsing_context| browsing_context.is_private);
         let listener = NetworkListener::new(
             request_builder,
             id,
-            self.public_resource_threads.clone(),
+            ancestor_urls,
+            self.resource_threads(top_level_browsing_context_id, is_private),
             self.network_listener_sender.clone(),
         );
 
         listener.initiate_fetch(Some(cancel_chan));
     }
 
+    /// The URLs of the documents of the ancestor browsing contexts of a pipeline,
+    /// from its parent to the top-level one.
+    fn ancestor_urls(&self, pipeline_id: PipelineId) -> Vec<ServoUrl> {
+        let mut ancestor_urls = vec![];
+        let mut parent_pipeline_id = self.parent_pipeline_id(pipeline_id);
+        while let Some(pipeline) = parent_pipeline_id.and_then(|id| self.pipelines.get(&id)) {
+            ancestor_urls.push(pipeline.url.clone());
+            parent_pipeline_id = self.parent_pipeline_id(pipeline.id);
+        }
+        ancestor_urls
+    }
+
+    /// The pipeline containing the browsing context of a pipeline, including
+    /// browsing contexts which are only created once their first document loads.
+    fn parent_pipeline_id(&self, pipeline_id: PipelineId) -> Option<PipelineId> {
+        let pipeline = self.pipelines.get(&pipeline_id)?;
+        if let Some(browsing_context) = self.browsing_contexts.get(&pipeline.browsing_context_id) {
+            return browsing_context.parent_pipeline_id;
+        }
+        self.pending_changes
+            .iter()
+            .find(|change| change.new_pipeline_id == pipeline_id)
+            .and_then(|change| change.new_browsing_context_info.as_ref())
+            .and_then(|info| info.parent_pipeline_id)
+    }
+
     // The script thread associated with pipeline_id has loaded a URL in an
     // iframe via script. This will result in a new pipeline being spawned and
     // a child being added to the parent browsing context. This message is never
@@ -2359,9 +3062,13 @@ where
         // TODO: Referrer?
         let load_data = LoadData::new(url.clone(), None, None, None);
 
-        let (script_sender, opener_browsing_context_id) =
+        let (script_sender, opener_browsing_context_id, opener_top_level_id) =
             match self.pipelines.get(&opener_pipeline_id) {
-                Some(pipeline) => (pipeline.event_loop.clone(), pipeline.browsing_context_id),
+                Some(pipeline) => (
+                    pipeline.event_loop.clone(),
+                    pipeline.browsing_context_id,
+                    pipeline.top_level_browsing_context_id,
+                ),
                 None => {
                     return warn!(
                         "Auxiliary loaded url in closed iframe {}.",
@@ -2392,6 +3099,12 @@ where
             load_data,
         );
 
+        // Auxiliary browsers stay in the ephemeral session of their opener.
+        let ephemeral_session = self
+            .browsers
+            .get(&opener_top_level_id)
+            .and_then(|browser| browser.ephemeral_session.clone());
+
         assert!(!self.pipelines.contains_key(&new_pipeline_id));
         self.pipelines.insert(new_pipeline_id, pipeline);
         self.browsers.insert(
@@ -2399,6 +3112,7 @@ where
             Browser {
                 focused_browsing_context_id: new_browsing_context_id,
                 session_history: JointSessionHistory::new(),
+                ephemeral_session,
             },
         );
 
@@ -2560,6 +3274,13 @@ where
                 },
             };
 
+        self.webdriver.send_browsing_context_event(
+            WebDriverBrowsingContextEvent::NavigationStarted(
+                browsing_context_id,
+                load_data.url.clone(),
+            ),
+        );
+
         match parent_pipeline_id {
             Some(parent_pipeline_id) => {
                 // Find the script thread for the pipeline containing the iframe
@@ -2689,6 +3410,11 @@ where
         if let Some(pipeline) = self.pipelines.get_mut(&pipeline_id) {
             debug!("marking pipeline {:?} as loaded", pipeline_id);
             pipeline.completely_loaded = true;
+            self.webdriver
+                .send_browsing_context_event(WebDriverBrowsingContextEvent::Load(
+                    pipeline.browsing_context_id,
+                    pipeline.url.clone(),
+                ));
         }
 
         // Notify the embedder that the TopLevelBrowsingContext current document
@@ -3175,7 +3901,7 @@ where
         browsing_context_id: BrowsingContextId,
         source_pipeline: PipelineId,
         origin: Option<ImmutableOrigin>,
-        data: Vec<u8>,
+        data: StructuredSerializedData,
     ) {
         let pipeline_id = match self.browsing_contexts.get(&browsing_context_id) {
             None => {
@@ -3463,6 +4189,93 @@ where
                 self.compositor_proxy
                     .send(ToCompositorMsg::CreatePng(reply));
             },
+            WebDriverCommandMsg::MouseButtonAction(mouse_event_type, mouse_button, x, y) => {
+                self.compositor_proxy
+                    .send(ToCompositorMsg::WebDriverMouseButtonEvent(
+                        mouse_event_type,
+                        mouse_button,
+                        x,
+                        y,
+                    ));
+            },
+            WebDriverCommandMsg::MouseMoveAction(x, y) => {
+                self.compositor_proxy
+                    .send(ToCompositorMsg::WebDriverMouseMoveEvent(x, y));
+            },
+            WebDriverCommandMsg::WheelScrollAction(delta, x, y) => {
+                self.compositor_proxy
+                    .send(ToCompositorMsg::WebDriverWheelEvent(delta, x, y));
+            },
+            WebDriverCommandMsg::GetUserPrompt(top_level_browsing_context_id, reply) => {
+                let prompt = self
+                    .webdriver
+                    .user_prompts
+                    .get(&top_level_browsing_context_id)
+                    .map(|prompt| (prompt.prompt_type(), prompt.message()));
+                let _ = reply.send(prompt);
+            },
+            WebDriverCommandMsg::HandleUserPrompt(top_level_browsing_context_id, action, reply) => {
+                let result = match self
+                    .webdriver
+                    .user_prompts
+                    .remove(&top_level_browsing_context_id)
+                {
+                    Some(prompt) => {
+                        prompt.handle(action);
+                        Ok(())
+                    },
+                    None => Err(WebDriverUserPromptError::NoSuchAlert),
+                };
+                let _ = reply.send(result);
+            },
+            WebDriverCommandMsg::SendUserPromptText(top_level_browsing_context_id, text, reply) => {
+                let result = match self
+                    .webdriver
+                    .user_prompts
+                    .get_mut(&top_level_browsing_context_id)
+                {
+                    Some(prompt) => prompt.set_text(text),
+                    None => Err(WebDriverUserPromptError::NoSuchAlert),
+                };
+                let _ = reply.send(result);
+            },
+            WebDriverCommandMsg::GetTopLevelBrowsingContexts(reply) => {
+                let _ = reply.send(self.browsers.keys().cloned().collect());
+            },
+            WebDriverCommandMsg::NewWindow(reply) => {
+                let top_level_browsing_context_id = TopLevelBrowsingContextId::new();
+                let url = ServoUrl::parse("about:blank").expect("infallible");
+                self.handle_new_top_level_browsing_context(
+                    url,
+                    top_level_browsing_context_id,
+                    None,
+                );
+                let _ = reply.send(top_level_browsing_context_id);
+            },
+            WebDriverCommandMsg::CloseWindow(top_level_browsing_context_id, reply) => {
+                self.handle_close_top_level_browsing_context(top_level_browsing_context_id);
+                // Let the embedder forget about the browser, as if script had closed it.
+                self.embedder_proxy.send((
+                    Some(top_level_browsing_context_id),
+                    EmbedderMsg::CloseBrowser,
+                ));
+                let _ = reply.send(());
+            },
+            WebDriverCommandMsg::SubscribeToBrowsingContextEvents(event_channel) => {
+                self.webdriver.event_channel = Some(event_channel);
+            },
+            WebDriverCommandMsg::SetWindowState(top_level_browsing_context_id, state, reply) => {
+                self.webdriver.resize_channel = Some(reply);
+                let msgs = vec![
+                    EmbedderMsg::SetMinimizedState(state == WebDriverWindowState::Minimized),
+                    EmbedderMsg::SetMaximizedState(state == WebDriverWindowState::Maximized),
+                    EmbedderMsg::SetFullscreenState(state == WebDriverWindowState::Fullscreen),
+                ];
+                for msg in msgs {
+                    self.embedder_proxy
+                        .send((Some(top_level_browsing_context_id), msg));
+                }
+            },
         }
     }
 
@@ -4397,6 +5210,7 @@ where
             .or_insert_with(|| Browser {
                 focused_browsing_context_id: BrowsingContextId::from(top_level_id),
                 session_history: JointSessionHistory::new(),
+                ephemeral_session: None,
             })
             .session_history
     }
@@ -4,47 +4,86 @@
 
 //! The listener that encapsulates all state for an in-progress document request.
 //! Any redirects that are encountered are followed. Whenever a non-redirect
-//! response is received, it is forwarded to the appropriate script thread.
+//! response is received, it is forwarded to the appropriate script thread,
+//! unless it is to be downloaded rather than displayed, or its
+//! `frame-ancestors` policy forbids it from being embedded.
 
 use crossbeam_channel::Sender;
 use http::header::LOCATION;
+use http::Method;
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use msg::constellation_msg::PipelineId;
+use net::download_manager::is_download;
 use net::http_loader::{set_default_accept, set_default_accept_language};
-use net_traits::request::{Destination, Referrer, RequestBuilder};
+use net_traits::csp::{CheckResult, CspList, Violation};
+use net_traits::request::{Referrer, RequestBuilder};
 use net_traits::response::ResponseInit;
 use net_traits::{CoreResourceMsg, FetchChannels, FetchMetadata, FetchResponseMsg};
-use net_traits::{IpcSend, NetworkError, ResourceThreads};
+use net_traits::{IpcSend, Metadata, NetworkError, ResourceThreads};
+use servo_url::ServoUrl;
+
+/// A message from a network listener to the constellation.
+#[derive(Debug)]
+pub enum NetworkListenerMsg {
+    /// A message for the script thread of the pipeline being navigated.
+    Fetch(FetchResponseMsg),
+    /// The response is being downloaded, so the navigation is to be abandoned.
+    Download,
+    /// Violations of the policies checked during the navigation, to be reported
+    /// to the document containing the browsing context being navigated.
+    CspViolations(Vec<Violation>),
+}
 
 pub struct NetworkListener {
     res_init: Option<ResponseInit>,
     request_builder: RequestBuilder,
     pipeline_id: PipelineId,
+    /// The URLs of the documents of the ancestors of the browsing context being navigated,
+    /// checked against the `frame-ancestors` directive of the response.
+    ancestor_urls: Vec<ServoUrl>,
     resource_threads: ResourceThreads,
-    sender: Sender<(PipelineId, FetchResponseMsg)>,
+    sender: Sender<(PipelineId, NetworkListenerMsg)>,
     should_send: bool,
+    /// Cancels the fetch in progress, when its response is handed over to the
+    /// download manager.
+    cancel_sender: Option<ipc::IpcSender<()>>,
 }
 
 impl NetworkListener {
     pub fn new(
         request_builder: RequestBuilder,
         pipeline_id: PipelineId,
+        ancestor_urls: Vec<ServoUrl>,
         resource_threads: ResourceThreads,
-        sender: Sender<(PipelineId, FetchResponseMsg)>,
+        sender: Sender<(PipelineId, NetworkListenerMsg)>,
     ) -> NetworkListener {
         NetworkListener {
             res_init: None,
             request_builder,
             pipeline_id,
+            ancestor_urls,
             resource_threads,
             sender,
             should_send: false,
+            cancel_sender: None,
         }
     }
 
     pub fn initiate_fetch(&self, cancel_chan: Option<ipc::IpcReceiver<()>>) {
         let (ipc_sender, ipc_receiver) = ipc::channel().expect("Failed to create IPC channel!");
+        let (cancel_sender, cancel_receiver) =
+            ipc::channel().expect("Failed to create IPC channel!");
+        // The fetch is cancelled by the navigating document, or by the listener itself.
+        if let Some(cancel_chan) = cancel_chan {
+            let cancel_sender = cancel_sender.clone();
+            ROUTER.add_route(
+                cancel_chan.to_opaque(),
+                Box::new(move |_| {
+                    let _ = cancel_sender.send(());
+                }),
+            );
+        }
 
         let mut listener = NetworkListener {
             res_init: self.res_init.clone(),
@@ -52,7 +91,9 @@ impl NetworkListener {
             resource_threads: self.resource_threads.clone(),
             sender: self.sender.clone(),
             pipeline_id: self.pipeline_id.clone(),
+            ancestor_urls: self.ancestor_urls.clone(),
             should_send: false,
+            cancel_sender: Some(cancel_sender),
         };
 
         let msg = match self.res_init {
@@ -60,15 +101,18 @@ impl NetworkListener {
                 self.request_builder.clone(),
                 res_init_.clone(),
                 ipc_sender,
-                None,
+                Some(cancel_receiver),
             ),
             None => {
-                set_default_accept(Destination::Document, &mut listener.request_builder.headers);
+                set_default_accept(
+                    listener.request_builder.destination,
+                    &mut listener.request_builder.headers,
+                );
                 set_default_accept_language(&mut listener.request_builder.headers);
 
                 CoreResourceMsg::Fetch(
                     listener.request_builder.clone(),
-                    FetchChannels::ResponseMsg(ipc_sender, cancel_chan),
+                    FetchChannels::ResponseMsg(ipc_sender, Some(cancel_receiver)),
                 )
             },
         };
@@ -79,6 +123,9 @@ impl NetworkListener {
                 let msg = message.to();
                 match msg {
                     Ok(FetchResponseMsg::ProcessResponse(res)) => listener.check_redirect(res),
+                    Ok(FetchResponseMsg::ProcessCspViolations(violations)) => {
+                        listener.report_csp_violations(violations)
+                    },
                     Ok(msg_) => listener.send(msg_),
                     Err(e) => warn!("Error while receiving network listener message: {}", e),
                 };
@@ -133,9 +180,46 @@ impl NetworkListener {
                         // Ideally the Fetch code would handle manual redirects on its own
                         self.initiate_fetch(None);
                     },
+                    // The download manager fetches the resource again, which would
+                    // submit any request but a GET twice, so the responses of other
+                    // requests are displayed instead.
+                    _ if is_download(metadata) && self.request_builder.method == Method::GET => {
+                        if let Some(ref cancel_sender) = self.cancel_sender {
+                            let _ = cancel_sender.send(());
+                        }
+                        let mut request_builder = self.request_builder.clone();
+                        request_builder.url = metadata.final_url.clone();
+                        request_builder.url_list.clear();
+                        let msg = CoreResourceMsg::Download(request_builder, None);
+                        if let Err(e) = self.resource_threads.sender().send(msg) {
+                            warn!("Resource thread unavailable ({})", e);
+                        }
+                        if let Err(e) = self
+                            .sender
+                            .send((self.pipeline_id, NetworkListenerMsg::Download))
+                        {
+                            warn!(
+                                "Failed to abandon navigation of pipeline {}: {:?}",
+                                self.pipeline_id, e
+                            );
+                        }
+                    },
                     _ => {
                         // Response should be processed by script thread.
                         self.should_send = true;
+                        let (result, violations) = self.check_frame_ancestors(metadata);
+                        if result == CheckResult::Blocked {
+                            let error = NetworkError::Internal(
+                                "Blocked by Content Security Policy (frame-ancestors)".into(),
+                            );
+                            self.report_csp_violations(violations);
+                            self.send(FetchResponseMsg::ProcessResponse(Err(error.clone())));
+                            self.send(FetchResponseMsg::ProcessResponseEOF(Err(error)));
+                            // The body of the blocked response is never shown.
+                            self.should_send = false;
+                            return;
+                        }
+                        self.report_csp_violations(violations);
                         self.send(FetchResponseMsg::ProcessResponse(Ok(res_metadata)));
                     },
                 };
@@ -147,9 +231,37 @@ impl NetworkListener {
         };
     }
 
+    /// <https://w3c.github.io/webappsec-csp/#should-block-navigation-response>
+    fn check_frame_ancestors(&self, metadata: &Metadata) -> (CheckResult, Vec<Violation>) {
+        let headers = match metadata.headers {
+            Some(ref headers) if !self.ancestor_urls.is_empty() => headers,
+            _ => return (CheckResult::Allowed, vec![]),
+        };
+        CspList::parse_response_headers(headers)
+            .should_navigation_response_be_blocked(&metadata.final_url, &self.ancestor_urls)
+    }
+
+    fn report_csp_violations(&self, violations: Vec<Violation>) {
+        if violations.is_empty() {
+            return;
+        }
+        if let Err(e) = self.sender.send((
+            self.pipeline_id,
+            NetworkListenerMsg::CspViolations(violations),
+        )) {
+            warn!(
+                "Failed to report CSP violations of pipeline {}: {:?}",
+                self.pipeline_id, e
+            );
+        }
+    }
+
     fn send(&mut self, msg: FetchResponseMsg) {
         if self.should_send {
-            if let Err(e) = self.sender.send((self.pipeline_id, msg)) {
+            if let Err(e) = self
+                .sender
+                .send((self.pipeline_id, NetworkListenerMsg::Fetch(msg)))
+            {
                 warn!(
                     "Failed to forward network message to pipeline {}: {:?}",
                     self.pipeline_id, e
@@ -140,6 +140,15 @@ impl ActorRegistry {
         actors.push(actor);
     }
 
+    /// Register the actors added with `register_later`, which happens after each message
+    /// is handled.
+    pub fn register_pending(&mut self) {
+        let new_actors = replace(&mut *self.new_actors.borrow_mut(), vec![]);
+        for actor in new_actors.into_iter() {
+            self.actors.insert(actor.name().to_owned(), actor);
+        }
+    }
+
     /// Find an actor by registered name
     pub fn find<'a, T: Any>(&'a self, name: &str) -> &'a T {
         let actor = self.actors.get(name).unwrap();
@@ -175,10 +184,7 @@ impl ActorRegistry {
                 }
             },
         }
-        let new_actors = replace(&mut *self.new_actors.borrow_mut(), vec![]);
-        for actor in new_actors.into_iter() {
-            self.actors.insert(actor.name().to_owned(), actor);
-        }
+        self.register_pending();
 
         let old_actors = replace(&mut *self.old_actors.borrow_mut(), vec![]);
         for name in old_actors {
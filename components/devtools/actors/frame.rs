@@ -0,0 +1,185 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Liberally derived from the [Firefox JS implementation]
+//! (https://searchfox.org/mozilla-central/source/devtools/server/actors/frame.js).
+//! A frame of the stack of a paused page, which lives until the page resumes.
+
+use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+use crate::actors::object::ObjectActor;
+use crate::actors::source::{SourceActor, SourceForm};
+use crate::protocol::JsonPacketStream;
+use devtools_traits::{DevtoolScriptControlMsg, EnvironmentInfo, FrameInfo};
+use ipc_channel::ipc::{self, IpcSender};
+use msg::constellation_msg::PipelineId;
+use serde_json::{self, Map, Value};
+use std::net::TcpStream;
+
+#[derive(Serialize)]
+struct WhereMsg {
+    actor: Option<String>,
+    url: Option<String>,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Serialize)]
+pub struct FrameForm {
+    actor: String,
+    depth: u32,
+    #[serde(rename = "type")]
+    type_: String,
+    displayName: Option<String>,
+    this: Value,
+    #[serde(rename = "where")]
+    where_: WhereMsg,
+}
+
+#[derive(Serialize)]
+struct FunctionMsg {
+    displayName: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BindingsMsg {
+    arguments: Vec<Value>,
+    variables: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct EnvironmentForm {
+    #[serde(rename = "type")]
+    type_: String,
+    parent: Option<Box<EnvironmentForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<FunctionMsg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bindings: Option<BindingsMsg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object: Option<Value>,
+}
+
+pub struct FrameActor {
+    name: String,
+    depth: u32,
+    pipeline: PipelineId,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+}
+
+impl Actor for FrameActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(
+        &self,
+        registry: &ActorRegistry,
+        msg_type: &str,
+        _msg: &Map<String, Value>,
+        stream: &mut TcpStream,
+    ) -> Result<ActorMessageStatus, ()> {
+        Ok(match msg_type {
+            "getEnvironment" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetEnvironments(
+                        self.pipeline,
+                        self.depth,
+                        tx,
+                    ))
+                    .unwrap();
+                let environments = rx.recv().unwrap_or_default();
+                // The scope chain comes innermost first, and each scope links to its parent.
+                let environment = environments.into_iter().rev().fold(None, |parent, env| {
+                    Some(Box::new(self.environment_form(registry, env, parent)))
+                });
+                let mut msg = match environment {
+                    Some(environment) => serde_json::to_value(environment).unwrap(),
+                    None => Value::Object(Map::new()),
+                };
+                msg.as_object_mut()
+                    .unwrap()
+                    .insert("from".to_owned(), Value::String(self.name()));
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            _ => ActorMessageStatus::Ignored,
+        })
+    }
+}
+
+impl FrameActor {
+    /// The form of the given frame, with a new actor for it.
+    pub fn form(
+        registry: &ActorRegistry,
+        frame: FrameInfo,
+        pipeline: PipelineId,
+        script_chan: &IpcSender<DevtoolScriptControlMsg>,
+    ) -> FrameForm {
+        let name = registry.new_name("frame");
+        registry.register_later(Box::new(FrameActor {
+            name: name.clone(),
+            depth: frame.depth,
+            pipeline,
+            script_chan: script_chan.clone(),
+        }));
+        let (source, url) = match frame.source {
+            Some(source) => {
+                let url = source.url.clone();
+                let SourceForm { actor, .. } =
+                    SourceActor::form(registry, source, pipeline, script_chan);
+                (Some(actor), Some(url))
+            },
+            None => (None, None),
+        };
+        FrameForm {
+            actor: name,
+            depth: frame.depth,
+            type_: frame.type_,
+            displayName: frame.callee,
+            this: ObjectActor::encode_value(registry, frame.this, pipeline, script_chan),
+            where_: WhereMsg {
+                actor: source,
+                url,
+                line: frame.line,
+                column: frame.column,
+            },
+        }
+    }
+
+    fn environment_form(
+        &self,
+        registry: &ActorRegistry,
+        env: EnvironmentInfo,
+        parent: Option<Box<EnvironmentForm>>,
+    ) -> EnvironmentForm {
+        let encode =
+            |value| ObjectActor::encode_value(registry, value, self.pipeline, &self.script_chan);
+        let bindings = match env.object {
+            Some(_) => None,
+            None => {
+                let mut variables = Map::new();
+                for (name, value) in env.bindings {
+                    let mut binding = Map::new();
+                    binding.insert("value".to_owned(), encode(value));
+                    variables.insert(name, Value::Object(binding));
+                }
+                Some(BindingsMsg {
+                    arguments: vec![],
+                    variables,
+                })
+            },
+        };
+        EnvironmentForm {
+            function: env.callee.map(|name| FunctionMsg {
+                displayName: Some(name),
+            }),
+            type_: env.type_,
+            parent,
+            bindings,
+            object: env.object.map(encode),
+        }
+    }
+}
@@ -6,21 +6,31 @@
 //! (http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/inspector.js).
 
 use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+use crate::actors::stylesheets::{StyleSheetForm, StyleSheetsActor};
 use crate::protocol::JsonPacketStream;
 use devtools_traits::DevtoolScriptControlMsg::{GetChildren, GetDocumentElement, GetRootNode};
 use devtools_traits::DevtoolScriptControlMsg::{GetLayout, ModifyAttribute};
-use devtools_traits::{ComputedNodeLayout, DevtoolScriptControlMsg, NodeInfo};
+use devtools_traits::{AppliedRuleId, AppliedRuleInfo, ComputedNodeLayout};
+use devtools_traits::{DevtoolScriptControlMsg, DomMutation, NodeInfo, RuleModification};
 use ipc_channel::ipc::{self, IpcSender};
 use msg::constellation_msg::PipelineId;
 use serde_json::{self, Map, Value};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
 use std::net::TcpStream;
 
+/// The `type` of the rules standing for the style attribute of an element.
+const ELEMENT_STYLE: u32 = 100;
+/// The `type` of `CSSStyleRule`s.
+const STYLE_RULE: u32 = 1;
+
 pub struct InspectorActor {
     pub name: String,
     pub walker: RefCell<Option<String>>,
     pub pageStyle: RefCell<Option<String>>,
     pub highlighter: RefCell<Option<String>>,
+    pub styleSheets: String,
     pub script_chan: IpcSender<DevtoolScriptControlMsg>,
     pub pipeline: PipelineId,
 }
@@ -91,6 +101,11 @@ struct ModifyAttributeReply {
     from: String,
 }
 
+#[derive(Serialize)]
+struct SetNodeValueReply {
+    from: String,
+}
+
 impl Actor for NodeActor {
     fn name(&self) -> String {
         self.name.clone()
@@ -126,6 +141,20 @@ impl Actor for NodeActor {
                 ActorMessageStatus::Processed
             },
 
+            "setNodeValue" => {
+                let value = msg.get("value").unwrap().as_str().unwrap().to_owned();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::SetNodeValue(
+                        self.pipeline,
+                        registry.actor_to_script(self.name()),
+                        value,
+                    ))
+                    .unwrap();
+                let reply = SetNodeValueReply { from: self.name() };
+                stream.write_json_packet(&reply);
+                ActorMessageStatus::Processed
+            },
+
             _ => ActorMessageStatus::Ignored,
         })
     }
@@ -247,10 +276,70 @@ impl NodeInfoToProtocol for NodeInfo {
     }
 }
 
-struct WalkerActor {
+pub struct WalkerActor {
     name: String,
     script_chan: IpcSender<DevtoolScriptControlMsg>,
     pipeline: PipelineId,
+    /// The clients of this walker, which are told when there are new mutations.
+    streams: RefCell<Vec<TcpStream>>,
+    /// The mutations of the document the clients did not get yet.
+    mutations: RefCell<Vec<MutationMsg>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum MutationMsg {
+    #[serde(rename = "attributes")]
+    Attributes {
+        target: String,
+        attributeName: String,
+        attributeNamespace: String,
+        newValue: Option<String>,
+    },
+    #[serde(rename = "characterData")]
+    CharacterData { target: String, newValue: String },
+    #[serde(rename = "childList")]
+    ChildList {
+        target: String,
+        added: Vec<NodeActorMsg>,
+        removed: Vec<String>,
+        numChildren: usize,
+    },
+}
+
+#[derive(Serialize)]
+struct NewMutationsMsg {
+    from: String,
+    #[serde(rename = "type")]
+    type_: String,
+}
+
+#[derive(Serialize)]
+struct GetMutationsReply {
+    from: String,
+    mutations: Vec<MutationMsg>,
+}
+
+#[derive(Serialize)]
+struct InsertBeforeReply {
+    from: String,
+}
+
+#[derive(Serialize)]
+struct RemoveNodeReply {
+    from: String,
+    nextSibling: Option<NodeActorMsg>,
+}
+
+#[derive(Serialize)]
+struct OuterHTMLReply {
+    from: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct SetOuterHTMLReply {
+    from: String,
 }
 
 #[derive(Serialize)]
@@ -346,11 +435,172 @@ impl Actor for WalkerActor {
                 ActorMessageStatus::Processed
             },
 
+            "insertBefore" => {
+                let node = msg.get("node").unwrap().as_str().unwrap();
+                let parent = msg.get("parent").unwrap().as_str().unwrap();
+                let sibling = msg
+                    .get("sibling")
+                    .and_then(|sibling| sibling.as_str())
+                    .map(|sibling| registry.actor_to_script(sibling.to_owned()));
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::InsertBefore(
+                        self.pipeline,
+                        registry.actor_to_script(node.to_owned()),
+                        registry.actor_to_script(parent.to_owned()),
+                        sibling,
+                    ))
+                    .unwrap();
+                let msg = InsertBeforeReply { from: self.name() };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "removeNode" => {
+                let node = msg.get("node").unwrap().as_str().unwrap();
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::RemoveNode(
+                        self.pipeline,
+                        registry.actor_to_script(node.to_owned()),
+                        tx,
+                    ))
+                    .unwrap();
+                let next_sibling = rx.recv().unwrap();
+                let msg = RemoveNodeReply {
+                    from: self.name(),
+                    nextSibling: next_sibling.map(|sibling| {
+                        sibling.encode(registry, true, self.script_chan.clone(), self.pipeline)
+                    }),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "outerHTML" => {
+                let node = msg.get("node").unwrap().as_str().unwrap();
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetOuterHTML(
+                        self.pipeline,
+                        registry.actor_to_script(node.to_owned()),
+                        tx,
+                    ))
+                    .unwrap();
+                let msg = OuterHTMLReply {
+                    from: self.name(),
+                    value: rx.recv().unwrap().ok_or(())?,
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "setOuterHTML" => {
+                let node = msg.get("node").unwrap().as_str().unwrap();
+                let value = msg.get("value").unwrap().as_str().unwrap().to_owned();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::SetOuterHTML(
+                        self.pipeline,
+                        registry.actor_to_script(node.to_owned()),
+                        value,
+                    ))
+                    .unwrap();
+                let msg = SetOuterHTMLReply { from: self.name() };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "getMutations" => {
+                let msg = GetMutationsReply {
+                    from: self.name(),
+                    mutations: mem::replace(&mut *self.mutations.borrow_mut(), vec![]),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
             _ => ActorMessageStatus::Ignored,
         })
     }
 }
 
+impl WalkerActor {
+    /// Keep the given mutations for the clients to get, telling them there are new ones
+    /// if they got all the previous ones. The mutations of the nodes the clients do not
+    /// know of are dropped.
+    pub fn add_mutations(&self, registry: &ActorRegistry, mutations: Vec<DomMutation>) {
+        let mutations: Vec<_> = mutations
+            .into_iter()
+            .filter_map(|mutation| self.encode_mutation(registry, mutation))
+            .collect();
+        if mutations.is_empty() {
+            return;
+        }
+        let mut pending = self.mutations.borrow_mut();
+        if pending.is_empty() {
+            let msg = NewMutationsMsg {
+                from: self.name(),
+                type_: "newMutations".to_owned(),
+            };
+            for stream in &mut *self.streams.borrow_mut() {
+                stream.write_json_packet(&msg);
+            }
+        }
+        pending.extend(mutations);
+    }
+
+    fn encode_mutation(
+        &self,
+        registry: &ActorRegistry,
+        mutation: DomMutation,
+    ) -> Option<MutationMsg> {
+        Some(match mutation {
+            DomMutation::Attribute {
+                target,
+                name,
+                namespace,
+                new_value,
+            } => MutationMsg::Attributes {
+                target: self.known_actor(registry, target)?,
+                attributeName: name,
+                attributeNamespace: namespace,
+                newValue: new_value,
+            },
+            DomMutation::CharacterData { target, new_value } => MutationMsg::CharacterData {
+                target: self.known_actor(registry, target)?,
+                newValue: new_value,
+            },
+            DomMutation::ChildList {
+                target,
+                added,
+                removed,
+                num_children,
+            } => MutationMsg::ChildList {
+                target: self.known_actor(registry, target)?,
+                added: added
+                    .into_iter()
+                    .map(|node| {
+                        node.encode(registry, true, self.script_chan.clone(), self.pipeline)
+                    })
+                    .collect(),
+                removed: removed
+                    .into_iter()
+                    .filter_map(|node| self.known_actor(registry, node))
+                    .collect(),
+                numChildren: num_children,
+            },
+        })
+    }
+
+    /// The actor of the node with the given id, if the clients know of it.
+    fn known_actor(&self, registry: &ActorRegistry, node_id: String) -> Option<String> {
+        if registry.script_actor_registered(node_id.clone()) {
+            Some(registry.script_to_actor(node_id))
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct GetPageStyleReply {
     from: String,
@@ -366,13 +616,17 @@ struct PageStyleActor {
     name: String,
     script_chan: IpcSender<DevtoolScriptControlMsg>,
     pipeline: PipelineId,
+    /// The actor listing the stylesheets the rules are in.
+    style_sheets: String,
+    /// The actors of the rules applied so far, by rule id.
+    rules: RefCell<HashMap<AppliedRuleId, String>>,
 }
 
 #[derive(Serialize)]
 struct GetAppliedReply {
     entries: Vec<AppliedEntry>,
     rules: Vec<AppliedRule>,
-    sheets: Vec<AppliedSheet>,
+    sheets: Vec<StyleSheetForm>,
     from: String,
 }
 
@@ -394,24 +648,14 @@ struct AppliedEntry {
 struct AppliedRule {
     actor: String,
     #[serde(rename = "type")]
-    type_: String,
-    href: String,
+    type_: u32,
+    href: Option<String>,
+    selectors: Vec<String>,
     cssText: String,
+    authoredText: String,
     line: u32,
     column: u32,
-    parentStyleSheet: String,
-}
-
-#[derive(Serialize)]
-struct AppliedSheet {
-    actor: String,
-    href: String,
-    nodeHref: String,
-    disabled: bool,
-    title: String,
-    system: bool,
-    styleSheetIndex: isize,
-    ruleCount: usize,
+    parentStyleSheet: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -472,12 +716,48 @@ impl Actor for PageStyleActor {
         stream: &mut TcpStream,
     ) -> Result<ActorMessageStatus, ()> {
         Ok(match msg_type {
+            // TODO: the rules inherited from the ancestors of the node, when asked for.
             "getApplied" => {
-                //TODO: query script for relevant applied styles to node (msg.node)
+                let target = msg.get("node").unwrap().as_str().unwrap();
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetAppliedRules(
+                        self.pipeline,
+                        registry.actor_to_script(target.to_owned()),
+                        tx,
+                    ))
+                    .unwrap();
+                let applied = rx.recv().unwrap().ok_or(())?;
+
+                let style_sheets = registry.find::<StyleSheetsActor>(&self.style_sheets);
+                let mut entries = vec![];
+                let mut rules = vec![];
+                let mut sheets: Vec<StyleSheetForm> = vec![];
+                for info in applied {
+                    let sheet = info
+                        .sheet
+                        .clone()
+                        .map(|sheet| style_sheets.form(registry, sheet));
+                    let parent_style_sheet = sheet.as_ref().map(|sheet| sheet.actor.clone());
+                    let actor = self.rule_actor(registry, &info, parent_style_sheet.clone());
+                    entries.push(AppliedEntry {
+                        rule: actor.clone(),
+                        pseudoElement: Value::Null,
+                        isSystem: false,
+                        matchedSelectors: info.matched_selectors.clone(),
+                    });
+                    rules.push(StyleRuleActor::encode(actor, &info, parent_style_sheet));
+                    if let Some(sheet) = sheet {
+                        if sheets.iter().all(|known| known.actor != sheet.actor) {
+                            sheets.push(sheet);
+                        }
+                    }
+                }
+
                 let msg = GetAppliedReply {
-                    entries: vec![],
-                    rules: vec![],
-                    sheets: vec![],
+                    entries,
+                    rules,
+                    sheets,
                     from: self.name(),
                 };
                 stream.write_json_packet(&msg);
@@ -584,6 +864,145 @@ impl Actor for PageStyleActor {
     }
 }
 
+impl PageStyleActor {
+    /// The name of the actor of the given rule, created if needed, and told of the rule as
+    /// it now is otherwise.
+    fn rule_actor(
+        &self,
+        registry: &ActorRegistry,
+        info: &AppliedRuleInfo,
+        parent_style_sheet: Option<String>,
+    ) -> String {
+        let mut rules = self.rules.borrow_mut();
+        if let Some(name) = rules.get(&info.id) {
+            *registry.find::<StyleRuleActor>(name).info.borrow_mut() = info.clone();
+            return name.clone();
+        }
+        let name = registry.new_name("stylerule");
+        registry.register_later(Box::new(StyleRuleActor {
+            name: name.clone(),
+            info: RefCell::new(info.clone()),
+            parent_style_sheet,
+            script_chan: self.script_chan.clone(),
+            pipeline: self.pipeline,
+        }));
+        rules.insert(info.id.clone(), name.clone());
+        name
+    }
+}
+
+/// A rule applied to a node, whose declarations can be edited.
+struct StyleRuleActor {
+    name: String,
+    info: RefCell<AppliedRuleInfo>,
+    parent_style_sheet: Option<String>,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+    pipeline: PipelineId,
+}
+
+#[derive(Serialize)]
+struct ModifyRuleReply {
+    from: String,
+    rule: AppliedRule,
+}
+
+impl Actor for StyleRuleActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(
+        &self,
+        _registry: &ActorRegistry,
+        msg_type: &str,
+        msg: &Map<String, Value>,
+        stream: &mut TcpStream,
+    ) -> Result<ActorMessageStatus, ()> {
+        let modifications = match msg_type {
+            "modifyProperties" => msg
+                .get("modifications")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|modification| {
+                    let name = modification.get("name")?.as_str()?.to_owned();
+                    match modification.get("type")?.as_str()? {
+                        "set" => Some(RuleModification::SetProperty {
+                            name,
+                            value: modification.get("value")?.as_str()?.to_owned(),
+                            priority: modification
+                                .get("priority")
+                                .and_then(|priority| priority.as_str())
+                                .unwrap_or("")
+                                .to_owned(),
+                        }),
+                        "remove" => Some(RuleModification::RemoveProperty(name)),
+                        _ => None,
+                    }
+                })
+                .collect(),
+            "setRuleText" => {
+                let text = msg.get("newText").unwrap().as_str().unwrap().to_owned();
+                vec![RuleModification::SetText(text)]
+            },
+            _ => return Ok(ActorMessageStatus::Ignored),
+        };
+
+        let (tx, rx) = ipc::channel().unwrap();
+        self.script_chan
+            .send(DevtoolScriptControlMsg::ModifyRule(
+                self.pipeline,
+                self.info.borrow().id.clone(),
+                modifications,
+                tx,
+            ))
+            .unwrap();
+        let declarations = rx.recv().unwrap().ok_or(())?;
+        self.info.borrow_mut().declarations = declarations;
+
+        let msg = ModifyRuleReply {
+            from: self.name(),
+            rule: StyleRuleActor::encode(
+                self.name(),
+                &self.info.borrow(),
+                self.parent_style_sheet.clone(),
+            ),
+        };
+        stream.write_json_packet(&msg);
+        Ok(ActorMessageStatus::Processed)
+    }
+}
+
+impl StyleRuleActor {
+    fn encode(
+        name: String,
+        info: &AppliedRuleInfo,
+        parent_style_sheet: Option<String>,
+    ) -> AppliedRule {
+        AppliedRule {
+            actor: name,
+            type_: if info.sheet.is_some() {
+                STYLE_RULE
+            } else {
+                ELEMENT_STYLE
+            },
+            href: info.sheet.as_ref().map(|sheet| {
+                sheet
+                    .href
+                    .clone()
+                    .unwrap_or_else(|| sheet.node_href.clone())
+            }),
+            selectors: info.selectors.clone(),
+            cssText: info.declarations.clone(),
+            authoredText: info.declarations.clone(),
+            line: info.line,
+            column: info.column,
+            parentStyleSheet: parent_style_sheet,
+        }
+    }
+}
+
 impl Actor for InspectorActor {
     fn name(&self) -> String {
         self.name.clone()
@@ -603,10 +1022,25 @@ impl Actor for InspectorActor {
                         name: registry.new_name("walker"),
                         script_chan: self.script_chan.clone(),
                         pipeline: self.pipeline,
+                        streams: RefCell::new(vec![stream.try_clone().unwrap()]),
+                        mutations: RefCell::new(vec![]),
                     };
                     let mut walker_name = self.walker.borrow_mut();
                     *walker_name = Some(walker.name());
                     registry.register_later(Box::new(walker));
+                    self.script_chan
+                        .send(DevtoolScriptControlMsg::WantsMutationNotifications(
+                            self.pipeline,
+                            true,
+                        ))
+                        .unwrap();
+                } else {
+                    let walker = self.walker.borrow().clone().unwrap();
+                    registry
+                        .find::<WalkerActor>(&walker)
+                        .streams
+                        .borrow_mut()
+                        .push(stream.try_clone().unwrap());
                 }
 
                 let (tx, rx) = ipc::channel().unwrap();
@@ -635,6 +1069,8 @@ impl Actor for InspectorActor {
                         name: registry.new_name("pageStyle"),
                         script_chan: self.script_chan.clone(),
                         pipeline: self.pipeline,
+                        style_sheets: self.styleSheets.clone(),
+                        rules: RefCell::new(HashMap::new()),
                     };
                     let mut pageStyle = self.pageStyle.borrow_mut();
                     *pageStyle = Some(style.name());
@@ -678,3 +1114,338 @@ impl Actor for InspectorActor {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StyleRuleActor, WalkerActor};
+    use crate::actor::{Actor, ActorRegistry};
+    use crate::protocol::tests::{answer_script_message, message, read_packet, stream_pair};
+    use devtools_traits::{AppliedRuleId, AppliedRuleInfo, DevtoolScriptControlMsg, DomMutation};
+    use devtools_traits::{NodeInfo, RuleModification, StyleSheetInfo};
+    use ipc_channel::ipc::{self, IpcReceiver};
+    use msg::constellation_msg::TEST_PIPELINE_ID;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::net::TcpStream;
+
+    fn walker_actor(
+        streams: Vec<TcpStream>,
+    ) -> (WalkerActor, IpcReceiver<DevtoolScriptControlMsg>) {
+        let (script_chan, script_port) = ipc::channel().unwrap();
+        let actor = WalkerActor {
+            name: "walker".to_owned(),
+            script_chan,
+            pipeline: TEST_PIPELINE_ID,
+            streams: RefCell::new(streams),
+            mutations: RefCell::new(vec![]),
+        };
+        (actor, script_port)
+    }
+
+    /// A registry knowing of the nodes `n1` to `n3`, whose actors are `node1` to `node3`.
+    fn registry_with_nodes() -> ActorRegistry {
+        let registry = ActorRegistry::new();
+        for index in 1..4 {
+            registry.register_script_actor(format!("n{}", index), format!("node{}", index));
+        }
+        registry
+    }
+
+    fn node_info(id: &str, parent: &str) -> NodeInfo {
+        NodeInfo {
+            uniqueId: id.to_owned(),
+            baseURI: "http://example.com/".to_owned(),
+            parent: parent.to_owned(),
+            nodeType: 1,
+            namespaceURI: "http://www.w3.org/1999/xhtml".to_owned(),
+            nodeName: "P".to_owned(),
+            numChildren: 0,
+            name: String::new(),
+            publicId: String::new(),
+            systemId: String::new(),
+            attrs: vec![],
+            isDocumentElement: false,
+            shortValue: String::new(),
+            incompleteValue: false,
+        }
+    }
+
+    #[test]
+    fn insert_before() {
+        let registry = registry_with_nodes();
+        let (actor, script_port) = walker_actor(vec![]);
+        let (mut stream, mut client) = stream_pair();
+
+        let msg = message(json!({ "node": "node1", "parent": "node2", "sibling": "node3" }));
+        actor
+            .handle_message(&registry, "insertBefore", &msg, &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::InsertBefore(pipeline, node, parent, sibling) => {
+                assert_eq!(pipeline, TEST_PIPELINE_ID);
+                assert_eq!(node, "n1");
+                assert_eq!(parent, "n2");
+                assert_eq!(sibling, Some("n3".to_owned()));
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(read_packet(&mut client), json!({ "from": "walker" }));
+
+        let msg = message(json!({ "node": "node1", "parent": "node2" }));
+        actor
+            .handle_message(&registry, "insertBefore", &msg, &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::InsertBefore(_, _, _, None) => {},
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(read_packet(&mut client), json!({ "from": "walker" }));
+    }
+
+    #[test]
+    fn remove_node_replies_with_the_next_sibling() {
+        let registry = registry_with_nodes();
+        let (actor, script_port) = walker_actor(vec![]);
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::RemoveNode(_, node, reply) => {
+                assert_eq!(node, "n1");
+                reply.send(Some(node_info("n4", "n2"))).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({ "node": "node1" }));
+        actor
+            .handle_message(&registry, "removeNode", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(reply["from"], "walker");
+        let sibling = &reply["nextSibling"];
+        assert_eq!(sibling["parent"], "node2");
+        assert_eq!(sibling["nodeName"], "P");
+        assert_eq!(registry.script_to_actor("n4".to_owned()), sibling["actor"]);
+    }
+
+    #[test]
+    fn outer_html() {
+        let registry = registry_with_nodes();
+        let (actor, script_port) = walker_actor(vec![]);
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::GetOuterHTML(_, node, reply) => {
+                assert_eq!(node, "n1");
+                reply.send(Some("<p>a</p>".to_owned())).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({ "node": "node1" }));
+        actor
+            .handle_message(&registry, "outerHTML", &msg, &mut stream)
+            .unwrap();
+        let script_port = script.join().unwrap();
+        assert_eq!(
+            read_packet(&mut client),
+            json!({ "from": "walker", "value": "<p>a</p>" })
+        );
+
+        let msg = message(json!({ "node": "node1", "value": "<p>b</p>" }));
+        actor
+            .handle_message(&registry, "setOuterHTML", &msg, &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::SetOuterHTML(_, node, value) => {
+                assert_eq!(node, "n1");
+                assert_eq!(value, "<p>b</p>");
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(read_packet(&mut client), json!({ "from": "walker" }));
+    }
+
+    #[test]
+    fn mutations_are_kept_until_the_clients_get_them() {
+        let registry = registry_with_nodes();
+        let (mut stream, mut client) = stream_pair();
+        let (actor, _script_port) = walker_actor(vec![stream.try_clone().unwrap()]);
+
+        actor.add_mutations(
+            &registry,
+            vec![
+                DomMutation::Attribute {
+                    target: "n1".to_owned(),
+                    name: "class".to_owned(),
+                    namespace: String::new(),
+                    new_value: Some("a".to_owned()),
+                },
+                DomMutation::CharacterData {
+                    target: "unknown".to_owned(),
+                    new_value: "text".to_owned(),
+                },
+            ],
+        );
+        assert_eq!(
+            read_packet(&mut client),
+            json!({ "from": "walker", "type": "newMutations" })
+        );
+        actor.add_mutations(
+            &registry,
+            vec![DomMutation::ChildList {
+                target: "n2".to_owned(),
+                added: vec![],
+                removed: vec!["n3".to_owned(), "unknown".to_owned()],
+                num_children: 1,
+            }],
+        );
+
+        let msg = message(json!({}));
+        actor
+            .handle_message(&registry, "getMutations", &msg, &mut stream)
+            .unwrap();
+        let reply = read_packet(&mut client);
+        assert_eq!(
+            reply["mutations"],
+            json!([
+                {
+                    "type": "attributes",
+                    "target": "node1",
+                    "attributeName": "class",
+                    "attributeNamespace": "",
+                    "newValue": "a",
+                },
+                {
+                    "type": "childList",
+                    "target": "node2",
+                    "added": [],
+                    "removed": ["node3"],
+                    "numChildren": 1,
+                },
+            ])
+        );
+
+        actor
+            .handle_message(&registry, "getMutations", &msg, &mut stream)
+            .unwrap();
+        assert_eq!(
+            read_packet(&mut client),
+            json!({ "from": "walker", "mutations": [] })
+        );
+    }
+
+    fn style_rule_actor() -> (StyleRuleActor, IpcReceiver<DevtoolScriptControlMsg>) {
+        let (script_chan, script_port) = ipc::channel().unwrap();
+        let info = AppliedRuleInfo {
+            id: AppliedRuleId::StyleSheet("sheet".to_owned(), vec![0]),
+            selectors: vec!["p".to_owned()],
+            matched_selectors: vec!["p".to_owned()],
+            declarations: "color: red;".to_owned(),
+            sheet: Some(StyleSheetInfo {
+                id: "sheet".to_owned(),
+                href: Some("http://example.com/a.css".to_owned()),
+                node_href: "http://example.com/".to_owned(),
+                title: None,
+                disabled: false,
+                index: 0,
+                rule_count: 1,
+            }),
+            line: 1,
+            column: 1,
+        };
+        let actor = StyleRuleActor {
+            name: "stylerule".to_owned(),
+            info: RefCell::new(info),
+            parent_style_sheet: Some("stylesheet".to_owned()),
+            script_chan,
+            pipeline: TEST_PIPELINE_ID,
+        };
+        (actor, script_port)
+    }
+
+    #[test]
+    fn modify_properties() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = style_rule_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::ModifyRule(_, id, modifications, reply) => {
+                assert_eq!(id, AppliedRuleId::StyleSheet("sheet".to_owned(), vec![0]));
+                match &*modifications {
+                    [RuleModification::SetProperty {
+                        name,
+                        value,
+                        priority,
+                    }, RuleModification::RemoveProperty(removed)] => {
+                        assert_eq!(name, "margin");
+                        assert_eq!(value, "0");
+                        assert_eq!(priority, "important");
+                        assert_eq!(removed, "color");
+                    },
+                    modifications => panic!("unexpected modifications {:?}", modifications),
+                }
+                reply
+                    .send(Some("margin: 0 !important;".to_owned()))
+                    .unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({
+            "modifications": [
+                { "type": "set", "name": "margin", "value": "0", "priority": "important" },
+                { "type": "remove", "name": "color" },
+                { "type": "unknown", "name": "padding" },
+            ],
+        }));
+        actor
+            .handle_message(&registry, "modifyProperties", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(reply["from"], "stylerule");
+        assert_eq!(
+            reply["rule"],
+            json!({
+                "actor": "stylerule",
+                "type": 1,
+                "href": "http://example.com/a.css",
+                "selectors": ["p"],
+                "cssText": "margin: 0 !important;",
+                "authoredText": "margin: 0 !important;",
+                "line": 1,
+                "column": 1,
+                "parentStyleSheet": "stylesheet",
+            })
+        );
+        assert_eq!(actor.info.borrow().declarations, "margin: 0 !important;");
+    }
+
+    #[test]
+    fn set_rule_text() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = style_rule_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::ModifyRule(_, _, modifications, reply) => {
+                match &*modifications {
+                    [RuleModification::SetText(text)] => assert_eq!(text, "color: blue"),
+                    modifications => panic!("unexpected modifications {:?}", modifications),
+                }
+                reply.send(Some("color: blue;".to_owned())).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({ "newText": "color: blue" }));
+        actor
+            .handle_message(&registry, "setRuleText", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(reply["rule"]["cssText"], "color: blue;");
+    }
+}
@@ -10,11 +10,15 @@ use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
 use crate::protocol::JsonPacketStream;
 use devtools_traits::HttpRequest as DevtoolsHttpRequest;
 use devtools_traits::HttpResponse as DevtoolsHttpResponse;
+use devtools_traits::HttpResponseEnd as DevtoolsHttpResponseEnd;
+use devtools_traits::SecurityInfo as DevtoolsSecurityInfo;
+use devtools_traits::{CertificateInfo, CertificateName, MixedContentStatus, NetworkTimings};
 use headers::{ContentType, Cookie, HeaderMapExt};
 use http::{header, HeaderMap};
 use hyper::{Method, StatusCode};
 use serde_json::{Map, Value};
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
+use std::str;
 use time::Tm;
 
 struct HttpRequest {
@@ -24,14 +28,22 @@ struct HttpRequest {
     body: Option<Vec<u8>>,
     startedDateTime: Tm,
     timeStamp: i64,
-    connect_time: u64,
-    send_time: u64,
+    timings: NetworkTimings,
+    mixed_content: Option<MixedContentStatus>,
 }
 
 struct HttpResponse {
     headers: Option<HeaderMap>,
     status: Option<(StatusCode, String)>,
+    http_version: String,
+    remote_address: Option<SocketAddr>,
+    security_info: Option<DevtoolsSecurityInfo>,
+    /// The body, once it was received, unless it was too large to be kept.
     body: Option<Vec<u8>>,
+    body_size: usize,
+    transferred_size: usize,
+    /// Whether the body was received.
+    ended: bool,
 }
 
 pub struct NetworkEventActor {
@@ -50,6 +62,7 @@ pub struct EventActor {
     pub timeStamp: i64,
     pub isXHR: bool,
     pub private: bool,
+    pub blockedReason: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -115,10 +128,20 @@ struct GetResponseHeadersReply {
     rawHeaders: String,
 }
 
+#[derive(Serialize)]
+struct ResponseContent {
+    mimeType: String,
+    text: String,
+    size: usize,
+    transferredSize: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+}
+
 #[derive(Serialize)]
 struct GetResponseContentReply {
     from: String,
-    content: Option<Vec<u8>>,
+    content: Option<ResponseContent>,
     contentDiscarded: bool,
 }
 
@@ -143,12 +166,13 @@ struct GetResponseCookiesReply {
 
 #[derive(Serialize)]
 struct Timings {
-    blocked: u32,
-    dns: u32,
+    blocked: u64,
+    dns: u64,
     connect: u64,
+    ssl: u64,
     send: u64,
-    wait: u32,
-    receive: u32,
+    wait: u64,
+    receive: u64,
 }
 
 #[derive(Serialize)]
@@ -161,6 +185,43 @@ struct GetEventTimingsReply {
 #[derive(Serialize)]
 struct SecurityInfo {
     state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    protocolVersion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cipherSuite: Option<String>,
+    /// The certificate of the server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cert: Option<CertificateForm>,
+    /// The certificates from the one of the server to the one closest to a root.
+    certChain: Vec<CertificateForm>,
+}
+
+#[derive(Clone, Serialize)]
+struct CertificateForm {
+    subject: CertificateNameForm,
+    issuer: CertificateNameForm,
+    validity: CertificateValidity,
+    fingerprint: CertificateFingerprint,
+    serialNumber: String,
+}
+
+#[derive(Clone, Serialize)]
+struct CertificateNameForm {
+    commonName: Option<String>,
+    organization: Option<String>,
+    organizationalUnit: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct CertificateValidity {
+    start: String,
+    end: String,
+}
+
+#[derive(Clone, Serialize)]
+struct CertificateFingerprint {
+    sha1: String,
+    sha256: String,
 }
 
 #[derive(Serialize)]
@@ -272,40 +333,59 @@ impl Actor for NetworkEventActor {
                 ActorMessageStatus::Processed
             },
             "getResponseContent" => {
+                let content = self.response.body.as_ref().map(|body| {
+                    // Binary bodies are sent as base64.
+                    let (text, encoding) = match str::from_utf8(body) {
+                        Ok(text) => (text.to_owned(), None),
+                        Err(_) => (base64::encode(body), Some("base64".to_owned())),
+                    };
+                    ResponseContent {
+                        mimeType: self.mime_type(),
+                        text,
+                        size: self.response.body_size,
+                        transferredSize: self.response.transferred_size,
+                        encoding,
+                    }
+                });
                 let msg = GetResponseContentReply {
                     from: self.name(),
-                    content: self.response.body.clone(),
-                    contentDiscarded: self.response.body.is_none(),
+                    contentDiscarded: self.response.ended && content.is_none(),
+                    content,
                 };
                 stream.write_json_packet(&msg);
                 ActorMessageStatus::Processed
             },
             "getEventTimings" => {
-                // TODO: This is a fake timings msg
-                let timingsObj = Timings {
-                    blocked: 0,
-                    dns: 0,
-                    connect: self.request.connect_time,
-                    send: self.request.send_time,
-                    wait: 0,
-                    receive: 0,
-                };
-                let total = timingsObj.connect + timingsObj.send;
-                // TODO: Send the correct values for all these fields.
+                let timings = &self.request.timings;
                 let msg = GetEventTimingsReply {
                     from: self.name(),
-                    timings: timingsObj,
-                    totalTime: total,
+                    timings: Timings {
+                        blocked: timings.blocked,
+                        dns: timings.dns,
+                        connect: timings.connect,
+                        ssl: timings.ssl,
+                        send: timings.send,
+                        wait: timings.wait,
+                        receive: timings.receive,
+                    },
+                    totalTime: self.total_time(),
                 };
                 stream.write_json_packet(&msg);
                 ActorMessageStatus::Processed
             },
             "getSecurityInfo" => {
-                // TODO: Send the correct values for securityInfo.
+                let security_info = self.response.security_info.as_ref();
+                let cert_chain: Vec<_> = security_info.map_or(vec![], |info| {
+                    info.certificates.iter().map(certificate_form).collect()
+                });
                 let msg = GetSecurityInfoReply {
                     from: self.name(),
                     securityInfo: SecurityInfo {
-                        state: "insecure".to_owned(),
+                        state: self.security_state(),
+                        protocolVersion: security_info.map(|info| info.protocol_version.clone()),
+                        cipherSuite: security_info.map(|info| info.cipher_suite.clone()),
+                        cert: cert_chain.first().cloned(),
+                        certChain: cert_chain,
                     },
                 };
                 stream.write_json_packet(&msg);
@@ -327,13 +407,19 @@ impl NetworkEventActor {
                 body: None,
                 startedDateTime: time::now(),
                 timeStamp: time::get_time().sec,
-                send_time: 0,
-                connect_time: 0,
+                timings: NetworkTimings::default(),
+                mixed_content: None,
             },
             response: HttpResponse {
                 headers: None,
                 status: None,
+                http_version: String::new(),
+                remote_address: None,
+                security_info: None,
                 body: None,
+                body_size: 0,
+                transferred_size: 0,
+                ended: false,
             },
             is_xhr: false,
         }
@@ -346,8 +432,8 @@ impl NetworkEventActor {
         self.request.body = request.body;
         self.request.startedDateTime = request.startedDateTime;
         self.request.timeStamp = request.timeStamp;
-        self.request.connect_time = request.connect_time;
-        self.request.send_time = request.send_time;
+        self.request.timings = request.timings;
+        self.request.mixed_content = request.mixed_content;
         self.is_xhr = request.is_xhr;
     }
 
@@ -357,7 +443,17 @@ impl NetworkEventActor {
             let status_text = String::from_utf8_lossy(st).into_owned();
             (StatusCode::from_u16(s).unwrap(), status_text)
         });
+        self.response.http_version = response.http_version;
+        self.response.remote_address = response.remote_address;
+        self.response.security_info = response.security_info;
+    }
+
+    pub fn add_response_end(&mut self, response: DevtoolsHttpResponseEnd) {
         self.response.body = response.body;
+        self.response.body_size = response.body_size;
+        self.response.transferred_size = response.transferred_size;
+        self.response.ended = true;
+        self.request.timings.receive = response.receive_time;
     }
 
     pub fn event_actor(&self) -> EventActor {
@@ -370,11 +466,26 @@ impl NetworkEventActor {
             timeStamp: self.request.timeStamp,
             isXHR: self.is_xhr,
             private: false,
+            blockedReason: match self.request.mixed_content {
+                Some(MixedContentStatus::Blocked) => Some("mixed-content".to_owned()),
+                _ => None,
+            },
+        }
+    }
+
+    /// The state reported in the request's security info, which is "broken"
+    /// if any mixed content was blocked or fetched insecurely.
+    pub fn security_state(&self) -> String {
+        match self.request.mixed_content {
+            Some(MixedContentStatus::Blocked) | Some(MixedContentStatus::Allowed) => "broken",
+            _ if self.response.security_info.is_some() => "secure",
+            _ if self.request.url.starts_with("https:") => "secure",
+            _ => "insecure",
         }
+        .to_owned()
     }
 
     pub fn response_start(&self) -> ResponseStartMsg {
-        // TODO: Send the correct values for all these fields.
         let hSizeOption = self.response.headers.as_ref().map(|headers| headers.len());
         let hSize = hSizeOption.unwrap_or(0);
         let (status_code, status_message) = self
@@ -384,11 +495,16 @@ impl NetworkEventActor {
             .map_or((0, "".to_owned()), |(code, text)| {
                 (code.as_u16(), text.clone())
             });
-        // TODO: Send the correct values for remoteAddress and remotePort and http_version.
         ResponseStartMsg {
-            httpVersion: "HTTP/1.1".to_owned(),
-            remoteAddress: "63.245.217.43".to_owned(),
-            remotePort: 443,
+            httpVersion: self.response.http_version.clone(),
+            remoteAddress: self
+                .response
+                .remote_address
+                .map_or(String::new(), |address| address.ip().to_string()),
+            remotePort: self
+                .response
+                .remote_address
+                .map_or(0, |address| address.port() as u32),
             status: status_code.to_string(),
             statusText: status_message,
             headersSize: hSize,
@@ -396,20 +512,20 @@ impl NetworkEventActor {
         }
     }
 
+    fn mime_type(&self) -> String {
+        self.response
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.typed_get::<ContentType>())
+            .map_or(String::new(), |content_type| content_type.to_string())
+    }
+
     pub fn response_content(&self) -> ResponseContentMsg {
-        let mut mString = "".to_owned();
-        if let Some(ref headers) = self.response.headers {
-            mString = match headers.typed_get::<ContentType>() {
-                Some(ct) => ct.to_string(),
-                _ => "".to_owned(),
-            };
-        }
-        // TODO: Set correct values when response's body is sent to the devtools in http_loader.
         ResponseContentMsg {
-            mimeType: mString,
-            contentSize: 0,
-            transferredSize: 0,
-            discardResponseBody: true,
+            mimeType: self.mime_type(),
+            contentSize: self.response.body_size as u32,
+            transferredSize: self.response.transferred_size as u32,
+            discardResponseBody: self.response.body.is_none(),
         }
     }
 
@@ -462,6 +578,37 @@ impl NetworkEventActor {
     }
 
     pub fn total_time(&self) -> u64 {
-        self.request.connect_time + self.request.send_time
+        let timings = &self.request.timings;
+        timings.blocked +
+            timings.dns +
+            timings.connect +
+            timings.ssl +
+            timings.send +
+            timings.wait +
+            timings.receive
+    }
+}
+
+fn certificate_form(certificate: &CertificateInfo) -> CertificateForm {
+    CertificateForm {
+        subject: certificate_name_form(&certificate.subject),
+        issuer: certificate_name_form(&certificate.issuer),
+        validity: CertificateValidity {
+            start: certificate.not_before.clone(),
+            end: certificate.not_after.clone(),
+        },
+        fingerprint: CertificateFingerprint {
+            sha1: certificate.sha1_fingerprint.clone(),
+            sha256: certificate.sha256_fingerprint.clone(),
+        },
+        serialNumber: certificate.serial_number.clone(),
+    }
+}
+
+fn certificate_name_form(name: &CertificateName) -> CertificateNameForm {
+    CertificateNameForm {
+        commonName: name.common_name.clone(),
+        organization: name.organization.clone(),
+        organizationalUnit: name.organizational_unit.clone(),
     }
 }
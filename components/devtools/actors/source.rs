@@ -0,0 +1,103 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Liberally derived from the [Firefox JS implementation]
+//! (https://searchfox.org/mozilla-central/source/devtools/server/actors/source.js).
+//! A script source of a page, as the debugger knows it.
+
+use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+use crate::protocol::JsonPacketStream;
+use devtools_traits::{DevtoolScriptControlMsg, SourceInfo};
+use ipc_channel::ipc::{self, IpcSender};
+use msg::constellation_msg::PipelineId;
+use serde_json::{Map, Value};
+use std::net::TcpStream;
+
+#[derive(Serialize)]
+pub struct SourceForm {
+    actor: String,
+    url: String,
+    introductionType: Option<String>,
+    isBlackBoxed: bool,
+}
+
+#[derive(Serialize)]
+struct SourceReply {
+    from: String,
+    source: String,
+    contentType: String,
+}
+
+pub struct SourceActor {
+    name: String,
+    source_id: String,
+    pipeline: PipelineId,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+}
+
+impl Actor for SourceActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(
+        &self,
+        _registry: &ActorRegistry,
+        msg_type: &str,
+        _msg: &Map<String, Value>,
+        stream: &mut TcpStream,
+    ) -> Result<ActorMessageStatus, ()> {
+        Ok(match msg_type {
+            "source" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetSourceText(
+                        self.pipeline,
+                        self.source_id.clone(),
+                        tx,
+                    ))
+                    .unwrap();
+                let msg = SourceReply {
+                    from: self.name(),
+                    source: rx.recv().ok().and_then(|text| text).unwrap_or_default(),
+                    contentType: "text/javascript".to_owned(),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            _ => ActorMessageStatus::Ignored,
+        })
+    }
+}
+
+impl SourceActor {
+    /// The form of the given source, with the actor of that source, created if needed.
+    pub fn form(
+        registry: &ActorRegistry,
+        source: SourceInfo,
+        pipeline: PipelineId,
+        script_chan: &IpcSender<DevtoolScriptControlMsg>,
+    ) -> SourceForm {
+        let name = if registry.script_actor_registered(source.id.clone()) {
+            registry.script_to_actor(source.id.clone())
+        } else {
+            let name = registry.new_name("source");
+            registry.register_script_actor(source.id.clone(), name.clone());
+            registry.register_later(Box::new(SourceActor {
+                name: name.clone(),
+                source_id: source.id,
+                pipeline,
+                script_chan: script_chan.clone(),
+            }));
+            name
+        };
+        SourceForm {
+            actor: name,
+            url: source.url,
+            introductionType: source.introduction_type,
+            isBlackBoxed: false,
+        }
+    }
+}
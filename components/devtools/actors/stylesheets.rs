@@ -2,12 +2,88 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+//! Liberally derived from the [Firefox JS implementation]
+//! (https://searchfox.org/mozilla-central/source/devtools/server/actors/stylesheets.js).
+//! The stylesheets of a page, as the style editor lists and edits them.
+
 use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+use crate::protocol::JsonPacketStream;
+use devtools_traits::{CssRuleInfo, DevtoolScriptControlMsg, StyleSheetInfo};
+use ipc_channel::ipc::{self, IpcSender};
+use msg::constellation_msg::PipelineId;
 use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::net::TcpStream;
 
+/// The `type` of `CSSMediaRule`s.
+const MEDIA_RULE: u16 = 4;
+
+#[derive(Serialize)]
+pub struct StyleSheetForm {
+    pub actor: String,
+    href: Option<String>,
+    nodeHref: String,
+    disabled: bool,
+    title: Option<String>,
+    system: bool,
+    styleSheetIndex: usize,
+    ruleCount: usize,
+}
+
+#[derive(Serialize)]
+struct GetStyleSheetsReply {
+    from: String,
+    styleSheets: Vec<StyleSheetForm>,
+}
+
+#[derive(Serialize)]
+struct GetTextReply {
+    from: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct MediaRuleForm {
+    mediaText: String,
+    conditionText: String,
+    matches: bool,
+    line: u32,
+    column: u32,
+    parentStyleSheet: String,
+}
+
+#[derive(Serialize)]
+struct GetMediaRulesReply {
+    from: String,
+    mediaRules: Vec<MediaRuleForm>,
+}
+
+#[derive(Serialize)]
+struct StyleAppliedMsg {
+    from: String,
+    #[serde(rename = "type")]
+    type_: String,
+    kind: u32,
+}
+
+#[derive(Serialize)]
+struct UpdateReply {
+    from: String,
+}
+
+#[derive(Serialize)]
+struct ToggleDisabledReply {
+    from: String,
+    disabled: bool,
+}
+
 pub struct StyleSheetsActor {
     pub name: String,
+    pipeline: PipelineId,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+    /// The actors of the stylesheets listed so far, by stylesheet id.
+    sheets: RefCell<HashMap<String, String>>,
 }
 
 impl Actor for StyleSheetsActor {
@@ -16,17 +92,404 @@ impl Actor for StyleSheetsActor {
     }
     fn handle_message(
         &self,
-        _: &ActorRegistry,
-        _: &str,
-        _: &Map<String, Value>,
-        _: &mut TcpStream,
+        registry: &ActorRegistry,
+        msg_type: &str,
+        _msg: &Map<String, Value>,
+        stream: &mut TcpStream,
     ) -> Result<ActorMessageStatus, ()> {
-        Ok(ActorMessageStatus::Ignored)
+        Ok(match msg_type {
+            "getStyleSheets" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetStyleSheets(self.pipeline, tx))
+                    .unwrap();
+                let sheets = rx.recv().unwrap_or_default();
+                let msg = GetStyleSheetsReply {
+                    from: self.name(),
+                    styleSheets: sheets
+                        .into_iter()
+                        .map(|sheet| self.form(registry, sheet))
+                        .collect(),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            _ => ActorMessageStatus::Ignored,
+        })
     }
 }
 
 impl StyleSheetsActor {
-    pub fn new(name: String) -> StyleSheetsActor {
-        StyleSheetsActor { name: name }
+    pub fn new(
+        name: String,
+        pipeline: PipelineId,
+        script_chan: IpcSender<DevtoolScriptControlMsg>,
+    ) -> StyleSheetsActor {
+        StyleSheetsActor {
+            name: name,
+            pipeline,
+            script_chan,
+            sheets: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The form of the given stylesheet, with the actor of that stylesheet, created if needed.
+    pub fn form(&self, registry: &ActorRegistry, sheet: StyleSheetInfo) -> StyleSheetForm {
+        let mut sheets = self.sheets.borrow_mut();
+        let sheet_id = sheet.id.clone();
+        let name = sheets
+            .entry(sheet.id)
+            .or_insert_with(|| {
+                let name = registry.new_name("stylesheet");
+                registry.register_later(Box::new(StyleSheetActor {
+                    name: name.clone(),
+                    sheet_id,
+                    pipeline: self.pipeline,
+                    script_chan: self.script_chan.clone(),
+                }));
+                name
+            })
+            .clone();
+        StyleSheetForm {
+            actor: name,
+            href: sheet.href,
+            nodeHref: sheet.node_href,
+            disabled: sheet.disabled,
+            title: sheet.title,
+            system: false,
+            styleSheetIndex: sheet.index,
+            ruleCount: sheet.rule_count,
+        }
+    }
+}
+
+/// A stylesheet of a page.
+struct StyleSheetActor {
+    name: String,
+    sheet_id: String,
+    pipeline: PipelineId,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+}
+
+impl Actor for StyleSheetActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(
+        &self,
+        _registry: &ActorRegistry,
+        msg_type: &str,
+        msg: &Map<String, Value>,
+        stream: &mut TcpStream,
+    ) -> Result<ActorMessageStatus, ()> {
+        Ok(match msg_type {
+            "getText" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetStyleSheetText(
+                        self.pipeline,
+                        self.sheet_id.clone(),
+                        tx,
+                    ))
+                    .unwrap();
+                let msg = GetTextReply {
+                    from: self.name(),
+                    text: rx.recv().ok().and_then(|text| text).unwrap_or_default(),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "getMediaRules" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetStyleSheetRules(
+                        self.pipeline,
+                        self.sheet_id.clone(),
+                        tx,
+                    ))
+                    .unwrap();
+                let rules = rx.recv().ok().and_then(|rules| rules).unwrap_or_default();
+                let msg = GetMediaRulesReply {
+                    from: self.name(),
+                    mediaRules: rules
+                        .into_iter()
+                        .filter(|rule| rule.type_ == MEDIA_RULE)
+                        .map(|rule| self.media_rule_form(rule))
+                        .collect(),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "update" => {
+                let text = match msg.get("text").and_then(|text| text.as_str()) {
+                    Some(text) => text.to_owned(),
+                    None => return Ok(ActorMessageStatus::Ignored),
+                };
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::UpdateStyleSheet(
+                        self.pipeline,
+                        self.sheet_id.clone(),
+                        text,
+                        tx,
+                    ))
+                    .unwrap();
+                if rx.recv().unwrap_or(false) {
+                    let msg = StyleAppliedMsg {
+                        from: self.name(),
+                        type_: "style-applied".to_owned(),
+                        kind: 0,
+                    };
+                    stream.write_json_packet(&msg);
+                }
+                let reply = UpdateReply { from: self.name() };
+                stream.write_json_packet(&reply);
+                ActorMessageStatus::Processed
+            },
+
+            "toggleDisabled" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::ToggleStyleSheetDisabled(
+                        self.pipeline,
+                        self.sheet_id.clone(),
+                        tx,
+                    ))
+                    .unwrap();
+                let reply = ToggleDisabledReply {
+                    from: self.name(),
+                    disabled: rx
+                        .recv()
+                        .ok()
+                        .and_then(|disabled| disabled)
+                        .unwrap_or_default(),
+                };
+                stream.write_json_packet(&reply);
+                ActorMessageStatus::Processed
+            },
+
+            _ => ActorMessageStatus::Ignored,
+        })
+    }
+}
+
+impl StyleSheetActor {
+    fn media_rule_form(&self, rule: CssRuleInfo) -> MediaRuleForm {
+        let media_text = rule.media_text.unwrap_or_default();
+        MediaRuleForm {
+            conditionText: media_text.clone(),
+            mediaText: media_text,
+            matches: rule.matches,
+            line: rule.line,
+            column: rule.column,
+            parentStyleSheet: self.name(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StyleSheetActor, StyleSheetsActor};
+    use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+    use crate::protocol::tests::{answer_script_message, message, read_packet, stream_pair};
+    use devtools_traits::{CssRuleInfo, DevtoolScriptControlMsg, StyleSheetInfo};
+    use ipc_channel::ipc::{self, IpcReceiver};
+    use msg::constellation_msg::TEST_PIPELINE_ID;
+    use serde_json::json;
+
+    fn sheet_info(id: &str, href: Option<&str>) -> StyleSheetInfo {
+        StyleSheetInfo {
+            id: id.to_owned(),
+            href: href.map(|href| href.to_owned()),
+            node_href: "http://example.com/".to_owned(),
+            title: None,
+            disabled: false,
+            index: 0,
+            rule_count: 2,
+        }
+    }
+
+    fn rule_info(type_: u16, media_text: Option<&str>, line: u32) -> CssRuleInfo {
+        CssRuleInfo {
+            type_,
+            css_text: String::new(),
+            media_text: media_text.map(|text| text.to_owned()),
+            matches: true,
+            line,
+            column: 1,
+        }
+    }
+
+    fn sheet_actor() -> (StyleSheetActor, IpcReceiver<DevtoolScriptControlMsg>) {
+        let (script_chan, script_port) = ipc::channel().unwrap();
+        let actor = StyleSheetActor {
+            name: "stylesheet".to_owned(),
+            sheet_id: "sheet".to_owned(),
+            pipeline: TEST_PIPELINE_ID,
+            script_chan,
+        };
+        (actor, script_port)
+    }
+
+    #[test]
+    fn get_style_sheets_reuses_the_actors_of_known_sheets() {
+        let registry = ActorRegistry::new();
+        let (script_chan, mut script_port) = ipc::channel().unwrap();
+        let actor = StyleSheetsActor::new("stylesheets".to_owned(), TEST_PIPELINE_ID, script_chan);
+        let (mut stream, mut client) = stream_pair();
+
+        let mut actors = vec![];
+        for _ in 0..2 {
+            let script = answer_script_message(script_port, |msg| match msg {
+                DevtoolScriptControlMsg::GetStyleSheets(_, reply) => reply
+                    .send(vec![
+                        sheet_info("a", Some("http://example.com/a.css")),
+                        sheet_info("b", None),
+                    ])
+                    .unwrap(),
+                msg => panic!("unexpected message {:?}", msg),
+            });
+            let msg = message(json!({}));
+            actor
+                .handle_message(&registry, "getStyleSheets", &msg, &mut stream)
+                .unwrap();
+            script_port = script.join().unwrap();
+
+            let reply = read_packet(&mut client);
+            assert_eq!(reply["from"], "stylesheets");
+            let sheets = reply["styleSheets"].as_array().unwrap();
+            assert_eq!(sheets.len(), 2);
+            assert_eq!(sheets[0]["href"], "http://example.com/a.css");
+            assert_eq!(sheets[1]["href"], json!(null));
+            assert_eq!(sheets[1]["nodeHref"], "http://example.com/");
+            assert_eq!(sheets[1]["ruleCount"], 2);
+            assert_eq!(sheets[1]["system"], false);
+            actors.push((sheets[0]["actor"].clone(), sheets[1]["actor"].clone()));
+        }
+        assert_ne!(actors[0].0, actors[0].1);
+        assert_eq!(actors[0], actors[1]);
+    }
+
+    #[test]
+    fn get_text() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = sheet_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::GetStyleSheetText(_, id, reply) => {
+                assert_eq!(id, "sheet");
+                reply.send(Some("p { color: red }".to_owned())).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        actor
+            .handle_message(&registry, "getText", &message(json!({})), &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(reply["from"], "stylesheet");
+        assert_eq!(reply["text"], "p { color: red }");
+    }
+
+    #[test]
+    fn get_media_rules_lists_only_media_rules() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = sheet_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::GetStyleSheetRules(_, _, reply) => reply
+                .send(Some(vec![
+                    rule_info(1, None, 1),
+                    rule_info(4, Some("(min-width: 100px)"), 2),
+                    rule_info(1, None, 3),
+                ]))
+                .unwrap(),
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({}));
+        actor
+            .handle_message(&registry, "getMediaRules", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(
+            reply["mediaRules"],
+            json!([{
+                "mediaText": "(min-width: 100px)",
+                "conditionText": "(min-width: 100px)",
+                "matches": true,
+                "line": 2,
+                "column": 1,
+                "parentStyleSheet": "stylesheet",
+            }])
+        );
+    }
+
+    #[test]
+    fn update_tells_the_style_applied_before_replying() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = sheet_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::UpdateStyleSheet(_, _, text, reply) => {
+                assert_eq!(text, "p { color: blue }");
+                reply.send(true).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({ "text": "p { color: blue }", "transition": true }));
+        actor
+            .handle_message(&registry, "update", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let applied = read_packet(&mut client);
+        assert_eq!(
+            applied,
+            json!({ "from": "stylesheet", "type": "style-applied", "kind": 0 })
+        );
+        assert_eq!(read_packet(&mut client), json!({ "from": "stylesheet" }));
+    }
+
+    #[test]
+    fn update_without_text_is_ignored() {
+        let registry = ActorRegistry::new();
+        let (actor, _script_port) = sheet_actor();
+        let (mut stream, _client) = stream_pair();
+
+        let msg = message(json!({ "transition": true }));
+        let status = actor.handle_message(&registry, "update", &msg, &mut stream);
+        assert!(status == Ok(ActorMessageStatus::Ignored));
+    }
+
+    #[test]
+    fn toggle_disabled() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = sheet_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::ToggleStyleSheetDisabled(_, _, reply) => {
+                reply.send(Some(true)).unwrap()
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        let msg = message(json!({}));
+        actor
+            .handle_message(&registry, "toggleDisabled", &msg, &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        assert_eq!(reply, json!({ "from": "stylesheet", "disabled": true }));
     }
 }
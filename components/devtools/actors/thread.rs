@@ -2,9 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+//! Liberally derived from the [Firefox JS implementation]
+//! (https://searchfox.org/mozilla-central/source/devtools/server/actors/thread.js).
+//! Script debugging of a page: its sources, breakpoints, pauses and stepping.
+
 use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+use crate::actors::frame::{FrameActor, FrameForm};
+use crate::actors::object::ObjectActor;
+use crate::actors::source::{SourceActor, SourceForm};
 use crate::protocol::JsonPacketStream;
+use devtools_traits::ResumeLimit;
+use devtools_traits::{BreakpointLocation, DevtoolScriptControlMsg, PauseInfo, PauseReason};
+use ipc_channel::ipc::{self, IpcSender};
+use msg::constellation_msg::PipelineId;
 use serde_json::{Map, Value};
+use std::cell::RefCell;
 use std::net::TcpStream;
 
 #[derive(Serialize)]
@@ -24,6 +36,18 @@ enum PoppedFrameMsg {}
 struct WhyMsg {
     #[serde(rename = "type")]
     type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exception: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ThreadPausedMsg {
+    from: String,
+    #[serde(rename = "type")]
+    type_: String,
+    actor: String,
+    frame: FrameForm,
+    why: WhyMsg,
 }
 
 #[derive(Serialize)]
@@ -40,27 +64,106 @@ struct ThreadInterruptedReply {
     type_: String,
 }
 
+#[derive(Serialize)]
+struct ThreadDetachedReply {
+    from: String,
+    #[serde(rename = "type")]
+    type_: String,
+}
+
 #[derive(Serialize)]
 struct ReconfigureReply {
     from: String,
 }
 
+#[derive(Serialize)]
+struct EmptyReply {
+    from: String,
+}
+
 #[derive(Serialize)]
 struct SourcesReply {
     from: String,
-    sources: Vec<Source>,
+    sources: Vec<SourceForm>,
 }
 
 #[derive(Serialize)]
-enum Source {}
+struct FramesReply {
+    from: String,
+    frames: Vec<FrameForm>,
+}
 
 pub struct ThreadActor {
     name: String,
+    pipeline: PipelineId,
+    script_chan: IpcSender<DevtoolScriptControlMsg>,
+    /// The clients attached to this thread, which are told when it pauses.
+    streams: RefCell<Vec<TcpStream>>,
 }
 
 impl ThreadActor {
-    pub fn new(name: String) -> ThreadActor {
-        ThreadActor { name: name }
+    pub fn new(
+        name: String,
+        pipeline: PipelineId,
+        script_chan: IpcSender<DevtoolScriptControlMsg>,
+    ) -> ThreadActor {
+        ThreadActor {
+            name: name,
+            pipeline,
+            script_chan,
+            streams: RefCell::new(vec![]),
+        }
+    }
+
+    /// Tell the attached clients that script paused in the page.
+    pub fn paused(&self, registry: &ActorRegistry, pause: PauseInfo) {
+        let PauseInfo { why, frame } = pause;
+        let (type_, exception) = match why {
+            PauseReason::Interrupted => ("interrupted", None),
+            PauseReason::Breakpoint => ("breakpoint", None),
+            PauseReason::ResumeLimit => ("resumeLimit", None),
+            PauseReason::DebuggerStatement => ("debuggerStatement", None),
+            PauseReason::Exception(value) => {
+                let value =
+                    ObjectActor::encode_value(registry, value, self.pipeline, &self.script_chan);
+                ("exception", Some(value))
+            },
+        };
+        let msg = ThreadPausedMsg {
+            from: self.name(),
+            type_: "paused".to_owned(),
+            actor: registry.new_name("pause"),
+            frame: FrameActor::form(registry, frame, self.pipeline, &self.script_chan),
+            why: WhyMsg {
+                type_: type_.to_owned(),
+                exception,
+            },
+        };
+        for stream in &mut *self.streams.borrow_mut() {
+            stream.write_json_packet(&msg);
+        }
+    }
+
+    fn set_pause_on_exceptions(&self, options: Option<&Value>) {
+        let pause_on_exceptions = options
+            .and_then(|options| options.get("pauseOnExceptions"))
+            .and_then(|value| value.as_bool());
+        if let Some(enabled) = pause_on_exceptions {
+            self.script_chan
+                .send(DevtoolScriptControlMsg::PauseOnExceptions(
+                    self.pipeline,
+                    enabled,
+                ))
+                .unwrap();
+        }
+    }
+
+    fn breakpoint_location(msg: &Map<String, Value>) -> Option<BreakpointLocation> {
+        let location = msg.get("location")?;
+        Some(BreakpointLocation {
+            url: location.get("sourceUrl")?.as_str()?.to_owned(),
+            line: location.get("line")?.as_u64()? as u32,
+        })
     }
 }
 
@@ -73,11 +176,13 @@ impl Actor for ThreadActor {
         &self,
         registry: &ActorRegistry,
         msg_type: &str,
-        _msg: &Map<String, Value>,
+        msg: &Map<String, Value>,
         stream: &mut TcpStream,
     ) -> Result<ActorMessageStatus, ()> {
         Ok(match msg_type {
             "attach" => {
+                self.set_pause_on_exceptions(msg.get("options"));
+                self.streams.borrow_mut().push(stream.try_clone().unwrap());
                 let msg = ThreadAttachedReply {
                     from: self.name(),
                     type_: "paused".to_owned(),
@@ -85,13 +190,40 @@ impl Actor for ThreadActor {
                     poppedFrames: vec![],
                     why: WhyMsg {
                         type_: "attached".to_owned(),
+                        exception: None,
                     },
                 };
                 stream.write_json_packet(&msg);
                 ActorMessageStatus::Processed
             },
 
+            "detach" => {
+                // A page is never left paused without a client.
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::Resume(self.pipeline, None))
+                    .unwrap();
+                let msg = ThreadDetachedReply {
+                    from: self.name(),
+                    type_: "detached".to_owned(),
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
             "resume" => {
+                let limit = msg
+                    .get("resumeLimit")
+                    .and_then(|limit| limit.get("type"))
+                    .and_then(|limit| limit.as_str())
+                    .and_then(|limit| match limit {
+                        "step" => Some(ResumeLimit::StepIn),
+                        "next" => Some(ResumeLimit::StepOver),
+                        "finish" => Some(ResumeLimit::StepOut),
+                        _ => None,
+                    });
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::Resume(self.pipeline, limit))
+                    .unwrap();
                 let msg = ThreadResumedReply {
                     from: self.name(),
                     type_: "resumed".to_owned(),
@@ -101,6 +233,9 @@ impl Actor for ThreadActor {
             },
 
             "interrupt" => {
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::Interrupt(self.pipeline))
+                    .unwrap();
                 let msg = ThreadInterruptedReply {
                     from: self.name(),
                     type_: "interrupted".to_owned(),
@@ -110,14 +245,76 @@ impl Actor for ThreadActor {
             },
 
             "reconfigure" => {
+                self.set_pause_on_exceptions(msg.get("options"));
                 stream.write_json_packet(&ReconfigureReply { from: self.name() });
                 ActorMessageStatus::Processed
             },
 
+            "pauseOnExceptions" => {
+                self.set_pause_on_exceptions(Some(&Value::Object(msg.clone())));
+                stream.write_json_packet(&EmptyReply { from: self.name() });
+                ActorMessageStatus::Processed
+            },
+
             "sources" => {
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::ListSources(self.pipeline, tx))
+                    .unwrap();
+                let sources = rx
+                    .recv()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|source| {
+                        SourceActor::form(registry, source, self.pipeline, &self.script_chan)
+                    })
+                    .collect();
                 let msg = SourcesReply {
                     from: self.name(),
-                    sources: vec![],
+                    sources,
+                };
+                stream.write_json_packet(&msg);
+                ActorMessageStatus::Processed
+            },
+
+            "setBreakpoint" | "removeBreakpoint" => {
+                let location = match ThreadActor::breakpoint_location(msg) {
+                    Some(location) => location,
+                    None => return Ok(ActorMessageStatus::Ignored),
+                };
+                let msg = if msg_type == "setBreakpoint" {
+                    DevtoolScriptControlMsg::SetBreakpoint(self.pipeline, location)
+                } else {
+                    DevtoolScriptControlMsg::RemoveBreakpoint(self.pipeline, location)
+                };
+                self.script_chan.send(msg).unwrap();
+                stream.write_json_packet(&EmptyReply { from: self.name() });
+                ActorMessageStatus::Processed
+            },
+
+            "frames" => {
+                let start = msg
+                    .get("start")
+                    .and_then(|start| start.as_u64())
+                    .unwrap_or(0);
+                let count = msg.get("count").and_then(|count| count.as_u64());
+                let (tx, rx) = ipc::channel().unwrap();
+                self.script_chan
+                    .send(DevtoolScriptControlMsg::GetFrames(self.pipeline, tx))
+                    .unwrap();
+                let frames = rx
+                    .recv()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .skip(start as usize)
+                    .take(count.map_or(usize::max_value(), |count| count as usize))
+                    .map(|frame| {
+                        FrameActor::form(registry, frame, self.pipeline, &self.script_chan)
+                    })
+                    .collect();
+                let msg = FramesReply {
+                    from: self.name(),
+                    frames,
                 };
                 stream.write_json_packet(&msg);
                 ActorMessageStatus::Processed
@@ -127,3 +324,191 @@ impl Actor for ThreadActor {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadActor;
+    use crate::actor::{Actor, ActorMessageStatus, ActorRegistry};
+    use crate::protocol::tests::{answer_script_message, message, read_packet, stream_pair};
+    use devtools_traits::{BreakpointLocation, DevtoolScriptControlMsg, ResumeLimit, SourceInfo};
+    use ipc_channel::ipc::{self, IpcReceiver};
+    use msg::constellation_msg::TEST_PIPELINE_ID;
+    use serde_json::json;
+
+    fn thread_actor() -> (ThreadActor, IpcReceiver<DevtoolScriptControlMsg>) {
+        let (script_chan, script_port) = ipc::channel().unwrap();
+        let actor = ThreadActor::new("thread".to_owned(), TEST_PIPELINE_ID, script_chan);
+        (actor, script_port)
+    }
+
+    #[test]
+    fn attach_replies_paused_and_sets_pause_on_exceptions() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = thread_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let msg = message(json!({ "options": { "pauseOnExceptions": true } }));
+        let status = actor.handle_message(&registry, "attach", &msg, &mut stream);
+        assert!(status == Ok(ActorMessageStatus::Processed));
+
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::PauseOnExceptions(pipeline, enabled) => {
+                assert_eq!(pipeline, TEST_PIPELINE_ID);
+                assert!(enabled);
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        let reply = read_packet(&mut client);
+        assert_eq!(reply["from"], "thread");
+        assert_eq!(reply["type"], "paused");
+        assert_eq!(reply["why"], json!({ "type": "attached" }));
+        assert_eq!(reply["poppedFrames"], json!([]));
+    }
+
+    #[test]
+    fn resume_maps_resume_limits() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = thread_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let limits = vec![
+            (None, None),
+            (Some("step"), Some(ResumeLimit::StepIn)),
+            (Some("next"), Some(ResumeLimit::StepOver)),
+            (Some("finish"), Some(ResumeLimit::StepOut)),
+            (Some("break"), None),
+        ];
+        for (limit_type, expected_limit) in limits {
+            let msg = match limit_type {
+                Some(limit_type) => message(json!({ "resumeLimit": { "type": limit_type } })),
+                None => message(json!({})),
+            };
+            actor
+                .handle_message(&registry, "resume", &msg, &mut stream)
+                .unwrap();
+            match script_port.recv().unwrap() {
+                DevtoolScriptControlMsg::Resume(_, limit) => assert_eq!(limit, expected_limit),
+                msg => panic!("unexpected message {:?}", msg),
+            }
+            let reply = read_packet(&mut client);
+            assert_eq!(reply, json!({ "from": "thread", "type": "resumed" }));
+        }
+    }
+
+    #[test]
+    fn interrupt_and_detach() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = thread_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        actor
+            .handle_message(&registry, "interrupt", &message(json!({})), &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::Interrupt(pipeline) => assert_eq!(pipeline, TEST_PIPELINE_ID),
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        let reply = read_packet(&mut client);
+        assert_eq!(reply, json!({ "from": "thread", "type": "interrupted" }));
+
+        actor
+            .handle_message(&registry, "detach", &message(json!({})), &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::Resume(_, None) => {},
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        let reply = read_packet(&mut client);
+        assert_eq!(reply, json!({ "from": "thread", "type": "detached" }));
+    }
+
+    #[test]
+    fn sources_lists_the_sources_of_script() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = thread_actor();
+        let (mut stream, mut client) = stream_pair();
+
+        let script = answer_script_message(script_port, |msg| match msg {
+            DevtoolScriptControlMsg::ListSources(_, reply) => reply
+                .send(vec![
+                    SourceInfo {
+                        id: "1".to_owned(),
+                        url: "http://example.com/a.js".to_owned(),
+                        introduction_type: Some("scriptElement".to_owned()),
+                    },
+                    SourceInfo {
+                        id: "2".to_owned(),
+                        url: "http://example.com/".to_owned(),
+                        introduction_type: None,
+                    },
+                ])
+                .unwrap(),
+            msg => panic!("unexpected message {:?}", msg),
+        });
+        actor
+            .handle_message(&registry, "sources", &message(json!({})), &mut stream)
+            .unwrap();
+        script.join().unwrap();
+
+        let reply = read_packet(&mut client);
+        let sources = reply["sources"].as_array().unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0]["url"], "http://example.com/a.js");
+        assert_eq!(sources[0]["introductionType"], "scriptElement");
+        assert_eq!(sources[0]["isBlackBoxed"], false);
+        assert_eq!(sources[1]["introductionType"], json!(null));
+        assert_ne!(sources[0]["actor"], sources[1]["actor"]);
+        assert_eq!(
+            registry.script_to_actor("1".to_owned()),
+            sources[0]["actor"].as_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn set_and_remove_breakpoints() {
+        let registry = ActorRegistry::new();
+        let (actor, script_port) = thread_actor();
+        let (mut stream, mut client) = stream_pair();
+        let location = BreakpointLocation {
+            url: "http://example.com/a.js".to_owned(),
+            line: 3,
+        };
+        let msg = message(json!({
+            "location": { "sourceUrl": "http://example.com/a.js", "line": 3 },
+        }));
+
+        actor
+            .handle_message(&registry, "setBreakpoint", &msg, &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::SetBreakpoint(_, set) => assert_eq!(set, location),
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(read_packet(&mut client), json!({ "from": "thread" }));
+
+        actor
+            .handle_message(&registry, "removeBreakpoint", &msg, &mut stream)
+            .unwrap();
+        match script_port.recv().unwrap() {
+            DevtoolScriptControlMsg::RemoveBreakpoint(_, removed) => {
+                assert_eq!(removed, location)
+            },
+            msg => panic!("unexpected message {:?}", msg),
+        }
+        assert_eq!(read_packet(&mut client), json!({ "from": "thread" }));
+    }
+
+    #[test]
+    fn breakpoints_without_a_location_are_ignored() {
+        let registry = ActorRegistry::new();
+        let (actor, _script_port) = thread_actor();
+        let (mut stream, _client) = stream_pair();
+
+        let msg = message(json!({ "location": { "sourceUrl": "http://example.com/a.js" } }));
+        let status = actor.handle_message(&registry, "setBreakpoint", &msg, &mut stream);
+        assert!(status == Ok(ActorMessageStatus::Ignored));
+        let msg = message(json!({}));
+        let status = actor.handle_message(&registry, "removeBreakpoint", &msg, &mut stream);
+        assert!(status == Ok(ActorMessageStatus::Ignored));
+    }
+}
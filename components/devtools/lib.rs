@@ -23,7 +23,7 @@ use crate::actors::console::ConsoleActor;
 use crate::actors::device::DeviceActor;
 use crate::actors::emulation::EmulationActor;
 use crate::actors::framerate::FramerateActor;
-use crate::actors::inspector::InspectorActor;
+use crate::actors::inspector::{InspectorActor, WalkerActor};
 use crate::actors::network_event::{EventActor, NetworkEventActor, ResponseStartMsg};
 use crate::actors::performance::PerformanceActor;
 use crate::actors::profiler::ProfilerActor;
@@ -34,9 +34,10 @@ use crate::actors::timeline::TimelineActor;
 use crate::actors::worker::WorkerActor;
 use crate::protocol::JsonPacketStream;
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use devtools_traits::NetworkEvent;
 use devtools_traits::{ChromeToDevtoolsControlMsg, ConsoleMessage, DevtoolsControlMsg};
-use devtools_traits::{DevtoolScriptControlMsg, DevtoolsPageInfo, LogLevel, NetworkEvent};
-use devtools_traits::{ScriptToDevtoolsControlMsg, WorkerId};
+use devtools_traits::{DevtoolScriptControlMsg, DevtoolsPageInfo, DomMutation, LogLevel};
+use devtools_traits::{PauseInfo, ScriptToDevtoolsControlMsg, WorkerId};
 use ipc_channel::ipc::IpcSender;
 use msg::constellation_msg::PipelineId;
 use std::borrow::ToOwned;
@@ -55,6 +56,7 @@ mod actors {
     pub mod console;
     pub mod device;
     pub mod emulation;
+    pub mod frame;
     pub mod framerate;
     pub mod inspector;
     pub mod memory;
@@ -63,6 +65,7 @@ mod actors {
     pub mod performance;
     pub mod profiler;
     pub mod root;
+    pub mod source;
     pub mod stylesheets;
     pub mod thread;
     pub mod timeline;
@@ -245,24 +248,31 @@ fn run_server(
 
             let emulation = EmulationActor::new(actors.new_name("emulation"));
 
+            // the strange switch between styleSheets and stylesheets is due
+            // to an inconsistency in devtools. See Bug #1498893 in bugzilla
+            let styleSheets = StyleSheetsActor::new(
+                actors.new_name("stylesheets"),
+                pipeline,
+                script_sender.clone(),
+            );
+
             let inspector = InspectorActor {
                 name: actors.new_name("inspector"),
                 walker: RefCell::new(None),
                 pageStyle: RefCell::new(None),
                 highlighter: RefCell::new(None),
+                styleSheets: styleSheets.name(),
                 script_chan: script_sender.clone(),
                 pipeline: pipeline,
             };
 
-            let timeline = TimelineActor::new(actors.new_name("timeline"), pipeline, script_sender);
+            let timeline =
+                TimelineActor::new(actors.new_name("timeline"), pipeline, script_sender.clone());
 
             let profiler = ProfilerActor::new(actors.new_name("profiler"));
             let performance = PerformanceActor::new(actors.new_name("performance"));
 
-            // the strange switch between styleSheets and stylesheets is due
-            // to an inconsistency in devtools. See Bug #1498893 in bugzilla
-            let styleSheets = StyleSheetsActor::new(actors.new_name("stylesheets"));
-            let thread = ThreadActor::new(actors.new_name("context"));
+            let thread = ThreadActor::new(actors.new_name("context"), pipeline, script_sender);
 
             let DevtoolsPageInfo { title, url } = page_info;
             let target = BrowsingContextActor {
@@ -383,6 +393,46 @@ fn run_server(
         }
     }
 
+    fn handle_debugger_paused(
+        actors: Arc<Mutex<ActorRegistry>>,
+        id: PipelineId,
+        pause: PauseInfo,
+        actor_pipelines: &HashMap<PipelineId, String>,
+    ) {
+        let mut actors = actors.lock().unwrap();
+        let actor_name = match actor_pipelines.get(&id) {
+            Some(name) => name,
+            None => return,
+        };
+        let thread = &actors.find::<BrowsingContextActor>(actor_name).thread;
+        actors.find::<ThreadActor>(thread).paused(&actors, pause);
+        // The frame and object actors of the pause must be known for the requests to come.
+        actors.register_pending();
+    }
+
+    fn handle_dom_mutations(
+        actors: Arc<Mutex<ActorRegistry>>,
+        id: PipelineId,
+        mutations: Vec<DomMutation>,
+        actor_pipelines: &HashMap<PipelineId, String>,
+    ) {
+        let mut actors = actors.lock().unwrap();
+        let actor_name = match actor_pipelines.get(&id) {
+            Some(name) => name,
+            None => return,
+        };
+        let inspector = &actors.find::<BrowsingContextActor>(actor_name).inspector;
+        let walker = match *actors.find::<InspectorActor>(inspector).walker.borrow() {
+            Some(ref walker) => walker.clone(),
+            None => return,
+        };
+        actors
+            .find::<WalkerActor>(&walker)
+            .add_mutations(&actors, mutations);
+        // The node actors of the added nodes must be known for the requests to come.
+        actors.register_pending();
+    }
+
     fn handle_network_event(
         actors: Arc<Mutex<ActorRegistry>>,
         mut connections: Vec<TcpStream>,
@@ -459,10 +509,10 @@ fn run_server(
                 let msg = NetworkEventUpdateMsg {
                     from: netevent_actor_name.clone(),
                     type_: "networkEventUpdate".to_owned(),
-                    updateType: "eventTimings".to_owned(),
+                    updateType: "securityInfo".to_owned(),
                 };
-                let extra = EventTimingsUpdateMsg {
-                    totalTime: actor.total_time(),
+                let extra = SecurityInfoUpdateMsg {
+                    state: actor.security_state(),
                 };
                 for stream in &mut connections {
                     stream.write_merged_json_packet(&msg, &extra);
@@ -471,40 +521,44 @@ fn run_server(
                 let msg = NetworkEventUpdateMsg {
                     from: netevent_actor_name.clone(),
                     type_: "networkEventUpdate".to_owned(),
-                    updateType: "securityInfo".to_owned(),
-                };
-                let extra = SecurityInfoUpdateMsg {
-                    state: "insecure".to_owned(),
+                    updateType: "responseCookies".to_owned(),
                 };
                 for stream in &mut connections {
-                    stream.write_merged_json_packet(&msg, &extra);
+                    stream.write_merged_json_packet(&msg, &actor.response_cookies());
                 }
 
                 let msg = NetworkEventUpdateMsg {
-                    from: netevent_actor_name.clone(),
+                    from: netevent_actor_name,
                     type_: "networkEventUpdate".to_owned(),
-                    updateType: "responseContent".to_owned(),
+                    updateType: "responseHeaders".to_owned(),
                 };
                 for stream in &mut connections {
-                    stream.write_merged_json_packet(&msg, &actor.response_content());
+                    stream.write_merged_json_packet(&msg, &actor.response_headers());
                 }
+            },
+            NetworkEvent::HttpResponseEnd(httpresponseend) => {
+                //Store the body and the time it took to receive it in the actor
+                actor.add_response_end(httpresponseend);
 
                 let msg = NetworkEventUpdateMsg {
                     from: netevent_actor_name.clone(),
                     type_: "networkEventUpdate".to_owned(),
-                    updateType: "responseCookies".to_owned(),
+                    updateType: "responseContent".to_owned(),
                 };
                 for stream in &mut connections {
-                    stream.write_merged_json_packet(&msg, &actor.response_cookies());
+                    stream.write_merged_json_packet(&msg, &actor.response_content());
                 }
 
                 let msg = NetworkEventUpdateMsg {
                     from: netevent_actor_name,
                     type_: "networkEventUpdate".to_owned(),
-                    updateType: "responseHeaders".to_owned(),
+                    updateType: "eventTimings".to_owned(),
+                };
+                let extra = EventTimingsUpdateMsg {
+                    totalTime: actor.total_time(),
                 };
                 for stream in &mut connections {
-                    stream.write_merged_json_packet(&msg, &actor.response_headers());
+                    stream.write_merged_json_packet(&msg, &extra);
                 }
             },
         }
@@ -606,6 +660,14 @@ fn run_server(
                     &actor_workers,
                 )
             },
+            DevtoolsControlMsg::FromScript(ScriptToDevtoolsControlMsg::DebuggerPaused(
+                id,
+                pause,
+            )) => handle_debugger_paused(actors.clone(), id, pause, &actor_pipelines),
+            DevtoolsControlMsg::FromScript(ScriptToDevtoolsControlMsg::DomMutations(
+                id,
+                mutations,
+            )) => handle_dom_mutations(actors.clone(), id, mutations, &actor_pipelines),
             DevtoolsControlMsg::FromChrome(ChromeToDevtoolsControlMsg::NetworkEvent(
                 request_id,
                 network_event,
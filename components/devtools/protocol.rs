@@ -87,3 +87,46 @@ impl JsonPacketStream for TcpStream {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::JsonPacketStream;
+    use devtools_traits::DevtoolScriptControlMsg;
+    use ipc_channel::ipc::IpcReceiver;
+    use serde_json::{Map, Value};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread::{self, JoinHandle};
+
+    /// A connected pair of streams: the one the actors write to, and the client's.
+    pub fn stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    /// The next packet the client got.
+    pub fn read_packet(client: &mut TcpStream) -> Value {
+        client.read_json_packet().unwrap().unwrap()
+    }
+
+    /// The given JSON object, as the actors get the messages of the clients.
+    pub fn message(json: Value) -> Map<String, Value> {
+        json.as_object().unwrap().clone()
+    }
+
+    /// Answer the next message sent to script from another thread, as the actors wait for
+    /// the reply of script when handling a message. The receiver is given back once done.
+    pub fn answer_script_message<F>(
+        receiver: IpcReceiver<DevtoolScriptControlMsg>,
+        answer: F,
+    ) -> JoinHandle<IpcReceiver<DevtoolScriptControlMsg>>
+    where
+        F: FnOnce(DevtoolScriptControlMsg) + Send + 'static,
+    {
+        thread::spawn(move || {
+            answer(receiver.recv().unwrap());
+            receiver
+        })
+    }
+}
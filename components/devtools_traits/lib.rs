@@ -23,7 +23,7 @@ use http::HeaderMap;
 use ipc_channel::ipc::IpcSender;
 use msg::constellation_msg::PipelineId;
 use servo_url::ServoUrl;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
 use time::{self, Duration, Tm};
 
 // Information would be attached to NewGlobal to be received and show in devtools.
@@ -82,10 +82,39 @@ pub enum ScriptToDevtoolsControlMsg {
 
     /// Report a CSS parse error for the given pipeline
     ReportCSSError(PipelineId, CSSError),
+
+    /// The script of the given pipeline paused in the debugger; it runs nothing but
+    /// devtools messages until it is sent a `Resume` message.
+    DebuggerPaused(PipelineId, PauseInfo),
+
+    /// The document of the given pipeline was mutated, while devtools asked to be told of it
+    /// with a `WantsMutationNotifications` message.
+    DomMutations(PipelineId, Vec<DomMutation>),
+}
+
+/// A mutation of a document, as seen once the microtask checkpoint it happened before comes.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum DomMutation {
+    /// An attribute of the node with the given id was set or removed.
+    Attribute {
+        target: String,
+        name: String,
+        namespace: String,
+        new_value: Option<String>,
+    },
+    /// The data of the text, comment or processing instruction node with the given id changed.
+    CharacterData { target: String, new_value: String },
+    /// Children were added to or removed from the node with the given id. The added nodes
+    /// are the ones still children of it.
+    ChildList {
+        target: String,
+        added: Vec<NodeInfo>,
+        removed: Vec<String>,
+        num_children: usize,
+    },
 }
 
-/// Serialized JS return values
-/// TODO: generalize this beyond the EvaluateJS message?
+/// Serialized JS values, as returned by the EvaluateJS message and inspected in the debugger.
 #[derive(Debug, Deserialize, Serialize)]
 pub enum EvaluateJSReply {
     VoidValue,
@@ -204,6 +233,30 @@ pub enum DevtoolScriptControlMsg {
     ),
     /// Update a given node's attributes with a list of modifications.
     ModifyAttribute(PipelineId, String, Vec<Modification>),
+    /// Move the given node under the given parent, before the given sibling or last.
+    InsertBefore(PipelineId, String, String, Option<String>),
+    /// Remove the given node from its parent, replying with the sibling that followed it.
+    RemoveNode(PipelineId, String, IpcSender<Option<NodeInfo>>),
+    /// Retrieve the serialization of the given node and its descendants.
+    GetOuterHTML(PipelineId, String, IpcSender<Option<String>>),
+    /// Replace the given node with the nodes parsed from the given markup.
+    SetOuterHTML(PipelineId, String, String),
+    /// Set the value of the given node, the data of text and comment nodes.
+    SetNodeValue(PipelineId, String, String),
+    /// Request mutation notifications for the document of a given pipeline (true if
+    /// desired, false otherwise).
+    WantsMutationNotifications(PipelineId, bool),
+    /// Retrieve the element style and the style rules that apply to the given node, the ones
+    /// that win first.
+    GetAppliedRules(PipelineId, String, IpcSender<Option<Vec<AppliedRuleInfo>>>),
+    /// Apply the given modifications to the declarations of a rule, replying with the
+    /// declarations as modified.
+    ModifyRule(
+        PipelineId,
+        AppliedRuleId,
+        Vec<RuleModification>,
+        IpcSender<Option<String>>,
+    ),
     /// Request live console messages for a given pipeline (true if desired, false otherwise).
     WantsLiveNotifications(PipelineId, bool),
     /// Request live notifications for a given set of timeline events for a given pipeline.
@@ -219,6 +272,198 @@ pub enum DevtoolScriptControlMsg {
     RequestAnimationFrame(PipelineId, String),
     /// Direct the given pipeline to reload the current page.
     Reload(PipelineId),
+    /// Retrieve the script sources the debugger knows of in the given pipeline.
+    ListSources(PipelineId, IpcSender<Vec<SourceInfo>>),
+    /// Retrieve the text of the script source with the given id.
+    GetSourceText(PipelineId, String, IpcSender<Option<String>>),
+    /// Pause whenever the given line is reached, including in the scripts loaded later.
+    SetBreakpoint(PipelineId, BreakpointLocation),
+    /// Remove a breakpoint set with `SetBreakpoint`.
+    RemoveBreakpoint(PipelineId, BreakpointLocation),
+    /// Whether to pause when an exception is thrown in the given pipeline.
+    PauseOnExceptions(PipelineId, bool),
+    /// Pause the given pipeline as soon as it runs script.
+    Interrupt(PipelineId),
+    /// Resume the paused pipeline, to pause again at the given limit if any.
+    Resume(PipelineId, Option<ResumeLimit>),
+    /// Retrieve the stack of the paused pipeline, youngest frame first.
+    GetFrames(PipelineId, IpcSender<Vec<FrameInfo>>),
+    /// Retrieve the scope chain of the frame at the given depth of the paused pipeline,
+    /// innermost scope first.
+    GetEnvironments(PipelineId, u32, IpcSender<Vec<EnvironmentInfo>>),
+    /// Retrieve the prototype and own properties of an object of the paused pipeline.
+    GetObjectProperties(PipelineId, String, IpcSender<Option<ObjectProperties>>),
+    /// Retrieve the stylesheets of the document of the given pipeline, in tree order.
+    GetStyleSheets(PipelineId, IpcSender<Vec<StyleSheetInfo>>),
+    /// Retrieve the text of the stylesheet with the given id.
+    GetStyleSheetText(PipelineId, String, IpcSender<Option<String>>),
+    /// Retrieve the rules of the stylesheet with the given id, nested rules following the
+    /// rule they are nested in.
+    GetStyleSheetRules(PipelineId, String, IpcSender<Option<Vec<CssRuleInfo>>>),
+    /// Replace the rules of the stylesheet with the given id with the ones of the given text,
+    /// restyling the document.
+    UpdateStyleSheet(PipelineId, String, String, IpcSender<bool>),
+    /// Toggle whether the stylesheet with the given id applies, replying with whether it is
+    /// now disabled.
+    ToggleStyleSheetDisabled(PipelineId, String, IpcSender<Option<bool>>),
+}
+
+/// A rule whose declarations apply to a node.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum AppliedRuleId {
+    /// The style attribute of the element with the given id.
+    Element(String),
+    /// The style rule at the given position in the stylesheet with the given id; each index
+    /// is into the rules nested in the rule the previous one points at.
+    StyleSheet(String, Vec<usize>),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AppliedRuleInfo {
+    pub id: AppliedRuleId,
+    pub selectors: Vec<String>,
+    /// The selectors that match the node.
+    pub matched_selectors: Vec<String>,
+    /// The declarations of the rule, as in the `cssText` of its style.
+    pub declarations: String,
+    /// The stylesheet the rule is in, unless it is the style of an element.
+    pub sheet: Option<StyleSheetInfo>,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A change to the declarations of a rule, as `CSSStyleDeclaration` makes it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum RuleModification {
+    SetProperty {
+        name: String,
+        value: String,
+        priority: String,
+    },
+    RemoveProperty(String),
+    /// Replace all the declarations with the ones of the given text.
+    SetText(String),
+}
+
+/// A stylesheet of a document.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StyleSheetInfo {
+    /// Identifies the stylesheet among the ones of its document.
+    pub id: String,
+    /// The URL the stylesheet was loaded from, unless it is inline.
+    pub href: Option<String>,
+    /// The URL of the document.
+    pub node_href: String,
+    pub title: Option<String>,
+    pub disabled: bool,
+    /// The position of the stylesheet in `document.styleSheets`.
+    pub index: usize,
+    pub rule_count: usize,
+}
+
+/// A rule of a stylesheet, and where it starts in the stylesheet text.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CssRuleInfo {
+    /// The CSSOM type of the rule, such as 1 for style rules and 4 for media rules.
+    pub type_: u16,
+    pub css_text: String,
+    /// The media query list of a media rule.
+    pub media_text: Option<String>,
+    /// Whether the condition of a conditional rule holds; true for other rules.
+    pub matches: bool,
+    /// The line of the stylesheet text the rule starts at, starting at 1.
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A script source known to the debugger.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SourceInfo {
+    /// Identifies the source among the sources of every pipeline.
+    pub id: String,
+    pub url: String,
+    /// How the source was introduced, such as `scriptElement` or `eval`.
+    pub introduction_type: Option<String>,
+}
+
+/// A line of the scripts of a URL.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BreakpointLocation {
+    pub url: String,
+    pub line: u32,
+}
+
+/// Where to pause again when resuming from a pause.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ResumeLimit {
+    /// Pause at the next line, including in the functions called in between.
+    StepIn,
+    /// Pause at the next line of the current frame, or in its caller once it returns.
+    StepOver,
+    /// Pause in the caller of the current frame once it returns.
+    StepOut,
+}
+
+/// Why a pipeline paused in the debugger.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum PauseReason {
+    Interrupted,
+    Breakpoint,
+    ResumeLimit,
+    DebuggerStatement,
+    /// An exception with the given value was thrown.
+    Exception(EvaluateJSReply),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PauseInfo {
+    pub why: PauseReason,
+    /// The frame the pipeline paused in.
+    pub frame: FrameInfo,
+}
+
+/// A frame of the stack of a paused pipeline.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FrameInfo {
+    /// The number of frames younger than this one.
+    pub depth: u32,
+    /// One of `call`, `eval`, `global` or `module`.
+    pub type_: String,
+    /// The name of the function called, if any.
+    pub callee: Option<String>,
+    pub this: EvaluateJSReply,
+    pub source: Option<SourceInfo>,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A scope of the scope chain of a frame.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnvironmentInfo {
+    /// One of `function`, `block`, `object` or `with`.
+    pub type_: String,
+    /// The name of the function of a function scope.
+    pub callee: Option<String>,
+    /// The variables of a function or block scope.
+    pub bindings: Vec<(String, EvaluateJSReply)>,
+    /// The object whose properties are the variables of an object or with scope.
+    pub object: Option<EvaluateJSReply>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PropertyInfo {
+    pub name: String,
+    /// The value of a data property, or undefined for an accessor.
+    pub value: EvaluateJSReply,
+    pub enumerable: bool,
+    pub writable: bool,
+    pub configurable: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ObjectProperties {
+    pub prototype: EvaluateJSReply,
+    pub properties: Vec<PropertyInfo>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -299,23 +544,94 @@ pub struct HttpRequest {
     pub pipeline_id: PipelineId,
     pub startedDateTime: Tm,
     pub timeStamp: i64,
-    pub connect_time: u64,
-    pub send_time: u64,
+    pub timings: NetworkTimings,
     pub is_xhr: bool,
+    pub mixed_content: Option<MixedContentStatus>,
+}
+
+/// How a request was treated by the
+/// [mixed content](https://w3c.github.io/webappsec-mixed-content/) checks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MixedContentStatus {
+    Blocked,
+    Upgraded,
+    Allowed,
+}
+
+/// How long each step of a request took, in milliseconds. Receiving the body is only
+/// known once the response ends.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkTimings {
+    /// Waiting for a connection to send the request over.
+    pub blocked: u64,
+    pub dns: u64,
+    pub connect: u64,
+    pub ssl: u64,
+    pub send: u64,
+    /// Waiting for the first byte of the response.
+    pub wait: u64,
+    pub receive: u64,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct HttpResponse {
     pub headers: Option<HeaderMap>,
     pub status: Option<(u16, Vec<u8>)>,
+    pub http_version: String,
+    /// The address of the server, or of the proxy the request was sent through.
+    pub remote_address: Option<SocketAddr>,
+    /// The security of the connection, if it was made over TLS.
+    pub security_info: Option<SecurityInfo>,
+    pub pipeline_id: PipelineId,
+}
+
+/// The body of a response, once it was received in full.
+#[derive(Debug, PartialEq)]
+pub struct HttpResponseEnd {
+    /// The decoded body, unless it was larger than devtools keep.
     pub body: Option<Vec<u8>>,
+    /// The size of the decoded body.
+    pub body_size: usize,
+    /// The size of the body as it was transferred, before decoding.
+    pub transferred_size: usize,
+    /// How long receiving the body took, in milliseconds.
+    pub receive_time: u64,
     pub pipeline_id: PipelineId,
 }
 
+/// The TLS parameters of a connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityInfo {
+    pub protocol_version: String,
+    pub cipher_suite: String,
+    /// The certificate chain of the server, starting with its own certificate.
+    pub certificates: Vec<CertificateInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CertificateInfo {
+    pub subject: CertificateName,
+    pub issuer: CertificateName,
+    pub serial_number: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub sha1_fingerprint: String,
+    pub sha256_fingerprint: String,
+}
+
+/// The parts of the distinguished name of a certificate subject or issuer which devtools show.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CertificateName {
+    pub common_name: Option<String>,
+    pub organization: Option<String>,
+    pub organizational_unit: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum NetworkEvent {
     HttpRequest(HttpRequest),
     HttpResponse(HttpResponse),
+    HttpResponseEnd(HttpResponseEnd),
 }
 
 impl TimelineMarker {
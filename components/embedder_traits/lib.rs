@@ -19,6 +19,7 @@ use keyboard_types::KeyboardEvent;
 use msg::constellation_msg::{InputMethodType, PipelineId, TopLevelBrowsingContextId};
 use servo_url::ServoUrl;
 use std::fmt::{Debug, Error, Formatter};
+use std::path::PathBuf;
 use webrender_api::units::{DeviceIntPoint, DeviceIntSize};
 
 /// A cursor for the window. This is different from a CSS cursor (see
@@ -122,13 +123,18 @@ pub enum EmbedderMsg {
     ResizeTo(DeviceIntSize),
     // Show an alert message.
     Alert(String, IpcSender<()>),
+    /// Ask the user to confirm a message, replying whether it was accepted.
+    Confirm(String, IpcSender<bool>),
+    /// Ask the user to enter some text, given a message and a default value. Replies
+    /// with `None` if the prompt was cancelled.
+    Prompt(String, String, IpcSender<Option<String>>),
     /// Wether or not to allow a pipeline to load a url.
     AllowNavigationRequest(PipelineId, ServoUrl),
     /// Whether or not to allow script to open a new tab/browser
     AllowOpeningBrowser(IpcSender<bool>),
     /// A new browser was created by script
     BrowserCreated(TopLevelBrowsingContextId),
-    /// Wether or not to unload a document
+    /// Show a beforeunload prompt asking wether or not to unload a document
     AllowUnload(IpcSender<bool>),
     /// Sends an unconsumed key event back to the embedder.
     Keyboard(KeyboardEvent),
@@ -146,6 +152,10 @@ pub enum EmbedderMsg {
     HistoryChanged(Vec<ServoUrl>, usize),
     /// Enter or exit fullscreen
     SetFullscreenState(bool),
+    /// Maximize or restore the window
+    SetMaximizedState(bool),
+    /// Minimize or restore the window
+    SetMinimizedState(bool),
     /// The load of a page has begun
     LoadStart,
     /// The load of a page has completed
@@ -166,6 +176,17 @@ pub enum EmbedderMsg {
     Shutdown,
     /// Report a complete sampled profile
     ReportProfile(Vec<u8>),
+    /// The cookies of the given registrable domain have changed.
+    CookiesChanged(String, Vec<CookieChange>),
+    /// Ask the user for credentials to answer an HTTP authentication challenge.
+    PromptCredentials(CredentialsRequest, IpcSender<Option<Credentials>>),
+    /// A download has started, and is being written to the given file.
+    DownloadStarted(DownloadId, ServoUrl, PathBuf),
+    /// A download has made progress. The number of bytes received so far, and the
+    /// total number of bytes, if known.
+    DownloadProgress(DownloadId, u64, Option<u64>),
+    /// A download has ended.
+    DownloadFinished(DownloadId, DownloadOutcome),
 }
 
 impl Debug for EmbedderMsg {
@@ -176,6 +197,8 @@ impl Debug for EmbedderMsg {
             EmbedderMsg::MoveTo(..) => write!(f, "MoveTo"),
             EmbedderMsg::ResizeTo(..) => write!(f, "ResizeTo"),
             EmbedderMsg::Alert(..) => write!(f, "Alert"),
+            EmbedderMsg::Confirm(..) => write!(f, "Confirm"),
+            EmbedderMsg::Prompt(..) => write!(f, "Prompt"),
             EmbedderMsg::AllowUnload(..) => write!(f, "AllowUnload"),
             EmbedderMsg::AllowNavigationRequest(..) => write!(f, "AllowNavigationRequest"),
             EmbedderMsg::Keyboard(..) => write!(f, "Keyboard"),
@@ -187,6 +210,8 @@ impl Debug for EmbedderMsg {
             EmbedderMsg::CloseBrowser => write!(f, "CloseBrowser"),
             EmbedderMsg::HistoryChanged(..) => write!(f, "HistoryChanged"),
             EmbedderMsg::SetFullscreenState(..) => write!(f, "SetFullscreenState"),
+            EmbedderMsg::SetMaximizedState(..) => write!(f, "SetMaximizedState"),
+            EmbedderMsg::SetMinimizedState(..) => write!(f, "SetMinimizedState"),
             EmbedderMsg::LoadStart => write!(f, "LoadStart"),
             EmbedderMsg::LoadComplete => write!(f, "LoadComplete"),
             EmbedderMsg::Panic(..) => write!(f, "Panic"),
@@ -198,10 +223,69 @@ impl Debug for EmbedderMsg {
             EmbedderMsg::AllowOpeningBrowser(..) => write!(f, "AllowOpeningBrowser"),
             EmbedderMsg::BrowserCreated(..) => write!(f, "BrowserCreated"),
             EmbedderMsg::ReportProfile(..) => write!(f, "ReportProfile"),
+            EmbedderMsg::CookiesChanged(..) => write!(f, "CookiesChanged"),
+            EmbedderMsg::PromptCredentials(..) => write!(f, "PromptCredentials"),
+            EmbedderMsg::DownloadStarted(..) => write!(f, "DownloadStarted"),
+            EmbedderMsg::DownloadProgress(..) => write!(f, "DownloadProgress"),
+            EmbedderMsg::DownloadFinished(..) => write!(f, "DownloadFinished"),
         }
     }
 }
 
+/// The scheme of an HTTP authentication challenge.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AuthenticationScheme {
+    /// [Basic](https://tools.ietf.org/html/rfc7617) authentication.
+    Basic,
+    /// [Digest](https://tools.ietf.org/html/rfc7616) authentication.
+    Digest,
+}
+
+/// An HTTP authentication challenge for which the user is asked for credentials.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CredentialsRequest {
+    /// The ASCII serialization of the origin that sent the challenge.
+    pub origin: String,
+    /// The protection space the credentials are asked for.
+    pub realm: String,
+    /// The scheme the credentials will be sent with.
+    pub scheme: AuthenticationScheme,
+    /// Whether the challenge comes from a proxy rather than from the origin.
+    pub for_proxy: bool,
+}
+
+/// A user name and password supplied by the user.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Credentials {
+    pub user_name: String,
+    pub password: String,
+}
+
+/// A cookie that was stored or removed.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CookieChange {
+    pub domain: String,
+    pub path: String,
+    pub name: String,
+    /// Whether the cookie was removed, rather than stored.
+    pub removed: bool,
+}
+
+/// Identifies a download, for the lifetime of the process.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct DownloadId(pub u64);
+
+/// How a download ended.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DownloadOutcome {
+    /// The whole resource was written to the file.
+    Complete,
+    /// The download was cancelled, and the partial file removed.
+    Cancelled,
+    /// The download failed, for the given reason.
+    Failed(String),
+}
+
 /// Filter for file selection;
 /// the `String` content is expected to be extension (e.g, "doc", without the prefixing ".")
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -60,6 +60,7 @@ pub enum Resource {
     PresentationalHintsCSS,
     QuirksModeCSS,
     RippyPNG,
+    DebuggerJS,
 }
 
 pub trait ResourceReaderMethods {
@@ -94,6 +95,7 @@ fn resources_for_tests() -> Box<dyn ResourceReaderMethods + Sync + Send> {
                 Resource::PresentationalHintsCSS => "presentational-hints.css",
                 Resource::QuirksModeCSS => "quirks-mode.css",
                 Resource::RippyPNG => "rippy.png",
+                Resource::DebuggerJS => "debugger.js",
             };
             let mut path = env::current_exe().unwrap();
             path = path.canonicalize().unwrap();
@@ -21,6 +21,7 @@ use crate::display_list::items::{IframeDisplayItem, OpaqueNode};
 use crate::display_list::items::{PopAllTextShadowsDisplayItem, PushTextShadowDisplayItem};
 use crate::display_list::items::{StackingContext, StackingContextType, StickyFrameData};
 use crate::display_list::items::{TextOrientation, WebRenderImageInfo};
+use crate::display_list::PrintData;
 use crate::display_list::ToLayout;
 use crate::flow::{BaseFlow, Flow, FlowFlags};
 use crate::flow_ref::FlowRef;
@@ -322,6 +323,9 @@ pub struct DisplayListBuildState<'a> {
 
     /// Stores text runs to answer text queries used to place a cursor inside text.
     pub indexable_text: IndexableText,
+
+    /// Stores the images and page breaks needed to print the display list.
+    pub print_data: PrintData,
 }
 
 impl<'a> DisplayListBuildState<'a> {
@@ -342,6 +346,7 @@ impl<'a> DisplayListBuildState<'a> {
             ),
             iframe_sizes: Vec::new(),
             indexable_text: IndexableText::default(),
+            print_data: PrintData::default(),
         }
     }
 
@@ -1521,6 +1526,9 @@ impl Fragment {
         }
 
         self.restyle_damage.remove(ServoRestyleDamage::REPAINT);
+        state
+            .print_data
+            .add_break_hint(&*self.style, &stacking_relative_border_box);
         self.build_display_list_no_damage(
             state,
             stacking_relative_border_box,
@@ -1823,6 +1831,7 @@ impl Fragment {
                 // Place the image into the display list.
                 if let Some(ref image) = image_fragment.image {
                     if let Some(id) = image.id {
+                        state.print_data.images.insert(id, image.clone());
                         let base = create_base_display_item(state);
                         state.add_image_item(
                             base,
@@ -3009,6 +3018,13 @@ impl IndexableText {
                 .range_index_of_advance(&item[0].range, offset.x),
         )
     }
+
+    /// Returns the text runs of all the text items.
+    pub fn text_runs(&self) -> impl Iterator<Item = &TextRun> {
+        self.inner
+            .values()
+            .flat_map(|items| items.iter().map(|item| &*item.text_run))
+    }
 }
 
 trait ToF32Px {
@@ -8,6 +8,8 @@ pub use self::builder::IndexableText;
 pub use self::builder::StackingContextCollectionFlags;
 pub use self::builder::StackingContextCollectionState;
 pub use self::conversions::ToLayout;
+pub use self::print::{default_page_margins, page_margins, page_margins_from_declarations};
+pub use self::print::{paginate, print_to_pdf, BreakHint, PrintData};
 pub use self::webrender_helpers::WebRenderDisplayListConverter;
 
 mod background;
@@ -16,4 +18,5 @@ mod builder;
 mod conversions;
 mod gradient;
 pub mod items;
+mod print;
 mod webrender_helpers;
@@ -0,0 +1,956 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Printing display lists on pages of paper, as PDF documents.
+//!
+//! The document is cut into pages the way it is laid out in its viewport, scaled down when it
+//! is wider than the pages. Page breaks are moved up so as not to cut through lines of text,
+//! images, and boxes with `break-inside: avoid`, and are forced by `break-before` and
+//! `break-after`. The margins of the pages come from the `@page` rules of the document.
+//!
+//! Gradients, shadows, filters other than opacity, rounded corners and the contents of iframes
+//! are not printed yet, and fonts are embedded whole.
+//!
+//! https://www.adobe.com/content/dam/acom/en/devnet/pdf/pdfs/PDF32000_2008.pdf
+
+use crate::display_list::items::{ClipScrollNodeIndex, ClipScrollNodeType, DisplayItem};
+use crate::display_list::items::{DisplayList, DisplayListSection};
+use crate::display_list::IndexableText;
+use app_units::Au;
+use euclid::{Rect, TypedSideOffsets2D, TypedSize2D};
+use fnv::FnvHashMap;
+use gfx::text::TextRun;
+use net_traits::image::base::Image;
+use pixels::PixelFormat;
+use script_traits::PrintSettings;
+use servo_geometry::MaxRect;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use style::properties::{ComputedValues, PropertyDeclaration, PropertyDeclarationBlock};
+use style::shared_lock::StylesheetGuards;
+use style::stylist::Stylist;
+use style::values::computed::{BreakBetween, BreakWithin};
+use style::values::generics::length::LengthPercentageOrAuto;
+use style::values::specified::{self, LengthPercentage, NoCalcLength};
+use style_traits::CSSPixel;
+use webrender_api::units::{LayoutPoint, LayoutRect, LayoutSize};
+use webrender_api::{BorderDetails, BorderStyle, ColorF, FilterOp, FontInstanceKey, ImageKey};
+
+/// The number of PDF points, 1/72 of an inch, in a CSS pixel, 1/96 of an inch.
+const POINTS_PER_PX: f32 = 0.75;
+
+/// The margin of pages the document gives none to, 1cm as in
+/// https://w3c.github.io/webdriver/#print-page.
+const DEFAULT_PAGE_MARGIN: f32 = 96. / 2.54;
+
+/// A box whose style asks for or against page breaks around or inside it.
+pub struct BreakHint {
+    /// The top of the border box, in the coordinates of its reference frame.
+    top: f32,
+    /// The bottom of the border box, in the coordinates of its reference frame.
+    bottom: f32,
+    before: BreakBetween,
+    after: BreakBetween,
+    inside: BreakWithin,
+}
+
+/// What printing needs of a display list besides its items, collected while it is built.
+#[derive(Default)]
+pub struct PrintData {
+    /// The images of the display list, by key.
+    pub images: FnvHashMap<ImageKey, Arc<Image>>,
+    pub break_hints: Vec<BreakHint>,
+}
+
+impl BreakHint {
+    pub fn new(
+        top: f32,
+        bottom: f32,
+        before: BreakBetween,
+        after: BreakBetween,
+        inside: BreakWithin,
+    ) -> BreakHint {
+        BreakHint {
+            top,
+            bottom,
+            before,
+            after,
+            inside,
+        }
+    }
+}
+
+impl PrintData {
+    /// Remember the page breaks the style of a box asks for, if any.
+    pub fn add_break_hint(&mut self, style: &ComputedValues, border_box: &Rect<Au>) {
+        let box_style = style.get_box();
+        if box_style.break_before == BreakBetween::Auto &&
+            box_style.break_after == BreakBetween::Auto &&
+            box_style.break_inside == BreakWithin::Auto
+        {
+            return;
+        }
+        self.break_hints.push(BreakHint::new(
+            border_box.origin.y.to_f32_px(),
+            border_box.max_y().to_f32_px(),
+            box_style.break_before,
+            box_style.break_after,
+            box_style.break_inside,
+        ));
+    }
+}
+
+fn is_forced_break(value: BreakBetween) -> bool {
+    match value {
+        BreakBetween::Always | BreakBetween::Page | BreakBetween::Left | BreakBetween::Right => {
+            true
+        },
+        BreakBetween::Auto | BreakBetween::Avoid => false,
+    }
+}
+
+/// The margins the `@page` rules of the document give to pages of the given size.
+pub fn page_margins(
+    stylist: &Stylist,
+    guards: &StylesheetGuards,
+    page_size: &TypedSize2D<f32, CSSPixel>,
+) -> TypedSideOffsets2D<f32, CSSPixel> {
+    // The author's rules come last, to override those of the user agent and the user.
+    let mut blocks = vec![];
+    for (data, origin) in stylist.iter_extra_data_origins_rev() {
+        let guard = guards.for_origin(origin);
+        for rule in &data.pages {
+            blocks.push(rule.read_with(guard).block.read_with(guard));
+        }
+    }
+    page_margins_from_declarations(blocks, page_size)
+}
+
+/// The margins the declaration blocks of `@page` rules, in cascade order, give to pages of
+/// the given size.
+pub fn page_margins_from_declarations<'a, I>(
+    blocks: I,
+    page_size: &TypedSize2D<f32, CSSPixel>,
+) -> TypedSideOffsets2D<f32, CSSPixel>
+where
+    I: IntoIterator<Item = &'a PropertyDeclarationBlock>,
+{
+    let mut margins = default_page_margins();
+    for block in blocks {
+        for declaration in block.declarations() {
+            match *declaration {
+                PropertyDeclaration::MarginTop(ref value) => {
+                    set_page_margin(&mut margins.top, value, page_size.height)
+                },
+                PropertyDeclaration::MarginRight(ref value) => {
+                    set_page_margin(&mut margins.right, value, page_size.width)
+                },
+                PropertyDeclaration::MarginBottom(ref value) => {
+                    set_page_margin(&mut margins.bottom, value, page_size.height)
+                },
+                PropertyDeclaration::MarginLeft(ref value) => {
+                    set_page_margin(&mut margins.left, value, page_size.width)
+                },
+                _ => {},
+            }
+        }
+    }
+    margins
+}
+
+pub fn default_page_margins() -> TypedSideOffsets2D<f32, CSSPixel> {
+    TypedSideOffsets2D::new_all_same(DEFAULT_PAGE_MARGIN)
+}
+
+/// Percentages are of the width of the page for the left and right margins, and of its height
+/// for the top and bottom ones.
+fn set_page_margin(margin: &mut f32, value: &specified::LengthPercentageOrAuto, basis: f32) {
+    *margin = match *value {
+        LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Length(
+            NoCalcLength::Absolute(ref length),
+        )) => length.to_px(),
+        LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Percentage(percentage)) => {
+            basis * percentage.0
+        },
+        // Pages have no font or viewport for other lengths to be relative to.
+        _ => return,
+    };
+}
+
+/// Print the display list on pages, as a PDF document.
+pub fn print_to_pdf(
+    display_list: &DisplayList,
+    indexable_text: &IndexableText,
+    print_data: &PrintData,
+    margins: TypedSideOffsets2D<f32, CSSPixel>,
+    settings: &PrintSettings,
+) -> Vec<u8> {
+    let page_size = settings.page_size;
+    let content_width = (page_size.width - margins.left - margins.right).max(1.);
+    let content_height = (page_size.height - margins.top - margins.bottom).max(1.);
+
+    let document_size = display_list.bounds().size;
+    let mut scale = settings.scale;
+    if settings.shrink_to_fit && document_size.width * scale > content_width {
+        scale = content_width / document_size.width;
+    }
+
+    let tops = page_tops(display_list, print_data, content_height / scale);
+    let mut writer = PdfWriter::new(indexable_text, &print_data.images);
+    let mut pages = vec![];
+    for (index, &top) in tops.iter().enumerate() {
+        let number = index + 1;
+        if !settings.page_ranges.is_empty() &&
+            !settings
+                .page_ranges
+                .iter()
+                .any(|&(first, last)| first <= number && number <= last)
+        {
+            continue;
+        }
+        let bottom = tops.get(index + 1).cloned().unwrap_or(document_size.height);
+
+        let mut content = String::new();
+        // Draw in CSS pixels, downwards from the top left corner of the page, as layout does.
+        write_operation(
+            &mut content,
+            &[
+                POINTS_PER_PX,
+                0.,
+                0.,
+                -POINTS_PER_PX,
+                0.,
+                page_size.height * POINTS_PER_PX,
+            ],
+            "cm",
+        );
+        write_operation(
+            &mut content,
+            &[margins.left, margins.top, content_width, content_height],
+            "re W n",
+        );
+        write_operation(
+            &mut content,
+            &[
+                scale,
+                0.,
+                0.,
+                scale,
+                margins.left,
+                margins.top - top * scale,
+            ],
+            "cm",
+        );
+        write_operation(
+            &mut content,
+            &[0., top, document_size.width, bottom - top],
+            "re W n",
+        );
+        writer.write_items(&mut content, display_list, top, bottom, settings.background);
+        pages.push(content);
+    }
+    writer.finish(pages, &page_size)
+}
+
+/// The top of each page, in the coordinates of the display list.
+fn page_tops(display_list: &DisplayList, print_data: &PrintData, page_height: f32) -> Vec<f32> {
+    // The page breaks would rather not cut through lines of text and images.
+    let mut unbreakable: Vec<(f32, f32)> = vec![];
+    let mut reference_frames = 0;
+    let mut stacking_contexts = vec![];
+    for item in &display_list.list {
+        match *item {
+            DisplayItem::PushStackingContext(ref item) => {
+                let establishes_reference_frame =
+                    item.stacking_context.established_reference_frame.is_some();
+                if establishes_reference_frame {
+                    reference_frames += 1;
+                }
+                stacking_contexts.push(establishes_reference_frame);
+            },
+            DisplayItem::PopStackingContext(_) => {
+                if stacking_contexts.pop() == Some(true) {
+                    reference_frames -= 1;
+                }
+            },
+            DisplayItem::Text(_) | DisplayItem::Image(_) if reference_frames == 0 => {
+                let bounds = item.bounds();
+                unbreakable.push((bounds.min_y(), bounds.max_y()));
+            },
+            _ => {},
+        }
+    }
+    paginate(
+        display_list.bounds().size.height,
+        page_height,
+        unbreakable,
+        &print_data.break_hints,
+    )
+}
+
+/// The top of each page of a document of the given height. The page breaks are forced by
+/// `break-before` and `break-after`, and moved up so as not to cut through the `unbreakable`
+/// spans and the boxes with `break-inside: avoid`, unless they are taller than pages.
+pub fn paginate(
+    document_height: f32,
+    page_height: f32,
+    mut unbreakable: Vec<(f32, f32)>,
+    break_hints: &[BreakHint],
+) -> Vec<f32> {
+    let mut forced = vec![];
+    for hint in break_hints {
+        if hint.inside == BreakWithin::Avoid {
+            unbreakable.push((hint.top, hint.bottom));
+        }
+        if is_forced_break(hint.before) {
+            forced.push(hint.top);
+        }
+        if is_forced_break(hint.after) {
+            forced.push(hint.bottom);
+        }
+    }
+    unbreakable.retain(|&(start, end)| end - start <= page_height);
+    forced.retain(|&position| position < document_height);
+    forced.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut tops = vec![0.];
+    let mut top = 0.;
+    loop {
+        let mut bottom = top + page_height;
+        if let Some(&position) = forced
+            .iter()
+            .find(|&&position| position > top && position < bottom)
+        {
+            top = position;
+            tops.push(top);
+            continue;
+        }
+        if bottom >= document_height {
+            return tops;
+        }
+        while let Some(start) = unbreakable
+            .iter()
+            .filter(|&&(start, end)| start > top && start < bottom && end > bottom)
+            .map(|&(start, _)| start)
+            .fold(None, |min: Option<f32>, start| {
+                Some(min.map_or(start, |min| min.min(start)))
+            })
+        {
+            bottom = start;
+        }
+        top = bottom;
+        tops.push(top);
+    }
+}
+
+/// A number the way PDF expects it, without exponent.
+struct Number(f32);
+
+impl fmt::Display for Number {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let value = if self.0.is_finite() { self.0 } else { 0. };
+        let digits = format!("{:.3}", value);
+        let digits = digits.trim_end_matches('0').trim_end_matches('.');
+        formatter.write_str(if digits == "-0" || digits.is_empty() {
+            "0"
+        } else {
+            digits
+        })
+    }
+}
+
+fn write_operation(content: &mut String, operands: &[f32], operator: &str) {
+    for operand in operands {
+        let _ = write!(content, "{} ", Number(*operand));
+    }
+    content.push_str(operator);
+    content.push('\n');
+}
+
+fn write_rect(content: &mut String, rect: &LayoutRect, operator: &str) {
+    write_operation(
+        content,
+        &[
+            rect.origin.x,
+            rect.origin.y,
+            rect.size.width,
+            rect.size.height,
+        ],
+        operator,
+    );
+}
+
+/// The state of the drawing of the stacking contexts that contain an item.
+struct StackingContextState {
+    /// Whether the stacking context established a reference frame, with its own coordinates.
+    establishes_reference_frame: bool,
+    /// The opacity everything in the stacking context is drawn with.
+    opacity: f32,
+}
+
+/// Writes the objects of a PDF document, and the resources its pages use.
+struct PdfWriter<'a> {
+    /// The objects of the document, numbered from 1.
+    objects: Vec<Vec<u8>>,
+    text_runs: FnvHashMap<FontInstanceKey, &'a TextRun>,
+    images: &'a FnvHashMap<ImageKey, Arc<Image>>,
+    /// The resource names of the fonts embedded so far, by font template, or `None` for those
+    /// that can't be.
+    fonts: FnvHashMap<usize, Option<String>>,
+    /// The resource names of the images embedded so far, or `None` for those that can't be.
+    image_names: FnvHashMap<ImageKey, Option<String>>,
+    /// The resource names of the graphics states setting opacities, by opacity in thousandths.
+    opacities: FnvHashMap<u32, String>,
+    /// The `/Font`, `/XObject` and `/ExtGState` resources of the pages.
+    resources: [Vec<(String, usize)>; 3],
+}
+
+const FONT_RESOURCES: usize = 0;
+const IMAGE_RESOURCES: usize = 1;
+const GRAPHICS_STATE_RESOURCES: usize = 2;
+
+impl<'a> PdfWriter<'a> {
+    fn new(
+        indexable_text: &'a IndexableText,
+        images: &'a FnvHashMap<ImageKey, Arc<Image>>,
+    ) -> PdfWriter<'a> {
+        PdfWriter {
+            objects: vec![],
+            text_runs: indexable_text
+                .text_runs()
+                .map(|text_run| (text_run.font_key, text_run))
+                .collect(),
+            images,
+            fonts: FnvHashMap::default(),
+            image_names: FnvHashMap::default(),
+            opacities: FnvHashMap::default(),
+            resources: [vec![], vec![], vec![]],
+        }
+    }
+
+    /// Add an object to the document, returning its number.
+    fn add_object(&mut self, object: Vec<u8>) -> usize {
+        self.objects.push(object);
+        self.objects.len()
+    }
+
+    fn add_stream(&mut self, dictionary: &str, data: &[u8]) -> usize {
+        let mut object =
+            format!("<< {} /Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        self.add_object(object)
+    }
+
+    fn add_resource(&mut self, kind: usize, prefix: &str, object: usize) -> String {
+        let name = format!("{}{}", prefix, self.resources[kind].len());
+        self.resources[kind].push((name.clone(), object));
+        name
+    }
+
+    /// Write the drawing of the items that may show between the given top and bottom.
+    fn write_items(
+        &mut self,
+        content: &mut String,
+        display_list: &DisplayList,
+        top: f32,
+        bottom: f32,
+        background: bool,
+    ) {
+        let mut stacking_contexts = vec![StackingContextState {
+            establishes_reference_frame: false,
+            opacity: 1.,
+        }];
+        let mut reference_frames = 0;
+        for item in &display_list.list {
+            let opacity = stacking_contexts.last().map_or(1., |state| state.opacity);
+            match *item {
+                DisplayItem::PushStackingContext(ref item) => {
+                    let stacking_context = &item.stacking_context;
+                    let opacity =
+                        stacking_context
+                            .filters
+                            .iter()
+                            .fold(opacity, |opacity, filter| match *filter {
+                                FilterOp::Opacity(_, value) => opacity * value,
+                                _ => opacity,
+                            });
+                    let establishes_reference_frame =
+                        stacking_context.established_reference_frame.is_some();
+                    if establishes_reference_frame {
+                        reference_frames += 1;
+                        let origin = stacking_context.bounds.origin;
+                        content.push_str("q\n");
+                        write_operation(content, &[1., 0., 0., 1., origin.x, origin.y], "cm");
+                        if let Some(ref transform) = stacking_context.transform {
+                            write_operation(
+                                content,
+                                &[
+                                    transform.m11,
+                                    transform.m12,
+                                    transform.m21,
+                                    transform.m22,
+                                    transform.m41,
+                                    transform.m42,
+                                ],
+                                "cm",
+                            );
+                        }
+                    }
+                    stacking_contexts.push(StackingContextState {
+                        establishes_reference_frame,
+                        opacity,
+                    });
+                    continue;
+                },
+                DisplayItem::PopStackingContext(_) => {
+                    if let Some(state) = stacking_contexts.pop() {
+                        if state.establishes_reference_frame {
+                            reference_frames -= 1;
+                            content.push_str("Q\n");
+                        }
+                    }
+                    continue;
+                },
+                _ => {},
+            }
+
+            let base = item.base();
+            let is_background = match base.section {
+                DisplayListSection::BackgroundAndBorders |
+                DisplayListSection::BlockBackgroundsAndBorders => true,
+                _ => false,
+            };
+            let clip = clip_rect(display_list, item);
+            // Only the items in the coordinates of the page can be told to be outside of it.
+            if clip.size.width <= 0. ||
+                clip.size.height <= 0. ||
+                (reference_frames == 0 && (clip.max_y() <= top || clip.min_y() >= bottom))
+            {
+                continue;
+            }
+
+            match *item {
+                DisplayItem::Rectangle(ref item) => {
+                    if !background && is_background {
+                        continue;
+                    }
+                    content.push_str("q\n");
+                    self.write_color(content, &item.item.color, opacity, "rg");
+                    write_rect(content, &clip, "re f");
+                    content.push_str("Q\n");
+                },
+                DisplayItem::Line(ref item) => {
+                    let area = item.item.area.intersection(&clip).unwrap_or(clip);
+                    content.push_str("q\n");
+                    self.write_color(content, &item.item.color, opacity, "rg");
+                    write_rect(content, &area, "re f");
+                    content.push_str("Q\n");
+                },
+                DisplayItem::Border(ref item) => {
+                    let border = match item.item.details {
+                        BorderDetails::Normal(ref border) => border,
+                        BorderDetails::NinePatch(_) => continue,
+                    };
+                    let bounds = item.item.bounds;
+                    let widths = item.item.widths;
+                    let sides = [
+                        (
+                            &border.top,
+                            LayoutRect::new(
+                                bounds.origin,
+                                LayoutSize::new(bounds.size.width, widths.top),
+                            ),
+                        ),
+                        (
+                            &border.bottom,
+                            LayoutRect::new(
+                                LayoutPoint::new(bounds.origin.x, bounds.max_y() - widths.bottom),
+                                LayoutSize::new(bounds.size.width, widths.bottom),
+                            ),
+                        ),
+                        (
+                            &border.left,
+                            LayoutRect::new(
+                                bounds.origin,
+                                LayoutSize::new(widths.left, bounds.size.height),
+                            ),
+                        ),
+                        (
+                            &border.right,
+                            LayoutRect::new(
+                                LayoutPoint::new(bounds.max_x() - widths.right, bounds.origin.y),
+                                LayoutSize::new(widths.right, bounds.size.height),
+                            ),
+                        ),
+                    ];
+                    content.push_str("q\n");
+                    write_rect(content, &clip, "re W n");
+                    for &(side, ref rect) in &sides {
+                        if side.style == BorderStyle::None || side.style == BorderStyle::Hidden {
+                            continue;
+                        }
+                        // Every other style is drawn as solid.
+                        self.write_color(content, &side.color, opacity, "rg");
+                        write_rect(content, rect, "re f");
+                    }
+                    content.push_str("Q\n");
+                },
+                DisplayItem::Text(ref item) => {
+                    let text_run = match self.text_runs.get(&item.item.font_key) {
+                        Some(text_run) => *text_run,
+                        None => continue,
+                    };
+                    let font = match self.font(text_run) {
+                        Some(font) => font,
+                        None => continue,
+                    };
+                    content.push_str("q\n");
+                    write_rect(content, &clip, "re W n");
+                    self.write_color(content, &item.item.color, opacity, "rg");
+                    let _ = writeln!(
+                        content,
+                        "BT /{} {} Tf",
+                        font,
+                        Number(text_run.actual_pt_size.to_f32_px())
+                    );
+                    // Glyphs are drawn upwards, so flip them back.
+                    for glyph in &item.data {
+                        let _ = writeln!(
+                            content,
+                            "1 0 0 -1 {} {} Tm <{:04X}> Tj",
+                            Number(glyph.point.x),
+                            Number(glyph.point.y),
+                            glyph.index
+                        );
+                    }
+                    content.push_str("ET\nQ\n");
+                },
+                DisplayItem::Image(ref item) => {
+                    if !background && is_background {
+                        continue;
+                    }
+                    let image = match self.image(item.item.image_key) {
+                        Some(image) => image,
+                        None => continue,
+                    };
+                    let item = &item.item;
+                    let tile_width = item.stretch_size.width + item.tile_spacing.width;
+                    let tile_height = item.stretch_size.height + item.tile_spacing.height;
+                    if tile_width <= 0. || tile_height <= 0. {
+                        continue;
+                    }
+                    content.push_str("q\n");
+                    write_rect(content, &clip, "re W n");
+                    self.write_opacity(content, opacity);
+                    // Only draw the tiles that are not clipped out.
+                    let first_column = ((clip.min_x() - item.bounds.min_x()) / tile_width)
+                        .floor()
+                        .max(0.);
+                    let first_row = ((clip.min_y() - item.bounds.min_y()) / tile_height)
+                        .floor()
+                        .max(0.);
+                    let mut y = item.bounds.min_y() + first_row * tile_height;
+                    while y < clip.max_y().min(item.bounds.max_y()) {
+                        let mut x = item.bounds.min_x() + first_column * tile_width;
+                        while x < clip.max_x().min(item.bounds.max_x()) {
+                            // Images are drawn upwards in the unit square, so flip them back.
+                            content.push_str("q\n");
+                            write_operation(
+                                content,
+                                &[
+                                    item.stretch_size.width,
+                                    0.,
+                                    0.,
+                                    -item.stretch_size.height,
+                                    x,
+                                    y + item.stretch_size.height,
+                                ],
+                                "cm",
+                            );
+                            let _ = writeln!(content, "/{} Do\nQ", image);
+                            x += tile_width;
+                        }
+                        y += tile_height;
+                    }
+                    content.push_str("Q\n");
+                },
+                // TODO: Print gradients, shadows and iframes.
+                DisplayItem::Gradient(_) |
+                DisplayItem::RadialGradient(_) |
+                DisplayItem::BoxShadow(_) |
+                DisplayItem::PushTextShadow(_) |
+                DisplayItem::PopAllTextShadows(_) |
+                DisplayItem::Iframe(_) |
+                DisplayItem::DefineClipScrollNode(_) |
+                DisplayItem::PushStackingContext(_) |
+                DisplayItem::PopStackingContext(_) => {},
+            }
+        }
+    }
+
+    fn write_color(&mut self, content: &mut String, color: &ColorF, opacity: f32, operator: &str) {
+        self.write_opacity(content, color.a * opacity);
+        write_operation(content, &[color.r, color.g, color.b], operator);
+    }
+
+    /// Set the opacity of what is drawn next, until the graphics state is restored.
+    fn write_opacity(&mut self, content: &mut String, opacity: f32) {
+        let thousandths = (opacity.max(0.).min(1.) * 1000.).round() as u32;
+        let name = match self.opacities.get(&thousandths) {
+            Some(name) => name.clone(),
+            None => {
+                let opacity = Number(thousandths as f32 / 1000.);
+                let object = format!("<< /Type /ExtGState /ca {} /CA {} >>", opacity, opacity);
+                let object = self.add_object(object.into_bytes());
+                let name = self.add_resource(GRAPHICS_STATE_RESOURCES, "G", object);
+                self.opacities.insert(thousandths, name.clone());
+                name
+            },
+        };
+        let _ = writeln!(content, "/{} gs", name);
+    }
+
+    /// The resource name of the font of a text run, embedding it the first time.
+    fn font(&mut self, text_run: &TextRun) -> Option<String> {
+        let template = &*text_run.font_template as *const _ as usize;
+        if let Some(name) = self.fonts.get(&template) {
+            return name.clone();
+        }
+        let bytes = text_run.font_template.bytes();
+        // Glyphs are shown by their index, which OpenType fonts with CFF outlines use as CIDs.
+        let (subtype, file_key, file_dictionary) = match bytes.get(0..4) {
+            Some(b"OTTO") => ("CIDFontType0", "FontFile3", "/Subtype /OpenType"),
+            Some(&[0, 1, 0, 0]) | Some(b"true") => ("CIDFontType2", "FontFile2", ""),
+            _ => {
+                warn!("Can't embed font {:?} in PDF.", text_run.font_template);
+                self.fonts.insert(template, None);
+                return None;
+            },
+        };
+        let base_font = format!("/Font{}", self.resources[FONT_RESOURCES].len());
+        let file = self.add_stream(
+            &format!("{} /Length1 {}", file_dictionary, bytes.len()),
+            &bytes,
+        );
+        let metrics = &text_run.font_metrics;
+        let em = text_run.actual_pt_size.to_f32_px().max(1.);
+        let units = |length: Au| Number(length.to_f32_px() / em * 1000.);
+        let descriptor = self.add_object(
+            format!(
+                "<< /Type /FontDescriptor /FontName {} /Flags 4 /FontBBox [0 {} 1000 {}] \
+                 /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{} {} 0 R >>",
+                base_font,
+                units(-metrics.descent),
+                units(metrics.ascent),
+                units(metrics.ascent),
+                units(-metrics.descent),
+                units(metrics.ascent),
+                file_key,
+                file,
+            )
+            .into_bytes(),
+        );
+        let cid_to_gid_map = if subtype == "CIDFontType2" {
+            " /CIDToGIDMap /Identity"
+        } else {
+            ""
+        };
+        let descendant = self.add_object(
+            format!(
+                "<< /Type /Font /Subtype /{} /BaseFont {} /CIDSystemInfo << /Registry (Adobe) \
+                 /Ordering (Identity) /Supplement 0 >> /FontDescriptor {} 0 R{} >>",
+                subtype, base_font, descriptor, cid_to_gid_map,
+            )
+            .into_bytes(),
+        );
+        let font = self.add_object(
+            format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont {} /Encoding /Identity-H \
+                 /DescendantFonts [{} 0 R] >>",
+                base_font, descendant,
+            )
+            .into_bytes(),
+        );
+        let name = self.add_resource(FONT_RESOURCES, "F", font);
+        self.fonts.insert(template, Some(name.clone()));
+        Some(name)
+    }
+
+    /// The resource name of an image, embedding it the first time.
+    fn image(&mut self, key: ImageKey) -> Option<String> {
+        if let Some(name) = self.image_names.get(&key) {
+            return name.clone();
+        }
+        let image = match self.images.get(&key) {
+            Some(image) => image.clone(),
+            // Canvases and videos are not kept by layout.
+            None => {
+                self.image_names.insert(key, None);
+                return None;
+            },
+        };
+        let (color_space, channels, alpha) = match image.format {
+            PixelFormat::K8 => ("DeviceGray", 1, None),
+            PixelFormat::KA8 => ("DeviceGray", 2, Some(1)),
+            PixelFormat::RGB8 => ("DeviceRGB", 3, None),
+            PixelFormat::RGBA8 | PixelFormat::BGRA8 => ("DeviceRGB", 4, Some(3)),
+        };
+        let color_channels = if color_space == "DeviceRGB" { 3 } else { 1 };
+        let pixels = image.bytes.chunks(channels);
+        let mut colors = Vec::with_capacity(pixels.len() * color_channels);
+        let mut alphas = Vec::with_capacity(if alpha.is_some() { pixels.len() } else { 0 });
+        for pixel in pixels {
+            // Loaded images have their colors premultiplied by their alpha.
+            let a = alpha.map_or(255, |index| pixel[index]);
+            let unpremultiply = |value: u8| {
+                if a == 0 {
+                    0
+                } else {
+                    (u32::from(value) * 255 / u32::from(a)).min(255) as u8
+                }
+            };
+            match image.format {
+                PixelFormat::BGRA8 => colors.extend(
+                    [pixel[2], pixel[1], pixel[0]]
+                        .iter()
+                        .map(|&value| unpremultiply(value)),
+                ),
+                _ => colors.extend(
+                    pixel[..color_channels]
+                        .iter()
+                        .map(|&value| unpremultiply(value)),
+                ),
+            }
+            if alpha.is_some() {
+                alphas.push(a);
+            }
+        }
+        let dictionary = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8",
+            image.width, image.height,
+        );
+        let mask = if alpha.is_some() {
+            let mask = self.add_stream(&format!("{} /ColorSpace /DeviceGray", dictionary), &alphas);
+            format!(" /SMask {} 0 R", mask)
+        } else {
+            String::new()
+        };
+        let object = self.add_stream(
+            &format!("{} /ColorSpace /{}{}", dictionary, color_space, mask),
+            &colors,
+        );
+        let name = self.add_resource(IMAGE_RESOURCES, "I", object);
+        self.image_names.insert(key, Some(name.clone()));
+        Some(name)
+    }
+
+    /// Write out the document with the given content streams for its pages.
+    fn finish(mut self, pages: Vec<String>, page_size: &TypedSize2D<f32, CSSPixel>) -> Vec<u8> {
+        let mut resources = String::from("<<");
+        for &(kind, key) in &[
+            (FONT_RESOURCES, "Font"),
+            (IMAGE_RESOURCES, "XObject"),
+            (GRAPHICS_STATE_RESOURCES, "ExtGState"),
+        ] {
+            if self.resources[kind].is_empty() {
+                continue;
+            }
+            let _ = write!(resources, " /{} <<", key);
+            for &(ref name, object) in &self.resources[kind] {
+                let _ = write!(resources, " /{} {} 0 R", name, object);
+            }
+            resources.push_str(" >>");
+        }
+        resources.push_str(" >>");
+        let resources = self.add_object(resources.into_bytes());
+
+        // The page tree comes before its pages, which refer to it as their parent.
+        let page_tree = self.add_object(vec![]);
+        let mut kids = vec![];
+        for content in pages {
+            let content = self.add_stream("", content.as_bytes());
+            kids.push(
+                self.add_object(
+                    format!(
+                        "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R \
+                         /Contents {} 0 R >>",
+                        page_tree,
+                        Number(page_size.width * POINTS_PER_PX),
+                        Number(page_size.height * POINTS_PER_PX),
+                        resources,
+                        content,
+                    )
+                    .into_bytes(),
+                ),
+            );
+        }
+        let kids: Vec<String> = kids.iter().map(|kid| format!("{} 0 R", kid)).collect();
+        self.objects[page_tree - 1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        )
+        .into_bytes();
+        let catalog =
+            self.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>", page_tree).into_bytes());
+
+        // The comment of non-ASCII bytes tells tools the file is binary.
+        let mut output = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = vec![];
+        for (index, object) in self.objects.iter().enumerate() {
+            offsets.push(output.len());
+            output.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            output.extend_from_slice(object);
+            output.extend_from_slice(b"\nendobj\n");
+        }
+        let cross_reference_table = output.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1);
+        for offset in offsets {
+            let _ = write!(trailer, "{:010} 00000 n \n", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1,
+            catalog,
+            cross_reference_table,
+        );
+        output.extend_from_slice(trailer.as_bytes());
+        output
+    }
+}
+
+/// The rectangle an item is drawn in, which for rectangles is the item itself, clipped by the
+/// clip nodes it is in up to its reference frame.
+fn clip_rect(display_list: &DisplayList, item: &DisplayItem) -> LayoutRect {
+    let base = item.base();
+    let mut clip = base.clip_rect;
+    let mut index = base
+        .clipping_and_scrolling
+        .clipping
+        .unwrap_or(base.clipping_and_scrolling.scrolling);
+    while index != ClipScrollNodeIndex::root_scroll_node() &&
+        index != ClipScrollNodeIndex::root_reference_frame()
+    {
+        let node = &display_list.clip_scroll_nodes[index.to_index()];
+        match node.node_type {
+            ClipScrollNodeType::Clip | ClipScrollNodeType::ScrollFrame(..) => {
+                clip = clip
+                    .intersection(&node.clip.main)
+                    .unwrap_or_else(LayoutRect::zero);
+            },
+            ClipScrollNodeType::StickyFrame(_) => {},
+            // Reference frames have their own coordinates.
+            ClipScrollNodeType::Placeholder => break,
+        }
+        index = node.parent_index;
+    }
+    if clip == LayoutRect::max_rect() {
+        // Items that are not clipped can be drawn in full.
+        item.bounds()
+    } else {
+        clip
+    }
+}
@@ -7,7 +7,7 @@
 use crate::construct::ConstructionResult;
 use crate::context::LayoutContext;
 use crate::display_list::items::{DisplayList, OpaqueNode, ScrollOffsetMap};
-use crate::display_list::IndexableText;
+use crate::display_list::{IndexableText, PrintData};
 use crate::flow::{Flow, GetBaseFlow};
 use crate::fragment::{Fragment, FragmentBorderBoxIterator, SpecificFragmentInfo};
 use crate::inline::InlineFragmentNodeFlags;
@@ -18,7 +18,7 @@ use app_units::Au;
 use euclid::{Point2D, Rect, Size2D, Vector2D};
 use ipc_channel::ipc::IpcSender;
 use msg::constellation_msg::PipelineId;
-use script_layout_interface::rpc::TextIndexResponse;
+use script_layout_interface::rpc::{BoxSizes, BoxSizesResponse, TextIndexResponse};
 use script_layout_interface::rpc::{ContentBoxResponse, ContentBoxesResponse, LayoutRPC};
 use script_layout_interface::rpc::{NodeGeometryResponse, NodeScrollIdResponse};
 use script_layout_interface::rpc::{OffsetParentResponse, ResolvedStyleResponse, StyleResponse};
@@ -55,12 +55,18 @@ pub struct LayoutThreadData {
 
     pub indexable_text: IndexableText,
 
+    /// The images and page breaks needed to print the display list.
+    pub print_data: PrintData,
+
     /// A queued response for the union of the content boxes of a node.
     pub content_box_response: Option<Rect<Au>>,
 
     /// A queued response for the content boxes of a node.
     pub content_boxes_response: Vec<Rect<Au>>,
 
+    /// A queued response for the border and content box sizes of a node.
+    pub box_sizes_response: Option<BoxSizes>,
+
     /// A queued response for the client {top, left, width, height} of a node in pixels.
     pub client_rect_response: Rect<i32>,
 
@@ -133,6 +139,12 @@ impl LayoutRPC for LayoutRPCImpl {
         ContentBoxesResponse(rw_data.content_boxes_response.clone())
     }
 
+    fn box_sizes(&self) -> BoxSizesResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        BoxSizesResponse(rw_data.box_sizes_response)
+    }
+
     fn nodes_from_point_response(&self) -> Vec<UntrustedNodeAddress> {
         let &LayoutRPCImpl(ref rw_data) = self;
         let rw_data = rw_data.lock().unwrap();
@@ -246,6 +258,51 @@ impl FragmentBorderBoxIterator for CollectingFragmentBorderBoxIterator {
     }
 }
 
+struct BoxSizesFragmentBorderBoxIterator {
+    node_address: OpaqueNode,
+    result: Option<BoxSizes>,
+}
+
+impl BoxSizesFragmentBorderBoxIterator {
+    fn new(node_address: OpaqueNode) -> BoxSizesFragmentBorderBoxIterator {
+        BoxSizesFragmentBorderBoxIterator {
+            node_address: node_address,
+            result: None,
+        }
+    }
+}
+
+impl FragmentBorderBoxIterator for BoxSizesFragmentBorderBoxIterator {
+    fn process(&mut self, fragment: &Fragment, _: i32, border_box: &Rect<Au>) {
+        let writing_mode = fragment.style.writing_mode;
+        let border_padding = fragment.border_padding.to_physical(writing_mode);
+        let border = fragment
+            .style
+            .logical_border_width()
+            .to_physical(writing_mode);
+        let content_box = Rect::new(
+            Point2D::new(
+                border_padding.left - border.left,
+                border_padding.top - border.top,
+            ),
+            Size2D::new(
+                border_box.size.width - border_padding.horizontal(),
+                border_box.size.height - border_padding.vertical(),
+            ),
+        );
+        self.result = Some(BoxSizes {
+            border_box: border_box.size,
+            content_box: content_box,
+            vertical_writing_mode: writing_mode.is_vertical(),
+        });
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        // Only the first box of the node is measured.
+        self.result.is_none() && fragment.contains_node(self.node_address)
+    }
+}
+
 enum Side {
     Left,
     Right,
@@ -386,6 +443,15 @@ pub fn process_content_boxes_request(
     iterator.rects
 }
 
+pub fn process_box_sizes_request(
+    requested_node: OpaqueNode,
+    layout_root: &mut dyn Flow,
+) -> Option<BoxSizes> {
+    let mut iterator = BoxSizesFragmentBorderBoxIterator::new(requested_node);
+    sequential::iterate_through_flow_tree_fragment_border_boxes(layout_root, &mut iterator);
+    iterator.result
+}
+
 struct FragmentLocatingFragmentIterator {
     node_address: OpaqueNode,
     client_rect: Rect<i32>,
@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use euclid::TypedSize2D;
+use layout::display_list::BreakHint;
+use layout::display_list::{default_page_margins, page_margins_from_declarations, paginate};
+use servo_arc::Arc;
+use servo_url::ServoUrl;
+use style::context::QuirksMode;
+use style::media_queries::MediaList;
+use style::shared_lock::SharedRwLock;
+use style::stylesheets::{CssRule, Origin, Stylesheet};
+use style::values::computed::{BreakBetween, BreakWithin};
+
+fn hint(top: f32, bottom: f32, before: BreakBetween, after: BreakBetween) -> BreakHint {
+    BreakHint::new(top, bottom, before, after, BreakWithin::Auto)
+}
+
+fn avoid_inside(top: f32, bottom: f32) -> BreakHint {
+    BreakHint::new(
+        top,
+        bottom,
+        BreakBetween::Auto,
+        BreakBetween::Auto,
+        BreakWithin::Avoid,
+    )
+}
+
+#[test]
+fn test_document_is_cut_into_pages_of_the_page_height() {
+    assert_eq!(paginate(250., 100., vec![], &[]), vec![0., 100., 200.]);
+    assert_eq!(paginate(200., 100., vec![], &[]), vec![0., 100.]);
+    assert_eq!(paginate(50., 100., vec![], &[]), vec![0.]);
+    assert_eq!(paginate(0., 100., vec![], &[]), vec![0.]);
+}
+
+#[test]
+fn test_page_breaks_move_up_to_not_cut_through_lines() {
+    // A line from 90 to 110 starts the second page.
+    assert_eq!(
+        paginate(250., 100., vec![(10., 30.), (90., 110.)], &[]),
+        vec![0., 90., 190.]
+    );
+    // Nested spans move the break up to the one starting first.
+    assert_eq!(
+        paginate(150., 100., vec![(80., 105.), (60., 120.)], &[]),
+        vec![0., 60.]
+    );
+}
+
+#[test]
+fn test_spans_taller_than_pages_are_cut() {
+    assert_eq!(
+        paginate(300., 100., vec![(50., 250.)], &[]),
+        vec![0., 100., 200.]
+    );
+}
+
+#[test]
+fn test_break_inside_avoid() {
+    assert_eq!(
+        paginate(250., 100., vec![], &[avoid_inside(70., 150.)]),
+        vec![0., 70., 170.]
+    );
+    // Boxes taller than pages are broken anyway.
+    assert_eq!(
+        paginate(250., 100., vec![], &[avoid_inside(70., 190.)]),
+        vec![0., 100., 200.]
+    );
+}
+
+#[test]
+fn test_break_before_and_after_force_page_breaks() {
+    let hints = [
+        hint(40., 60., BreakBetween::Page, BreakBetween::Auto),
+        hint(60., 80., BreakBetween::Auto, BreakBetween::Always),
+    ];
+    assert_eq!(
+        paginate(250., 100., vec![], &hints),
+        vec![0., 40., 80., 180.]
+    );
+
+    for &value in &[BreakBetween::Left, BreakBetween::Right] {
+        let hints = [hint(30., 50., value, BreakBetween::Auto)];
+        assert_eq!(paginate(100., 100., vec![], &hints), vec![0., 30.]);
+    }
+}
+
+#[test]
+fn test_break_between_auto_and_avoid_do_not_force_page_breaks() {
+    let hints = [
+        hint(30., 50., BreakBetween::Avoid, BreakBetween::Avoid),
+        hint(50., 70., BreakBetween::Auto, BreakBetween::Auto),
+    ];
+    assert_eq!(paginate(150., 100., vec![], &hints), vec![0., 100.]);
+}
+
+#[test]
+fn test_forced_breaks_at_the_edges_of_the_document_add_no_pages() {
+    let hints = [
+        hint(0., 20., BreakBetween::Page, BreakBetween::Auto),
+        hint(80., 100., BreakBetween::Auto, BreakBetween::Page),
+    ];
+    assert_eq!(paginate(100., 100., vec![], &hints), vec![0.]);
+}
+
+fn page_margins(css: &str, width: f32, height: f32) -> (f32, f32, f32, f32) {
+    let lock = SharedRwLock::new();
+    let media = Arc::new(lock.wrap(MediaList::empty()));
+    let stylesheet = Stylesheet::from_str(
+        css,
+        ServoUrl::parse("about::test").unwrap(),
+        Origin::Author,
+        media,
+        lock.clone(),
+        None,
+        None,
+        QuirksMode::NoQuirks,
+        0,
+    );
+    let guard = lock.read();
+    let rules = stylesheet.contents.rules.read_with(&guard);
+    let blocks = rules.0.iter().filter_map(|rule| match *rule {
+        CssRule::Page(ref rule) => Some(rule.read_with(&guard).block.read_with(&guard)),
+        _ => None,
+    });
+    let margins = page_margins_from_declarations(blocks, &TypedSize2D::new(width, height));
+    (margins.top, margins.right, margins.bottom, margins.left)
+}
+
+#[test]
+fn test_page_margins_default_to_one_centimeter() {
+    let default = default_page_margins();
+    let expected = (default.top, default.right, default.bottom, default.left);
+    assert_eq!(page_margins("", 816., 1056.), expected);
+    assert_eq!(page_margins("@page { color: red }", 816., 1056.), expected);
+    assert!((default.top - 96. / 2.54).abs() < 0.001);
+}
+
+#[test]
+fn test_page_margins_from_page_rules() {
+    assert_eq!(
+        page_margins("@page { margin: 48px 1in 0 2px }", 816., 1056.),
+        (48., 96., 0., 2.)
+    );
+    assert_eq!(
+        page_margins(
+            "@page { margin-top: 10px } @page { margin-top: 20px }",
+            816.,
+            1056.
+        ),
+        (20., 96. / 2.54, 96. / 2.54, 96. / 2.54)
+    );
+}
+
+#[test]
+fn test_page_margin_percentages_are_of_the_page_size() {
+    // Left and right margins are of the width of the page, top and bottom ones of its
+    // height.
+    assert_eq!(
+        page_margins("@page { margin: 25% 12.5% }", 800., 1000.),
+        (250., 100., 250., 100.)
+    );
+    assert_eq!(
+        page_margins("@page { margin: 25% 12.5% }", 1000., 800.),
+        (200., 125., 200., 125.)
+    );
+}
+
+#[test]
+fn test_page_margins_relative_to_fonts_are_ignored() {
+    let default = 96. / 2.54;
+    assert_eq!(
+        page_margins("@page { margin: 2em auto }", 816., 1056.),
+        (default, default, default, default)
+    );
+}
@@ -45,12 +45,14 @@ use layout::context::LayoutContext;
 use layout::context::RegisteredPainter;
 use layout::context::RegisteredPainters;
 use layout::display_list::items::{OpaqueNode, WebRenderImageInfo};
+use layout::display_list::{default_page_margins, page_margins, print_to_pdf, PrintData};
 use layout::display_list::{IndexableText, ToLayout, WebRenderDisplayListConverter};
 use layout::flow::{Flow, GetBaseFlow, ImmutableFlowUtils, MutableOwnedFlowUtils};
 use layout::flow_ref::FlowRef;
 use layout::incremental::{RelayoutMode, SpecialRestyleDamage};
 use layout::layout_debug;
 use layout::parallel;
+use layout::query::process_box_sizes_request;
 use layout::query::{
     process_content_box_request, process_content_boxes_request, LayoutRPCImpl, LayoutThreadData,
 };
@@ -86,7 +88,7 @@ use script_layout_interface::wrapper_traits::LayoutNode;
 use script_traits::Painter;
 use script_traits::{ConstellationControlMsg, LayoutControlMsg, LayoutMsg as ConstellationMsg};
 use script_traits::{DrawAPaintImageResult, IFrameSizeMsg, PaintWorkletError, WindowSizeType};
-use script_traits::{ScrollState, UntrustedNodeAddress};
+use script_traits::{PrintSettings, ScrollState, UntrustedNodeAddress};
 use selectors::Element;
 use servo_arc::Arc as ServoArc;
 use servo_atoms::Atom;
@@ -601,8 +603,10 @@ impl LayoutThread {
                 constellation_chan: constellation_chan,
                 display_list: None,
                 indexable_text: IndexableText::default(),
+                print_data: PrintData::default(),
                 content_box_response: None,
                 content_boxes_response: Vec::new(),
+                box_sizes_response: None,
                 client_rect_response: Rect::zero(),
                 scroll_id_response: None,
                 scroll_area_response: Rect::zero(),
@@ -717,6 +721,7 @@ impl LayoutThread {
             Msg::RegisterPaint(..) => LayoutHangAnnotation::RegisterPaint,
             Msg::SetNavigationStart(..) => LayoutHangAnnotation::SetNavigationStart,
             Msg::GetRunningAnimations(..) => LayoutHangAnnotation::GetRunningAnimations,
+            Msg::Print(..) => LayoutHangAnnotation::Print,
         };
         self.background_hang_monitor
             .notify_activity(HangAnnotation::Layout(hang_annotation));
@@ -757,6 +762,9 @@ impl LayoutThread {
             Request::FromPipeline(LayoutControlMsg::ExitNow) => {
                 self.handle_request_helper(Msg::ExitNow, possibly_locked_rw_data)
             },
+            Request::FromPipeline(LayoutControlMsg::Print(settings, sender)) => {
+                self.handle_request_helper(Msg::Print(settings, sender), possibly_locked_rw_data)
+            },
             Request::FromPipeline(LayoutControlMsg::PaintMetric(epoch, paint_time)) => {
                 self.paint_time_metrics.maybe_set_metric(epoch, paint_time);
                 true
@@ -895,11 +903,40 @@ impl LayoutThread {
             Msg::GetRunningAnimations(sender) => {
                 let _ = sender.send(self.running_animations.read().len());
             },
+            Msg::Print(settings, sender) => {
+                let rw_data = possibly_locked_rw_data.lock();
+                let _ = sender.send(self.print(&settings, &*rw_data));
+            },
         }
 
         true
     }
 
+    /// Print the last display list built on pages, as a PDF document.
+    fn print(&self, settings: &PrintSettings, rw_data: &LayoutThreadData) -> Option<Vec<u8>> {
+        let display_list = rw_data.display_list.as_ref()?;
+        let margins = match (settings.margins, self.document_shared_lock.as_ref()) {
+            (Some(margins), _) => margins,
+            (None, Some(author_shared_lock)) => {
+                let author_guard = author_shared_lock.read();
+                let ua_or_user_guard = UA_STYLESHEETS.shared_lock.read();
+                let guards = StylesheetGuards {
+                    author: &author_guard,
+                    ua_or_user: &ua_or_user_guard,
+                };
+                page_margins(&self.stylist, &guards, &settings.page_size)
+            },
+            (None, None) => default_page_margins(),
+        };
+        Some(print_to_pdf(
+            display_list,
+            &rw_data.indexable_text,
+            &rw_data.print_data,
+            margins,
+            settings,
+        ))
+    }
+
     fn collect_reports<'a, 'b>(
         &self,
         reports_chan: ReportsChan,
@@ -1208,6 +1245,8 @@ impl LayoutThread {
                             &mut build_state.indexable_text,
                             IndexableText::default(),
                         );
+                        rw_data.print_data =
+                            std::mem::replace(&mut build_state.print_data, PrintData::default());
                         rw_data.display_list = Some(build_state.to_display_list());
                     }
                 }
@@ -1310,6 +1349,9 @@ impl LayoutThread {
                         &QueryMsg::ContentBoxesQuery(_) => {
                             rw_data.content_boxes_response = Vec::new();
                         },
+                        &QueryMsg::BoxSizesQuery(_) => {
+                            rw_data.box_sizes_response = None;
+                        },
                         &QueryMsg::NodesFromPointQuery(..) => {
                             rw_data.nodes_from_point_response = Vec::new();
                         },
@@ -1629,6 +1671,9 @@ impl LayoutThread {
                 &QueryMsg::ContentBoxesQuery(node) => {
                     rw_data.content_boxes_response = process_content_boxes_request(node, root_flow);
                 },
+                &QueryMsg::BoxSizesQuery(node) => {
+                    rw_data.box_sizes_response = process_box_sizes_request(node, root_flow);
+                },
                 &QueryMsg::TextIndexQuery(node, point_in_node) => {
                     let point_in_node = Point2D::new(
                         Au::from_f32_px(point_in_node.x),
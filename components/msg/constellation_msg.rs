@@ -74,6 +74,27 @@ impl PipelineNamespace {
             index: HistoryStateIndex(self.next_index()),
         }
     }
+
+    fn next_message_port_id(&mut self) -> MessagePortId {
+        MessagePortId {
+            namespace_id: self.id,
+            index: MessagePortIndex(self.next_index()),
+        }
+    }
+
+    fn next_message_port_router_id(&mut self) -> MessagePortRouterId {
+        MessagePortRouterId {
+            namespace_id: self.id,
+            index: MessagePortRouterIndex(self.next_index()),
+        }
+    }
+
+    fn next_broadcast_channel_router_id(&mut self) -> BroadcastChannelRouterId {
+        BroadcastChannelRouterId {
+            namespace_id: self.id,
+            index: BroadcastChannelRouterIndex(self.next_index()),
+        }
+    }
 }
 
 thread_local!(pub static PIPELINE_NAMESPACE: Cell<Option<PipelineNamespace>> = Cell::new(None));
@@ -246,6 +267,105 @@ impl fmt::Display for HistoryStateId {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct MessagePortIndex(pub NonZeroU32);
+malloc_size_of_is_0!(MessagePortIndex);
+
+/// The identifier of a MessagePort, which stays the same when the port is
+/// transferred to another global.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct MessagePortId {
+    pub namespace_id: PipelineNamespaceId,
+    pub index: MessagePortIndex,
+}
+
+impl MessagePortId {
+    pub fn new() -> MessagePortId {
+        PIPELINE_NAMESPACE.with(|tls| {
+            let mut namespace = tls.get().expect("No namespace set for this thread!");
+            let next_message_port_id = namespace.next_message_port_id();
+            tls.set(Some(namespace));
+            next_message_port_id
+        })
+    }
+}
+
+impl fmt::Display for MessagePortId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let PipelineNamespaceId(namespace_id) = self.namespace_id;
+        let MessagePortIndex(index) = self.index;
+        write!(fmt, "({},{})", namespace_id, index.get())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct MessagePortRouterIndex(pub NonZeroU32);
+malloc_size_of_is_0!(MessagePortRouterIndex);
+
+/// The identifier of the router through which a global receives the messages
+/// for the ports it manages.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct MessagePortRouterId {
+    pub namespace_id: PipelineNamespaceId,
+    pub index: MessagePortRouterIndex,
+}
+
+impl MessagePortRouterId {
+    pub fn new() -> MessagePortRouterId {
+        PIPELINE_NAMESPACE.with(|tls| {
+            let mut namespace = tls.get().expect("No namespace set for this thread!");
+            let next_message_port_router_id = namespace.next_message_port_router_id();
+            tls.set(Some(namespace));
+            next_message_port_router_id
+        })
+    }
+}
+
+impl fmt::Display for MessagePortRouterId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let PipelineNamespaceId(namespace_id) = self.namespace_id;
+        let MessagePortRouterIndex(index) = self.index;
+        write!(fmt, "({},{})", namespace_id, index.get())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct BroadcastChannelRouterIndex(pub NonZeroU32);
+malloc_size_of_is_0!(BroadcastChannelRouterIndex);
+
+/// The identifier of the router through which a global receives broadcasts
+/// for its BroadcastChannels.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct BroadcastChannelRouterId {
+    pub namespace_id: PipelineNamespaceId,
+    pub index: BroadcastChannelRouterIndex,
+}
+
+impl BroadcastChannelRouterId {
+    pub fn new() -> BroadcastChannelRouterId {
+        PIPELINE_NAMESPACE.with(|tls| {
+            let mut namespace = tls.get().expect("No namespace set for this thread!");
+            let next_broadcast_channel_router_id = namespace.next_broadcast_channel_router_id();
+            tls.set(Some(namespace));
+            next_broadcast_channel_router_id
+        })
+    }
+}
+
+impl fmt::Display for BroadcastChannelRouterId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let PipelineNamespaceId(namespace_id) = self.namespace_id;
+        let BroadcastChannelRouterIndex(index) = self.index;
+        write!(fmt, "({},{})", namespace_id, index.get())
+    }
+}
+
 // We provide ids just for unit testing.
 pub const TEST_NAMESPACE: PipelineNamespaceId = PipelineNamespaceId(1234);
 #[allow(unsafe_code)]
@@ -306,6 +426,7 @@ pub enum LayoutHangAnnotation {
     RegisterPaint,
     SetNavigationStart,
     GetRunningAnimations,
+    Print,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
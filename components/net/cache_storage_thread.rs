@@ -0,0 +1,331 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The cache storage thread: it keeps the caches of every origin, and saves
+//! them to disk as soon as they change.
+
+use crate::resource_thread;
+use http::header;
+use http::Method;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::cache_storage_thread::{CacheId, CacheQueryOptions, CacheStorageThreadMsg};
+use net_traits::cache_storage_thread::{CachedRequest, CachedResponse};
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::thread;
+use url::Position;
+
+pub trait CacheStorageThreadFactory {
+    fn new(config_dir: Option<PathBuf>) -> Self;
+}
+
+impl CacheStorageThreadFactory for IpcSender<CacheStorageThreadMsg> {
+    /// Create a cache storage thread
+    fn new(config_dir: Option<PathBuf>) -> IpcSender<CacheStorageThreadMsg> {
+        let (chan, port) = ipc::channel().unwrap();
+        thread::Builder::new()
+            .name("CacheStorageManager".to_owned())
+            .spawn(move || {
+                CacheStorageManager::new(port, config_dir).start();
+            })
+            .expect("Thread spawning failed");
+        chan
+    }
+}
+
+/// <https://w3c.github.io/ServiceWorker/#dfn-request-response-list>
+type RequestResponseList = Vec<(CachedRequest, CachedResponse)>;
+
+/// The caches of each origin as they are saved: by name, in creation order.
+type SavedCaches = HashMap<String, Vec<(String, RequestResponseList)>>;
+
+struct CacheStorageManager {
+    port: IpcReceiver<CacheStorageThreadMsg>,
+    /// The caches of each origin, by name, in creation order.
+    storages: HashMap<String, Vec<(String, CacheId)>>,
+    /// Every cache, including those deleted from their cache storage since.
+    caches: HashMap<CacheId, RequestResponseList>,
+    next_cache_id: u64,
+    config_dir: Option<PathBuf>,
+}
+
+impl CacheStorageManager {
+    fn new(
+        port: IpcReceiver<CacheStorageThreadMsg>,
+        config_dir: Option<PathBuf>,
+    ) -> CacheStorageManager {
+        let mut manager = CacheStorageManager {
+            port: port,
+            storages: HashMap::new(),
+            caches: HashMap::new(),
+            next_cache_id: 0,
+            config_dir: config_dir,
+        };
+        if let Some(ref config_dir) = manager.config_dir.clone() {
+            let mut saved = SavedCaches::new();
+            resource_thread::read_json_from_file(&mut saved, config_dir, "cache_storage.json");
+            for (origin, caches) in saved {
+                for (name, list) in caches {
+                    let id = manager.create_cache(list);
+                    manager
+                        .storages
+                        .entry(origin.clone())
+                        .or_insert(vec![])
+                        .push((name, id));
+                }
+            }
+        }
+        manager
+    }
+
+    fn start(&mut self) {
+        loop {
+            match self.port.recv().unwrap() {
+                CacheStorageThreadMsg::Match(sender, origin, name, request, options) => {
+                    let _ = sender.send(self.match_storage(&origin, name, &request, options));
+                },
+                CacheStorageThreadMsg::Has(sender, origin, name) => {
+                    let _ = sender.send(self.find_cache(&origin, &name).is_some());
+                },
+                CacheStorageThreadMsg::Open(sender, origin, name) => {
+                    let _ = sender.send(self.open(&origin, name));
+                    self.save_state()
+                },
+                CacheStorageThreadMsg::Delete(sender, origin, name) => {
+                    let _ = sender.send(self.delete(&origin, &name));
+                    self.save_state()
+                },
+                CacheStorageThreadMsg::Keys(sender, origin) => {
+                    let names = self
+                        .storages
+                        .get(&origin.ascii_serialization())
+                        .map_or(vec![], |caches| {
+                            caches.iter().map(|&(ref name, _)| name.clone()).collect()
+                        });
+                    let _ = sender.send(names);
+                },
+                CacheStorageThreadMsg::MatchAll(sender, id, request, options) => {
+                    let responses = self
+                        .query_cache(id, request.as_ref(), options)
+                        .into_iter()
+                        .map(|(_, response)| response.clone())
+                        .collect();
+                    let _ = sender.send(responses);
+                },
+                CacheStorageThreadMsg::Put(sender, id, entries) => {
+                    self.put(id, entries);
+                    let _ = sender.send(());
+                    self.save_state()
+                },
+                CacheStorageThreadMsg::DeleteEntries(sender, id, request, options) => {
+                    let _ = sender.send(self.delete_entries(id, &request, options));
+                    self.save_state()
+                },
+                CacheStorageThreadMsg::RequestKeys(sender, id, request, options) => {
+                    let requests = self
+                        .query_cache(id, request.as_ref(), options)
+                        .into_iter()
+                        .map(|(request, _)| request.clone())
+                        .collect();
+                    let _ = sender.send(requests);
+                },
+                CacheStorageThreadMsg::Exit(sender) => {
+                    // Nothing to do since the caches are saved eagerly.
+                    let _ = sender.send(());
+                    break;
+                },
+            }
+        }
+    }
+
+    fn save_state(&self) {
+        if let Some(ref config_dir) = self.config_dir {
+            let saved: SavedCaches = self
+                .storages
+                .iter()
+                .map(|(origin, caches)| {
+                    let caches = caches
+                        .iter()
+                        .map(|&(ref name, id)| (name.clone(), self.caches[&id].clone()))
+                        .collect();
+                    (origin.clone(), caches)
+                })
+                .collect();
+            resource_thread::write_json_to_file(&saved, config_dir, "cache_storage.json");
+        }
+    }
+
+    fn create_cache(&mut self, list: RequestResponseList) -> CacheId {
+        let id = CacheId(self.next_cache_id);
+        self.next_cache_id += 1;
+        self.caches.insert(id, list);
+        id
+    }
+
+    fn find_cache(&self, origin: &ImmutableOrigin, name: &str) -> Option<CacheId> {
+        self.storages
+            .get(&origin.ascii_serialization())?
+            .iter()
+            .find(|&&(ref cache_name, _)| cache_name == name)
+            .map(|&(_, id)| id)
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-storage-open>
+    fn open(&mut self, origin: &ImmutableOrigin, name: String) -> CacheId {
+        if let Some(id) = self.find_cache(origin, &name) {
+            return id;
+        }
+        let id = self.create_cache(vec![]);
+        self.storages
+            .entry(origin.ascii_serialization())
+            .or_insert(vec![])
+            .push((name, id));
+        id
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-storage-delete>
+    fn delete(&mut self, origin: &ImmutableOrigin, name: &str) -> bool {
+        let caches = match self.storages.get_mut(&origin.ascii_serialization()) {
+            Some(caches) => caches,
+            None => return false,
+        };
+        let count = caches.len();
+        caches.retain(|&(ref cache_name, _)| cache_name != name);
+        count != caches.len()
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-storage-match>
+    fn match_storage(
+        &self,
+        origin: &ImmutableOrigin,
+        name: Option<String>,
+        request: &CachedRequest,
+        options: CacheQueryOptions,
+    ) -> Option<CachedResponse> {
+        let caches = self.storages.get(&origin.ascii_serialization())?;
+        caches
+            .iter()
+            .filter(|&&(ref cache_name, _)| name.as_ref().map_or(true, |name| name == cache_name))
+            .filter_map(|&(_, id)| {
+                self.query_cache(id, Some(request), options)
+                    .first()
+                    .map(|&(_, response)| response.clone())
+            })
+            .next()
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#query-cache>
+    fn query_cache(
+        &self,
+        id: CacheId,
+        request: Option<&CachedRequest>,
+        options: CacheQueryOptions,
+    ) -> Vec<(&CachedRequest, &CachedResponse)> {
+        let list = match self.caches.get(&id) {
+            Some(list) => list,
+            None => return vec![],
+        };
+        list.iter()
+            .filter(|&&(ref cached_request, ref cached_response)| {
+                request.map_or(true, |request| {
+                    request_matches_cached_item(request, cached_request, cached_response, options)
+                })
+            })
+            .map(|&(ref request, ref response)| (request, response))
+            .collect()
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#batch-cache-operations>
+    fn put(&mut self, id: CacheId, entries: RequestResponseList) {
+        let list = match self.caches.get_mut(&id) {
+            Some(list) => list,
+            None => return,
+        };
+        for (request, response) in entries {
+            list.retain(|&(ref cached_request, ref cached_response)| {
+                !request_matches_cached_item(
+                    &request,
+                    cached_request,
+                    cached_response,
+                    CacheQueryOptions::default(),
+                )
+            });
+            list.push((request, response));
+        }
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#cache-delete>
+    fn delete_entries(
+        &mut self,
+        id: CacheId,
+        request: &CachedRequest,
+        options: CacheQueryOptions,
+    ) -> bool {
+        let list = match self.caches.get_mut(&id) {
+            Some(list) => list,
+            None => return false,
+        };
+        let count = list.len();
+        list.retain(|&(ref cached_request, ref cached_response)| {
+            !request_matches_cached_item(request, cached_request, cached_response, options)
+        });
+        count != list.len()
+    }
+}
+
+/// <https://w3c.github.io/ServiceWorker/#request-matches-cached-item-algorithm>
+fn request_matches_cached_item(
+    request: &CachedRequest,
+    cached_request: &CachedRequest,
+    cached_response: &CachedResponse,
+    options: CacheQueryOptions,
+) -> bool {
+    // Step 1.
+    if !options.ignore_method && request.method != Method::GET {
+        return false;
+    }
+
+    // Steps 2-7.
+    let end = if options.ignore_search {
+        Position::AfterPath
+    } else {
+        Position::AfterQuery
+    };
+    if !urls_match(&request.url, &cached_request.url, end) {
+        return false;
+    }
+
+    // Step 8.
+    if options.ignore_vary {
+        return true;
+    }
+
+    // Step 9.
+    for value in cached_response.headers.get_all(header::VARY) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+        for field_name in value.split(',').map(str::trim) {
+            // Step 9.1.
+            if field_name == "*" {
+                return false;
+            }
+            // Step 9.2.
+            let values = request.headers.get_all(field_name).iter();
+            let cached_values = cached_request.headers.get_all(field_name).iter();
+            if !values.eq(cached_values) {
+                return false;
+            }
+        }
+    }
+
+    // Step 10.
+    true
+}
+
+fn urls_match(a: &ServoUrl, b: &ServoUrl, end: Position) -> bool {
+    a.as_url()[..end] == b.as_url()[..end]
+}
@@ -3,45 +3,322 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::hosts::replace_host;
-use hyper::client::connect::{Connect, Destination};
-use hyper::client::HttpConnector as HyperHttpConnector;
+use crate::proxy::{self, ProxyConfig, ProxyKind};
+use crate::resource_thread::AuthCache;
+use devtools_traits::{CertificateInfo, CertificateName, SecurityInfo};
+use futures::future::{self, Either, Loop};
+use futures::sync::oneshot;
+use hyper::client::connect::{Connect, Connected, Destination};
 use hyper::rt::Future;
 use hyper::{Body, Client};
-use hyper_openssl::HttpsConnector;
-use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslOptions};
-use openssl::x509;
+use hyper_openssl::{HttpsConnector, MaybeHttpsStream};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslOptions, SslRef};
+use openssl::x509::{self, X509NameRef, X509Ref};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex, RwLock};
+use threadpool::ThreadPool;
+use time::precise_time_ns;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 use tokio::prelude::future::Executor;
+use tokio::prelude::Poll;
 
 pub const BUF_SIZE: usize = 32768;
 
+/// The number of threads host names are resolved on.
+const DNS_THREADS: usize = 4;
+
+type TcpFuture = Box<dyn Future<Item = (TcpStream, TcpTiming), Error = io::Error> + Send>;
+
+/// When the steps of opening a TCP connection happened, in nanoseconds.
+#[derive(Clone, Copy, Debug, Default)]
+struct TcpTiming {
+    domain_lookup_start: u64,
+    domain_lookup_end: u64,
+    connect_start: u64,
+    connect_end: u64,
+}
+
+/// A TCP connection, which remembers how it was opened.
+#[derive(Debug)]
+pub struct TcpConnection {
+    stream: TcpStream,
+    timing: TcpTiming,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl TcpConnection {
+    /// Wrap a stream, which is ready for requests now that any proxy was negotiated with.
+    fn new(stream: TcpStream, mut timing: TcpTiming) -> TcpConnection {
+        timing.connect_end = precise_time_ns();
+        TcpConnection {
+            remote_addr: stream.peer_addr().ok(),
+            stream,
+            timing,
+        }
+    }
+}
+
+impl Read for TcpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsyncRead for TcpConnection {}
+
+impl AsyncWrite for TcpConnection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        AsyncWrite::shutdown(&mut self.stream)
+    }
+}
+
 pub struct HttpConnector {
-    inner: HyperHttpConnector,
+    /// Where host names are resolved, which is done here rather than by the system
+    /// on connection, to tell how long it takes.
+    dns_pool: Mutex<ThreadPool>,
+    proxy_config: ProxyConfig,
+    /// Where the credentials for the proxies are kept.
+    auth_cache: Arc<RwLock<AuthCache>>,
 }
 
 impl HttpConnector {
-    fn new() -> HttpConnector {
-        let mut inner = HyperHttpConnector::new(4);
-        inner.enforce_http(false);
-        inner.set_happy_eyeballs_timeout(None);
-        HttpConnector { inner }
+    fn new(proxy_config: ProxyConfig, auth_cache: Arc<RwLock<AuthCache>>) -> HttpConnector {
+        HttpConnector {
+            dns_pool: Mutex::new(ThreadPool::with_name("DNSResolver".to_owned(), DNS_THREADS)),
+            proxy_config,
+            auth_cache,
+        }
+    }
+
+    /// Resolve the given host, unless it is an IP address already.
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl Future<Item = Vec<SocketAddr>, Error = io::Error> + Send {
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_owned();
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Either::A(future::ok(vec![SocketAddr::new(ip, port)]));
+        }
+        let (sender, receiver) = oneshot::channel();
+        self.dns_pool.lock().unwrap().execute(move || {
+            let addrs = (&*host, port).to_socket_addrs();
+            let _ = sender.send(addrs.map(|addrs| addrs.collect()));
+        });
+        Either::B(receiver.then(|result| match result {
+            Ok(addrs) => addrs,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "host name resolution was cancelled",
+            )),
+        }))
+    }
+
+    /// Open a TCP connection to the given host, trying each of its addresses in turn.
+    fn connect_tcp(&self, host: &str, port: u16) -> TcpFuture {
+        let domain_lookup_start = precise_time_ns();
+        Box::new(self.resolve(host, port).and_then(move |addrs| {
+            let connect_start = precise_time_ns();
+            let timing = TcpTiming {
+                domain_lookup_start,
+                domain_lookup_end: connect_start,
+                connect_start,
+                connect_end: 0,
+            };
+            future::loop_fn(
+                (addrs.into_iter(), None),
+                |(mut addrs, last_error): (_, Option<io::Error>)| match addrs.next() {
+                    Some(addr) => {
+                        Either::A(TcpStream::connect(&addr).then(move |result| match result {
+                            Ok(stream) => Ok(Loop::Break(stream)),
+                            Err(error) => Ok(Loop::Continue((addrs, Some(error)))),
+                        }))
+                    },
+                    None => Either::B(future::err(last_error.unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "the host has no addresses")
+                    }))),
+                },
+            )
+            .map(move |stream| (stream, timing))
+        }))
     }
 }
 
 impl Connect for HttpConnector {
-    type Transport = <HyperHttpConnector as Connect>::Transport;
-    type Error = <HyperHttpConnector as Connect>::Error;
-    type Future = <HyperHttpConnector as Connect>::Future;
+    type Transport = TcpConnection;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = (TcpConnection, Connected), Error = io::Error> + Send>;
 
     fn connect(&self, dest: Destination) -> Self::Future {
+        let port = dest
+            .port()
+            .unwrap_or(if dest.scheme() == "https" { 443 } else { 80 });
         // Perform host replacement when making the actual TCP connection.
-        let mut new_dest = dest.clone();
-        let addr = replace_host(dest.host());
-        new_dest.set_host(&*addr).unwrap();
-        self.inner.connect(new_dest)
+        let addr = replace_host(dest.host()).into_owned();
+        let proxy = match self
+            .proxy_config
+            .proxy_for(dest.scheme(), dest.host(), port)
+        {
+            Some(proxy) => proxy,
+            None => {
+                return Box::new(self.connect_tcp(&addr, port).map(|(stream, timing)| {
+                    (TcpConnection::new(stream, timing), Connected::new())
+                }));
+            },
+        };
+
+        let connecting = self.connect_tcp(&replace_host(&proxy.host), proxy.port);
+        let credentials = proxy.credentials(&self.auth_cache);
+
+        match (proxy.kind, dest.scheme() == "https") {
+            // The request is sent with an absolute URL, for the proxy to forward it.
+            (ProxyKind::Http, false) => Box::new(connecting.map(|(stream, timing)| {
+                (
+                    TcpConnection::new(stream, timing),
+                    Connected::new().proxy(true),
+                )
+            })),
+            (ProxyKind::Http, true) => Box::new(connecting.and_then(move |(stream, timing)| {
+                proxy::tunnel(stream, &addr, port, credentials)
+                    .map(move |stream| (TcpConnection::new(stream, timing), Connected::new()))
+            })),
+            (ProxyKind::Socks5, _) => Box::new(connecting.and_then(move |(stream, timing)| {
+                proxy::socks5_connect(stream, &addr, port, credentials)
+                    .map(move |stream| (TcpConnection::new(stream, timing), Connected::new()))
+            })),
+        }
+    }
+}
+
+/// What is known of the connection a response was received over, which is found in the
+/// extensions of the response.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    /// When the steps of opening the connection happened, in nanoseconds. The secure
+    /// connection start is zero for connections without TLS, and the connection end
+    /// follows the TLS handshake otherwise.
+    pub domain_lookup_start: u64,
+    pub domain_lookup_end: u64,
+    pub connect_start: u64,
+    pub secure_connection_start: u64,
+    pub connect_end: u64,
+    /// The address of the server, or of the proxy the connection goes through.
+    pub remote_addr: Option<SocketAddr>,
+    pub security_info: Option<SecurityInfo>,
+}
+
+impl ConnectionInfo {
+    /// The information of a connection which was just opened.
+    fn new(stream: &MaybeHttpsStream<TcpConnection>) -> ConnectionInfo {
+        let (connection, ssl) = match *stream {
+            MaybeHttpsStream::Http(ref connection) => (connection, None),
+            MaybeHttpsStream::Https(ref stream) => {
+                (stream.get_ref().get_ref(), Some(stream.get_ref().ssl()))
+            },
+        };
+        let timing = connection.timing;
+        ConnectionInfo {
+            domain_lookup_start: timing.domain_lookup_start,
+            domain_lookup_end: timing.domain_lookup_end,
+            connect_start: timing.connect_start,
+            secure_connection_start: ssl.map_or(0, |_| timing.connect_end),
+            connect_end: ssl.map_or(timing.connect_end, |_| precise_time_ns()),
+            remote_addr: connection.remote_addr,
+            security_info: ssl.map(security_info),
+        }
+    }
+}
+
+fn security_info(ssl: &SslRef) -> SecurityInfo {
+    SecurityInfo {
+        protocol_version: ssl.version_str().to_owned(),
+        cipher_suite: ssl
+            .current_cipher()
+            .map_or(String::new(), |cipher| cipher.name().to_owned()),
+        // On the client side, the chain starts with the certificate of the server.
+        certificates: ssl
+            .peer_cert_chain()
+            .map_or(vec![], |chain| chain.iter().map(certificate_info).collect()),
+    }
+}
+
+fn certificate_info(certificate: &X509Ref) -> CertificateInfo {
+    let fingerprint = |digest| {
+        certificate
+            .digest(digest)
+            .ok()
+            .map_or(String::new(), |bytes| {
+                bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+    };
+    CertificateInfo {
+        subject: certificate_name(certificate.subject_name()),
+        issuer: certificate_name(certificate.issuer_name()),
+        serial_number: certificate
+            .serial_number()
+            .to_bn()
+            .and_then(|number| number.to_hex_str())
+            .map_or(String::new(), |number| number.to_string()),
+        not_before: certificate.not_before().to_string(),
+        not_after: certificate.not_after().to_string(),
+        sha1_fingerprint: fingerprint(MessageDigest::sha1()),
+        sha256_fingerprint: fingerprint(MessageDigest::sha256()),
+    }
+}
+
+fn certificate_name(name: &X509NameRef) -> CertificateName {
+    let entry = |nid| {
+        name.entries_by_nid(nid)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .map(|data| data.to_string())
+    };
+    CertificateName {
+        common_name: entry(Nid::COMMONNAME),
+        organization: entry(Nid::ORGANIZATIONNAME),
+        organizational_unit: entry(Nid::ORGANIZATIONALUNITNAME),
     }
 }
 
-pub type Connector = HttpsConnector<HttpConnector>;
+/// Opens connections over TLS for HTTPS, and keeps the `ConnectionInfo` of each
+/// connection with the responses received over it.
+pub struct Connector {
+    inner: HttpsConnector<HttpConnector>,
+}
+
+impl Connect for Connector {
+    type Transport = MaybeHttpsStream<TcpConnection>;
+    type Error = <HttpsConnector<HttpConnector> as Connect>::Error;
+    type Future = Box<dyn Future<Item = (Self::Transport, Connected), Error = Self::Error> + Send>;
+
+    fn connect(&self, dest: Destination) -> Self::Future {
+        Box::new(self.inner.connect(dest).map(|(stream, connected)| {
+            let info = ConnectionInfo::new(&stream);
+            (stream, connected.extra(info))
+        }))
+    }
+}
 
 pub fn create_ssl_connector_builder(certs: &str) -> SslConnectorBuilder {
     // certs include multiple certificates. We could add all of them at once,
@@ -89,13 +366,20 @@ pub fn create_ssl_connector_builder(certs: &str) -> SslConnectorBuilder {
 
 pub fn create_http_client<E>(
     ssl_connector_builder: SslConnectorBuilder,
+    proxy_config: ProxyConfig,
+    auth_cache: Arc<RwLock<AuthCache>>,
     executor: E,
 ) -> Client<Connector, Body>
 where
     E: Executor<Box<dyn Future<Error = (), Item = ()> + Send + 'static>> + Sync + Send + 'static,
 {
-    let connector =
-        HttpsConnector::with_connector(HttpConnector::new(), ssl_connector_builder).unwrap();
+    let connector = Connector {
+        inner: HttpsConnector::with_connector(
+            HttpConnector::new(proxy_config, auth_cache),
+            ssl_connector_builder,
+        )
+        .unwrap(),
+    };
     Client::builder()
         .http1_title_case_headers(true)
         .executor(executor)
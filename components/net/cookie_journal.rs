@@ -0,0 +1,144 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![deny(missing_docs)]
+
+//! Incremental persistence of the cookie jar, so that cookies survive a crash.
+//!
+//! The jar is stored as a snapshot, `cookie_jar.json`, and a journal,
+//! `cookie_jar.journal`. Every change to the jar is appended to the journal
+//! as a single line of JSON as soon as it is made, and the journal is replayed
+//! on top of the snapshot on startup. A trailing line left incomplete by a
+//! crash is simply ignored.
+//!
+//! Once the journal grows long enough, the whole jar is written to a new
+//! snapshot, which is moved over the old one before the journal is truncated.
+
+use crate::cookie::Cookie;
+use crate::fs_util::write_atomically;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_FILE_NAME: &'static str = "cookie_jar.json";
+const JOURNAL_FILE_NAME: &'static str = "cookie_jar.journal";
+
+/// The number of journal entries after which the jar is written to a new snapshot.
+const MAX_JOURNAL_ENTRIES: usize = 1000;
+
+/// A single change to the cookie jar.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum JournalEntry {
+    /// A cookie was stored, replacing any cookie with the same domain, path and name.
+    Set(Cookie),
+    /// The cookie with the given domain, path and name was removed.
+    Remove {
+        /// The domain of the removed cookie.
+        domain: String,
+        /// The path of the removed cookie.
+        path: String,
+        /// The name of the removed cookie.
+        name: String,
+    },
+}
+
+/// The on-disk journal of a cookie jar.
+#[derive(Debug)]
+pub struct CookieJournal {
+    /// The directory in which the snapshot and the journal are stored.
+    root: PathBuf,
+    /// The journal, opened for appending.
+    file: File,
+    /// The number of entries currently in the journal.
+    len: usize,
+}
+
+impl CookieJournal {
+    /// Open the journal stored in `root`, returning it along with the contents
+    /// of the snapshot, if any, and the entries to replay on top of it.
+    /// Returns `None` if the journal can't be opened.
+    pub fn open(root: &Path) -> Option<(CookieJournal, Option<Vec<u8>>, Vec<JournalEntry>)> {
+        let snapshot = fs::read(root.join(SNAPSHOT_FILE_NAME)).ok();
+        let entries = read_entries(&root.join(JOURNAL_FILE_NAME));
+        let file = match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(root.join(JOURNAL_FILE_NAME))
+        {
+            Ok(file) => file,
+            Err(why) => {
+                warn!(
+                    "couldn't open cookie journal in {}: {}",
+                    root.display(),
+                    why
+                );
+                return None;
+            },
+        };
+        let journal = CookieJournal {
+            root: root.to_owned(),
+            file,
+            len: entries.len(),
+        };
+        Some((journal, snapshot, entries))
+    }
+
+    /// Append `entries` to the journal.
+    pub fn append(&mut self, entries: &[JournalEntry]) {
+        let mut data = vec![];
+        for entry in entries {
+            match serde_json::to_vec(entry) {
+                Ok(line) => data.extend_from_slice(&line),
+                Err(_) => continue,
+            }
+            data.push(b'\n');
+        }
+        // A single write, so that a crash can at most cut off the last line.
+        if let Err(why) = self.file.write_all(&data) {
+            warn!("couldn't write to cookie journal: {}", why);
+        }
+        self.len += entries.len();
+    }
+
+    /// Whether the journal has grown long enough to be folded into a new snapshot.
+    pub fn needs_compaction(&self) -> bool {
+        self.len >= MAX_JOURNAL_ENTRIES
+    }
+
+    /// Replace the snapshot with `snapshot`, and empty the journal.
+    pub fn compact(&mut self, snapshot: &[u8]) {
+        if let Err(why) = write_atomically(&self.root.join(SNAPSHOT_FILE_NAME), snapshot) {
+            // Keep the journal, it still holds changes missing from the old snapshot.
+            warn!("couldn't write cookie jar snapshot: {}", why);
+            return;
+        }
+        if let Err(why) = self.file.set_len(0) {
+            warn!("couldn't truncate cookie journal: {}", why);
+            return;
+        }
+        self.len = 0;
+    }
+}
+
+fn read_entries(path: &Path) -> Vec<JournalEntry> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(_) => return vec![],
+    };
+    let mut entries = vec![];
+    for line in data.split(|byte| *byte == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_slice(line) {
+            Ok(entry) => entries.push(entry),
+            // Only the last line can be incomplete, there is nothing left to replay.
+            Err(why) => {
+                warn!("ignoring truncated cookie journal entry: {}", why);
+                break;
+            },
+        }
+    }
+    entries
+}
@@ -6,18 +6,28 @@
 //! http://tools.ietf.org/html/rfc6265
 
 use crate::cookie::Cookie;
+use crate::cookie_journal::{CookieJournal, JournalEntry};
+use embedder_traits::{CookieChange, EmbedderMsg, EmbedderProxy};
+use msg::constellation_msg::TopLevelBrowsingContextId;
 use net_traits::pub_domains::reg_suffix;
 use net_traits::CookieSource;
 use servo_url::ServoUrl;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::path::Path;
 use time::{self, Tm};
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct CookieStorage {
     version: u32,
     cookies_map: HashMap<String, Vec<Cookie>>,
     max_per_host: usize,
+    /// The journal every change is written to, if this jar is persistent.
+    #[serde(skip)]
+    journal: Option<CookieJournal>,
+    /// The embedder to notify when cookies change.
+    #[serde(skip)]
+    embedder_proxy: Option<EmbedderProxy>,
 }
 
 impl CookieStorage {
@@ -26,15 +36,77 @@ impl CookieStorage {
             version: 1,
             cookies_map: HashMap::new(),
             max_per_host: max_cookies,
+            journal: None,
+            embedder_proxy: None,
         }
     }
 
+    /// Create a jar stored in `config_dir`, restoring the cookies saved there,
+    /// and writing every subsequent change to disk as it happens.
+    pub fn new_persistent(max_cookies: usize, config_dir: &Path) -> CookieStorage {
+        let (journal, snapshot, entries) = match CookieJournal::open(config_dir) {
+            Some(journal) => journal,
+            None => return CookieStorage::new(max_cookies),
+        };
+        let mut storage = snapshot
+            .and_then(|snapshot| match serde_json::from_slice(&snapshot) {
+                Ok(storage) => Some(storage),
+                Err(why) => {
+                    warn!("Could not decode cookie jar: {}", why);
+                    None
+                },
+            })
+            .unwrap_or_else(|| CookieStorage::new(max_cookies));
+        storage.max_per_host = max_cookies;
+        for entry in entries {
+            storage.apply(entry);
+        }
+        // Start from a fresh journal, so that nothing is ever appended
+        // after an entry left incomplete by a crash.
+        storage.journal = Some(journal);
+        storage.flush();
+        storage
+    }
+
+    /// Notify the embedder, through `embedder_proxy`, whenever cookies change.
+    pub fn set_embedder_proxy(&mut self, embedder_proxy: EmbedderProxy) {
+        self.embedder_proxy = Some(embedder_proxy);
+    }
+
+    /// Write the whole jar to disk, making the journal of changes redundant.
+    pub fn flush(&mut self) {
+        let mut journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return,
+        };
+        match serde_json::to_vec_pretty(&*self) {
+            Ok(snapshot) => journal.compact(&snapshot),
+            Err(why) => warn!("Could not encode cookie jar: {}", why),
+        }
+        self.journal = Some(journal);
+    }
+
     // http://tools.ietf.org/html/rfc6265#section-5.3
     pub fn remove(
         &mut self,
         cookie: &Cookie,
         url: &ServoUrl,
         source: CookieSource,
+    ) -> Result<Option<Cookie>, ()> {
+        let removed = self.take(cookie, url, source)?;
+        if let Some(ref removed) = removed {
+            let domain = reg_host(removed.cookie.domain().unwrap_or(""));
+            self.record_changes(&domain, vec![removal_entry(removed)], None);
+        }
+        Ok(removed)
+    }
+
+    /// Remove the cookie matching `cookie`, without recording the change.
+    fn take(
+        &mut self,
+        cookie: &Cookie,
+        url: &ServoUrl,
+        source: CookieSource,
     ) -> Result<Option<Cookie>, ()> {
         let domain = reg_host(cookie.cookie.domain().as_ref().unwrap_or(&""));
         let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
@@ -85,48 +157,71 @@ impl CookieStorage {
     }
     pub fn clear_storage(&mut self, url: &ServoUrl) {
         let domain = reg_host(url.host_str().unwrap_or(""));
-        let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
+        let cookies = self.cookies_map.entry(domain.clone()).or_insert(vec![]);
+        let mut changes = vec![];
         for cookie in cookies.iter_mut() {
             cookie.set_expiry_time_negative();
+            changes.push(JournalEntry::Set(cookie.clone()));
         }
+        self.record_changes(&domain, changes, None);
     }
 
     // http://tools.ietf.org/html/rfc6265#section-5.3
-    pub fn push(&mut self, mut cookie: Cookie, url: &ServoUrl, source: CookieSource) {
+    pub fn push(&mut self, cookie: Cookie, url: &ServoUrl, source: CookieSource) {
+        self.push_for_browser(cookie, url, source, None)
+    }
+
+    /// Store `cookie`, letting the embedder know the change was caused by `browser`.
+    pub fn push_for_browser(
+        &mut self,
+        mut cookie: Cookie,
+        url: &ServoUrl,
+        source: CookieSource,
+        browser: Option<TopLevelBrowsingContextId>,
+    ) {
         // https://www.ietf.org/id/draft-ietf-httpbis-cookie-alone-01.txt Step 1
         if cookie.cookie.secure().unwrap_or(false) && !url.is_secure_scheme() {
             return;
         }
 
-        let old_cookie = self.remove(&cookie, url, source);
+        let old_cookie = self.take(&cookie, url, source);
         if old_cookie.is_err() {
             // This new cookie is not allowed to overwrite an existing one.
             return;
         }
 
         // Step 11
+        let mut changes = vec![];
         if let Some(old_cookie) = old_cookie.unwrap() {
             // Step 11.3
             cookie.creation_time = old_cookie.creation_time;
+            changes.push(removal_entry(&old_cookie));
         }
 
         // Step 12
         let domain = reg_host(&cookie.cookie.domain().as_ref().unwrap_or(&""));
-        let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
+        let cookies = self.cookies_map.entry(domain.clone()).or_insert(vec![]);
 
+        let mut has_room = true;
         if cookies.len() == self.max_per_host {
-            let old_len = cookies.len();
-            cookies.retain(|c| !is_cookie_expired(&c));
-            let new_len = cookies.len();
+            let (expired, live): (Vec<Cookie>, Vec<Cookie>) =
+                cookies.drain(..).partition(is_cookie_expired);
+            *cookies = live;
+            changes.extend(expired.iter().map(removal_entry));
 
             // https://www.ietf.org/id/draft-ietf-httpbis-cookie-alone-01.txt
-            if new_len == old_len &&
-                !evict_one_cookie(cookie.cookie.secure().unwrap_or(false), cookies)
-            {
-                return;
+            if expired.is_empty() {
+                match evict_one_cookie(cookie.cookie.secure().unwrap_or(false), cookies) {
+                    Some(evicted_cookie) => changes.push(removal_entry(&evicted_cookie)),
+                    None => has_room = false,
+                }
             }
         }
-        cookies.push(cookie);
+        if has_room {
+            changes.push(JournalEntry::Set(cookie.clone()));
+            cookies.push(cookie);
+        }
+        self.record_changes(&domain, changes, browser);
     }
 
     pub fn cookie_comparator(a: &Cookie, b: &Cookie) -> Ordering {
@@ -205,12 +300,128 @@ impl CookieStorage {
                 c.cookie.clone()
             })
     }
+
+    /// All unexpired cookies whose domain is `domain` or one of its subdomains,
+    /// or all unexpired cookies if `domain` is `None`.
+    pub fn cookies_for_domain(&self, domain: Option<&str>) -> Vec<cookie_rs::Cookie<'static>> {
+        self.cookies_map
+            .values()
+            .flat_map(|cookies| cookies.iter())
+            .filter(|c| !is_cookie_expired(c) && is_in_domain(c, domain))
+            .map(|c| c.cookie.clone())
+            .collect()
+    }
+
+    /// Remove all cookies whose domain is `domain` or one of its subdomains,
+    /// or all cookies if `domain` is `None`.
+    pub fn remove_cookies_for_domain(&mut self, domain: Option<&str>) {
+        let mut changes = vec![];
+        for (host, cookies) in self.cookies_map.iter_mut() {
+            let (removed, kept): (Vec<Cookie>, Vec<Cookie>) =
+                cookies.drain(..).partition(|c| is_in_domain(c, domain));
+            *cookies = kept;
+            if !removed.is_empty() {
+                changes.push((host.clone(), removed));
+            }
+        }
+        for (host, removed) in changes {
+            self.record_changes(&host, removed.iter().map(removal_entry).collect(), None);
+        }
+    }
+
+    /// Apply a change read back from the journal.
+    fn apply(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Set(cookie) => {
+                let domain = reg_host(cookie.cookie.domain().unwrap_or(""));
+                let cookies = self.cookies_map.entry(domain).or_insert(vec![]);
+                cookies.retain(|c| {
+                    c.cookie.domain() != cookie.cookie.domain() ||
+                        c.cookie.path() != cookie.cookie.path() ||
+                        c.cookie.name() != cookie.cookie.name()
+                });
+                cookies.push(cookie);
+            },
+            JournalEntry::Remove { domain, path, name } => {
+                let cookies = self.cookies_map.entry(reg_host(&domain)).or_insert(vec![]);
+                cookies.retain(|c| {
+                    c.cookie.domain() != Some(&*domain) ||
+                        c.cookie.path() != Some(&*path) ||
+                        c.cookie.name() != name
+                });
+            },
+        }
+    }
+
+    /// Write `changes` to the cookies of `domain` to the journal,
+    /// and let the embedder know about them, along with the browser that caused them if any.
+    fn record_changes(
+        &mut self,
+        domain: &str,
+        changes: Vec<JournalEntry>,
+        browser: Option<TopLevelBrowsingContextId>,
+    ) {
+        if changes.is_empty() {
+            return;
+        }
+        let needs_compaction = match self.journal {
+            Some(ref mut journal) => {
+                journal.append(&changes);
+                journal.needs_compaction()
+            },
+            None => false,
+        };
+        if needs_compaction {
+            self.flush();
+        }
+        if let Some(ref embedder_proxy) = self.embedder_proxy {
+            let changes = changes.iter().map(cookie_change).collect();
+            let msg = EmbedderMsg::CookiesChanged(domain.to_owned(), changes);
+            embedder_proxy.send((browser, msg));
+        }
+    }
 }
 
 fn reg_host<'a>(url: &'a str) -> String {
     reg_suffix(url).to_lowercase()
 }
 
+fn is_in_domain(cookie: &Cookie, domain: Option<&str>) -> bool {
+    match domain {
+        Some(domain) => Cookie::domain_match(cookie.cookie.domain().unwrap_or(""), domain),
+        None => true,
+    }
+}
+
+fn cookie_change(entry: &JournalEntry) -> CookieChange {
+    match *entry {
+        JournalEntry::Set(ref cookie) => CookieChange {
+            domain: cookie.cookie.domain().unwrap_or("").to_owned(),
+            path: cookie.cookie.path().unwrap_or("").to_owned(),
+            name: cookie.cookie.name().to_owned(),
+            removed: false,
+        },
+        JournalEntry::Remove {
+            ref domain,
+            ref path,
+            ref name,
+        } => CookieChange {
+            domain: domain.clone(),
+            path: path.clone(),
+            name: name.clone(),
+            removed: true,
+        },
+    }
+}
+
+fn removal_entry(cookie: &Cookie) -> JournalEntry {
+    JournalEntry::Remove {
+        domain: cookie.cookie.domain().unwrap_or("").to_owned(),
+        path: cookie.cookie.path().unwrap_or("").to_owned(),
+        name: cookie.cookie.name().to_owned(),
+    }
+}
+
 fn is_cookie_expired(cookie: &Cookie) -> bool {
     match cookie.expiry_time {
         Some(ref t) => t.to_timespec() <= time::get_time(),
@@ -218,23 +429,22 @@ fn is_cookie_expired(cookie: &Cookie) -> bool {
     }
 }
 
-fn evict_one_cookie(is_secure_cookie: bool, cookies: &mut Vec<Cookie>) -> bool {
+/// Returns the evicted cookie, or `None` if there is no cookie that can be evicted
+/// to make room for a new one.
+fn evict_one_cookie(is_secure_cookie: bool, cookies: &mut Vec<Cookie>) -> Option<Cookie> {
     // Remove non-secure cookie with oldest access time
     let oldest_accessed: Option<(usize, Tm)> = get_oldest_accessed(false, cookies);
 
     if let Some((index, _)) = oldest_accessed {
-        cookies.remove(index);
+        Some(cookies.remove(index))
     } else {
         // All secure cookies were found
         if !is_secure_cookie {
-            return false;
+            return None;
         }
         let oldest_accessed: Option<(usize, Tm)> = get_oldest_accessed(true, cookies);
-        if let Some((index, _)) = oldest_accessed {
-            cookies.remove(index);
-        }
+        oldest_accessed.map(|(index, _)| cookies.remove(index))
     }
-    return true;
 }
 
 fn get_oldest_accessed(is_secure_cookie: bool, cookies: &mut Vec<Cookie>) -> Option<(usize, Tm)> {
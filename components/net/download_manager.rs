@@ -0,0 +1,573 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![deny(missing_docs)]
+
+//! Downloads, which fetch a resource to a file rather than to a document.
+//!
+//! A download is written to a `.part` file next to its destination, which is
+//! moved into place once the whole resource has been received. Pausing a
+//! download cancels its fetch but keeps the partial file, and resuming it
+//! fetches the rest of the resource with a `Range` request.
+
+use crate::http_loader::HttpState;
+use embedder_traits::{DownloadId, DownloadOutcome, EmbedderMsg, EmbedderProxy};
+use headers::{ContentLength, ContentRange, HeaderMapExt};
+use http::header::{self, HeaderValue};
+use hyper::StatusCode;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use mime::{self, Mime};
+use net_traits::request::{Destination, RedirectMode, Request, RequestBuilder};
+use net_traits::response::Response;
+use net_traits::{FetchTaskTarget, Metadata};
+use servo_config::basedir;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::percent_encoding::percent_decode;
+
+/// The minimum time between two progress reports of a download.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+static NEXT_DOWNLOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the response to a navigation is to be downloaded rather than displayed:
+/// either it is sent as an attachment, or no document can be made of its type.
+pub fn is_download(metadata: &Metadata) -> bool {
+    let is_attachment = metadata
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get(header::CONTENT_DISPOSITION))
+        .map_or(false, is_attachment);
+    let is_renderable = metadata.content_type.as_ref().map_or(true, |content_type| {
+        let mime: Mime = content_type.clone().into_inner().into();
+        is_renderable(&mime)
+    });
+    is_attachment || !is_renderable
+}
+
+/// The types of the responses that are displayed in a document rather than downloaded.
+fn is_renderable(mime: &Mime) -> bool {
+    if mime.type_() == mime::TEXT || mime.type_() == mime::IMAGE {
+        return true;
+    }
+    mime.type_() == mime::APPLICATION &&
+        (mime.subtype() == mime::XML ||
+            (mime.subtype() == "xhtml" && mime.suffix() == Some(mime::XML)))
+}
+
+/// <https://tools.ietf.org/html/rfc6266#section-4.2>
+fn is_attachment(content_disposition: &HeaderValue) -> bool {
+    content_disposition
+        .to_str()
+        .ok()
+        .and_then(|value| value.split(';').next())
+        .map_or(false, |disposition| {
+            disposition.trim().eq_ignore_ascii_case("attachment")
+        })
+}
+
+/// The file name given by a `Content-Disposition` header, preferring the
+/// extended `filename*` parameter over the plain `filename` one.
+/// <https://tools.ietf.org/html/rfc6266#section-4.3>
+fn content_disposition_file_name(content_disposition: &HeaderValue) -> Option<String> {
+    let value = content_disposition.to_str().ok()?;
+    let mut file_name = None;
+    for param in split_params(value).into_iter().skip(1) {
+        let mut parts = param.splitn(2, '=');
+        let name = parts.next()?.trim();
+        let value = match parts.next() {
+            Some(value) => unquote(value.trim()),
+            None => continue,
+        };
+        if name.eq_ignore_ascii_case("filename*") {
+            // <https://tools.ietf.org/html/rfc5987#section-3.2>: charset'language'value
+            let mut parts = value.splitn(3, '\'');
+            let charset = parts.next().unwrap_or("");
+            let encoded = match parts.nth(1) {
+                Some(encoded) if charset.eq_ignore_ascii_case("utf-8") => encoded,
+                _ => continue,
+            };
+            if let Ok(decoded) = percent_decode(encoded.as_bytes()).decode_utf8() {
+                return Some(decoded.into_owned());
+            }
+        } else if name.eq_ignore_ascii_case("filename") {
+            file_name = Some(value);
+        }
+    }
+    file_name
+}
+
+/// Split a header value on the semicolons that aren't in a quoted string.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                params.push(&value[start..index]);
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    params.push(&value[start..]);
+    params
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_owned();
+    }
+    let mut unquoted = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// The name of the file `response` is saved to: the one given by its
+/// `Content-Disposition` header, else the suggested one, else the last
+/// segment of its URL.
+fn file_name(response: &Response, suggested_name: Option<&str>) -> String {
+    let from_url = || {
+        let url = response.url()?;
+        let segment = url.as_url().path_segments()?.last()?;
+        percent_decode(segment.as_bytes())
+            .decode_utf8()
+            .ok()
+            .map(|segment| segment.into_owned())
+    };
+    response
+        .headers
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(content_disposition_file_name)
+        .and_then(|name| sanitize_file_name(&name))
+        .or_else(|| suggested_name.and_then(sanitize_file_name))
+        .or_else(|| from_url().and_then(|name| sanitize_file_name(&name)))
+        .unwrap_or_else(|| "download".to_owned())
+}
+
+/// Make `name`, which comes from the network or from a page, safe to use as
+/// the name of a file in the download directory.
+fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(|c| c == '/' || c == '\\').next()?;
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_owned())
+    }
+}
+
+/// The directory downloads are saved to.
+fn download_directory() -> PathBuf {
+    let directory = pref!(network.downloads.directory);
+    if !directory.is_empty() {
+        return PathBuf::from(directory);
+    }
+    basedir::default_download_dir().unwrap_or_else(env::temp_dir)
+}
+
+/// A path for a new file named `name` in `directory`, numbering the name if a
+/// file of that name already exists, or is being downloaded.
+fn unique_path(directory: &Path, name: &str) -> PathBuf {
+    let name = Path::new(name);
+    let stem = name.file_stem().map_or("download".into(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let extension = name
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+    let mut path = directory.join(name);
+    let mut number = 1;
+    while path.exists() || partial_path(&path).exists() {
+        let numbered = match extension {
+            Some(ref extension) => format!("{} ({}).{}", stem, number, extension),
+            None => format!("{} ({})", stem, number),
+        };
+        path = directory.join(numbered);
+        number += 1;
+    }
+    path
+}
+
+/// The path a download is written to until it is complete.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+struct Download {
+    /// The request to fetch the resource with.
+    request: RequestBuilder,
+    /// The name of the file suggested by the page, if any.
+    suggested_name: Option<String>,
+    /// The state the download is fetched with.
+    http_state: Arc<HttpState>,
+    /// Identifies the current fetch of the download. Incremented whenever a fetch is
+    /// started or stopped, so that whatever is left of an earlier fetch is ignored.
+    attempt: u64,
+    /// Cancels the current fetch, if there is one.
+    cancel_sender: Option<IpcSender<()>>,
+    /// The file the download is moved to once complete, once a response was received.
+    path: Option<PathBuf>,
+    /// The number of bytes written to the partial file.
+    received: u64,
+    /// The size of the resource, if known.
+    total: Option<u64>,
+    /// The `ETag` or `Last-Modified` header of the response, to make sure a
+    /// resumed download gets the rest of the same resource.
+    validator: Option<HeaderValue>,
+}
+
+/// Keeps track of the downloads, be they running or paused.
+#[derive(Clone)]
+pub struct DownloadManager {
+    downloads: Arc<Mutex<HashMap<DownloadId, Download>>>,
+    embedder_proxy: EmbedderProxy,
+    /// The directory downloads are saved to, if not the one of the preferences.
+    directory: Option<PathBuf>,
+}
+
+/// What is needed to start a fetch for a download.
+pub struct DownloadFetch {
+    /// The request to fetch.
+    pub request: RequestBuilder,
+    /// The state to fetch it with.
+    pub http_state: Arc<HttpState>,
+    /// The channel through which the fetch may be cancelled.
+    pub cancel_chan: IpcReceiver<()>,
+    /// Where the response is to be sent.
+    pub target: DownloadTarget,
+}
+
+impl DownloadManager {
+    /// Create a manager reporting on the downloads to `embedder_proxy`.
+    pub fn new(embedder_proxy: EmbedderProxy) -> DownloadManager {
+        DownloadManager {
+            downloads: Arc::new(Mutex::new(HashMap::new())),
+            embedder_proxy,
+            directory: None,
+        }
+    }
+
+    /// Create a manager saving the downloads to `directory`, whatever the
+    /// preferences say.
+    pub fn with_directory(embedder_proxy: EmbedderProxy, directory: PathBuf) -> DownloadManager {
+        DownloadManager {
+            directory: Some(directory),
+            ..DownloadManager::new(embedder_proxy)
+        }
+    }
+
+    /// Add a download of the resource requested by `request`, without starting it.
+    pub fn add(
+        &self,
+        mut request: RequestBuilder,
+        suggested_name: Option<String>,
+        http_state: Arc<HttpState>,
+    ) -> DownloadId {
+        // A download isn't for any kind of document, and follows redirects on its own.
+        request.destination = Destination::None;
+        request.redirect_mode = RedirectMode::Follow;
+        let id = DownloadId(NEXT_DOWNLOAD_ID.fetch_add(1, Ordering::SeqCst));
+        let download = Download {
+            request,
+            suggested_name,
+            http_state,
+            attempt: 0,
+            cancel_sender: None,
+            path: None,
+            received: 0,
+            total: None,
+            validator: None,
+        };
+        self.downloads.lock().unwrap().insert(id, download);
+        id
+    }
+
+    /// Prepare a fetch for the rest of the download `id`. Returns `None` if
+    /// the download doesn't exist, or is already being fetched.
+    pub fn start_fetch(&self, id: DownloadId) -> Option<DownloadFetch> {
+        let mut downloads = self.downloads.lock().unwrap();
+        let download = downloads.get_mut(&id)?;
+        if download.cancel_sender.is_some() {
+            return None;
+        }
+        let (cancel_sender, cancel_chan) = ipc::channel().expect("Failed to create IPC channel!");
+        download.attempt += 1;
+        download.cancel_sender = Some(cancel_sender);
+
+        let mut request = download.request.clone();
+        if download.received > 0 {
+            let range = format!("bytes={}-", download.received);
+            request
+                .headers
+                .insert(header::RANGE, HeaderValue::from_str(&range).unwrap());
+            if let Some(ref validator) = download.validator {
+                request.headers.insert(header::IF_RANGE, validator.clone());
+            }
+        }
+
+        Some(DownloadFetch {
+            request,
+            http_state: download.http_state.clone(),
+            cancel_chan,
+            target: DownloadTarget {
+                id,
+                attempt: download.attempt,
+                downloads: self.downloads.clone(),
+                embedder_proxy: self.embedder_proxy.clone(),
+                directory: self.directory.clone(),
+                file: None,
+                failure: None,
+                last_progress: None,
+            },
+        })
+    }
+
+    /// Stop fetching the download `id`, keeping what was received so far.
+    pub fn pause(&self, id: DownloadId) {
+        let mut downloads = self.downloads.lock().unwrap();
+        if let Some(download) = downloads.get_mut(&id) {
+            if let Some(cancel_sender) = download.cancel_sender.take() {
+                download.attempt += 1;
+                let _ = cancel_sender.send(());
+            }
+        }
+    }
+
+    /// Stop the download `id` for good, and remove what was received so far.
+    pub fn cancel(&self, id: DownloadId) {
+        let download = match self.downloads.lock().unwrap().remove(&id) {
+            Some(download) => download,
+            None => return,
+        };
+        if let Some(cancel_sender) = download.cancel_sender {
+            let _ = cancel_sender.send(());
+        }
+        if let Some(path) = download.path {
+            let _ = fs::remove_file(partial_path(&path));
+        }
+        self.embedder_proxy.send((
+            None,
+            EmbedderMsg::DownloadFinished(id, DownloadOutcome::Cancelled),
+        ));
+    }
+}
+
+/// Writes the response of a fetch for a download to its partial file.
+pub struct DownloadTarget {
+    id: DownloadId,
+    attempt: u64,
+    downloads: Arc<Mutex<HashMap<DownloadId, Download>>>,
+    embedder_proxy: EmbedderProxy,
+    directory: Option<PathBuf>,
+    file: Option<File>,
+    /// Why the download failed, if it did before the end of the response.
+    failure: Option<String>,
+    last_progress: Option<Instant>,
+}
+
+impl DownloadTarget {
+    /// The download this fetch is for, unless the fetch was stopped since.
+    fn current<'a>(
+        &self,
+        downloads: &'a mut HashMap<DownloadId, Download>,
+    ) -> Option<&'a mut Download> {
+        downloads
+            .get_mut(&self.id)
+            .filter(|download| download.attempt == self.attempt)
+    }
+
+    /// Open the partial file of `download` for `response`, appending to it if
+    /// `response` is the rest of what was received before.
+    fn open_file(&self, download: &mut Download, response: &Response) -> Result<File, String> {
+        let resumed = match response.status {
+            Some((StatusCode::PARTIAL_CONTENT, _)) if download.received > 0 => {
+                let content_range = response.headers.typed_get::<ContentRange>();
+                let start = content_range
+                    .as_ref()
+                    .and_then(|range| range.bytes_range())
+                    .map(|(start, _)| start);
+                if start != Some(download.received) {
+                    return Err("The server sent the wrong part of the resource".to_owned());
+                }
+                download.total = content_range.and_then(|range| range.bytes_len());
+                true
+            },
+            _ => {
+                download.received = 0;
+                download.total = response
+                    .headers
+                    .typed_get::<ContentLength>()
+                    .map(|length| length.0);
+                download.validator = response
+                    .headers
+                    .get(header::ETAG)
+                    .or_else(|| response.headers.get(header::LAST_MODIFIED))
+                    .cloned();
+                false
+            },
+        };
+
+        let path = match download.path {
+            Some(ref path) => path.clone(),
+            None => {
+                let directory = self.directory.clone().unwrap_or_else(download_directory);
+                fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+                let name = file_name(response, download.suggested_name.as_ref().map(|s| &**s));
+                let path = unique_path(&directory, &name);
+                download.path = Some(path.clone());
+                let url = response
+                    .url()
+                    .cloned()
+                    .unwrap_or_else(|| download.request.url.clone());
+                self.embedder_proxy.send((
+                    None,
+                    EmbedderMsg::DownloadStarted(self.id, url, path.clone()),
+                ));
+                path
+            },
+        };
+
+        let file = if resumed {
+            OpenOptions::new().append(true).open(partial_path(&path))
+        } else {
+            File::create(partial_path(&path))
+        };
+        file.map_err(|e| e.to_string())
+    }
+
+    /// Stop the fetch, which will fail with `reason` once it ends.
+    fn fail(&mut self, download: &mut Download, reason: String) {
+        self.failure = Some(reason);
+        if let Some(cancel_sender) = download.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+    }
+
+    fn finish(&mut self, download: &Download, response: &Response) -> DownloadOutcome {
+        if let Some(reason) = self.failure.take() {
+            return DownloadOutcome::Failed(reason);
+        }
+        if let Some(error) = response.get_network_error() {
+            return DownloadOutcome::Failed(format!("{:?}", error));
+        }
+        if response.aborted.load(Ordering::Relaxed) {
+            return DownloadOutcome::Failed("The fetch was aborted".to_owned());
+        }
+        let (path, file) = match (download.path.as_ref(), self.file.take()) {
+            (Some(path), Some(file)) => (path, file),
+            _ => {
+                let status = response.actual_response().raw_status.as_ref();
+                let status = status.map_or(String::new(), |status| {
+                    format!("{} {}", status.0, String::from_utf8_lossy(&status.1))
+                });
+                return DownloadOutcome::Failed(format!("Unexpected response {}", status));
+            },
+        };
+        if let Err(e) = file.sync_all() {
+            return DownloadOutcome::Failed(e.to_string());
+        }
+        match fs::rename(partial_path(path), path) {
+            Ok(()) => DownloadOutcome::Complete,
+            Err(e) => DownloadOutcome::Failed(e.to_string()),
+        }
+    }
+}
+
+impl FetchTaskTarget for DownloadTarget {
+    fn process_request_body(&mut self, _: &Request) {}
+
+    fn process_request_eof(&mut self, _: &Request) {}
+
+    fn process_response(&mut self, response: &Response) {
+        let downloads = self.downloads.clone();
+        let mut downloads = downloads.lock().unwrap();
+        let download = match self.current(&mut downloads) {
+            Some(download) => download,
+            None => return,
+        };
+        let response = response.actual_response();
+        match response.status {
+            Some((status, _)) if status.is_success() => {},
+            // Reported once the response ends.
+            _ => return,
+        }
+        match self.open_file(download, response) {
+            Ok(file) => self.file = Some(file),
+            Err(reason) => self.fail(download, reason),
+        }
+    }
+
+    fn process_response_chunk(&mut self, chunk: Vec<u8>) {
+        let downloads = self.downloads.clone();
+        let mut downloads = downloads.lock().unwrap();
+        let download = match self.current(&mut downloads) {
+            Some(download) => download,
+            None => return,
+        };
+        let result = match self.file {
+            Some(ref mut file) => file.write_all(&chunk),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.file = None;
+            return self.fail(download, e.to_string());
+        }
+        download.received += chunk.len() as u64;
+
+        if self.last_progress.map_or(true, |last_progress| {
+            last_progress.elapsed() >= PROGRESS_INTERVAL
+        }) {
+            self.last_progress = Some(Instant::now());
+            self.embedder_proxy.send((
+                None,
+                EmbedderMsg::DownloadProgress(self.id, download.received, download.total),
+            ));
+        }
+    }
+
+    fn process_response_eof(&mut self, response: &Response) {
+        let downloads = self.downloads.clone();
+        let mut downloads = downloads.lock().unwrap();
+        if self.current(&mut downloads).is_none() {
+            return;
+        }
+        let download = downloads.remove(&self.id).unwrap();
+        let outcome = self.finish(&download, response);
+        if outcome != DownloadOutcome::Complete {
+            if let Some(ref path) = download.path {
+                let _ = fs::remove_file(partial_path(path));
+            }
+        } else {
+            self.embedder_proxy.send((
+                None,
+                EmbedderMsg::DownloadProgress(self.id, download.received, download.total),
+            ));
+        }
+        self.embedder_proxy
+            .send((None, EmbedderMsg::DownloadFinished(self.id, outcome)));
+    }
+
+    fn process_csp_violations(&mut self, _: &Request, _: Vec<net_traits::csp::Violation>) {}
+}
@@ -5,26 +5,30 @@
 use crate::data_loader::decode;
 use crate::fetch::cors_cache::CorsCache;
 use crate::filemanager_thread::{fetch_file_in_chunks, FileManager, FILE_CHUNK_SIZE};
-use crate::http_loader::{determine_request_referrer, http_fetch, HttpState};
+use crate::http_loader::send_blocked_mixed_content_request_to_devtools;
+use crate::http_loader::{determine_request_referrer, http_fetch, CredentialsPrompt, HttpState};
 use crate::http_loader::{set_default_accept, set_default_accept_language};
 use crate::subresource_integrity::is_response_integrity_valid;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use devtools_traits::DevtoolsControlMsg;
+use embedder_traits::{CredentialsRequest, EmbedderProxy};
 use headers::{AccessControlExposeHeaders, ContentType, HeaderMapExt, Range};
 use http::header::{self, HeaderMap, HeaderName, HeaderValue};
 use hyper::Method;
 use hyper::StatusCode;
-use ipc_channel::ipc::IpcReceiver;
+use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use mime::{self, Mime};
 use mime_guess::guess_mime_type;
+use msg::constellation_msg::TopLevelBrowsingContextId;
 use net_traits::blob_url_store::{parse_blob_url, BlobURLStoreError};
+use net_traits::csp::{CheckResult, PolicyDisposition};
 use net_traits::filemanager_thread::RelativePos;
 use net_traits::request::{CredentialsMode, Destination, Referrer, Request, RequestMode};
-use net_traits::request::{Origin, ResponseTainting, Window};
+use net_traits::request::{Initiator, MixedContent, Origin, ResponseTainting, Window};
 use net_traits::response::{Response, ResponseBody, ResponseType};
-use net_traits::ResourceAttribute;
+use net_traits::{CustomResponseMediator, ResourceAttribute};
 use net_traits::{FetchTaskTarget, NetworkError, ReferrerPolicy, ResourceFetchTiming};
-use servo_url::ServoUrl;
+use servo_url::{Host, ImmutableOrigin, ServoUrl};
 use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufReader, Seek, SeekFrom};
@@ -52,8 +56,25 @@ pub struct FetchContext {
     pub user_agent: Cow<'static, str>,
     pub devtools_chan: Option<Sender<DevtoolsControlMsg>>,
     pub filemanager: FileManager,
+    pub embedder_proxy: EmbedderProxy,
+    /// Where requests are sent for a service worker to respond to them.
+    pub swmanager_chan: Option<IpcSender<CustomResponseMediator>>,
     pub cancellation_listener: Arc<Mutex<CancellationListener>>,
     pub timing: Arc<Mutex<ResourceFetchTiming>>,
+    pub credentials_prompt: Arc<Mutex<CredentialsPrompt>>,
+}
+
+impl FetchContext {
+    /// The request for credentials the fetch was stopped for, if any, along with the
+    /// browser whose user is to be asked.
+    pub fn deferred_credentials_request(
+        &self,
+    ) -> Option<(Option<TopLevelBrowsingContextId>, CredentialsRequest)> {
+        match *self.credentials_prompt.lock().unwrap() {
+            CredentialsPrompt::Defer(ref request) => request.clone(),
+            _ => None,
+        }
+    }
 }
 
 pub struct CancellationListener {
@@ -163,10 +184,12 @@ pub fn main_fetch(
     }
 
     // Step 3.
-    // TODO: handle content security policy violations.
+    // <https://w3c.github.io/webappsec-csp/#report-for-request>
+    report_csp_violations_for_request(request, target, PolicyDisposition::Report);
 
     // Step 4.
     // TODO: handle upgrade to a potentially secure URL.
+    upgrade_mixed_content_request(request);
 
     // Step 5.
     if should_be_blocked_due_to_bad_port(&request.current_url()) {
@@ -174,8 +197,22 @@ pub fn main_fetch(
             "Request attempted on bad port".into(),
         )));
     }
-    // TODO: handle blocking as mixed content.
-    // TODO: handle blocking by content security policy.
+    if should_request_be_blocked_as_mixed_content(request) {
+        request.mixed_content = Some(MixedContent::Blocked);
+        if let Some(ref devtools_chan) = context.devtools_chan {
+            send_blocked_mixed_content_request_to_devtools(request, devtools_chan);
+        }
+        response = Some(Response::network_error(NetworkError::Internal(
+            "Blocked as mixed content".into(),
+        )));
+    }
+    if report_csp_violations_for_request(request, target, PolicyDisposition::Enforce) ==
+        CheckResult::Blocked
+    {
+        response = Some(Response::network_error(NetworkError::Internal(
+            "Blocked by Content Security Policy".into(),
+        )));
+    }
 
     // Step 6
     // TODO: handle request's client's referrer policy.
@@ -291,6 +328,12 @@ pub fn main_fetch(
         return response;
     }
 
+    // Nothing is processed of a fetch that is to be started again, once the user
+    // answers a request for credentials.
+    if context.deferred_credentials_request().is_some() {
+        return response;
+    }
+
     // Step 14.
     let mut response = if !response.is_network_error() && response.internal_response.is_none() {
         // Substep 1.
@@ -338,6 +381,8 @@ pub fn main_fetch(
             should_be_blocked_due_to_nosniff(request.destination, &response.headers);
         let should_replace_with_mime_type_error = !response_is_network_error &&
             should_be_blocked_due_to_mime_type(request.destination, &response.headers);
+        let should_replace_with_mixed_content_error = !response_is_network_error &&
+            should_response_be_blocked_as_mixed_content(request, &response);
 
         // Step 15.
         let mut network_error_response = response
@@ -356,10 +401,14 @@ pub fn main_fetch(
         }
 
         // Step 17.
-        // TODO: handle blocking as mixed content.
         // TODO: handle blocking by content security policy.
         let blocked_error_response;
-        let internal_response = if should_replace_with_nosniff_error {
+        let internal_response = if should_replace_with_mixed_content_error {
+            // Defer rebinding result
+            blocked_error_response =
+                Response::network_error(NetworkError::Internal("Blocked as mixed content".into()));
+            &blocked_error_response
+        } else if should_replace_with_nosniff_error {
             // Defer rebinding result
             blocked_error_response =
                 Response::network_error(NetworkError::Internal("Blocked by nosniff".into()));
@@ -921,3 +970,151 @@ fn is_bad_port(port: u16) -> bool {
 
     BAD_PORTS.binary_search(&port).is_ok()
 }
+
+/// <https://w3c.github.io/webappsec-secure-contexts/#is-url-trustworthy>
+pub fn is_url_potentially_trustworthy(url: &ServoUrl) -> bool {
+    // Step 1.
+    if url.as_str() == "about:blank" || url.as_str() == "about:srcdoc" {
+        return true;
+    }
+
+    // Step 2.
+    if url.scheme() == "data" {
+        return true;
+    }
+
+    // Step 3.
+    // File URLs have an opaque origin, so check for them before computing it.
+    url.scheme() == "file" || is_origin_potentially_trustworthy(&url.origin())
+}
+
+/// <https://w3c.github.io/webappsec-secure-contexts/#is-origin-trustworthy>
+fn is_origin_potentially_trustworthy(origin: &ImmutableOrigin) -> bool {
+    match *origin {
+        // Step 1.
+        ImmutableOrigin::Opaque(_) => false,
+        ImmutableOrigin::Tuple(ref scheme, ref host, _) => {
+            // Step 3.
+            if scheme == "https" || scheme == "wss" {
+                return true;
+            }
+
+            // Steps 4-5.
+            match *host {
+                Host::Ipv4(address) => address.is_loopback(),
+                Host::Ipv6(address) => address.is_loopback(),
+                Host::Domain(ref domain) => domain == "localhost" || domain.ends_with(".localhost"),
+            }
+        },
+    }
+}
+
+/// <https://w3c.github.io/webappsec-mixed-content/#categorize-settings-object>
+fn does_client_prohibit_mixed_security_contexts(request: &Request) -> bool {
+    // Step 1.
+    let is_trustworthy = match request.origin {
+        Origin::Origin(ref origin) => is_origin_potentially_trustworthy(origin),
+        Origin::Client => false,
+    };
+
+    // Step 2.
+    is_trustworthy || request.has_trustworthy_ancestor_origin
+}
+
+/// Whether a request is exempt from mixed content checks because it is a
+/// top-level navigation. Navigations of nested browsing contexts are checked.
+fn is_top_level_navigation_request(request: &Request) -> bool {
+    request.destination == Destination::Document
+}
+
+/// Check `request` against its policies, reporting the violations of those with
+/// the given disposition. Report-only policies are checked before the request is
+/// upgraded, and enforced policies after, as the fetch specification orders it.
+/// <https://w3c.github.io/webappsec-csp/#should-block-request>
+fn report_csp_violations_for_request(
+    request: &Request,
+    target: Target,
+    disposition: PolicyDisposition,
+) -> CheckResult {
+    let csp_list = match request.csp_list {
+        Some(ref csp_list) => csp_list,
+        None => return CheckResult::Allowed,
+    };
+    let (result, violations) = csp_list.should_request_be_blocked(request);
+    let violations: Vec<_> = violations
+        .into_iter()
+        .filter(|violation| violation.disposition == disposition)
+        .collect();
+    if !violations.is_empty() {
+        target.process_csp_violations(request, violations);
+    }
+    match disposition {
+        PolicyDisposition::Enforce => result,
+        PolicyDisposition::Report => CheckResult::Allowed,
+    }
+}
+
+/// <https://w3c.github.io/webappsec-mixed-content/#category-upgradeable>
+fn is_upgradeable_mixed_content(request: &Request) -> bool {
+    match request.destination {
+        Destination::Image => request.initiator != Initiator::ImageSet,
+        Destination::Audio | Destination::Video => true,
+        _ => false,
+    }
+}
+
+/// <https://w3c.github.io/webappsec-mixed-content/#upgrade-algorithm>
+fn upgrade_mixed_content_request(request: &mut Request) {
+    // Step 1.
+    let url = request.current_url();
+    if is_url_potentially_trustworthy(&url) ||
+        !does_client_prohibit_mixed_security_contexts(request) ||
+        !is_upgradeable_mixed_content(request)
+    {
+        return;
+    }
+
+    // Requests to IP addresses are not upgraded, since certificates are rarely
+    // issued for them. They are still optionally-blockable, so let them through.
+    let is_ip_address = match url.host() {
+        Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)) => true,
+        _ => false,
+    };
+    if is_ip_address || url.scheme() != "http" {
+        request.mixed_content = Some(MixedContent::Allowed);
+        return;
+    }
+
+    // Step 2.
+    let _ = request.current_url_mut().as_mut_url().set_scheme("https");
+    request.mixed_content = Some(MixedContent::Upgraded);
+}
+
+/// <https://w3c.github.io/webappsec-mixed-content/#should-block-fetch>
+pub fn should_request_be_blocked_as_mixed_content(request: &Request) -> bool {
+    // Step 1.
+    let allowed = request.mixed_content == Some(MixedContent::Allowed) ||
+        is_url_potentially_trustworthy(&request.current_url()) ||
+        !does_client_prohibit_mixed_security_contexts(request) ||
+        is_top_level_navigation_request(request);
+
+    // Step 2.
+    !allowed
+}
+
+/// <https://w3c.github.io/webappsec-mixed-content/#should-block-response>
+pub fn should_response_be_blocked_as_mixed_content(request: &Request, response: &Response) -> bool {
+    let url = match response.actual_response().url() {
+        Some(url) => url,
+        None => return false,
+    };
+
+    // Step 1.
+    let allowed = request.mixed_content == Some(MixedContent::Allowed) ||
+        is_url_potentially_trustworthy(url) ||
+        !does_client_prohibit_mixed_security_contexts(request) ||
+        is_top_level_navigation_request(request);
+
+    // Step 2.
+    !allowed
+}
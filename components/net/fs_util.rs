@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Helpers for the files in which the resource threads keep their state.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Write `data` to a temporary file, and then move it to `path`,
+/// so a crash can never leave a half-written file behind.
+pub fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)
+}
+
+/// A 64-bit FNV-1a hash of `bytes`, used to name files after arbitrary keys.
+///
+/// Unlike the standard library hasher, its output is stable across releases.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,292 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Parsing of HTTP authentication challenges, and the credentials answering them.
+//!
+//! Only the [Basic](https://tools.ietf.org/html/rfc7617) and
+//! [Digest](https://tools.ietf.org/html/rfc7616) schemes are supported.
+
+use embedder_traits::{AuthenticationScheme, Credentials};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::Method;
+use openssl::hash::{hash, MessageDigest};
+use servo_url::ServoUrl;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A challenge from a `WWW-Authenticate` or `Proxy-Authenticate` header.
+#[derive(Clone, Debug)]
+pub struct Challenge {
+    pub scheme: AuthenticationScheme,
+    pub realm: String,
+    /// The other parameters of the challenge, keyed by their lowercase name.
+    params: HashMap<String, String>,
+}
+
+impl Challenge {
+    /// The value of the `Authorization` or `Proxy-Authorization` header answering this
+    /// challenge with `credentials`, for a `method` request to `url`.
+    pub fn authorization(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &ServoUrl,
+    ) -> Option<HeaderValue> {
+        let value = match self.scheme {
+            AuthenticationScheme::Basic => format!(
+                "Basic {}",
+                base64::encode(&format!(
+                    "{}:{}",
+                    credentials.user_name, credentials.password
+                ))
+            ),
+            AuthenticationScheme::Digest => self.digest_authorization(credentials, method, url)?,
+        };
+        HeaderValue::from_bytes(value.as_bytes()).ok()
+    }
+
+    /// <https://tools.ietf.org/html/rfc7616#section-3.4>
+    fn digest_authorization(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &ServoUrl,
+    ) -> Option<String> {
+        let nonce = self.params.get("nonce")?;
+        let algorithm = self
+            .params
+            .get("algorithm")
+            .map_or("MD5", |algorithm| &**algorithm);
+        let (message_digest, session) = match &*algorithm.to_ascii_uppercase() {
+            "MD5" => (MessageDigest::md5(), false),
+            "MD5-SESS" => (MessageDigest::md5(), true),
+            "SHA-256" => (MessageDigest::sha256(), false),
+            "SHA-256-SESS" => (MessageDigest::sha256(), true),
+            _ => return None,
+        };
+        // Integrity protection of the body isn't supported, only plain "auth" is.
+        let qop = match self.params.get("qop") {
+            Some(qop) if qop.split(',').any(|qop| qop.trim() == "auth") => Some("auth"),
+            Some(_) => return None,
+            None => None,
+        };
+        let digest = |data: String| {
+            hash(message_digest, data.as_bytes())
+                .ok()
+                .map(|digest| to_hex(&digest))
+        };
+
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        };
+        let cnonce = Uuid::new_v4().to_simple().to_string();
+        // Every challenge is only answered once, so this is always the first use of the nonce.
+        let nonce_count = "00000001";
+
+        let mut ha1 = digest(format!(
+            "{}:{}:{}",
+            credentials.user_name, self.realm, credentials.password
+        ))?;
+        if session {
+            ha1 = digest(format!("{}:{}:{}", ha1, nonce, cnonce))?;
+        }
+        let ha2 = digest(format!("{}:{}", method.as_str(), uri))?;
+        let response = match qop {
+            Some(qop) => digest(format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, nonce, nonce_count, cnonce, qop, ha2
+            ))?,
+            None => digest(format!("{}:{}:{}", ha1, nonce, ha2))?,
+        };
+
+        let mut value = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            quote(&credentials.user_name),
+            quote(&self.realm),
+            quote(nonce),
+            quote(&uri),
+            algorithm,
+            response
+        );
+        if let Some(qop) = qop {
+            value.push_str(&format!(
+                ", qop={}, nc={}, cnonce=\"{}\"",
+                qop, nonce_count, cnonce
+            ));
+        }
+        if let Some(opaque) = self.params.get("opaque") {
+            value.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        Some(value)
+    }
+}
+
+/// The supported challenges of the `name` headers of `headers`, strongest first.
+pub fn parse_challenges(headers: &HeaderMap, name: HeaderName) -> Vec<Challenge> {
+    let mut challenges: Vec<Challenge> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_header_value)
+        .collect();
+    // Digest doesn't send the password in the clear, so prefer it over Basic.
+    challenges.sort_by_key(|challenge| challenge.scheme != AuthenticationScheme::Digest);
+    challenges
+}
+
+/// Whether `value`, an `Authorization` or `Proxy-Authorization` header,
+/// holds credentials for `scheme`.
+pub fn is_for_scheme(value: &HeaderValue, scheme: AuthenticationScheme) -> bool {
+    let name: &[u8] = match scheme {
+        AuthenticationScheme::Basic => b"basic ",
+        AuthenticationScheme::Digest => b"digest ",
+    };
+    let value = value.as_bytes();
+    value.len() >= name.len() && value[..name.len()].eq_ignore_ascii_case(name)
+}
+
+/// Parse a header value holding a comma-separated list of challenges, where the
+/// parameters of a challenge are themselves separated by commas.
+fn parse_header_value(value: &str) -> Vec<Challenge> {
+    let mut tokenizer = Tokenizer {
+        input: value,
+        position: 0,
+    };
+    let mut challenges = vec![];
+    let mut current: Option<(&str, HashMap<String, String>)> = None;
+    loop {
+        tokenizer.skip_separators();
+        if tokenizer.is_at_end() {
+            break;
+        }
+        let name = match tokenizer.token() {
+            Some(name) => name,
+            None => {
+                tokenizer.skip_past_comma();
+                continue;
+            },
+        };
+        tokenizer.skip_whitespace();
+        if tokenizer.eat(b'=') {
+            tokenizer.skip_whitespace();
+            if let (Some(value), Some((_, params))) = (tokenizer.value(), current.as_mut()) {
+                params.insert(name.to_ascii_lowercase(), value);
+            }
+            tokenizer.skip_past_comma();
+        } else {
+            // A token that isn't followed by `=` starts a new challenge.
+            challenges.extend(current.take().and_then(to_challenge));
+            current = Some((name, HashMap::new()));
+        }
+    }
+    challenges.extend(current.and_then(to_challenge));
+    challenges
+}
+
+fn to_challenge((scheme, mut params): (&str, HashMap<String, String>)) -> Option<Challenge> {
+    let scheme = if scheme.eq_ignore_ascii_case("basic") {
+        AuthenticationScheme::Basic
+    } else if scheme.eq_ignore_ascii_case("digest") {
+        AuthenticationScheme::Digest
+    } else {
+        return None;
+    };
+    let realm = params.remove("realm")?;
+    Some(Challenge {
+        scheme,
+        realm,
+        params,
+    })
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn is_at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).cloned()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.eat(b' ') || self.eat(b'\t') {}
+    }
+
+    fn skip_separators(&mut self) {
+        while self.eat(b' ') || self.eat(b'\t') || self.eat(b',') {}
+    }
+
+    /// Skip anything left of the current element of the list, such as a malformed value.
+    fn skip_past_comma(&mut self) {
+        match self.input[self.position..].find(',') {
+            Some(offset) => self.position += offset + 1,
+            None => self.position = self.input.len(),
+        }
+    }
+
+    /// <https://tools.ietf.org/html/rfc7230#section-3.2.6>
+    fn token(&mut self) -> Option<&'a str> {
+        let start = self.position;
+        while let Some(byte) = self.peek() {
+            if !byte.is_ascii_alphanumeric() && !b"!#$%&'*+-.^_`|~".contains(&byte) {
+                break;
+            }
+            self.position += 1;
+        }
+        if self.position == start {
+            None
+        } else {
+            Some(&self.input[start..self.position])
+        }
+    }
+
+    fn value(&mut self) -> Option<String> {
+        if self.peek() == Some(b'"') {
+            self.quoted_string()
+        } else {
+            self.token().map(str::to_owned)
+        }
+    }
+
+    /// <https://tools.ietf.org/html/rfc7230#section-3.2.6>
+    fn quoted_string(&mut self) -> Option<String> {
+        let mut value = String::new();
+        let mut chars = self.input[self.position + 1..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += offset + 2;
+                    return Some(value);
+                },
+                '\\' => value.push(chars.next()?.1),
+                c => value.push(c),
+            }
+        }
+        None
+    }
+}
+
+/// Escape `value` so that it can be put in a quoted string.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
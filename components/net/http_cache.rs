@@ -4,15 +4,17 @@
 
 #![deny(missing_docs)]
 
-//! A memory cache implementing the logic specified in <http://tools.ietf.org/html/rfc7234>
+//! A memory cache, optionally backed by a disk cache,
+//! implementing the logic specified in <http://tools.ietf.org/html/rfc7234>
 //! and <http://tools.ietf.org/html/rfc7232>.
 
 use crate::fetch::methods::{Data, DoneChannel};
+use crate::http_cache_disk::{DiskCache, StoredResource};
 use crossbeam_channel::{unbounded, Sender};
 use headers::{
     CacheControl, ContentRange, Expires, HeaderMapExt, LastModified, Pragma, Range, Vary,
 };
-use http::header::HeaderValue;
+use http::header::{HeaderName, HeaderValue};
 use http::{header, HeaderMap};
 use hyper::{Method, StatusCode};
 use malloc_size_of::Measurable;
@@ -26,6 +28,7 @@ use servo_arc::Arc;
 use servo_url::ServoUrl;
 use std::collections::HashMap;
 use std::ops::Bound;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::SystemTime;
@@ -58,6 +61,8 @@ struct CachedResource {
     body: Arc<Mutex<ResponseBody>>,
     aborted: Arc<AtomicBool>,
     awaiting_body: Arc<Mutex<Vec<Sender<Data>>>>,
+    /// The file of the disk cache holding the body of this resource, once it is complete.
+    stored_body: Option<String>,
     data: Measurable<MeasurableCachedResource>,
 }
 
@@ -120,11 +125,14 @@ pub struct CachedResponse {
     pub needs_validation: bool,
 }
 
-/// A memory cache.
+/// A memory cache, optionally backed by a disk cache.
 #[derive(MallocSizeOf)]
 pub struct HttpCache {
     /// cached responses.
     entries: HashMap<CacheKey, Vec<CachedResource>>,
+    /// The on-disk tier of the cache, if any.
+    #[ignore_malloc_size_of = "Stored on disk"]
+    disk: Option<DiskCache>,
 }
 
 /// Determine if a given response is cacheable based on the initial metadata received.
@@ -348,6 +356,7 @@ fn create_resource_with_bytes_from_resource(
         body: Arc::new(Mutex::new(ResponseBody::Done(bytes.to_owned()))),
         aborted: Arc::new(AtomicBool::new(false)),
         awaiting_body: Arc::new(Mutex::new(vec![])),
+        stored_body: None,
         data: Measurable(MeasurableCachedResource {
             metadata: resource.data.metadata.clone(),
             location_url: resource.data.location_url.clone(),
@@ -361,6 +370,98 @@ fn create_resource_with_bytes_from_resource(
     }
 }
 
+/// The headers of the original request of a resource named by its `Vary` header,
+/// the only ones needed to match later requests against it.
+/// Returns `None` if the resource varies on credentials, which are never written to disk.
+fn vary_request_headers(resource: &CachedResource) -> Option<HeaderMap> {
+    let mut vary_headers = HeaderMap::new();
+    let response_headers = resource.data.metadata.headers.lock().unwrap();
+    let vary = match response_headers.typed_get::<Vary>() {
+        Some(vary) => vary,
+        None => return Some(vary_headers),
+    };
+    if vary.is_any() {
+        // Never used to construct a response anyway.
+        return None;
+    }
+    let request_headers = resource.request_headers.lock().unwrap();
+    for name in vary.iter_strs() {
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        if name == header::AUTHORIZATION ||
+            name == header::COOKIE ||
+            name == header::PROXY_AUTHORIZATION
+        {
+            return None;
+        }
+        for value in request_headers.get_all(&name) {
+            vary_headers.append(name.clone(), value.clone());
+        }
+    }
+    Some(vary_headers)
+}
+
+/// Convert a resource to its on-disk representation.
+fn stored_resource_from_resource(
+    resource: &CachedResource,
+    request_headers: HeaderMap,
+    body_file: String,
+    body_len: u64,
+) -> StoredResource {
+    let metadata = &resource.data.metadata;
+    StoredResource {
+        request_headers,
+        response_headers: metadata.headers.lock().unwrap().clone(),
+        final_url: metadata.data.final_url.clone(),
+        content_type: metadata.data.content_type.clone(),
+        charset: metadata.data.charset.clone(),
+        metadata_status: metadata.data.status.clone(),
+        location_url: resource.data.location_url.clone(),
+        https_state: resource.data.https_state.clone(),
+        status: resource
+            .data
+            .status
+            .as_ref()
+            .map(|&(ref code, ref reason)| (code.as_u16(), reason.clone())),
+        raw_status: resource.data.raw_status.clone(),
+        url_list: resource.data.url_list.clone(),
+        expires: resource.data.expires.num_seconds(),
+        last_validated: resource.data.last_validated.to_timespec().sec,
+        body_file,
+        body_len,
+    }
+}
+
+/// Create a resource from its on-disk representation.
+fn resource_from_stored_resource(stored_resource: StoredResource, body: Vec<u8>) -> CachedResource {
+    CachedResource {
+        request_headers: Arc::new(Mutex::new(stored_resource.request_headers)),
+        body: Arc::new(Mutex::new(ResponseBody::Done(body))),
+        aborted: Arc::new(AtomicBool::new(false)),
+        awaiting_body: Arc::new(Mutex::new(vec![])),
+        stored_body: Some(stored_resource.body_file),
+        data: Measurable(MeasurableCachedResource {
+            metadata: CachedMetadata {
+                headers: Arc::new(Mutex::new(stored_resource.response_headers)),
+                data: Measurable(MeasurableCachedMetadata {
+                    final_url: stored_resource.final_url,
+                    content_type: stored_resource.content_type,
+                    charset: stored_resource.charset,
+                    status: stored_resource.metadata_status,
+                }),
+            },
+            location_url: stored_resource.location_url,
+            https_state: stored_resource.https_state,
+            status: stored_resource.status.and_then(|(code, reason)| {
+                StatusCode::from_u16(code).ok().map(|code| (code, reason))
+            }),
+            raw_status: stored_resource.raw_status,
+            url_list: stored_resource.url_list,
+            expires: Duration::seconds(stored_resource.expires),
+            last_validated: time::at(Timespec::new(stored_resource.last_validated, 0)),
+        }),
+    }
+}
+
 /// Support for range requests <https://tools.ietf.org/html/rfc7233>.
 fn handle_range_request(
     request: &Request,
@@ -560,6 +661,123 @@ impl HttpCache {
     pub fn new() -> HttpCache {
         HttpCache {
             entries: HashMap::new(),
+            disk: None,
+        }
+    }
+
+    /// Create a new memory cache instance, backed by a disk cache stored in `directory`,
+    /// whose files use at most `max_size` bytes.
+    /// If the directory can't be used, the cache only lives in memory.
+    pub fn new_persistent(directory: PathBuf, max_size: u64) -> HttpCache {
+        HttpCache {
+            entries: HashMap::new(),
+            disk: DiskCache::new(directory, max_size),
+        }
+    }
+
+    /// Whether resources for `request` are stored on disk, but haven't been loaded yet.
+    /// Responses are only constructed from resources in memory,
+    /// so `load_from_disk` needs to be called first if this returns true.
+    pub fn needs_loading_from_disk(&self, request: &Request) -> bool {
+        if request.method != Method::GET {
+            return false;
+        }
+        let entry_key = CacheKey::new(request.clone());
+        !self.entries.contains_key(&entry_key) &&
+            self.disk
+                .as_ref()
+                .map_or(false, |disk| disk.contains(&entry_key.url))
+    }
+
+    /// Load the resources stored on disk for `request`, unless they are already in memory.
+    pub fn load_from_disk(&mut self, request: &Request) {
+        if request.method != Method::GET {
+            return;
+        }
+        self.load_entry_from_disk(&CacheKey::new(request.clone()));
+    }
+
+    /// Load the resources stored on disk for a key, unless they are already in memory.
+    fn load_entry_from_disk(&mut self, entry_key: &CacheKey) {
+        if self.entries.contains_key(entry_key) {
+            return;
+        }
+        let disk = match self.disk {
+            Some(ref mut disk) => disk,
+            None => return,
+        };
+        if let Some(stored_resources) = disk.load(&entry_key.url) {
+            let resources = stored_resources
+                .into_iter()
+                .map(|(stored_resource, body)| resource_from_stored_resource(stored_resource, body))
+                .collect();
+            self.entries.insert(entry_key.clone(), resources);
+        }
+    }
+
+    /// Write the resources with a complete body stored for a key to disk.
+    /// Bodies are only written once, later calls only update the metadata.
+    fn persist(&mut self, entry_key: &CacheKey) {
+        let disk = match self.disk {
+            Some(ref mut disk) => disk,
+            None => return,
+        };
+        let cached_resources = match self.entries.get_mut(entry_key) {
+            Some(cached_resources) => cached_resources,
+            None => return,
+        };
+        let mut stored_resources = vec![];
+        let mut new_bodies = vec![];
+        for cached_resource in cached_resources.iter_mut() {
+            if cached_resource.aborted.load(Ordering::Relaxed) {
+                continue;
+            }
+            let (body_len, new_body) = match *cached_resource.body.lock().unwrap() {
+                ResponseBody::Done(ref body) => {
+                    let new_body = match cached_resource.stored_body {
+                        Some(_) => None,
+                        None => Some(body.clone()),
+                    };
+                    (body.len() as u64, new_body)
+                },
+                ResponseBody::Empty | ResponseBody::Receiving(..) => continue,
+            };
+            let request_headers = match vary_request_headers(cached_resource) {
+                Some(request_headers) => request_headers,
+                // Only kept in memory.
+                None => continue,
+            };
+            if let Some(body) = new_body {
+                let body_file = disk.new_body_file(&entry_key.url);
+                new_bodies.push((body_file.clone(), body));
+                cached_resource.stored_body = Some(body_file);
+            }
+            let body_file = cached_resource.stored_body.clone().unwrap();
+            stored_resources.push(stored_resource_from_resource(
+                cached_resource,
+                request_headers,
+                body_file,
+                body_len,
+            ));
+        }
+        let removed_urls = disk.store(&entry_key.url, stored_resources, new_bodies);
+        for url in removed_urls {
+            let removed_key = CacheKey::from_servo_url(&url);
+            if removed_key == *entry_key {
+                // The bodies aren't on disk, they'll be written again the next time.
+                if let Some(cached_resources) = self.entries.get_mut(entry_key) {
+                    for cached_resource in cached_resources.iter_mut() {
+                        cached_resource.stored_body = None;
+                    }
+                }
+            } else if let Some(mut cached_resources) = self.entries.remove(&removed_key) {
+                // Evicted from disk: drop the resources referring to body files that are gone,
+                // keeping those whose body is still being received.
+                cached_resources.retain(|resource| resource.stored_body.is_none());
+                if !cached_resources.is_empty() {
+                    self.entries.insert(removed_key, cached_resources);
+                }
+            }
         }
     }
 
@@ -668,9 +886,10 @@ impl HttpCache {
     }
 
     /// Updating consumers who received a response constructed with a ResponseBody::Receiving.
+    /// Resources whose body just completed are also written to disk.
     pub fn update_awaiting_consumers(&mut self, request: &Request, response: &Response) {
+        let entry_key = CacheKey::new(request.clone());
         if let ResponseBody::Done(ref completed_body) = *response.body.lock().unwrap() {
-            let entry_key = CacheKey::new(request.clone());
             if let Some(cached_resources) = self.entries.get(&entry_key) {
                 // Ensure we only wake-up consumers of relevant resources,
                 // ie we don't want to wake-up 200 awaiting consumers with a 206.
@@ -696,6 +915,22 @@ impl HttpCache {
                 }
             }
         }
+        let needs_persisting = self
+            .entries
+            .get(&entry_key)
+            .map_or(false, |cached_resources| {
+                cached_resources.iter().any(|resource| {
+                    resource.stored_body.is_none() &&
+                        !resource.aborted.load(Ordering::Relaxed) &&
+                        match *resource.body.lock().unwrap() {
+                            ResponseBody::Done(..) => true,
+                            ResponseBody::Empty | ResponseBody::Receiving(..) => false,
+                        }
+                })
+            });
+        if needs_persisting {
+            self.persist(&entry_key);
+        }
     }
 
     /// Freshening Stored Responses upon Validation.
@@ -708,6 +943,8 @@ impl HttpCache {
     ) -> Option<Response> {
         assert_eq!(response.status.map(|s| s.0), Some(StatusCode::NOT_MODIFIED));
         let entry_key = CacheKey::new(request.clone());
+        self.load_entry_from_disk(&entry_key);
+        let mut refreshed_response = None;
         if let Some(cached_resources) = self.entries.get_mut(&entry_key) {
             for cached_resource in cached_resources.iter_mut() {
                 // done_chan will have been set to Some(..) by http_network_fetch.
@@ -748,19 +985,28 @@ impl HttpCache {
                 let mut stored_headers = cached_resource.data.metadata.headers.lock().unwrap();
                 stored_headers.extend(response.headers);
                 constructed_response.headers = stored_headers.clone();
-                return Some(constructed_response);
+                refreshed_response = Some(constructed_response);
+                break;
             }
         }
-        None
+        if refreshed_response.is_some() {
+            // Keep the refreshed headers and expiry on disk in sync.
+            self.persist(&entry_key);
+        }
+        refreshed_response
     }
 
     fn invalidate_for_url(&mut self, url: &ServoUrl) {
         let entry_key = CacheKey::from_servo_url(url);
+        self.load_entry_from_disk(&entry_key);
         if let Some(cached_resources) = self.entries.get_mut(&entry_key) {
             for cached_resource in cached_resources.iter_mut() {
                 cached_resource.data.expires = Duration::seconds(0i64);
             }
+        } else {
+            return;
         }
+        self.persist(&entry_key);
     }
 
     /// Invalidation.
@@ -834,6 +1080,7 @@ impl HttpCache {
             body: response.body.clone(),
             aborted: response.aborted.clone(),
             awaiting_body: Arc::new(Mutex::new(vec![])),
+            stored_body: None,
             data: Measurable(MeasurableCachedResource {
                 metadata: cacheable_metadata,
                 location_url: response.location_url.clone(),
@@ -845,8 +1092,13 @@ impl HttpCache {
                 last_validated: time::now(),
             }),
         };
-        let entry = self.entries.entry(entry_key).or_insert(vec![]);
+        // Make sure resources already stored on disk for this key aren't overwritten.
+        self.load_entry_from_disk(&entry_key);
+        let entry = self.entries.entry(entry_key.clone()).or_insert(vec![]);
         entry.push(entry_resource);
+        // If the body is still being received,
+        // this will happen in `update_awaiting_consumers` instead.
+        self.persist(&entry_key);
         // TODO: Complete incomplete responses, including 206 response, when stored here.
         // See A cache MAY complete a stored incomplete response by making a subsequent range request
         // https://tools.ietf.org/html/rfc7234#section-3.1
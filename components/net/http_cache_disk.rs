@@ -0,0 +1,477 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![deny(missing_docs)]
+
+//! An on-disk tier for the HTTP cache, so that cached responses survive restarts.
+//!
+//! Every cache key has a metadata file, `<hash>.json`, named after a hash of its URL,
+//! which describes all resources stored for the key. The body of each resource has
+//! a file of its own, written once when the body is complete, so that updating the
+//! metadata of a key never rewrites bodies.
+//! An `index.json` file keeps track of the files, size and last use of every key,
+//! which is used to evict the least recently used entries once the cache
+//! grows past its size limit.
+//!
+//! Files are written by a dedicated thread, so the cache is never blocked on
+//! the disk, and always to a temporary location first and then renamed,
+//! so a crash can never leave a half-written file behind.
+//! The index is only written from time to time: files it doesn't know about
+//! are removed on startup, which at worst loses the most recently stored entries.
+
+use crate::fs_util::{stable_hash, write_atomically};
+use crossbeam_channel::{self, Receiver, Sender};
+use http::HeaderMap;
+use net_traits::response::HttpsState;
+use servo_url::ServoUrl;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The version of the on-disk format, bumped whenever it changes incompatibly.
+const DISK_CACHE_VERSION: u32 = 2;
+
+const INDEX_FILE_NAME: &'static str = "index.json";
+
+/// The minimum time between two writes of the index.
+const INDEX_WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A resource, as stored on disk.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct StoredResource {
+    /// The headers of the request that led to this resource being stored
+    /// which its `Vary` header names, needed to match later requests.
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub request_headers: HeaderMap,
+    /// The stored response headers, including validators.
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub response_headers: HeaderMap,
+    /// Final URL after redirects.
+    pub final_url: ServoUrl,
+    /// MIME type / subtype.
+    pub content_type: Option<String>,
+    /// Character set.
+    pub charset: Option<String>,
+    /// The status, as found in the metadata of the response.
+    pub metadata_status: Option<(u16, Vec<u8>)>,
+    /// The location URL of the response.
+    pub location_url: Option<Result<ServoUrl, String>>,
+    /// The HTTPS state of the response.
+    pub https_state: HttpsState,
+    /// The status code and reason phrase.
+    pub status: Option<(u16, String)>,
+    /// The raw status of the response.
+    pub raw_status: Option<(u16, Vec<u8>)>,
+    /// The URL list of the response.
+    pub url_list: Vec<ServoUrl>,
+    /// The freshness lifetime of the resource, in seconds.
+    pub expires: i64,
+    /// The time at which the resource was last validated, in seconds since the epoch.
+    pub last_validated: i64,
+    /// The name of the file holding the body of this resource.
+    pub body_file: String,
+    /// The length of the body of this resource.
+    pub body_len: u64,
+}
+
+/// The contents of the metadata file of a cache key.
+#[derive(Deserialize, Serialize)]
+struct StoredEntry {
+    url: ServoUrl,
+    resources: Vec<StoredResource>,
+}
+
+/// Book-keeping information about a single cache key.
+#[derive(Clone, Deserialize, Serialize)]
+struct IndexEntry {
+    url: ServoUrl,
+    /// The files holding the bodies of the resources of this key.
+    body_files: Vec<String>,
+    /// The combined size of the metadata and body files.
+    size: u64,
+    /// The value of the index clock when this entry was last used.
+    last_used: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DiskCacheIndex {
+    version: u32,
+    /// A counter incremented every time an entry is used, providing the LRU order.
+    clock: u64,
+    /// A counter used to give every body file a unique name.
+    next_body_id: u64,
+    /// Entries, keyed by the file stem used for them.
+    entries: HashMap<String, IndexEntry>,
+}
+
+impl DiskCacheIndex {
+    fn new() -> DiskCacheIndex {
+        DiskCacheIndex {
+            version: DISK_CACHE_VERSION,
+            clock: 0,
+            next_body_id: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn total_size(&self) -> u64 {
+        self.entries.values().map(|entry| entry.size).sum()
+    }
+}
+
+/// A file operation, performed by the writer thread of the cache.
+enum DiskOperation {
+    Write(PathBuf, Vec<u8>),
+    Remove(PathBuf),
+    /// Reply once all previous operations are done.
+    Flush(Sender<()>),
+}
+
+fn run_writer(receiver: Receiver<DiskOperation>) {
+    while let Ok(operation) = receiver.recv() {
+        match operation {
+            DiskOperation::Write(path, data) => {
+                if let Err(why) = write_atomically(&path, &data) {
+                    warn!("couldn't write http cache file {}: {}", path.display(), why);
+                }
+            },
+            DiskOperation::Remove(path) => {
+                let _ = fs::remove_file(path);
+            },
+            DiskOperation::Flush(sender) => {
+                let _ = sender.send(());
+            },
+        }
+    }
+}
+
+/// A persistent HTTP cache, stored in a directory.
+pub struct DiskCache {
+    /// The directory in which the cache files are stored.
+    root: PathBuf,
+    /// The maximum combined size of all files of the cache, in bytes.
+    max_size: u64,
+    index: DiskCacheIndex,
+    /// Whether the index has changed since it was last written.
+    index_changed: bool,
+    /// When the index was last written.
+    index_written: Instant,
+    writer: Sender<DiskOperation>,
+}
+
+impl DiskCache {
+    /// Open the cache stored in `root`, creating the directory if needed.
+    /// Returns `None` if the directory can't be used.
+    pub fn new(root: PathBuf, max_size: u64) -> Option<DiskCache> {
+        if let Err(why) = fs::create_dir_all(&root) {
+            warn!(
+                "couldn't create http cache directory {}: {}",
+                root.display(),
+                why
+            );
+            return None;
+        }
+        let (writer, receiver) = crossbeam_channel::unbounded();
+        if let Err(why) = thread::Builder::new()
+            .name("HttpCacheWriter".to_owned())
+            .spawn(move || run_writer(receiver))
+        {
+            warn!("couldn't start http cache writer: {}", why);
+            return None;
+        }
+        let index = match read_index(&root.join(INDEX_FILE_NAME)) {
+            Some(ref index) if index.version != DISK_CACHE_VERSION => None,
+            index => index,
+        };
+        let mut cache = DiskCache {
+            root,
+            max_size,
+            index: index.unwrap_or_else(DiskCacheIndex::new),
+            index_changed: false,
+            index_written: Instant::now(),
+            writer,
+        };
+        // Either files written after the index was last saved, or a cache we can't trust.
+        cache.remove_unknown_files();
+        // The size limit might have been lowered since the last run.
+        cache.evict_to(cache.max_size);
+        cache.write_index();
+        Some(cache)
+    }
+
+    /// Whether resources are stored for `url`.
+    pub fn contains(&self, url: &ServoUrl) -> bool {
+        self.index
+            .entries
+            .get(&file_stem(url))
+            .map_or(false, |entry| entry.url == *url)
+    }
+
+    /// Load all resources stored for `url`, along with their bodies.
+    pub fn load(&mut self, url: &ServoUrl) -> Option<Vec<(StoredResource, Vec<u8>)>> {
+        if !self.contains(url) {
+            // Either nothing is stored, or a hash collision with another URL.
+            return None;
+        }
+        let stem = file_stem(url);
+        let resources = match self.read_entry(&stem, url) {
+            Ok(resources) => resources,
+            Err(why) => {
+                warn!("couldn't read http cache entry for {}: {}", url, why);
+                self.remove(url);
+                return None;
+            },
+        };
+        self.index.clock += 1;
+        let clock = self.index.clock;
+        if let Some(entry) = self.index.entries.get_mut(&stem) {
+            entry.last_used = clock;
+        }
+        self.index_did_change();
+        Some(resources)
+    }
+
+    /// A name for the file of a new body of a resource of `url`.
+    pub fn new_body_file(&mut self, url: &ServoUrl) -> String {
+        self.index.next_body_id += 1;
+        format!("{}-{}.body", file_stem(url), self.index.next_body_id)
+    }
+
+    /// Replace the resources stored for `url`.
+    ///
+    /// `new_bodies` are the bodies of the resources whose body file hasn't been
+    /// written yet, along with the name of that file.
+    ///
+    /// Returns the URLs whose body files are no longer on disk: the entries evicted
+    /// to make room, and `url` itself if its resources couldn't be stored.
+    pub fn store(
+        &mut self,
+        url: &ServoUrl,
+        resources: Vec<StoredResource>,
+        new_bodies: Vec<(String, Vec<u8>)>,
+    ) -> Vec<ServoUrl> {
+        if resources.is_empty() {
+            self.remove(url);
+            return vec![];
+        }
+        let stem = file_stem(url);
+        if let Some(entry) = self.index.entries.get(&stem) {
+            if entry.url != *url {
+                // Don't clobber the entry of another URL with the same hash.
+                return vec![url.clone()];
+            }
+        }
+        let entry = StoredEntry {
+            url: url.clone(),
+            resources,
+        };
+        let metadata = match serde_json::to_vec(&entry) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                self.remove(url);
+                return vec![url.clone()];
+            },
+        };
+        let body_files: Vec<String> = entry
+            .resources
+            .iter()
+            .map(|resource| resource.body_file.clone())
+            .collect();
+        let size = metadata.len() as u64 +
+            entry
+                .resources
+                .iter()
+                .map(|resource| resource.body_len)
+                .sum::<u64>();
+        if size > self.max_size {
+            // This entry alone would fill the cache.
+            self.remove(url);
+            return vec![url.clone()];
+        }
+        // Write the bodies first: metadata without its body is detected as corrupt on load,
+        // while a body without matching metadata is removed on startup.
+        for (body_file, body) in new_bodies {
+            self.send(DiskOperation::Write(self.root.join(body_file), body));
+        }
+        self.send(DiskOperation::Write(
+            self.root.join(format!("{}.json", stem)),
+            metadata,
+        ));
+        self.index.clock += 1;
+        let entry = IndexEntry {
+            url: url.clone(),
+            body_files,
+            size,
+            last_used: self.index.clock,
+        };
+        if let Some(old_entry) = self.index.entries.insert(stem, entry.clone()) {
+            // Remove the bodies of resources that were replaced.
+            for body_file in old_entry.body_files {
+                if !entry.body_files.contains(&body_file) {
+                    self.send(DiskOperation::Remove(self.root.join(body_file)));
+                }
+            }
+        }
+        let evicted = self.evict_to(self.max_size);
+        self.index_did_change();
+        evicted
+    }
+
+    /// Remove all resources stored for `url`.
+    pub fn remove(&mut self, url: &ServoUrl) {
+        if !self.contains(url) {
+            return;
+        }
+        let stem = file_stem(url);
+        self.remove_entry(&stem);
+        self.index_did_change();
+    }
+
+    /// Wait until all pending changes are on disk, writing the index if needed.
+    pub fn flush(&mut self) {
+        if self.index_changed {
+            self.write_index();
+        }
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        self.send(DiskOperation::Flush(sender));
+        let _ = receiver.recv();
+    }
+
+    /// Evict the least recently used entries, until the cache is no larger than `size`.
+    /// Returns the URLs of the evicted entries.
+    fn evict_to(&mut self, size: u64) -> Vec<ServoUrl> {
+        let mut evicted = vec![];
+        let mut total_size = self.index.total_size();
+        if total_size <= size {
+            return evicted;
+        }
+        let mut entries: Vec<(String, u64, u64)> = self
+            .index
+            .entries
+            .iter()
+            .map(|(stem, entry)| (stem.clone(), entry.last_used, entry.size))
+            .collect();
+        entries.sort_by_key(|&(_, last_used, _)| last_used);
+        for (stem, _, entry_size) in entries {
+            if total_size <= size {
+                break;
+            }
+            evicted.extend(self.remove_entry(&stem));
+            total_size -= entry_size;
+        }
+        evicted
+    }
+
+    fn read_entry(&self, stem: &str, url: &ServoUrl) -> io::Result<Vec<(StoredResource, Vec<u8>)>> {
+        let metadata = fs::read(self.root.join(format!("{}.json", stem)))?;
+        let entry: StoredEntry = serde_json::from_slice(&metadata)
+            .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why))?;
+        if entry.url != *url {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "entry stored for another url",
+            ));
+        }
+        let mut resources = vec![];
+        for resource in entry.resources {
+            let body = fs::read(self.root.join(&resource.body_file))?;
+            if body.len() as u64 != resource.body_len {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated body"));
+            }
+            resources.push((resource, body));
+        }
+        Ok(resources)
+    }
+
+    /// Remove the files of an entry, returning its URL if the index knew about it.
+    fn remove_entry(&mut self, stem: &str) -> Option<ServoUrl> {
+        let entry = self.index.entries.remove(stem);
+        if let Some(ref entry) = entry {
+            for body_file in &entry.body_files {
+                self.send(DiskOperation::Remove(self.root.join(body_file)));
+            }
+        }
+        self.send(DiskOperation::Remove(
+            self.root.join(format!("{}.json", stem)),
+        ));
+        entry.map(|entry| entry.url)
+    }
+
+    /// Record a change to the index, writing it if it hasn't been for a while.
+    fn index_did_change(&mut self) {
+        self.index_changed = true;
+        if self.index_written.elapsed() >= INDEX_WRITE_INTERVAL {
+            self.write_index();
+        }
+    }
+
+    fn write_index(&mut self) {
+        let index = match serde_json::to_vec(&self.index) {
+            Ok(index) => index,
+            Err(_) => return,
+        };
+        self.send(DiskOperation::Write(self.root.join(INDEX_FILE_NAME), index));
+        self.index_changed = false;
+        self.index_written = Instant::now();
+    }
+
+    fn send(&self, operation: DiskOperation) {
+        let _ = self.writer.send(operation);
+    }
+
+    /// Remove the files that aren't referenced by the index.
+    fn remove_unknown_files(&self) {
+        let mut known_files: HashSet<String> = HashSet::new();
+        known_files.insert(INDEX_FILE_NAME.to_owned());
+        for (stem, entry) in &self.index.entries {
+            known_files.insert(format!("{}.json", stem));
+            known_files.extend(entry.body_files.iter().cloned());
+        }
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let is_known = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| known_files.contains(name));
+            if path.is_file() && !is_known {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn read_index(path: &Path) -> Option<DiskCacheIndex> {
+    let data = fs::read(path).ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(index) => Some(index),
+        Err(why) => {
+            warn!("couldn't decode http cache index: {}", why);
+            None
+        },
+    }
+}
+
+/// The name, without extension, of the metadata file of the resources of `url`.
+fn file_stem(url: &ServoUrl) -> String {
+    format!("{:016x}", stable_hash(url.as_str().as_bytes()))
+}
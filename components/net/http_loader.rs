@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::connector::{create_http_client, Connector};
+use crate::connector::{create_http_client, ConnectionInfo, Connector};
 use crate::cookie;
 use crate::cookie_storage::CookieStorage;
 use crate::decoder::Decoder;
@@ -12,18 +12,23 @@ use crate::fetch::methods::{
 };
 use crate::fetch::methods::{Data, DoneChannel, FetchContext, Target};
 use crate::hsts::HstsList;
+use crate::http_auth;
 use crate::http_cache::HttpCache;
-use crate::resource_thread::AuthCache;
+use crate::proxy::{Proxy, ProxyAuthenticationRequired, ProxyConfig};
+use crate::resource_thread::{AuthCache, AuthCacheEntry};
 use crossbeam_channel::{unbounded, Sender};
 use devtools_traits::{
     ChromeToDevtoolsControlMsg, DevtoolsControlMsg, HttpRequest as DevtoolsHttpRequest,
 };
-use devtools_traits::{HttpResponse as DevtoolsHttpResponse, NetworkEvent};
+use devtools_traits::{HttpResponse as DevtoolsHttpResponse, MixedContentStatus, NetworkEvent};
+use devtools_traits::{HttpResponseEnd as DevtoolsHttpResponseEnd, NetworkTimings, SecurityInfo};
+use embedder_traits::{AuthenticationScheme, Credentials, CredentialsRequest};
+use embedder_traits::{EmbedderMsg, EmbedderProxy};
 use headers::authorization::Basic;
 use headers::{AccessControlAllowCredentials, AccessControlAllowHeaders, HeaderMapExt};
 use headers::{
     AccessControlAllowMethods, AccessControlRequestHeaders, AccessControlRequestMethod,
-    Authorization,
+    Authorization, ProxyAuthorization,
 };
 use headers::{AccessControlAllowOrigin, AccessControlMaxAge};
 use headers::{CacheControl, ContentEncoding, ContentLength};
@@ -33,24 +38,30 @@ use headers::{
 use http::header::{self, HeaderName, HeaderValue};
 use http::uri::Authority;
 use http::{HeaderMap, Request as HyperRequest};
-use hyper::{Body, Client, Method, Response as HyperResponse, StatusCode};
+use hyper::{Body, Client, Error as HyperError, Method, Response as HyperResponse, StatusCode};
 use hyper_serde::Serde;
-use msg::constellation_msg::{HistoryStateId, PipelineId};
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use msg::constellation_msg::{HistoryStateId, PipelineId, TopLevelBrowsingContextId};
 use net_traits::quality::{quality_to_value, Quality, QualityItem};
-use net_traits::request::{CacheMode, CredentialsMode, Destination, Origin};
-use net_traits::request::{RedirectMode, Referrer, Request, RequestMode};
-use net_traits::request::{ResponseTainting, ServiceWorkersMode};
+use net_traits::request::{CacheMode, CredentialsMode, Destination, MixedContent, Origin};
+use net_traits::request::{RedirectMode, Referrer, Request, RequestBuilder, RequestMode};
+use net_traits::request::{ResponseTainting, ServiceWorkersMode, Window};
 use net_traits::response::{HttpsState, Response, ResponseBody, ResponseType};
-use net_traits::{CookieSource, FetchMetadata, NetworkError, ReferrerPolicy};
+use net_traits::{CookieSource, CustomResponseMediator, FetchMetadata};
+use net_traits::{NetworkError, ReferrerPolicy};
 use net_traits::{RedirectStartValue, ResourceAttribute, ResourceFetchTiming};
 use openssl::ssl::SslConnectorBuilder;
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::io;
 use std::iter::FromIterator;
 use std::mem;
+use std::net::SocketAddr;
 use std::ops::Deref;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, SystemTime};
 use time::{self, Tm};
@@ -65,26 +76,45 @@ pub struct HttpState {
     pub hsts_list: RwLock<HstsList>,
     pub cookie_jar: RwLock<CookieStorage>,
     pub http_cache: RwLock<HttpCache>,
-    pub auth_cache: RwLock<AuthCache>,
+    pub auth_cache: Arc<RwLock<AuthCache>>,
     pub history_states: RwLock<HashMap<HistoryStateId, Vec<u8>>>,
+    pub proxy_config: ProxyConfig,
     pub client: Client<Connector, Body>,
 }
 
 impl HttpState {
     pub fn new(ssl_connector_builder: SslConnectorBuilder) -> HttpState {
+        HttpState::with_proxy_config(ssl_connector_builder, ProxyConfig::from_prefs())
+    }
+
+    pub fn with_proxy_config(
+        ssl_connector_builder: SslConnectorBuilder,
+        proxy_config: ProxyConfig,
+    ) -> HttpState {
+        let auth_cache = Arc::new(RwLock::new(AuthCache::new()));
         HttpState {
             hsts_list: RwLock::new(HstsList::new()),
             cookie_jar: RwLock::new(CookieStorage::new(150)),
-            auth_cache: RwLock::new(AuthCache::new()),
+            auth_cache: auth_cache.clone(),
             history_states: RwLock::new(HashMap::new()),
             http_cache: RwLock::new(HttpCache::new()),
-            client: create_http_client(ssl_connector_builder, HANDLE.lock().unwrap().executor()),
+            proxy_config: proxy_config.clone(),
+            client: create_http_client(
+                ssl_connector_builder,
+                proxy_config,
+                auth_cache,
+                HANDLE.lock().unwrap().executor(),
+            ),
         }
     }
 }
 
-fn precise_time_ms() -> u64 {
-    time::precise_time_ns() / (1000 * 1000)
+/// The milliseconds between two times in nanoseconds, or zero if either step was skipped.
+fn duration_ms(start: u64, end: u64) -> u64 {
+    if start == 0 {
+        return 0;
+    }
+    end.saturating_sub(start) / (1000 * 1000)
 }
 
 // Step 3 of https://fetch.spec.whatwg.org/#concept-fetch.
@@ -94,7 +124,7 @@ pub fn set_default_accept(destination: Destination, headers: &mut HeaderMap) {
     }
     let value = match destination {
         // Step 3.2.
-        Destination::Document => vec![
+        Destination::Document | Destination::IFrame => vec![
             QualityItem::new(mime::TEXT_HTML, Quality::from_u16(1000)),
             QualityItem::new(
                 "application/xhtml+xml".parse().unwrap(),
@@ -241,12 +271,17 @@ pub fn set_request_cookies(
     }
 }
 
-fn set_cookie_for_url(cookie_jar: &RwLock<CookieStorage>, request: &ServoUrl, cookie_val: &str) {
+fn set_cookie_for_url(
+    cookie_jar: &RwLock<CookieStorage>,
+    request: &ServoUrl,
+    cookie_val: &str,
+    browser: Option<TopLevelBrowsingContextId>,
+) {
     let mut cookie_jar = cookie_jar.write().unwrap();
     let source = CookieSource::HTTP;
 
     if let Some(cookie) = cookie::Cookie::from_cookie_string(cookie_val.into(), request, source) {
-        cookie_jar.push(cookie, request, source);
+        cookie_jar.push_for_browser(cookie, request, source, browser);
     }
 }
 
@@ -254,10 +289,11 @@ fn set_cookies_from_headers(
     url: &ServoUrl,
     headers: &HeaderMap,
     cookie_jar: &RwLock<CookieStorage>,
+    browser: Option<TopLevelBrowsingContextId>,
 ) {
     for cookie in headers.get_all(header::SET_COOKIE) {
         if let Ok(cookie_str) = cookie.to_str() {
-            set_cookie_for_url(&cookie_jar, &url, &cookie_str);
+            set_cookie_for_url(&cookie_jar, &url, &cookie_str, browser);
         }
     }
 }
@@ -270,9 +306,9 @@ fn prepare_devtools_request(
     body: Option<Vec<u8>>,
     pipeline_id: PipelineId,
     now: Tm,
-    connect_time: u64,
-    send_time: u64,
+    timings: NetworkTimings,
     is_xhr: bool,
+    mixed_content: Option<MixedContentStatus>,
 ) -> ChromeToDevtoolsControlMsg {
     let request = DevtoolsHttpRequest {
         url: url,
@@ -282,9 +318,9 @@ fn prepare_devtools_request(
         pipeline_id: pipeline_id,
         startedDateTime: now,
         timeStamp: now.to_timespec().sec,
-        connect_time: connect_time,
-        send_time: send_time,
+        timings: timings,
         is_xhr: is_xhr,
+        mixed_content: mixed_content,
     };
     let net_event = NetworkEvent::HttpRequest(request);
 
@@ -300,17 +336,58 @@ fn send_request_to_devtools(
         .unwrap();
 }
 
+/// Notify devtools of a request that was blocked as mixed content, and so
+/// never reached the network.
+pub fn send_blocked_mixed_content_request_to_devtools(
+    request: &Request,
+    devtools_chan: &Sender<DevtoolsControlMsg>,
+) {
+    let pipeline_id = match request.pipeline_id {
+        Some(pipeline_id) => pipeline_id,
+        None => {
+            debug!("Not notifying devtools (no pipeline_id)");
+            return;
+        },
+    };
+    let msg = prepare_devtools_request(
+        uuid::Uuid::new_v4().to_simple().to_string(),
+        request.current_url(),
+        request.method.clone(),
+        request.headers.clone(),
+        request.body.clone(),
+        pipeline_id,
+        time::now(),
+        NetworkTimings::default(),
+        request.destination == Destination::None,
+        Some(MixedContentStatus::Blocked),
+    );
+    send_request_to_devtools(msg, devtools_chan);
+}
+
+fn devtools_mixed_content_status(mixed_content: MixedContent) -> MixedContentStatus {
+    match mixed_content {
+        MixedContent::Blocked => MixedContentStatus::Blocked,
+        MixedContent::Upgraded => MixedContentStatus::Upgraded,
+        MixedContent::Allowed => MixedContentStatus::Allowed,
+    }
+}
+
 fn send_response_to_devtools(
     devtools_chan: &Sender<DevtoolsControlMsg>,
     request_id: String,
     headers: Option<HeaderMap>,
     status: Option<(u16, Vec<u8>)>,
+    http_version: String,
+    remote_address: Option<SocketAddr>,
+    security_info: Option<SecurityInfo>,
     pipeline_id: PipelineId,
 ) {
     let response = DevtoolsHttpResponse {
         headers: headers,
         status: status,
-        body: None,
+        http_version: http_version,
+        remote_address: remote_address,
+        security_info: security_info,
         pipeline_id: pipeline_id,
     };
     let net_event_response = NetworkEvent::HttpResponse(response);
@@ -319,6 +396,97 @@ fn send_response_to_devtools(
     let _ = devtools_chan.send(DevtoolsControlMsg::FromChrome(msg));
 }
 
+/// Tell devtools that the body of a response was received, which they only keep up to
+/// the `devtools.netmonitor.response-body-limit` pref.
+fn send_response_end_to_devtools(
+    devtools_chan: &Sender<DevtoolsControlMsg>,
+    request_id: String,
+    body: &[u8],
+    transferred_size: usize,
+    receive_time: u64,
+    pipeline_id: PipelineId,
+) {
+    let body_limit = pref!(devtools.netmonitor.response_body_limit);
+    let response = DevtoolsHttpResponseEnd {
+        body: if body.len() as i64 <= body_limit {
+            Some(body.to_vec())
+        } else {
+            None
+        },
+        body_size: body.len(),
+        transferred_size: transferred_size,
+        receive_time: receive_time,
+        pipeline_id: pipeline_id,
+    };
+    let net_event_response = NetworkEvent::HttpResponseEnd(response);
+
+    let msg = ChromeToDevtoolsControlMsg::NetworkEvent(request_id, net_event_response);
+    let _ = devtools_chan.send(DevtoolsControlMsg::FromChrome(msg));
+}
+
+/// Set the connection steps of the resource timing, once the response started, from the
+/// connection it was received over. The steps all happen at the fetch start when a
+/// persistent connection was used, rather than one opened after the request was sent at
+/// `send_start`.
+fn set_connection_timing(
+    timing: &mut ResourceFetchTiming,
+    connection: Option<&ConnectionInfo>,
+    send_start: u64,
+) {
+    match connection {
+        Some(info) if info.domain_lookup_start >= send_start => {
+            timing.set_attribute(ResourceAttribute::DomainLookupStart(
+                info.domain_lookup_start,
+            ));
+            timing.set_attribute(ResourceAttribute::DomainLookupEnd(info.domain_lookup_end));
+            timing.set_attribute(ResourceAttribute::ConnectStart(info.connect_start));
+            timing.set_attribute(ResourceAttribute::SecureConnectionStart(
+                info.secure_connection_start,
+            ));
+            timing.set_attribute(ResourceAttribute::ConnectEnd(info.connect_end));
+        },
+        _ => {
+            let fetch_start = timing.fetch_start;
+            let is_secure = connection.map_or(false, |info| info.security_info.is_some());
+            timing.set_attribute(ResourceAttribute::DomainLookupStart(fetch_start));
+            timing.set_attribute(ResourceAttribute::DomainLookupEnd(fetch_start));
+            timing.set_attribute(ResourceAttribute::ConnectStart(fetch_start));
+            timing.set_attribute(ResourceAttribute::SecureConnectionStart(if is_secure {
+                fetch_start
+            } else {
+                0
+            }));
+            timing.set_attribute(ResourceAttribute::ConnectEnd(fetch_start));
+        },
+    }
+}
+
+/// The timings devtools show for a request sent at `send_start`, before its body is received.
+/// Hyper does not tell when the request was written, so sending it counts as waiting.
+fn devtools_timings(timing: &ResourceFetchTiming, send_start: u64) -> NetworkTimings {
+    let connect_end = if timing.secure_connection_start != 0 {
+        timing.secure_connection_start
+    } else {
+        timing.connect_end
+    };
+    NetworkTimings {
+        blocked: duration_ms(timing.request_start, send_start),
+        dns: duration_ms(timing.domain_lookup_start, timing.domain_lookup_end),
+        connect: duration_ms(timing.connect_start, connect_end),
+        ssl: if timing.secure_connection_start != 0 {
+            duration_ms(timing.secure_connection_start, timing.connect_end)
+        } else {
+            0
+        },
+        send: 0,
+        wait: duration_ms(
+            std::cmp::max(send_start, timing.connect_end),
+            timing.response_start,
+        ),
+        receive: 0,
+    }
+}
+
 fn auth_from_cache(
     auth_cache: &RwLock<AuthCache>,
     origin: &ImmutableOrigin,
@@ -337,6 +505,236 @@ fn auth_from_cache(
     }
 }
 
+fn proxy_auth_from_cache(
+    auth_cache: &RwLock<AuthCache>,
+    proxy: &Proxy,
+) -> Option<ProxyAuthorization<Basic>> {
+    let (user_name, password) = proxy.credentials(auth_cache)?;
+    Some(ProxyAuthorization(
+        Authorization::basic(&user_name, &password).0,
+    ))
+}
+
+/// Credentials added to a request in answer to an authentication challenge, to be
+/// put in the authentication cache once they are accepted.
+struct PendingAuthentication {
+    realm: String,
+    scheme: AuthenticationScheme,
+    credentials: Credentials,
+    /// The host and port of the proxy the credentials are for, if they aren't for
+    /// the origin.
+    proxy: Option<String>,
+}
+
+/// How a fetch gets the credentials the user is asked for.
+pub enum CredentialsPrompt {
+    /// Wait for the user to answer.
+    Wait,
+    /// Stop the fetch rather than waiting, so that it doesn't hold a thread of the fetch
+    /// pool, and keep the request for credentials, for the fetch to be started again
+    /// once the user answers it.
+    Defer(Option<(Option<TopLevelBrowsingContextId>, CredentialsRequest)>),
+    /// What the user answered to the request the fetch was stopped for.
+    Answered(CredentialsRequest, Option<Credentials>),
+}
+
+/// Answer the authentication challenge of `response`, from the origin or from the
+/// proxy, by adding credentials to `request`. The credentials accepted before for the
+/// same realm are used, unless they were just rejected, otherwise the user is asked.
+fn authenticate(
+    request: &mut Request,
+    response: &Response,
+    for_proxy: bool,
+    context: &FetchContext,
+) -> Option<PendingAuthentication> {
+    let (challenge_header, authorization_header) = if for_proxy {
+        (header::PROXY_AUTHENTICATE, header::PROXY_AUTHORIZATION)
+    } else {
+        (header::WWW_AUTHENTICATE, header::AUTHORIZATION)
+    };
+    let challenge = http_auth::parse_challenges(&response.headers, challenge_header)
+        .into_iter()
+        .next()?;
+
+    // The credentials for a proxy are kept for its host and port.
+    let proxy = if for_proxy {
+        let proxy = context
+            .state
+            .proxy_config
+            .proxy_for_url(&request.current_url())?;
+        Some(proxy.authority())
+    } else {
+        None
+    };
+    let origin = match proxy {
+        Some(ref proxy) => format!("http://{}", proxy),
+        None => request.current_url().origin().ascii_serialization(),
+    };
+    // The credentials for a proxy tunnel are sent up front by the connector, which can
+    // only send Basic ones, and only sends those of the URL of the proxy, if it has any.
+    let tunnel = if for_proxy {
+        context
+            .state
+            .proxy_config
+            .tunnelling_proxy(&request.current_url())
+    } else {
+        None
+    };
+    if let Some(tunnel) = tunnel {
+        if challenge.scheme != AuthenticationScheme::Basic || tunnel.credentials.is_some() {
+            return None;
+        }
+    }
+    let rejected = match tunnel {
+        Some(tunnel) => {
+            let mut auth_cache = context.state.auth_cache.write().unwrap();
+            auth_cache
+                .proxy_entries
+                .remove(&tunnel.authority())
+                .is_some()
+        },
+        None => request
+            .headers
+            .get(&authorization_header)
+            .map_or(false, |value| {
+                http_auth::is_for_scheme(value, challenge.scheme)
+            }),
+    };
+    let cached = if rejected {
+        None
+    } else {
+        let auth_cache = context.state.auth_cache.read().unwrap();
+        let realms = match proxy {
+            Some(ref proxy) => auth_cache.proxy_realms.get(proxy),
+            None => auth_cache.realms.get(&origin),
+        };
+        let entry = realms.and_then(|realms| realms.get(&challenge.realm));
+        entry.map(|entry| Credentials {
+            user_name: entry.user_name.clone(),
+            password: entry.password.clone(),
+        })
+    };
+    let credentials = match cached {
+        Some(credentials) => credentials,
+        None => prompt_credentials(
+            context,
+            request.top_level_browsing_context_id,
+            CredentialsRequest {
+                origin,
+                realm: challenge.realm.clone(),
+                scheme: challenge.scheme,
+                for_proxy,
+            },
+        )?,
+    };
+
+    match tunnel {
+        Some(tunnel) => {
+            let mut auth_cache = context.state.auth_cache.write().unwrap();
+            auth_cache.proxy_entries.insert(
+                tunnel.authority(),
+                AuthCacheEntry {
+                    user_name: credentials.user_name.clone(),
+                    password: credentials.password.clone(),
+                },
+            );
+        },
+        None => {
+            let value =
+                challenge.authorization(&credentials, &request.method, &request.current_url())?;
+            request.headers.insert(authorization_header, value);
+        },
+    }
+    Some(PendingAuthentication {
+        realm: challenge.realm,
+        scheme: challenge.scheme,
+        credentials,
+        proxy,
+    })
+}
+
+/// Ask the user of the browser the request is made for for credentials, unless the
+/// fetch is to be stopped instead, or the user already answered.
+fn prompt_credentials(
+    context: &FetchContext,
+    top_level_browsing_context_id: Option<TopLevelBrowsingContextId>,
+    request: CredentialsRequest,
+) -> Option<Credentials> {
+    {
+        let mut prompt = context.credentials_prompt.lock().unwrap();
+        match mem::replace(&mut *prompt, CredentialsPrompt::Wait) {
+            CredentialsPrompt::Wait => {},
+            CredentialsPrompt::Defer(_) => {
+                *prompt =
+                    CredentialsPrompt::Defer(Some((top_level_browsing_context_id, request)));
+                return None;
+            },
+            CredentialsPrompt::Answered(answered, credentials) => {
+                if answered == request {
+                    return credentials;
+                }
+            },
+        }
+    }
+    wait_for_credentials(&context.embedder_proxy, top_level_browsing_context_id, request)
+}
+
+/// Ask the embedder for credentials, blocking until the user answers.
+pub fn wait_for_credentials(
+    embedder_proxy: &EmbedderProxy,
+    top_level_browsing_context_id: Option<TopLevelBrowsingContextId>,
+    request: CredentialsRequest,
+) -> Option<Credentials> {
+    let (sender, receiver) = ipc::channel().expect("Failed to create IPC channel!");
+    embedder_proxy.send((
+        top_level_browsing_context_id,
+        EmbedderMsg::PromptCredentials(request, sender),
+    ));
+    match receiver.recv() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            warn!("Failed to receive credentials from embedder ({:?}).", e);
+            None
+        },
+    }
+}
+
+/// [Create an authentication entry](https://fetch.spec.whatwg.org/#authentication-entry)
+/// for the credentials accepted by `url` or by the proxy.
+fn store_authentication_entry(
+    auth_cache: &RwLock<AuthCache>,
+    url: &ServoUrl,
+    authentication: PendingAuthentication,
+) {
+    let entry = AuthCacheEntry {
+        user_name: authentication.credentials.user_name,
+        password: authentication.credentials.password,
+    };
+    // Digest credentials can only be sent in answer to a challenge.
+    let send_up_front = authentication.scheme == AuthenticationScheme::Basic;
+    let mut auth_cache = auth_cache.write().unwrap();
+    if let Some(proxy) = authentication.proxy {
+        if send_up_front {
+            auth_cache.proxy_entries.insert(proxy.clone(), entry.clone());
+        }
+        auth_cache
+            .proxy_realms
+            .entry(proxy)
+            .or_insert_with(HashMap::new)
+            .insert(authentication.realm, entry);
+    } else {
+        let origin = url.origin().ascii_serialization();
+        if send_up_front {
+            auth_cache.entries.insert(origin.clone(), entry.clone());
+        }
+        auth_cache
+            .realms
+            .entry(origin)
+            .or_insert_with(HashMap::new)
+            .insert(authentication.realm, entry);
+    }
+}
+
 fn obtain_response(
     client: &Client<Connector, Body>,
     url: &ServoUrl,
@@ -348,10 +746,15 @@ fn obtain_response(
     iters: u32,
     request_id: Option<&str>,
     is_xhr: bool,
+    mixed_content: Option<MixedContentStatus>,
     context: &FetchContext,
 ) -> Box<
     dyn Future<
-        Item = (HyperResponse<Decoder>, Option<ChromeToDevtoolsControlMsg>),
+        Item = (
+            HyperResponse<Decoder>,
+            Option<ChromeToDevtoolsControlMsg>,
+            Arc<AtomicUsize>,
+        ),
         Error = NetworkError,
     >,
 > {
@@ -378,21 +781,6 @@ fn obtain_response(
         },
     }
 
-    context
-        .timing
-        .lock()
-        .unwrap()
-        .set_attribute(ResourceAttribute::DomainLookupStart);
-
-    // TODO(#21261) connect_start: set if a persistent connection is *not* used and the last non-redirected
-    // fetch passes the timing allow check
-    let connect_start = precise_time_ms();
-    context
-        .timing
-        .lock()
-        .unwrap()
-        .set_attribute(ResourceAttribute::ConnectStart(connect_start));
-
     // https://url.spec.whatwg.org/#percent-encoded-bytes
     let request = HyperRequest::builder()
         .method(method)
@@ -412,26 +800,31 @@ fn obtain_response(
     };
     *request.headers_mut() = headers.clone();
 
-    let connect_end = precise_time_ms();
-    context
-        .timing
-        .lock()
-        .unwrap()
-        .set_attribute(ResourceAttribute::ConnectEnd(connect_end));
-
     let request_id = request_id.map(|v| v.to_owned());
     let pipeline_id = pipeline_id.clone();
     let closure_url = url.clone();
     let method = method.clone();
-    let send_start = precise_time_ms();
+    let timing = context.timing.clone();
+    let transferred_size = Arc::new(AtomicUsize::new(0));
+    let tunnel_transferred_size = transferred_size.clone();
+    let send_start = time::precise_time_ns();
 
     Box::new(
         client
             .request(request)
             .and_then(move |res| {
-                let send_end = precise_time_ms();
-
-                // TODO(#21271) response_start: immediately after receiving first byte of response
+                // The response starts with its headers, which hyper only hands over once
+                // they were all received.
+                let timings = {
+                    let mut timing = timing.lock().unwrap();
+                    set_connection_timing(
+                        &mut timing,
+                        res.extensions().get::<ConnectionInfo>(),
+                        send_start,
+                    );
+                    timing.set_attribute(ResourceAttribute::ResponseStart);
+                    devtools_timings(&timing, send_start)
+                };
 
                 let msg = if let Some(request_id) = request_id {
                     if let Some(pipeline_id) = pipeline_id {
@@ -443,14 +836,10 @@ fn obtain_response(
                             Some(request_body.clone()),
                             pipeline_id,
                             time::now(),
-                            connect_end - connect_start,
-                            send_end - send_start,
+                            timings,
                             is_xhr,
+                            mixed_content,
                         ))
-                    // TODO: ^This is not right, connect_start is taken before contructing the
-                    // request and connect_end at the end of it. send_start is takend before the
-                    // connection too. I'm not sure it's currently possible to get the time at the
-                    // point between the connection and the start of a request.
                     } else {
                         debug!("Not notifying devtools (no pipeline_id)");
                         None
@@ -459,12 +848,110 @@ fn obtain_response(
                     debug!("Not notifying devtools (no request_id)");
                     None
                 };
-                Ok((Decoder::detect(res), msg))
+
+                // Count the bytes of the body before it is decoded.
+                let counter = transferred_size.clone();
+                let res = res.map(|body| {
+                    Body::wrap_stream(body.inspect(move |chunk| {
+                        counter.fetch_add(chunk.len(), Ordering::Relaxed);
+                    }))
+                });
+                Ok((Decoder::detect(res), msg, transferred_size))
             })
-            .map_err(move |e| NetworkError::from_hyper_error(&e)),
+            .or_else(move |e| match proxy_authentication_required(&e) {
+                Some(res) => Ok((Decoder::detect(res), None, tunnel_transferred_size)),
+                None => Err(NetworkError::from_hyper_error(&e)),
+            }),
     )
 }
 
+/// The `407` response of a proxy that didn't open a tunnel to the origin, for lack of
+/// credentials, which the user is asked for as if the origin had responded with it.
+fn proxy_authentication_required(error: &HyperError) -> Option<HyperResponse<Body>> {
+    let error = error.cause2()?.downcast_ref::<io::Error>()?;
+    let required = ProxyAuthenticationRequired::find(error)?;
+    let mut response = HyperResponse::new(Body::empty());
+    *response.status_mut() = StatusCode::PROXY_AUTHENTICATION_REQUIRED;
+    *response.headers_mut() = required.headers.clone();
+    Some(response)
+}
+
+/// How long a fetch waits for a service worker to respond, before going to the network.
+const SERVICE_WORKER_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// [Handle fetch](https://w3c.github.io/ServiceWorker/#handle-fetch): ask the service
+/// worker manager for a service worker to respond to the request, which it finds by the
+/// scope a navigation falls in, or by the client a subresource is requested for.
+fn handle_fetch(request: &Request, context: &FetchContext) -> Option<Response> {
+    let swmanager_chan = context.swmanager_chan.as_ref()?;
+    if !pref!(dom.serviceworker.enabled) {
+        return None;
+    }
+    // Other requests, such as the ones for the scripts of workers, are never handled.
+    if !request.is_navigation_request() && !request.is_subresource_request() {
+        return None;
+    }
+    let origin = match request.origin {
+        Origin::Origin(ref origin) => origin.clone(),
+        Origin::Client => return None,
+    };
+
+    let mut request_builder = RequestBuilder::new(request.current_url())
+        .method(request.method.clone())
+        .headers(request.headers.clone())
+        .body(request.body.clone())
+        .destination(request.destination)
+        .mode(request.mode.clone())
+        .credentials_mode(request.credentials_mode)
+        .origin(origin)
+        .referrer(Some(request.referrer.clone()))
+        .referrer_policy(request.referrer_policy)
+        .pipeline_id(request.pipeline_id)
+        .redirect_mode(request.redirect_mode)
+        .integrity_metadata(request.integrity_metadata.clone());
+    request_builder.cache_mode = request.cache_mode;
+
+    let (response_chan, response_port) = ipc::channel().unwrap();
+    let response_port = ROUTER.route_ipc_receiver_to_new_crossbeam_receiver(response_port);
+    let mediator = CustomResponseMediator {
+        response_chan,
+        request: request_builder,
+    };
+    if swmanager_chan.send(mediator).is_err() {
+        return None;
+    }
+    // The service worker may stop, or take too long, without responding, in which
+    // case the request goes to the network.
+    let custom_response = match response_port
+        .recv_timeout(SERVICE_WORKER_FETCH_TIMEOUT)
+        .ok()??
+    {
+        Ok(custom_response) => custom_response,
+        Err(error) => return Some(Response::network_error(error)),
+    };
+
+    let url = custom_response
+        .url
+        .unwrap_or_else(|| request.current_url());
+    let mut response = Response::new(url, ResourceFetchTiming::new(request.timing_type()));
+    response.headers = custom_response.headers;
+    response.status = Some(custom_response.raw_status.clone());
+    response.raw_status = Some((
+        custom_response.raw_status.0.as_u16(),
+        custom_response.raw_status.1.into_bytes(),
+    ));
+    *response.body.lock().unwrap() = ResponseBody::Done(custom_response.body);
+    // Filter the response the way the service worker got it, so that the checks of
+    // http fetch see an opaque response as opaque.
+    let mut response = match custom_response.response_type {
+        ResponseType::Default => response,
+        ResponseType::Error(error) => return Some(Response::network_error(error)),
+        response_type => response.to_filtered(response_type),
+    };
+    response.return_internal = false;
+    Some(response)
+}
+
 /// [HTTP fetch](https://fetch.spec.whatwg.org#http-fetch)
 pub fn http_fetch(
     request: &mut Request,
@@ -486,8 +973,8 @@ pub fn http_fetch(
 
     // Step 3
     if request.service_workers_mode == ServiceWorkersMode::All {
-        // TODO: Substep 1
-        // Set response to the result of invoking handle fetch for request.
+        // Substep 1
+        response = handle_fetch(request, context);
 
         // Substep 2
         if let Some(ref res) = response {
@@ -540,9 +1027,8 @@ pub fn http_fetch(
             request.service_workers_mode = ServiceWorkersMode::None;
         }
 
-        // Generally, we use a persistent connection, so we will also set other PerformanceResourceTiming
-        //   attributes to this as well (domain_lookup_start, domain_lookup_end, connect_start, connect_end,
-        //   secure_connection_start)
+        // The connection steps are set once the response starts, since only then is it known
+        //   whether a persistent connection was used.
         // TODO(#21254) also set startTime equal to either fetch_start or redirect_start
         //   (https://w3c.github.io/resource-timing/#dfn-starttime)
         context
@@ -799,6 +1285,10 @@ fn http_network_or_cache_fetch(
     // Step 4
     let mut revalidating_flag = false;
 
+    // Whether this fetch is a retry made with credentials for the proxy,
+    // whose outcome is handled by the fetch that made it.
+    let proxy_authentication_fetch_flag = request.headers.contains_key(header::PROXY_AUTHORIZATION);
+
     // TODO: Implement Window enum for Request
     let request_has_no_window = true;
 
@@ -972,9 +1462,31 @@ fn http_network_or_cache_fetch(
     }
 
     // Step 5.18
-    // TODO If there’s a proxy-authentication entry, use it as appropriate.
+    // The credentials for a proxy tunnel are only sent to the proxy, by the connector.
+    let forwarding_proxy = context
+        .state
+        .proxy_config
+        .forwarding_proxy(&http_request.current_url());
+    if let Some(proxy) = forwarding_proxy {
+        if !http_request
+            .headers
+            .contains_key(header::PROXY_AUTHORIZATION)
+        {
+            if let Some(basic) = proxy_auth_from_cache(&context.state.auth_cache, proxy) {
+                http_request.headers.typed_insert(basic);
+            }
+        }
+    }
 
     // Step 5.19
+    let needs_loading_from_disk = context.state.http_cache.read().map_or(false, |http_cache| {
+        http_cache.needs_loading_from_disk(&http_request)
+    });
+    if needs_loading_from_disk {
+        if let Ok(mut http_cache) = context.state.http_cache.write() {
+            http_cache.load_from_disk(&http_request);
+        }
+    }
     if let Ok(http_cache) = context.state.http_cache.read() {
         if let Some(response_from_cache) = http_cache.construct_response(&http_request, done_chan) {
             let response_headers = response_from_cache.response.headers.clone();
@@ -1043,7 +1555,9 @@ fn http_network_or_cache_fetch(
     wait_for_cached_response(done_chan, &mut response);
 
     // Step 6
-    // TODO: https://infra.spec.whatwg.org/#if-aborted
+    if context.cancellation_listener.lock().unwrap().cancelled() {
+        return Response::network_error(NetworkError::Internal("Fetch aborted".into()));
+    }
 
     // Step 7
     if response.is_none() {
@@ -1102,67 +1616,106 @@ fn http_network_or_cache_fetch(
     // TODO: handle CORS not set and cross-origin blocked
 
     // Step 10
-    // FIXME: Figure out what to do with request window objects
-    if let (Some((StatusCode::UNAUTHORIZED, _)), false, true) =
-        (response.status.as_ref(), cors_flag, credentials_flag)
-    {
-        // Substep 1
-        // TODO: Spec says requires testing on multiple WWW-Authenticate headers
-
+    // The retries below come back here rather than answering a new challenge
+    // themselves, so that only the credentials that were accepted get cached.
+    if let (Some((StatusCode::UNAUTHORIZED, _)), false, true, Window::Client, false) = (
+        response.status.as_ref(),
+        cors_flag,
+        credentials_flag,
+        &http_request.window,
+        authentication_fetch_flag,
+    ) {
         // Substep 2
         if http_request.body.is_some() {
             // TODO Implement body source
         }
 
         // Substep 3
-        if !http_request.use_url_credentials || authentication_fetch_flag {
-            // FIXME: Prompt the user for username and password from the window
+        let mut prompt = !http_request.use_url_credentials;
+        loop {
+            let authentication = if prompt {
+                // Substep 1
+                match authenticate(http_request, &response, false, context) {
+                    Some(authentication) => Some(authentication),
+                    None => return response,
+                }
+            } else {
+                None
+            };
 
-            // Wrong, but will have to do until we are able to prompt the user
-            // otherwise this creates an infinite loop
-            // We basically pretend that the user declined to enter credentials
-            return response;
-        }
+            // Substep 4
+            response = http_network_or_cache_fetch(
+                http_request,
+                true, /* authentication flag */
+                cors_flag,
+                done_chan,
+                context,
+            );
 
-        // Substep 4
-        response = http_network_or_cache_fetch(
-            http_request,
-            true, /* authentication flag */
-            cors_flag,
-            done_chan,
-            context,
-        );
+            match response.status {
+                Some((StatusCode::UNAUTHORIZED, _)) => prompt = true,
+                // The credentials never made it to the origin.
+                None | Some((StatusCode::PROXY_AUTHENTICATION_REQUIRED, _)) => break,
+                Some(_) => {
+                    // Step 12
+                    if let Some(authentication) = authentication {
+                        store_authentication_entry(
+                            &context.state.auth_cache,
+                            &http_request.current_url(),
+                            authentication,
+                        );
+                    }
+                    break;
+                },
+            }
+        }
     }
 
     // Step 11
-    if let Some((StatusCode::PROXY_AUTHENTICATION_REQUIRED, _)) = response.status.as_ref() {
+    if let (Some((StatusCode::PROXY_AUTHENTICATION_REQUIRED, _)), false) =
+        (response.status.as_ref(), proxy_authentication_fetch_flag)
+    {
         // Step 1
-        if request_has_no_window {
+        if http_request.window == Window::NoWindow {
             return Response::network_error(NetworkError::Internal(
                 "Can't find Window object".into(),
             ));
         }
 
-        // Step 2
-        // TODO: Spec says requires testing on Proxy-Authenticate headers
-
-        // Step 3
-        // FIXME: Prompt the user for proxy authentication credentials
+        loop {
+            // Steps 2-3
+            let authentication = match authenticate(http_request, &response, true, context) {
+                Some(authentication) => authentication,
+                None => return response,
+            };
 
-        // Wrong, but will have to do until we are able to prompt the user
-        // otherwise this creates an infinite loop
-        // We basically pretend that the user declined to enter credentials
-        return response;
+            // Step 4
+            response = http_network_or_cache_fetch(
+                http_request,
+                authentication_fetch_flag,
+                cors_flag,
+                done_chan,
+                context,
+            );
 
-        // Step 4
-        // return http_network_or_cache_fetch(request, authentication_fetch_flag,
-        //                                    cors_flag, done_chan, context);
+            match response.status {
+                Some((StatusCode::PROXY_AUTHENTICATION_REQUIRED, _)) => continue,
+                None => break,
+                Some(_) => {
+                    store_authentication_entry(
+                        &context.state.auth_cache,
+                        &http_request.current_url(),
+                        authentication,
+                    );
+                    break;
+                },
+            }
+        }
     }
 
     // Step 12
-    if authentication_fetch_flag {
-        // TODO Create the authentication entry for request and the given realm
-    }
+    // The authentication entry is created by the fetch that answered the challenge,
+    // which knows the realm the credentials are for.
 
     // Step 13
     response
@@ -1228,6 +1781,12 @@ fn http_network_fetch(
     // do not. Once we support other kinds of fetches we'll need to be more fine grained here
     // since things like image fetches are classified differently by devtools
     let is_xhr = request.destination == Destination::None;
+
+    // Don't send the request at all if the fetch was aborted meanwhile.
+    if context.cancellation_listener.lock().unwrap().cancelled() {
+        return Response::network_error(NetworkError::Internal("Fetch aborted".into()));
+    }
+
     let response_future = obtain_response(
         &context.state.client,
         &url,
@@ -1239,12 +1798,13 @@ fn http_network_fetch(
         request.redirect_count + 1,
         request_id.as_ref().map(Deref::deref),
         is_xhr,
+        request.mixed_content.map(devtools_mixed_content_status),
         context,
     );
 
     let pipeline_id = request.pipeline_id;
     // This will only get the headers, the body is read later
-    let (res, msg) = match response_future.wait() {
+    let (res, msg, transferred_size) = match response_future.wait() {
         Ok(wrapped_response) => wrapped_response,
         Err(error) => return Response::network_error(error),
     };
@@ -1286,6 +1846,8 @@ fn http_network_fetch(
     let devtools_sender = context.devtools_chan.clone();
     let meta_status = meta.status;
     let meta_headers = meta.headers;
+    let connection = res.extensions().get::<ConnectionInfo>().cloned();
+    let http_version = format!("{:?}", res.version());
     let cancellation_listener = context.cancellation_listener.clone();
     if cancellation_listener.lock().unwrap().cancelled() {
         return Response::network_error(NetworkError::Internal("Fetch aborted".into()));
@@ -1304,9 +1866,12 @@ fn http_network_fetch(
         if let Some(pipeline_id) = pipeline_id {
             send_response_to_devtools(
                 &sender,
-                request_id.unwrap(),
+                request_id.clone().unwrap(),
                 meta_headers.map(Serde::into_inner),
                 meta_status,
+                http_version,
+                connection.as_ref().and_then(|info| info.remote_addr),
+                connection.and_then(|info| info.security_info),
                 pipeline_id,
             );
         }
@@ -1338,11 +1903,24 @@ fn http_network_fetch(
                     ResponseBody::Receiving(ref mut body) => mem::replace(body, vec![]),
                     _ => vec![],
                 };
+                let receive_time = {
+                    let mut timing = timing_ptr2.lock().unwrap();
+                    timing.set_attribute(ResourceAttribute::ResponseEnd);
+                    duration_ms(timing.response_start, timing.response_end)
+                };
+                if let (Some(sender), Some(request_id), Some(pipeline_id)) =
+                    (devtools_sender, request_id, pipeline_id)
+                {
+                    send_response_end_to_devtools(
+                        &sender,
+                        request_id,
+                        &completed_body,
+                        transferred_size.load(Ordering::Relaxed),
+                        receive_time,
+                        pipeline_id,
+                    );
+                }
                 *body = ResponseBody::Done(completed_body);
-                timing_ptr2
-                    .lock()
-                    .unwrap()
-                    .set_attribute(ResourceAttribute::ResponseEnd);
                 let _ = done_sender2.send(Data::Done);
                 future::ok(())
             })
@@ -1393,7 +1971,12 @@ fn http_network_fetch(
     // TODO this step isn't possible yet
     // Step 15
     if credentials_flag {
-        set_cookies_from_headers(&url, &response.headers, &context.state.cookie_jar);
+        set_cookies_from_headers(
+            &url,
+            &response.headers,
+            &context.state.cookie_jar,
+            request.top_level_browsing_context_id,
+        );
     }
 
     // TODO these steps
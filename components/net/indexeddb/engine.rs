@@ -0,0 +1,907 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The storage engine behind IndexedDB: an in-memory model of the object
+//! stores and indexes of a database, which is kept on disk by the journal of
+//! the database.
+//!
+//! Changes made by read/write transactions are recorded in an undo log, so
+//! they can be reverted if the transaction aborts, and turned into the changes
+//! written to the journal if it commits. Upgrade transactions can change the
+//! schema, and are reverted from a copy of the whole database.
+
+use crate::fs_util::write_atomically;
+use net_traits::indexeddb_thread::{BackendError, BackendResult, CursorDirection};
+use net_traits::indexeddb_thread::{CursorPosition, CursorRecord, DatabaseInfo, IndexInfo};
+use net_traits::indexeddb_thread::{IndexKeys, IndexedDBKey, IndexedDBKeyRange, KeyPath};
+use net_traits::indexeddb_thread::{ObjectStoreInfo, Operation, OperationResult};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::ops::Bound;
+use std::path::Path;
+
+/// The largest number a key generator can produce: 2^53.
+const MAX_GENERATED_KEY: u64 = 9007199254740992;
+
+/// The version of the on-disk format, bumped whenever it changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+
+/// A structured clone, stored as base64 rather than as a JSON array of numbers.
+#[derive(Clone)]
+struct StoredValue(Vec<u8>);
+
+impl Serialize for StoredValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for StoredValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<StoredValue, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(&encoded)
+            .map(StoredValue)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serializes a map as a sequence of pairs, since JSON objects can only
+/// have strings as keys.
+mod map_as_pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs: Vec<(K, V)> = Deserialize::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Record {
+    value: StoredValue,
+    /// The keys of the entries of this record in each index.
+    index_keys: IndexKeys,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Index {
+    info: IndexInfo,
+    /// The primary keys of the records referenced by each key of the index.
+    #[serde(with = "map_as_pairs")]
+    entries: BTreeMap<IndexedDBKey, BTreeSet<IndexedDBKey>>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct ObjectStore {
+    key_path: Option<KeyPath>,
+    auto_increment: bool,
+    /// <https://w3c.github.io/IndexedDB/#key-generator-current-number>
+    current_number: u64,
+    #[serde(with = "map_as_pairs")]
+    records: BTreeMap<IndexedDBKey, Record>,
+    indexes: BTreeMap<String, Index>,
+}
+
+/// A change made by a committed read/write transaction, as written to the journal.
+/// Changes hold the resulting values, so replaying them more than once is harmless.
+#[derive(Deserialize, Serialize)]
+pub enum Change {
+    Record {
+        store: String,
+        key: IndexedDBKey,
+        new: Option<Record>,
+    },
+    KeyGenerator {
+        store: String,
+        current_number: u64,
+    },
+}
+
+/// A change made by a read/write transaction, recording how to revert it.
+pub enum UndoEntry {
+    Record {
+        store: String,
+        key: IndexedDBKey,
+        old: Option<Record>,
+    },
+    KeyGenerator {
+        store: String,
+        current_number: u64,
+    },
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Database {
+    format_version: u32,
+    pub name: String,
+    pub version: u64,
+    stores: BTreeMap<String, ObjectStore>,
+}
+
+impl Database {
+    /// A database that has just been created, with version 0.
+    pub fn new(name: String) -> Database {
+        Database {
+            format_version: FORMAT_VERSION,
+            name,
+            version: 0,
+            stores: BTreeMap::new(),
+        }
+    }
+
+    /// Read the database stored at `path`, if any.
+    pub fn load(path: &Path) -> Option<Database> {
+        let data = fs::read(path).ok()?;
+        match serde_json::from_slice::<Database>(&data) {
+            Ok(ref database) if database.format_version != FORMAT_VERSION => {
+                warn!(
+                    "ignoring indexeddb database with unknown format {}",
+                    path.display()
+                );
+                None
+            },
+            Ok(database) => Some(database),
+            Err(why) => {
+                warn!(
+                    "couldn't decode indexeddb database {}: {}",
+                    path.display(),
+                    why
+                );
+                None
+            },
+        }
+    }
+
+    /// Write the database to `path`, through a temporary file so a crash can
+    /// never leave it half-written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        write_atomically(path, &data)
+    }
+
+    pub fn info(&self) -> DatabaseInfo {
+        DatabaseInfo {
+            name: self.name.clone(),
+            version: self.version,
+            object_stores: self
+                .stores
+                .iter()
+                .map(|(name, store)| ObjectStoreInfo {
+                    name: name.clone(),
+                    key_path: store.key_path.clone(),
+                    auto_increment: store.auto_increment,
+                    indexes: store
+                        .indexes
+                        .values()
+                        .map(|index| index.info.clone())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn has_object_store(&self, name: &str) -> bool {
+        self.stores.contains_key(name)
+    }
+
+    /// Run an operation, recording how to revert any change it makes in `undo_log`.
+    /// Checking that the operation is allowed by the mode of the transaction is
+    /// left to the caller.
+    pub fn execute(
+        &mut self,
+        operation: Operation,
+        undo_log: &mut Vec<UndoEntry>,
+    ) -> BackendResult<OperationResult> {
+        match operation {
+            Operation::Get(store, index, range) => {
+                let store = self.store(&store)?;
+                let value = match index {
+                    Some(index) => store
+                        .index_entries(&index, &range)?
+                        .next()
+                        .map(|(_, primary_key)| store.records[primary_key].value.0.clone()),
+                    None => in_range(&store.records, &range)
+                        .next()
+                        .map(|(_, record)| record.value.0.clone()),
+                };
+                Ok(OperationResult::Value(value))
+            },
+            Operation::GetKey(store, index, range) => {
+                let store = self.store(&store)?;
+                let key = match index {
+                    Some(index) => store
+                        .index_entries(&index, &range)?
+                        .next()
+                        .map(|(_, primary_key)| primary_key.clone()),
+                    None => in_range(&store.records, &range)
+                        .next()
+                        .map(|(key, _)| key.clone()),
+                };
+                Ok(OperationResult::Key(key))
+            },
+            Operation::GetAll(store, index, range, count) => {
+                let store = self.store(&store)?;
+                let count = limit(count);
+                let values = match index {
+                    Some(index) => store
+                        .index_entries(&index, &range)?
+                        .take(count)
+                        .map(|(_, primary_key)| store.records[primary_key].value.0.clone())
+                        .collect(),
+                    None => in_range(&store.records, &range)
+                        .take(count)
+                        .map(|(_, record)| record.value.0.clone())
+                        .collect(),
+                };
+                Ok(OperationResult::Values(values))
+            },
+            Operation::GetAllKeys(store, index, range, count) => {
+                let store = self.store(&store)?;
+                let count = limit(count);
+                let keys = match index {
+                    Some(index) => store
+                        .index_entries(&index, &range)?
+                        .take(count)
+                        .map(|(_, primary_key)| primary_key.clone())
+                        .collect(),
+                    None => in_range(&store.records, &range)
+                        .take(count)
+                        .map(|(key, _)| key.clone())
+                        .collect(),
+                };
+                Ok(OperationResult::Keys(keys))
+            },
+            Operation::Count(store, index, range) => {
+                let store = self.store(&store)?;
+                let count = match index {
+                    Some(index) => store.index_entries(&index, &range)?.count(),
+                    None => in_range(&store.records, &range).count(),
+                };
+                Ok(OperationResult::Count(count as u64))
+            },
+            Operation::Put {
+                store,
+                key,
+                value,
+                index_keys,
+                no_overwrite,
+            } => self
+                .put(store, key, value, index_keys, no_overwrite, undo_log)
+                .map(|key| OperationResult::Key(Some(key))),
+            Operation::Delete(store_name, range) => {
+                let store = self.store_mut(&store_name)?;
+                let keys: Vec<_> = in_range(&store.records, &range)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in keys {
+                    let old = store.remove_record(&key);
+                    undo_log.push(UndoEntry::Record {
+                        store: store_name.clone(),
+                        key,
+                        old,
+                    });
+                }
+                Ok(OperationResult::Undefined)
+            },
+            Operation::Clear(store_name) => {
+                let store = self.store_mut(&store_name)?;
+                for index in store.indexes.values_mut() {
+                    index.entries.clear();
+                }
+                let records = std::mem::replace(&mut store.records, BTreeMap::new());
+                undo_log.extend(records.into_iter().map(|(key, record)| UndoEntry::Record {
+                    store: store_name.clone(),
+                    key,
+                    old: Some(record),
+                }));
+                Ok(OperationResult::Undefined)
+            },
+            Operation::GenerateKey(store_name) => {
+                let store = self.store_mut(&store_name)?;
+                let key = store.generate_key(&store_name, undo_log)?;
+                Ok(OperationResult::Key(Some(key)))
+            },
+            Operation::Iterate {
+                store,
+                index,
+                range,
+                direction,
+                position,
+                key_only,
+            } => {
+                let store = self.store(&store)?;
+                let record = store.iterate(index, &range, direction, position)?;
+                Ok(OperationResult::Cursor(record.map(|(key, primary_key)| {
+                    let value = if key_only {
+                        None
+                    } else {
+                        Some(store.records[&primary_key].value.0.clone())
+                    };
+                    CursorRecord {
+                        key,
+                        primary_key,
+                        value,
+                    }
+                })))
+            },
+            Operation::CreateObjectStore(name, key_path, auto_increment) => {
+                if self.stores.contains_key(&name) {
+                    return Err(BackendError::Constraint);
+                }
+                self.stores.insert(
+                    name,
+                    ObjectStore {
+                        key_path,
+                        auto_increment,
+                        current_number: 1,
+                        records: BTreeMap::new(),
+                        indexes: BTreeMap::new(),
+                    },
+                );
+                Ok(OperationResult::Undefined)
+            },
+            Operation::DeleteObjectStore(name) => self
+                .stores
+                .remove(&name)
+                .map(|_| OperationResult::Undefined)
+                .ok_or(BackendError::NotFound),
+            Operation::RenameObjectStore(name, new_name) => {
+                if name == new_name {
+                    return Ok(OperationResult::Undefined);
+                }
+                if self.stores.contains_key(&new_name) {
+                    return Err(BackendError::Constraint);
+                }
+                let store = self.stores.remove(&name).ok_or(BackendError::NotFound)?;
+                self.stores.insert(new_name, store);
+                Ok(OperationResult::Undefined)
+            },
+            Operation::CreateIndex {
+                store,
+                info,
+                entries,
+            } => self
+                .store_mut(&store)?
+                .create_index(info, entries)
+                .map(|_| OperationResult::Undefined),
+            Operation::DeleteIndex(store, name) => {
+                let store = self.store_mut(&store)?;
+                store.indexes.remove(&name).ok_or(BackendError::NotFound)?;
+                for record in store.records.values_mut() {
+                    record.index_keys.retain(|&(ref index, _)| *index != name);
+                }
+                Ok(OperationResult::Undefined)
+            },
+            Operation::RenameIndex(store, name, new_name) => {
+                let store = self.store_mut(&store)?;
+                if name == new_name {
+                    return Ok(OperationResult::Undefined);
+                }
+                if store.indexes.contains_key(&new_name) {
+                    return Err(BackendError::Constraint);
+                }
+                let mut index = store.indexes.remove(&name).ok_or(BackendError::NotFound)?;
+                index.info.name = new_name.clone();
+                store.indexes.insert(new_name.clone(), index);
+                for record in store.records.values_mut() {
+                    for &mut (ref mut index, _) in record.index_keys.iter_mut() {
+                        if *index == name {
+                            *index = new_name.clone();
+                        }
+                    }
+                }
+                Ok(OperationResult::Undefined)
+            },
+            Operation::GetAllRecords(store) => {
+                let store = self.store(&store)?;
+                let records = store
+                    .records
+                    .iter()
+                    .map(|(key, record)| (key.clone(), record.value.0.clone()))
+                    .collect();
+                Ok(OperationResult::Records(records))
+            },
+        }
+    }
+
+    /// Revert the changes recorded in `undo_log`.
+    pub fn undo(&mut self, undo_log: Vec<UndoEntry>) {
+        for entry in undo_log.into_iter().rev() {
+            match entry {
+                UndoEntry::Record { store, key, old } => {
+                    if let Some(store) = self.stores.get_mut(&store) {
+                        store.remove_record(&key);
+                        if let Some(old) = old {
+                            store.insert_record(key, old);
+                        }
+                    }
+                },
+                UndoEntry::KeyGenerator {
+                    store,
+                    current_number,
+                } => {
+                    if let Some(store) = self.stores.get_mut(&store) {
+                        store.current_number = current_number;
+                    }
+                },
+            }
+        }
+    }
+
+    /// The changes recorded in `undo_log`, along with the values they left behind.
+    pub fn changes(&self, undo_log: &[UndoEntry]) -> Vec<Change> {
+        let mut changed_records = BTreeSet::new();
+        let mut changed_generators = BTreeSet::new();
+        for entry in undo_log {
+            match *entry {
+                UndoEntry::Record {
+                    ref store, ref key, ..
+                } => {
+                    changed_records.insert((store, key));
+                },
+                UndoEntry::KeyGenerator { ref store, .. } => {
+                    changed_generators.insert(store);
+                },
+            }
+        }
+        let records = changed_records.into_iter().map(|(store, key)| Change::Record {
+            store: store.clone(),
+            key: key.clone(),
+            new: self
+                .stores
+                .get(store)
+                .and_then(|store| store.records.get(key))
+                .cloned(),
+        });
+        let generators = changed_generators
+            .into_iter()
+            .filter_map(|store| {
+                let current_number = self.stores.get(store)?.current_number;
+                Some(Change::KeyGenerator {
+                    store: store.clone(),
+                    current_number,
+                })
+            });
+        records.chain(generators).collect()
+    }
+
+    /// Apply changes replayed from the journal.
+    pub fn apply(&mut self, changes: Vec<Change>) {
+        for change in changes {
+            match change {
+                Change::Record { store, key, new } => {
+                    if let Some(store) = self.stores.get_mut(&store) {
+                        store.remove_record(&key);
+                        if let Some(new) = new {
+                            store.insert_record(key, new);
+                        }
+                    }
+                },
+                Change::KeyGenerator {
+                    store,
+                    current_number,
+                } => {
+                    if let Some(store) = self.stores.get_mut(&store) {
+                        store.current_number = current_number;
+                    }
+                },
+            }
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#store-a-record-into-an-object-store>
+    fn put(
+        &mut self,
+        store_name: String,
+        key: Option<IndexedDBKey>,
+        value: Vec<u8>,
+        index_keys: IndexKeys,
+        no_overwrite: bool,
+        undo_log: &mut Vec<UndoEntry>,
+    ) -> BackendResult<IndexedDBKey> {
+        let store = self.store_mut(&store_name)?;
+
+        // Step 1.
+        let key = match key {
+            Some(key) => {
+                // Step 2.
+                if store.auto_increment {
+                    if let IndexedDBKey::Number(number) = key {
+                        store.possibly_update_key_generator(&store_name, number, undo_log);
+                    }
+                }
+                key
+            },
+            None => store.generate_key(&store_name, undo_log)?,
+        };
+
+        // Step 3.
+        if no_overwrite && store.records.contains_key(&key) {
+            return Err(BackendError::Constraint);
+        }
+
+        // Step 5. Check unique indexes before changing anything.
+        for &(ref index_name, ref keys) in &index_keys {
+            let index = store
+                .indexes
+                .get(index_name)
+                .ok_or(BackendError::NotFound)?;
+            if !index.info.unique {
+                continue;
+            }
+            let conflicts = keys.iter().any(|index_key| {
+                index
+                    .entries
+                    .get(index_key)
+                    .map_or(false, |primary_keys| primary_keys.iter().any(|k| *k != key))
+            });
+            if conflicts {
+                return Err(BackendError::Constraint);
+            }
+        }
+
+        // Steps 4 and 6-7.
+        let old = store.remove_record(&key);
+        store.insert_record(
+            key.clone(),
+            Record {
+                value: StoredValue(value),
+                index_keys,
+            },
+        );
+        undo_log.push(UndoEntry::Record {
+            store: store_name,
+            key: key.clone(),
+            old,
+        });
+        Ok(key)
+    }
+
+    fn store(&self, name: &str) -> BackendResult<&ObjectStore> {
+        self.stores.get(name).ok_or(BackendError::NotFound)
+    }
+
+    fn store_mut(&mut self, name: &str) -> BackendResult<&mut ObjectStore> {
+        self.stores.get_mut(name).ok_or(BackendError::NotFound)
+    }
+}
+
+impl ObjectStore {
+    fn remove_record(&mut self, key: &IndexedDBKey) -> Option<Record> {
+        let record = self.records.remove(key)?;
+        for &(ref index_name, ref index_keys) in &record.index_keys {
+            if let Some(index) = self.indexes.get_mut(index_name) {
+                for index_key in index_keys {
+                    let now_empty = match index.entries.get_mut(index_key) {
+                        Some(primary_keys) => {
+                            primary_keys.remove(key);
+                            primary_keys.is_empty()
+                        },
+                        None => false,
+                    };
+                    if now_empty {
+                        index.entries.remove(index_key);
+                    }
+                }
+            }
+        }
+        Some(record)
+    }
+
+    fn insert_record(&mut self, key: IndexedDBKey, record: Record) {
+        for &(ref index_name, ref index_keys) in &record.index_keys {
+            if let Some(index) = self.indexes.get_mut(index_name) {
+                for index_key in index_keys {
+                    index
+                        .entries
+                        .entry(index_key.clone())
+                        .or_insert_with(BTreeSet::new)
+                        .insert(key.clone());
+                }
+            }
+        }
+        self.records.insert(key, record);
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#generate-a-key>
+    fn generate_key(
+        &mut self,
+        store_name: &str,
+        undo_log: &mut Vec<UndoEntry>,
+    ) -> BackendResult<IndexedDBKey> {
+        if !self.auto_increment {
+            return Err(BackendError::Data);
+        }
+        // Steps 2-3.
+        let key = self.current_number;
+        if key > MAX_GENERATED_KEY {
+            return Err(BackendError::Constraint);
+        }
+        // Step 4.
+        undo_log.push(UndoEntry::KeyGenerator {
+            store: store_name.to_owned(),
+            current_number: self.current_number,
+        });
+        self.current_number += 1;
+        // Step 5.
+        Ok(IndexedDBKey::Number(key as f64))
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#possibly-update-the-key-generator>
+    fn possibly_update_key_generator(
+        &mut self,
+        store_name: &str,
+        number: f64,
+        undo_log: &mut Vec<UndoEntry>,
+    ) {
+        // Step 3.
+        let value = number.min(MAX_GENERATED_KEY as f64).floor();
+        // Step 4.
+        if value >= self.current_number as f64 {
+            undo_log.push(UndoEntry::KeyGenerator {
+                store: store_name.to_owned(),
+                current_number: self.current_number,
+            });
+            // Once 2^53 is reached, generating keys fails with a ConstraintError.
+            self.current_number = value as u64 + 1;
+        }
+    }
+
+    fn create_index(
+        &mut self,
+        info: IndexInfo,
+        entries: Vec<(IndexedDBKey, Vec<IndexedDBKey>)>,
+    ) -> BackendResult<()> {
+        if self.indexes.contains_key(&info.name) {
+            return Err(BackendError::Constraint);
+        }
+        let mut index = Index {
+            info: info.clone(),
+            entries: BTreeMap::new(),
+        };
+        for &(ref primary_key, ref index_keys) in &entries {
+            if !self.records.contains_key(primary_key) {
+                continue;
+            }
+            for index_key in index_keys {
+                let primary_keys = index
+                    .entries
+                    .entry(index_key.clone())
+                    .or_insert_with(BTreeSet::new);
+                if info.unique && !primary_keys.is_empty() && !primary_keys.contains(primary_key) {
+                    return Err(BackendError::Constraint);
+                }
+                primary_keys.insert(primary_key.clone());
+            }
+        }
+        for (primary_key, index_keys) in entries {
+            if let Some(record) = self.records.get_mut(&primary_key) {
+                record.index_keys.push((info.name.clone(), index_keys));
+            }
+        }
+        self.indexes.insert(info.name, index);
+        Ok(())
+    }
+
+    /// The (key, primary key) pairs of an index whose key is in `range`, in order.
+    fn index_entries<'a>(
+        &'a self,
+        index: &str,
+        range: &'a IndexedDBKeyRange,
+    ) -> BackendResult<impl DoubleEndedIterator<Item = (&'a IndexedDBKey, &'a IndexedDBKey)> + 'a>
+    {
+        let index = self.indexes.get(index).ok_or(BackendError::NotFound)?;
+        Ok(in_range(&index.entries, range)
+            .flat_map(|(key, primary_keys)| primary_keys.iter().map(move |pk| (key, pk))))
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#iterate-a-cursor>
+    ///
+    /// Returns the key and primary key of the record the cursor moves to.
+    fn iterate(
+        &self,
+        index: Option<String>,
+        range: &IndexedDBKeyRange,
+        direction: CursorDirection,
+        mut position: CursorPosition,
+    ) -> BackendResult<Option<(IndexedDBKey, IndexedDBKey)>> {
+        let index = match index {
+            Some(name) => Some(self.indexes.get(&name).ok_or(BackendError::NotFound)?),
+            None => None,
+        };
+        let mut found = None;
+        // Step 5.
+        for _ in 0..position.count.max(1) {
+            found = match index {
+                Some(index) => find_in_index(index, range, direction, &position),
+                None => find_in_store(&self.records, range, direction, &position),
+            };
+            match found {
+                Some((ref key, ref primary_key)) => {
+                    position.current = Some((key.clone(), primary_key.clone()));
+                    position.target_key = None;
+                    position.target_primary_key = None;
+                },
+                // Step 5.6.
+                None => return Ok(None),
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// The number of results to return for a requested count, where 0 means all.
+fn limit(count: Option<u32>) -> usize {
+    match count {
+        Some(count) if count > 0 => count as usize,
+        _ => usize::max_value(),
+    }
+}
+
+/// The entries of `map` whose key is in `range`, in order.
+fn in_range<'a, V>(
+    map: &'a BTreeMap<IndexedDBKey, V>,
+    range: &'a IndexedDBKeyRange,
+) -> Box<dyn DoubleEndedIterator<Item = (&'a IndexedDBKey, &'a V)> + 'a> {
+    if range.is_empty() {
+        // `BTreeMap::range` panics on inverted ranges.
+        return Box::new(std::iter::empty());
+    }
+    let lower = match range.lower {
+        Some(ref key) if range.lower_open => Bound::Excluded(key),
+        Some(ref key) => Bound::Included(key),
+        None => Bound::Unbounded,
+    };
+    let upper = match range.upper {
+        Some(ref key) if range.upper_open => Bound::Excluded(key),
+        Some(ref key) => Bound::Included(key),
+        None => Bound::Unbounded,
+    };
+    Box::new(map.range::<IndexedDBKey, _>((lower, upper)))
+}
+
+/// The key to start searching from: the greatest of the given keys when moving
+/// forward, and the least of them when moving backward.
+fn search_start<'a>(forward: bool, keys: Vec<Option<&'a IndexedDBKey>>) -> Bound<&'a IndexedDBKey> {
+    let keys = keys.into_iter().flatten();
+    let start = if forward { keys.max() } else { keys.min() };
+    start.map_or(Bound::Unbounded, Bound::Included)
+}
+
+/// Steps 6-7 of <https://w3c.github.io/IndexedDB/#iterate-a-cursor>, for a
+/// cursor over an object store.
+fn find_in_store(
+    records: &BTreeMap<IndexedDBKey, Record>,
+    range: &IndexedDBKeyRange,
+    direction: CursorDirection,
+    position: &CursorPosition,
+) -> Option<(IndexedDBKey, IndexedDBKey)> {
+    let current = position.current.as_ref().map(|&(ref key, _)| key);
+    let target = position.target_key.as_ref();
+    let found = match direction {
+        CursorDirection::Next | CursorDirection::NextUnique => {
+            let start = search_start(true, vec![range.lower.as_ref(), current, target]);
+            records
+                .range::<IndexedDBKey, _>((start, Bound::Unbounded))
+                .map(|(key, _)| key)
+                .take_while(|key| !range.is_above(key))
+                .find(|key| {
+                    !range.is_below(key) &&
+                        target.map_or(true, |target| *key >= target) &&
+                        current.map_or(true, |current| *key > current)
+                })
+        },
+        CursorDirection::Prev | CursorDirection::PrevUnique => {
+            let start = search_start(false, vec![range.upper.as_ref(), current, target]);
+            records
+                .range::<IndexedDBKey, _>((Bound::Unbounded, start))
+                .rev()
+                .map(|(key, _)| key)
+                .take_while(|key| !range.is_below(key))
+                .find(|key| {
+                    !range.is_above(key) &&
+                        target.map_or(true, |target| *key <= target) &&
+                        current.map_or(true, |current| *key < current)
+                })
+        },
+    };
+    found.map(|key| (key.clone(), key.clone()))
+}
+
+/// Steps 6-7 of <https://w3c.github.io/IndexedDB/#iterate-a-cursor>, for a
+/// cursor over an index.
+fn find_in_index(
+    index: &Index,
+    range: &IndexedDBKeyRange,
+    direction: CursorDirection,
+    position: &CursorPosition,
+) -> Option<(IndexedDBKey, IndexedDBKey)> {
+    let current = position.current.as_ref();
+    let target = position.target_key.as_ref();
+    let target_primary_key = position.target_primary_key.as_ref();
+    let current_key = current.map(|&(ref key, _)| key);
+    match direction {
+        CursorDirection::Next | CursorDirection::NextUnique => {
+            let unique = direction == CursorDirection::NextUnique;
+            let start = search_start(true, vec![range.lower.as_ref(), current_key, target]);
+            index
+                .entries
+                .range::<IndexedDBKey, _>((start, Bound::Unbounded))
+                .take_while(|&(key, _)| !range.is_above(key))
+                .flat_map(|(key, primary_keys)| primary_keys.iter().map(move |pk| (key, pk)))
+                .find(|&(key, pk)| {
+                    let after_target = match (target, target_primary_key) {
+                        (Some(target), Some(target_pk)) => {
+                            key > target || (key == target && pk >= target_pk)
+                        },
+                        (Some(target), None) => key >= target,
+                        _ => true,
+                    };
+                    let after_current = match current {
+                        Some(&(ref current, _)) if unique => key > current,
+                        Some(&(ref current, ref current_pk)) => {
+                            key > current || (key == current && pk > current_pk)
+                        },
+                        None => true,
+                    };
+                    !range.is_below(key) && after_target && after_current
+                })
+                .map(|(key, pk)| (key.clone(), pk.clone()))
+        },
+        CursorDirection::Prev | CursorDirection::PrevUnique => {
+            let unique = direction == CursorDirection::PrevUnique;
+            let start = search_start(false, vec![range.upper.as_ref(), current_key, target]);
+            index
+                .entries
+                .range::<IndexedDBKey, _>((Bound::Unbounded, start))
+                .rev()
+                .take_while(|&(key, _)| !range.is_below(key))
+                .flat_map(|(key, primary_keys)| primary_keys.iter().rev().map(move |pk| (key, pk)))
+                .find(|&(key, pk)| {
+                    let before_target = match (target, target_primary_key) {
+                        (Some(target), Some(target_pk)) => {
+                            key < target || (key == target && pk <= target_pk)
+                        },
+                        (Some(target), None) => key <= target,
+                        _ => true,
+                    };
+                    let before_current = match current {
+                        Some(&(ref current, _)) if unique => key < current,
+                        Some(&(ref current, ref current_pk)) => {
+                            key < current || (key == current && pk < current_pk)
+                        },
+                        None => true,
+                    };
+                    !range.is_above(key) && before_target && before_current
+                })
+                .map(|(key, pk)| {
+                    if unique {
+                        // Unique cursors going backward stop at the first
+                        // record with a given key, not the last one.
+                        let first = index.entries[key].iter().next().unwrap_or(pk);
+                        (key.clone(), first.clone())
+                    } else {
+                        (key.clone(), pk.clone())
+                    }
+                })
+        },
+    }
+}
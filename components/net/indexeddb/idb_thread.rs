@@ -0,0 +1,559 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The IndexedDB backend thread: it owns every database, hands out
+//! connections to them, and schedules the transactions run over them.
+
+use super::engine::{Database, UndoEntry};
+use super::journal::DatabaseJournal;
+use crate::fs_util::stable_hash;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use net_traits::indexeddb_thread::{BackendError, BackendResult, ConnectionEvent, ConnectionId};
+use net_traits::indexeddb_thread::{DeleteEvent, IndexedDBThreadMsg, IndexedDBTxnMode};
+use net_traits::indexeddb_thread::{Operation, OperationResult, TransactionId};
+use servo_url::ImmutableOrigin;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+pub trait IndexedDBThreadFactory {
+    fn new(config_dir: Option<PathBuf>) -> Self;
+}
+
+impl IndexedDBThreadFactory for IpcSender<IndexedDBThreadMsg> {
+    /// Create an IndexedDB thread
+    fn new(config_dir: Option<PathBuf>) -> IpcSender<IndexedDBThreadMsg> {
+        let (chan, port) = ipc::channel().unwrap();
+        thread::Builder::new()
+            .name("IndexedDBManager".to_owned())
+            .spawn(move || {
+                IndexedDBManager::new(port, config_dir).start();
+            })
+            .expect("Thread spawning failed");
+        chan
+    }
+}
+
+/// Databases are identified by the serialization of their origin, and their name.
+type DatabaseKey = (String, String);
+
+/// A request to open or delete a database, waiting for the ones before it.
+enum PendingRequest {
+    Open(IpcSender<ConnectionEvent>, Option<u64>),
+    Delete(IpcSender<DeleteEvent>),
+}
+
+struct DatabaseState {
+    /// The database, unless it doesn't exist yet or has been deleted.
+    database: Option<Database>,
+    /// Where the database is stored, if it is stored on disk at all.
+    journal: Option<DatabaseJournal>,
+    /// The open connections to the database.
+    connections: Vec<ConnectionId>,
+    /// <https://w3c.github.io/IndexedDB/#connection-queue>
+    queue: VecDeque<PendingRequest>,
+    /// Whether the open connections were asked to close for the request at
+    /// the front of the queue.
+    waiting_for_close: bool,
+    /// The connection whose upgrade transaction is running, if any.
+    upgrading: Option<ConnectionId>,
+    /// The unfinished transactions over the database, in creation order.
+    transactions: Vec<TransactionId>,
+}
+
+impl DatabaseState {
+    /// Write the changes made by a committing transaction to disk.
+    fn save(&mut self, transaction: &Transaction) -> io::Result<()> {
+        let (database, journal) = match (&self.database, &mut self.journal) {
+            (Some(database), Some(journal)) => (database, journal),
+            _ => return Ok(()),
+        };
+        match transaction.mode {
+            IndexedDBTxnMode::Readonly => Ok(()),
+            IndexedDBTxnMode::Versionchange => journal.save(database),
+            IndexedDBTxnMode::Readwrite => {
+                if transaction.undo_log.is_empty() {
+                    return Ok(());
+                }
+                journal.append(&database.changes(&transaction.undo_log))?;
+                if journal.needs_compaction() {
+                    if let Err(why) = journal.save(database) {
+                        // The journal still holds every change, so the commit stands.
+                        warn!("couldn't write indexeddb snapshot: {}", why);
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Revert the changes made by a transaction that aborted.
+    fn revert(&mut self, transaction: Transaction) {
+        match transaction.snapshot {
+            // A database created for the upgrade never existed.
+            Some(ref snapshot) if snapshot.version == 0 => self.database = None,
+            Some(snapshot) => self.database = Some(snapshot),
+            None => {
+                if let Some(ref mut database) = self.database {
+                    database.undo(transaction.undo_log);
+                }
+            },
+        }
+    }
+}
+
+struct Connection {
+    database: DatabaseKey,
+    sender: IpcSender<ConnectionEvent>,
+}
+
+struct Transaction {
+    database: DatabaseKey,
+    mode: IndexedDBTxnMode,
+    scope: Vec<String>,
+    /// Notified once the transaction starts; upgrade transactions notify
+    /// their connection instead.
+    start_sender: Option<IpcSender<()>>,
+    started: bool,
+    undo_log: Vec<UndoEntry>,
+    /// The database as it was before an upgrade transaction.
+    snapshot: Option<Database>,
+}
+
+impl Transaction {
+    /// <https://w3c.github.io/IndexedDB/#transaction-scheduling>
+    fn overlaps(&self, other: &Transaction) -> bool {
+        if self.mode == IndexedDBTxnMode::Versionchange ||
+            other.mode == IndexedDBTxnMode::Versionchange
+        {
+            return true;
+        }
+        if self.mode == IndexedDBTxnMode::Readonly && other.mode == IndexedDBTxnMode::Readonly {
+            return false;
+        }
+        self.scope.iter().any(|store| other.scope.contains(store))
+    }
+}
+
+struct IndexedDBManager {
+    port: IpcReceiver<IndexedDBThreadMsg>,
+    databases: HashMap<DatabaseKey, DatabaseState>,
+    connections: HashMap<ConnectionId, Connection>,
+    transactions: HashMap<TransactionId, Transaction>,
+    next_connection_id: u64,
+    config_dir: Option<PathBuf>,
+}
+
+impl IndexedDBManager {
+    fn new(port: IpcReceiver<IndexedDBThreadMsg>, config_dir: Option<PathBuf>) -> IndexedDBManager {
+        IndexedDBManager {
+            port: port,
+            databases: HashMap::new(),
+            connections: HashMap::new(),
+            transactions: HashMap::new(),
+            next_connection_id: 1,
+            config_dir: config_dir,
+        }
+    }
+}
+
+impl IndexedDBManager {
+    fn start(&mut self) {
+        loop {
+            match self.port.recv().unwrap() {
+                IndexedDBThreadMsg::Open(sender, origin, name, version) => {
+                    let key = self.database_key(&origin, name);
+                    self.queue_request(key, PendingRequest::Open(sender, version))
+                },
+                IndexedDBThreadMsg::DeleteDatabase(sender, origin, name) => {
+                    let key = self.database_key(&origin, name);
+                    self.queue_request(key, PendingRequest::Delete(sender))
+                },
+                IndexedDBThreadMsg::Close(connection) => self.close(connection),
+                IndexedDBThreadMsg::CreateTransaction(sender, id, mode, scope) => {
+                    self.create_transaction(id, mode, scope, Some(sender))
+                },
+                IndexedDBThreadMsg::Operation(sender, id, operation) => {
+                    let _ = sender.send(self.execute(id, operation));
+                },
+                IndexedDBThreadMsg::Commit(sender, id) => {
+                    let _ = sender.send(self.commit(id));
+                },
+                IndexedDBThreadMsg::Abort(sender, id) => {
+                    self.abort(id);
+                    let _ = sender.send(());
+                },
+                IndexedDBThreadMsg::Exit(sender) => {
+                    // Nothing to do since databases are saved when transactions commit.
+                    let _ = sender.send(());
+                    break;
+                },
+            }
+        }
+    }
+
+    /// Find the state of a database, reading it from disk the first time it is used.
+    fn database_key(&mut self, origin: &ImmutableOrigin, name: String) -> DatabaseKey {
+        let key = (origin.ascii_serialization(), name);
+        if !self.databases.contains_key(&key) {
+            // Opaque origins all serialize the same way, so they are never persisted.
+            let mut journal = match self.config_dir {
+                Some(ref config_dir) if origin.is_tuple() => Some(DatabaseJournal::new(
+                    database_path(config_dir, &key.0, &key.1),
+                )),
+                _ => None,
+            };
+            let database = journal.as_mut().and_then(|journal| journal.load());
+            self.databases.insert(
+                key.clone(),
+                DatabaseState {
+                    database,
+                    journal,
+                    connections: vec![],
+                    queue: VecDeque::new(),
+                    waiting_for_close: false,
+                    upgrading: None,
+                    transactions: vec![],
+                },
+            );
+        }
+        key
+    }
+
+    fn queue_request(&mut self, key: DatabaseKey, request: PendingRequest) {
+        self.databases
+            .get_mut(&key)
+            .expect("Database state missing")
+            .queue
+            .push_back(request);
+        self.process_queue(&key);
+    }
+
+    /// Handle the requests waiting to open or delete a database, until one of
+    /// them has to wait for connections to close or for an upgrade to finish.
+    fn process_queue(&mut self, key: &DatabaseKey) {
+        loop {
+            let state = match self.databases.get_mut(key) {
+                Some(state) => state,
+                None => return,
+            };
+            if state.upgrading.is_some() {
+                return;
+            }
+            let current_version = state.database.as_ref().map_or(0, |db| db.version);
+
+            // <https://w3c.github.io/IndexedDB/#open-a-database>, steps 5-6, and
+            // <https://w3c.github.io/IndexedDB/#delete-a-database>, steps 5-6.
+            let (needs_exclusive_access, new_version) = match state.queue.front() {
+                None => return,
+                Some(&PendingRequest::Open(ref sender, version)) => {
+                    let requested = version.unwrap_or(current_version.max(1));
+                    if requested < current_version {
+                        let _ = sender.send(ConnectionEvent::Error(BackendError::Version));
+                        state.queue.pop_front();
+                        continue;
+                    }
+                    (requested > current_version, Some(requested))
+                },
+                Some(&PendingRequest::Delete(_)) => (true, None),
+            };
+
+            if needs_exclusive_access && !state.connections.is_empty() {
+                if !state.waiting_for_close {
+                    state.waiting_for_close = true;
+                    for connection in &state.connections {
+                        let _ = self.connections[connection].sender.send(
+                            ConnectionEvent::VersionChange {
+                                old_version: current_version,
+                                new_version,
+                            },
+                        );
+                    }
+                    // Connections are closed by script, so they can't have closed yet.
+                    match state.queue.front() {
+                        Some(&PendingRequest::Open(ref sender, _)) => {
+                            let _ = sender.send(ConnectionEvent::Blocked {
+                                old_version: current_version,
+                                new_version: new_version.unwrap_or(current_version),
+                            });
+                        },
+                        Some(&PendingRequest::Delete(ref sender)) => {
+                            let _ = sender.send(DeleteEvent::Blocked {
+                                old_version: current_version,
+                            });
+                        },
+                        None => {},
+                    }
+                }
+                return;
+            }
+            state.waiting_for_close = false;
+
+            match state.queue.pop_front() {
+                Some(PendingRequest::Open(sender, _)) => {
+                    let requested = new_version.unwrap_or(current_version);
+                    self.open_connection(key, sender, requested);
+                },
+                Some(PendingRequest::Delete(sender)) => {
+                    // <https://w3c.github.io/IndexedDB/#delete-a-database>, steps 7-10.
+                    state.database = None;
+                    if let Some(ref mut journal) = state.journal {
+                        journal.remove();
+                    }
+                    let _ = sender.send(DeleteEvent::Deleted {
+                        old_version: current_version,
+                    });
+                },
+                None => return,
+            }
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#open-a-database>, steps 7-10.
+    fn open_connection(
+        &mut self,
+        key: &DatabaseKey,
+        sender: IpcSender<ConnectionEvent>,
+        version: u64,
+    ) {
+        let id = ConnectionId(self.next_connection_id);
+        self.next_connection_id += 1;
+
+        let state = self.databases.get_mut(key).expect("Database state missing");
+        state.connections.push(id);
+        let database = state
+            .database
+            .get_or_insert_with(|| Database::new(key.1.clone()));
+
+        if database.version == version {
+            let _ = sender.send(ConnectionEvent::Opened {
+                connection: id,
+                info: database.info(),
+            });
+        } else {
+            // <https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction>, steps 1-6.
+            // The connection is told about the upgrade once its transaction starts.
+            let snapshot = database.clone();
+            database.version = version;
+            state.upgrading = Some(id);
+            self.transactions.insert(
+                TransactionId {
+                    connection: id,
+                    serial: 0,
+                },
+                Transaction {
+                    database: key.clone(),
+                    mode: IndexedDBTxnMode::Versionchange,
+                    scope: vec![],
+                    start_sender: None,
+                    started: false,
+                    undo_log: vec![],
+                    snapshot: Some(snapshot),
+                },
+            );
+            state.transactions.push(TransactionId {
+                connection: id,
+                serial: 0,
+            });
+        }
+        self.connections.insert(
+            id,
+            Connection {
+                database: key.clone(),
+                sender,
+            },
+        );
+        self.schedule_transactions(key);
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#close-a-database-connection>
+    ///
+    /// Transactions created by the connection are left to finish.
+    fn close(&mut self, id: ConnectionId) {
+        let key = match self.connections.remove(&id) {
+            Some(connection) => connection.database,
+            None => return,
+        };
+        if let Some(state) = self.databases.get_mut(&key) {
+            state.connections.retain(|connection| *connection != id);
+        }
+        self.process_queue(&key);
+    }
+
+    fn create_transaction(
+        &mut self,
+        id: TransactionId,
+        mode: IndexedDBTxnMode,
+        scope: Vec<String>,
+        start_sender: Option<IpcSender<()>>,
+    ) {
+        let key = match self.connections.get(&id.connection) {
+            Some(connection) => connection.database.clone(),
+            None => {
+                warn!("Transaction created for closed connection {:?}", id);
+                return;
+            },
+        };
+        self.transactions.insert(
+            id,
+            Transaction {
+                database: key.clone(),
+                mode,
+                scope,
+                start_sender,
+                started: false,
+                undo_log: vec![],
+                snapshot: None,
+            },
+        );
+        if let Some(state) = self.databases.get_mut(&key) {
+            state.transactions.push(id);
+        }
+        self.schedule_transactions(&key);
+    }
+
+    /// Start every transaction that no earlier unfinished transaction overlaps with.
+    fn schedule_transactions(&mut self, key: &DatabaseKey) {
+        let state = match self.databases.get(key) {
+            Some(state) => state,
+            None => return,
+        };
+        for (position, id) in state.transactions.iter().enumerate() {
+            let transaction = &self.transactions[id];
+            if transaction.started {
+                continue;
+            }
+            let blocked = state.transactions[..position]
+                .iter()
+                .any(|earlier| self.transactions[earlier].overlaps(transaction));
+            if blocked {
+                continue;
+            }
+
+            let transaction = self.transactions.get_mut(id).expect("Transaction missing");
+            transaction.started = true;
+            match transaction.start_sender {
+                Some(ref sender) => {
+                    let _ = sender.send(());
+                },
+                None => {
+                    // <https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction>, step 10.
+                    let old_version = transaction.snapshot.as_ref().map_or(0, |db| db.version);
+                    if let Some(connection) = self.connections.get(&id.connection) {
+                        let _ = connection.sender.send(ConnectionEvent::UpgradeNeeded {
+                            connection: id.connection,
+                            old_version,
+                            info: state.database.as_ref().expect("Upgraded database").info(),
+                        });
+                    }
+                },
+            }
+        }
+    }
+
+    fn execute(
+        &mut self,
+        id: TransactionId,
+        operation: Operation,
+    ) -> BackendResult<OperationResult> {
+        let transaction = match self.transactions.get_mut(&id) {
+            Some(transaction) if transaction.started => transaction,
+            _ => return Err(BackendError::Unknown),
+        };
+        let allowed = match operation {
+            Operation::CreateObjectStore(..) |
+            Operation::DeleteObjectStore(..) |
+            Operation::RenameObjectStore(..) |
+            Operation::CreateIndex { .. } |
+            Operation::DeleteIndex(..) |
+            Operation::RenameIndex(..) => transaction.mode == IndexedDBTxnMode::Versionchange,
+            Operation::Put { .. } |
+            Operation::Delete(..) |
+            Operation::Clear(..) |
+            Operation::GenerateKey(..) => transaction.mode != IndexedDBTxnMode::Readonly,
+            _ => true,
+        };
+        if !allowed {
+            return Err(BackendError::Unknown);
+        }
+        let database = self
+            .databases
+            .get_mut(&transaction.database)
+            .and_then(|state| state.database.as_mut());
+        match database {
+            Some(database) => database.execute(operation, &mut transaction.undo_log),
+            None => Err(BackendError::NotFound),
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#commit-a-transaction>
+    fn commit(&mut self, id: TransactionId) -> BackendResult<()> {
+        let transaction = self.transactions.remove(&id).ok_or(BackendError::Unknown)?;
+        let key = transaction.database.clone();
+        let state = match self.finish_transaction(id, &transaction) {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        let mut result = Ok(());
+        if let Err(why) = state.save(&transaction) {
+            warn!("couldn't save indexeddb database {}: {}", key.1, why);
+            // Script aborts the transaction once the commit fails,
+            // which has to leave the database as it was.
+            state.revert(transaction);
+            result = Err(BackendError::Unknown);
+        }
+
+        self.schedule_transactions(&key);
+        self.process_queue(&key);
+        result
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#abort-a-transaction>
+    fn abort(&mut self, id: TransactionId) {
+        let transaction = match self.transactions.remove(&id) {
+            Some(transaction) => transaction,
+            None => return,
+        };
+        let key = transaction.database.clone();
+        if let Some(state) = self.finish_transaction(id, &transaction) {
+            state.revert(transaction);
+        }
+        self.schedule_transactions(&key);
+        self.process_queue(&key);
+    }
+
+    /// Forget about a transaction that committed or aborted, returning the
+    /// state of the database it ran over.
+    fn finish_transaction(
+        &mut self,
+        id: TransactionId,
+        transaction: &Transaction,
+    ) -> Option<&mut DatabaseState> {
+        let state = self.databases.get_mut(&transaction.database)?;
+        state.transactions.retain(|transaction| *transaction != id);
+        if transaction.mode == IndexedDBTxnMode::Versionchange {
+            state.upgrading = None;
+        }
+        Some(state)
+    }
+}
+
+/// Where a database is stored: a directory per origin, holding a file per
+/// database, named after a hash of the name of the database.
+fn database_path(config_dir: &Path, origin: &str, name: &str) -> PathBuf {
+    let origin: String = origin
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    let hash = stable_hash(name.as_bytes());
+    config_dir
+        .join("IndexedDB")
+        .join(origin)
+        .join(format!("{:016x}.json", hash))
+}
@@ -0,0 +1,136 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Incremental persistence of IndexedDB databases.
+//!
+//! A database is stored as a snapshot, `<hash>.json`, and a journal,
+//! `<hash>.journal`. A read/write transaction that commits appends its changes
+//! to the journal as a single line of JSON, which is synced before the commit
+//! succeeds, and the journal is replayed on top of the snapshot when the
+//! database is loaded. A trailing line left incomplete by a crash is ignored,
+//! so a transaction is either entirely on disk or not at all.
+//!
+//! Upgrade transactions, which can change the schema, write a new snapshot and
+//! empty the journal, as does a read/write transaction once the journal has
+//! grown long enough.
+
+use super::engine::{Change, Database};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The number of journal lines after which the database is written to a new snapshot.
+const MAX_JOURNAL_LINES: usize = 1000;
+
+/// The files in which a database is stored.
+pub struct DatabaseJournal {
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    /// The journal, opened for appending once something is written to it.
+    file: Option<File>,
+    /// The number of lines currently in the journal.
+    len: usize,
+}
+
+impl DatabaseJournal {
+    /// The journal of the database whose snapshot is stored at `snapshot_path`.
+    pub fn new(snapshot_path: PathBuf) -> DatabaseJournal {
+        DatabaseJournal {
+            journal_path: snapshot_path.with_extension("journal"),
+            snapshot_path,
+            file: None,
+            len: 0,
+        }
+    }
+
+    /// Read the database, replaying the journal on top of the snapshot.
+    pub fn load(&mut self) -> Option<Database> {
+        let mut database = Database::load(&self.snapshot_path)?;
+        let lines = read_lines(&self.journal_path);
+        self.len = lines.len();
+        for changes in lines {
+            database.apply(changes);
+        }
+        Some(database)
+    }
+
+    /// Append the changes of a committing transaction to the journal.
+    pub fn append(&mut self, changes: &[Change]) -> io::Result<()> {
+        let mut line = serde_json::to_vec(changes)?;
+        line.push(b'\n');
+        let file = self.open()?;
+        let previous_len = file.metadata()?.len();
+        // A single write, so that a crash can at most cut off this line.
+        let written = file.write_all(&line).and_then(|_| file.sync_data());
+        if written.is_err() {
+            // Don't leave a partial line behind, which would hide the lines after it.
+            let _ = file.set_len(previous_len);
+            return written;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Whether the journal has grown long enough to be folded into a new snapshot.
+    pub fn needs_compaction(&self) -> bool {
+        self.len >= MAX_JOURNAL_LINES
+    }
+
+    /// Replace the snapshot with `database`, and empty the journal.
+    pub fn save(&mut self, database: &Database) -> io::Result<()> {
+        if let Some(parent) = self.snapshot_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        database.save(&self.snapshot_path)?;
+        if self.len > 0 {
+            // Lines left behind only repeat changes the new snapshot already holds.
+            match self.open().and_then(|file| file.set_len(0)) {
+                Ok(()) => self.len = 0,
+                Err(why) => warn!("couldn't truncate indexeddb journal: {}", why),
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the files of the database.
+    pub fn remove(&mut self) {
+        self.file = None;
+        self.len = 0;
+        let _ = fs::remove_file(&self.journal_path);
+        let _ = fs::remove_file(&self.snapshot_path);
+    }
+
+    fn open(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.journal_path)?;
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+}
+
+fn read_lines(path: &Path) -> Vec<Vec<Change>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(_) => return vec![],
+    };
+    let mut lines = vec![];
+    for line in data.split(|byte| *byte == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_slice(line) {
+            Ok(changes) => lines.push(changes),
+            // Only the last line can be incomplete, there is nothing left to replay.
+            Err(why) => {
+                warn!("ignoring truncated indexeddb journal line: {}", why);
+                break;
+            },
+        }
+    }
+    lines
+}
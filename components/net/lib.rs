@@ -19,18 +19,30 @@ extern crate serde;
 #[macro_use]
 extern crate servo_config;
 
+pub mod cache_storage_thread;
 pub mod connector;
 pub mod cookie;
+mod cookie_journal;
 pub mod cookie_storage;
 mod data_loader;
 mod decoder;
+pub mod download_manager;
 pub mod filemanager_thread;
+mod fs_util;
 mod hosts;
 pub mod hsts;
+mod http_auth;
 pub mod http_cache;
+mod http_cache_disk;
 pub mod http_loader;
 pub mod image_cache;
+pub mod indexeddb {
+    mod engine;
+    pub mod idb_thread;
+    mod journal;
+}
 pub mod mime_classifier;
+pub mod proxy;
 pub mod resource_thread;
 mod storage_thread;
 pub mod subresource_integrity;
@@ -4,24 +4,31 @@
 
 //! A thread that takes a URL and streams back the binary data.
 
+use crate::cache_storage_thread::CacheStorageThreadFactory;
 use crate::connector::{create_http_client, create_ssl_connector_builder};
 use crate::cookie;
 use crate::cookie_storage::CookieStorage;
+use crate::download_manager::DownloadManager;
 use crate::fetch::cors_cache::CorsCache;
 use crate::fetch::methods::{fetch, CancellationListener, FetchContext};
 use crate::filemanager_thread::FileManager;
 use crate::hsts::HstsList;
 use crate::http_cache::HttpCache;
-use crate::http_loader::{http_redirect_fetch, HttpState, HANDLE};
+use crate::http_loader::{http_redirect_fetch, wait_for_credentials, CredentialsPrompt};
+use crate::http_loader::{HttpState, HANDLE};
+use crate::indexeddb::idb_thread::IndexedDBThreadFactory;
+use crate::proxy::ProxyConfig;
 use crate::storage_thread::StorageThreadFactory;
 use crate::websocket_loader;
 use crossbeam_channel::Sender;
 use devtools_traits::DevtoolsControlMsg;
 use embedder_traits::resources::{self, Resource};
-use embedder_traits::EmbedderProxy;
+use embedder_traits::{DownloadId, EmbedderProxy};
 use hyper_serde::Serde;
 use ipc_channel::ipc::{self, IpcReceiver, IpcReceiverSet, IpcSender};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use net_traits::cache_storage_thread::CacheStorageThreadMsg;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
 use net_traits::request::{Destination, RequestBuilder};
 use net_traits::response::{Response, ResponseInit};
 use net_traits::storage_thread::StorageThreadMsg;
@@ -34,9 +41,9 @@ use profile_traits::mem::ProfilerChan as MemProfilerChan;
 use profile_traits::mem::{Report, ReportKind, ReportsChan};
 use profile_traits::time::ProfilerChan;
 use serde::{Deserialize, Serialize};
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
 use std::borrow::{Cow, ToOwned};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs::{self, File};
 use std::io::prelude::*;
@@ -64,13 +71,30 @@ pub fn new_resource_threads(
         config_dir.clone(),
         certificate_path,
     );
-    let storage: IpcSender<StorageThreadMsg> = StorageThreadFactory::new(config_dir);
+    let storage: IpcSender<StorageThreadMsg> = StorageThreadFactory::new(config_dir.clone());
+    let idb: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(config_dir.clone());
+    let caches: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(config_dir);
+    // Private browsing never touches the disk.
+    let private_storage: IpcSender<StorageThreadMsg> = StorageThreadFactory::new(None);
+    let private_idb: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let private_caches: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None);
     (
-        ResourceThreads::new(public_core, storage.clone()),
-        ResourceThreads::new(private_core, storage),
+        ResourceThreads::new(public_core, storage, idb, caches),
+        ResourceThreads::new(private_core, private_storage, private_idb, private_caches),
     )
 }
 
+/// Create the threads of a new ephemeral session, which never touch the disk.
+/// The messages sent to its core thread are handled by a resource thread once the
+/// returned receiver is handed to it with `CoreResourceMsg::NewEphemeralSession`.
+pub fn new_ephemeral_resource_threads() -> (ResourceThreads, IpcReceiver<CoreResourceMsg>) {
+    let (core, core_port) = ipc::channel().unwrap();
+    let storage: IpcSender<StorageThreadMsg> = StorageThreadFactory::new(None);
+    let idb: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let caches: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None);
+    (ResourceThreads::new(core, storage, idb, caches), core_port)
+}
+
 /// Create a CoreResourceThread
 pub fn new_core_resource_thread(
     user_agent: Cow<'static, str>,
@@ -84,6 +108,7 @@ pub fn new_core_resource_thread(
     let (public_setup_chan, public_setup_port) = ipc::channel().unwrap();
     let (private_setup_chan, private_setup_port) = ipc::channel().unwrap();
     let (report_chan, report_port) = ipc::channel().unwrap();
+    let (ephemeral_chan, ephemeral_port) = ipc::channel().unwrap();
 
     thread::Builder::new()
         .name("ResourceManager".to_owned())
@@ -92,7 +117,7 @@ pub fn new_core_resource_thread(
                 user_agent,
                 devtools_chan,
                 time_profiler_chan,
-                embedder_proxy,
+                embedder_proxy.clone(),
                 certificate_path.clone(),
             );
 
@@ -100,10 +125,21 @@ pub fn new_core_resource_thread(
                 resource_manager,
                 config_dir,
                 certificate_path,
+                embedder_proxy,
+                ephemeral_sessions: HashMap::new(),
+                next_ephemeral_session_id: 0,
+                ephemeral_chan,
             };
 
             mem_profiler_chan.run_with_memory_reporting(
-                || (channel_manager.start(public_setup_port, private_setup_port, report_port)),
+                || {
+                    channel_manager.start(
+                        public_setup_port,
+                        private_setup_port,
+                        ephemeral_port,
+                        report_port,
+                    )
+                },
                 String::from("network-cache-reporter"),
                 report_chan,
                 |report_chan| report_chan,
@@ -117,39 +153,74 @@ struct ResourceChannelManager {
     resource_manager: CoreResourceManager,
     config_dir: Option<PathBuf>,
     certificate_path: Option<String>,
+    embedder_proxy: EmbedderProxy,
+    /// The state of every live ephemeral session, keyed by the id of the session.
+    ephemeral_sessions: HashMap<u64, Arc<HttpState>>,
+    next_ephemeral_session_id: u64,
+    /// The channel on which the messages of every ephemeral session are forwarded,
+    /// along with the id of their session.
+    ephemeral_chan: IpcSender<(u64, CoreResourceMsg)>,
+}
+
+fn read_certificates(certificate_path: Option<String>) -> String {
+    match certificate_path {
+        Some(ref path) => fs::read_to_string(path).expect("Couldn't not find certificate file"),
+        None => resources::read_string(Resource::SSLCertificates),
+    }
+}
+
+/// Create the state of a private or ephemeral session, which is only ever kept in memory.
+fn create_ephemeral_http_state(certs: &str) -> HttpState {
+    let mut http_state = HttpState::new(create_ssl_connector_builder(certs));
+    http_state.hsts_list = RwLock::new(HstsList::from_servo_preload());
+    http_state
 }
 
 fn create_http_states(
     config_dir: Option<&Path>,
     certificate_path: Option<String>,
+    embedder_proxy: EmbedderProxy,
 ) -> (Arc<HttpState>, Arc<HttpState>) {
     let mut hsts_list = HstsList::from_servo_preload();
     let mut auth_cache = AuthCache::new();
-    let http_cache = HttpCache::new();
-    let mut cookie_jar = CookieStorage::new(150);
+    let http_cache = match config_dir {
+        Some(config_dir) if pref!(network.http_cache.disk.enabled) => HttpCache::new_persistent(
+            config_dir.join("http_cache"),
+            pref!(network.http_cache.disk.max_size) as u64,
+        ),
+        _ => HttpCache::new(),
+    };
+    let mut cookie_jar = match config_dir {
+        Some(config_dir) => CookieStorage::new_persistent(150, config_dir),
+        None => CookieStorage::new(150),
+    };
+    cookie_jar.set_embedder_proxy(embedder_proxy);
     if let Some(config_dir) = config_dir {
         read_json_from_file(&mut auth_cache, config_dir, "auth_cache.json");
         read_json_from_file(&mut hsts_list, config_dir, "hsts_list.json");
-        read_json_from_file(&mut cookie_jar, config_dir, "cookie_jar.json");
     }
 
-    let certs = match certificate_path {
-        Some(ref path) => fs::read_to_string(path).expect("Couldn't not find certificate file"),
-        None => resources::read_string(Resource::SSLCertificates),
-    };
+    let certs = read_certificates(certificate_path);
 
     let ssl_connector_builder = create_ssl_connector_builder(&certs);
+    let auth_cache = Arc::new(RwLock::new(auth_cache));
+    let proxy_config = ProxyConfig::from_prefs();
     let http_state = HttpState {
         cookie_jar: RwLock::new(cookie_jar),
-        auth_cache: RwLock::new(auth_cache),
+        auth_cache: auth_cache.clone(),
         http_cache: RwLock::new(http_cache),
         hsts_list: RwLock::new(hsts_list),
         history_states: RwLock::new(HashMap::new()),
-        client: create_http_client(ssl_connector_builder, HANDLE.lock().unwrap().executor()),
+        proxy_config: proxy_config.clone(),
+        client: create_http_client(
+            ssl_connector_builder,
+            proxy_config,
+            auth_cache,
+            HANDLE.lock().unwrap().executor(),
+        ),
     };
 
-    let private_ssl_client = create_ssl_connector_builder(&certs);
-    let private_http_state = HttpState::new(private_ssl_client);
+    let private_http_state = create_ephemeral_http_state(&certs);
 
     (Arc::new(http_state), Arc::new(private_http_state))
 }
@@ -160,16 +231,19 @@ impl ResourceChannelManager {
         &mut self,
         public_receiver: IpcReceiver<CoreResourceMsg>,
         private_receiver: IpcReceiver<CoreResourceMsg>,
+        ephemeral_receiver: IpcReceiver<(u64, CoreResourceMsg)>,
         memory_reporter: IpcReceiver<ReportsChan>,
     ) {
         let (public_http_state, private_http_state) = create_http_states(
             self.config_dir.as_ref().map(Deref::deref),
             self.certificate_path.clone(),
+            self.embedder_proxy.clone(),
         );
 
         let mut rx_set = IpcReceiverSet::new().unwrap();
         let private_id = rx_set.add(private_receiver).unwrap();
         let public_id = rx_set.add(public_receiver).unwrap();
+        let ephemeral_id = rx_set.add(ephemeral_receiver).unwrap();
         let reporter_id = rx_set.add(memory_reporter).unwrap();
 
         loop {
@@ -186,11 +260,10 @@ impl ResourceChannelManager {
                         self.process_report(msg, &private_http_state, &public_http_state);
                         continue;
                     }
-                } else {
+                } else if id == private_id || id == public_id {
                     let group = if id == private_id {
                         &private_http_state
                     } else {
-                        assert_eq!(id, public_id);
                         &public_http_state
                     };
                     if let Ok(msg) = data.to() {
@@ -198,11 +271,60 @@ impl ResourceChannelManager {
                             return;
                         }
                     }
+                } else if id == ephemeral_id {
+                    let (session_id, msg) = match data.to() {
+                        Ok(data) => data,
+                        Err(_) => continue,
+                    };
+                    let group = match self.ephemeral_sessions.get(&session_id) {
+                        Some(group) => group.clone(),
+                        None => continue,
+                    };
+                    match msg {
+                        CoreResourceMsg::Exit(sender) => {
+                            // Dropping the state of the session is all there is to do.
+                            self.ephemeral_sessions.remove(&session_id);
+                            let _ = sender.send(());
+                        },
+                        msg => {
+                            self.process_msg(msg, &group);
+                        },
+                    }
                 }
             }
         }
     }
 
+    /// Handle the messages of a new ephemeral session, received on `receiver`.
+    ///
+    /// A thread forwards them to the resource thread until the session exits, so that
+    /// its receiver is dropped then rather than kept in the set of receivers for good.
+    fn new_ephemeral_session(&mut self, receiver: IpcReceiver<CoreResourceMsg>) {
+        let session_id = self.next_ephemeral_session_id;
+        self.next_ephemeral_session_id += 1;
+        let certs = read_certificates(self.certificate_path.clone());
+        self.ephemeral_sessions
+            .insert(session_id, Arc::new(create_ephemeral_http_state(&certs)));
+        let ephemeral_chan = self.ephemeral_chan.clone();
+        thread::Builder::new()
+            .name("EphemeralSession".to_owned())
+            .spawn(move || loop {
+                let msg = match receiver.recv() {
+                    Ok(msg) => msg,
+                    // Every sender is gone, so the session can't be used anymore.
+                    Err(_) => CoreResourceMsg::Exit(ipc::channel().unwrap().0),
+                };
+                let is_exit = match msg {
+                    CoreResourceMsg::Exit(..) => true,
+                    _ => false,
+                };
+                if ephemeral_chan.send((session_id, msg)).is_err() || is_exit {
+                    break;
+                }
+            })
+            .expect("Thread spawning failed");
+    }
+
     fn process_report(
         &mut self,
         msg: ReportsChan,
@@ -254,6 +376,22 @@ impl ResourceChannelManager {
                     .clear_storage(&request);
                 return true;
             },
+            CoreResourceMsg::GetCookiesForDomain(domain, consumer) => {
+                let cookie_jar = http_state.cookie_jar.read().unwrap();
+                let cookies = cookie_jar
+                    .cookies_for_domain(domain.as_ref().map(String::as_str))
+                    .into_iter()
+                    .map(Serde)
+                    .collect();
+                consumer.send(cookies).unwrap();
+            },
+            CoreResourceMsg::RemoveCookiesForDomain(domain) => {
+                http_state
+                    .cookie_jar
+                    .write()
+                    .unwrap()
+                    .remove_cookies_for_domain(domain.as_ref().map(String::as_str));
+            },
             CoreResourceMsg::FetchRedirect(req_init, res_init, sender, cancel_chan) => self
                 .resource_manager
                 .fetch(req_init, Some(res_init), sender, http_state, cancel_chan),
@@ -279,6 +417,9 @@ impl ResourceChannelManager {
             CoreResourceMsg::NetworkMediator(mediator_chan) => {
                 self.resource_manager.swmanager_chan = Some(mediator_chan)
             },
+            CoreResourceMsg::ServiceWorkerOrigins(origins) => {
+                self.resource_manager.service_worker_origins = origins
+            },
             CoreResourceMsg::GetCookiesDataForUrl(url, consumer, source) => {
                 let mut cookie_jar = http_state.cookie_jar.write().unwrap();
                 let cookies = cookie_jar
@@ -303,6 +444,20 @@ impl ResourceChannelManager {
                     history_states.remove(&history_state);
                 }
             },
+            CoreResourceMsg::NewEphemeralSession(receiver) => {
+                self.new_ephemeral_session(receiver);
+            },
+            CoreResourceMsg::Download(request, suggested_name) => {
+                let id = self.resource_manager.downloads.add(
+                    request,
+                    suggested_name,
+                    http_state.clone(),
+                );
+                self.resource_manager.run_download(id);
+            },
+            CoreResourceMsg::PauseDownload(id) => self.resource_manager.downloads.pause(id),
+            CoreResourceMsg::ResumeDownload(id) => self.resource_manager.run_download(id),
+            CoreResourceMsg::CancelDownload(id) => self.resource_manager.downloads.cancel(id),
             CoreResourceMsg::Synchronize(sender) => {
                 let _ = sender.send(());
             },
@@ -315,8 +470,8 @@ impl ResourceChannelManager {
                         },
                         Err(_) => warn!("Error writing auth cache to disk"),
                     }
-                    match http_state.cookie_jar.read() {
-                        Ok(jar) => write_json_to_file(&*jar, config_dir, "cookie_jar.json"),
+                    match http_state.cookie_jar.write() {
+                        Ok(mut jar) => jar.flush(),
                         Err(_) => warn!("Error writing cookie jar to disk"),
                     }
                     match http_state.hsts_list.read() {
@@ -401,6 +556,9 @@ impl AuthCache {
         AuthCache {
             version: 1,
             entries: HashMap::new(),
+            realms: HashMap::new(),
+            proxy_entries: HashMap::new(),
+            proxy_realms: HashMap::new(),
         }
     }
 }
@@ -408,14 +566,65 @@ impl AuthCache {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AuthCache {
     pub version: u32,
+    /// The Basic credentials sent up front to each origin, keyed by its ASCII serialization.
     pub entries: HashMap<String, AuthCacheEntry>,
+    /// The credentials accepted for each realm of each origin.
+    #[serde(default)]
+    pub realms: HashMap<String, HashMap<String, AuthCacheEntry>>,
+    /// The Basic credentials sent up front to each proxy, keyed by its host and port.
+    #[serde(default)]
+    pub proxy_entries: HashMap<String, AuthCacheEntry>,
+    /// The credentials accepted for each realm of each proxy.
+    #[serde(default)]
+    pub proxy_realms: HashMap<String, HashMap<String, AuthCacheEntry>>,
+}
+
+fn timing_type(destination: Destination) -> ResourceTimingType {
+    match destination {
+        Destination::Document | Destination::IFrame => ResourceTimingType::Navigation,
+        _ => ResourceTimingType::Resource,
+    }
+}
+
+/// Run a fetch on the fetch pool. A fetch for which the user has to be asked for
+/// credentials stops rather than holding a thread of the pool until the user answers,
+/// and is then started again with the answer, on a thread of its own.
+fn spawn_fetch<F>(fetch_pool: &rayon::ThreadPool, context: FetchContext, mut run: F)
+where
+    F: FnMut(&FetchContext) + Send + 'static,
+{
+    fetch_pool.spawn(move || {
+        run(&context);
+        let (top_level_browsing_context_id, request) = match context.deferred_credentials_request()
+        {
+            Some(deferred) => deferred,
+            None => return,
+        };
+        thread::Builder::new()
+            .name("FetchCredentials".to_owned())
+            .spawn(move || {
+                let credentials = wait_for_credentials(
+                    &context.embedder_proxy,
+                    top_level_browsing_context_id,
+                    request.clone(),
+                );
+                *context.credentials_prompt.lock().unwrap() =
+                    CredentialsPrompt::Answered(request, credentials);
+                run(&context);
+            })
+            .expect("Thread spawning failed");
+    });
 }
 
 pub struct CoreResourceManager {
     user_agent: Cow<'static, str>,
     devtools_chan: Option<Sender<DevtoolsControlMsg>>,
     swmanager_chan: Option<IpcSender<CustomResponseMediator>>,
+    /// The origins with an activated service worker.
+    service_worker_origins: HashSet<ImmutableOrigin>,
     filemanager: FileManager,
+    embedder_proxy: EmbedderProxy,
+    downloads: DownloadManager,
     fetch_pool: rayon::ThreadPool,
     certificate_path: Option<String>,
 }
@@ -436,7 +645,10 @@ impl CoreResourceManager {
             user_agent: user_agent,
             devtools_chan: devtools_channel,
             swmanager_chan: None,
-            filemanager: FileManager::new(embedder_proxy),
+            service_worker_origins: HashSet::new(),
+            filemanager: FileManager::new(embedder_proxy.clone()),
+            downloads: DownloadManager::new(embedder_proxy.clone()),
+            embedder_proxy: embedder_proxy,
             fetch_pool: pool,
             certificate_path,
         }
@@ -467,28 +679,40 @@ impl CoreResourceManager {
         let ua = self.user_agent.clone();
         let dc = self.devtools_chan.clone();
         let filemanager = self.filemanager.clone();
-
-        let timing_type = match request_builder.destination {
-            Destination::Document => ResourceTimingType::Navigation,
-            _ => ResourceTimingType::Resource,
+        let embedder_proxy = self.embedder_proxy.clone();
+        let timing_type = timing_type(request_builder.destination);
+
+        // Don't ask the service worker manager about requests no service worker can
+        // handle: a navigation to an origin, or a subresource request of a document of
+        // an origin, without any activated service worker.
+        let origin = match timing_type {
+            ResourceTimingType::Navigation => request_builder.url.origin(),
+            _ => request_builder.origin.clone(),
+        };
+        let swmanager_chan = if self.service_worker_origins.contains(&origin) {
+            self.swmanager_chan.clone()
+        } else {
+            None
         };
 
-        self.fetch_pool.spawn(move || {
-            let mut request = request_builder.build();
-            // XXXManishearth: Check origin against pipeline id (also ensure that the mode is allowed)
-            // todo load context / mimesniff in fetch
-            // todo referrer policy?
-            // todo service worker stuff
-            let context = FetchContext {
-                state: http_state,
-                user_agent: ua,
-                devtools_chan: dc,
-                filemanager: filemanager,
-                cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(cancel_chan))),
-                timing: Arc::new(Mutex::new(ResourceFetchTiming::new(request.timing_type()))),
-            };
+        // XXXManishearth: Check origin against pipeline id (also ensure that the mode is allowed)
+        // todo load context / mimesniff in fetch
+        // todo referrer policy?
+        let context = FetchContext {
+            state: http_state,
+            user_agent: ua,
+            devtools_chan: dc,
+            filemanager: filemanager,
+            embedder_proxy: embedder_proxy,
+            swmanager_chan: swmanager_chan,
+            cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(cancel_chan))),
+            timing: Arc::new(Mutex::new(ResourceFetchTiming::new(timing_type))),
+            credentials_prompt: Arc::new(Mutex::new(CredentialsPrompt::Defer(None))),
+        };
 
-            match res_init_ {
+        spawn_fetch(&self.fetch_pool, context, move |context| {
+            let mut request = request_builder.clone().build();
+            match res_init_.clone() {
                 Some(res_init) => {
                     let response = Response::from_init(res_init, timing_type);
                     http_redirect_fetch(
@@ -498,14 +722,50 @@ impl CoreResourceManager {
                         true,
                         &mut sender,
                         &mut None,
-                        &context,
+                        context,
                     );
                 },
-                None => fetch(&mut request, &mut sender, &context),
+                None => fetch(&mut request, &mut sender, context),
             };
         });
     }
 
+    /// Fetch what is left of the download `id`, unless it is being fetched already.
+    fn run_download(&self, id: DownloadId) {
+        let download = match self.downloads.start_fetch(id) {
+            Some(download) => download,
+            None => return,
+        };
+        let ua = self.user_agent.clone();
+        let dc = self.devtools_chan.clone();
+        let filemanager = self.filemanager.clone();
+        let embedder_proxy = self.embedder_proxy.clone();
+
+        let context = FetchContext {
+            state: download.http_state,
+            user_agent: ua,
+            devtools_chan: dc,
+            filemanager: filemanager,
+            embedder_proxy: embedder_proxy,
+            // Downloads are never handled by service workers.
+            swmanager_chan: None,
+            cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(Some(
+                download.cancel_chan,
+            )))),
+            timing: Arc::new(Mutex::new(ResourceFetchTiming::new(timing_type(
+                download.request.destination,
+            )))),
+            credentials_prompt: Arc::new(Mutex::new(CredentialsPrompt::Defer(None))),
+        };
+
+        let request_builder = download.request;
+        let mut target = download.target;
+        spawn_fetch(&self.fetch_pool, context, move |context| {
+            let mut request = request_builder.clone().build();
+            fetch(&mut request, &mut target, context);
+        });
+    }
+
     fn websocket_connect(
         &self,
         request: RequestBuilder,
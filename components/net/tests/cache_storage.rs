@@ -0,0 +1,257 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::TempDir;
+use http::header::{self, HeaderMap, HeaderValue};
+use http::Method;
+use ipc_channel::ipc::{self, IpcSender};
+use net::cache_storage_thread::CacheStorageThreadFactory;
+use net_traits::cache_storage_thread::{CacheId, CacheQueryOptions, CacheStorageThreadMsg};
+use net_traits::cache_storage_thread::{CachedRequest, CachedResponse};
+use servo_url::{ImmutableOrigin, ServoUrl};
+
+fn origin() -> ImmutableOrigin {
+    ServoUrl::parse("https://example.com").unwrap().origin()
+}
+
+fn request(url: &str) -> CachedRequest {
+    CachedRequest {
+        url: ServoUrl::parse(url).unwrap(),
+        method: Method::GET,
+        headers: HeaderMap::new(),
+    }
+}
+
+fn response(body: &str) -> CachedResponse {
+    CachedResponse {
+        url: None,
+        status: (200, b"OK".to_vec()),
+        headers: HeaderMap::new(),
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn open(thread: &IpcSender<CacheStorageThreadMsg>, name: &str) -> CacheId {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Open(
+            sender,
+            origin(),
+            name.to_owned(),
+        ))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn put(
+    thread: &IpcSender<CacheStorageThreadMsg>,
+    id: CacheId,
+    entries: Vec<(CachedRequest, CachedResponse)>,
+) {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Put(sender, id, entries))
+        .unwrap();
+    receiver.recv().unwrap();
+}
+
+fn match_all(
+    thread: &IpcSender<CacheStorageThreadMsg>,
+    id: CacheId,
+    request: Option<CachedRequest>,
+    options: CacheQueryOptions,
+) -> Vec<String> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::MatchAll(
+            sender, id, request, options,
+        ))
+        .unwrap();
+    receiver
+        .recv()
+        .unwrap()
+        .into_iter()
+        .map(|response| String::from_utf8(response.body).unwrap())
+        .collect()
+}
+
+fn keys(thread: &IpcSender<CacheStorageThreadMsg>) -> Vec<String> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Keys(sender, origin()))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn exit(thread: &IpcSender<CacheStorageThreadMsg>) {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread.send(CacheStorageThreadMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
+#[test]
+fn test_open_and_delete_caches() {
+    let thread: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None);
+    let first = open(&thread, "first");
+    let second = open(&thread, "second");
+    assert_ne!(first, second);
+    assert_eq!(open(&thread, "first"), first);
+    assert_eq!(keys(&thread), vec!["first", "second"]);
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Delete(
+            sender,
+            origin(),
+            "first".to_owned(),
+        ))
+        .unwrap();
+    assert!(receiver.recv().unwrap());
+    assert_eq!(keys(&thread), vec!["second"]);
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Has(
+            sender,
+            origin(),
+            "first".to_owned(),
+        ))
+        .unwrap();
+    assert!(!receiver.recv().unwrap());
+
+    // Caches of other origins are kept apart.
+    let (sender, receiver) = ipc::channel().unwrap();
+    let other_origin = ServoUrl::parse("https://example.org").unwrap().origin();
+    thread
+        .send(CacheStorageThreadMsg::Keys(sender, other_origin))
+        .unwrap();
+    assert!(receiver.recv().unwrap().is_empty());
+    exit(&thread);
+}
+
+#[test]
+fn test_put_replaces_matching_entries() {
+    let thread: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None);
+    let id = open(&thread, "cache");
+    put(
+        &thread,
+        id,
+        vec![
+            (request("https://example.com/a?v=1"), response("a1")),
+            (request("https://example.com/b"), response("b")),
+        ],
+    );
+    put(
+        &thread,
+        id,
+        vec![(request("https://example.com/a?v=1"), response("a2"))],
+    );
+
+    let options = CacheQueryOptions::default();
+    let a = Some(request("https://example.com/a?v=1"));
+    assert_eq!(match_all(&thread, id, a, options), vec!["a2"]);
+    assert_eq!(match_all(&thread, id, None, options), vec!["b", "a2"]);
+
+    let other_query = Some(request("https://example.com/a?v=2"));
+    assert!(match_all(&thread, id, other_query.clone(), options).is_empty());
+    let ignore_search = CacheQueryOptions {
+        ignore_search: true,
+        ..options
+    };
+    assert_eq!(
+        match_all(&thread, id, other_query, ignore_search),
+        vec!["a2"]
+    );
+
+    let mut post = request("https://example.com/b");
+    post.method = Method::POST;
+    assert!(match_all(&thread, id, Some(post.clone()), options).is_empty());
+    let ignore_method = CacheQueryOptions {
+        ignore_method: true,
+        ..options
+    };
+    assert_eq!(match_all(&thread, id, Some(post), ignore_method), vec!["b"]);
+    exit(&thread);
+}
+
+#[test]
+fn test_match_respects_vary() {
+    let thread: IpcSender<CacheStorageThreadMsg> = CacheStorageThreadFactory::new(None);
+    let id = open(&thread, "cache");
+    let mut english = request("https://example.com/page");
+    english
+        .headers
+        .insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static("en"));
+    let mut varying = response("english");
+    varying
+        .headers
+        .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+    put(&thread, id, vec![(english.clone(), varying)]);
+
+    let mut french = request("https://example.com/page");
+    french
+        .headers
+        .insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static("fr"));
+    let options = CacheQueryOptions::default();
+    assert_eq!(
+        match_all(&thread, id, Some(english), options),
+        vec!["english"]
+    );
+    assert!(match_all(&thread, id, Some(french.clone()), options).is_empty());
+    let ignore_vary = CacheQueryOptions {
+        ignore_vary: true,
+        ..options
+    };
+    assert_eq!(
+        match_all(&thread, id, Some(french), ignore_vary),
+        vec!["english"]
+    );
+
+    // Looking a request up in every cache of the origin finds nothing if none match.
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Match(
+            sender,
+            origin(),
+            None,
+            request("https://example.com/missing"),
+            options,
+        ))
+        .unwrap();
+    assert!(receiver.recv().unwrap().is_none());
+    exit(&thread);
+}
+
+#[test]
+fn test_caches_persist_across_restarts() {
+    let config_dir = TempDir::new("cache-storage");
+    {
+        let thread: IpcSender<CacheStorageThreadMsg> =
+            CacheStorageThreadFactory::new(Some(config_dir.path().to_owned()));
+        let id = open(&thread, "offline");
+        put(
+            &thread,
+            id,
+            vec![(request("https://example.com/"), response("home"))],
+        );
+        exit(&thread);
+    }
+
+    let thread: IpcSender<CacheStorageThreadMsg> =
+        CacheStorageThreadFactory::new(Some(config_dir.path().to_owned()));
+    assert_eq!(keys(&thread), vec!["offline"]);
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(CacheStorageThreadMsg::Match(
+            sender,
+            origin(),
+            Some("offline".to_owned()),
+            request("https://example.com/"),
+            CacheQueryOptions::default(),
+        ))
+        .unwrap();
+    let response = receiver.recv().unwrap().unwrap();
+    assert_eq!(response.body, b"home");
+    exit(&thread);
+}
@@ -2,10 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::TempDir;
 use net::cookie::Cookie;
 use net::cookie_storage::CookieStorage;
 use net_traits::CookieSource;
 use servo_url::ServoUrl;
+use std::fs::OpenOptions;
+use std::io::Write;
 
 #[test]
 fn test_domain_match() {
@@ -475,3 +478,68 @@ fn test_cookie_eviction_all_nonsecure_new_nonsecure() {
         "extra2=bar; extra3=bar; extra4=bar; extra5=bar; foo=bar"
     );
 }
+
+fn stored_cookies(storage: &CookieStorage) -> Vec<String> {
+    let mut cookies: Vec<String> = storage
+        .cookies_for_domain(None)
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+        .collect();
+    cookies.sort();
+    cookies
+}
+
+#[test]
+fn test_persistent_storage_survives_crash() {
+    let config_dir = TempDir::new("cookies");
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    {
+        // Never flushed, as if the process had crashed.
+        let mut storage = CookieStorage::new_persistent(5, config_dir.path());
+        add_cookie_to_storage(&mut storage, &url, "foo=bar");
+        add_cookie_to_storage(&mut storage, &url, "baz=qux");
+        add_cookie_to_storage(&mut storage, &url, "foo=changed");
+    }
+    // Simulate a crash in the middle of writing an entry.
+    let mut journal = OpenOptions::new()
+        .append(true)
+        .open(config_dir.path().join("cookie_jar.journal"))
+        .unwrap();
+    journal.write_all(b"{\"Set\":{\"cook").unwrap();
+
+    let mut storage = CookieStorage::new_persistent(5, config_dir.path());
+    assert_eq!(stored_cookies(&storage), vec!["baz=qux", "foo=changed"]);
+    // The jar was written to a new snapshot, so the incomplete entry is gone.
+    add_cookie_to_storage(&mut storage, &url, "new=cookie");
+    let storage = CookieStorage::new_persistent(5, config_dir.path());
+    assert_eq!(
+        stored_cookies(&storage),
+        vec!["baz=qux", "foo=changed", "new=cookie"]
+    );
+}
+
+#[test]
+fn test_cookies_for_domain() {
+    let mut storage = CookieStorage::new(5);
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let sub_url = ServoUrl::parse("https://sub.example.com/").unwrap();
+    let other_url = ServoUrl::parse("https://example.org/").unwrap();
+    add_cookie_to_storage(&mut storage, &url, "foo=bar");
+    add_cookie_to_storage(&mut storage, &sub_url, "baz=qux");
+    add_cookie_to_storage(&mut storage, &other_url, "other=cookie");
+
+    let mut names: Vec<String> = storage
+        .cookies_for_domain(Some("example.com"))
+        .iter()
+        .map(|cookie| cookie.name().to_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["baz", "foo"]);
+    assert_eq!(storage.cookies_for_domain(Some("sub.example.com")).len(), 1);
+    assert_eq!(storage.cookies_for_domain(None).len(), 3);
+
+    storage.remove_cookies_for_domain(Some("sub.example.com"));
+    assert_eq!(storage.cookies_for_domain(Some("example.com")).len(), 1);
+    storage.remove_cookies_for_domain(None);
+    assert!(storage.cookies_for_domain(None).is_empty());
+}
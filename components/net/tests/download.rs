@@ -0,0 +1,190 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::create_embedder_proxy;
+use crate::make_server;
+use crate::new_fetch_context;
+use crate::TempDir;
+use crossbeam_channel::{unbounded, Receiver};
+use embedder_traits::{DownloadId, DownloadOutcome, EmbedderMsg};
+use headers::{ContentLength, HeaderMapExt};
+use http::header::{self, HeaderValue};
+use http::StatusCode;
+use hyper::body::Body;
+use hyper::{Request as HyperRequest, Response as HyperResponse};
+use net::download_manager::DownloadManager;
+use net::fetch::methods::{self, FetchContext};
+use net_traits::request::RequestBuilder;
+use net_traits::response::Response;
+use net_traits::{FetchTaskTarget, ResourceFetchTiming, ResourceTimingType};
+use servo_url::ServoUrl;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+/// A download manager saving to `directory`, and a receiver for its reports to
+/// the embedder.
+fn new_download_manager(
+    directory: &TempDir,
+) -> (DownloadManager, FetchContext, Receiver<EmbedderMsg>) {
+    let (embedder_sender, embedder_receiver) = unbounded();
+    let mut embedder_proxy = create_embedder_proxy();
+    embedder_proxy.sender = embedder_sender;
+    let (sender, receiver) = unbounded();
+    thread::spawn(move || {
+        while let Ok((_, msg)) = embedder_receiver.recv() {
+            let _ = sender.send(msg);
+        }
+    });
+    (
+        DownloadManager::with_directory(embedder_proxy.clone(), directory.path().to_owned()),
+        new_fetch_context(None, Some(embedder_proxy)),
+        receiver,
+    )
+}
+
+fn expect_started(receiver: &Receiver<EmbedderMsg>, id: DownloadId) -> PathBuf {
+    match receiver.recv().unwrap() {
+        EmbedderMsg::DownloadStarted(started, _, path) => {
+            assert_eq!(started, id);
+            path
+        },
+        msg => panic!("Unexpected embedder message {:?}", msg),
+    }
+}
+
+fn expect_finished(receiver: &Receiver<EmbedderMsg>, id: DownloadId) -> DownloadOutcome {
+    loop {
+        match receiver.recv().unwrap() {
+            EmbedderMsg::DownloadProgress(..) => {},
+            EmbedderMsg::DownloadFinished(finished, outcome) => {
+                assert_eq!(finished, id);
+                return outcome;
+            },
+            msg => panic!("Unexpected embedder message {:?}", msg),
+        }
+    }
+}
+
+/// A response as the first fetch of a resource of `length` bytes would receive it.
+fn partial_response(url: ServoUrl, length: u64) -> Response {
+    let mut response = Response::new(
+        url,
+        ResourceFetchTiming::new(ResourceTimingType::Navigation),
+    );
+    response
+        .headers
+        .insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+    response.headers.typed_insert(ContentLength(length));
+    response
+}
+
+#[test]
+fn test_download_is_named_after_content_disposition() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        response.headers_mut().insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"../report for download test.txt\""),
+        );
+        *response.body_mut() = b"Yay!".to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let directory = TempDir::new("download");
+    let (downloads, context, receiver) = new_download_manager(&directory);
+    let id = downloads.add(
+        RequestBuilder::new(url.clone()).origin(url.origin()),
+        Some("suggested.txt".to_owned()),
+        context.state.clone(),
+    );
+    let mut download = downloads.start_fetch(id).unwrap();
+    methods::fetch(
+        &mut download.request.build(),
+        &mut download.target,
+        &context,
+    );
+    let _ = server.close();
+
+    let path = expect_started(&receiver, id);
+    assert_eq!(path.file_name().unwrap(), "report for download test.txt");
+    assert_eq!(expect_finished(&receiver, id), DownloadOutcome::Complete);
+    assert_eq!(fs::read(&path).unwrap(), b"Yay!");
+}
+
+#[test]
+fn test_resumed_download_requests_the_rest_of_the_resource() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        assert_eq!(
+            request.headers().get(header::RANGE),
+            Some(&HeaderValue::from_static("bytes=5-"))
+        );
+        assert_eq!(
+            request.headers().get(header::IF_RANGE),
+            Some(&HeaderValue::from_static("\"v1\""))
+        );
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        response.headers_mut().insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_static("bytes 5-9/10"),
+        );
+        *response.body_mut() = b"56789".to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+    let url = url.join("/resumed%20download%20test.bin").unwrap();
+
+    let directory = TempDir::new("download");
+    let (downloads, context, receiver) = new_download_manager(&directory);
+    let id = downloads.add(
+        RequestBuilder::new(url.clone()).origin(url.origin()),
+        None,
+        context.state.clone(),
+    );
+
+    // The first fetch is stopped halfway through the resource.
+    let mut first = downloads.start_fetch(id).unwrap();
+    assert!(downloads.start_fetch(id).is_none());
+    first
+        .target
+        .process_response(&partial_response(url.clone(), 10));
+    first.target.process_response_chunk(b"01234".to_vec());
+    downloads.pause(id);
+    first.target.process_response_chunk(b"junk".to_vec());
+    let path = expect_started(&receiver, id);
+    assert_eq!(path.file_name().unwrap(), "resumed download test.bin");
+
+    let mut second = downloads.start_fetch(id).unwrap();
+    methods::fetch(&mut second.request.build(), &mut second.target, &context);
+    let _ = server.close();
+
+    assert_eq!(expect_finished(&receiver, id), DownloadOutcome::Complete);
+    assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+}
+
+#[test]
+fn test_cancelled_download_removes_the_partial_file() {
+    let url = ServoUrl::parse("http://localhost/cancelled%20download%20test.bin").unwrap();
+
+    let directory = TempDir::new("download");
+    let (downloads, context, receiver) = new_download_manager(&directory);
+    let id = downloads.add(
+        RequestBuilder::new(url.clone()).origin(url.origin()),
+        None,
+        context.state.clone(),
+    );
+    let mut download = downloads.start_fetch(id).unwrap();
+    download.target.process_response(&partial_response(url, 10));
+    download.target.process_response_chunk(b"01234".to_vec());
+    let path = expect_started(&receiver, id);
+    assert!(path
+        .with_file_name("cancelled download test.bin.part")
+        .exists());
+
+    downloads.cancel(id);
+    assert_eq!(expect_finished(&receiver, id), DownloadOutcome::Cancelled);
+    assert!(!path.exists());
+    assert!(!path
+        .with_file_name("cancelled download test.bin.part")
+        .exists());
+    assert!(downloads.start_fetch(id).is_none());
+}
@@ -12,6 +12,7 @@ use crate::{
 use crossbeam_channel::{unbounded, Sender};
 use devtools_traits::HttpRequest as DevtoolsHttpRequest;
 use devtools_traits::HttpResponse as DevtoolsHttpResponse;
+use devtools_traits::MixedContentStatus;
 use headers::{AccessControlAllowCredentials, AccessControlAllowHeaders, AccessControlAllowOrigin};
 use headers::{AccessControlAllowMethods, AccessControlMaxAge, HeaderMapExt};
 use headers::{
@@ -22,6 +23,7 @@ use http::uri::Authority;
 use http::{Method, StatusCode};
 use hyper::body::Body;
 use hyper::{Request as HyperRequest, Response as HyperResponse};
+use ipc_channel::ipc;
 use mime::{self, Mime};
 use msg::constellation_msg::TEST_PIPELINE_ID;
 use net::connector::create_ssl_connector_builder;
@@ -29,20 +31,27 @@ use net::fetch::cors_cache::CorsCache;
 use net::fetch::methods::{self, CancellationListener, FetchContext};
 use net::filemanager_thread::FileManager;
 use net::hsts::HstsEntry;
+use net::http_loader::CredentialsPrompt;
+use net::proxy::ProxyConfig;
 use net::test::HttpState;
-use net_traits::request::{Destination, Origin, RedirectMode, Referrer, Request, RequestMode};
+use net_traits::csp::{CspList, Policy, PolicyDisposition, PolicySource, Violation};
+use net_traits::request::{Destination, MixedContent, Origin, RedirectMode};
+use net_traits::request::{Referrer, Request, RequestMode};
 use net_traits::response::{CacheState, Response, ResponseBody, ResponseType};
 use net_traits::{
-    FetchTaskTarget, IncludeSubdomains, NetworkError, ReferrerPolicy, ResourceFetchTiming,
-    ResourceTimingType,
+    CustomResponse, CustomResponseMediator, FetchTaskTarget, IncludeSubdomains, NetworkError,
+    ReferrerPolicy, ResourceFetchTiming, ResourceTimingType,
 };
+use servo_config::set_pref;
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::fs::File;
 use std::io::Read;
 use std::iter::FromIterator;
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
@@ -82,6 +91,128 @@ fn test_fetch_on_bad_port_is_network_error() {
     )
 }
 
+#[test]
+fn test_fetch_blockable_mixed_content_is_network_error() {
+    let url = ServoUrl::parse("http://www.example.org/script.js").unwrap();
+    let origin = ServoUrl::parse("https://www.example.org").unwrap().origin();
+    let mut request = Request::new(url, Some(Origin::Origin(origin)), None);
+    request.destination = Destination::Script;
+    request.referrer = Referrer::NoReferrer;
+    let fetch_response = fetch(&mut request, None);
+    assert!(fetch_response.is_network_error());
+    let fetch_error = fetch_response.get_network_error().unwrap();
+    assert_eq!(
+        fetch_error,
+        &NetworkError::Internal("Blocked as mixed content".into())
+    );
+    assert_eq!(request.mixed_content, Some(MixedContent::Blocked));
+}
+
+#[test]
+fn test_fetch_upgradeable_mixed_content_is_upgraded() {
+    // A bad port keeps the upgraded request from reaching the network.
+    let url = ServoUrl::parse("http://www.example.org:6667/image.png").unwrap();
+    let origin = ServoUrl::parse("https://www.example.org").unwrap().origin();
+    let mut request = Request::new(url, Some(Origin::Origin(origin)), None);
+    request.destination = Destination::Image;
+    request.referrer = Referrer::NoReferrer;
+    let fetch_response = fetch(&mut request, None);
+    assert_eq!(
+        fetch_response.get_network_error(),
+        Some(&NetworkError::Internal(
+            "Request attempted on bad port".into()
+        ))
+    );
+    assert_eq!(request.mixed_content, Some(MixedContent::Upgraded));
+    assert_eq!(
+        request.current_url().as_str(),
+        "https://www.example.org:6667/image.png"
+    );
+}
+
+#[test]
+fn test_fetch_upgraded_mixed_content_is_checked_against_csp_after_upgrade() {
+    // A bad port keeps the upgraded request from reaching the network.
+    let url = ServoUrl::parse("http://www.example.org:6667/image.png").unwrap();
+    let origin = ServoUrl::parse("https://www.example.org").unwrap().origin();
+    let mut request = Request::new(url, Some(Origin::Origin(origin)), None);
+    request.destination = Destination::Image;
+    request.referrer = Referrer::NoReferrer;
+    request.csp_list = Some(CspList(vec![Policy::parse(
+        "img-src https:",
+        PolicySource::Header,
+        PolicyDisposition::Enforce,
+    )]));
+    let fetch_response = fetch(&mut request, None);
+    assert_eq!(
+        fetch_response.get_network_error(),
+        Some(&NetworkError::Internal(
+            "Request attempted on bad port".into()
+        ))
+    );
+    assert_eq!(request.mixed_content, Some(MixedContent::Upgraded));
+}
+
+#[test]
+fn test_fetch_mixed_content_in_nested_browsing_context_is_network_error() {
+    let url = ServoUrl::parse("http://www.example.org/frame.html").unwrap();
+    let origin = Origin::Origin(url.origin());
+    let mut request = Request::new(url, Some(origin), None);
+    request.destination = Destination::IFrame;
+    request.has_trustworthy_ancestor_origin = true;
+    request.referrer = Referrer::NoReferrer;
+    let fetch_response = fetch(&mut request, None);
+    assert_eq!(
+        fetch_response.get_network_error(),
+        Some(&NetworkError::Internal("Blocked as mixed content".into()))
+    );
+    assert_eq!(request.mixed_content, Some(MixedContent::Blocked));
+}
+
+#[test]
+fn test_fetch_mixed_content_to_potentially_trustworthy_url() {
+    static MESSAGE: &'static [u8] = b"";
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.body_mut() = MESSAGE.to_vec().into();
+    };
+    let (server, url) = make_server(handler);
+
+    // The test server is on localhost, which is potentially trustworthy.
+    let origin = ServoUrl::parse("https://www.example.org").unwrap().origin();
+    let mut request = Request::new(url, Some(Origin::Origin(origin)), None);
+    request.destination = Destination::Script;
+    request.referrer = Referrer::NoReferrer;
+    let fetch_response = fetch(&mut request, None);
+    let _ = server.close();
+
+    assert!(!fetch_response.is_network_error());
+    assert_eq!(request.mixed_content, None);
+}
+
+#[test]
+fn test_fetch_blocked_mixed_content_is_reported_to_devtools() {
+    let url = ServoUrl::parse("http://www.example.org/data.json").unwrap();
+    let origin = ServoUrl::parse("https://www.example.org").unwrap().origin();
+    let mut request = Request::new(
+        url.clone(),
+        Some(Origin::Origin(origin)),
+        Some(TEST_PIPELINE_ID),
+    );
+    request.referrer = Referrer::NoReferrer;
+
+    let (devtools_chan, devtools_port) = unbounded();
+    let fetch_response = fetch(&mut request, Some(devtools_chan));
+    assert!(fetch_response.is_network_error());
+
+    let devhttprequest = expect_devtools_http_request(&devtools_port);
+    assert_eq!(devhttprequest.url, url);
+    assert_eq!(devhttprequest.pipeline_id, TEST_PIPELINE_ID);
+    assert_eq!(
+        devhttprequest.mixed_content,
+        Some(MixedContentStatus::Blocked)
+    );
+}
+
 #[test]
 fn test_fetch_response_body_matches_const_message() {
     static MESSAGE: &'static [u8] = b"Hello World!";
@@ -143,6 +274,7 @@ fn test_fetch_blob() {
             assert_eq!(self.buffer, self.expected);
             let _ = self.sender.send(response.clone());
         }
+        fn process_csp_violations(&mut self, _: &Request, _: Vec<Violation>) {}
     }
 
     let context = new_fetch_context(None, None);
@@ -630,6 +762,33 @@ fn test_fetch_with_local_urls_only() {
     assert!(!local_response.is_network_error());
     assert!(server_response.is_network_error());
 }
+
+#[test]
+fn test_fetch_aborted_before_the_request_is_sent() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let requests_2 = requests.clone();
+    let handler = move |_: HyperRequest<Body>, _: &mut HyperResponse<Body>| {
+        requests_2.fetch_add(1, Ordering::SeqCst);
+    };
+    let (server, url) = make_server(handler);
+
+    let (cancel_sender, cancel_receiver) = ipc::channel().unwrap();
+    cancel_sender.send(()).unwrap();
+    let mut context = new_fetch_context(None, None);
+    context.cancellation_listener =
+        Arc::new(Mutex::new(CancellationListener::new(Some(cancel_receiver))));
+
+    let origin = Origin::Origin(url.origin());
+    let mut request = Request::new(url, Some(origin), None);
+    request.referrer = Referrer::NoReferrer;
+    let response = fetch_with_context(&mut request, &mut context);
+
+    let _ = server.close();
+
+    assert!(response.is_network_error());
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+}
+
 // NOTE(emilio): If this test starts failing:
 //
 // openssl req -x509 -nodes -days 3650 -newkey rsa:2048 \
@@ -660,14 +819,20 @@ fn test_fetch_with_hsts() {
     let ssl_client = create_ssl_connector_builder(&ca_content);
 
     let mut context = FetchContext {
-        state: Arc::new(HttpState::new(ssl_client)),
+        state: Arc::new(HttpState::with_proxy_config(
+            ssl_client,
+            ProxyConfig::default(),
+        )),
         user_agent: DEFAULT_USER_AGENT.into(),
         devtools_chan: None,
         filemanager: FileManager::new(create_embedder_proxy()),
+        embedder_proxy: create_embedder_proxy(),
+        swmanager_chan: None,
         cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
         timing: Arc::new(Mutex::new(ResourceFetchTiming::new(
             ResourceTimingType::Navigation,
         ))),
+        credentials_prompt: Arc::new(Mutex::new(CredentialsPrompt::Wait)),
     };
 
     {
@@ -1088,6 +1253,9 @@ fn test_fetch_with_devtools() {
 
     headers.typed_insert::<UserAgent>(DEFAULT_USER_AGENT.parse().unwrap());
 
+    // The test server only listens on IPv4.
+    let remote_address = SocketAddr::from(([127, 0, 0, 1], url.port().unwrap()));
+
     let httprequest = DevtoolsHttpRequest {
         url: url,
         method: Method::GET,
@@ -1096,9 +1264,9 @@ fn test_fetch_with_devtools() {
         pipeline_id: TEST_PIPELINE_ID,
         startedDateTime: devhttprequest.startedDateTime,
         timeStamp: devhttprequest.timeStamp,
-        connect_time: devhttprequest.connect_time,
-        send_time: devhttprequest.send_time,
+        timings: devhttprequest.timings.clone(),
         is_xhr: true,
+        mixed_content: None,
     };
 
     let content = "Yay!";
@@ -1113,10 +1281,72 @@ fn test_fetch_with_devtools() {
     let httpresponse = DevtoolsHttpResponse {
         headers: Some(response_headers),
         status: Some((200, b"OK".to_vec())),
-        body: None,
+        http_version: "HTTP/1.1".to_owned(),
+        remote_address: Some(remote_address),
+        security_info: None,
         pipeline_id: TEST_PIPELINE_ID,
     };
 
     assert_eq!(devhttprequest, httprequest);
     assert_eq!(devhttpresponse, httpresponse);
 }
+
+/// A fetch context whose service worker responds to every request with `response`.
+fn new_service_worker_fetch_context(response: CustomResponse) -> FetchContext {
+    set_pref!(dom.serviceworker.enabled, true);
+    let (swmanager_chan, swmanager_port) = ipc::channel::<CustomResponseMediator>().unwrap();
+    thread::spawn(move || {
+        while let Ok(mediator) = swmanager_port.recv() {
+            let _ = mediator.response_chan.send(Some(Ok(response.clone())));
+        }
+    });
+    let mut context = new_fetch_context(None, None);
+    context.swmanager_chan = Some(swmanager_chan);
+    context
+}
+
+#[test]
+fn test_fetch_keeps_the_type_and_url_of_a_service_worker_response() {
+    let response_url = ServoUrl::parse("http://example.com/cached.js").unwrap();
+    let mut context = new_service_worker_fetch_context(CustomResponse::new(
+        ResponseType::Cors,
+        Some(response_url.clone()),
+        HeaderMap::new(),
+        (StatusCode::OK, "OK".to_owned()),
+        b"Yay!".to_vec(),
+    ));
+    let url = ServoUrl::parse("http://example.com/script.js").unwrap();
+    let origin = Origin::Origin(ServoUrl::parse("http://example.org").unwrap().origin());
+    let mut request = Request::new(url, Some(origin), Some(TEST_PIPELINE_ID));
+    request.destination = Destination::Script;
+    request.mode = RequestMode::CorsMode;
+    request.referrer = Referrer::NoReferrer;
+    let response = fetch_with_context(&mut request, &mut context);
+
+    assert_eq!(response.response_type, ResponseType::Cors);
+    assert_eq!(response.actual_response().url(), Some(&response_url));
+    assert_eq!(
+        *response.actual_response().body.lock().unwrap(),
+        ResponseBody::Done(b"Yay!".to_vec())
+    );
+}
+
+#[test]
+fn test_fetch_rejects_an_opaque_service_worker_response_to_a_cors_request() {
+    let mut context = new_service_worker_fetch_context(CustomResponse::new(
+        ResponseType::Opaque,
+        None,
+        HeaderMap::new(),
+        (StatusCode::OK, "".to_owned()),
+        vec![],
+    ));
+    let url = ServoUrl::parse("http://example.com/script.js").unwrap();
+    let origin = Origin::Origin(ServoUrl::parse("http://example.org").unwrap().origin());
+    let mut request = Request::new(url, Some(origin), Some(TEST_PIPELINE_ID));
+    request.destination = Destination::Script;
+    request.mode = RequestMode::CorsMode;
+    request.referrer = Referrer::NoReferrer;
+    let response = fetch_with_context(&mut request, &mut context);
+
+    assert!(response.is_network_error());
+}
@@ -2,8 +2,9 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::TempDir;
 use crossbeam_channel::unbounded;
-use http::header::{HeaderValue, EXPIRES};
+use http::header::{HeaderValue, ACCEPT_LANGUAGE, AUTHORIZATION, COOKIE, ETAG, EXPIRES, VARY};
 use http::StatusCode;
 use msg::constellation_msg::TEST_PIPELINE_ID;
 use net::http_cache::HttpCache;
@@ -11,6 +12,7 @@ use net_traits::request::{Origin, Request};
 use net_traits::response::{Response, ResponseBody};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use servo_url::ServoUrl;
+use std::fs;
 
 #[test]
 fn test_refreshing_resource_sets_done_chan_the_appropriate_value() {
@@ -48,3 +50,164 @@ fn test_refreshing_resource_sets_done_chan_the_appropriate_value() {
         }
     })
 }
+
+#[test]
+fn test_persistent_cache_survives_restart() {
+    let cache_dir = TempDir::new("http-cache");
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let request = Request::new(
+        url.clone(),
+        Some(Origin::Origin(url.clone().origin())),
+        Some(TEST_PIPELINE_ID),
+    );
+    let timing = ResourceFetchTiming::new(ResourceTimingType::Navigation);
+    let mut response = Response::new(url.clone(), timing);
+    response.status = Some((StatusCode::OK, String::from("OK")));
+    response.raw_status = Some((200, b"OK".to_vec()));
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"servo\""));
+    *response.body.lock().unwrap() = ResponseBody::Done(b"persisted".to_vec());
+    {
+        let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+        cache.store(&request, &response);
+    }
+    // A new cache using the same directory finds the stored response,
+    // along with the validator needed to revalidate it.
+    let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+    assert!(cache.needs_loading_from_disk(&request));
+    cache.load_from_disk(&request);
+    let cached_response = cache
+        .construct_response(&request, &mut None)
+        .expect("response should have been stored on disk");
+    assert_eq!(
+        cached_response.response.headers.get(ETAG),
+        Some(&HeaderValue::from_static("\"servo\""))
+    );
+    match *cached_response.response.body.lock().unwrap() {
+        ResponseBody::Done(ref body) => assert_eq!(body, b"persisted"),
+        _ => panic!("expected a complete body"),
+    }
+}
+
+#[test]
+fn test_persistent_cache_evicts_least_recently_used_entries() {
+    let cache_dir = TempDir::new("http-cache");
+    let body = vec![0; 1500];
+    let store = |cache: &mut HttpCache, url: &ServoUrl| {
+        let request = Request::new(
+            url.clone(),
+            Some(Origin::Origin(url.clone().origin())),
+            Some(TEST_PIPELINE_ID),
+        );
+        let timing = ResourceFetchTiming::new(ResourceTimingType::Navigation);
+        let mut response = Response::new(url.clone(), timing);
+        response.raw_status = Some((200, b"OK".to_vec()));
+        response
+            .headers
+            .insert(ETAG, HeaderValue::from_static("\"servo\""));
+        *response.body.lock().unwrap() = ResponseBody::Done(body.clone());
+        cache.store(&request, &response);
+        request
+    };
+    let first_url = ServoUrl::parse("https://servo.org/first").unwrap();
+    let second_url = ServoUrl::parse("https://servo.org/second").unwrap();
+    {
+        // Only one of the two bodies fits in the cache.
+        let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 2500);
+        let first_request = store(&mut cache, &first_url);
+        store(&mut cache, &second_url);
+        // The evicted entry is dropped from memory as well, since its body file is gone.
+        assert!(cache
+            .construct_response(&first_request, &mut None)
+            .is_none());
+    }
+    let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 2500);
+    let first_request = Request::new(first_url.clone(), None, Some(TEST_PIPELINE_ID));
+    let second_request = Request::new(second_url.clone(), None, Some(TEST_PIPELINE_ID));
+    assert!(!cache.needs_loading_from_disk(&first_request));
+    assert!(cache.needs_loading_from_disk(&second_request));
+    cache.load_from_disk(&first_request);
+    cache.load_from_disk(&second_request);
+    assert!(cache
+        .construct_response(&first_request, &mut None)
+        .is_none());
+    assert!(cache
+        .construct_response(&second_request, &mut None)
+        .is_some());
+}
+
+#[test]
+fn test_persistent_cache_only_stores_request_headers_named_by_vary() {
+    let cache_dir = TempDir::new("http-cache");
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let request_with_language = |language: &'static str| {
+        let mut request = Request::new(url.clone(), None, Some(TEST_PIPELINE_ID));
+        request
+            .headers
+            .insert(ACCEPT_LANGUAGE, HeaderValue::from_static(language));
+        request
+            .headers
+            .insert(COOKIE, HeaderValue::from_static("secret-cookie"));
+        request
+            .headers
+            .insert(AUTHORIZATION, HeaderValue::from_static("secret-password"));
+        request
+    };
+    let timing = ResourceFetchTiming::new(ResourceTimingType::Navigation);
+    let mut response = Response::new(url.clone(), timing);
+    response.raw_status = Some((200, b"OK".to_vec()));
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"servo\""));
+    response
+        .headers
+        .insert(VARY, HeaderValue::from_static("Accept-Language"));
+    *response.body.lock().unwrap() = ResponseBody::Done(b"bonjour".to_vec());
+    {
+        let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+        cache.store(&request_with_language("fr"), &response);
+    }
+    for entry in fs::read_dir(cache_dir.path()).unwrap() {
+        let contents = fs::read(entry.unwrap().path()).unwrap();
+        let contents = String::from_utf8_lossy(&contents);
+        assert!(!contents.contains("secret"));
+    }
+    // The stored value of the header the response varies on is still matched.
+    let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+    let french_request = request_with_language("fr");
+    cache.load_from_disk(&french_request);
+    assert!(cache.construct_response(&french_request, &mut None).is_some());
+    let english_request = request_with_language("en");
+    assert!(cache
+        .construct_response(&english_request, &mut None)
+        .is_none());
+}
+
+#[test]
+fn test_persistent_cache_does_not_store_resources_varying_on_credentials() {
+    let cache_dir = TempDir::new("http-cache");
+    let url = ServoUrl::parse("https://servo.org").unwrap();
+    let mut request = Request::new(url.clone(), None, Some(TEST_PIPELINE_ID));
+    request
+        .headers
+        .insert(COOKIE, HeaderValue::from_static("secret-cookie"));
+    let timing = ResourceFetchTiming::new(ResourceTimingType::Navigation);
+    let mut response = Response::new(url.clone(), timing);
+    response.raw_status = Some((200, b"OK".to_vec()));
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"servo\""));
+    response
+        .headers
+        .insert(VARY, HeaderValue::from_static("Cookie"));
+    *response.body.lock().unwrap() = ResponseBody::Done(b"private".to_vec());
+    {
+        let mut cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+        cache.store(&request, &response);
+        // Still cached in memory.
+        assert!(cache.construct_response(&request, &mut None).is_some());
+    }
+    let cache = HttpCache::new_persistent(cache_dir.path().to_owned(), 1024 * 1024);
+    assert!(!cache.needs_loading_from_disk(&request));
+}
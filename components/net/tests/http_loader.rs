@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::create_embedder_proxy;
 use crate::fetch;
 use crate::fetch_with_context;
 use crate::make_server;
@@ -10,7 +11,10 @@ use cookie_rs::Cookie as CookiePair;
 use crossbeam_channel::{unbounded, Receiver};
 use devtools_traits::HttpRequest as DevtoolsHttpRequest;
 use devtools_traits::HttpResponse as DevtoolsHttpResponse;
+use devtools_traits::HttpResponseEnd as DevtoolsHttpResponseEnd;
 use devtools_traits::{ChromeToDevtoolsControlMsg, DevtoolsControlMsg, NetworkEvent};
+use embedder_traits::resources::{self, Resource};
+use embedder_traits::{AuthenticationScheme, Credentials, CredentialsRequest, EmbedderMsg};
 use flate2::write::{DeflateEncoder, GzEncoder};
 use flate2::Compression;
 use futures::{self, Future, Stream};
@@ -25,19 +29,26 @@ use http::{Method, StatusCode};
 use hyper::body::Body;
 use hyper::{Request as HyperRequest, Response as HyperResponse};
 use msg::constellation_msg::TEST_PIPELINE_ID;
+use net::connector::create_ssl_connector_builder;
 use net::cookie::Cookie;
 use net::cookie_storage::CookieStorage;
+use net::fetch::methods::FetchContext;
+use net::proxy::{Proxy, ProxyConfig};
 use net::resource_thread::AuthCacheEntry;
-use net::test::replace_host_table;
+use net::test::{replace_host_table, HttpState};
 use net_traits::request::{CredentialsMode, Destination, RequestBuilder, RequestMode};
 use net_traits::response::ResponseBody;
 use net_traits::{CookieSource, NetworkError};
+use openssl::hash::{hash, MessageDigest};
+use servo_config::pref;
 use servo_url::{ImmutableOrigin, ServoUrl};
 use std::collections::HashMap;
 use std::io::Write;
+use std::net::SocketAddr;
 use std::str;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
 use std::time::Duration;
 
 fn mock_origin() -> ImmutableOrigin {
@@ -62,17 +73,61 @@ fn assert_cookie_for_domain(
     assert_eq!(cookies.as_ref().map(|c| &**c), cookie);
 }
 
+/// A fetch context whose embedder answers every prompt for credentials with
+/// `username` and `test`, along with the requests for credentials it got.
+fn new_prompting_fetch_context() -> (FetchContext, Receiver<CredentialsRequest>) {
+    let (embedder_sender, embedder_receiver) = unbounded();
+    let mut embedder_proxy = create_embedder_proxy();
+    embedder_proxy.sender = embedder_sender;
+    let (prompt_sender, prompt_receiver) = unbounded();
+    thread::spawn(move || {
+        while let Ok((_, msg)) = embedder_receiver.recv() {
+            if let EmbedderMsg::PromptCredentials(request, sender) = msg {
+                let _ = prompt_sender.send(request);
+                let _ = sender.send(Some(Credentials {
+                    user_name: "username".to_owned(),
+                    password: "test".to_owned(),
+                }));
+            }
+        }
+    });
+    (
+        new_fetch_context(None, Some(embedder_proxy)),
+        prompt_receiver,
+    )
+}
+
+fn md5_hex(data: &str) -> String {
+    hash(MessageDigest::md5(), data.as_bytes())
+        .unwrap()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The next network event devtools are told of, other than the end of a response, which
+/// comes once its body is received, and so possibly after the request it redirects to.
+fn expect_devtools_network_event(devtools_port: &Receiver<DevtoolsControlMsg>) -> NetworkEvent {
+    loop {
+        match devtools_port.recv().unwrap() {
+            DevtoolsControlMsg::FromChrome(ChromeToDevtoolsControlMsg::NetworkEvent(
+                _,
+                NetworkEvent::HttpResponseEnd(_),
+            )) => continue,
+            DevtoolsControlMsg::FromChrome(ChromeToDevtoolsControlMsg::NetworkEvent(
+                _,
+                net_event,
+            )) => return net_event,
+            _ => panic!("No NetworkEvent Received"),
+        }
+    }
+}
+
 pub fn expect_devtools_http_request(
     devtools_port: &Receiver<DevtoolsControlMsg>,
 ) -> DevtoolsHttpRequest {
-    match devtools_port.recv().unwrap() {
-        DevtoolsControlMsg::FromChrome(ChromeToDevtoolsControlMsg::NetworkEvent(_, net_event)) => {
-            match net_event {
-                NetworkEvent::HttpRequest(httprequest) => httprequest,
-
-                _ => panic!("No HttpRequest Received"),
-            }
-        },
+    match expect_devtools_network_event(devtools_port) {
+        NetworkEvent::HttpRequest(httprequest) => httprequest,
         _ => panic!("No HttpRequest Received"),
     }
 }
@@ -80,16 +135,21 @@ pub fn expect_devtools_http_request(
 pub fn expect_devtools_http_response(
     devtools_port: &Receiver<DevtoolsControlMsg>,
 ) -> DevtoolsHttpResponse {
+    match expect_devtools_network_event(devtools_port) {
+        NetworkEvent::HttpResponse(httpresponse) => httpresponse,
+        _ => panic!("No HttpResponse Received"),
+    }
+}
+
+pub fn expect_devtools_http_response_end(
+    devtools_port: &Receiver<DevtoolsControlMsg>,
+) -> DevtoolsHttpResponseEnd {
     match devtools_port.recv().unwrap() {
         DevtoolsControlMsg::FromChrome(ChromeToDevtoolsControlMsg::NetworkEvent(
             _,
-            net_event_response,
-        )) => match net_event_response {
-            NetworkEvent::HttpResponse(httpresponse) => httpresponse,
-
-            _ => panic!("No HttpResponse Received"),
-        },
-        _ => panic!("No HttpResponse Received"),
+            NetworkEvent::HttpResponseEnd(httpresponseend),
+        )) => httpresponseend,
+        _ => panic!("No HttpResponseEnd Received"),
     }
 }
 
@@ -276,6 +336,9 @@ fn test_request_and_response_data_with_network_messages() {
 
     headers.typed_insert::<UserAgent>(crate::DEFAULT_USER_AGENT.parse().unwrap());
 
+    // The test server only listens on IPv4.
+    let remote_address = SocketAddr::from(([127, 0, 0, 1], url.port().unwrap()));
+
     let httprequest = DevtoolsHttpRequest {
         url: url,
         method: Method::GET,
@@ -284,9 +347,9 @@ fn test_request_and_response_data_with_network_messages() {
         pipeline_id: TEST_PIPELINE_ID,
         startedDateTime: devhttprequest.startedDateTime,
         timeStamp: devhttprequest.timeStamp,
-        connect_time: devhttprequest.connect_time,
-        send_time: devhttprequest.send_time,
+        timings: devhttprequest.timings.clone(),
         is_xhr: false,
+        mixed_content: None,
     };
 
     let content = "Yay!";
@@ -306,7 +369,9 @@ fn test_request_and_response_data_with_network_messages() {
     let httpresponse = DevtoolsHttpResponse {
         headers: Some(response_headers),
         status: Some((200, b"OK".to_vec())),
-        body: None,
+        http_version: "HTTP/1.1".to_owned(),
+        remote_address: Some(remote_address),
+        security_info: None,
         pipeline_id: TEST_PIPELINE_ID,
     };
 
@@ -347,6 +412,83 @@ fn test_request_and_response_message_from_devtool_without_pipeline_id() {
     assert!(devtools_port.try_recv().is_err());
 }
 
+#[test]
+fn test_response_end_message_has_decoded_body_and_timings() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write(b"Yay!").unwrap();
+    let encoded_content = encoder.finish().unwrap();
+    let encoded_size = encoded_content.len();
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        *response.body_mut() = encoded_content.clone().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let mut request = RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .build();
+
+    let (devtools_chan, devtools_port) = unbounded();
+    let response = fetch(&mut request, Some(devtools_chan));
+    assert!(!response.is_network_error());
+
+    let _ = server.close();
+
+    let devhttprequest = expect_devtools_http_request(&devtools_port);
+    let devhttpresponse = expect_devtools_http_response(&devtools_port);
+    let devhttpresponseend = expect_devtools_http_response_end(&devtools_port);
+
+    // The request was sent over a new connection, without TLS.
+    assert_eq!(devhttprequest.timings.ssl, 0);
+    assert_eq!(devhttpresponse.security_info, None);
+    assert_eq!(
+        devhttpresponseend,
+        DevtoolsHttpResponseEnd {
+            body: Some(b"Yay!".to_vec()),
+            body_size: 4,
+            transferred_size: encoded_size,
+            receive_time: devhttpresponseend.receive_time,
+            pipeline_id: TEST_PIPELINE_ID,
+        }
+    );
+}
+
+#[test]
+fn test_response_end_message_discards_bodies_over_the_limit() {
+    let body_limit = pref!(devtools.netmonitor.response_body_limit);
+    let content = vec![b'a'; body_limit as usize + 1];
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        *response.body_mut() = content.clone().into();
+    };
+    let (server, url) = make_server(handler);
+
+    let mut request = RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .build();
+
+    let (devtools_chan, devtools_port) = unbounded();
+    let response = fetch(&mut request, Some(devtools_chan));
+    assert!(!response.is_network_error());
+
+    let _ = server.close();
+
+    let _ = expect_devtools_http_request(&devtools_port);
+    let _ = expect_devtools_http_response(&devtools_port);
+    let devhttpresponseend = expect_devtools_http_response_end(&devtools_port);
+
+    assert_eq!(devhttpresponseend.body, None);
+    assert_eq!(devhttpresponseend.body_size, body_limit as usize + 1);
+    assert_eq!(devhttpresponseend.transferred_size, body_limit as usize + 1);
+}
+
 #[test]
 fn test_redirected_request_to_devtools() {
     let post_handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
@@ -1348,6 +1490,181 @@ fn test_auth_ui_needs_www_auth() {
     );
 }
 
+#[test]
+fn test_auth_prompt_retries_with_basic_credentials_and_caches_them() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let expected = Authorization::basic("username", "test");
+        if request.headers().typed_get::<Authorization<Basic>>() != Some(expected) {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"servo\""),
+            );
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    let mut request = RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .body(None)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .credentials_mode(CredentialsMode::Include)
+        .build();
+
+    let (mut context, prompts) = new_prompting_fetch_context();
+    let response = fetch_with_context(&mut request, &mut context);
+
+    let _ = server.close();
+
+    assert!(response
+        .internal_response
+        .unwrap()
+        .status
+        .unwrap()
+        .0
+        .is_success());
+
+    let prompt = prompts.try_recv().unwrap();
+    let origin = url.origin().ascii_serialization();
+    assert_eq!(prompt.origin, origin);
+    assert_eq!(prompt.realm, "servo");
+    assert_eq!(prompt.scheme, AuthenticationScheme::Basic);
+    assert!(!prompt.for_proxy);
+    assert!(prompts.try_recv().is_err());
+
+    let auth_cache = context.state.auth_cache.read().unwrap();
+    assert_eq!(auth_cache.entries[&origin].user_name, "username");
+    assert_eq!(auth_cache.realms[&origin]["servo"].password, "test");
+}
+
+#[test]
+fn test_auth_prompt_answers_digest_challenge() {
+    let ha1 = md5_hex("username:servo:test");
+    let ha2 = md5_hex("GET:/");
+    let expected = format!(
+        "response=\"{}\"",
+        md5_hex(&format!("{}:nonce:{}", ha1, ha2))
+    );
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let authorized = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| {
+                value.starts_with("Digest ") &&
+                    value.contains("username=\"username\"") &&
+                    value.contains("opaque=\"opaque\"") &&
+                    value.contains(&expected)
+            });
+        if !authorized {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(
+                    "Basic realm=\"servo\", Digest realm=\"servo\", nonce=\"nonce\", opaque=\"opaque\"",
+                ),
+            );
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    let mut request = RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .body(None)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .credentials_mode(CredentialsMode::Include)
+        .build();
+
+    let (mut context, prompts) = new_prompting_fetch_context();
+    let response = fetch_with_context(&mut request, &mut context);
+
+    let _ = server.close();
+
+    assert!(response
+        .internal_response
+        .unwrap()
+        .status
+        .unwrap()
+        .0
+        .is_success());
+    assert_eq!(
+        prompts.try_recv().unwrap().scheme,
+        AuthenticationScheme::Digest
+    );
+
+    // Digest credentials can't be sent up front, but are kept for the realm.
+    let origin = url.origin().ascii_serialization();
+    let auth_cache = context.state.auth_cache.read().unwrap();
+    assert!(!auth_cache.entries.contains_key(&origin));
+    assert_eq!(auth_cache.realms[&origin]["servo"].user_name, "username");
+}
+
+#[test]
+fn test_proxy_auth_prompt_retries_with_proxy_credentials() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let expected = HeaderValue::from_static("Basic dXNlcm5hbWU6dGVzdA==");
+        if request.headers().get(header::PROXY_AUTHORIZATION) != Some(&expected) {
+            *response.status_mut() = StatusCode::PROXY_AUTHENTICATION_REQUIRED;
+            response.headers_mut().insert(
+                header::PROXY_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"proxy\""),
+            );
+        }
+    };
+    let (server, proxy_url) = make_server(handler);
+
+    let proxy = Proxy::parse(proxy_url.as_str()).unwrap();
+    let (mut context, prompts) = new_prompting_fetch_context();
+    let ssl_connector =
+        create_ssl_connector_builder(&resources::read_string(Resource::SSLCertificates));
+    context.state = Arc::new(HttpState::with_proxy_config(
+        ssl_connector,
+        ProxyConfig::new(Some(proxy.clone()), None, ""),
+    ));
+
+    let url = ServoUrl::parse("http://servo.test/proxied").unwrap();
+    let mut request = RequestBuilder::new(url.clone())
+        .method(Method::GET)
+        .body(None)
+        .destination(Destination::Document)
+        .origin(mock_origin())
+        .pipeline_id(Some(TEST_PIPELINE_ID))
+        .build();
+    let response = fetch_with_context(&mut request, &mut context);
+
+    let _ = server.close();
+
+    assert!(response
+        .internal_response
+        .unwrap()
+        .status
+        .unwrap()
+        .0
+        .is_success());
+
+    let prompt = prompts.try_recv().unwrap();
+    assert_eq!(prompt.realm, "proxy");
+    assert!(prompt.for_proxy);
+
+    assert_eq!(prompt.origin, format!("http://{}", proxy.authority()));
+
+    // The credentials are kept for that proxy only.
+    let auth_cache = context.state.auth_cache.read().unwrap();
+    assert_eq!(
+        auth_cache.proxy_entries[&proxy.authority()].user_name,
+        "username"
+    );
+    assert_eq!(
+        auth_cache.proxy_realms[&proxy.authority()]["proxy"].user_name,
+        "username"
+    );
+    assert_eq!(auth_cache.proxy_entries.len(), 1);
+}
+
 #[test]
 fn test_origin_set() {
     let origin_header = Arc::new(Mutex::new(None));
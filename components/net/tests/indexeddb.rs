@@ -0,0 +1,358 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::TempDir;
+use ipc_channel::ipc::{self, IpcSender};
+use net::indexeddb::idb_thread::IndexedDBThreadFactory;
+use net_traits::indexeddb_thread::{BackendError, ConnectionEvent, ConnectionId};
+use net_traits::indexeddb_thread::{CursorDirection, CursorPosition, IndexInfo};
+use net_traits::indexeddb_thread::{IndexedDBKey, IndexedDBKeyRange, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{IndexedDBTxnMode, KeyPath, Operation};
+use net_traits::indexeddb_thread::{OperationResult, TransactionId};
+use servo_url::ServoUrl;
+use std::fs;
+
+fn number(n: f64) -> IndexedDBKey {
+    IndexedDBKey::Number(n)
+}
+
+fn open(
+    thread: &IpcSender<IndexedDBThreadMsg>,
+    version: Option<u64>,
+) -> ipc::IpcReceiver<ConnectionEvent> {
+    let origin = ServoUrl::parse("https://example.com").unwrap().origin();
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(IndexedDBThreadMsg::Open(
+            sender,
+            origin,
+            "db".to_owned(),
+            version,
+        ))
+        .unwrap();
+    receiver
+}
+
+fn upgrade_transaction(receiver: &ipc::IpcReceiver<ConnectionEvent>) -> TransactionId {
+    match receiver.recv().unwrap() {
+        ConnectionEvent::UpgradeNeeded { connection, .. } => TransactionId {
+            connection,
+            serial: 0,
+        },
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+fn run(
+    thread: &IpcSender<IndexedDBThreadMsg>,
+    id: TransactionId,
+    operation: Operation,
+) -> Result<OperationResult, BackendError> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(IndexedDBThreadMsg::Operation(sender, id, operation))
+        .unwrap();
+    receiver.recv().unwrap()
+}
+
+fn put(thread: &IpcSender<IndexedDBThreadMsg>, id: TransactionId, key: f64, value: u8) {
+    let operation = Operation::Put {
+        store: "store".to_owned(),
+        key: Some(number(key)),
+        value: vec![value],
+        index_keys: vec![("index".to_owned(), vec![number((value % 2) as f64)])],
+        no_overwrite: false,
+    };
+    run(thread, id, operation).unwrap();
+}
+
+fn commit(thread: &IpcSender<IndexedDBThreadMsg>, id: TransactionId) {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread.send(IndexedDBThreadMsg::Commit(sender, id)).unwrap();
+    receiver.recv().unwrap().unwrap();
+}
+
+/// Create a database with an object store and an index over it.
+fn create_database(thread: &IpcSender<IndexedDBThreadMsg>) -> ConnectionId {
+    let receiver = open(thread, Some(1));
+    let upgrade = upgrade_transaction(&receiver);
+    run(
+        thread,
+        upgrade,
+        Operation::CreateObjectStore("store".to_owned(), None, false),
+    )
+    .unwrap();
+    let index = IndexInfo {
+        name: "index".to_owned(),
+        key_path: KeyPath::String("parity".to_owned()),
+        unique: false,
+        multi_entry: false,
+    };
+    run(
+        thread,
+        upgrade,
+        Operation::CreateIndex {
+            store: "store".to_owned(),
+            info: index,
+            entries: vec![],
+        },
+    )
+    .unwrap();
+    commit(thread, upgrade);
+    upgrade.connection
+}
+
+fn create_transaction(
+    thread: &IpcSender<IndexedDBThreadMsg>,
+    id: TransactionId,
+    mode: IndexedDBTxnMode,
+) -> ipc::IpcReceiver<()> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread
+        .send(IndexedDBThreadMsg::CreateTransaction(
+            sender,
+            id,
+            mode,
+            vec!["store".to_owned()],
+        ))
+        .unwrap();
+    receiver
+}
+
+#[test]
+fn test_upgrade_and_reopen() {
+    let thread: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let connection = create_database(&thread);
+    let id = TransactionId {
+        connection,
+        serial: 1,
+    };
+    create_transaction(&thread, id, IndexedDBTxnMode::Readwrite)
+        .recv()
+        .unwrap();
+    put(&thread, id, 1., 42);
+    commit(&thread, id);
+    thread.send(IndexedDBThreadMsg::Close(connection)).unwrap();
+
+    match open(&thread, None).recv().unwrap() {
+        ConnectionEvent::Opened { info, .. } => {
+            assert_eq!(info.version, 1);
+            assert_eq!(info.object_stores.len(), 1);
+            assert_eq!(info.object_stores[0].indexes.len(), 1);
+        },
+        event => panic!("unexpected event {:?}", event),
+    }
+    match open(&thread, Some(0)).recv().unwrap() {
+        ConnectionEvent::Error(BackendError::Version) => {},
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[test]
+fn test_abort_reverts_changes() {
+    let thread: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let connection = create_database(&thread);
+    let id = TransactionId {
+        connection,
+        serial: 1,
+    };
+    create_transaction(&thread, id, IndexedDBTxnMode::Readwrite)
+        .recv()
+        .unwrap();
+    put(&thread, id, 1., 42);
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread.send(IndexedDBThreadMsg::Abort(sender, id)).unwrap();
+    receiver.recv().unwrap();
+
+    let id = TransactionId {
+        connection,
+        serial: 2,
+    };
+    create_transaction(&thread, id, IndexedDBTxnMode::Readonly)
+        .recv()
+        .unwrap();
+    let range = IndexedDBKeyRange::unbounded();
+    match run(
+        &thread,
+        id,
+        Operation::Count("store".to_owned(), None, range),
+    ) {
+        Ok(OperationResult::Count(0)) => {},
+        result => panic!("unexpected result {:?}", result),
+    }
+    let operation = Operation::Put {
+        store: "store".to_owned(),
+        key: Some(number(1.)),
+        value: vec![],
+        index_keys: vec![],
+        no_overwrite: false,
+    };
+    assert!(run(&thread, id, operation).is_err());
+}
+
+fn count(thread: &IpcSender<IndexedDBThreadMsg>, id: TransactionId) -> u64 {
+    create_transaction(thread, id, IndexedDBTxnMode::Readonly)
+        .recv()
+        .unwrap();
+    let range = IndexedDBKeyRange::unbounded();
+    match run(thread, id, Operation::Count("store".to_owned(), None, range)) {
+        Ok(OperationResult::Count(count)) => count,
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+fn exit(thread: &IpcSender<IndexedDBThreadMsg>) {
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread.send(IndexedDBThreadMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
+#[test]
+fn test_committed_changes_survive_restart() {
+    let config_dir = TempDir::new("indexeddb");
+    let thread: IpcSender<IndexedDBThreadMsg> =
+        IndexedDBThreadFactory::new(Some(config_dir.path().to_owned()));
+    let connection = create_database(&thread);
+    for serial in 1..4 {
+        let id = TransactionId { connection, serial };
+        create_transaction(&thread, id, IndexedDBTxnMode::Readwrite)
+            .recv()
+            .unwrap();
+        put(&thread, id, serial as f64, serial as u8);
+        commit(&thread, id);
+    }
+    exit(&thread);
+
+    // The changes are replayed from the journal on top of the snapshot.
+    let thread: IpcSender<IndexedDBThreadMsg> =
+        IndexedDBThreadFactory::new(Some(config_dir.path().to_owned()));
+    let connection = match open(&thread, None).recv().unwrap() {
+        ConnectionEvent::Opened { connection, .. } => connection,
+        event => panic!("unexpected event {:?}", event),
+    };
+    assert_eq!(
+        count(
+            &thread,
+            TransactionId {
+                connection,
+                serial: 1,
+            }
+        ),
+        3
+    );
+    exit(&thread);
+}
+
+#[test]
+fn test_failed_commit_reverts_changes() {
+    let config_dir = TempDir::new("indexeddb");
+    let thread: IpcSender<IndexedDBThreadMsg> =
+        IndexedDBThreadFactory::new(Some(config_dir.path().to_owned()));
+    let connection = create_database(&thread);
+    // Make the journal impossible to create, by putting a file where its directory was.
+    let origin_dir = config_dir.path().join("IndexedDB").join("https___example.com");
+    fs::remove_dir_all(&origin_dir).unwrap();
+    fs::write(&origin_dir, b"").unwrap();
+
+    let id = TransactionId {
+        connection,
+        serial: 1,
+    };
+    create_transaction(&thread, id, IndexedDBTxnMode::Readwrite)
+        .recv()
+        .unwrap();
+    put(&thread, id, 1., 42);
+    let (sender, receiver) = ipc::channel().unwrap();
+    thread.send(IndexedDBThreadMsg::Commit(sender, id)).unwrap();
+    assert!(receiver.recv().unwrap().is_err());
+
+    let id = TransactionId {
+        connection,
+        serial: 2,
+    };
+    assert_eq!(count(&thread, id), 0);
+    exit(&thread);
+}
+
+#[test]
+fn test_transaction_scheduling() {
+    let thread: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let connection = create_database(&thread);
+    let id = |serial| TransactionId { connection, serial };
+
+    let first_reader = create_transaction(&thread, id(1), IndexedDBTxnMode::Readonly);
+    let writer = create_transaction(&thread, id(2), IndexedDBTxnMode::Readwrite);
+    let second_reader = create_transaction(&thread, id(3), IndexedDBTxnMode::Readonly);
+    first_reader.recv().unwrap();
+    // The writer waits for the first reader, and the second reader for the writer.
+    assert!(writer.try_recv().is_err());
+    assert!(second_reader.try_recv().is_err());
+
+    commit(&thread, id(1));
+    writer.recv().unwrap();
+    assert!(second_reader.try_recv().is_err());
+    commit(&thread, id(2));
+    second_reader.recv().unwrap();
+}
+
+#[test]
+fn test_index_cursor() {
+    let thread: IpcSender<IndexedDBThreadMsg> = IndexedDBThreadFactory::new(None);
+    let connection = create_database(&thread);
+    let id = TransactionId {
+        connection,
+        serial: 1,
+    };
+    create_transaction(&thread, id, IndexedDBTxnMode::Readwrite)
+        .recv()
+        .unwrap();
+    for key in 1..6 {
+        put(&thread, id, key as f64, key);
+    }
+
+    let iterate = |direction, current: Option<(f64, f64)>| {
+        let operation = Operation::Iterate {
+            store: "store".to_owned(),
+            index: Some("index".to_owned()),
+            range: IndexedDBKeyRange::unbounded(),
+            direction,
+            position: CursorPosition {
+                current: current.map(|(key, primary_key)| (number(key), number(primary_key))),
+                target_key: None,
+                target_primary_key: None,
+                count: 1,
+            },
+            key_only: true,
+        };
+        match run(&thread, id, operation) {
+            Ok(OperationResult::Cursor(record)) => {
+                record.map(|record| (record.key, record.primary_key))
+            },
+            result => panic!("unexpected result {:?}", result),
+        }
+    };
+
+    // Odd values have index key 1, even values index key 0.
+    assert_eq!(
+        iterate(CursorDirection::Next, None),
+        Some((number(0.), number(2.)))
+    );
+    assert_eq!(
+        iterate(CursorDirection::Next, Some((0., 4.))),
+        Some((number(1.), number(1.)))
+    );
+    assert_eq!(
+        iterate(CursorDirection::NextUnique, Some((0., 2.))),
+        Some((number(1.), number(1.)))
+    );
+    assert_eq!(
+        iterate(CursorDirection::Prev, None),
+        Some((number(1.), number(5.)))
+    );
+    assert_eq!(
+        iterate(CursorDirection::PrevUnique, None),
+        Some((number(1.), number(1.)))
+    );
+    assert_eq!(iterate(CursorDirection::Prev, Some((0., 2.))), None);
+}
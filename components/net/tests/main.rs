@@ -7,16 +7,20 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod cache_storage;
 mod cookie;
 mod cookie_http_state;
 mod data_loader;
+mod download;
 mod fetch;
 mod file_loader;
 mod filemanager_thread;
 mod hsts;
 mod http_cache;
 mod http_loader;
+mod indexeddb;
 mod mime_classifier;
+mod proxy;
 mod resource_thread;
 mod subresource_integrity;
 
@@ -33,18 +37,24 @@ use net::connector::create_ssl_connector_builder;
 use net::fetch::cors_cache::CorsCache;
 use net::fetch::methods::{self, CancellationListener, FetchContext};
 use net::filemanager_thread::FileManager;
+use net::http_loader::CredentialsPrompt;
+use net::proxy::ProxyConfig;
 use net::test::HttpState;
+use net_traits::csp::Violation;
 use net_traits::request::Request;
 use net_traits::response::Response;
 use net_traits::{FetchTaskTarget, ResourceFetchTiming, ResourceTimingType};
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use servo_url::ServoUrl;
+use std::env;
+use std::fs;
 use std::net::TcpListener as StdTcpListener;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
 use tokio_openssl::SslAcceptorExt;
+use uuid::Uuid;
 
 lazy_static! {
     pub static ref HANDLE: Mutex<Runtime> = { Mutex::new(Runtime::new().unwrap()) };
@@ -89,14 +99,20 @@ fn new_fetch_context(
         create_ssl_connector_builder(&resources::read_string(Resource::SSLCertificates));
     let sender = fc.unwrap_or_else(|| create_embedder_proxy());
     FetchContext {
-        state: Arc::new(HttpState::new(ssl_connector)),
+        state: Arc::new(HttpState::with_proxy_config(
+            ssl_connector,
+            ProxyConfig::default(),
+        )),
         user_agent: DEFAULT_USER_AGENT.into(),
         devtools_chan: dc,
-        filemanager: FileManager::new(sender),
+        filemanager: FileManager::new(sender.clone()),
+        embedder_proxy: sender,
+        swmanager_chan: None,
         cancellation_listener: Arc::new(Mutex::new(CancellationListener::new(None))),
         timing: Arc::new(Mutex::new(ResourceFetchTiming::new(
             ResourceTimingType::Navigation,
         ))),
+        credentials_prompt: Arc::new(Mutex::new(CredentialsPrompt::Wait)),
     }
 }
 impl FetchTaskTarget for FetchResponseCollector {
@@ -108,6 +124,7 @@ impl FetchTaskTarget for FetchResponseCollector {
     fn process_response_eof(&mut self, response: &Response) {
         let _ = self.sender.send(response.clone());
     }
+    fn process_csp_violations(&mut self, _: &Request, _: Vec<Violation>) {}
 }
 
 fn fetch(request: &mut Request, dc: Option<Sender<DevtoolsControlMsg>>) -> Response {
@@ -147,6 +164,27 @@ impl Server {
     }
 }
 
+/// A directory of its own for a test, removed along with its contents when dropped.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(prefix: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("servo-{}-{}", prefix, Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 fn make_server<H>(handler: H) -> (Server, ServoUrl)
 where
     H: Fn(HyperRequest<Body>, &mut HyperResponse<Body>) + Send + Sync + 'static,
@@ -3,13 +3,29 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::create_embedder_proxy;
+use crate::make_server;
+use crossbeam_channel::unbounded;
+use embedder_traits::{Credentials, EmbedderMsg};
+use headers::authorization::{Authorization, Basic};
+use headers::HeaderMapExt;
+use http::header::{self, HeaderValue};
+use http::StatusCode;
+use hyper::body::Body;
+use hyper::{Request as HyperRequest, Response as HyperResponse};
+use hyper_serde::Serde;
 use ipc_channel::ipc;
-use net::resource_thread::new_core_resource_thread;
+use msg::constellation_msg::{TopLevelBrowsingContextId, TEST_BROWSING_CONTEXT_ID};
+use net::resource_thread::{new_core_resource_thread, new_ephemeral_resource_threads};
 use net::test::parse_hostsfile;
-use net_traits::CoreResourceMsg;
+use net_traits::request::{CredentialsMode, Destination, RequestBuilder, RequestMode};
+use net_traits::{
+    fetch_async, CookieSource, CoreResourceMsg, FetchMetadata, FetchResponseMsg, IpcSend,
+};
 use profile_traits::mem::ProfilerChan as MemProfilerChan;
 use profile_traits::time::ProfilerChan;
+use servo_url::ServoUrl;
 use std::net::IpAddr;
+use std::thread;
 
 fn ip(s: &str) -> IpAddr {
     s.parse().unwrap()
@@ -33,6 +49,273 @@ fn test_exit() {
     receiver.recv().unwrap();
 }
 
+#[test]
+fn test_ephemeral_session_is_isolated() {
+    let (tx, _rx) = ipc::channel().unwrap();
+    let (mtx, _mrx) = ipc::channel().unwrap();
+    let (resource_thread, _private_resource_thread) = new_core_resource_thread(
+        "".into(),
+        None,
+        ProfilerChan(tx),
+        MemProfilerChan(mtx),
+        create_embedder_proxy(),
+        None,
+        None,
+    );
+    let (session, session_port) = new_ephemeral_resource_threads();
+    resource_thread
+        .send(CoreResourceMsg::NewEphemeralSession(session_port))
+        .unwrap();
+
+    let url = ServoUrl::parse("https://example.com/").unwrap();
+    let cookie = cookie_rs::Cookie::parse("session=only").unwrap();
+    session
+        .send(CoreResourceMsg::SetCookieForUrl(
+            url.clone(),
+            Serde(cookie),
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    session
+        .send(CoreResourceMsg::GetCookiesForUrl(
+            url.clone(),
+            sender,
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+    assert_eq!(receiver.recv().unwrap(), Some("session=only".to_owned()));
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread
+        .send(CoreResourceMsg::GetCookiesForUrl(
+            url,
+            sender,
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+    assert_eq!(receiver.recv().unwrap(), None);
+
+    // Discarding the session leaves the resource thread running.
+    let (sender, receiver) = ipc::channel().unwrap();
+    session.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread
+        .send(CoreResourceMsg::Synchronize(sender))
+        .unwrap();
+    receiver.recv().unwrap();
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
+#[test]
+fn test_ephemeral_navigation_does_not_use_the_public_cookie_jar() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        assert!(request.headers().get(header::COOKIE).is_none());
+        response.headers_mut().insert(
+            header::SET_COOKIE,
+            HeaderValue::from_static("ephemeral=navigation"),
+        );
+    };
+    let (server, url) = make_server(handler);
+
+    let (tx, _rx) = ipc::channel().unwrap();
+    let (mtx, _mrx) = ipc::channel().unwrap();
+    let (resource_thread, _private_resource_thread) = new_core_resource_thread(
+        "".into(),
+        None,
+        ProfilerChan(tx),
+        MemProfilerChan(mtx),
+        create_embedder_proxy(),
+        None,
+        None,
+    );
+    let cookie = cookie_rs::Cookie::parse("public=jar").unwrap();
+    resource_thread
+        .send(CoreResourceMsg::SetCookieForUrl(
+            url.clone(),
+            Serde(cookie),
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+    let (session, session_port) = new_ephemeral_resource_threads();
+    resource_thread
+        .send(CoreResourceMsg::NewEphemeralSession(session_port))
+        .unwrap();
+
+    let request = RequestBuilder::new(url.clone())
+        .destination(Destination::Document)
+        .mode(RequestMode::Navigate)
+        .credentials_mode(CredentialsMode::Include)
+        .origin(url.origin());
+    let (done_sender, done_receiver) = ipc::channel().unwrap();
+    fetch_async(request, &session.sender(), move |msg| {
+        if let FetchResponseMsg::ProcessResponseEOF(result) = msg {
+            done_sender.send(result.is_ok()).unwrap();
+        }
+    });
+    assert!(done_receiver.recv().unwrap());
+    let _ = server.close();
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread
+        .send(CoreResourceMsg::GetCookiesForUrl(
+            url.clone(),
+            sender,
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+    assert_eq!(receiver.recv().unwrap(), Some("public=jar".to_owned()));
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    session
+        .send(CoreResourceMsg::GetCookiesForUrl(
+            url,
+            sender,
+            CookieSource::HTTP,
+        ))
+        .unwrap();
+    assert_eq!(
+        receiver.recv().unwrap(),
+        Some("ephemeral=navigation".to_owned())
+    );
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
+#[test]
+fn test_credentials_are_asked_of_the_browser_of_the_request() {
+    let handler = move |request: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        let expected = Authorization::basic("username", "test");
+        if request.headers().typed_get::<Authorization<Basic>>() != Some(expected) {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"servo\""),
+            );
+        }
+    };
+    let (server, url) = make_server(handler);
+
+    let (embedder_sender, embedder_receiver) = unbounded();
+    let mut embedder_proxy = create_embedder_proxy();
+    embedder_proxy.sender = embedder_sender;
+    let (prompt_sender, prompt_receiver) = unbounded();
+    thread::spawn(move || {
+        while let Ok((browser, msg)) = embedder_receiver.recv() {
+            if let EmbedderMsg::PromptCredentials(_, sender) = msg {
+                let _ = prompt_sender.send(browser);
+                let _ = sender.send(Some(Credentials {
+                    user_name: "username".to_owned(),
+                    password: "test".to_owned(),
+                }));
+            }
+        }
+    });
+
+    let (tx, _rx) = ipc::channel().unwrap();
+    let (mtx, _mrx) = ipc::channel().unwrap();
+    let (resource_thread, _private_resource_thread) = new_core_resource_thread(
+        "".into(),
+        None,
+        ProfilerChan(tx),
+        MemProfilerChan(mtx),
+        embedder_proxy,
+        None,
+        None,
+    );
+
+    let browser = TopLevelBrowsingContextId(TEST_BROWSING_CONTEXT_ID);
+    let request = RequestBuilder::new(url.clone())
+        .destination(Destination::Document)
+        .credentials_mode(CredentialsMode::Include)
+        .origin(url.origin())
+        .top_level_browsing_context_id(Some(browser));
+    let (status_sender, status_receiver) = ipc::channel().unwrap();
+    fetch_async(request, &resource_thread, move |msg| {
+        if let FetchResponseMsg::ProcessResponse(Ok(metadata)) = msg {
+            let metadata = match metadata {
+                FetchMetadata::Unfiltered(metadata) => metadata,
+                FetchMetadata::Filtered { unsafe_, .. } => unsafe_,
+            };
+            status_sender.send(metadata.status.unwrap().0).unwrap();
+        }
+    });
+    // The first attempt, which stopped to ask for credentials, isn't responded to.
+    assert_eq!(status_receiver.recv().unwrap(), 200);
+    let _ = server.close();
+    assert_eq!(prompt_receiver.recv().unwrap(), Some(browser));
+    assert!(prompt_receiver.try_recv().is_err());
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
+#[test]
+fn test_embedder_is_told_which_browser_changed_cookies() {
+    let handler = move |_: HyperRequest<Body>, response: &mut HyperResponse<Body>| {
+        response
+            .headers_mut()
+            .insert(header::SET_COOKIE, HeaderValue::from_static("foo=bar"));
+    };
+    let (server, url) = make_server(handler);
+
+    let (embedder_sender, embedder_receiver) = unbounded();
+    let mut embedder_proxy = create_embedder_proxy();
+    embedder_proxy.sender = embedder_sender;
+
+    let (tx, _rx) = ipc::channel().unwrap();
+    let (mtx, _mrx) = ipc::channel().unwrap();
+    let (resource_thread, _private_resource_thread) = new_core_resource_thread(
+        "".into(),
+        None,
+        ProfilerChan(tx),
+        MemProfilerChan(mtx),
+        embedder_proxy,
+        None,
+        None,
+    );
+
+    let browser = TopLevelBrowsingContextId(TEST_BROWSING_CONTEXT_ID);
+    let request = RequestBuilder::new(url.clone())
+        .destination(Destination::Document)
+        .credentials_mode(CredentialsMode::Include)
+        .origin(url.origin())
+        .top_level_browsing_context_id(Some(browser));
+    let (done_sender, done_receiver) = ipc::channel().unwrap();
+    fetch_async(request, &resource_thread, move |msg| {
+        if let FetchResponseMsg::ProcessResponseEOF(_) = msg {
+            done_sender.send(()).unwrap();
+        }
+    });
+    done_receiver.recv().unwrap();
+    let _ = server.close();
+
+    let changes = loop {
+        if let (changed_by, EmbedderMsg::CookiesChanged(domain, changes)) =
+            embedder_receiver.recv().unwrap()
+        {
+            assert_eq!(changed_by, Some(browser));
+            assert_eq!(domain, "localhost");
+            break changes;
+        }
+    };
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].name, "foo");
+    assert!(!changes[0].removed);
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    resource_thread.send(CoreResourceMsg::Exit(sender)).unwrap();
+    receiver.recv().unwrap();
+}
+
 #[test]
 fn test_parse_hostsfile() {
     let mock_hosts_file_content = "127.0.0.1 foo.bar.com\n127.0.0.2 servo.test.server";
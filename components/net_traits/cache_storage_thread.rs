@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Messages and types shared between script and the thread keeping the
+//! [cache storages](https://w3c.github.io/ServiceWorker/#cachestorage-interface)
+//! of every origin.
+//!
+//! Responses are stored whole: script reads their body before sending them
+//! to the cache storage thread, which never fetches anything itself.
+
+use http::{HeaderMap, Method};
+use ipc_channel::ipc::IpcSender;
+use servo_url::{ImmutableOrigin, ServoUrl};
+
+/// Identifies a cache for as long as the cache storage thread runs, including
+/// after the cache was deleted from its cache storage, since the `Cache` objects
+/// script already has keep working on it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize)]
+pub struct CacheId(pub u64);
+
+/// The request of an entry in a
+/// [request to response map](https://w3c.github.io/ServiceWorker/#dfn-request-response-list).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedRequest {
+    pub url: ServoUrl,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub method: Method,
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub headers: HeaderMap,
+}
+
+/// The response of an entry in a
+/// [request to response map](https://w3c.github.io/ServiceWorker/#dfn-request-response-list).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedResponse {
+    pub url: Option<ServoUrl>,
+    pub status: (u16, Vec<u8>),
+    #[serde(
+        deserialize_with = "::hyper_serde::deserialize",
+        serialize_with = "::hyper_serde::serialize"
+    )]
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// <https://w3c.github.io/ServiceWorker/#dictdef-cachequeryoptions>
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct CacheQueryOptions {
+    pub ignore_search: bool,
+    pub ignore_method: bool,
+    pub ignore_vary: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum CacheStorageThreadMsg {
+    /// Look a request up in the cache of that name, or in every cache of the
+    /// origin in creation order.
+    Match(
+        IpcSender<Option<CachedResponse>>,
+        ImmutableOrigin,
+        Option<String>,
+        CachedRequest,
+        CacheQueryOptions,
+    ),
+    /// Whether the origin has a cache of that name.
+    Has(IpcSender<bool>, ImmutableOrigin, String),
+    /// Open the cache of that name, creating it if the origin has none.
+    Open(IpcSender<CacheId>, ImmutableOrigin, String),
+    /// Delete the cache of that name, replying whether there was one.
+    Delete(IpcSender<bool>, ImmutableOrigin, String),
+    /// The names of the caches of the origin, in creation order.
+    Keys(IpcSender<Vec<String>>, ImmutableOrigin),
+    /// The responses of a cache matching a request, or all of them.
+    MatchAll(
+        IpcSender<Vec<CachedResponse>>,
+        CacheId,
+        Option<CachedRequest>,
+        CacheQueryOptions,
+    ),
+    /// Store entries in a cache at once, each replacing the entries its request matches.
+    Put(IpcSender<()>, CacheId, Vec<(CachedRequest, CachedResponse)>),
+    /// Remove the entries of a cache matching a request, replying whether there were any.
+    DeleteEntries(IpcSender<bool>, CacheId, CachedRequest, CacheQueryOptions),
+    /// The requests of a cache matching a request, or all of them.
+    RequestKeys(
+        IpcSender<Vec<CachedRequest>>,
+        CacheId,
+        Option<CachedRequest>,
+        CacheQueryOptions,
+    ),
+    /// Send a reply when done cleaning up thread resources and then shut it down.
+    Exit(IpcSender<()>),
+}
@@ -0,0 +1,756 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! An implementation of [Content Security Policy](https://w3c.github.io/webappsec-csp/).
+//!
+//! Policies are parsed from `Content-Security-Policy` headers and `<meta http-equiv>`
+//! elements, and are checked both by the fetch algorithm (for requests) and by script
+//! (for inline content, string compilation and framing).
+
+use crate::request::{Destination, Origin, Request};
+use http::HeaderMap;
+use servo_url::{ImmutableOrigin, ServoUrl};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// <https://w3c.github.io/webappsec-csp/#policy-disposition>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum PolicyDisposition {
+    Enforce,
+    Report,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#policy-source>
+#[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
+pub enum PolicySource {
+    Header,
+    Meta,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#directives>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub struct Directive {
+    /// The directive's name, always in ASCII lowercase.
+    pub name: String,
+    /// The directive's value, a list of tokens.
+    pub value: Vec<String>,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#content-security-policy-object>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct Policy {
+    pub directives: Vec<Directive>,
+    pub disposition: PolicyDisposition,
+    pub source: PolicySource,
+    /// The serialized policy this was parsed from, exposed to violation reports.
+    pub serialized: String,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#csp-list>
+#[derive(Clone, Debug, Default, Deserialize, MallocSizeOf, Serialize)]
+pub struct CspList(pub Vec<Policy>);
+
+/// The result of checking something against a policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckResult {
+    Allowed,
+    Blocked,
+}
+
+/// <https://w3c.github.io/webappsec-csp/#violation-resource>
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ViolationResource {
+    Inline,
+    Eval,
+    Url(ServoUrl),
+}
+
+/// <https://w3c.github.io/webappsec-csp/#violation>
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Violation {
+    pub resource: ViolationResource,
+    /// The name of the directive which was violated.
+    pub directive: String,
+    /// <https://w3c.github.io/webappsec-csp/#violation-effective-directive>
+    pub effective_directive: String,
+    /// The serialized policy which was violated.
+    pub policy: String,
+    pub disposition: PolicyDisposition,
+    /// The URLs listed in the `report-uri` directive of the violated policy.
+    pub report_uris: Vec<String>,
+    /// <https://w3c.github.io/webappsec-csp/#violation-sample>
+    pub sample: Option<String>,
+}
+
+/// The kind of inline content being checked.
+/// <https://w3c.github.io/webappsec-csp/#should-block-inline>
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InlineCheckType {
+    Script,
+    ScriptAttribute,
+    Style,
+    StyleAttribute,
+    Navigation,
+}
+
+impl InlineCheckType {
+    /// <https://w3c.github.io/webappsec-csp/#effective-directive-for-inline-check>
+    fn effective_directive(&self) -> &'static str {
+        match *self {
+            InlineCheckType::Script | InlineCheckType::Navigation => "script-src-elem",
+            InlineCheckType::ScriptAttribute => "script-src-attr",
+            InlineCheckType::Style => "style-src-elem",
+            InlineCheckType::StyleAttribute => "style-src-attr",
+        }
+    }
+
+    fn is_script(&self) -> bool {
+        match *self {
+            InlineCheckType::Script |
+            InlineCheckType::ScriptAttribute |
+            InlineCheckType::Navigation => true,
+            InlineCheckType::Style | InlineCheckType::StyleAttribute => false,
+        }
+    }
+}
+
+/// The maximum length of the sample of inline content included in violation reports.
+const SAMPLE_LENGTH: usize = 40;
+
+/// <https://w3c.github.io/webappsec-csp/#directive-fallback-list>
+fn directive_fallback_list(effective_directive: &str) -> &'static [&'static str] {
+    match effective_directive {
+        "script-src" => &["script-src", "default-src"],
+        "script-src-elem" => &["script-src-elem", "script-src", "default-src"],
+        "script-src-attr" => &["script-src-attr", "script-src", "default-src"],
+        "style-src-elem" => &["style-src-elem", "style-src", "default-src"],
+        "style-src-attr" => &["style-src-attr", "style-src", "default-src"],
+        "worker-src" => &["worker-src", "child-src", "script-src", "default-src"],
+        "connect-src" => &["connect-src", "default-src"],
+        "manifest-src" => &["manifest-src", "default-src"],
+        "object-src" => &["object-src", "default-src"],
+        "frame-src" => &["frame-src", "child-src", "default-src"],
+        "media-src" => &["media-src", "default-src"],
+        "font-src" => &["font-src", "default-src"],
+        "img-src" => &["img-src", "default-src"],
+        _ => &[],
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#effective-directive-for-a-request>
+fn effective_directive_for_request(request: &Request) -> Option<&'static str> {
+    match request.destination {
+        Destination::None => Some("connect-src"),
+        Destination::Manifest => Some("manifest-src"),
+        Destination::Audio | Destination::Track | Destination::Video => Some("media-src"),
+        Destination::Font => Some("font-src"),
+        Destination::Image => Some("img-src"),
+        Destination::Style => Some("style-src-elem"),
+        Destination::Script | Destination::Xslt => Some("script-src-elem"),
+        Destination::ServiceWorker | Destination::SharedWorker | Destination::Worker => {
+            Some("worker-src")
+        },
+        Destination::Embed | Destination::Object => Some("object-src"),
+        Destination::IFrame => Some("frame-src"),
+        Destination::Document | Destination::Report => None,
+    }
+}
+
+impl Policy {
+    /// <https://w3c.github.io/webappsec-csp/#parse-serialized-policy>
+    pub fn parse(serialized: &str, source: PolicySource, disposition: PolicyDisposition) -> Policy {
+        let mut directives: Vec<Directive> = vec![];
+        for token in serialized.split(';') {
+            let token = token.trim_matches(is_ascii_whitespace);
+            if token.is_empty() {
+                continue;
+            }
+            let mut parts = token
+                .split(is_ascii_whitespace)
+                .filter(|part| !part.is_empty());
+            let name = match parts.next() {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            if directives.iter().any(|directive| directive.name == name) {
+                // Only the first directive with a given name is taken into account.
+                continue;
+            }
+            let value = parts.map(str::to_owned).collect();
+            directives.push(Directive { name, value });
+        }
+        Policy {
+            directives,
+            disposition,
+            source,
+            serialized: serialized.trim_matches(is_ascii_whitespace).to_owned(),
+        }
+    }
+
+    fn directive(&self, name: &str) -> Option<&Directive> {
+        self.directives
+            .iter()
+            .find(|directive| directive.name == name)
+    }
+
+    /// Find the directive which governs `effective_directive` in this policy, if any.
+    /// <https://w3c.github.io/webappsec-csp/#should-directive-execute>
+    fn governing_directive(&self, effective_directive: &str) -> Option<&Directive> {
+        directive_fallback_list(effective_directive)
+            .iter()
+            .filter_map(|name| self.directive(name))
+            .next()
+    }
+
+    fn report_uris(&self) -> Vec<String> {
+        self.directive("report-uri")
+            .map(|directive| directive.value.clone())
+            .unwrap_or_default()
+    }
+
+    fn violation(
+        &self,
+        resource: ViolationResource,
+        directive: &Directive,
+        effective_directive: &str,
+        sample: Option<String>,
+    ) -> Violation {
+        Violation {
+            resource,
+            directive: directive.name.clone(),
+            effective_directive: effective_directive.to_owned(),
+            policy: self.serialized.clone(),
+            disposition: self.disposition,
+            report_uris: self.report_uris(),
+            sample,
+        }
+    }
+}
+
+impl CspList {
+    /// Parse the policies delivered by the `Content-Security-Policy` and
+    /// `Content-Security-Policy-Report-Only` headers of a response.
+    /// <https://w3c.github.io/webappsec-csp/#parse-response-csp>
+    pub fn parse_response_headers(headers: &HeaderMap) -> CspList {
+        let mut list = CspList::default();
+        let header_dispositions = [
+            ("content-security-policy", PolicyDisposition::Enforce),
+            (
+                "content-security-policy-report-only",
+                PolicyDisposition::Report,
+            ),
+        ];
+        for &(header_name, disposition) in header_dispositions.iter() {
+            for value in headers.get_all(header_name).iter() {
+                let value = match value.to_str() {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+                for serialized in value.split(',') {
+                    let policy = Policy::parse(serialized, PolicySource::Header, disposition);
+                    if !policy.directives.is_empty() {
+                        list.0.push(policy);
+                    }
+                }
+            }
+        }
+        list
+    }
+
+    /// Add a policy delivered by a `<meta http-equiv="Content-Security-Policy">` element.
+    /// <https://html.spec.whatwg.org/multipage/#attr-meta-http-equiv-content-security-policy>
+    pub fn append_meta_policy(&mut self, content: &str) {
+        let mut policy = Policy::parse(content, PolicySource::Meta, PolicyDisposition::Enforce);
+        // These directives are not supported when delivered through a meta element.
+        policy
+            .directives
+            .retain(|directive| match &*directive.name {
+                "report-uri" | "frame-ancestors" | "sandbox" => false,
+                _ => true,
+            });
+        if !policy.directives.is_empty() {
+            self.0.push(policy);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#should-block-request>
+    pub fn should_request_be_blocked(&self, request: &Request) -> (CheckResult, Vec<Violation>) {
+        let mut result = CheckResult::Allowed;
+        let mut violations = vec![];
+        let effective_directive = match effective_directive_for_request(request) {
+            Some(effective_directive) => effective_directive,
+            None => return (result, violations),
+        };
+        let origin = match request.origin {
+            Origin::Origin(ref origin) => origin.clone(),
+            Origin::Client => return (result, violations),
+        };
+        let url = request.current_url();
+        let redirected = request.redirect_count > 0;
+        for policy in &self.0 {
+            let directive = match policy.governing_directive(effective_directive) {
+                Some(directive) => directive,
+                None => continue,
+            };
+            if !url_matches_source_list_nonce_aware(
+                &url,
+                &directive.value,
+                &origin,
+                redirected,
+                &request.cryptographic_nonce_metadata,
+                effective_directive,
+            ) {
+                violations.push(policy.violation(
+                    ViolationResource::Url(url.clone()),
+                    directive,
+                    effective_directive,
+                    None,
+                ));
+                if policy.disposition == PolicyDisposition::Enforce {
+                    result = CheckResult::Blocked;
+                }
+            }
+        }
+        (result, violations)
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#should-block-inline>
+    pub fn should_elements_inline_type_behavior_be_blocked(
+        &self,
+        nonce: &str,
+        type_: InlineCheckType,
+        source: &str,
+    ) -> (CheckResult, Vec<Violation>) {
+        let mut result = CheckResult::Allowed;
+        let mut violations = vec![];
+        let effective_directive = type_.effective_directive();
+        for policy in &self.0 {
+            let directive = match policy.governing_directive(effective_directive) {
+                Some(directive) => directive,
+                None => continue,
+            };
+            if element_matches_source_list(&directive.value, nonce, type_, source) {
+                continue;
+            }
+            let sample = if directive.value.iter().any(|e| e == "'report-sample'") {
+                Some(source.chars().take(SAMPLE_LENGTH).collect())
+            } else {
+                None
+            };
+            violations.push(policy.violation(
+                ViolationResource::Inline,
+                directive,
+                effective_directive,
+                sample,
+            ));
+            if policy.disposition == PolicyDisposition::Enforce {
+                result = CheckResult::Blocked;
+            }
+        }
+        (result, violations)
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#can-compile-strings>
+    pub fn is_js_evaluation_allowed(&self) -> (CheckResult, Vec<Violation>) {
+        let mut result = CheckResult::Allowed;
+        let mut violations = vec![];
+        for policy in &self.0 {
+            let directive = match policy.governing_directive("script-src") {
+                Some(directive) => directive,
+                None => continue,
+            };
+            if directive.value.iter().any(|e| e == "'unsafe-eval'") {
+                continue;
+            }
+            violations.push(policy.violation(
+                ViolationResource::Eval,
+                directive,
+                "script-src",
+                None,
+            ));
+            if policy.disposition == PolicyDisposition::Enforce {
+                result = CheckResult::Blocked;
+            }
+        }
+        (result, violations)
+    }
+
+    /// Check the `frame-ancestors` directive of the policies delivered with a response
+    /// for `url`, given the URLs of the documents of its ancestor browsing contexts.
+    /// <https://w3c.github.io/webappsec-csp/#frame-ancestors-navigation-response>
+    pub fn should_navigation_response_be_blocked(
+        &self,
+        url: &ServoUrl,
+        ancestors: &[ServoUrl],
+    ) -> (CheckResult, Vec<Violation>) {
+        let mut result = CheckResult::Allowed;
+        let mut violations = vec![];
+        let origin = url.origin();
+        for policy in &self.0 {
+            if policy.source != PolicySource::Header {
+                continue;
+            }
+            let directive = match policy.directive("frame-ancestors") {
+                Some(directive) => directive,
+                None => continue,
+            };
+            let blocked_ancestor = ancestors.iter().find(|ancestor| {
+                !url_matches_source_list(ancestor, &directive.value, &origin, false)
+            });
+            if let Some(ancestor) = blocked_ancestor {
+                violations.push(policy.violation(
+                    ViolationResource::Url(ancestor.clone()),
+                    directive,
+                    "frame-ancestors",
+                    None,
+                ));
+                if policy.disposition == PolicyDisposition::Enforce {
+                    result = CheckResult::Blocked;
+                }
+            }
+        }
+        (result, violations)
+    }
+}
+
+fn is_ascii_whitespace(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\x0C' | '\r' | ' ' => true,
+        _ => false,
+    }
+}
+
+fn is_nonce_or_hash_source(expression: &str) -> bool {
+    let expression = expression.to_ascii_lowercase();
+    expression.starts_with("'nonce-") ||
+        expression.starts_with("'sha256-") ||
+        expression.starts_with("'sha384-") ||
+        expression.starts_with("'sha512-")
+}
+
+/// Strip the quotes and prefix from a nonce or hash source, returning its value.
+fn source_value<'a>(expression: &'a str, prefix: &str) -> Option<&'a str> {
+    let has_prefix = expression
+        .get(..prefix.len())
+        .map_or(false, |start| start.eq_ignore_ascii_case(prefix));
+    if expression.len() <= prefix.len() + 1 || !has_prefix || !expression.ends_with('\'') {
+        return None;
+    }
+    Some(&expression[prefix.len()..expression.len() - 1])
+}
+
+/// Like `url_matches_source_list`, but also allowing requests carrying a nonce
+/// listed in a script or style source list.
+/// <https://w3c.github.io/webappsec-csp/#script-pre-request>
+fn url_matches_source_list_nonce_aware(
+    url: &ServoUrl,
+    source_list: &[String],
+    origin: &ImmutableOrigin,
+    redirected: bool,
+    nonce: &str,
+    effective_directive: &str,
+) -> bool {
+    let nonce_applies = effective_directive.starts_with("script-src") ||
+        effective_directive.starts_with("style-src");
+    if nonce_applies && !nonce.is_empty() {
+        let nonce_matches = source_list
+            .iter()
+            .filter_map(|expression| source_value(expression, "'nonce-"))
+            .any(|value| value == nonce);
+        if nonce_matches {
+            return true;
+        }
+    }
+    url_matches_source_list(url, source_list, origin, redirected)
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-url-to-source-list>
+pub fn url_matches_source_list(
+    url: &ServoUrl,
+    source_list: &[String],
+    origin: &ImmutableOrigin,
+    redirected: bool,
+) -> bool {
+    if source_list.is_empty() {
+        return false;
+    }
+    if source_list.len() == 1 && source_list[0].eq_ignore_ascii_case("'none'") {
+        return false;
+    }
+    source_list
+        .iter()
+        .any(|expression| url_matches_expression(url, expression, origin, redirected))
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-url-to-source-expression>
+fn url_matches_expression(
+    url: &ServoUrl,
+    expression: &str,
+    origin: &ImmutableOrigin,
+    redirected: bool,
+) -> bool {
+    let url_scheme = url.scheme();
+    let origin_scheme = match *origin {
+        ImmutableOrigin::Tuple(ref scheme, ..) => Some(&**scheme),
+        ImmutableOrigin::Opaque(..) => None,
+    };
+
+    // Step 1.
+    if expression == "*" {
+        let is_network_scheme = match url_scheme {
+            "http" | "https" | "ws" | "wss" => true,
+            _ => false,
+        };
+        return is_network_scheme || origin_scheme == Some(url_scheme);
+    }
+
+    // Step 2.
+    if expression.ends_with(':') && !expression.contains('/') {
+        return scheme_part_matches(&expression[..expression.len() - 1], url_scheme);
+    }
+
+    // Step 4.
+    if expression.eq_ignore_ascii_case("'self'") {
+        let url_origin = url.origin();
+        if url_origin == *origin {
+            return true;
+        }
+        if let (
+            &ImmutableOrigin::Tuple(ref scheme, ref host, port),
+            &ImmutableOrigin::Tuple(ref url_scheme, ref url_host, url_port),
+        ) = (origin, &url_origin)
+        {
+            // An upgrade from an insecure scheme to its secure counterpart is allowed.
+            let upgraded = (scheme == "http" && (url_scheme == "https" || url_scheme == "wss")) ||
+                (scheme == "ws" && url_scheme == "wss");
+            let default_port = |scheme: &str, port: u16| match (scheme, port) {
+                ("http", 80) | ("ws", 80) | ("https", 443) | ("wss", 443) => true,
+                _ => false,
+            };
+            return host == url_host &&
+                (port == url_port ||
+                    (default_port(scheme, port) && default_port(url_scheme, url_port))) &&
+                (scheme == url_scheme || upgraded);
+        }
+        return false;
+    }
+
+    if expression.starts_with('\'') {
+        // Other keyword, nonce and hash sources never match URLs.
+        return false;
+    }
+
+    // Step 3.
+    match HostSource::parse(expression) {
+        Some(host_source) => host_source.matches(url, origin_scheme, redirected),
+        None => false,
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#grammardef-host-source>
+struct HostSource<'a> {
+    scheme: Option<&'a str>,
+    host: &'a str,
+    port: Option<&'a str>,
+    path: Option<&'a str>,
+}
+
+impl<'a> HostSource<'a> {
+    fn parse(expression: &'a str) -> Option<HostSource<'a>> {
+        let (scheme, rest) = match expression.find("://") {
+            Some(index) => (Some(&expression[..index]), &expression[index + 3..]),
+            None => (None, expression),
+        };
+        let host_end = rest.find(|c| c == ':' || c == '/').unwrap_or(rest.len());
+        let host = &rest[..host_end];
+        if host.is_empty() {
+            return None;
+        }
+        let mut rest = &rest[host_end..];
+        let port = if rest.starts_with(':') {
+            let port_end = rest.find('/').unwrap_or(rest.len());
+            let port = &rest[1..port_end];
+            rest = &rest[port_end..];
+            Some(port)
+        } else {
+            None
+        };
+        let path = if rest.is_empty() { None } else { Some(rest) };
+        Some(HostSource {
+            scheme,
+            host,
+            port,
+            path,
+        })
+    }
+
+    fn matches(&self, url: &ServoUrl, origin_scheme: Option<&str>, redirected: bool) -> bool {
+        let url_scheme = url.scheme();
+        // Step 3.1.
+        match self.scheme {
+            Some(scheme) => {
+                if !scheme_part_matches(scheme, url_scheme) {
+                    return false;
+                }
+            },
+            None => {
+                let allowed = match origin_scheme {
+                    Some(origin_scheme) => {
+                        origin_scheme == url_scheme ||
+                            (origin_scheme == "http" && url_scheme == "https")
+                    },
+                    None => false,
+                };
+                if !allowed {
+                    return false;
+                }
+            },
+        }
+
+        // Step 3.2 - 3.3.
+        let url_host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        if !host_part_matches(self.host, &url_host) {
+            return false;
+        }
+
+        // Step 3.4.
+        if !port_part_matches(self.port, url) {
+            return false;
+        }
+
+        // Step 3.5 - 3.6.
+        match self.path {
+            Some(path) if !redirected => path_part_matches(path, url.path()),
+            _ => true,
+        }
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-schemes>
+fn scheme_part_matches(expression_scheme: &str, url_scheme: &str) -> bool {
+    let expression_scheme = expression_scheme.to_ascii_lowercase();
+    match (&*expression_scheme, url_scheme) {
+        (a, b) if a == b => true,
+        ("http", "https") => true,
+        ("ws", "wss") | ("ws", "http") | ("ws", "https") => true,
+        ("wss", "https") => true,
+        _ => false,
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-hosts>
+fn host_part_matches(expression_host: &str, url_host: &str) -> bool {
+    let expression_host = expression_host.to_ascii_lowercase();
+    if expression_host.starts_with('*') {
+        let remaining = &expression_host[1..];
+        return url_host.ends_with(remaining) && url_host.len() > remaining.len();
+    }
+    expression_host == url_host
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-ports>
+fn port_part_matches(expression_port: Option<&str>, url: &ServoUrl) -> bool {
+    let url_port = url.port_or_known_default();
+    let expression_port = match expression_port {
+        None => return url.port().is_none(),
+        Some("*") => return true,
+        Some(port) => match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => return false,
+        },
+    };
+    if Some(expression_port) == url_port {
+        return true;
+    }
+    expression_port == 80 && url_port == Some(443)
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-paths>
+fn path_part_matches(expression_path: &str, url_path: &str) -> bool {
+    if expression_path.is_empty() || (expression_path == "/" && url_path.is_empty()) {
+        return true;
+    }
+    let decode = |path: &str| {
+        url::percent_encoding::percent_decode(path.as_bytes())
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    let expression_path = decode(expression_path);
+    let url_path = decode(url_path);
+    if expression_path.ends_with('/') {
+        url_path.starts_with(&*expression_path)
+    } else {
+        url_path == expression_path
+    }
+}
+
+/// <https://w3c.github.io/webappsec-csp/#match-element-to-source-list>
+fn element_matches_source_list(
+    source_list: &[String],
+    nonce: &str,
+    type_: InlineCheckType,
+    source: &str,
+) -> bool {
+    let is_attribute = match type_ {
+        InlineCheckType::ScriptAttribute |
+        InlineCheckType::StyleAttribute |
+        InlineCheckType::Navigation => true,
+        InlineCheckType::Script | InlineCheckType::Style => false,
+    };
+
+    // Step 1.
+    let has_nonce_or_hash = source_list.iter().any(|e| is_nonce_or_hash_source(e));
+    let has_strict_dynamic = type_.is_script() &&
+        source_list
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case("'strict-dynamic'"));
+    let has_unsafe_inline = source_list
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case("'unsafe-inline'"));
+    if has_unsafe_inline && !has_nonce_or_hash && !has_strict_dynamic {
+        return true;
+    }
+
+    // Step 2.
+    if !is_attribute && !nonce.is_empty() {
+        let nonce_matches = source_list
+            .iter()
+            .filter_map(|expression| source_value(expression, "'nonce-"))
+            .any(|value| value == nonce);
+        if nonce_matches {
+            return true;
+        }
+    }
+
+    // Step 3 - 5.
+    let unsafe_hashes = source_list
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case("'unsafe-hashes'"));
+    if !is_attribute || unsafe_hashes {
+        for expression in source_list {
+            let (value, digest) = if let Some(value) = source_value(expression, "'sha256-") {
+                (value, base64::encode(&Sha256::digest(source.as_bytes())))
+            } else if let Some(value) = source_value(expression, "'sha384-") {
+                (value, base64::encode(&Sha384::digest(source.as_bytes())))
+            } else if let Some(value) = source_value(expression, "'sha512-") {
+                (value, base64::encode(&Sha512::digest(source.as_bytes())))
+            } else {
+                continue;
+            };
+            // Both base64 and base64url encodings are accepted.
+            let value = value.replace('-', "+").replace('_', "/");
+            if value.trim_end_matches('=') == digest.trim_end_matches('=') {
+                return true;
+            }
+        }
+    }
+
+    false
+}
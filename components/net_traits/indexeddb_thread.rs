@@ -0,0 +1,362 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Messages and types shared between script and the IndexedDB backend thread.
+//!
+//! Values are stored as opaque structured clones, so the backend never needs
+//! to run script: keys, including the keys of every index entry, are computed
+//! by script and sent along with the values they belong to.
+
+use ipc_channel::ipc::IpcSender;
+use servo_url::ImmutableOrigin;
+use std::cmp::Ordering;
+
+/// A [key](https://w3c.github.io/IndexedDB/#key-construct).
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub enum IndexedDBKey {
+    Number(f64),
+    Date(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<IndexedDBKey>),
+}
+
+impl IndexedDBKey {
+    /// The rank of the type of this key, in the order keys of different
+    /// types compare in.
+    fn type_rank(&self) -> u8 {
+        match *self {
+            IndexedDBKey::Number(_) => 0,
+            IndexedDBKey::Date(_) => 1,
+            IndexedDBKey::String(_) => 2,
+            IndexedDBKey::Binary(_) => 3,
+            IndexedDBKey::Array(_) => 4,
+        }
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#compare-two-keys>
+impl Ord for IndexedDBKey {
+    fn cmp(&self, other: &IndexedDBKey) -> Ordering {
+        match (self, other) {
+            (&IndexedDBKey::Number(a), &IndexedDBKey::Number(b)) |
+            (&IndexedDBKey::Date(a), &IndexedDBKey::Date(b)) => {
+                // Keys are never NaN.
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            },
+            (&IndexedDBKey::String(ref a), &IndexedDBKey::String(ref b)) => {
+                // Strings compare by code units, not code points.
+                a.encode_utf16().cmp(b.encode_utf16())
+            },
+            (&IndexedDBKey::Binary(ref a), &IndexedDBKey::Binary(ref b)) => a.cmp(b),
+            (&IndexedDBKey::Array(ref a), &IndexedDBKey::Array(ref b)) => a.cmp(b),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for IndexedDBKey {
+    fn partial_cmp(&self, other: &IndexedDBKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexedDBKey {
+    fn eq(&self, other: &IndexedDBKey) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexedDBKey {}
+
+/// A [key range](https://w3c.github.io/IndexedDB/#range-construct).
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub struct IndexedDBKeyRange {
+    pub lower: Option<IndexedDBKey>,
+    pub upper: Option<IndexedDBKey>,
+    pub lower_open: bool,
+    pub upper_open: bool,
+}
+
+impl IndexedDBKeyRange {
+    /// A range containing every key.
+    pub fn unbounded() -> IndexedDBKeyRange {
+        IndexedDBKeyRange {
+            lower: None,
+            upper: None,
+            lower_open: false,
+            upper_open: false,
+        }
+    }
+
+    /// A range containing only `key`.
+    pub fn only(key: IndexedDBKey) -> IndexedDBKeyRange {
+        IndexedDBKeyRange {
+            lower: Some(key.clone()),
+            upper: Some(key),
+            lower_open: false,
+            upper_open: false,
+        }
+    }
+
+    /// Whether `key` is below the lower bound of this range.
+    pub fn is_below(&self, key: &IndexedDBKey) -> bool {
+        match self.lower {
+            Some(ref lower) if self.lower_open => key <= lower,
+            Some(ref lower) => key < lower,
+            None => false,
+        }
+    }
+
+    /// Whether `key` is above the upper bound of this range.
+    pub fn is_above(&self, key: &IndexedDBKey) -> bool {
+        match self.upper {
+            Some(ref upper) if self.upper_open => key >= upper,
+            Some(ref upper) => key > upper,
+            None => false,
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#in>
+    pub fn contains(&self, key: &IndexedDBKey) -> bool {
+        !self.is_below(key) && !self.is_above(key)
+    }
+
+    /// Whether no key can be in this range.
+    pub fn is_empty(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (&Some(ref lower), &Some(ref upper)) => {
+                lower > upper || (lower == upper && (self.lower_open || self.upper_open))
+            },
+            _ => false,
+        }
+    }
+}
+
+/// A [key path](https://w3c.github.io/IndexedDB/#key-path-construct).
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum KeyPath {
+    String(String),
+    Sequence(Vec<String>),
+}
+
+/// <https://w3c.github.io/IndexedDB/#transaction-mode>
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum IndexedDBTxnMode {
+    Readonly,
+    Readwrite,
+    Versionchange,
+}
+
+/// <https://w3c.github.io/IndexedDB/#cursor-direction>
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum CursorDirection {
+    Next,
+    NextUnique,
+    Prev,
+    PrevUnique,
+}
+
+/// A connection to a database, as handed out by the backend.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct ConnectionId(pub u64);
+
+/// A transaction, identified by its connection and a serial number chosen by
+/// the owner of the connection. Serial number 0 is reserved for upgrade
+/// transactions, which are created by the backend.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct TransactionId {
+    pub connection: ConnectionId,
+    pub serial: u64,
+}
+
+/// The schema of an index.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub key_path: KeyPath,
+    pub unique: bool,
+    pub multi_entry: bool,
+}
+
+/// The schema of an object store.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct ObjectStoreInfo {
+    pub name: String,
+    pub key_path: Option<KeyPath>,
+    pub auto_increment: bool,
+    pub indexes: Vec<IndexInfo>,
+}
+
+/// The schema of a database, as seen by a new connection.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct DatabaseInfo {
+    pub name: String,
+    pub version: u64,
+    pub object_stores: Vec<ObjectStoreInfo>,
+}
+
+/// Failures of backend operations, each corresponding to the `DOMException`
+/// of the same name.
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum BackendError {
+    Constraint,
+    Data,
+    NotFound,
+    Quota,
+    Version,
+    Unknown,
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+/// The keys of an index entry for a record, as computed by script.
+pub type IndexKeys = Vec<(String, Vec<IndexedDBKey>)>;
+
+/// The position of a cursor, and where it has been asked to go next.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CursorPosition {
+    /// The key and primary key of the record the cursor is at, if any.
+    pub current: Option<(IndexedDBKey, IndexedDBKey)>,
+    /// The key passed to `continue()` or `continuePrimaryKey()`.
+    pub target_key: Option<IndexedDBKey>,
+    /// The primary key passed to `continuePrimaryKey()`.
+    pub target_primary_key: Option<IndexedDBKey>,
+    /// How many records to move forward by.
+    pub count: u32,
+}
+
+/// A record found by a cursor.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CursorRecord {
+    pub key: IndexedDBKey,
+    pub primary_key: IndexedDBKey,
+    pub value: Option<Vec<u8>>,
+}
+
+/// An operation run in a transaction. The source of most requests is either an
+/// object store, or one of its indexes if the index name is given.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Operation {
+    Get(String, Option<String>, IndexedDBKeyRange),
+    GetKey(String, Option<String>, IndexedDBKeyRange),
+    GetAll(String, Option<String>, IndexedDBKeyRange, Option<u32>),
+    GetAllKeys(String, Option<String>, IndexedDBKeyRange, Option<u32>),
+    Count(String, Option<String>, IndexedDBKeyRange),
+    /// Store a value with the given key, or a generated one if none is given.
+    Put {
+        store: String,
+        key: Option<IndexedDBKey>,
+        value: Vec<u8>,
+        index_keys: IndexKeys,
+        no_overwrite: bool,
+    },
+    Delete(String, IndexedDBKeyRange),
+    Clear(String),
+    /// Take the next key from the key generator of an object store.
+    GenerateKey(String),
+    /// Move a cursor, returning the record it ends up at.
+    Iterate {
+        store: String,
+        index: Option<String>,
+        range: IndexedDBKeyRange,
+        direction: CursorDirection,
+        position: CursorPosition,
+        key_only: bool,
+    },
+    /// Only allowed in upgrade transactions.
+    CreateObjectStore(String, Option<KeyPath>, bool),
+    DeleteObjectStore(String),
+    RenameObjectStore(String, String),
+    /// Create an index, with the keys of the records already in the store.
+    CreateIndex {
+        store: String,
+        info: IndexInfo,
+        entries: Vec<(IndexedDBKey, Vec<IndexedDBKey>)>,
+    },
+    DeleteIndex(String, String),
+    RenameIndex(String, String, String),
+    /// Read every record of an object store, to compute the keys of a new index.
+    GetAllRecords(String),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum OperationResult {
+    Value(Option<Vec<u8>>),
+    Key(Option<IndexedDBKey>),
+    Values(Vec<Vec<u8>>),
+    Keys(Vec<IndexedDBKey>),
+    Records(Vec<(IndexedDBKey, Vec<u8>)>),
+    Count(u64),
+    Cursor(Option<CursorRecord>),
+    Undefined,
+}
+
+/// Events about a connection, and the open request that created it.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum ConnectionEvent {
+    /// Other connections are still open after being asked to close.
+    Blocked {
+        old_version: u64,
+        new_version: u64,
+    },
+    /// The connection was opened, and its upgrade transaction started.
+    UpgradeNeeded {
+        connection: ConnectionId,
+        old_version: u64,
+        info: DatabaseInfo,
+    },
+    /// The connection was opened without needing an upgrade.
+    Opened {
+        connection: ConnectionId,
+        info: DatabaseInfo,
+    },
+    Error(BackendError),
+    /// Another connection wants to upgrade or delete the database.
+    VersionChange {
+        old_version: u64,
+        new_version: Option<u64>,
+    },
+}
+
+/// Events about a request to delete a database.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum DeleteEvent {
+    Blocked { old_version: u64 },
+    Deleted { old_version: u64 },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum IndexedDBThreadMsg {
+    /// Open a connection to a database, upgrading it to the given version if needed.
+    Open(
+        IpcSender<ConnectionEvent>,
+        ImmutableOrigin,
+        String,
+        Option<u64>,
+    ),
+    /// Delete a database once all connections to it are closed.
+    DeleteDatabase(IpcSender<DeleteEvent>, ImmutableOrigin, String),
+    /// Close a connection, letting its transactions finish.
+    Close(ConnectionId),
+    /// Create a transaction over the given object stores. The sender is
+    /// notified once the transaction can start.
+    CreateTransaction(IpcSender<()>, TransactionId, IndexedDBTxnMode, Vec<String>),
+    /// Run an operation in a started transaction.
+    Operation(
+        IpcSender<BackendResult<OperationResult>>,
+        TransactionId,
+        Operation,
+    ),
+    /// Commit a transaction, making its changes durable.
+    Commit(IpcSender<BackendResult<()>>, TransactionId),
+    /// Abort a transaction, reverting its changes.
+    Abort(IpcSender<()>, TransactionId),
+    /// Send a reply when done cleaning up thread resources and then shut it down.
+    Exit(IpcSender<()>),
+}
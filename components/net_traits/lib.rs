@@ -17,11 +17,15 @@ extern crate serde;
 #[macro_use]
 extern crate url;
 
+use crate::cache_storage_thread::CacheStorageThreadMsg;
+use crate::csp::Violation;
 use crate::filemanager_thread::FileManagerThreadMsg;
+use crate::indexeddb_thread::IndexedDBThreadMsg;
 use crate::request::{Request, RequestBuilder};
-use crate::response::{HttpsState, Response, ResponseInit};
+use crate::response::{HttpsState, Response, ResponseInit, ResponseType};
 use crate::storage_thread::StorageThreadMsg;
 use cookie::Cookie;
+use embedder_traits::DownloadId;
 use headers::{ContentType, HeaderMapExt, ReferrerPolicy as ReferrerPolicyHeader};
 use http::{Error as HttpError, HeaderMap};
 use hyper::Error as HyperError;
@@ -32,14 +36,18 @@ use ipc_channel::router::ROUTER;
 use ipc_channel::Error as IpcError;
 use mime::Mime;
 use msg::constellation_msg::HistoryStateId;
-use servo_url::ServoUrl;
+use servo_url::{ImmutableOrigin, ServoUrl};
+use std::collections::HashSet;
 use std::error::Error;
 use time::precise_time_ns;
 use url::percent_encoding;
 
 pub mod blob_url_store;
+pub mod cache_storage_thread;
+pub mod csp;
 pub mod filemanager_thread;
 pub mod image_cache;
+pub mod indexeddb_thread;
 pub mod pub_domains;
 pub mod quality;
 pub mod request;
@@ -72,6 +80,9 @@ pub enum LoadContext {
 
 #[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
 pub struct CustomResponse {
+    /// The type of the response, whose filter is applied again by the fetch it answers.
+    pub response_type: ResponseType,
+    pub url: Option<ServoUrl>,
     #[ignore_malloc_size_of = "Defined in hyper"]
     #[serde(
         deserialize_with = "::hyper_serde::deserialize",
@@ -89,11 +100,15 @@ pub struct CustomResponse {
 
 impl CustomResponse {
     pub fn new(
+        response_type: ResponseType,
+        url: Option<ServoUrl>,
         headers: HeaderMap,
         raw_status: (StatusCode, String),
         body: Vec<u8>,
     ) -> CustomResponse {
         CustomResponse {
+            response_type: response_type,
+            url: url,
             headers: headers,
             raw_status: raw_status,
             body: body,
@@ -101,10 +116,13 @@ impl CustomResponse {
     }
 }
 
+/// A request a service worker is asked to answer, by a fetch event.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CustomResponseMediator {
-    pub response_chan: IpcSender<Option<CustomResponse>>,
-    pub load_url: ServoUrl,
+    /// Receives `None` when the request should go to the network instead, and
+    /// a network error when the service worker failed to respond to it.
+    pub response_chan: IpcSender<Option<Result<CustomResponse, NetworkError>>>,
+    pub request: RequestBuilder,
 }
 
 /// [Policies](https://w3c.github.io/webappsec-referrer-policy/#referrer-policy-states)
@@ -157,6 +175,7 @@ pub enum FetchResponseMsg {
     ProcessResponse(Result<FetchMetadata, NetworkError>),
     ProcessResponseChunk(Vec<u8>),
     ProcessResponseEOF(Result<ResourceFetchTiming, NetworkError>),
+    ProcessCspViolations(Vec<Violation>),
 }
 
 pub trait FetchTaskTarget {
@@ -182,6 +201,10 @@ pub trait FetchTaskTarget {
     ///
     /// Fired when the response is fully fetched
     fn process_response_eof(&mut self, response: &Response);
+
+    /// Fired when the request violated the Content Security Policy of its client.
+    /// <https://w3c.github.io/webappsec-csp/#report-for-request>
+    fn process_csp_violations(&mut self, request: &Request, violations: Vec<Violation>);
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -210,6 +233,7 @@ pub trait FetchResponseListener {
     fn resource_timing(&self) -> &ResourceFetchTiming;
     fn resource_timing_mut(&mut self) -> &mut ResourceFetchTiming;
     fn submit_resource_timing(&mut self);
+    fn process_csp_violations(&mut self, violations: Vec<Violation>);
 }
 
 impl FetchTaskTarget for IpcSender<FetchResponseMsg> {
@@ -238,6 +262,10 @@ impl FetchTaskTarget for IpcSender<FetchResponseMsg> {
                 .clone())));
         }
     }
+
+    fn process_csp_violations(&mut self, _: &Request, violations: Vec<Violation>) {
+        let _ = self.send(FetchResponseMsg::ProcessCspViolations(violations));
+    }
 }
 
 pub trait Action<Listener> {
@@ -269,6 +297,9 @@ impl<T: FetchResponseListener> Action<T> for FetchResponseMsg {
                     Err(e) => listener.process_response_eof(Err(e)),
                 }
             },
+            FetchResponseMsg::ProcessCspViolations(violations) => {
+                listener.process_csp_violations(violations)
+            },
         }
     }
 }
@@ -300,13 +331,22 @@ where
 pub struct ResourceThreads {
     core_thread: CoreResourceThread,
     storage_thread: IpcSender<StorageThreadMsg>,
+    idb_thread: IpcSender<IndexedDBThreadMsg>,
+    cache_storage_thread: IpcSender<CacheStorageThreadMsg>,
 }
 
 impl ResourceThreads {
-    pub fn new(c: CoreResourceThread, s: IpcSender<StorageThreadMsg>) -> ResourceThreads {
+    pub fn new(
+        c: CoreResourceThread,
+        s: IpcSender<StorageThreadMsg>,
+        i: IpcSender<IndexedDBThreadMsg>,
+        cs: IpcSender<CacheStorageThreadMsg>,
+    ) -> ResourceThreads {
         ResourceThreads {
             core_thread: c,
             storage_thread: s,
+            idb_thread: i,
+            cache_storage_thread: cs,
         }
     }
 }
@@ -331,6 +371,26 @@ impl IpcSend<StorageThreadMsg> for ResourceThreads {
     }
 }
 
+impl IpcSend<IndexedDBThreadMsg> for ResourceThreads {
+    fn send(&self, msg: IndexedDBThreadMsg) -> IpcSendResult {
+        self.idb_thread.send(msg)
+    }
+
+    fn sender(&self) -> IpcSender<IndexedDBThreadMsg> {
+        self.idb_thread.clone()
+    }
+}
+
+impl IpcSend<CacheStorageThreadMsg> for ResourceThreads {
+    fn send(&self, msg: CacheStorageThreadMsg) -> IpcSendResult {
+        self.cache_storage_thread.send(msg)
+    }
+
+    fn sender(&self) -> IpcSender<CacheStorageThreadMsg> {
+        self.cache_storage_thread.clone()
+    }
+}
+
 // Ignore the sub-fields
 malloc_size_of_is_0!(ResourceThreads);
 
@@ -396,16 +456,36 @@ pub enum CoreResourceMsg {
         CookieSource,
     ),
     DeleteCookies(ServoUrl),
+    /// Retrieve all cookies of a domain and its subdomains, or of all domains if none is given
+    GetCookiesForDomain(Option<String>, IpcSender<Vec<Serde<Cookie<'static>>>>),
+    /// Remove all cookies of a domain and its subdomains, or of all domains if none is given
+    RemoveCookiesForDomain(Option<String>),
     /// Get a history state by a given history state id
     GetHistoryState(HistoryStateId, IpcSender<Option<Vec<u8>>>),
     /// Set a history state for a given history state id
     SetHistoryState(HistoryStateId, Vec<u8>),
     /// Removes history states for the given ids
     RemoveHistoryStates(Vec<HistoryStateId>),
+    /// Start handling the messages sent to the core thread of a new ephemeral session,
+    /// which keeps all of its state in memory, isolated from every other session.
+    /// Sending `Exit` to its core thread discards it.
+    NewEphemeralSession(IpcReceiver<CoreResourceMsg>),
+    /// Fetch a resource to a file in the download directory, rather than to a document,
+    /// optionally suggesting a name for the file
+    Download(RequestBuilder, Option<String>),
+    /// Stop a download, keeping what was received so far
+    PauseDownload(DownloadId),
+    /// Restart a paused download where it stopped
+    ResumeDownload(DownloadId),
+    /// Stop a download, and remove what was received so far
+    CancelDownload(DownloadId),
     /// Synchronization message solely for knowing the state of the ResourceChannelManager loop
     Synchronize(IpcSender<()>),
     /// Send the network sender in constellation to CoreResourceThread
     NetworkMediator(IpcSender<CustomResponseMediator>),
+    /// The origins with an activated service worker: only the requests of their
+    /// documents are sent to the service worker manager.
+    ServiceWorkerOrigins(HashSet<ImmutableOrigin>),
     /// Message forwarded to file manager's handler
     ToFileManager(FileManagerThreadMsg),
     /// Break the load handler loop, send a reply when done cleaning up local resources
@@ -442,6 +522,7 @@ pub struct ResourceCorsData {
 #[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
 pub struct ResourceFetchTiming {
     pub domain_lookup_start: u64,
+    pub domain_lookup_end: u64,
     pub timing_type: ResourceTimingType,
     /// Number of redirects until final resource (currently limited to 20)
     pub redirect_count: u16,
@@ -452,6 +533,7 @@ pub struct ResourceFetchTiming {
     pub redirect_start: u64,
     // pub redirect_end: u64,
     pub connect_start: u64,
+    pub secure_connection_start: u64,
     pub connect_end: u64,
 }
 
@@ -463,12 +545,14 @@ pub enum RedirectStartValue {
 
 pub enum ResourceAttribute {
     RedirectCount(u16),
-    DomainLookupStart,
+    DomainLookupStart(u64),
+    DomainLookupEnd(u64),
     RequestStart,
     ResponseStart,
     RedirectStart(RedirectStartValue),
     FetchStart,
     ConnectStart(u64),
+    SecureConnectionStart(u64),
     ConnectEnd(u64),
     ResponseEnd,
 }
@@ -486,12 +570,14 @@ impl ResourceFetchTiming {
         ResourceFetchTiming {
             timing_type: timing_type,
             domain_lookup_start: 0,
+            domain_lookup_end: 0,
             redirect_count: 0,
             request_start: 0,
             response_start: 0,
             fetch_start: 0,
             redirect_start: 0,
             connect_start: 0,
+            secure_connection_start: 0,
             connect_end: 0,
             response_end: 0,
         }
@@ -501,7 +587,8 @@ impl ResourceFetchTiming {
     // time origin (as described in Performance::now)
     pub fn set_attribute(&mut self, attribute: ResourceAttribute) {
         match attribute {
-            ResourceAttribute::DomainLookupStart => self.domain_lookup_start = precise_time_ns(),
+            ResourceAttribute::DomainLookupStart(val) => self.domain_lookup_start = val,
+            ResourceAttribute::DomainLookupEnd(val) => self.domain_lookup_end = val,
             ResourceAttribute::RedirectCount(count) => self.redirect_count = count,
             ResourceAttribute::RequestStart => self.request_start = precise_time_ns(),
             ResourceAttribute::ResponseStart => self.response_start = precise_time_ns(),
@@ -515,6 +602,7 @@ impl ResourceFetchTiming {
             },
             ResourceAttribute::FetchStart => self.fetch_start = precise_time_ns(),
             ResourceAttribute::ConnectStart(val) => self.connect_start = val,
+            ResourceAttribute::SecureConnectionStart(val) => self.secure_connection_start = val,
             ResourceAttribute::ConnectEnd(val) => self.connect_end = val,
             ResourceAttribute::ResponseEnd => self.response_end = precise_time_ns(),
         }
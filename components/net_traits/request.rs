@@ -2,11 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::csp::CspList;
 use crate::ReferrerPolicy;
 use crate::ResourceTimingType;
 use http::HeaderMap;
 use hyper::Method;
-use msg::constellation_msg::PipelineId;
+use msg::constellation_msg::{PipelineId, TopLevelBrowsingContextId};
 use servo_url::{ImmutableOrigin, ServoUrl};
 
 /// An [initiator](https://fetch.spec.whatwg.org/#concept-request-initiator)
@@ -27,6 +28,7 @@ pub enum Destination {
     Document,
     Embed,
     Font,
+    IFrame,
     Image,
     Manifest,
     Object,
@@ -142,6 +144,20 @@ pub enum ParserMetadata {
     NotParserInserted,
 }
 
+/// The outcome of the [mixed content](https://w3c.github.io/webappsec-mixed-content/)
+/// checks for a request.
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize)]
+pub enum MixedContent {
+    /// Blockable mixed content, which is never fetched.
+    Blocked,
+    /// Optionally-blockable mixed content that was upgraded to a potentially
+    /// trustworthy URL.
+    Upgraded,
+    /// Optionally-blockable mixed content that could not be upgraded, and was
+    /// fetched as is.
+    Allowed,
+}
+
 #[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
 pub struct RequestBuilder {
     #[serde(
@@ -173,11 +189,16 @@ pub struct RequestBuilder {
     pub referrer: Option<Referrer>,
     pub referrer_policy: Option<ReferrerPolicy>,
     pub pipeline_id: Option<PipelineId>,
+    /// The browser the request is made for, whose user is asked for credentials.
+    pub top_level_browsing_context_id: Option<TopLevelBrowsingContextId>,
     pub redirect_mode: RedirectMode,
     pub integrity_metadata: String,
     // to keep track of redirects
     pub url_list: Vec<ServoUrl>,
     pub parser_metadata: ParserMetadata,
+    pub cryptographic_nonce_metadata: String,
+    pub csp_list: Option<CspList>,
+    pub has_trustworthy_ancestor_origin: bool,
 }
 
 impl RequestBuilder {
@@ -200,10 +221,14 @@ impl RequestBuilder {
             referrer: None,
             referrer_policy: None,
             pipeline_id: None,
+            top_level_browsing_context_id: TopLevelBrowsingContextId::installed(),
             redirect_mode: RedirectMode::Follow,
             integrity_metadata: "".to_owned(),
             url_list: vec![],
             parser_metadata: ParserMetadata::Default,
+            cryptographic_nonce_metadata: "".to_owned(),
+            csp_list: None,
+            has_trustworthy_ancestor_origin: false,
         }
     }
 
@@ -277,6 +302,14 @@ impl RequestBuilder {
         self
     }
 
+    pub fn top_level_browsing_context_id(
+        mut self,
+        top_level_browsing_context_id: Option<TopLevelBrowsingContextId>,
+    ) -> RequestBuilder {
+        self.top_level_browsing_context_id = top_level_browsing_context_id;
+        self
+    }
+
     pub fn redirect_mode(mut self, redirect_mode: RedirectMode) -> RequestBuilder {
         self.redirect_mode = redirect_mode;
         self
@@ -292,6 +325,24 @@ impl RequestBuilder {
         self
     }
 
+    pub fn cryptographic_nonce_metadata(mut self, nonce: String) -> RequestBuilder {
+        self.cryptographic_nonce_metadata = nonce;
+        self
+    }
+
+    pub fn csp_list(mut self, csp_list: Option<CspList>) -> RequestBuilder {
+        self.csp_list = csp_list;
+        self
+    }
+
+    pub fn has_trustworthy_ancestor_origin(
+        mut self,
+        has_trustworthy_ancestor_origin: bool,
+    ) -> RequestBuilder {
+        self.has_trustworthy_ancestor_origin = has_trustworthy_ancestor_origin;
+        self
+    }
+
     pub fn build(self) -> Request {
         let mut request = Request::new(
             self.url.clone(),
@@ -303,6 +354,7 @@ impl RequestBuilder {
         request.unsafe_request = self.unsafe_request;
         request.body = self.body;
         request.service_workers_mode = self.service_workers_mode;
+        request.top_level_browsing_context_id = self.top_level_browsing_context_id;
         request.destination = self.destination;
         request.synchronous = self.synchronous;
         request.mode = self.mode;
@@ -321,6 +373,9 @@ impl RequestBuilder {
         request.url_list = url_list;
         request.integrity_metadata = self.integrity_metadata;
         request.parser_metadata = self.parser_metadata;
+        request.cryptographic_nonce_metadata = self.cryptographic_nonce_metadata;
+        request.csp_list = self.csp_list;
+        request.has_trustworthy_ancestor_origin = self.has_trustworthy_ancestor_origin;
         request
     }
 }
@@ -362,6 +417,8 @@ pub struct Request {
     /// <https://fetch.spec.whatwg.org/#concept-request-referrer-policy>
     pub referrer_policy: Option<ReferrerPolicy>,
     pub pipeline_id: Option<PipelineId>,
+    /// The browser the request is made for, whose user is asked for credentials.
+    pub top_level_browsing_context_id: Option<TopLevelBrowsingContextId>,
     /// <https://fetch.spec.whatwg.org/#synchronous-flag>
     pub synchronous: bool,
     /// <https://fetch.spec.whatwg.org/#concept-request-mode>
@@ -388,6 +445,18 @@ pub struct Request {
     pub response_tainting: ResponseTainting,
     /// <https://fetch.spec.whatwg.org/#concept-request-parser-metadata>
     pub parser_metadata: ParserMetadata,
+    /// <https://fetch.spec.whatwg.org/#concept-request-nonce-metadata>
+    pub cryptographic_nonce_metadata: String,
+    /// The [CSP list](https://html.spec.whatwg.org/multipage/#concept-settings-object-csp-list)
+    /// of the request's client.
+    pub csp_list: Option<CspList>,
+    /// Whether the document of an ancestor of the request's client has a potentially
+    /// trustworthy origin, which prohibits mixed content in nested browsing contexts.
+    /// <https://w3c.github.io/webappsec-mixed-content/#categorize-settings-object>
+    pub has_trustworthy_ancestor_origin: bool,
+    /// The outcome of the mixed content checks for this request, if it is
+    /// mixed content.
+    pub mixed_content: Option<MixedContent>,
 }
 
 impl Request {
@@ -408,6 +477,7 @@ impl Request {
             referrer: Referrer::Client,
             referrer_policy: None,
             pipeline_id: pipeline_id,
+            top_level_browsing_context_id: TopLevelBrowsingContextId::installed(),
             synchronous: false,
             mode: RequestMode::NoCors,
             use_cors_preflight: false,
@@ -420,6 +490,10 @@ impl Request {
             parser_metadata: ParserMetadata::Default,
             redirect_count: 0,
             response_tainting: ResponseTainting::Basic,
+            cryptographic_nonce_metadata: String::new(),
+            csp_list: None,
+            has_trustworthy_ancestor_origin: false,
+            mixed_content: None,
         }
     }
 
@@ -440,7 +514,7 @@ impl Request {
 
     /// <https://fetch.spec.whatwg.org/#navigation-request>
     pub fn is_navigation_request(&self) -> bool {
-        self.destination == Destination::Document
+        self.destination == Destination::Document || self.destination == Destination::IFrame
     }
 
     /// <https://fetch.spec.whatwg.org/#subresource-request>
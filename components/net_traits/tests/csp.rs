@@ -0,0 +1,236 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use http::header::{HeaderMap, HeaderValue};
+use net_traits::csp::{CheckResult, CspList, InlineCheckType, Policy};
+use net_traits::csp::{PolicyDisposition, PolicySource};
+use net_traits::request::{Destination, Origin, Request};
+use servo_url::ServoUrl;
+
+fn policy_list(serialized: &str) -> CspList {
+    CspList(vec![Policy::parse(
+        serialized,
+        PolicySource::Header,
+        PolicyDisposition::Enforce,
+    )])
+}
+
+fn check_request(csp_list: &CspList, url: &str, destination: Destination) -> CheckResult {
+    let origin = ServoUrl::parse("https://example.com/").unwrap().origin();
+    let mut request = Request::new(
+        ServoUrl::parse(url).unwrap(),
+        Some(Origin::Origin(origin)),
+        None,
+    );
+    request.destination = destination;
+    csp_list.should_request_be_blocked(&request).0
+}
+
+#[test]
+fn test_parse_policy() {
+    let policy = Policy::parse(
+        " Script-Src 'self' https://cdn.example.com ; img-src * ; script-src 'none';",
+        PolicySource::Header,
+        PolicyDisposition::Enforce,
+    );
+    assert_eq!(policy.directives.len(), 2);
+    assert_eq!(policy.directives[0].name, "script-src");
+    assert_eq!(
+        policy.directives[0].value,
+        vec!["'self'", "https://cdn.example.com"]
+    );
+    assert_eq!(policy.directives[1].name, "img-src");
+    assert_eq!(policy.directives[1].value, vec!["*"]);
+}
+
+#[test]
+fn test_parse_response_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append(
+        "content-security-policy",
+        HeaderValue::from_static("default-src 'self', img-src *"),
+    );
+    headers.append(
+        "content-security-policy-report-only",
+        HeaderValue::from_static("script-src 'none'"),
+    );
+    let csp_list = CspList::parse_response_headers(&headers);
+    assert_eq!(csp_list.0.len(), 3);
+    assert_eq!(csp_list.0[0].disposition, PolicyDisposition::Enforce);
+    assert_eq!(csp_list.0[1].disposition, PolicyDisposition::Enforce);
+    assert_eq!(csp_list.0[2].disposition, PolicyDisposition::Report);
+}
+
+#[test]
+fn test_meta_policy_ignores_unsupported_directives() {
+    let mut csp_list = CspList::default();
+    csp_list.append_meta_policy("frame-ancestors 'none'; report-uri /report");
+    assert!(csp_list.is_empty());
+    csp_list.append_meta_policy("frame-ancestors 'none'; img-src 'self'");
+    assert_eq!(csp_list.0.len(), 1);
+    assert_eq!(csp_list.0[0].directives.len(), 1);
+    assert_eq!(csp_list.0[0].source, PolicySource::Meta);
+}
+
+#[test]
+fn test_request_matching_source_list() {
+    let csp_list = policy_list("default-src 'self'; img-src https://*.images.com:* data:");
+    let result = |url| check_request(&csp_list, url, Destination::Image);
+    assert_eq!(result("https://a.images.com/cat.png"), CheckResult::Allowed);
+    assert_eq!(
+        result("https://a.images.com:8443/cat.png"),
+        CheckResult::Allowed
+    );
+    assert_eq!(result("data:image/png,"), CheckResult::Allowed);
+    assert_eq!(result("https://images.com/cat.png"), CheckResult::Blocked);
+    assert_eq!(result("http://a.images.com/cat.png"), CheckResult::Blocked);
+    assert_eq!(result("https://example.com/cat.png"), CheckResult::Blocked);
+
+    // Scripts fall back to default-src.
+    let result = |url| check_request(&csp_list, url, Destination::Script);
+    assert_eq!(result("https://example.com/app.js"), CheckResult::Allowed);
+    assert_eq!(
+        result("https://cdn.example.com/app.js"),
+        CheckResult::Blocked
+    );
+}
+
+#[test]
+fn test_frame_src() {
+    let csp_list = policy_list("default-src 'none'; child-src https://frames.com");
+    let result = |url, destination| check_request(&csp_list, url, destination);
+    // Navigations of nested browsing contexts fall back to child-src.
+    assert_eq!(
+        result("https://frames.com/", Destination::IFrame),
+        CheckResult::Allowed
+    );
+    assert_eq!(
+        result("https://example.com/", Destination::IFrame),
+        CheckResult::Blocked
+    );
+    // Top-level navigations aren't restricted.
+    assert_eq!(
+        result("https://example.com/", Destination::Document),
+        CheckResult::Allowed
+    );
+
+    let csp_list = policy_list("frame-src 'self'; child-src https://frames.com");
+    let result = |url| check_request(&csp_list, url, Destination::IFrame);
+    assert_eq!(result("https://example.com/frame"), CheckResult::Allowed);
+    assert_eq!(result("https://frames.com/"), CheckResult::Blocked);
+}
+
+#[test]
+fn test_request_matching_path() {
+    let csp_list = policy_list("script-src https://example.com/js/ https://example.com/one.js");
+    let result = |url| check_request(&csp_list, url, Destination::Script);
+    assert_eq!(
+        result("https://example.com/js/app.js"),
+        CheckResult::Allowed
+    );
+    assert_eq!(result("https://example.com/one.js"), CheckResult::Allowed);
+    assert_eq!(result("https://example.com/two.js"), CheckResult::Blocked);
+    assert_eq!(
+        result("https://example.com/jsx/app.js"),
+        CheckResult::Blocked
+    );
+}
+
+#[test]
+fn test_report_only_policy_does_not_block() {
+    let csp_list = CspList(vec![Policy::parse(
+        "img-src 'none'",
+        PolicySource::Header,
+        PolicyDisposition::Report,
+    )]);
+    let origin = ServoUrl::parse("https://example.com/").unwrap().origin();
+    let mut request = Request::new(
+        ServoUrl::parse("https://example.com/cat.png").unwrap(),
+        Some(Origin::Origin(origin)),
+        None,
+    );
+    request.destination = Destination::Image;
+    let (result, violations) = csp_list.should_request_be_blocked(&request);
+    assert_eq!(result, CheckResult::Allowed);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].effective_directive, "img-src");
+}
+
+#[test]
+fn test_inline_script() {
+    let csp_list = policy_list(
+        "script-src 'nonce-abc123' 'sha256-5jFwrAK0UV47oFbVg/iCCBbxD8X1w+QvoOUepu4C2YA='",
+    );
+    let result = |nonce, source| {
+        csp_list
+            .should_elements_inline_type_behavior_be_blocked(nonce, InlineCheckType::Script, source)
+            .0
+    };
+    assert_eq!(result("abc123", "anything()"), CheckResult::Allowed);
+    assert_eq!(result("", "alert(1);"), CheckResult::Allowed);
+    assert_eq!(result("", "alert(2);"), CheckResult::Blocked);
+    assert_eq!(result("wrong", "alert(2);"), CheckResult::Blocked);
+
+    // Nonces and hashes disable 'unsafe-inline'.
+    let csp_list = policy_list("script-src 'unsafe-inline' 'nonce-abc123'");
+    let (result, _) = csp_list.should_elements_inline_type_behavior_be_blocked(
+        "",
+        InlineCheckType::Script,
+        "alert(2);",
+    );
+    assert_eq!(result, CheckResult::Blocked);
+
+    let csp_list = policy_list("script-src 'unsafe-inline'");
+    let (result, _) = csp_list.should_elements_inline_type_behavior_be_blocked(
+        "",
+        InlineCheckType::ScriptAttribute,
+        "alert(2);",
+    );
+    assert_eq!(result, CheckResult::Allowed);
+
+    // Source expressions with non-ASCII characters where a prefix is expected are ignored.
+    let csp_list = policy_list("script-src 'nonce\u{e9}abc' 'sha256\u{e9}abc'");
+    let (result, _) = csp_list.should_elements_inline_type_behavior_be_blocked(
+        "abc",
+        InlineCheckType::Script,
+        "alert(2);",
+    );
+    assert_eq!(result, CheckResult::Blocked);
+}
+
+#[test]
+fn test_eval() {
+    let csp_list = policy_list("default-src 'self'");
+    assert_eq!(csp_list.is_js_evaluation_allowed().0, CheckResult::Blocked);
+    let csp_list = policy_list("script-src 'self' 'unsafe-eval'");
+    assert_eq!(csp_list.is_js_evaluation_allowed().0, CheckResult::Allowed);
+    let csp_list = policy_list("img-src 'none'");
+    assert_eq!(csp_list.is_js_evaluation_allowed().0, CheckResult::Allowed);
+}
+
+#[test]
+fn test_frame_ancestors() {
+    let csp_list = policy_list("frame-ancestors 'self' https://trusted.com");
+    let url = ServoUrl::parse("https://example.com/frame.html").unwrap();
+    let ancestors = |urls: &[&str]| -> Vec<ServoUrl> {
+        urls.iter()
+            .map(|url| ServoUrl::parse(url).unwrap())
+            .collect()
+    };
+    let result = |urls: &[&str]| {
+        csp_list
+            .should_navigation_response_be_blocked(&url, &ancestors(urls))
+            .0
+    };
+    assert_eq!(result(&[]), CheckResult::Allowed);
+    assert_eq!(result(&["https://example.com/"]), CheckResult::Allowed);
+    assert_eq!(
+        result(&["https://example.com/", "https://trusted.com/"]),
+        CheckResult::Allowed
+    );
+    assert_eq!(
+        result(&["https://example.com/", "https://evil.com/"]),
+        CheckResult::Blocked
+    );
+}
@@ -4,15 +4,18 @@
 
 use crate::compartments::{AlreadyInCompartment, InCompartment};
 use crate::dom::bindings::codegen::Bindings::FormDataBinding::FormDataMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseBodyInit;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::DomRoot;
-use crate::dom::bindings::str::USVString;
+use crate::dom::bindings::str::{DOMString, USVString};
 use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::blob::{Blob, BlobImpl};
 use crate::dom::formdata::FormData;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::promise::Promise;
+use crate::dom::readablestream::ReadableStream;
+use crate::dom::xmlhttprequest::Extractable;
 use js::jsapi::Heap;
 use js::jsapi::JSContext;
 use js::jsapi::JSObject;
@@ -39,6 +42,34 @@ pub enum BodyType {
     ArrayBuffer,
 }
 
+/// The body extracted from a `ResponseBodyInit`.
+pub enum ExtractedBody {
+    Bytes(Vec<u8>),
+    Stream(DomRoot<ReadableStream>),
+}
+
+// https://fetch.spec.whatwg.org/#concept-bodyinit-extract
+pub fn extract_body(init: &ResponseBodyInit) -> Fallible<(ExtractedBody, Option<DOMString>)> {
+    let (bytes, content_type) = match *init {
+        ResponseBodyInit::ReadableStream(ref stream) => {
+            // Step 3
+            if stream.is_disturbed() || stream.is_locked() {
+                return Err(Error::Type(
+                    "The body stream is disturbed or locked".to_string(),
+                ));
+            }
+            return Ok((ExtractedBody::Stream(stream.clone()), None));
+        },
+        ResponseBodyInit::String(ref s) => s.extract(),
+        ResponseBodyInit::URLSearchParams(ref usp) => usp.extract(),
+        ResponseBodyInit::Blob(ref b) => b.extract(),
+        ResponseBodyInit::FormData(ref formdata) => formdata.extract(),
+        ResponseBodyInit::ArrayBuffer(ref typedarray) => (typedarray.to_vec(), None),
+        ResponseBodyInit::ArrayBufferView(ref typedarray) => (typedarray.to_vec(), None),
+    };
+    Ok((ExtractedBody::Bytes(bytes), content_type))
+}
+
 pub enum FetchedData {
     Text(String),
     Json(RootedTraceableBox<Heap<JSValue>>),
@@ -68,7 +99,8 @@ pub fn consume_body<T: BodyOperations + DomObject>(object: &T, body_type: BodyTy
     object.set_body_promise(&promise, body_type);
 
     // Steps 2-4
-    // TODO: Body does not yet have a stream.
+    // The stream of the body, if it has one, is read by `set_body_promise`,
+    // and the promise is fulfilled once it is done.
 
     consume_body_with_promise(object, body_type, &promise);
 
@@ -0,0 +1,242 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Script debugging for the devtools server, with SpiderMonkey's Debugger API.
+//!
+//! The Debugger API is only available to JS, and cannot debug the globals of its own
+//! compartment, so each script thread runs `resources/debugger.js` in a global of its own,
+//! which every window of the thread is a debuggee of. Requests and replies are exchanged with
+//! it as JSON. When it pauses, the script thread only handles devtools messages until the
+//! devtools server resumes it.
+
+use crate::dom::bindings::conversions::jsstring_to_str;
+use crate::dom::bindings::reflector::DomObject;
+use crate::dom::bindings::utils::AsCCharPtrPtr;
+use crate::dom::globalscope::GlobalScope;
+use crate::script_thread::ScriptThread;
+use devtools_traits::DevtoolScriptControlMsg;
+use embedder_traits::resources::{self, Resource};
+use js::conversions::ToJSValConvertible;
+use js::jsapi::RealmOptions;
+use js::jsapi::{CallArgs, HandleValueArray, Heap, JSAutoRealm, JSContext, JSObject};
+use js::jsapi::{JS_ClearPendingException, JS_NewGlobalObject, OnNewGlobalHookOption};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::wrappers::{EvaluateUtf8, JS_CallFunctionName, JS_DefineDebuggerObject};
+use js::rust::wrappers::{JS_DefineFunction, JS_WrapValue};
+use js::rust::SIMPLE_GLOBAL_CLASS;
+use js::rust::{CompileOptionsWrapper, HandleValue, MutableHandleValue, Runtime};
+use msg::constellation_msg::PipelineId;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ffi::CString;
+use std::ptr;
+
+/// The debugger of the windows of a script thread.
+#[derive(JSTraceable)]
+pub struct ScriptDebugger {
+    /// The global `resources/debugger.js` runs in.
+    global: Box<Heap<*mut JSObject>>,
+}
+
+impl ScriptDebugger {
+    #[allow(unsafe_code)]
+    pub fn new(cx: *mut JSContext) -> ScriptDebugger {
+        unsafe {
+            let mut options = RealmOptions::default();
+            options.creationOptions_.invisibleToDebugger_ = true;
+            rooted!(in(cx) let global = JS_NewGlobalObject(
+                cx,
+                &SIMPLE_GLOBAL_CLASS,
+                ptr::null_mut(),
+                OnNewGlobalHookOption::FireOnNewGlobalHook,
+                &options,
+            ));
+            assert!(!global.is_null());
+
+            let _ac = JSAutoRealm::new(cx, global.get());
+            assert!(JS_DefineDebuggerObject(cx, global.handle()));
+            assert!(!JS_DefineFunction(
+                cx,
+                global.handle(),
+                b"pauseThread\0".as_c_char_ptr(),
+                Some(pause_thread),
+                2,
+                0,
+            )
+            .is_null());
+
+            let source = resources::read_string(Resource::DebuggerJS);
+            let filename = CString::new("debugger.js").unwrap();
+            let options = CompileOptionsWrapper::new(cx, filename.as_ptr(), 1);
+            rooted!(in(cx) let mut rval = UndefinedValue());
+            if !EvaluateUtf8(
+                cx,
+                options.ptr,
+                source.as_ptr() as *const _,
+                source.len() as libc::size_t,
+                rval.handle_mut(),
+            ) {
+                JS_ClearPendingException(cx);
+                error!("Failed to run the debugger script.");
+            }
+
+            ScriptDebugger {
+                global: Heap::boxed(global.get()),
+            }
+        }
+    }
+
+    /// Debug the window of the given pipeline, before it runs any script.
+    #[allow(unsafe_code)]
+    pub fn add_debuggee(&self, pipeline_id: PipelineId, global: &GlobalScope) {
+        let cx = global.get_cx();
+        unsafe {
+            let _ac = JSAutoRealm::new(cx, self.global.get());
+            rooted!(in(cx) let mut debuggee = ObjectValue(global.reflector().get_jsobject().get()));
+            if !JS_WrapValue(cx, debuggee.handle_mut()) {
+                return JS_ClearPendingException(cx);
+            }
+            rooted!(in(cx) let mut pipeline = UndefinedValue());
+            pipeline_key(pipeline_id).to_jsval(cx, pipeline.handle_mut());
+            rooted!(in(cx) let mut rval = UndefinedValue());
+            self.call(
+                cx,
+                "addDebuggee",
+                &[pipeline.get(), debuggee.get()],
+                rval.handle_mut(),
+            );
+        }
+    }
+
+    /// Stop debugging the window of the given pipeline, once it is discarded.
+    pub fn remove_debuggee(&self, pipeline_id: PipelineId) {
+        let _: Option<()> = self.request("removeDebuggee", (pipeline_key(pipeline_id),));
+    }
+
+    pub fn handle_msg(&self, msg: DevtoolScriptControlMsg) {
+        match msg {
+            DevtoolScriptControlMsg::ListSources(id, reply) => {
+                let sources = self.request("listSources", (pipeline_key(id),));
+                let _ = reply.send(sources.unwrap_or_default());
+            },
+            DevtoolScriptControlMsg::GetSourceText(id, source, reply) => {
+                let text = self.request("sourceText", (pipeline_key(id), source));
+                let _ = reply.send(text.and_then(|text| text));
+            },
+            DevtoolScriptControlMsg::SetBreakpoint(id, location) => {
+                let _: Option<()> = self.request("setBreakpoint", (pipeline_key(id), location));
+            },
+            DevtoolScriptControlMsg::RemoveBreakpoint(id, location) => {
+                let _: Option<()> = self.request("removeBreakpoint", (pipeline_key(id), location));
+            },
+            DevtoolScriptControlMsg::PauseOnExceptions(id, enabled) => {
+                let _: Option<()> = self.request("pauseOnExceptions", (pipeline_key(id), enabled));
+            },
+            DevtoolScriptControlMsg::Interrupt(id) => {
+                let _: Option<()> = self.request("interrupt", (pipeline_key(id),));
+            },
+            // The pipeline is not paused, since the script thread handles this itself while
+            // it is.
+            DevtoolScriptControlMsg::Resume(..) => {},
+            DevtoolScriptControlMsg::GetFrames(id, reply) => {
+                let frames = self.request("frames", (pipeline_key(id),));
+                let _ = reply.send(frames.unwrap_or_default());
+            },
+            DevtoolScriptControlMsg::GetEnvironments(id, depth, reply) => {
+                let environments = self.request("environments", (pipeline_key(id), depth));
+                let _ = reply.send(environments.unwrap_or_default());
+            },
+            DevtoolScriptControlMsg::GetObjectProperties(id, object, reply) => {
+                let properties = self.request("objectProperties", (pipeline_key(id), object));
+                let _ = reply.send(properties.and_then(|properties| properties));
+            },
+            msg => warn!("Unexpected devtools message for the debugger: {:?}", msg),
+        }
+    }
+
+    /// Call the request handler of the given name with the given arguments, as JSON.
+    #[allow(unsafe_code)]
+    fn request<A: Serialize, T: DeserializeOwned>(&self, name: &str, args: A) -> Option<T> {
+        let cx = Runtime::get();
+        unsafe {
+            let _ac = JSAutoRealm::new(cx, self.global.get());
+            rooted!(in(cx) let mut name_value = UndefinedValue());
+            name.to_jsval(cx, name_value.handle_mut());
+            rooted!(in(cx) let mut args_value = UndefinedValue());
+            serde_json::to_string(&args)
+                .unwrap()
+                .to_jsval(cx, args_value.handle_mut());
+            rooted!(in(cx) let mut rval = UndefinedValue());
+            if !self.call(
+                cx,
+                "handleRequest",
+                &[name_value.get(), args_value.get()],
+                rval.handle_mut(),
+            ) || !rval.is_string()
+            {
+                return None;
+            }
+            let reply = jsstring_to_str(cx, rval.to_string());
+            match serde_json::from_str(&reply) {
+                Ok(reply) => Some(reply),
+                Err(error) => {
+                    warn!("Invalid reply of the debugger to {}: {}", name, error);
+                    None
+                },
+            }
+        }
+    }
+
+    /// Call a function of the debugger global, which must be the current global.
+    #[allow(unsafe_code)]
+    unsafe fn call(
+        &self,
+        cx: *mut JSContext,
+        name: &str,
+        args: &[JSVal],
+        rval: MutableHandleValue,
+    ) -> bool {
+        rooted!(in(cx) let global = self.global.get());
+        let name_cstring = CString::new(name).unwrap();
+        let args = HandleValueArray::from_rooted_slice(args);
+        if !JS_CallFunctionName(cx, global.handle(), name_cstring.as_ptr(), &args, rval) {
+            JS_ClearPendingException(cx);
+            warn!("The debugger failed to run {}.", name);
+            return false;
+        }
+        true
+    }
+}
+
+/// How the debugger script refers to a pipeline.
+fn pipeline_key(pipeline_id: PipelineId) -> String {
+    serde_json::to_string(&pipeline_id).unwrap()
+}
+
+/// The `pauseThread(pipeline, pauseInfo)` function of the debugger global, which handles
+/// devtools messages until the devtools server resumes the pipeline, and returns the
+/// `ResumeLimit` it was resumed with.
+#[allow(unsafe_code)]
+unsafe extern "C" fn pause_thread(cx: *mut JSContext, argc: u32, vp: *mut JSVal) -> bool {
+    let args = CallArgs::from_vp(vp, argc);
+    let pipeline = HandleValue::from_raw(args.get(0));
+    let info = HandleValue::from_raw(args.get(1));
+    if !pipeline.is_string() || !info.is_string() {
+        *args.rval() = UndefinedValue();
+        return true;
+    }
+    let pipeline_id = serde_json::from_str(&jsstring_to_str(cx, pipeline.to_string()));
+    let info = serde_json::from_str(&jsstring_to_str(cx, info.to_string()));
+    let limit = match (pipeline_id, info) {
+        (Ok(pipeline_id), Ok(info)) => ScriptThread::pause_for_debugger(pipeline_id, info),
+        _ => {
+            warn!("Invalid pause of the debugger.");
+            None
+        },
+    };
+    serde_json::to_string(&limit)
+        .unwrap()
+        .to_jsval(cx, MutableHandleValue::from_raw(args.rval()));
+    true
+}
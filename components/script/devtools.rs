@@ -3,31 +3,58 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::compartments::enter_realm;
+use crate::dom::bindings::codegen::Bindings::AttrBinding::AttrMethods;
+use crate::dom::bindings::codegen::Bindings::CSSGroupingRuleBinding::CSSGroupingRuleMethods;
+use crate::dom::bindings::codegen::Bindings::CSSRuleListBinding::CSSRuleListMethods;
 use crate::dom::bindings::codegen::Bindings::CSSStyleDeclarationBinding::CSSStyleDeclarationMethods;
+use crate::dom::bindings::codegen::Bindings::CSSStyleRuleBinding::CSSStyleRuleMethods;
+use crate::dom::bindings::codegen::Bindings::CSSStyleSheetBinding::CSSStyleSheetMethods;
 use crate::dom::bindings::codegen::Bindings::DOMRectBinding::DOMRectMethods;
 use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use crate::dom::bindings::codegen::Bindings::ElementBinding::ElementMethods;
+use crate::dom::bindings::codegen::Bindings::HTMLElementBinding::HTMLElementMethods;
+use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
+use crate::dom::bindings::codegen::Bindings::StyleSheetBinding::StyleSheetMethods;
 use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use crate::dom::bindings::conversions::{jsstring_to_str, ConversionResult, FromJSValConvertible};
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::root::DomRoot;
 use crate::dom::bindings::str::DOMString;
-use crate::dom::document::AnimationFrameCallback;
+use crate::dom::cssgroupingrule::CSSGroupingRule;
+use crate::dom::cssstylerule::CSSStyleRule;
+use crate::dom::cssstylesheet::CSSStyleSheet;
+use crate::dom::document::{AnimationFrameCallback, Document};
 use crate::dom::element::Element;
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::htmlelement::HTMLElement;
+use crate::dom::htmlstyleelement::HTMLStyleElement;
+use crate::dom::mutationobserver::Mutation;
 use crate::dom::node::{window_from_node, Node, ShadowIncluding};
+use crate::dom::stylesheet::StyleSheet;
 use crate::dom::window::Window;
 use crate::script_thread::Documents;
+use cssparser::ToCss as SelectorToCss;
+use devtools_traits::ScriptToDevtoolsControlMsg;
 use devtools_traits::TimelineMarkerType;
+use devtools_traits::{AppliedRuleId, AppliedRuleInfo, RuleModification};
 use devtools_traits::{AutoMargins, CachedConsoleMessage, CachedConsoleMessageTypes};
-use devtools_traits::{ComputedNodeLayout, ConsoleAPI, PageError};
-use devtools_traits::{EvaluateJSReply, Modification, NodeInfo, TimelineMarker};
+use devtools_traits::{ComputedNodeLayout, ConsoleAPI, CssRuleInfo, PageError, StyleSheetInfo};
+use devtools_traits::{DomMutation, EvaluateJSReply, Modification, NodeInfo, TimelineMarker};
+use html5ever::{LocalName, Namespace};
 use ipc_channel::ipc::IpcSender;
 use js::jsval::UndefinedValue;
 use js::rust::wrappers::ObjectClassName;
 use msg::constellation_msg::PipelineId;
+use selectors::parser::SelectorList;
+use std::cmp::Reverse;
 use std::ffi::CStr;
 use std::str;
+use style::context::QuirksMode;
+use style::dom_apis;
+use style::media_queries::Device;
+use style::shared_lock::{SharedRwLockReadGuard, ToCssWithGuard};
+use style::stylesheets::CssRule;
+use style_traits::ToCss;
 use uuid::Uuid;
 
 #[allow(unsafe_code)]
@@ -259,10 +286,243 @@ pub fn handle_modify_attribute(
     }
 }
 
+pub fn handle_insert_before(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    parent_id: String,
+    sibling_id: Option<String>,
+) {
+    let node = match find_node_by_unique_id(documents, pipeline, &*node_id) {
+        None => {
+            return warn!(
+                "node id {} for pipeline id {} is not found",
+                node_id, pipeline
+            )
+        },
+        Some(node) => node,
+    };
+    let parent = match find_node_by_unique_id(documents, pipeline, &*parent_id) {
+        None => {
+            return warn!(
+                "node id {} for pipeline id {} is not found",
+                parent_id, pipeline
+            )
+        },
+        Some(parent) => parent,
+    };
+    let sibling = match sibling_id {
+        None => None,
+        Some(sibling_id) => match find_node_by_unique_id(documents, pipeline, &*sibling_id) {
+            None => {
+                return warn!(
+                    "node id {} for pipeline id {} is not found",
+                    sibling_id, pipeline
+                );
+            },
+            Some(sibling) => Some(sibling),
+        },
+    };
+    if let Err(error) = parent.InsertBefore(&node, sibling.as_ref().map(|sibling| &**sibling)) {
+        warn!("failed to move node id {}: {:?}", node_id, error);
+    }
+}
+
+pub fn handle_remove_node(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    reply: IpcSender<Option<NodeInfo>>,
+) {
+    let node = match find_node_by_unique_id(documents, pipeline, &*node_id) {
+        None => return reply.send(None).unwrap(),
+        Some(node) => node,
+    };
+    let next_sibling = node.GetNextSibling();
+    node.remove_self();
+    reply
+        .send(next_sibling.map(|sibling| sibling.summarize()))
+        .unwrap();
+}
+
+pub fn handle_get_outer_html(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    reply: IpcSender<Option<String>>,
+) {
+    let html = find_node_by_unique_id(documents, pipeline, &*node_id)
+        .and_then(|node| {
+            node.downcast::<Element>()
+                .map(|element| element.GetOuterHTML())
+        })
+        .and_then(|html| html.ok())
+        .map(String::from);
+    reply.send(html).unwrap();
+}
+
+pub fn handle_set_outer_html(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    html: String,
+) {
+    let node = match find_node_by_unique_id(documents, pipeline, &*node_id) {
+        None => {
+            return warn!(
+                "node id {} for pipeline id {} is not found",
+                node_id, pipeline
+            )
+        },
+        Some(node) => node,
+    };
+    let element = match node.downcast::<Element>() {
+        None => return warn!("node id {} is not an element", node_id),
+        Some(element) => element,
+    };
+    if let Err(error) = element.SetOuterHTML(DOMString::from(html)) {
+        warn!(
+            "failed to set the outer HTML of node id {}: {:?}",
+            node_id, error
+        );
+    }
+}
+
+pub fn handle_set_node_value(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    value: String,
+) {
+    match find_node_by_unique_id(documents, pipeline, &*node_id) {
+        None => warn!(
+            "node id {} for pipeline id {} is not found",
+            node_id, pipeline
+        ),
+        Some(node) => node.SetNodeValue(Some(DOMString::from(value))),
+    }
+}
+
 pub fn handle_wants_live_notifications(global: &GlobalScope, send_notifications: bool) {
     global.set_devtools_wants_updates(send_notifications);
 }
 
+pub fn handle_wants_mutation_notifications(
+    documents: &Documents,
+    pipeline: PipelineId,
+    send_notifications: bool,
+) {
+    if let Some(window) = documents.find_window(pipeline) {
+        window.set_devtools_wants_mutations(send_notifications);
+    }
+}
+
+/// A mutation of a document devtools are told of at the next microtask checkpoint, with the
+/// values it results in, which are not set yet when it is queued.
+#[derive(JSTraceable, MallocSizeOf)]
+pub enum DevtoolsMutation {
+    Attribute {
+        target: DomRoot<Node>,
+        name: LocalName,
+        namespace: Namespace,
+    },
+    CharacterData {
+        target: DomRoot<Node>,
+    },
+    ChildList {
+        target: DomRoot<Node>,
+        added: Vec<DomRoot<Node>>,
+        removed: Vec<DomRoot<Node>>,
+    },
+}
+
+/// Queue the given mutation of `target` for devtools, if they asked to be told of the
+/// mutations of its document.
+pub fn queue_mutation(target: &Node, mutation: &Mutation) {
+    let window = window_from_node(target);
+    if !window.devtools_wants_mutations() || !target.is_connected() {
+        return;
+    }
+    let target = DomRoot::from_ref(target);
+    let mutation = match *mutation {
+        Mutation::Attribute {
+            ref name,
+            ref namespace,
+            ..
+        } => DevtoolsMutation::Attribute {
+            target,
+            name: name.clone(),
+            namespace: namespace.clone(),
+        },
+        Mutation::CharacterData { .. } => DevtoolsMutation::CharacterData { target },
+        Mutation::ChildList { added, removed, .. } => DevtoolsMutation::ChildList {
+            target,
+            added: added
+                .unwrap_or(&[])
+                .iter()
+                .map(|node| DomRoot::from_ref(*node))
+                .collect(),
+            removed: removed
+                .unwrap_or(&[])
+                .iter()
+                .map(|node| DomRoot::from_ref(*node))
+                .collect(),
+        },
+    };
+    window.queue_devtools_mutation(mutation);
+}
+
+/// Tell devtools of the mutations queued for the document of `window`.
+pub fn notify_mutations(window: &Window) {
+    let mutations = window.take_devtools_mutations();
+    let global = window.upcast::<GlobalScope>();
+    let chan = match global.devtools_chan() {
+        Some(chan) => chan,
+        None => return,
+    };
+    let mutations = mutations
+        .into_iter()
+        .map(|mutation| match mutation {
+            DevtoolsMutation::Attribute {
+                target,
+                name,
+                namespace,
+            } => DomMutation::Attribute {
+                target: target.unique_id(),
+                new_value: target
+                    .downcast::<Element>()
+                    .and_then(|element| element.get_attribute(&namespace, &name))
+                    .map(|attr| String::from(attr.Value())),
+                name: name.to_string(),
+                namespace: namespace.to_string(),
+            },
+            DevtoolsMutation::CharacterData { target } => DomMutation::CharacterData {
+                target: target.unique_id(),
+                new_value: target.GetNodeValue().map(String::from).unwrap_or_default(),
+            },
+            DevtoolsMutation::ChildList {
+                target,
+                added,
+                removed,
+            } => DomMutation::ChildList {
+                target: target.unique_id(),
+                added: added
+                    .iter()
+                    .filter(|node| node.GetParentNode().as_ref() == Some(&target))
+                    .map(|node| node.summarize())
+                    .collect(),
+                removed: removed.iter().map(|node| node.unique_id()).collect(),
+                num_children: target.children_count() as usize,
+            },
+        })
+        .collect();
+    chan.send(ScriptToDevtoolsControlMsg::DomMutations(
+        global.pipeline_id(),
+        mutations,
+    ))
+    .unwrap();
+}
+
 pub fn handle_set_timeline_markers(
     documents: &Documents,
     pipeline: PipelineId,
@@ -296,3 +556,425 @@ pub fn handle_reload(documents: &Documents, id: PipelineId) {
         win.Location().reload_without_origin_check();
     }
 }
+
+/// The stylesheet with the given id; stylesheets are identified by their owner node, which
+/// owns at most one.
+fn find_stylesheet(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: &str,
+) -> Option<DomRoot<CSSStyleSheet>> {
+    let document = documents.find_document(pipeline)?;
+    (0..document.stylesheet_count())
+        .filter_map(|index| document.stylesheet_at(index))
+        .find(|sheet| sheet.owner().upcast::<Node>().unique_id() == sheet_id)
+}
+
+/// The line the source locations of the rules of `sheet` are relative to.
+fn stylesheet_line_number_offset(sheet: &CSSStyleSheet) -> u32 {
+    sheet
+        .get_owner()
+        .downcast::<HTMLStyleElement>()
+        .map_or(0, |style| style.line_number())
+}
+
+pub fn handle_get_stylesheets(
+    documents: &Documents,
+    pipeline: PipelineId,
+    reply: IpcSender<Vec<StyleSheetInfo>>,
+) {
+    let document = match documents.find_document(pipeline) {
+        None => return reply.send(vec![]).unwrap(),
+        Some(document) => document,
+    };
+    let sheets = (0..document.stylesheet_count())
+        .filter_map(|index| {
+            document
+                .stylesheet_at(index)
+                .map(|sheet| stylesheet_info(&document, index, &sheet))
+        })
+        .collect();
+    reply.send(sheets).unwrap();
+}
+
+/// The details of `sheet`, the one at `index` in the stylesheets of `document`.
+fn stylesheet_info(document: &Document, index: usize, sheet: &CSSStyleSheet) -> StyleSheetInfo {
+    let guard = sheet.shared_lock().read();
+    let rules = sheet.style_stylesheet().contents.rules.read_with(&guard);
+    StyleSheetInfo {
+        id: sheet.owner().upcast::<Node>().unique_id(),
+        href: sheet.upcast::<StyleSheet>().GetHref().map(String::from),
+        node_href: document.url().into_string(),
+        title: sheet.upcast::<StyleSheet>().GetTitle().map(String::from),
+        disabled: sheet.disabled(),
+        index,
+        rule_count: rules.0.len(),
+    }
+}
+
+pub fn handle_get_stylesheet_text(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: String,
+    reply: IpcSender<Option<String>>,
+) {
+    let text = find_stylesheet(documents, pipeline, &sheet_id).map(|sheet| {
+        if let Some(text) = sheet.edited_text() {
+            return text;
+        }
+        if let Some(style) = sheet.get_owner().downcast::<HTMLStyleElement>() {
+            return style
+                .upcast::<Node>()
+                .GetTextContent()
+                .map(String::from)
+                .unwrap_or_default();
+        }
+        // The text of linked sheets is not kept around once parsed.
+        let guard = sheet.shared_lock().read();
+        let rules = sheet.style_stylesheet().contents.rules.read_with(&guard);
+        rules
+            .0
+            .iter()
+            .map(|rule| rule.to_css_string(&guard))
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    reply.send(text).unwrap();
+}
+
+fn push_rule_infos(
+    rules: &[CssRule],
+    guard: &SharedRwLockReadGuard,
+    device: &Device,
+    quirks_mode: QuirksMode,
+    line_number_offset: u32,
+    infos: &mut Vec<CssRuleInfo>,
+) {
+    for rule in rules {
+        let (location, media_text, matches, nested_rules) = match *rule {
+            CssRule::Style(ref lock) => (lock.read_with(guard).source_location, None, true, None),
+            CssRule::Import(ref lock) => (lock.read_with(guard).source_location, None, true, None),
+            CssRule::Namespace(ref lock) => {
+                (lock.read_with(guard).source_location, None, true, None)
+            },
+            CssRule::FontFace(ref lock) => {
+                (lock.read_with(guard).source_location, None, true, None)
+            },
+            CssRule::Keyframes(ref lock) => {
+                (lock.read_with(guard).source_location, None, true, None)
+            },
+            CssRule::Page(ref lock) => (lock.read_with(guard).source_location, None, true, None),
+            CssRule::Media(ref lock) => {
+                let rule = lock.read_with(guard);
+                let media = rule.media_queries.read_with(guard);
+                (
+                    rule.source_location,
+                    Some(media.to_css_string()),
+                    media.evaluate(device, quirks_mode),
+                    Some(rule.rules.clone()),
+                )
+            },
+            CssRule::Supports(ref lock) => {
+                let rule = lock.read_with(guard);
+                (
+                    rule.source_location,
+                    None,
+                    rule.enabled,
+                    Some(rule.rules.clone()),
+                )
+            },
+            CssRule::Document(ref lock) => {
+                let rule = lock.read_with(guard);
+                (
+                    rule.source_location,
+                    None,
+                    rule.condition.evaluate(device),
+                    Some(rule.rules.clone()),
+                )
+            },
+            CssRule::FontFeatureValues(_) | CssRule::CounterStyle(_) | CssRule::Viewport(_) => {
+                continue;
+            },
+        };
+        infos.push(CssRuleInfo {
+            type_: rule.rule_type() as u16,
+            css_text: rule.to_css_string(guard),
+            media_text,
+            matches,
+            line: location.line.saturating_sub(line_number_offset) + 1,
+            column: location.column,
+        });
+        if let Some(nested_rules) = nested_rules {
+            push_rule_infos(
+                &nested_rules.read_with(guard).0,
+                guard,
+                device,
+                quirks_mode,
+                line_number_offset,
+                infos,
+            );
+        }
+    }
+}
+
+pub fn handle_get_stylesheet_rules(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: String,
+    reply: IpcSender<Option<Vec<CssRuleInfo>>>,
+) {
+    let infos = find_stylesheet(documents, pipeline, &sheet_id).map(|sheet| {
+        let document = sheet.get_owner().owner_doc();
+        let guard = sheet.shared_lock().read();
+        let rules = sheet.style_stylesheet().contents.rules.read_with(&guard);
+        let mut infos = vec![];
+        push_rule_infos(
+            &rules.0,
+            &guard,
+            &document.device(),
+            document.quirks_mode(),
+            stylesheet_line_number_offset(&sheet),
+            &mut infos,
+        );
+        infos
+    });
+    reply.send(infos).unwrap();
+}
+
+pub fn handle_update_stylesheet(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: String,
+    text: String,
+    reply: IpcSender<bool>,
+) {
+    let sheet = match find_stylesheet(documents, pipeline, &sheet_id) {
+        None => return reply.send(false).unwrap(),
+        Some(sheet) => sheet,
+    };
+    // Keep the source locations of the rules relative to the document, as when the
+    // sheet was first parsed.
+    sheet.set_text(text, stylesheet_line_number_offset(&sheet));
+    reply.send(true).unwrap();
+}
+
+pub fn handle_toggle_stylesheet_disabled(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: String,
+    reply: IpcSender<Option<bool>>,
+) {
+    let disabled = find_stylesheet(documents, pipeline, &sheet_id).map(|sheet| {
+        sheet.set_disabled(!sheet.disabled());
+        sheet.disabled()
+    });
+    reply.send(disabled).unwrap();
+}
+
+/// Collects the style rules of a stylesheet that apply to an element.
+struct AppliedRulesCollector<'a> {
+    element: &'a DomRoot<Element>,
+    device: &'a Device,
+    quirks_mode: QuirksMode,
+    sheet: StyleSheetInfo,
+    line_number_offset: u32,
+    /// The rules found, in tree order, with the specificity of their most specific selector
+    /// that matches.
+    rules: Vec<(u32, AppliedRuleInfo)>,
+}
+
+impl<'a> AppliedRulesCollector<'a> {
+    fn collect(&mut self, rules: &[CssRule], guard: &SharedRwLockReadGuard, path: &mut Vec<usize>) {
+        for (index, rule) in rules.iter().enumerate() {
+            path.push(index);
+            match *rule {
+                CssRule::Style(ref lock) => {
+                    let rule = lock.read_with(guard);
+                    let matched_selectors: Vec<_> = rule
+                        .selectors
+                        .0
+                        .iter()
+                        .filter(|selector| {
+                            let selector = SelectorList::from_vec(vec![(*selector).clone()]);
+                            dom_apis::element_matches(self.element, &selector, self.quirks_mode)
+                        })
+                        .collect();
+                    let specificity = matched_selectors
+                        .iter()
+                        .map(|selector| selector.specificity())
+                        .max();
+                    if let Some(specificity) = specificity {
+                        let mut declarations = String::new();
+                        rule.block
+                            .read_with(guard)
+                            .to_css(&mut declarations)
+                            .unwrap();
+                        let info = AppliedRuleInfo {
+                            id: AppliedRuleId::StyleSheet(self.sheet.id.clone(), path.clone()),
+                            selectors: rule
+                                .selectors
+                                .0
+                                .iter()
+                                .map(|selector| selector.to_css_string())
+                                .collect(),
+                            matched_selectors: matched_selectors
+                                .iter()
+                                .map(|selector| selector.to_css_string())
+                                .collect(),
+                            declarations,
+                            sheet: Some(self.sheet.clone()),
+                            line: rule
+                                .source_location
+                                .line
+                                .saturating_sub(self.line_number_offset) +
+                                1,
+                            column: rule.source_location.column,
+                        };
+                        self.rules.push((specificity, info));
+                    }
+                },
+                // Only the rules the CSSOM exposes the nested rules of are descended into,
+                // so that the path of the rules found leads back to them.
+                CssRule::Media(ref lock) => {
+                    let rule = lock.read_with(guard);
+                    let media = rule.media_queries.read_with(guard);
+                    if media.evaluate(self.device, self.quirks_mode) {
+                        self.collect(&rule.rules.read_with(guard).0, guard, path);
+                    }
+                },
+                CssRule::Supports(ref lock) => {
+                    let rule = lock.read_with(guard);
+                    if rule.enabled {
+                        self.collect(&rule.rules.read_with(guard).0, guard, path);
+                    }
+                },
+                _ => {},
+            }
+            path.pop();
+        }
+    }
+}
+
+pub fn handle_get_applied_rules(
+    documents: &Documents,
+    pipeline: PipelineId,
+    node_id: String,
+    reply: IpcSender<Option<Vec<AppliedRuleInfo>>>,
+) {
+    let node = match find_node_by_unique_id(documents, pipeline, &*node_id) {
+        None => return reply.send(None).unwrap(),
+        Some(node) => node,
+    };
+    let element = match node.downcast::<Element>() {
+        None => return reply.send(Some(vec![])).unwrap(),
+        Some(element) => DomRoot::from_ref(element),
+    };
+    let document = node.owner_doc();
+    let device = document.device();
+    let quirks_mode = document.quirks_mode();
+
+    let mut applied = vec![];
+    if let Some(html_element) = element.downcast::<HTMLElement>() {
+        applied.push(AppliedRuleInfo {
+            id: AppliedRuleId::Element(node_id),
+            selectors: vec![],
+            matched_selectors: vec![],
+            declarations: String::from(html_element.Style().CssText()),
+            sheet: None,
+            line: 0,
+            column: 0,
+        });
+    }
+
+    let mut rules = vec![];
+    for index in 0..document.stylesheet_count() {
+        let sheet = match document.stylesheet_at(index) {
+            Some(sheet) => sheet,
+            None => continue,
+        };
+        if sheet.disabled() {
+            continue;
+        }
+        let style_sheet = sheet.style_stylesheet();
+        let guard = sheet.shared_lock().read();
+        if !style_sheet
+            .media
+            .read_with(&guard)
+            .evaluate(&device, quirks_mode)
+        {
+            continue;
+        }
+        let mut collector = AppliedRulesCollector {
+            element: &element,
+            device: &device,
+            quirks_mode,
+            sheet: stylesheet_info(&document, index, &sheet),
+            line_number_offset: stylesheet_line_number_offset(&sheet),
+            rules: vec![],
+        };
+        let sheet_rules = style_sheet.contents.rules.read_with(&guard);
+        collector.collect(&sheet_rules.0, &guard, &mut vec![]);
+        rules.extend(collector.rules);
+    }
+
+    // The rules that win come first: the most specific ones, and of these the last ones.
+    rules.reverse();
+    rules.sort_by_key(|&(specificity, _)| Reverse(specificity));
+    applied.extend(rules.into_iter().map(|(_, rule)| rule));
+    reply.send(Some(applied)).unwrap();
+}
+
+/// The style rule at `path` in the stylesheet with the given id.
+fn find_style_rule(
+    documents: &Documents,
+    pipeline: PipelineId,
+    sheet_id: &str,
+    path: &[usize],
+) -> Option<DomRoot<CSSStyleRule>> {
+    let sheet = find_stylesheet(documents, pipeline, sheet_id)?;
+    let (index, parent_path) = path.split_last()?;
+    let mut rules = sheet.GetCssRules().ok()?;
+    for &parent_index in parent_path {
+        let parent = rules.Item(parent_index as u32)?;
+        rules = parent.downcast::<CSSGroupingRule>()?.CssRules();
+    }
+    DomRoot::downcast::<CSSStyleRule>(rules.Item(*index as u32)?)
+}
+
+pub fn handle_modify_rule(
+    documents: &Documents,
+    pipeline: PipelineId,
+    rule_id: AppliedRuleId,
+    modifications: Vec<RuleModification>,
+    reply: IpcSender<Option<String>>,
+) {
+    let style = match rule_id {
+        AppliedRuleId::Element(ref node_id) => find_node_by_unique_id(documents, pipeline, node_id)
+            .and_then(|node| {
+                node.downcast::<HTMLElement>()
+                    .map(|html_element| html_element.Style())
+            }),
+        AppliedRuleId::StyleSheet(ref sheet_id, ref path) => {
+            find_style_rule(documents, pipeline, sheet_id, path).map(|rule| rule.Style())
+        },
+    };
+    let style = match style {
+        None => return reply.send(None).unwrap(),
+        Some(style) => style,
+    };
+    for modification in modifications {
+        let result = match modification {
+            RuleModification::SetProperty {
+                name,
+                value,
+                priority,
+            } => style.SetProperty(name.into(), value.into(), priority.into()),
+            RuleModification::RemoveProperty(name) => style.RemoveProperty(name.into()).map(|_| ()),
+            RuleModification::SetText(text) => style.SetCssText(text.into()),
+        };
+        if let Err(error) = result {
+            warn!("failed to modify rule {:?}: {:?}", rule_id, error);
+        }
+    }
+    reply.send(Some(String::from(style.CssText()))).unwrap();
+}
@@ -0,0 +1,50 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::abortsignal::AbortSignal;
+use crate::dom::bindings::codegen::Bindings::AbortControllerBinding::{
+    self, AbortControllerMethods,
+};
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::globalscope::GlobalScope;
+use dom_struct::dom_struct;
+
+#[dom_struct]
+pub struct AbortController {
+    reflector_: Reflector,
+    signal: Dom<AbortSignal>,
+}
+
+impl AbortController {
+    fn new_inherited(signal: &AbortSignal) -> AbortController {
+        AbortController {
+            reflector_: Reflector::new(),
+            signal: Dom::from_ref(signal),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-abortcontroller
+    pub fn Constructor(global: &GlobalScope) -> Fallible<DomRoot<AbortController>> {
+        let signal = AbortSignal::new(global);
+        Ok(reflect_dom_object(
+            Box::new(AbortController::new_inherited(&signal)),
+            global,
+            AbortControllerBinding::Wrap,
+        ))
+    }
+}
+
+impl AbortControllerMethods for AbortController {
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-signal
+    fn Signal(&self) -> DomRoot<AbortSignal> {
+        DomRoot::from_ref(&*self.signal)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-abort
+    fn Abort(&self) {
+        self.signal.signal_abort();
+    }
+}
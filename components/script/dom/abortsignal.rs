@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::AbortSignalBinding::{self, AbortSignalMethods};
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::EventListenerBinding::EventListener;
+use crate::dom::bindings::codegen::Bindings::EventTargetBinding::EventListenerOptions;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::readablestream::PipeState;
+use crate::fetch::FetchAbortHandle;
+use dom_struct::dom_struct;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// <https://dom.spec.whatwg.org/#abortsignal-abort-algorithms>
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub enum AbortAlgorithm {
+    /// Remove an event listener that was added with the signal.
+    RemoveEventListener(
+        Dom<EventTarget>,
+        DOMString,
+        #[ignore_malloc_size_of = "Rc"] Rc<EventListener>,
+        bool,
+    ),
+    /// Abort an ongoing fetch.
+    Fetch(#[ignore_malloc_size_of = "Arc"] FetchAbortHandle),
+    /// Signal abort on a signal that follows this one.
+    FollowingSignal(Dom<AbortSignal>),
+    /// Abort a pipe between two streams.
+    Pipe(#[ignore_malloc_size_of = "Rc"] Rc<PipeState>),
+}
+
+impl AbortAlgorithm {
+    fn exec(&self) {
+        match *self {
+            AbortAlgorithm::RemoveEventListener(ref target, ref ty, ref listener, capture) => {
+                target.remove_event_listener(
+                    ty.clone(),
+                    Some(listener.clone()),
+                    EventListenerOptions { capture },
+                )
+            },
+            AbortAlgorithm::Fetch(ref handle) => handle.abort(),
+            AbortAlgorithm::FollowingSignal(ref signal) => signal.signal_abort(),
+            AbortAlgorithm::Pipe(ref pipe) => PipeState::abort(pipe),
+        }
+    }
+}
+
+#[dom_struct]
+pub struct AbortSignal {
+    eventtarget: EventTarget,
+    /// <https://dom.spec.whatwg.org/#abortsignal-aborted-flag>
+    aborted: Cell<bool>,
+    abort_algorithms: DomRefCell<Vec<AbortAlgorithm>>,
+}
+
+impl AbortSignal {
+    fn new_inherited() -> AbortSignal {
+        AbortSignal {
+            eventtarget: EventTarget::new_inherited(),
+            aborted: Cell::new(false),
+            abort_algorithms: DomRefCell::new(vec![]),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<AbortSignal> {
+        reflect_dom_object(
+            Box::new(AbortSignal::new_inherited()),
+            global,
+            AbortSignalBinding::Wrap,
+        )
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted.get()
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-add>
+    #[allow(unrooted_must_root)]
+    pub fn add_algorithm(&self, algorithm: AbortAlgorithm) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+        // Step 2.
+        self.abort_algorithms.borrow_mut().push(algorithm);
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-remove>
+    pub fn remove_algorithm<F>(&self, matches: F)
+    where
+        F: Fn(&AbortAlgorithm) -> bool,
+    {
+        // The algorithms are all dropped once the signal is aborted.
+        if self.aborted.get() {
+            return;
+        }
+        self.abort_algorithms
+            .borrow_mut()
+            .retain(|algorithm| !matches(algorithm));
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-signal-abort>
+    pub fn signal_abort(&self) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+        // Step 2.
+        self.aborted.set(true);
+        // Step 3. No algorithm can be added while this runs, now that the
+        // signal is aborted.
+        for algorithm in self.abort_algorithms.borrow().iter() {
+            algorithm.exec();
+        }
+        // Step 4.
+        self.abort_algorithms.borrow_mut().clear();
+        // Step 5.
+        self.upcast::<EventTarget>().fire_event(atom!("abort"));
+    }
+
+    /// <https://dom.spec.whatwg.org/#abortsignal-follow>
+    pub fn follow(&self, parent: &AbortSignal) {
+        // Step 1.
+        if self.aborted.get() {
+            return;
+        }
+        // Step 2.
+        if parent.aborted.get() {
+            return self.signal_abort();
+        }
+        // Step 3.
+        parent.add_algorithm(AbortAlgorithm::FollowingSignal(Dom::from_ref(self)));
+    }
+}
+
+impl AbortSignalMethods for AbortSignal {
+    // https://dom.spec.whatwg.org/#dom-abortsignal-aborted
+    fn Aborted(&self) -> bool {
+        self.aborted.get()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortsignal-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+}
@@ -2031,7 +2031,7 @@ class CGImports(CGWrapper):
         extras = []
         for t in types:
             # Importing these types in the same module that defines them is an error.
-            if t in dictionaries or t in enums:
+            if (t.isDictionary() and t in dictionaries) or (t.isEnum() and t in enums):
                 continue
             if t.isInterface() or t.isNamespace():
                 name = getIdentifier(t).name
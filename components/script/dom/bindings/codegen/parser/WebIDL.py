@@ -5587,7 +5587,6 @@ class Tokenizer(object):
         "setlike": "SETLIKE",
         "iterable": "ITERABLE",
         "namespace": "NAMESPACE",
-        "ReadableStream": "READABLESTREAM",
         "constructor": "CONSTRUCTOR",
         }
 
@@ -6804,7 +6803,6 @@ class Parser(Tokenizer):
             DistinguishableType : PrimitiveType Null
                                 | ARRAYBUFFER Null
                                 | SHAREDARRAYBUFFER Null
-                                | READABLESTREAM Null
                                 | OBJECT Null
         """
         if p[1] == "object":
@@ -6813,8 +6811,6 @@ class Parser(Tokenizer):
             type = BuiltinTypes[IDLBuiltinType.Types.ArrayBuffer]
         elif p[1] == "SharedArrayBuffer":
             type = BuiltinTypes[IDLBuiltinType.Types.SharedArrayBuffer]
-        elif p[1] == "ReadableStream":
-            type = BuiltinTypes[IDLBuiltinType.Types.ReadableStream]
         else:
             type = BuiltinTypes[p[1]]
 
@@ -84,6 +84,18 @@ pub enum Error {
     InvalidModification,
     /// NotReadableError DOMException
     NotReadable,
+    /// TransactionInactiveError DOMException
+    TransactionInactive,
+    /// ReadOnlyError DOMException
+    ReadOnly,
+    /// VersionError DOMException
+    Version,
+    /// ConstraintError DOMException
+    Constraint,
+    /// DataError DOMException
+    Data,
+    /// UnknownError DOMException
+    Unknown,
 
     /// TypeError JavaScript Error
     Type(String),
@@ -136,6 +148,12 @@ pub unsafe fn throw_dom_exception(cx: *mut JSContext, global: &GlobalScope, resu
         Error::TypeMismatch => DOMErrorName::TypeMismatchError,
         Error::InvalidModification => DOMErrorName::InvalidModificationError,
         Error::NotReadable => DOMErrorName::NotReadableError,
+        Error::TransactionInactive => DOMErrorName::TransactionInactiveError,
+        Error::ReadOnly => DOMErrorName::ReadOnlyError,
+        Error::Version => DOMErrorName::VersionError,
+        Error::Constraint => DOMErrorName::ConstraintError,
+        Error::Data => DOMErrorName::DataError,
+        Error::Unknown => DOMErrorName::UnknownError,
         Error::Type(message) => {
             assert!(!JS_IsExceptionPending(cx));
             throw_type_error(cx, &message);
@@ -12,6 +12,8 @@ use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::DomRoot;
 use crate::dom::blob::{Blob, BlobImpl};
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::messageport::MessagePort;
+use js::conversions::ToJSValConvertible;
 use js::glue::CopyJSStructuredCloneData;
 use js::glue::DeleteJSAutoStructuredCloneBuffer;
 use js::glue::GetLengthOfJSStructuredCloneData;
@@ -28,9 +30,13 @@ use js::jsapi::{JSObject, JS_ClearPendingException};
 use js::jsapi::{JSStructuredCloneCallbacks, JSStructuredCloneReader, JSStructuredCloneWriter};
 use js::jsapi::{JS_ReadBytes, JS_WriteBytes};
 use js::jsapi::{JS_ReadUint32Pair, JS_WriteUint32Pair};
+use js::jsval::UndefinedValue;
 use js::rust::wrappers::{JS_ReadStructuredClone, JS_WriteStructuredClone};
-use js::rust::{Handle, HandleValue, MutableHandleValue};
+use js::rust::{CustomAutoRooterGuard, Handle, HandleValue, MutableHandleValue};
 use libc::size_t;
+use msg::constellation_msg::MessagePortId;
+use script_traits::transferable::{MessagePortImpl, StructuredSerializedData};
+use std::collections::HashMap;
 use std::os::raw;
 use std::ptr;
 use std::slice;
@@ -44,6 +50,7 @@ enum StructuredCloneTags {
     /// To support additional types, add new tags with values incremented from the last one before Max.
     Min = 0xFFFF8000,
     DomBlob = 0xFFFF8001,
+    MessagePort = 0xFFFF8002,
     Max = 0xFFFFFFFF,
 }
 
@@ -126,7 +133,7 @@ impl StructuredCloneReader {
 unsafe fn read_blob(
     cx: *mut JSContext,
     r: *mut JSStructuredCloneReader,
-    sc_holder: &mut StructuredCloneHolder,
+    sc_holder: &mut StructuredDataHolder,
 ) -> *mut JSObject {
     let structured_reader = StructuredCloneReader { r: r };
     let blob_buffer = structured_reader.read_bytes();
@@ -138,7 +145,12 @@ unsafe fn read_blob(
         type_str,
     );
     let js_object = blob.reflector().get_jsobject().get();
-    sc_holder.blob = Some(blob);
+    if let StructuredDataHolder::Read {
+        blob: ref mut root, ..
+    } = *sc_holder
+    {
+        *root = Some(blob);
+    }
     js_object
 }
 
@@ -171,7 +183,7 @@ unsafe extern "C" fn read_callback(
         "tag should be higher than StructuredCloneTags::Min"
     );
     if tag == StructuredCloneTags::DomBlob as u32 {
-        return read_blob(cx, r, &mut *(closure as *mut StructuredCloneHolder));
+        return read_blob(cx, r, &mut *(closure as *mut StructuredDataHolder));
     }
     return ptr::null_mut();
 }
@@ -189,26 +201,43 @@ unsafe extern "C" fn write_callback(
 }
 
 unsafe extern "C" fn read_transfer_callback(
-    _cx: *mut JSContext,
+    cx: *mut JSContext,
     _r: *mut JSStructuredCloneReader,
-    _tag: u32,
+    tag: u32,
     _content: *mut raw::c_void,
-    _extra_data: u64,
-    _closure: *mut raw::c_void,
-    _return_object: RawMutableHandleObject,
+    extra_data: u64,
+    closure: *mut raw::c_void,
+    return_object: RawMutableHandleObject,
 ) -> bool {
+    if tag == StructuredCloneTags::MessagePort as u32 {
+        let sc_holder = &mut *(closure as *mut StructuredDataHolder);
+        let owner = GlobalScope::from_context(cx);
+        if let Ok(port) = MessagePort::transfer_receive(&owner, sc_holder, extra_data) {
+            return_object.set(port.reflector().get_jsobject().get());
+            return true;
+        }
+    }
     false
 }
 
 unsafe extern "C" fn write_transfer_callback(
-    _cx: *mut JSContext,
-    _obj: RawHandleObject,
-    _closure: *mut raw::c_void,
-    _tag: *mut u32,
-    _ownership: *mut TransferableOwnership,
+    cx: *mut JSContext,
+    obj: RawHandleObject,
+    closure: *mut raw::c_void,
+    tag: *mut u32,
+    ownership: *mut TransferableOwnership,
     _content: *mut *mut raw::c_void,
-    _extra_data: *mut u64,
+    extra_data: *mut u64,
 ) -> bool {
+    if let Ok(port) = root_from_handleobject::<MessagePort>(Handle::from_raw(obj), cx) {
+        let sc_holder = &mut *(closure as *mut StructuredDataHolder);
+        if let Ok(data) = port.transfer(sc_holder) {
+            *tag = StructuredCloneTags::MessagePort as u32;
+            *ownership = TransferableOwnership::SCTAG_TMO_CUSTOM;
+            *extra_data = data;
+            return true;
+        }
+    }
     false
 }
 
@@ -222,11 +251,11 @@ unsafe extern "C" fn free_transfer_callback(
 }
 
 unsafe extern "C" fn can_transfer_callback(
-    _cx: *mut JSContext,
-    _obj: RawHandleObject,
+    cx: *mut JSContext,
+    obj: RawHandleObject,
     _closure: *mut raw::c_void,
 ) -> bool {
-    false
+    root_from_handleobject::<MessagePort>(Handle::from_raw(obj), cx).is_ok()
 }
 
 unsafe extern "C" fn report_error_callback(_cx: *mut JSContext, _errorid: u32) {}
@@ -241,8 +270,123 @@ static STRUCTURED_CLONE_CALLBACKS: JSStructuredCloneCallbacks = JSStructuredClon
     canTransfer: Some(can_transfer_callback),
 };
 
-struct StructuredCloneHolder {
-    blob: Option<DomRoot<Blob>>,
+/// The state shared with the structured clone callbacks, through their closure.
+pub enum StructuredDataHolder {
+    /// Used while reading a clone.
+    Read {
+        /// The last blob that was read, rooted for the duration of the read.
+        blob: Option<DomRoot<Blob>>,
+        /// The ports created in the target global for the transferred ones.
+        message_ports: Vec<DomRoot<MessagePort>>,
+        /// The transferred ports, waiting to be claimed by the target global.
+        port_impls: Option<HashMap<MessagePortId, MessagePortImpl>>,
+    },
+    /// Used while writing a clone: the ports being transferred.
+    Write(Option<HashMap<MessagePortId, MessagePortImpl>>),
+}
+
+/// Writes a structured clone, transferring the objects in `transfer`.
+/// Returns a `DataClone` error if that fails.
+pub fn write(
+    cx: *mut JSContext,
+    message: HandleValue,
+    transfer: Option<CustomAutoRooterGuard<Vec<*mut JSObject>>>,
+) -> Fallible<StructuredSerializedData> {
+    unsafe {
+        rooted!(in(cx) let mut transfer_list = UndefinedValue());
+        if let Some(transfer) = transfer {
+            transfer.to_jsval(cx, transfer_list.handle_mut());
+        }
+        let mut sc_holder = StructuredDataHolder::Write(None);
+        let sc_holder_ptr = &mut sc_holder as *mut _;
+        let scbuf = NewJSAutoStructuredCloneBuffer(
+            StructuredCloneScope::DifferentProcess,
+            &STRUCTURED_CLONE_CALLBACKS,
+        );
+        let scdata = &mut ((*scbuf).data_);
+        let policy = CloneDataPolicy {
+            // TODO: SAB?
+            sharedArrayBuffer_: false,
+        };
+        let result = JS_WriteStructuredClone(
+            cx,
+            message,
+            scdata,
+            StructuredCloneScope::DifferentProcess,
+            policy,
+            &STRUCTURED_CLONE_CALLBACKS,
+            sc_holder_ptr as *mut raw::c_void,
+            transfer_list.handle(),
+        );
+        if !result {
+            JS_ClearPendingException(cx);
+            DeleteJSAutoStructuredCloneBuffer(scbuf);
+            return Err(Error::DataClone);
+        }
+
+        let nbytes = GetLengthOfJSStructuredCloneData(scdata);
+        let mut data = Vec::with_capacity(nbytes);
+        CopyJSStructuredCloneData(scdata, data.as_mut_ptr());
+        data.set_len(nbytes);
+
+        DeleteJSAutoStructuredCloneBuffer(scbuf);
+
+        let ports = match sc_holder {
+            StructuredDataHolder::Write(ports) => ports,
+            StructuredDataHolder::Read { .. } => unreachable!(),
+        };
+        Ok(StructuredSerializedData {
+            serialized: data,
+            ports,
+        })
+    }
+}
+
+/// Reads a structured clone into the realm of `global`, and returns
+/// the message ports that were transferred along with it.
+pub fn read(
+    global: &GlobalScope,
+    mut data: StructuredSerializedData,
+    rval: MutableHandleValue,
+) -> Result<Vec<DomRoot<MessagePort>>, ()> {
+    let cx = global.get_cx();
+    let _ac = enter_realm(&*global);
+    let mut sc_holder = StructuredDataHolder::Read {
+        blob: None,
+        message_ports: vec![],
+        port_impls: data.ports.take(),
+    };
+    let sc_holder_ptr = &mut sc_holder as *mut _;
+    unsafe {
+        let scbuf = NewJSAutoStructuredCloneBuffer(
+            StructuredCloneScope::DifferentProcess,
+            &STRUCTURED_CLONE_CALLBACKS,
+        );
+        let scdata = &mut ((*scbuf).data_);
+
+        WriteBytesToJSStructuredCloneData(data.serialized.as_ptr(), data.serialized.len(), scdata);
+
+        let result = JS_ReadStructuredClone(
+            cx,
+            scdata,
+            JS_STRUCTURED_CLONE_VERSION,
+            StructuredCloneScope::DifferentProcess,
+            rval,
+            &STRUCTURED_CLONE_CALLBACKS,
+            sc_holder_ptr as *mut raw::c_void,
+        );
+
+        DeleteJSAutoStructuredCloneBuffer(scbuf);
+
+        if !result {
+            JS_ClearPendingException(cx);
+            return Err(());
+        }
+    }
+    match sc_holder {
+        StructuredDataHolder::Read { message_ports, .. } => Ok(message_ports),
+        StructuredDataHolder::Write(_) => unreachable!(),
+    }
 }
 
 /// A buffer for a structured clone.
@@ -257,40 +401,7 @@ impl StructuredCloneData {
     // TODO: should this be unsafe?
     /// Writes a structured clone. Returns a `DataClone` error if that fails.
     pub fn write(cx: *mut JSContext, message: HandleValue) -> Fallible<StructuredCloneData> {
-        unsafe {
-            let scbuf = NewJSAutoStructuredCloneBuffer(
-                StructuredCloneScope::DifferentProcess,
-                &STRUCTURED_CLONE_CALLBACKS,
-            );
-            let scdata = &mut ((*scbuf).data_);
-            let policy = CloneDataPolicy {
-                // TODO: SAB?
-                sharedArrayBuffer_: false,
-            };
-            let result = JS_WriteStructuredClone(
-                cx,
-                message,
-                scdata,
-                StructuredCloneScope::DifferentProcess,
-                policy,
-                &STRUCTURED_CLONE_CALLBACKS,
-                ptr::null_mut(),
-                HandleValue::undefined(),
-            );
-            if !result {
-                JS_ClearPendingException(cx);
-                return Err(Error::DataClone);
-            }
-
-            let nbytes = GetLengthOfJSStructuredCloneData(scdata);
-            let mut data = Vec::with_capacity(nbytes);
-            CopyJSStructuredCloneData(scdata, data.as_mut_ptr());
-            data.set_len(nbytes);
-
-            DeleteJSAutoStructuredCloneBuffer(scbuf);
-
-            Ok(StructuredCloneData::Vector(data))
-        }
+        write(cx, message, None).map(|data| StructuredCloneData::Vector(data.serialized))
     }
 
     /// Converts a StructuredCloneData to Vec<u8> for inter-thread sharing
@@ -306,46 +417,9 @@ impl StructuredCloneData {
     /// Reads a structured clone.
     ///
     /// Panics if `JS_ReadStructuredClone` fails.
-    fn read_clone(global: &GlobalScope, data: *mut u64, nbytes: size_t, rval: MutableHandleValue) {
-        let cx = global.get_cx();
-        let _ac = enter_realm(&*global);
-        let mut sc_holder = StructuredCloneHolder { blob: None };
-        let sc_holder_ptr = &mut sc_holder as *mut _;
-        unsafe {
-            let scbuf = NewJSAutoStructuredCloneBuffer(
-                StructuredCloneScope::DifferentProcess,
-                &STRUCTURED_CLONE_CALLBACKS,
-            );
-            let scdata = &mut ((*scbuf).data_);
-
-            WriteBytesToJSStructuredCloneData(data as *const u8, nbytes, scdata);
-
-            assert!(JS_ReadStructuredClone(
-                cx,
-                scdata,
-                JS_STRUCTURED_CLONE_VERSION,
-                StructuredCloneScope::DifferentProcess,
-                rval,
-                &STRUCTURED_CLONE_CALLBACKS,
-                sc_holder_ptr as *mut raw::c_void
-            ));
-
-            DeleteJSAutoStructuredCloneBuffer(scbuf);
-        }
-    }
-
-    /// Thunk for the actual `read_clone` method. Resolves proper variant for read_clone.
     pub fn read(self, global: &GlobalScope, rval: MutableHandleValue) {
-        match self {
-            StructuredCloneData::Vector(mut vec_msg) => {
-                let nbytes = vec_msg.len();
-                let data = vec_msg.as_mut_ptr() as *mut u64;
-                StructuredCloneData::read_clone(global, data, nbytes, rval);
-            },
-            StructuredCloneData::Struct(data, nbytes) => {
-                StructuredCloneData::read_clone(global, data, nbytes, rval)
-            },
-        }
+        let data = StructuredSerializedData::new(self.move_to_arraybuffer());
+        read(global, data, rval).expect("JS_ReadStructuredClone failed");
     }
 }
 
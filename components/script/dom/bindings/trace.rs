@@ -76,12 +76,18 @@ use js::typedarray::TypedArrayElement;
 use media::WindowGLContext;
 use metrics::{InteractiveMetrics, InteractiveWindow};
 use mime::Mime;
+use msg::constellation_msg::{BroadcastChannelRouterId, MessagePortId, MessagePortRouterId};
 use msg::constellation_msg::{
     BrowsingContextId, HistoryStateId, PipelineId, TopLevelBrowsingContextId,
 };
+use net_traits::cache_storage_thread::{CacheId, CachedRequest, CachedResponse};
+use net_traits::csp::CspList;
 use net_traits::filemanager_thread::RelativePos;
 use net_traits::image::base::{Image, ImageMetadata};
 use net_traits::image_cache::{ImageCache, PendingImageId};
+use net_traits::indexeddb_thread::{ConnectionId, CursorDirection, DatabaseInfo, IndexInfo};
+use net_traits::indexeddb_thread::{IndexedDBKey, IndexedDBKeyRange, IndexedDBTxnMode};
+use net_traits::indexeddb_thread::{ObjectStoreInfo, Operation, TransactionId};
 use net_traits::request::{Request, RequestBuilder};
 use net_traits::response::HttpsState;
 use net_traits::response::{Response, ResponseBody};
@@ -91,6 +97,7 @@ use profile_traits::mem::ProfilerChan as MemProfilerChan;
 use profile_traits::time::ProfilerChan as TimeProfilerChan;
 use script_layout_interface::rpc::LayoutRPC;
 use script_layout_interface::OpaqueStyleAndLayoutData;
+use script_traits::transferable::MessagePortImpl;
 use script_traits::DrawAPaintImageResult;
 use script_traits::{DocumentActivity, ScriptToConstellationChan, TimerEventId, TimerSource};
 use script_traits::{UntrustedNodeAddress, WindowSizeData, WindowSizeType};
@@ -425,6 +432,12 @@ unsafe_no_jsmanaged_fields!(
     PipelineId,
     TopLevelBrowsingContextId
 );
+unsafe_no_jsmanaged_fields!(
+    BroadcastChannelRouterId,
+    MessagePortId,
+    MessagePortRouterId,
+    MessagePortImpl
+);
 unsafe_no_jsmanaged_fields!(TimerEventId, TimerSource);
 unsafe_no_jsmanaged_fields!(TimelineMarkerType);
 unsafe_no_jsmanaged_fields!(WorkerId);
@@ -436,6 +449,15 @@ unsafe_no_jsmanaged_fields!(UntrustedNodeAddress, OpaqueNode);
 unsafe_no_jsmanaged_fields!(LengthOrPercentageOrAuto);
 unsafe_no_jsmanaged_fields!(RGBA);
 unsafe_no_jsmanaged_fields!(StorageType);
+unsafe_no_jsmanaged_fields!(
+    IndexedDBKey,
+    IndexedDBKeyRange,
+    IndexedDBTxnMode,
+    CursorDirection
+);
+unsafe_no_jsmanaged_fields!(ConnectionId, TransactionId, Operation);
+unsafe_no_jsmanaged_fields!(CacheId, CachedRequest, CachedResponse);
+unsafe_no_jsmanaged_fields!(DatabaseInfo, ObjectStoreInfo, IndexInfo);
 unsafe_no_jsmanaged_fields!(CanvasGradientStop, LinearGradientStyle, RadialGradientStyle);
 unsafe_no_jsmanaged_fields!(LineCapStyle, LineJoinStyle, CompositionOrBlending);
 unsafe_no_jsmanaged_fields!(RepetitionStyle);
@@ -456,6 +478,7 @@ unsafe_no_jsmanaged_fields!(Stylesheet);
 unsafe_no_jsmanaged_fields!(HttpsState);
 unsafe_no_jsmanaged_fields!(Request);
 unsafe_no_jsmanaged_fields!(RequestBuilder);
+unsafe_no_jsmanaged_fields!(CspList);
 unsafe_no_jsmanaged_fields!(StyleSharedRwLock);
 unsafe_no_jsmanaged_fields!(USVString);
 unsafe_no_jsmanaged_fields!(ReferrerPolicy);
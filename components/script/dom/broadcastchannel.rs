@@ -0,0 +1,105 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::BroadcastChannelBinding::{
+    self, BroadcastChannelMethods,
+};
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::rust::HandleValue;
+use script_traits::transferable::BroadcastMsg;
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct BroadcastChannel {
+    eventtarget: EventTarget,
+    name: DOMString,
+    closed: Cell<bool>,
+}
+
+impl BroadcastChannel {
+    /// <https://html.spec.whatwg.org/multipage/#broadcastchannel>
+    pub fn Constructor(
+        global: &GlobalScope,
+        name: DOMString,
+    ) -> Fallible<DomRoot<BroadcastChannel>> {
+        let channel = reflect_dom_object(
+            Box::new(BroadcastChannel::new_inherited(name)),
+            global,
+            BroadcastChannelBinding::Wrap,
+        );
+        global.track_broadcast_channel(&channel);
+        Ok(channel)
+    }
+
+    fn new_inherited(name: DOMString) -> BroadcastChannel {
+        BroadcastChannel {
+            eventtarget: EventTarget::new_inherited(),
+            name,
+            closed: Cell::new(false),
+        }
+    }
+
+    pub fn name(&self) -> &DOMString {
+        &self.name
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#concept-broadcastchannel-closed>
+    pub fn closed(&self) -> bool {
+        self.closed.get()
+    }
+}
+
+impl BroadcastChannelMethods for BroadcastChannel {
+    // https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-name
+    fn Name(&self) -> DOMString {
+        self.name.clone()
+    }
+
+    #[allow(unsafe_code)]
+    // https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage
+    unsafe fn PostMessage(&self, cx: *mut JSContext, message: HandleValue) -> ErrorResult {
+        // Step 3.
+        if self.closed.get() {
+            return Err(Error::InvalidState);
+        }
+
+        // Step 4-5.
+        let data = structuredclone::write(cx, message, None)?;
+
+        // Step 6-8.
+        let global = self.global();
+        let msg = BroadcastMsg {
+            origin: global.origin().immutable().clone(),
+            channel_name: self.name.to_string(),
+            data,
+        };
+        global.schedule_broadcast(msg, self);
+        Ok(())
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-close
+    fn Close(&self) {
+        if self.closed.get() {
+            return;
+        }
+        self.closed.set(true);
+        self.global().untrack_broadcast_channel(self);
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-broadcastchannel-onmessage
+    event_handler!(message, GetOnmessage, SetOnmessage);
+
+    // https://html.spec.whatwg.org/multipage/#handler-broadcastchannel-onmessageerror
+    event_handler!(messageerror, GetOnmessageerror, SetOnmessageerror);
+}
@@ -0,0 +1,468 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::InCompartment;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::CacheBinding;
+use crate::dom::bindings::codegen::Bindings::CacheBinding::{CacheMethods, CacheQueryOptions};
+use crate::dom::bindings::codegen::Bindings::RequestBinding::{RequestInfo, RequestInit};
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::{ResponseMethods, ResponseType};
+use crate::dom::bindings::conversions::root_from_handlevalue;
+use crate::dom::bindings::error::Error;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::cachestorage::{resolve_with_response, send_to_cache_storage};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
+use crate::dom::request::Request;
+use crate::dom::response::{BodyConsumer, Response};
+use crate::fetch::Fetch;
+use dom_struct::dom_struct;
+use http::header::{self, HeaderMap};
+use http::Method;
+use ipc_channel::ipc::IpcSender;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use net_traits::cache_storage_thread::CacheQueryOptions as NetTraitsCacheQueryOptions;
+use net_traits::cache_storage_thread::{CacheId, CacheStorageThreadMsg};
+use net_traits::cache_storage_thread::{CachedRequest, CachedResponse};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::rc::Rc;
+
+// https://w3c.github.io/ServiceWorker/#cache-interface
+#[dom_struct]
+pub struct Cache {
+    reflector_: Reflector,
+    id: CacheId,
+}
+
+impl Cache {
+    fn new_inherited(id: CacheId) -> Cache {
+        Cache {
+            reflector_: Reflector::new(),
+            id: id,
+        }
+    }
+
+    pub fn new(global: &GlobalScope, id: CacheId) -> DomRoot<Cache> {
+        reflect_dom_object(
+            Box::new(Cache::new_inherited(id)),
+            global,
+            CacheBinding::Wrap,
+        )
+    }
+
+    fn send<T>(
+        &self,
+        promise: &Rc<Promise>,
+        message: impl FnOnce(IpcSender<T>, CacheId) -> CacheStorageThreadMsg,
+        settle: fn(&Promise, T),
+    ) where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+    {
+        let id = self.id;
+        send_to_cache_storage(
+            &self.global(),
+            promise,
+            |sender| message(sender, id),
+            settle,
+        );
+    }
+
+    /// Stores entries at once, then resolves the promise.
+    fn put_entries(&self, promise: &Rc<Promise>, entries: Vec<(CachedRequest, CachedResponse)>) {
+        self.send(
+            promise,
+            |sender, id| CacheStorageThreadMsg::Put(sender, id, entries),
+            |promise, ()| promise.resolve_native(&UndefinedValue()),
+        );
+    }
+}
+
+/// The options of a query, as the cache storage thread takes them.
+pub fn query_options(options: &CacheQueryOptions) -> NetTraitsCacheQueryOptions {
+    NetTraitsCacheQueryOptions {
+        ignore_search: options.ignoreSearch,
+        ignore_method: options.ignoreMethod,
+        ignore_vary: options.ignoreVary,
+    }
+}
+
+/// Only GET requests to http(s) URLs can be stored.
+fn check_stored_request(request: &CachedRequest) -> Result<(), Error> {
+    let scheme = request.url.scheme();
+    if (scheme != "http" && scheme != "https") || request.method != Method::GET {
+        return Err(Error::Type(
+            "Only GET requests to http(s) URLs can be cached".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the response varies on every header, which makes it useless to store.
+fn varies_on_everything(headers: &HeaderMap) -> bool {
+    headers.get_all(header::VARY).iter().any(|value| {
+        value.to_str().map_or(false, |value| {
+            value.split(',').any(|name| name.trim() == "*")
+        })
+    })
+}
+
+impl CacheMethods for Cache {
+    // https://w3c.github.io/ServiceWorker/#cache-match
+    fn Match(
+        &self,
+        request: RequestInfo,
+        options: &CacheQueryOptions,
+        comp: InCompartment,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        let request = match Request::from_request_info(&self.global(), request) {
+            Ok(request) => request.to_cached_request(),
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let options = query_options(options);
+        self.send(
+            &promise,
+            |sender, id| CacheStorageThreadMsg::MatchAll(sender, id, Some(request), options),
+            |promise, responses: Vec<CachedResponse>| {
+                resolve_with_response(promise, responses.into_iter().next())
+            },
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-matchall
+    fn MatchAll(
+        &self,
+        request: Option<RequestInfo>,
+        options: &CacheQueryOptions,
+        comp: InCompartment,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        let request =
+            match request.map(|request| Request::from_request_info(&self.global(), request)) {
+                Some(Ok(request)) => Some(request.to_cached_request()),
+                Some(Err(error)) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+                None => None,
+            };
+        let options = query_options(options);
+        self.send(
+            &promise,
+            |sender, id| CacheStorageThreadMsg::MatchAll(sender, id, request, options),
+            |promise, responses: Vec<CachedResponse>| {
+                let global = promise.global();
+                let responses: Vec<DomRoot<Response>> = responses
+                    .into_iter()
+                    .map(|response| Response::from_cached_response(&global, response))
+                    .collect();
+                promise.resolve_native(&responses)
+            },
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-add
+    fn Add(&self, request: RequestInfo, comp: InCompartment) -> Rc<Promise> {
+        self.AddAll(vec![request], comp)
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-addAll
+    fn AddAll(&self, requests: Vec<RequestInfo>, comp: InCompartment) -> Rc<Promise> {
+        let global = self.global();
+        let promise = Promise::new_in_current_compartment(&global, comp);
+
+        // Steps 1-3.
+        let mut checked_requests = vec![];
+        for request in requests {
+            let checked = Request::from_request_info(&global, request).and_then(|request| {
+                let cached_request = request.to_cached_request();
+                check_stored_request(&cached_request)?;
+                Ok((request, cached_request))
+            });
+            match checked {
+                Ok(checked) => checked_requests.push(checked),
+                Err(error) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+            }
+        }
+
+        // Steps 4-7.
+        let job = Rc::new(AddAllJob {
+            cache: Trusted::new(self),
+            promise: promise.clone(),
+            requests: checked_requests
+                .iter()
+                .map(|&(_, ref cached_request)| cached_request.clone())
+                .collect(),
+            responses: DomRefCell::new(vec![None; checked_requests.len()]),
+            failed: Cell::new(false),
+        });
+        if checked_requests.is_empty() {
+            job.store_if_complete();
+        }
+        for (index, (request, _)) in checked_requests.into_iter().enumerate() {
+            let fetched = Fetch(
+                &global,
+                RequestInfo::Request(request),
+                RequestInit::empty(),
+                comp,
+            );
+            let handler = PromiseNativeHandler::new(
+                &global,
+                Some(AddAllFetchHandler::new(&job, index, true)),
+                Some(AddAllFetchHandler::new(&job, index, false)),
+            );
+            fetched.append_native_handler(&handler);
+        }
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-put
+    fn Put(&self, request: RequestInfo, response: &Response, comp: InCompartment) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+
+        // Steps 1-4.
+        let request = match Request::from_request_info(&self.global(), request) {
+            Ok(request) => request.to_cached_request(),
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        if let Err(error) = check_stored_request(&request) {
+            promise.reject_error(error);
+            return promise;
+        }
+
+        // Step 5.
+        if response.Status() == 206 {
+            promise.reject_error(Error::Type("Partial responses can't be cached".to_owned()));
+            return promise;
+        }
+
+        // Step 6.
+        if varies_on_everything(&response.Headers().get_headers_list()) {
+            promise.reject_error(Error::Type(
+                "Responses varying on every header can't be cached".to_owned(),
+            ));
+            return promise;
+        }
+
+        // Step 7.
+        if response.BodyUsed() {
+            promise.reject_error(Error::Type(
+                "The body of the response was already used".to_owned(),
+            ));
+            return promise;
+        }
+
+        // Steps 8-13.
+        response.read_body(Box::new(PutBodyReader {
+            cache: Trusted::new(self),
+            promise: promise.clone(),
+            request: request,
+            response: Trusted::new(response),
+        }));
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-delete
+    fn Delete(
+        &self,
+        request: RequestInfo,
+        options: &CacheQueryOptions,
+        comp: InCompartment,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        let request = match Request::from_request_info(&self.global(), request) {
+            Ok(request) => request.to_cached_request(),
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let options = query_options(options);
+        self.send(
+            &promise,
+            |sender, id| CacheStorageThreadMsg::DeleteEntries(sender, id, request, options),
+            |promise, deleted| promise.resolve_native(&deleted),
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-keys
+    fn Keys(
+        &self,
+        request: Option<RequestInfo>,
+        options: &CacheQueryOptions,
+        comp: InCompartment,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        let request =
+            match request.map(|request| Request::from_request_info(&self.global(), request)) {
+                Some(Ok(request)) => Some(request.to_cached_request()),
+                Some(Err(error)) => {
+                    promise.reject_error(error);
+                    return promise;
+                },
+                None => None,
+            };
+        let options = query_options(options);
+        self.send(
+            &promise,
+            |sender, id| CacheStorageThreadMsg::RequestKeys(sender, id, request, options),
+            |promise, requests: Vec<CachedRequest>| {
+                let global = promise.global();
+                let requests: Vec<DomRoot<Request>> = requests
+                    .into_iter()
+                    .map(|request| Request::from_cached_request(&global, request))
+                    .collect();
+                promise.resolve_native(&requests)
+            },
+        );
+        promise
+    }
+}
+
+/// Stores the response given to `put` once its body is read.
+#[derive(JSTraceable, MallocSizeOf)]
+struct PutBodyReader {
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    cache: Trusted<Cache>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    #[ignore_malloc_size_of = "Defined in net_traits"]
+    request: CachedRequest,
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    response: Trusted<Response>,
+}
+
+impl BodyConsumer for PutBodyReader {
+    fn consume(&self, body: Vec<u8>) {
+        let response = self.response.root().to_cached_response(body);
+        self.cache
+            .root()
+            .put_entries(&self.promise, vec![(self.request.clone(), response)]);
+    }
+}
+
+/// The fetches of `addAll`, whose responses are stored at once when they all succeed.
+#[derive(JSTraceable, MallocSizeOf)]
+struct AddAllJob {
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    cache: Trusted<Cache>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    #[ignore_malloc_size_of = "Defined in net_traits"]
+    requests: Vec<CachedRequest>,
+    #[ignore_malloc_size_of = "Defined in net_traits"]
+    responses: DomRefCell<Vec<Option<CachedResponse>>>,
+    failed: Cell<bool>,
+}
+
+impl AddAllJob {
+    fn fail(&self, message: &str) {
+        if !self.failed.replace(true) {
+            self.promise.reject_error(Error::Type(message.to_owned()));
+        }
+    }
+
+    fn response_read(&self, index: usize, response: CachedResponse) {
+        self.responses.borrow_mut()[index] = Some(response);
+        self.store_if_complete();
+    }
+
+    fn store_if_complete(&self) {
+        if self.failed.get() {
+            return;
+        }
+        let mut responses = self.responses.borrow_mut();
+        if responses.iter().any(Option::is_none) {
+            return;
+        }
+        let entries = self
+            .requests
+            .iter()
+            .cloned()
+            .zip(responses.drain(..).filter_map(|response| response))
+            .collect();
+        self.cache.root().put_entries(&self.promise, entries);
+    }
+}
+
+/// Checks the response of one of the fetches of `addAll`, then reads its body.
+#[derive(JSTraceable, MallocSizeOf)]
+struct AddAllFetchHandler {
+    #[ignore_malloc_size_of = "Rc"]
+    job: Rc<AddAllJob>,
+    index: usize,
+    fulfilled: bool,
+}
+
+impl AddAllFetchHandler {
+    fn new(job: &Rc<AddAllJob>, index: usize, fulfilled: bool) -> Box<dyn Callback> {
+        Box::new(AddAllFetchHandler {
+            job: job.clone(),
+            index: index,
+            fulfilled: fulfilled,
+        })
+    }
+}
+
+impl Callback for AddAllFetchHandler {
+    fn callback(&self, cx: *mut JSContext, v: HandleValue) {
+        if !self.fulfilled {
+            return self.job.fail("A request to add to the cache failed");
+        }
+        let response = match root_from_handlevalue::<Response>(v, cx) {
+            Ok(response) => response,
+            Err(()) => return self.job.fail("A request to add to the cache failed"),
+        };
+        if response.Type() == ResponseType::Error || !response.Ok() || response.Status() == 206 {
+            return self
+                .job
+                .fail("A request to add to the cache got an unsuccessful response");
+        }
+        if varies_on_everything(&response.Headers().get_headers_list()) {
+            return self
+                .job
+                .fail("Responses varying on every header can't be cached");
+        }
+        response.read_body(Box::new(AddAllBodyReader {
+            job: self.job.clone(),
+            index: self.index,
+            response: Trusted::new(&*response),
+        }));
+    }
+}
+
+/// Keeps the response of one of the fetches of `addAll` once its body is read.
+#[derive(JSTraceable, MallocSizeOf)]
+struct AddAllBodyReader {
+    #[ignore_malloc_size_of = "Rc"]
+    job: Rc<AddAllJob>,
+    index: usize,
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    response: Trusted<Response>,
+}
+
+impl BodyConsumer for AddAllBodyReader {
+    fn consume(&self, body: Vec<u8>) {
+        let response = self.response.root().to_cached_response(body);
+        self.job.response_read(self.index, response);
+    }
+}
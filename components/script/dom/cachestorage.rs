@@ -0,0 +1,201 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::{enter_realm, InCompartment};
+use crate::dom::bindings::codegen::Bindings::CacheStorageBinding;
+use crate::dom::bindings::codegen::Bindings::CacheStorageBinding::{
+    CacheStorageMethods, MultiCacheQueryOptions,
+};
+use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInfo;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::refcounted::TrustedPromise;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::cache::{query_options, Cache};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::request::Request;
+use crate::dom::response::Response;
+use crate::task_source::{TaskSource, TaskSourceName};
+use dom_struct::dom_struct;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
+use js::jsval::UndefinedValue;
+use net_traits::cache_storage_thread::{CacheId, CacheStorageThreadMsg, CachedResponse};
+use net_traits::IpcSend;
+use serde::{Deserialize, Serialize};
+use servo_url::ImmutableOrigin;
+use std::rc::Rc;
+
+// https://w3c.github.io/ServiceWorker/#cachestorage-interface
+#[dom_struct]
+pub struct CacheStorage {
+    reflector_: Reflector,
+}
+
+impl CacheStorage {
+    fn new_inherited() -> CacheStorage {
+        CacheStorage {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<CacheStorage> {
+        reflect_dom_object(
+            Box::new(CacheStorage::new_inherited()),
+            global,
+            CacheStorageBinding::Wrap,
+        )
+    }
+
+    /// The origin caches are stored for, which must be a tuple origin.
+    fn origin(&self) -> Fallible<ImmutableOrigin> {
+        let origin = self.global().origin().immutable().clone();
+        if !origin.is_tuple() {
+            return Err(Error::Security);
+        }
+        Ok(origin)
+    }
+
+    /// Sends a message for the caches of the origin, rejecting the promise instead if
+    /// the origin can't have any.
+    fn send<T>(
+        &self,
+        promise: &Rc<Promise>,
+        message: impl FnOnce(IpcSender<T>, ImmutableOrigin) -> CacheStorageThreadMsg,
+        settle: fn(&Promise, T),
+    ) where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+    {
+        match self.origin() {
+            Ok(origin) => send_to_cache_storage(
+                &self.global(),
+                promise,
+                |sender| message(sender, origin),
+                settle,
+            ),
+            Err(error) => promise.reject_error(error),
+        }
+    }
+}
+
+/// Sends a message to the cache storage thread, then settles the promise with the
+/// reply in a task.
+pub fn send_to_cache_storage<T>(
+    global: &GlobalScope,
+    promise: &Rc<Promise>,
+    message: impl FnOnce(IpcSender<T>) -> CacheStorageThreadMsg,
+    settle: fn(&Promise, T),
+) where
+    T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+{
+    let (sender, receiver) = ipc::channel().unwrap();
+    let task_source = global.dom_manipulation_task_source();
+    let canceller = global.task_canceller(TaskSourceName::DOMManipulation);
+    let mut promise = Some(TrustedPromise::new(promise.clone()));
+    ROUTER.add_route(
+        receiver.to_opaque(),
+        Box::new(move |reply| {
+            let reply: T = reply.to().unwrap();
+            let promise = match promise.take() {
+                Some(promise) => promise,
+                None => return,
+            };
+            let _ = task_source.queue_with_canceller(
+                task!(settle_cache_storage_promise: move || {
+                    let promise = promise.root();
+                    let _ac = enter_realm(&*promise);
+                    settle(&promise, reply);
+                }),
+                &canceller,
+            );
+        }),
+    );
+    global.resource_threads().send(message(sender)).unwrap();
+}
+
+/// Resolves a promise with the response a cache matched, if any.
+pub fn resolve_with_response(promise: &Promise, response: Option<CachedResponse>) {
+    match response {
+        Some(response) => {
+            promise.resolve_native(&Response::from_cached_response(&promise.global(), response))
+        },
+        None => promise.resolve_native(&UndefinedValue()),
+    }
+}
+
+impl CacheStorageMethods for CacheStorage {
+    // https://w3c.github.io/ServiceWorker/#cache-storage-match
+    fn Match(
+        &self,
+        request: RequestInfo,
+        options: &MultiCacheQueryOptions,
+        comp: InCompartment,
+    ) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        let request = match Request::from_request_info(&self.global(), request) {
+            Ok(request) => request.to_cached_request(),
+            Err(error) => {
+                promise.reject_error(error);
+                return promise;
+            },
+        };
+        let name = options.cacheName.as_ref().map(|name| name.to_string());
+        let options = query_options(&options.parent);
+        self.send(
+            &promise,
+            |sender, origin| CacheStorageThreadMsg::Match(sender, origin, name, request, options),
+            resolve_with_response,
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-has
+    fn Has(&self, cache_name: DOMString, comp: InCompartment) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        self.send(
+            &promise,
+            |sender, origin| CacheStorageThreadMsg::Has(sender, origin, cache_name.into()),
+            |promise, has| promise.resolve_native(&has),
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-open
+    fn Open(&self, cache_name: DOMString, comp: InCompartment) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        self.send(
+            &promise,
+            |sender, origin| CacheStorageThreadMsg::Open(sender, origin, cache_name.into()),
+            |promise, id: CacheId| promise.resolve_native(&Cache::new(&promise.global(), id)),
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-delete
+    fn Delete(&self, cache_name: DOMString, comp: InCompartment) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        self.send(
+            &promise,
+            |sender, origin| CacheStorageThreadMsg::Delete(sender, origin, cache_name.into()),
+            |promise, deleted| promise.resolve_native(&deleted),
+        );
+        promise
+    }
+
+    // https://w3c.github.io/ServiceWorker/#cache-storage-keys
+    fn Keys(&self, comp: InCompartment) -> Rc<Promise> {
+        let promise = Promise::new_in_current_compartment(&self.global(), comp);
+        self.send(
+            &promise,
+            CacheStorageThreadMsg::Keys,
+            |promise, names: Vec<String>| {
+                let names: Vec<DOMString> = names.into_iter().map(DOMString::from).collect();
+                promise.resolve_native(&names)
+            },
+        );
+        promise
+    }
+}
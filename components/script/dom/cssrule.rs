@@ -13,6 +13,7 @@ use crate::dom::csskeyframerule::CSSKeyframeRule;
 use crate::dom::csskeyframesrule::CSSKeyframesRule;
 use crate::dom::cssmediarule::CSSMediaRule;
 use crate::dom::cssnamespacerule::CSSNamespaceRule;
+use crate::dom::csspagerule::CSSPageRule;
 use crate::dom::cssstylerule::CSSStyleRule;
 use crate::dom::cssstylesheet::CSSStyleSheet;
 use crate::dom::csssupportsrule::CSSSupportsRule;
@@ -63,6 +64,8 @@ impl CSSRule {
             rule as &dyn SpecificCSSRule
         } else if let Some(rule) = self.downcast::<CSSSupportsRule>() {
             rule as &dyn SpecificCSSRule
+        } else if let Some(rule) = self.downcast::<CSSPageRule>() {
+            rule as &dyn SpecificCSSRule
         } else {
             unreachable!()
         }
@@ -103,7 +106,9 @@ impl CSSRule {
             StyleCssRule::Supports(s) => {
                 DomRoot::upcast(CSSSupportsRule::new(window, parent_stylesheet, s))
             },
-            StyleCssRule::Page(_) => unreachable!(),
+            StyleCssRule::Page(s) => {
+                DomRoot::upcast(CSSPageRule::new(window, parent_stylesheet, s))
+            },
             StyleCssRule::Document(_) => unimplemented!(), // TODO
         }
     }
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::CSSStyleSheetBinding;
 use crate::dom::bindings::codegen::Bindings::CSSStyleSheetBinding::CSSStyleSheetMethods;
 use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
@@ -11,9 +12,11 @@ use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::cssrulelist::{CSSRuleList, RulesSource};
 use crate::dom::element::Element;
+use crate::dom::htmlelement::HTMLElement;
 use crate::dom::node::{stylesheets_owner_from_node, Node};
 use crate::dom::stylesheet::StyleSheet;
 use crate::dom::window::Window;
+use crate::stylesheet_loader::StylesheetLoader;
 use dom_struct::dom_struct;
 use servo_arc::Arc;
 use std::cell::Cell;
@@ -28,6 +31,8 @@ pub struct CSSStyleSheet {
     #[ignore_malloc_size_of = "Arc"]
     style_stylesheet: Arc<StyleStyleSheet>,
     origin_clean: Cell<bool>,
+    /// The text the rules of this sheet were last replaced with from devtools.
+    edited_text: DomRefCell<Option<String>>,
 }
 
 impl CSSStyleSheet {
@@ -44,6 +49,7 @@ impl CSSStyleSheet {
             rulelist: MutNullableDom::new(None),
             style_stylesheet: stylesheet,
             origin_clean: Cell::new(true),
+            edited_text: DomRefCell::new(None),
         }
     }
 
@@ -101,6 +107,30 @@ impl CSSStyleSheet {
     pub fn set_origin_clean(&self, origin_clean: bool) {
         self.origin_clean.set(origin_clean);
     }
+
+    pub fn edited_text(&self) -> Option<String> {
+        self.edited_text.borrow().clone()
+    }
+
+    /// Replace the rules of this sheet with the ones of `css`, as edited from devtools, and
+    /// restyle its owner's tree.
+    pub fn set_text(&self, css: String, line_number_offset: u32) {
+        let global = self.global();
+        let url_data = self.style_stylesheet.contents.url_data.read().clone();
+        let loader = StylesheetLoader::for_element(self.owner.downcast::<HTMLElement>().unwrap());
+        StyleStyleSheet::update_from_str(
+            &self.style_stylesheet,
+            &css,
+            url_data,
+            Some(&loader),
+            global.as_window().css_error_reporter(),
+            line_number_offset,
+        );
+        *self.edited_text.borrow_mut() = Some(css);
+        // The DOM rules of the list wrap the rules that were just replaced.
+        self.rulelist.set(None);
+        stylesheets_owner_from_node(self.owner().upcast::<Node>()).invalidate_stylesheets();
+    }
 }
 
 impl CSSStyleSheetMethods for CSSStyleSheet {
@@ -16,14 +16,14 @@ use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::{DomRoot, RootCollection, ThreadLocalStackRoots};
 use crate::dom::bindings::str::DOMString;
-use crate::dom::bindings::structuredclone::StructuredCloneData;
+use crate::dom::bindings::structuredclone;
 use crate::dom::errorevent::ErrorEvent;
 use crate::dom::event::{Event, EventBubbles, EventCancelable, EventStatus};
 use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::messageevent::MessageEvent;
 use crate::dom::worker::{TrustedWorkerAddress, Worker};
-use crate::dom::workerglobalscope::WorkerGlobalScope;
+use crate::dom::workerglobalscope::{install_worker_pipeline_namespace, WorkerGlobalScope};
 use crate::fetch::load_whole_resource;
 use crate::script_runtime::ScriptThreadEventCategory::WorkerEvent;
 use crate::script_runtime::{new_child_runtime, CommonScriptMsg, Runtime, ScriptChan, ScriptPort};
@@ -34,10 +34,10 @@ use devtools_traits::DevtoolScriptControlMsg;
 use dom_struct::dom_struct;
 use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
 use ipc_channel::router::ROUTER;
-use js::jsapi::JSContext;
 use js::jsapi::JS_AddInterruptCallback;
+use js::jsapi::{JSContext, JSObject};
 use js::jsval::UndefinedValue;
-use js::rust::HandleValue;
+use js::rust::{CustomAutoRooterGuard, HandleValue};
 use msg::constellation_msg::{PipelineId, TopLevelBrowsingContextId};
 use net_traits::image_cache::ImageCache;
 use net_traits::request::{CredentialsMode, Destination, ParserMetadata};
@@ -307,6 +307,7 @@ impl DedicatedWorkerGlobalScope {
         let top_level_browsing_context_id = TopLevelBrowsingContextId::installed();
         let current_global = GlobalScope::current().expect("No current global object");
         let origin = current_global.origin().immutable().clone();
+        let owner_csp_list = current_global.get_csp_list();
         let parent = current_global.runtime_handle();
 
         thread::Builder::new()
@@ -317,6 +318,7 @@ impl DedicatedWorkerGlobalScope {
                 if let Some(top_level_browsing_context_id) = top_level_browsing_context_id {
                     TopLevelBrowsingContextId::install(top_level_browsing_context_id);
                 }
+                install_worker_pipeline_namespace(&init.script_to_constellation_chan);
 
                 let roots = RootCollection::new();
                 let _stack_roots = ThreadLocalStackRoots::new(&roots);
@@ -338,7 +340,8 @@ impl DedicatedWorkerGlobalScope {
                     .pipeline_id(pipeline_id)
                     .referrer(referrer)
                     .referrer_policy(referrer_policy)
-                    .origin(origin);
+                    .origin(origin)
+                    .csp_list(owner_csp_list.clone());
 
                 let runtime = unsafe { new_child_runtime(parent) };
 
@@ -398,6 +401,7 @@ impl DedicatedWorkerGlobalScope {
                     },
                     Ok((metadata, bytes)) => (metadata, bytes),
                 };
+                scope.initialize_csp_list(&metadata, owner_csp_list);
                 scope.set_url(metadata.final_url);
                 let source = String::from_utf8_lossy(&bytes);
 
@@ -434,6 +438,7 @@ impl DedicatedWorkerGlobalScope {
                         parent_sender,
                         CommonScriptMsg::CollectReports,
                     );
+                global_scope.remove_web_messaging_infra();
             })
             .expect("Thread spawning failed");
     }
@@ -465,8 +470,17 @@ impl DedicatedWorkerGlobalScope {
                 let target = self.upcast();
                 let _ac = enter_realm(self);
                 rooted!(in(scope.get_cx()) let mut message = UndefinedValue());
-                data.read(scope.upcast(), message.handle_mut());
-                MessageEvent::dispatch_jsval(target, scope.upcast(), message.handle(), None, None);
+                match structuredclone::read(scope.upcast(), data, message.handle_mut()) {
+                    Ok(ports) => MessageEvent::dispatch_jsval(
+                        target,
+                        scope.upcast(),
+                        message.handle(),
+                        None,
+                        None,
+                        ports,
+                    ),
+                    Err(()) => MessageEvent::dispatch_error(target, scope.upcast()),
+                }
             },
             WorkerScriptMsg::Common(msg) => {
                 self.upcast::<WorkerGlobalScope>().process_event(msg);
@@ -560,8 +574,13 @@ unsafe extern "C" fn interrupt_callback(cx: *mut JSContext) -> bool {
 impl DedicatedWorkerGlobalScopeMethods for DedicatedWorkerGlobalScope {
     #[allow(unsafe_code)]
     // https://html.spec.whatwg.org/multipage/#dom-dedicatedworkerglobalscope-postmessage
-    unsafe fn PostMessage(&self, cx: *mut JSContext, message: HandleValue) -> ErrorResult {
-        let data = StructuredCloneData::write(cx, message)?;
+    unsafe fn PostMessage(
+        &self,
+        cx: *mut JSContext,
+        message: HandleValue,
+        transfer: CustomAutoRooterGuard<Vec<*mut JSObject>>,
+    ) -> ErrorResult {
+        let data = structuredclone::write(cx, message, Some(transfer))?;
         let worker = self.worker.borrow().as_ref().unwrap().clone();
         let pipeline_id = self.upcast::<GlobalScope>().pipeline_id();
         let task = Box::new(task!(post_worker_message: move || {
@@ -25,7 +25,7 @@ use crate::dom::bindings::codegen::Bindings::WindowBinding::{
     FrameRequestCallback, ScrollBehavior, WindowMethods,
 };
 use crate::dom::bindings::codegen::UnionTypes::NodeOrString;
-use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::error::{Error, ErrorInfo, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::{Castable, ElementTypeId, HTMLElementTypeId, NodeTypeId};
 use crate::dom::bindings::num::Finite;
 use crate::dom::bindings::refcounted::{Trusted, TrustedPromise};
@@ -71,6 +71,7 @@ use crate::dom::htmliframeelement::HTMLIFrameElement;
 use crate::dom::htmlimageelement::HTMLImageElement;
 use crate::dom::htmlscriptelement::{HTMLScriptElement, ScriptResult};
 use crate::dom::htmltitleelement::HTMLTitleElement;
+use crate::dom::intersectionobserver::IntersectionObserver;
 use crate::dom::keyboardevent::KeyboardEvent;
 use crate::dom::location::Location;
 use crate::dom::messageevent::MessageEvent;
@@ -85,6 +86,7 @@ use crate::dom::processinginstruction::ProcessingInstruction;
 use crate::dom::progressevent::ProgressEvent;
 use crate::dom::promise::Promise;
 use crate::dom::range::Range;
+use crate::dom::resizeobserver::ResizeObserver;
 use crate::dom::servoparser::ServoParser;
 use crate::dom::shadowroot::ShadowRoot;
 use crate::dom::storageevent::StorageEvent;
@@ -117,6 +119,7 @@ use html5ever::{LocalName, Namespace, QualName};
 use hyper_serde::Serde;
 use ipc_channel::ipc::{self, IpcSender};
 use js::jsapi::{JSContext, JSObject, JSRuntime};
+use js::rust::HandleValue;
 use keyboard_types::{Key, KeyState, Modifiers};
 use metrics::{
     InteractiveFlag, InteractiveMetrics, InteractiveWindow, ProfilerMetadataFactory,
@@ -124,6 +127,7 @@ use metrics::{
 };
 use mime::{self, Mime};
 use msg::constellation_msg::BrowsingContextId;
+use net_traits::csp::{CheckResult, CspList, InlineCheckType};
 use net_traits::pub_domains::is_pub_domain;
 use net_traits::request::RequestBuilder;
 use net_traits::response::HttpsState;
@@ -328,6 +332,8 @@ pub struct Document {
     origin: MutableOrigin,
     ///  https://w3c.github.io/webappsec-referrer-policy/#referrer-policy-states
     referrer_policy: Cell<Option<ReferrerPolicy>>,
+    /// <https://w3c.github.io/webappsec-csp/#concept-document-csp-list>
+    csp_list: DomRefCell<Option<CspList>>,
     /// <https://html.spec.whatwg.org/multipage/#dom-document-referrer>
     referrer: Option<String>,
     /// <https://html.spec.whatwg.org/multipage/#target-element>
@@ -385,6 +391,12 @@ pub struct Document {
     shadow_roots: DomRefCell<HashSet<Dom<ShadowRoot>>>,
     /// Whether any of the shadow roots need the stylesheets flushed.
     shadow_roots_styles_changed: Cell<bool>,
+    /// <https://w3c.github.io/IntersectionObserver/#document-intersectionobservertaskqueued>
+    intersection_observer_task_queued: Cell<bool>,
+    /// Intersection observers of this document that have targets.
+    intersection_observers: DomRefCell<Vec<Dom<IntersectionObserver>>>,
+    /// Resize observers of this document that have targets.
+    resize_observers: DomRefCell<Vec<Dom<ResizeObserver>>>,
 }
 
 #[derive(JSTraceable, MallocSizeOf)]
@@ -1718,6 +1730,7 @@ impl Document {
         request: RequestBuilder,
         fetch_target: IpcSender<FetchResponseMsg>,
     ) {
+        let request = request.csp_list(self.get_csp_list());
         let mut loader = self.loader.borrow_mut();
         loader.fetch_async(load, request, fetch_target);
     }
@@ -2457,6 +2470,171 @@ impl Document {
             self.responsive_images.borrow_mut().remove(i);
         }
     }
+
+    pub fn add_intersection_observer(&self, observer: &IntersectionObserver) {
+        self.intersection_observers
+            .borrow_mut()
+            .push(Dom::from_ref(observer));
+    }
+
+    pub fn remove_intersection_observer(&self, observer: &IntersectionObserver) {
+        self.intersection_observers
+            .borrow_mut()
+            .retain(|o| &**o != observer);
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#update-intersection-observations-algo>
+    pub fn update_intersection_observations(&self) {
+        let time = *self.window.Performance().Now();
+        // Step 1.
+        let observers: Vec<DomRoot<IntersectionObserver>> = self
+            .intersection_observers
+            .borrow()
+            .iter()
+            .map(|o| DomRoot::from_ref(&**o))
+            .collect();
+        // Step 2.
+        let mut queued = false;
+        for observer in observers {
+            queued |= observer.update_intersection_observations(self, time);
+        }
+        if queued {
+            self.queue_intersection_observer_task();
+        }
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#queue-intersection-observer-task>
+    fn queue_intersection_observer_task(&self) {
+        // Step 1.
+        if self.intersection_observer_task_queued.get() {
+            return;
+        }
+        // Step 2.
+        self.intersection_observer_task_queued.set(true);
+        // Step 3.
+        let window = self.window();
+        let document = Trusted::new(self);
+        window
+            .task_manager()
+            .dom_manipulation_task_source()
+            .queue(
+                task!(notify_intersection_observers: move || {
+                    document.root().notify_intersection_observers();
+                }),
+                window.upcast(),
+            )
+            .unwrap();
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#notify-intersection-observers-algo>
+    fn notify_intersection_observers(&self) {
+        // Step 1.
+        self.intersection_observer_task_queued.set(false);
+        // Step 2.
+        let observers: Vec<DomRoot<IntersectionObserver>> = self
+            .intersection_observers
+            .borrow()
+            .iter()
+            .map(|o| DomRoot::from_ref(&**o))
+            .collect();
+        // Step 3.
+        for observer in observers {
+            observer.notify();
+        }
+    }
+
+    pub fn add_resize_observer(&self, observer: &ResizeObserver) {
+        self.resize_observers
+            .borrow_mut()
+            .push(Dom::from_ref(observer));
+    }
+
+    pub fn remove_resize_observer(&self, observer: &ResizeObserver) {
+        self.resize_observers
+            .borrow_mut()
+            .retain(|o| &**o != observer);
+    }
+
+    /// The steps of the update the rendering algorithm that deliver resize observations.
+    /// <https://drafts.csswg.org/resize-observer/#html-event-loop>
+    pub fn update_resize_observations(&self) {
+        if self.resize_observers.borrow().is_empty() {
+            return;
+        }
+        // Step 1.
+        let mut depth = 0;
+        // Step 2.
+        self.gather_active_resize_observations_at_depth(depth);
+        // Step 3.
+        while self.has_active_resize_observations() {
+            // Step 3.1.
+            depth = self.broadcast_active_resize_observations();
+            // Step 3.2.
+            self.gather_active_resize_observations_at_depth(depth);
+        }
+        // Step 4.
+        if self.has_skipped_resize_observations() {
+            self.deliver_resize_loop_error_notification();
+        }
+    }
+
+    fn resize_observers(&self) -> Vec<DomRoot<ResizeObserver>> {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .map(|o| DomRoot::from_ref(&**o))
+            .collect()
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#gather-active-observations-h>
+    fn gather_active_resize_observations_at_depth(&self, depth: usize) {
+        for observer in self.resize_observers() {
+            observer.gather_active_observations_at_depth(depth);
+        }
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#has-active-observations-h>
+    fn has_active_resize_observations(&self) -> bool {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .any(|observer| observer.has_active_observations())
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#has-skipped-observations-h>
+    fn has_skipped_resize_observations(&self) -> bool {
+        self.resize_observers
+            .borrow()
+            .iter()
+            .any(|observer| observer.has_skipped_observations())
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#broadcast-active-observations>
+    fn broadcast_active_resize_observations(&self) -> usize {
+        // Step 1.
+        let mut shallowest_target_depth = usize::max_value();
+        // Step 2.
+        for observer in self.resize_observers() {
+            observer.broadcast_active_observations(&mut shallowest_target_depth);
+        }
+        // Step 3.
+        shallowest_target_depth
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#deliver-resize-loop-error-notification>
+    fn deliver_resize_loop_error_notification(&self) {
+        // Step 1-2.
+        let error_info = ErrorInfo {
+            message: "ResizeObserver loop completed with undelivered notifications.".to_owned(),
+            filename: String::new(),
+            lineno: 0,
+            column: 0,
+        };
+        // Step 3.
+        self.window
+            .upcast::<GlobalScope>()
+            .report_an_error(error_info, HandleValue::null());
+    }
 }
 
 #[derive(MallocSizeOf, PartialEq)]
@@ -2728,6 +2906,7 @@ impl Document {
             origin: origin,
             referrer: referrer,
             referrer_policy: Cell::new(referrer_policy),
+            csp_list: DomRefCell::new(None),
             target_element: MutNullableDom::new(None),
             last_click_info: DomRefCell::new(None),
             ignore_destructive_writes_counter: Default::default(),
@@ -2750,6 +2929,9 @@ impl Document {
             delayed_tasks: Default::default(),
             shadow_roots: DomRefCell::new(HashSet::new()),
             shadow_roots_styles_changed: Cell::new(false),
+            intersection_observer_task_queued: Cell::new(false),
+            intersection_observers: Default::default(),
+            resize_observers: Default::default(),
         }
     }
 
@@ -3015,6 +3197,44 @@ impl Document {
         return self.referrer_policy.get();
     }
 
+    pub fn set_csp_list(&self, csp_list: Option<CspList>) {
+        *self.csp_list.borrow_mut() = csp_list;
+    }
+
+    pub fn get_csp_list(&self) -> Option<CspList> {
+        self.csp_list.borrow().clone()
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#attr-meta-http-equiv-content-security-policy>
+    pub fn append_meta_csp_policy(&self, content: &str) {
+        self.csp_list
+            .borrow_mut()
+            .get_or_insert_with(Default::default)
+            .append_meta_policy(content);
+    }
+
+    /// Check inline content of `element` against the CSP list of this document,
+    /// reporting any violation.
+    /// <https://w3c.github.io/webappsec-csp/#should-block-inline>
+    pub fn should_elements_inline_type_behavior_be_blocked(
+        &self,
+        element: &Element,
+        type_: InlineCheckType,
+        source: &str,
+    ) -> CheckResult {
+        let (result, violations) = match *self.csp_list.borrow() {
+            Some(ref csp_list) => {
+                let nonce = element.get_string_attribute(&LocalName::from("nonce"));
+                csp_list.should_elements_inline_type_behavior_be_blocked(&nonce, type_, source)
+            },
+            None => return CheckResult::Allowed,
+        };
+        if !violations.is_empty() {
+            self.global().report_csp_violations(violations);
+        }
+        result
+    }
+
     pub fn set_target_element(&self, node: Option<&Element>) {
         if let Some(ref element) = self.target_element.get() {
             element.set_target_state(false);
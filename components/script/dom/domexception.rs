@@ -37,6 +37,13 @@ pub enum DOMErrorName {
     InvalidNodeTypeError = DOMExceptionConstants::INVALID_NODE_TYPE_ERR,
     DataCloneError = DOMExceptionConstants::DATA_CLONE_ERR,
     NotReadableError = DOMExceptionConstants::NOT_READABLE_ERR,
+    // Names without a legacy code, which have code 0.
+    TransactionInactiveError,
+    ReadOnlyError,
+    VersionError,
+    ConstraintError,
+    DataError,
+    UnknownError,
 }
 
 impl DOMErrorName {
@@ -64,6 +71,12 @@ impl DOMErrorName {
             "InvalidNodeTypeError" => Some(DOMErrorName::InvalidNodeTypeError),
             "DataCloneError" => Some(DOMErrorName::DataCloneError),
             "NotReadableError" => Some(DOMErrorName::NotReadableError),
+            "TransactionInactiveError" => Some(DOMErrorName::TransactionInactiveError),
+            "ReadOnlyError" => Some(DOMErrorName::ReadOnlyError),
+            "VersionError" => Some(DOMErrorName::VersionError),
+            "ConstraintError" => Some(DOMErrorName::ConstraintError),
+            "DataError" => Some(DOMErrorName::DataError),
+            "UnknownError" => Some(DOMErrorName::UnknownError),
             _ => None,
         }
     }
@@ -107,6 +120,22 @@ impl DOMException {
             },
             DOMErrorName::DataCloneError => "The object can not be cloned.",
             DOMErrorName::NotReadableError => "The I/O read operation failed.",
+            DOMErrorName::TransactionInactiveError => {
+                "The request was placed against a transaction which is not active."
+            },
+            DOMErrorName::ReadOnlyError => {
+                "The mutating operation was attempted in a read-only transaction."
+            },
+            DOMErrorName::VersionError => {
+                "The database is at a higher version than the one requested."
+            },
+            DOMErrorName::ConstraintError => {
+                "The mutation operation in the transaction failed because a constraint was not satisfied."
+            },
+            DOMErrorName::DataError => "The provided data is inadequate.",
+            DOMErrorName::UnknownError => {
+                "The operation failed for reasons unrelated to the database itself."
+            },
         };
 
         (
@@ -150,8 +179,8 @@ impl DOMExceptionMethods for DOMException {
     // https://heycam.github.io/webidl/#dfn-DOMException
     fn Code(&self) -> u16 {
         match DOMErrorName::from(&self.name) {
-            Some(code) => code as u16,
-            None => 0 as u16,
+            Some(code) if code as u16 <= DOMExceptionConstants::NOT_READABLE_ERR => code as u16,
+            _ => 0 as u16,
         }
     }
 
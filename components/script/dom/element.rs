@@ -96,6 +96,7 @@ use html5ever::{LocalName, Namespace, Prefix, QualName};
 use js::jsapi::Heap;
 use js::jsval::JSVal;
 use msg::constellation_msg::InputMethodType;
+use net_traits::csp::{CheckResult, InlineCheckType};
 use net_traits::request::CorsSettings;
 use ref_filter_map::ref_filter_map;
 use script_layout_interface::message::ReflowGoal;
@@ -442,6 +443,16 @@ impl Element {
         })
     }
 
+    /// Computed value of neither overflow-x nor overflow-y is "visible"
+    pub fn has_content_clip(&self) -> bool {
+        self.style().map_or(false, |s| {
+            let box_ = s.get_box();
+
+            box_.clone_overflow_x() != overflow_x::computed_value::T::Visible &&
+                box_.clone_overflow_y() != overflow_y::computed_value::T::Visible
+        })
+    }
+
     fn shadow_root(&self) -> Option<DomRoot<ShadowRoot>> {
         self.rare_data()
             .as_ref()?
@@ -2699,7 +2710,7 @@ impl VirtualMethods for Element {
                             _ => false,
                         };
 
-                        let block = if is_declaration {
+                        if is_declaration {
                             let mut value = AttrValue::String(String::new());
                             attr.swap_value(&mut value);
                             let (serialization, block) = match value {
@@ -2708,18 +2719,26 @@ impl VirtualMethods for Element {
                             };
                             let mut value = AttrValue::String(serialization);
                             attr.swap_value(&mut value);
-                            block
+                            Some(block)
+                        } else if doc.should_elements_inline_type_behavior_be_blocked(
+                            self,
+                            InlineCheckType::StyleAttribute,
+                            &**attr.value(),
+                        ) == CheckResult::Blocked
+                        {
+                            // https://drafts.csswg.org/css-style-attr/#interpret
+                            None
                         } else {
                             let win = window_from_node(self);
-                            Arc::new(doc.style_shared_lock().wrap(parse_style_attribute(
-                                &attr.value(),
-                                &doc.base_url(),
-                                win.css_error_reporter(),
-                                doc.quirks_mode(),
+                            Some(Arc::new(doc.style_shared_lock().wrap(
+                                parse_style_attribute(
+                                    &attr.value(),
+                                    &doc.base_url(),
+                                    win.css_error_reporter(),
+                                    doc.quirks_mode(),
+                                ),
                             )))
-                        };
-
-                        Some(block)
+                        }
                     },
                     AttributeMutation::Removed => None,
                 };
@@ -6,6 +6,7 @@ use crate::dom::bindings::callback::ExceptionHandling;
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::EventBinding;
 use crate::dom::bindings::codegen::Bindings::EventBinding::{EventConstants, EventMethods};
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMethods;
 use crate::dom::bindings::codegen::Bindings::PerformanceBinding::DOMHighResTimeStamp;
 use crate::dom::bindings::codegen::Bindings::PerformanceBinding::PerformanceBinding::PerformanceMethods;
 use crate::dom::bindings::error::Fallible;
@@ -18,6 +19,8 @@ use crate::dom::bindings::str::DOMString;
 use crate::dom::document::Document;
 use crate::dom::eventtarget::{CompiledEventListener, EventTarget, ListenerPhase};
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::IDBTransaction;
 use crate::dom::node::Node;
 use crate::dom::virtualmethods::vtable_for;
 use crate::dom::window::Window;
@@ -138,6 +141,15 @@ impl Event {
                     event_path.push(DomRoot::from_ref(document.window().upcast()));
                 }
             }
+        } else if let Some(request) = target.downcast::<IDBRequest>() {
+            // https://w3c.github.io/IndexedDB/#request-construct
+            if let Some(transaction) = request.transaction() {
+                event_path.push(DomRoot::from_ref(transaction.upcast()));
+                event_path.push(DomRoot::upcast(transaction.Db()));
+            }
+        } else if let Some(transaction) = target.downcast::<IDBTransaction>() {
+            // https://w3c.github.io/IndexedDB/#transaction-construct
+            event_path.push(DomRoot::upcast(transaction.Db()));
         }
         event_path
     }
@@ -3,6 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::compartments::enter_realm;
+use crate::dom::abortsignal::AbortAlgorithm;
 use crate::dom::beforeunloadevent::BeforeUnloadEvent;
 use crate::dom::bindings::callback::{CallbackContainer, CallbackFunction, ExceptionHandling};
 use crate::dom::bindings::cell::DomRefCell;
@@ -24,7 +25,7 @@ use crate::dom::bindings::codegen::UnionTypes::EventOrString;
 use crate::dom::bindings::error::{report_pending_exception, Error, Fallible};
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
-use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::root::{Dom, DomRoot};
 use crate::dom::bindings::str::DOMString;
 use crate::dom::element::Element;
 use crate::dom::errorevent::ErrorEvent;
@@ -671,8 +672,18 @@ impl EventTarget {
             Some(l) => l,
             None => return,
         };
+
+        // The listener isn't added if its signal is already aborted.
+        if options
+            .signal
+            .as_ref()
+            .map_or(false, |signal| signal.aborted())
+        {
+            return;
+        }
+
         let mut handlers = self.handlers.borrow_mut();
-        let entry = match handlers.entry(Atom::from(ty)) {
+        let entry = match handlers.entry(Atom::from(&*ty)) {
             Occupied(entry) => entry.into_mut(),
             Vacant(entry) => entry.insert(EventListeners(vec![])),
         };
@@ -684,12 +695,22 @@ impl EventTarget {
         };
         let new_entry = EventListenerEntry {
             phase: phase,
-            listener: EventListenerType::Additive(listener),
+            listener: EventListenerType::Additive(listener.clone()),
             once: options.once,
         };
         if !entry.contains(&new_entry) {
             entry.push(new_entry);
         }
+
+        // The listener is removed when its signal is aborted.
+        if let Some(signal) = options.signal {
+            signal.add_algorithm(AbortAlgorithm::RemoveEventListener(
+                Dom::from_ref(self),
+                ty,
+                listener,
+                options.parent.capture,
+            ));
+        }
     }
 
     // https://dom.spec.whatwg.org/#dom-eventtarget-removeeventlistener
@@ -770,6 +791,7 @@ impl From<AddEventListenerOptionsOrBoolean> for AddEventListenerOptions {
             AddEventListenerOptionsOrBoolean::Boolean(capture) => Self {
                 parent: EventListenerOptions { capture },
                 once: false,
+                signal: None,
             },
         }
     }
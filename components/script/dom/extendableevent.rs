@@ -6,28 +6,36 @@ use crate::dom::bindings::codegen::Bindings::EventBinding::{self, EventMethods};
 use crate::dom::bindings::codegen::Bindings::ExtendableEventBinding;
 use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
 use crate::dom::bindings::inheritance::Castable;
-use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
 use crate::dom::bindings::root::DomRoot;
 use crate::dom::bindings::str::DOMString;
 use crate::dom::event::Event;
+use crate::dom::promise::Promise;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
 use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
 use dom_struct::dom_struct;
 use js::jsapi::JSContext;
 use js::rust::HandleValue;
 use servo_atoms::Atom;
+use std::cell::Cell;
 
 // https://w3c.github.io/ServiceWorker/#extendable-event
 #[dom_struct]
 pub struct ExtendableEvent {
     event: Event,
-    extensions_allowed: bool,
+    /// <https://w3c.github.io/ServiceWorker/#extendableevent-pending-promises-count>
+    pending_promises: Cell<usize>,
+    /// Whether one of the lifetime promises was rejected.
+    rejected: Cell<bool>,
 }
 
 impl ExtendableEvent {
     pub fn new_inherited() -> ExtendableEvent {
         ExtendableEvent {
             event: Event::new_inherited(),
-            extensions_allowed: true,
+            pending_promises: Cell::new(0),
+            rejected: Cell::new(false),
         }
     }
     pub fn new(
@@ -62,13 +70,17 @@ impl ExtendableEvent {
     }
 
     // https://w3c.github.io/ServiceWorker/#wait-until-method
-    pub fn WaitUntil(&self, _cx: *mut JSContext, _val: HandleValue) -> ErrorResult {
+    pub fn WaitUntil(&self, f: &Promise) -> ErrorResult {
         // Step 1
-        if !self.extensions_allowed {
+        if !self.IsTrusted() {
             return Err(Error::InvalidState);
         }
         // Step 2
-        // TODO add a extended_promises array to enqueue the `val`
+        if !self.is_active() {
+            return Err(Error::InvalidState);
+        }
+        // Step 3
+        self.add_lifetime_promise(f);
         Ok(())
     }
 
@@ -76,6 +88,47 @@ impl ExtendableEvent {
     pub fn IsTrusted(&self) -> bool {
         self.event.IsTrusted()
     }
+
+    /// <https://w3c.github.io/ServiceWorker/#extendableevent-active>
+    pub fn is_active(&self) -> bool {
+        self.event.dispatching() || self.has_pending_promises()
+    }
+
+    pub fn has_pending_promises(&self) -> bool {
+        self.pending_promises.get() > 0
+    }
+
+    /// Whether one of the lifetime promises was rejected, once they all settled.
+    pub fn rejected(&self) -> bool {
+        self.rejected.get()
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#extendableevent-add-lifetime-promise>
+    pub fn add_lifetime_promise(&self, promise: &Promise) {
+        // Step 2
+        self.pending_promises.set(self.pending_promises.get() + 1);
+        // Step 3
+        let global = self.global();
+        let handler = PromiseNativeHandler::new(
+            &global,
+            Some(LifetimePromiseHandler::new(self, false)),
+            Some(LifetimePromiseHandler::new(self, true)),
+        );
+        promise.append_native_handler(&handler);
+    }
+
+    fn settle_lifetime_promise(&self, rejected: bool) {
+        if rejected {
+            self.rejected.set(true);
+        }
+        self.pending_promises.set(self.pending_promises.get() - 1);
+        if self.is_active() {
+            return;
+        }
+        if let Some(worker) = self.global().downcast::<ServiceWorkerGlobalScope>() {
+            worker.extendable_event_settled(self);
+        }
+    }
 }
 
 impl Default for ExtendableEventBinding::ExtendableEventInit {
@@ -85,3 +138,27 @@ impl Default for ExtendableEventBinding::ExtendableEventInit {
         }
     }
 }
+
+/// Decrements the pending promises count of an event once one of its lifetime
+/// promises settles.
+#[derive(JSTraceable, MallocSizeOf)]
+struct LifetimePromiseHandler {
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    event: Trusted<ExtendableEvent>,
+    rejected: bool,
+}
+
+impl LifetimePromiseHandler {
+    fn new(event: &ExtendableEvent, rejected: bool) -> Box<dyn Callback> {
+        Box::new(LifetimePromiseHandler {
+            event: Trusted::new(event),
+            rejected: rejected,
+        })
+    }
+}
+
+impl Callback for LifetimePromiseHandler {
+    fn callback(&self, _cx: *mut JSContext, _v: HandleValue) {
+        self.event.root().settle_lifetime_promise(self.rejected);
+    }
+}
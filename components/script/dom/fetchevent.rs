@@ -0,0 +1,304 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use crate::dom::bindings::codegen::Bindings::FetchEventBinding;
+use crate::dom::bindings::codegen::Bindings::FetchEventBinding::FetchEventMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseMethods;
+use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseType as DOMResponseType;
+use crate::dom::bindings::conversions::root_from_handlevalue;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::event::Event;
+use crate::dom::extendableevent::ExtendableEvent;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
+use crate::dom::request::Request;
+use crate::dom::response::{BodyConsumer, Response};
+use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
+use dom_struct::dom_struct;
+use hyper::StatusCode;
+use ipc_channel::ipc::IpcSender;
+use js::jsapi::JSContext;
+use js::rust::HandleValue;
+use net_traits::response::ResponseType;
+use net_traits::{CustomResponse, NetworkError};
+use servo_atoms::Atom;
+use std::cell::Cell;
+
+/// Where the response of a service worker to a fetch is sent: `None` sends the request
+/// to the network instead.
+pub type FetchResponseSender = IpcSender<Option<Result<CustomResponse, NetworkError>>>;
+
+// https://w3c.github.io/ServiceWorker/#fetchevent-interface
+#[dom_struct]
+pub struct FetchEvent {
+    event: ExtendableEvent,
+    request: Dom<Request>,
+    client_id: DOMString,
+    resulting_client_id: DOMString,
+    /// <https://w3c.github.io/ServiceWorker/#fetchevent-respond-with-entered-flag>
+    respond_with_entered: Cell<bool>,
+    /// The fetch the event was dispatched for, until it is responded to.
+    #[ignore_malloc_size_of = "Defined in ipc-channel"]
+    response_chan: DomRefCell<Option<FetchResponseSender>>,
+}
+
+impl FetchEvent {
+    fn new_inherited(
+        request: &Request,
+        client_id: DOMString,
+        resulting_client_id: DOMString,
+    ) -> FetchEvent {
+        FetchEvent {
+            event: ExtendableEvent::new_inherited(),
+            request: Dom::from_ref(request),
+            client_id: client_id,
+            resulting_client_id: resulting_client_id,
+            respond_with_entered: Cell::new(false),
+            response_chan: DomRefCell::new(None),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        type_: Atom,
+        bubbles: bool,
+        cancelable: bool,
+        request: &Request,
+        client_id: DOMString,
+        resulting_client_id: DOMString,
+    ) -> DomRoot<FetchEvent> {
+        let ev = reflect_dom_object(
+            Box::new(FetchEvent::new_inherited(
+                request,
+                client_id,
+                resulting_client_id,
+            )),
+            global,
+            FetchEventBinding::Wrap,
+        );
+        {
+            let event = ev.upcast::<Event>();
+            event.init_event(type_, bubbles, cancelable);
+        }
+        ev
+    }
+
+    pub fn Constructor(
+        worker: &ServiceWorkerGlobalScope,
+        type_: DOMString,
+        init: &FetchEventBinding::FetchEventInit,
+    ) -> Fallible<DomRoot<FetchEvent>> {
+        Ok(FetchEvent::new(
+            worker.upcast(),
+            Atom::from(type_),
+            init.parent.parent.bubbles,
+            init.parent.parent.cancelable,
+            &init.request,
+            init.clientId.clone(),
+            init.resultingClientId.clone(),
+        ))
+    }
+
+    /// The event dispatched for a fetch made by a client, which is answered through
+    /// `response_chan`.
+    pub fn new_for_fetch(
+        worker: &ServiceWorkerGlobalScope,
+        request: &Request,
+        response_chan: FetchResponseSender,
+    ) -> DomRoot<FetchEvent> {
+        let event = FetchEvent::new(
+            worker.upcast(),
+            atom!("fetch"),
+            false,
+            true,
+            request,
+            DOMString::new(),
+            DOMString::new(),
+        );
+        *event.response_chan.borrow_mut() = Some(response_chan);
+        event
+    }
+
+    /// <https://w3c.github.io/ServiceWorker/#handle-fetch>, the steps after the event
+    /// is dispatched: unless `respondWith` was called, the request goes to the network,
+    /// or fails if the event was canceled.
+    pub fn respond_if_not_entered(&self) {
+        if self.respond_with_entered.get() {
+            return;
+        }
+        if self.upcast::<Event>().DefaultPrevented() {
+            self.respond(Some(Err(NetworkError::Internal(
+                "The fetch event was canceled".into(),
+            ))));
+        } else {
+            self.respond(None);
+        }
+    }
+
+    fn respond(&self, response: Option<Result<CustomResponse, NetworkError>>) {
+        if let Some(response_chan) = self.response_chan.borrow_mut().take() {
+            let _ = response_chan.send(response);
+        }
+    }
+
+    fn respond_with_network_error(&self, message: &str) {
+        self.respond(Some(Err(NetworkError::Internal(message.into()))));
+    }
+}
+
+impl FetchEventMethods for FetchEvent {
+    // https://w3c.github.io/ServiceWorker/#fetchevent-request
+    fn Request(&self) -> DomRoot<Request> {
+        DomRoot::from_ref(&*self.request)
+    }
+
+    // https://w3c.github.io/ServiceWorker/#fetchevent-clientid
+    fn ClientId(&self) -> DOMString {
+        self.client_id.clone()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#fetchevent-resultingclientid
+    fn ResultingClientId(&self) -> DOMString {
+        self.resulting_client_id.clone()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#fetch-event-respondwith
+    fn RespondWith(&self, r: &Promise) -> ErrorResult {
+        let event = self.upcast::<Event>();
+        // Step 1.
+        if !event.dispatching() {
+            return Err(Error::InvalidState);
+        }
+        // Step 2.
+        if self.respond_with_entered.get() {
+            return Err(Error::InvalidState);
+        }
+        // Step 3.
+        self.upcast::<ExtendableEvent>().add_lifetime_promise(r);
+        // Steps 4-5.
+        event.StopPropagation();
+        event.StopImmediatePropagation();
+        // Steps 6-7.
+        self.respond_with_entered.set(true);
+        // Steps 8-9.
+        let handler = PromiseNativeHandler::new(
+            &self.global(),
+            Some(RespondWithHandler::new(self, true)),
+            Some(RespondWithHandler::new(self, false)),
+        );
+        r.append_native_handler(&handler);
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-istrusted
+    fn IsTrusted(&self) -> bool {
+        self.upcast::<Event>().IsTrusted()
+    }
+}
+
+/// Settles the `respondWith` promise of a fetch event.
+#[derive(JSTraceable, MallocSizeOf)]
+struct RespondWithHandler {
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    event: Trusted<FetchEvent>,
+    fulfilled: bool,
+}
+
+impl RespondWithHandler {
+    fn new(event: &FetchEvent, fulfilled: bool) -> Box<dyn Callback> {
+        Box::new(RespondWithHandler {
+            event: Trusted::new(event),
+            fulfilled: fulfilled,
+        })
+    }
+}
+
+impl Callback for RespondWithHandler {
+    fn callback(&self, cx: *mut JSContext, v: HandleValue) {
+        let event = self.event.root();
+        // Step 8.
+        if !self.fulfilled {
+            return event.respond_with_network_error("The respondWith promise was rejected");
+        }
+        // Step 9.1.
+        let response = match root_from_handlevalue::<Response>(v, cx) {
+            Ok(response) => response,
+            Err(()) => {
+                return event.respond_with_network_error(
+                    "The respondWith promise was not resolved with a Response",
+                );
+            },
+        };
+        // Step 9.2.
+        if response.BodyUsed() {
+            return event.respond_with_network_error("The body of the response was already used");
+        }
+        // Steps 9.3-9.4.
+        response.read_body(Box::new(ResponseBodyReader {
+            event: Trusted::new(&*event),
+            response: Trusted::new(&*response),
+        }));
+    }
+}
+
+/// Responds to the fetch of a fetch event once the body of the response it was
+/// given is read.
+#[derive(JSTraceable, MallocSizeOf)]
+struct ResponseBodyReader {
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    event: Trusted<FetchEvent>,
+    #[ignore_malloc_size_of = "Trusted<T> has unclear ownership like Dom<T>"]
+    response: Trusted<Response>,
+}
+
+impl BodyConsumer for ResponseBodyReader {
+    fn consume(&self, body: Vec<u8>) {
+        let response = self.response.root();
+        let response_type = match response.Type() {
+            DOMResponseType::Basic => ResponseType::Basic,
+            DOMResponseType::Cors => ResponseType::Cors,
+            DOMResponseType::Default => ResponseType::Default,
+            DOMResponseType::Error => {
+                return self
+                    .event
+                    .root()
+                    .respond_with_network_error("The response is a network error");
+            },
+            DOMResponseType::Opaque => ResponseType::Opaque,
+            DOMResponseType::Opaqueredirect => ResponseType::OpaqueRedirect,
+        };
+        let cached = response.to_cached_response(body);
+        let (code, reason) = cached.status;
+        let status = match (StatusCode::from_u16(code), &response_type) {
+            (Ok(status), _) => status,
+            // The status of an opaque response is hidden, and filtered out again anyway.
+            (Err(_), &ResponseType::Opaque) | (Err(_), &ResponseType::OpaqueRedirect) => {
+                StatusCode::OK
+            },
+            (Err(_), _) => {
+                return self
+                    .event
+                    .root()
+                    .respond_with_network_error("The response has an invalid status");
+            },
+        };
+        let reason = String::from_utf8_lossy(&reason).into_owned();
+        let response = CustomResponse::new(
+            response_type,
+            cached.url,
+            cached.headers,
+            (status, reason),
+            cached.body,
+        );
+        self.event.root().respond(Some(Ok(response)));
+    }
+}
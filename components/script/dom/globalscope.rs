@@ -3,25 +3,37 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventInit;
 use crate::dom::bindings::codegen::Bindings::EventSourceBinding::EventSourceBinding::EventSourceMethods;
+use crate::dom::bindings::codegen::Bindings::SecurityPolicyViolationEventBinding::{
+    SecurityPolicyViolationEventDisposition, SecurityPolicyViolationEventInit,
+};
 use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use crate::dom::bindings::codegen::Bindings::WorkerGlobalScopeBinding::WorkerGlobalScopeMethods;
 use crate::dom::bindings::conversions::{root_from_object, root_from_object_static};
 use crate::dom::bindings::error::{report_pending_exception, ErrorInfo};
 use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
 use crate::dom::bindings::reflector::DomObject;
-use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::settings_stack::{entry_global, incumbent_global, AutoEntryScript};
-use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::str::{DOMString, USVString};
+use crate::dom::bindings::structuredclone;
 use crate::dom::bindings::weakref::DOMTracker;
+use crate::dom::broadcastchannel::BroadcastChannel;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::dedicatedworkerglobalscope::DedicatedWorkerGlobalScope;
 use crate::dom::errorevent::ErrorEvent;
 use crate::dom::event::{Event, EventBubbles, EventCancelable, EventStatus};
 use crate::dom::eventsource::EventSource;
 use crate::dom::eventtarget::EventTarget;
+use crate::dom::idbfactory::IDBFactory;
+use crate::dom::messageevent::MessageEvent;
+use crate::dom::messageport::MessagePort;
 use crate::dom::paintworkletglobalscope::PaintWorkletGlobalScope;
 use crate::dom::performance::Performance;
+use crate::dom::securitypolicyviolationevent::SecurityPolicyViolationEvent;
 use crate::dom::window::Window;
 use crate::dom::workerglobalscope::WorkerGlobalScope;
 use crate::dom::workletglobalscope::WorkletGlobalScope;
@@ -33,35 +45,48 @@ use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::file_reading::FileReadingTaskSource;
 use crate::task_source::networking::NetworkingTaskSource;
 use crate::task_source::performance_timeline::PerformanceTimelineTaskSource;
+use crate::task_source::port_message::PortMessageQueue;
 use crate::task_source::remote_event::RemoteEventTaskSource;
 use crate::task_source::websocket::WebsocketTaskSource;
-use crate::task_source::TaskSourceName;
+use crate::task_source::{TaskSource, TaskSourceName};
 use crate::timers::{IsInterval, OneshotTimerCallback, OneshotTimerHandle};
 use crate::timers::{OneshotTimers, TimerCallback};
 use devtools_traits::{ScriptToDevtoolsControlMsg, WorkerId};
 use dom_struct::dom_struct;
-use ipc_channel::ipc::IpcSender;
+use http::header::{self, HeaderMap, HeaderValue};
+use hyper::Method;
+use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
 use js::glue::{IsWrapper, UnwrapObjectDynamic};
 use js::jsapi::JSObject;
 use js::jsapi::{CurrentGlobalOrNull, GetNonCCWObjectGlobal};
 use js::jsapi::{HandleObject, Heap};
 use js::jsapi::{JSAutoRealm, JSContext};
+use js::jsval::UndefinedValue;
 use js::panic::maybe_resume_unwind;
 use js::rust::wrappers::EvaluateUtf8;
 use js::rust::{get_object_class, CompileOptionsWrapper, ParentRuntime, Runtime};
 use js::rust::{HandleValue, MutableHandleValue};
 use js::{JSCLASS_IS_DOMJSCLASS, JSCLASS_IS_GLOBAL};
 use msg::constellation_msg::PipelineId;
+use msg::constellation_msg::{BroadcastChannelRouterId, MessagePortId, MessagePortRouterId};
+use net_traits::csp::{CheckResult, CspList, InlineCheckType, PolicyDisposition};
+use net_traits::csp::{Violation, ViolationResource};
 use net_traits::image_cache::ImageCache;
-use net_traits::{CoreResourceThread, IpcSend, ResourceThreads};
+use net_traits::request::{
+    CredentialsMode, Destination, RedirectMode, RequestBuilder, RequestMode,
+};
+use net_traits::{fetch_async, CoreResourceThread, IpcSend, ResourceThreads};
 use profile_traits::{mem as profile_mem, time as profile_time};
-use script_traits::{MsDuration, ScriptToConstellationChan, TimerEvent};
+use script_traits::transferable::PortMessageTask;
+use script_traits::transferable::{BroadcastMsg, MessagePortImpl, MessagePortMsg};
+use script_traits::{MsDuration, ScriptMsg, ScriptToConstellationChan, TimerEvent};
 use script_traits::{TimerEventId, TimerSchedulerMsg, TimerSource};
 use servo_url::{MutableOrigin, ServoUrl};
 use std::borrow::Cow;
 use std::cell::Cell;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::CString;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -77,10 +102,51 @@ impl Drop for AutoCloseWorker {
     }
 }
 
+/// A message port managed by a global.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub struct ManagedMessagePort {
+    /// The DOM port.
+    dom_port: Dom<MessagePort>,
+    /// The part of the port that travels with it when it is transferred.
+    #[ignore_malloc_size_of = "Defined in script_traits"]
+    port_impl: MessagePortImpl,
+}
+
+/// The message ports of a global.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub enum MessagePortState {
+    /// The router through which the ports receive their messages,
+    /// and the ports themselves.
+    Managed(
+        #[ignore_malloc_size_of = "Defined in msg"] MessagePortRouterId,
+        HashMap<MessagePortId, ManagedMessagePort>,
+    ),
+    /// This global doesn't manage any ports yet.
+    UnManaged,
+}
+
+/// The broadcast channels of a global.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub enum BroadcastChannelState {
+    /// The router through which the channels receive their messages,
+    /// and the channels themselves by name, in creation order.
+    Managed(
+        #[ignore_malloc_size_of = "Defined in msg"] BroadcastChannelRouterId,
+        HashMap<DOMString, VecDeque<Dom<BroadcastChannel>>>,
+    ),
+    /// This global doesn't have any broadcast channels yet.
+    UnManaged,
+}
+
 #[dom_struct]
 pub struct GlobalScope {
     eventtarget: EventTarget,
     crypto: MutNullableDom<Crypto>,
+    indexed_db: MutNullableDom<IDBFactory>,
+    caches: MutNullableDom<CacheStorage>,
     next_worker_id: Cell<WorkerId>,
 
     /// Pipeline id associated with this global.
@@ -140,6 +206,12 @@ pub struct GlobalScope {
     /// Vector storing references of all eventsources.
     event_source_tracker: DOMTracker<EventSource>,
 
+    /// The message ports managed by this global.
+    message_port_state: DomRefCell<MessagePortState>,
+
+    /// The broadcast channels of this global.
+    broadcast_channel_state: DomRefCell<BroadcastChannelState>,
+
     /// Storage for watching rejected promises waiting for some client to
     /// consume their rejection.
     /// Promises in this list have been rejected in the last turn of the
@@ -184,6 +256,8 @@ impl GlobalScope {
         Self {
             eventtarget: EventTarget::new_inherited(),
             crypto: Default::default(),
+            indexed_db: Default::default(),
+            caches: Default::default(),
             next_worker_id: Cell::new(WorkerId(0)),
             pipeline_id,
             devtools_wants_updates: Default::default(),
@@ -200,6 +274,8 @@ impl GlobalScope {
             microtask_queue,
             list_auto_close_worker: Default::default(),
             event_source_tracker: DOMTracker::new(),
+            message_port_state: DomRefCell::new(MessagePortState::UnManaged),
+            broadcast_channel_state: DomRefCell::new(BroadcastChannelState::UnManaged),
             uncaught_rejections: Default::default(),
             consumed_rejections: Default::default(),
             is_headless,
@@ -232,6 +308,391 @@ impl GlobalScope {
         canceled_any_fetch
     }
 
+    /// Stop receiving messages for the ports and broadcast channels of this global,
+    /// as it is going away.
+    pub fn remove_web_messaging_infra(&self) {
+        if let MessagePortState::Managed(router_id, _) = *self.message_port_state.borrow() {
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::RemoveMessagePortRouter(router_id));
+        }
+        *self.message_port_state.borrow_mut() = MessagePortState::UnManaged;
+
+        if let BroadcastChannelState::Managed(router_id, _) = *self.broadcast_channel_state.borrow()
+        {
+            let _ =
+                self.script_to_constellation_chan()
+                    .send(ScriptMsg::RemoveBroadcastChannelRouter(
+                        router_id,
+                        self.origin().immutable().clone(),
+                    ));
+        }
+        *self.broadcast_channel_state.borrow_mut() = BroadcastChannelState::UnManaged;
+    }
+
+    /// Start managing a port, creating the router through which this global
+    /// receives port messages if needed. `port_impl` is the transferred
+    /// state of an incoming port, or `None` for a brand new one.
+    pub fn track_message_port(&self, dom_port: &MessagePort, port_impl: Option<MessagePortImpl>) {
+        let mut state = self.message_port_state.borrow_mut();
+        if let MessagePortState::UnManaged = *state {
+            let router_id = MessagePortRouterId::new();
+            let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+            let context = Trusted::new(self);
+            let (task_source, canceller) = (
+                self.port_message_queue(),
+                self.task_canceller(TaskSourceName::PortMessage),
+            );
+            ROUTER.add_route(
+                receiver.to_opaque(),
+                Box::new(move |message| {
+                    let msg: MessagePortMsg = match message.to() {
+                        Ok(msg) => msg,
+                        Err(e) => return warn!("Error receiving a MessagePortMsg: {:?}", e),
+                    };
+                    let context = context.clone();
+                    let _ = task_source.queue_with_canceller(
+                        task!(process_message_port_msg: move || {
+                            context.root().handle_message_port_msg(msg);
+                        }),
+                        &canceller,
+                    );
+                }),
+            );
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::NewMessagePortRouter(router_id, sender));
+            *state = MessagePortState::Managed(router_id, HashMap::new());
+        }
+
+        let (router_id, ports) = match *state {
+            MessagePortState::Managed(router_id, ref mut ports) => (router_id, ports),
+            MessagePortState::UnManaged => unreachable!(),
+        };
+        let port_id = *dom_port.message_port_id();
+        let msg = if port_impl.is_some() {
+            ScriptMsg::CompleteMessagePortTransfer(router_id, port_id)
+        } else {
+            ScriptMsg::NewMessagePort(router_id, port_id)
+        };
+        ports.insert(
+            port_id,
+            ManagedMessagePort {
+                dom_port: Dom::from_ref(dom_port),
+                port_impl: port_impl.unwrap_or_else(|| MessagePortImpl::new(port_id)),
+            },
+        );
+        let _ = self.script_to_constellation_chan().send(msg);
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#entangle>
+    pub fn entangle_ports(&self, port1: MessagePortId, port2: MessagePortId) {
+        if let MessagePortState::Managed(_, ref mut ports) = *self.message_port_state.borrow_mut() {
+            for &(port_id, other_id) in &[(port1, port2), (port2, port1)] {
+                if let Some(managed_port) = ports.get_mut(&port_id) {
+                    managed_port.port_impl.entangle(other_id);
+                }
+            }
+        }
+        let _ = self
+            .script_to_constellation_chan()
+            .send(ScriptMsg::EntanglePorts(port1, port2));
+    }
+
+    /// Stop managing a port that is being transferred, and return the state
+    /// that travels with it.
+    pub fn mark_port_as_transferred(&self, port_id: &MessagePortId) -> Option<MessagePortImpl> {
+        let mut port_impl = match *self.message_port_state.borrow_mut() {
+            MessagePortState::Managed(_, ref mut ports) => ports
+                .remove(port_id)
+                .map(|ManagedMessagePort { port_impl, .. }| port_impl),
+            MessagePortState::UnManaged => None,
+        }?;
+        port_impl.set_transfer_pending();
+        let _ = self
+            .script_to_constellation_chan()
+            .send(ScriptMsg::MessagePortShipped(*port_id));
+        Some(port_impl)
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-messageport-start>
+    pub fn start_message_port(&self, port_id: &MessagePortId) {
+        let buffered = match *self.message_port_state.borrow_mut() {
+            MessagePortState::Managed(_, ref mut ports) => ports
+                .get_mut(port_id)
+                .and_then(|managed_port| managed_port.port_impl.start()),
+            MessagePortState::UnManaged => None,
+        };
+        if let Some(tasks) = buffered {
+            self.queue_port_messages(*port_id, tasks);
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-messageport-close>
+    pub fn close_message_port(&self, port_id: &MessagePortId) {
+        if let MessagePortState::Managed(_, ref mut ports) = *self.message_port_state.borrow_mut() {
+            let entangled_port = match ports.get_mut(port_id) {
+                Some(managed_port) => {
+                    managed_port.port_impl.close();
+                    managed_port.port_impl.entangled_port_id()
+                },
+                None => return,
+            };
+            ports.remove(port_id);
+            // Disentangle the other port right away if it is managed here too.
+            if let Some(other) = entangled_port.and_then(|id| ports.get_mut(&id)) {
+                other.port_impl.disentangle();
+            }
+        }
+        let _ = self
+            .script_to_constellation_chan()
+            .send(ScriptMsg::RemoveMessagePort(*port_id));
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-messageport-postmessage>
+    ///
+    /// Send a message to the port entangled with `port_id`. The message is routed
+    /// from a task, so that a transfer of the other port that happens in the
+    /// current task is known to the constellation by then.
+    pub fn post_messageport_msg(&self, port_id: MessagePortId, task: PortMessageTask) {
+        let entangled_port = match *self.message_port_state.borrow() {
+            MessagePortState::Managed(_, ref ports) => ports
+                .get(&port_id)
+                .and_then(|managed_port| managed_port.port_impl.entangled_port_id()),
+            MessagePortState::UnManaged => None,
+        };
+        if let Some(entangled_id) = entangled_port {
+            self.queue_port_messages(entangled_id, vec![task].into());
+        }
+    }
+
+    /// Queue tasks to route messages to a port, be it managed here or elsewhere.
+    fn queue_port_messages(&self, port_id: MessagePortId, tasks: VecDeque<PortMessageTask>) {
+        for task in tasks {
+            let this = Trusted::new(self);
+            let _ = self.port_message_queue().queue(
+                task!(route_port_message: move || {
+                    this.root().route_task_to_port(port_id, task);
+                }),
+                self,
+            );
+        }
+    }
+
+    fn handle_message_port_msg(&self, msg: MessagePortMsg) {
+        match msg {
+            MessagePortMsg::CompleteTransfer(port_id, tasks) => {
+                let pending = match *self.message_port_state.borrow_mut() {
+                    MessagePortState::Managed(_, ref mut ports) => {
+                        ports.get_mut(&port_id).and_then(|managed_port| {
+                            managed_port.port_impl.complete_transfer(tasks);
+                            managed_port.port_impl.take_pending_messages()
+                        })
+                    },
+                    MessagePortState::UnManaged => None,
+                };
+                if let Some(tasks) = pending {
+                    self.queue_port_messages(port_id, tasks);
+                }
+            },
+            MessagePortMsg::NewTask(port_id, task) => self.route_task_to_port(port_id, task),
+        }
+    }
+
+    /// Deliver a message to a port: dispatch it if the port is managed here and
+    /// its queue is enabled, buffer it if the queue isn't, and send it back to
+    /// the constellation if the port isn't managed here (anymore).
+    fn route_task_to_port(&self, port_id: MessagePortId, task: PortMessageTask) {
+        let to_dispatch = match *self.message_port_state.borrow_mut() {
+            MessagePortState::Managed(_, ref mut ports) => match ports.get_mut(&port_id) {
+                Some(managed_port) => managed_port
+                    .port_impl
+                    .handle_incoming(task)
+                    .map(|task| (DomRoot::from_ref(&*managed_port.dom_port), task)),
+                None => {
+                    let _ = self
+                        .script_to_constellation_chan()
+                        .send(ScriptMsg::RerouteMessagePort(port_id, task));
+                    None
+                },
+            },
+            MessagePortState::UnManaged => {
+                let _ = self
+                    .script_to_constellation_chan()
+                    .send(ScriptMsg::RerouteMessagePort(port_id, task));
+                None
+            },
+        };
+
+        // https://html.spec.whatwg.org/multipage/#dom-messageport-postmessage
+        // Step 7, substeps 3-6.
+        if let Some((dom_port, PortMessageTask { origin, data })) = to_dispatch {
+            let target = dom_port.upcast::<EventTarget>();
+            rooted!(in(self.get_cx()) let mut message = UndefinedValue());
+            match structuredclone::read(self, data, message.handle_mut()) {
+                Ok(ports) => MessageEvent::dispatch_jsval(
+                    target,
+                    self,
+                    message.handle(),
+                    Some(&origin.ascii_serialization()),
+                    None,
+                    ports,
+                ),
+                Err(()) => MessageEvent::dispatch_error(target, self),
+            }
+        }
+    }
+
+    /// Start tracking a broadcast channel, creating the router through which
+    /// this global receives broadcasts if needed.
+    pub fn track_broadcast_channel(&self, dom_channel: &BroadcastChannel) {
+        let mut state = self.broadcast_channel_state.borrow_mut();
+        if let BroadcastChannelState::UnManaged = *state {
+            let router_id = BroadcastChannelRouterId::new();
+            let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+            let context = Trusted::new(self);
+            let (task_source, canceller) = (
+                self.dom_manipulation_task_source(),
+                self.task_canceller(TaskSourceName::DOMManipulation),
+            );
+            ROUTER.add_route(
+                receiver.to_opaque(),
+                Box::new(move |message| {
+                    let msg: BroadcastMsg = match message.to() {
+                        Ok(msg) => msg,
+                        Err(e) => return warn!("Error receiving a BroadcastMsg: {:?}", e),
+                    };
+                    let context = context.clone();
+                    let _ = task_source.queue_with_canceller(
+                        task!(broadcast_message_event: move || {
+                            context.root().broadcast_message_event(msg, None);
+                        }),
+                        &canceller,
+                    );
+                }),
+            );
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::NewBroadcastChannelRouter(
+                    router_id,
+                    sender,
+                    self.origin().immutable().clone(),
+                ));
+            *state = BroadcastChannelState::Managed(router_id, HashMap::new());
+        }
+
+        if let BroadcastChannelState::Managed(router_id, ref mut channels) = *state {
+            let name = dom_channel.name().clone();
+            let entry = channels.entry(name.clone()).or_insert_with(|| {
+                let _ = self.script_to_constellation_chan().send(
+                    ScriptMsg::NewBroadcastChannelNameInRouter(
+                        router_id,
+                        name.to_string(),
+                        self.origin().immutable().clone(),
+                    ),
+                );
+                VecDeque::new()
+            });
+            entry.push_back(Dom::from_ref(dom_channel));
+        }
+    }
+
+    /// Stop tracking a closed broadcast channel.
+    pub fn untrack_broadcast_channel(&self, dom_channel: &BroadcastChannel) {
+        if let BroadcastChannelState::Managed(router_id, ref mut channels) =
+            *self.broadcast_channel_state.borrow_mut()
+        {
+            let name = dom_channel.name().clone();
+            if let Entry::Occupied(mut entry) = channels.entry(name.clone()) {
+                entry
+                    .get_mut()
+                    .retain(|channel| &**channel as *const _ != dom_channel as *const _);
+                if entry.get().is_empty() {
+                    entry.remove();
+                    let _ = self.script_to_constellation_chan().send(
+                        ScriptMsg::RemoveBroadcastChannelNameInRouter(
+                            router_id,
+                            name.to_string(),
+                            self.origin().immutable().clone(),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+    ///
+    /// Broadcast a message to the channels of other globals through the constellation,
+    /// and to the other channels of this global directly.
+    pub fn schedule_broadcast(&self, msg: BroadcastMsg, source: &BroadcastChannel) {
+        if let BroadcastChannelState::Managed(router_id, _) = *self.broadcast_channel_state.borrow()
+        {
+            let _ = self
+                .script_to_constellation_chan()
+                .send(ScriptMsg::ScheduleBroadcast(router_id, msg.clone()));
+        }
+        self.broadcast_message_event(msg, Some(source));
+    }
+
+    /// Queue a task to fire a message event on each of the channels of this global
+    /// with the name of the message, except the one it was posted on.
+    pub fn broadcast_message_event(&self, msg: BroadcastMsg, source: Option<&BroadcastChannel>) {
+        // Step 5, documents that aren't fully active don't get broadcasts.
+        if let Some(window) = self.downcast::<Window>() {
+            if !window.Document().is_fully_active() {
+                return;
+            }
+        }
+
+        let destinations: Vec<Trusted<BroadcastChannel>> =
+            match *self.broadcast_channel_state.borrow() {
+                BroadcastChannelState::Managed(_, ref channels) => channels
+                    .get(&DOMString::from(msg.channel_name.clone()))
+                    .map(|channels| {
+                        channels
+                            .iter()
+                            .filter(|channel| {
+                                source.map_or(true, |source| {
+                                    &***channel as *const _ != source as *const _
+                                })
+                            })
+                            .map(|channel| Trusted::new(&**channel))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                BroadcastChannelState::UnManaged => vec![],
+            };
+
+        // Step 7, in creation order.
+        for destination in destinations {
+            let BroadcastMsg { origin, data, .. } = msg.clone();
+            let _ = self.dom_manipulation_task_source().queue(
+                task!(broadcast_message: move || {
+                    let destination = destination.root();
+                    if destination.closed() {
+                        return;
+                    }
+                    let global = destination.global();
+                    let target = destination.upcast::<EventTarget>();
+                    rooted!(in(global.get_cx()) let mut message = UndefinedValue());
+                    match structuredclone::read(&global, data, message.handle_mut()) {
+                        Ok(ports) => MessageEvent::dispatch_jsval(
+                            target,
+                            &global,
+                            message.handle(),
+                            Some(&origin.ascii_serialization()),
+                            None,
+                            ports,
+                        ),
+                        Err(()) => MessageEvent::dispatch_error(target, &global),
+                    }
+                }),
+                self,
+            );
+        }
+    }
+
     /// Returns the global scope of the realm that the given DOM object's reflector
     /// was created in.
     #[allow(unsafe_code)]
@@ -319,6 +780,14 @@ impl GlobalScope {
         self.crypto.or_init(|| Crypto::new(self))
     }
 
+    pub fn indexed_db(&self) -> DomRoot<IDBFactory> {
+        self.indexed_db.or_init(|| IDBFactory::new(self))
+    }
+
+    pub fn caches(&self) -> DomRoot<CacheStorage> {
+        self.caches.or_init(|| CacheStorage::new(self))
+    }
+
     /// Get next worker id.
     pub fn get_next_worker_id(&self) -> WorkerId {
         let worker_id = self.next_worker_id.get();
@@ -482,6 +951,165 @@ impl GlobalScope {
         }
     }
 
+    /// Get the CSP list of this global scope, if any.
+    /// <https://html.spec.whatwg.org/multipage/#concept-settings-object-csp-list>
+    pub fn get_csp_list(&self) -> Option<CspList> {
+        if let Some(window) = self.downcast::<Window>() {
+            return window.Document().get_csp_list();
+        }
+        if let Some(worker) = self.downcast::<WorkerGlobalScope>() {
+            return worker.get_csp_list();
+        }
+        None
+    }
+
+    /// Check inline content that doesn't belong to an element, such as the source of
+    /// a `javascript:` URL, against the CSP list of this global scope.
+    /// <https://w3c.github.io/webappsec-csp/#should-block-inline>
+    pub fn should_inline_behavior_be_blocked(
+        &self,
+        type_: InlineCheckType,
+        source: &str,
+    ) -> CheckResult {
+        let csp_list = match self.get_csp_list() {
+            Some(csp_list) => csp_list,
+            None => return CheckResult::Allowed,
+        };
+        let (result, violations) =
+            csp_list.should_elements_inline_type_behavior_be_blocked("", type_, source);
+        if !violations.is_empty() {
+            self.report_csp_violations(violations);
+        }
+        result
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#can-compile-strings>
+    pub fn is_js_evaluation_allowed(&self) -> bool {
+        let csp_list = match self.get_csp_list() {
+            Some(csp_list) => csp_list,
+            None => return true,
+        };
+        let (result, violations) = csp_list.is_js_evaluation_allowed();
+        if !violations.is_empty() {
+            self.report_csp_violations(violations);
+        }
+        result == CheckResult::Allowed
+    }
+
+    /// <https://w3c.github.io/webappsec-csp/#report-violation>
+    pub fn report_csp_violations(&self, violations: Vec<Violation>) {
+        for violation in violations {
+            let document_url = self.get_url();
+            // Step 1 - 2.
+            let target = match self.downcast::<Window>() {
+                Some(window) => Trusted::new(window.Document().upcast::<EventTarget>()),
+                None => Trusted::new(self.upcast::<EventTarget>()),
+            };
+            let blocked_uri = match violation.resource {
+                ViolationResource::Inline => "inline".to_owned(),
+                ViolationResource::Eval => "eval".to_owned(),
+                ViolationResource::Url(ref url) => strip_url_for_reports(url, &document_url),
+            };
+
+            // Step 3.3 - 3.4.
+            // FIXME: the report-uri directive is deprecated in favour of report-to,
+            // which needs the Reporting API.
+            for report_uri in &violation.report_uris {
+                if let Ok(url) = document_url.join(report_uri) {
+                    self.send_csp_report(url, &document_url, &blocked_uri, &violation);
+                }
+            }
+
+            // Step 3.2.
+            let document_uri = strip_url_for_reports(&document_url, &document_url);
+            let _ = self.dom_manipulation_task_source().queue(
+                task!(fire_security_policy_violation_event: move || {
+                    let target = target.root();
+                    let init = SecurityPolicyViolationEventInit {
+                        parent: EventInit {
+                            bubbles: true,
+                            cancelable: false,
+                        },
+                        documentURI: USVString(document_uri),
+                        referrer: USVString(String::new()),
+                        blockedURI: USVString(blocked_uri),
+                        violatedDirective: DOMString::from(violation.directive.clone()),
+                        effectiveDirective: DOMString::from(violation.effective_directive.clone()),
+                        originalPolicy: DOMString::from(violation.policy.clone()),
+                        sourceFile: USVString(String::new()),
+                        sample: DOMString::from(violation.sample.clone().unwrap_or_default()),
+                        disposition: match violation.disposition {
+                            PolicyDisposition::Enforce => {
+                                SecurityPolicyViolationEventDisposition::Enforce
+                            },
+                            PolicyDisposition::Report => {
+                                SecurityPolicyViolationEventDisposition::Report
+                            },
+                        },
+                        statusCode: 0,
+                        lineNumber: 0,
+                        columnNumber: 0,
+                    };
+                    let event = SecurityPolicyViolationEvent::new(
+                        &target.global(),
+                        atom!("securitypolicyviolation"),
+                        EventBubbles::Bubbles,
+                        EventCancelable::NotCancelable,
+                        &init,
+                    );
+                    event.upcast::<Event>().fire(&target);
+                }),
+                self,
+            );
+        }
+    }
+
+    /// Send a violation report, in the format used by the `report-uri` directive.
+    /// <https://w3c.github.io/webappsec-csp/#deprecated-serialize-violation>
+    fn send_csp_report(
+        &self,
+        report_url: ServoUrl,
+        document_url: &ServoUrl,
+        blocked_uri: &str,
+        violation: &Violation,
+    ) {
+        let disposition = match violation.disposition {
+            PolicyDisposition::Enforce => "enforce",
+            PolicyDisposition::Report => "report",
+        };
+        let mut report = serde_json::json!({
+            "document-uri": strip_url_for_reports(document_url, document_url),
+            "referrer": "",
+            "blocked-uri": blocked_uri,
+            "effective-directive": violation.effective_directive,
+            "violated-directive": violation.directive,
+            "original-policy": violation.policy,
+            "disposition": disposition,
+            "status-code": 0,
+        });
+        if let Some(ref sample) = violation.sample {
+            report["script-sample"] = sample.clone().into();
+        }
+        let body = serde_json::json!({ "csp-report": report }).to_string();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/csp-report"),
+        );
+        let request = RequestBuilder::new(report_url)
+            .method(Method::POST)
+            .headers(headers)
+            .body(Some(body.into_bytes()))
+            .destination(Destination::Report)
+            .mode(RequestMode::NoCors)
+            .credentials_mode(CredentialsMode::CredentialsSameOrigin)
+            .redirect_mode(RedirectMode::Error)
+            .origin(self.origin().immutable().clone())
+            .pipeline_id(Some(self.pipeline_id()));
+        fetch_async(request, &self.core_resource_thread(), |_| {});
+    }
+
     /// Get the `&ResourceThreads` for this global scope.
     pub fn resource_threads(&self) -> &ResourceThreads {
         &self.resource_threads
@@ -527,6 +1155,18 @@ impl GlobalScope {
         unreachable!();
     }
 
+    /// `ScriptChan` to send messages to the port message queue of
+    /// this global scope.
+    pub fn port_message_queue(&self) -> PortMessageQueue {
+        if let Some(window) = self.downcast::<Window>() {
+            return window.task_manager().port_message_queue();
+        }
+        if let Some(worker) = self.downcast::<WorkerGlobalScope>() {
+            return worker.port_message_queue();
+        }
+        unreachable!();
+    }
+
     /// `ScriptChan` to send messages to the websocket task source of
     /// this global scope.
     pub fn websocket_task_source(&self) -> WebsocketTaskSource {
@@ -850,3 +1490,19 @@ unsafe fn global_scope_from_global_static(global: *mut JSObject) -> DomRoot<Glob
     );
     root_from_object_static(global).unwrap()
 }
+
+/// <https://w3c.github.io/webappsec-csp/#strip-url-for-use-in-reports>
+fn strip_url_for_reports(url: &ServoUrl, document_url: &ServoUrl) -> String {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return url.scheme().to_owned();
+    }
+    // Only expose the origin of cross-origin URLs, as their path might be sensitive.
+    if url.origin() != document_url.origin() {
+        return url.origin().ascii_serialization();
+    }
+    let mut url = url.clone().into_url();
+    url.set_fragment(None);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.into_string()
+}
@@ -19,6 +19,7 @@ use crate::dom::domtokenlist::DOMTokenList;
 use crate::dom::element::Element;
 use crate::dom::event::Event;
 use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
 use crate::dom::htmlelement::HTMLElement;
 use crate::dom::htmlimageelement::HTMLImageElement;
 use crate::dom::mouseevent::MouseEvent;
@@ -27,11 +28,13 @@ use crate::dom::urlhelper::UrlHelper;
 use crate::dom::virtualmethods::VirtualMethods;
 use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
-use net_traits::request::Referrer;
+use net_traits::request::{CredentialsMode, Referrer, RequestBuilder};
+use net_traits::CoreResourceMsg;
 use num_traits::ToPrimitive;
 use servo_url::ServoUrl;
 use std::default::Default;
 use style::attr::AttrValue;
+use style::str::HTML_SPACE_CHARACTERS;
 
 #[dom_struct]
 pub struct HTMLAnchorElement {
@@ -125,6 +128,12 @@ impl HTMLAnchorElementMethods for HTMLAnchorElement {
         self.upcast::<Node>().SetTextContent(Some(value))
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-a-download
+    make_getter!(Download, "download");
+
+    // https://html.spec.whatwg.org/multipage/#dom-a-download
+    make_setter!(SetDownload, "download");
+
     // https://html.spec.whatwg.org/multipage/#dom-a-rel
     make_getter!(Rel, "rel");
 
@@ -573,8 +582,11 @@ impl Activatable for HTMLAnchorElement {
         }
 
         // Step 2.
-        //TODO: Download the link is `download` attribute is set.
-        follow_hyperlink(element, ismap_suffix);
+        if element.has_attribute(&local_name!("download")) {
+            download_hyperlink(element, ismap_suffix);
+        } else {
+            follow_hyperlink(element, ismap_suffix);
+        }
     }
 
     //TODO:https://html.spec.whatwg.org/multipage/#the-a-element
@@ -588,6 +600,69 @@ impl Activatable for HTMLAnchorElement {
     }
 }
 
+/// <https://html.spec.whatwg.org/multipage/#downloading-hyperlinks>
+fn download_hyperlink(subject: &Element, hyperlink_suffix: Option<String>) {
+    // Step 1.
+    if subject.cannot_navigate() {
+        return;
+    }
+    // TODO: Step 2, the sandboxed downloads browsing context flag.
+
+    // Step 3-4.
+    let document = document_from_node(subject);
+    let attribute = subject.get_attribute(&ns!(), &local_name!("href")).unwrap();
+    let mut href = attribute.Value();
+    if let Some(ref suffix) = hyperlink_suffix {
+        href.push_str(suffix);
+    }
+    let url = match document.url().join(&href) {
+        Ok(url) => url,
+        Err(_) => return,
+    };
+
+    // The download attribute is ignored for cross-origin resources, whose name
+    // a page mustn't be able to choose.
+    let is_local = url.scheme() == "data" || url.scheme() == "blob";
+    if !is_local && !url.origin().same_origin(document.origin()) {
+        return follow_hyperlink(subject, hyperlink_suffix);
+    }
+
+    // Step 5-6. The file name is picked once the response is received, out of
+    // the one suggested here and the one of its Content-Disposition header.
+    let suggested_name = subject
+        .get_attribute(&ns!(), &local_name!("download"))
+        .map(|attribute| String::from(attribute.Value()))
+        .filter(|name| !name.is_empty());
+    let noreferrer = subject
+        .get_attribute(&ns!(), &local_name!("rel"))
+        .map_or(false, |link_types| {
+            link_types
+                .Value()
+                .split(HTML_SPACE_CHARACTERS)
+                .any(|link_type| link_type.eq_ignore_ascii_case("noreferrer"))
+        });
+    let referrer = if noreferrer {
+        Referrer::NoReferrer
+    } else {
+        Referrer::ReferrerUrl(document.url())
+    };
+    let window = document.window();
+    let request = RequestBuilder::new(url)
+        .credentials_mode(CredentialsMode::Include)
+        .origin(document.origin().immutable().clone())
+        .pipeline_id(Some(window.pipeline_id()))
+        .referrer(Some(referrer))
+        .referrer_policy(document.get_referrer_policy());
+    let msg = CoreResourceMsg::Download(request, suggested_name);
+    if let Err(e) = window
+        .upcast::<GlobalScope>()
+        .core_resource_thread()
+        .send(msg)
+    {
+        warn!("Failed to start download ({})", e);
+    }
+}
+
 /// <https://html.spec.whatwg.org/multipage/#following-hyperlinks-2>
 pub fn follow_hyperlink(subject: &Element, hyperlink_suffix: Option<String>) {
     // Step 1.
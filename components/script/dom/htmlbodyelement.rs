@@ -20,6 +20,7 @@ use cssparser::RGBA;
 use dom_struct::dom_struct;
 use embedder_traits::EmbedderMsg;
 use html5ever::{LocalName, Prefix};
+use net_traits::csp::{CheckResult, InlineCheckType};
 use servo_url::ServoUrl;
 use style::attr::AttrValue;
 
@@ -207,14 +208,25 @@ impl VirtualMethods for HTMLBodyElement {
                     &local_name!("onresize") |
                     &local_name!("onunload") |
                     &local_name!("onerror") => {
-                        let evtarget = window.upcast::<EventTarget>(); // forwarded event
-                        let source_line = 1; //TODO(#9604) obtain current JS execution line
-                        evtarget.set_event_handler_uncompiled(
-                            window.get_url(),
-                            source_line,
-                            &name[2..],
-                            DOMString::from((**attr.value()).to_owned()),
-                        );
+                        let source = DOMString::from((**attr.value()).to_owned());
+                        // https://html.spec.whatwg.org/multipage/#event-handler-attributes:event-handler-content-attributes-3
+                        let blocked = document_from_node(self)
+                            .should_elements_inline_type_behavior_be_blocked(
+                                self.upcast(),
+                                InlineCheckType::ScriptAttribute,
+                                &source,
+                            ) ==
+                            CheckResult::Blocked;
+                        if !blocked {
+                            let evtarget = window.upcast::<EventTarget>(); // forwarded event
+                            let source_line = 1; //TODO(#9604) obtain current JS execution line
+                            evtarget.set_event_handler_uncompiled(
+                                window.get_url(),
+                                source_line,
+                                &name[2..],
+                                source,
+                            );
+                        }
                         false
                     },
                     _ => true, // HTMLElement::attribute_mutated will take care of this.
@@ -34,6 +34,7 @@ use crate::dom::text::Text;
 use crate::dom::virtualmethods::VirtualMethods;
 use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
+use net_traits::csp::{CheckResult, InlineCheckType};
 use script_layout_interface::message::QueryMsg;
 use std::collections::HashSet;
 use std::default::Default;
@@ -726,14 +727,24 @@ impl VirtualMethods for HTMLElement {
         self.super_type().unwrap().attribute_mutated(attr, mutation);
         match (attr.local_name(), mutation) {
             (name, AttributeMutation::Set(_)) if name.starts_with("on") => {
+                // FIXME(ajeffrey): Convert directly from AttrValue to DOMString
+                let source = DOMString::from(&**attr.value());
+                // https://html.spec.whatwg.org/multipage/#event-handler-attributes:event-handler-content-attributes-3
+                if document_from_node(self).should_elements_inline_type_behavior_be_blocked(
+                    self.upcast(),
+                    InlineCheckType::ScriptAttribute,
+                    &source,
+                ) == CheckResult::Blocked
+                {
+                    return;
+                }
                 let evtarget = self.upcast::<EventTarget>();
                 let source_line = 1; //TODO(#9604) get current JS execution line
                 evtarget.set_event_handler_uncompiled(
                     window_from_node(self).get_url(),
                     source_line,
                     &name[2..],
-                    // FIXME(ajeffrey): Convert directly from AttrValue to DOMString
-                    DOMString::from(&**attr.value()),
+                    source,
                 );
             },
             _ => {},
@@ -32,6 +32,7 @@ use euclid::TypedSize2D;
 use html5ever::{LocalName, Prefix};
 use ipc_channel::ipc;
 use msg::constellation_msg::{BrowsingContextId, PipelineId, TopLevelBrowsingContextId};
+use net_traits::csp::{CheckResult, InlineCheckType};
 use net_traits::request::Referrer;
 use profile_traits::ipc as ProfiledIpc;
 use script_layout_interface::message::ReflowGoal;
@@ -139,6 +140,14 @@ impl HTMLIFrameElement {
         if let Some(ref mut load_data) = load_data {
             let is_javascript = load_data.url.scheme() == "javascript";
             if is_javascript {
+                // https://w3c.github.io/webappsec-csp/#should-block-navigation-request
+                if document.global().should_inline_behavior_be_blocked(
+                    InlineCheckType::Navigation,
+                    load_data.url.as_str(),
+                ) == CheckResult::Blocked
+                {
+                    return;
+                }
                 let window_proxy = self.GetContentWindow();
                 if let Some(window_proxy) = window_proxy {
                     ScriptThread::eval_js_url(&window_proxy.global(), load_data);
@@ -269,12 +278,14 @@ impl HTMLIFrameElement {
         };
 
         let document = document_from_node(self);
-        let load_data = LoadData::new(
+        let mut load_data = LoadData::new(
             url,
             creator_pipeline_id,
             Some(Referrer::ReferrerUrl(document.url())),
             document.get_referrer_policy(),
         );
+        // The `frame-src` directive of the document applies to the navigation.
+        load_data.csp_list = document.get_csp_list();
 
         let pipeline_id = self.pipeline_id();
         // If the initial `about:blank` page is the current page, load with replacement enabled,
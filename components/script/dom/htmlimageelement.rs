@@ -54,11 +54,12 @@ use html5ever::{LocalName, Prefix};
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use mime::{self, Mime};
+use net_traits::csp::Violation;
 use net_traits::image::base::{Image, ImageMetadata};
 use net_traits::image_cache::UsePlaceholder;
 use net_traits::image_cache::{CanRequestImages, ImageCache, ImageOrMetadataAvailable};
 use net_traits::image_cache::{ImageResponder, ImageResponse, ImageState, PendingImageId};
-use net_traits::request::RequestBuilder;
+use net_traits::request::{Destination, RequestBuilder};
 use net_traits::{FetchMetadata, FetchResponseListener, FetchResponseMsg, NetworkError};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use num_traits::ToPrimitive;
@@ -241,6 +242,11 @@ impl FetchResponseListener for ImageContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for ImageContext {
@@ -329,7 +335,9 @@ impl HTMLImageElement {
 
         let request = RequestBuilder::new(img_url.clone())
             .origin(document.origin().immutable().clone())
-            .pipeline_id(Some(document.global().pipeline_id()));
+            .destination(Destination::Image)
+            .pipeline_id(Some(document.global().pipeline_id()))
+            .csp_list(document.get_csp_list());
 
         // This is a background load because the load blocker already fulfills the
         // purpose of delaying the document's load event.
@@ -66,6 +66,7 @@ use http::header::{self, HeaderMap, HeaderValue};
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use media::{glplayer_channel, GLPlayerMsg, GLPlayerMsgForward};
+use net_traits::csp::Violation;
 use net_traits::image::base::Image;
 use net_traits::image_cache::ImageResponse;
 use net_traits::request::{CredentialsMode, Destination, Referrer, RequestBuilder, RequestMode};
@@ -811,7 +812,8 @@ impl HTMLMediaElement {
             .origin(document.origin().immutable().clone())
             .pipeline_id(Some(self.global().pipeline_id()))
             .referrer(Some(Referrer::ReferrerUrl(document.url())))
-            .referrer_policy(document.get_referrer_policy());
+            .referrer_policy(document.get_referrer_policy())
+            .csp_list(document.get_csp_list());
 
         let mut current_fetch_context = self.current_fetch_context.borrow_mut();
         if let Some(ref mut current_fetch_context) = *current_fetch_context {
@@ -2571,6 +2573,11 @@ impl FetchResponseListener for HTMLMediaElementFetchListener {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for HTMLMediaElementFetchListener {
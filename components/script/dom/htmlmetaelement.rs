@@ -98,6 +98,33 @@ impl HTMLMetaElement {
                 self.apply_referrer();
             }
         }
+
+        if let Some(ref http_equiv) = element.get_attribute(&ns!(), &local_name!("http-equiv")) {
+            let http_equiv = http_equiv.value();
+            let http_equiv = http_equiv.trim_matches(HTML_SPACE_CHARACTERS);
+
+            if http_equiv.eq_ignore_ascii_case("content-security-policy") {
+                self.apply_content_security_policy();
+            }
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#attr-meta-http-equiv-content-security-policy>
+    fn apply_content_security_policy(&self) {
+        // Step 1.
+        let in_head = self
+            .upcast::<Node>()
+            .GetParentElement()
+            .map_or(false, |parent| parent.is::<HTMLHeadElement>());
+        if !in_head {
+            return;
+        }
+
+        // Step 2 - 5.
+        let element = self.upcast::<Element>();
+        if let Some(ref content) = element.get_attribute(&ns!(), &local_name!("content")) {
+            document_from_node(self).append_meta_csp_policy(&content.value());
+        }
     }
 
     #[allow(unrooted_must_root)]
@@ -32,6 +32,7 @@ use html5ever::{LocalName, Prefix};
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
 use js::jsval::UndefinedValue;
+use net_traits::csp::{CheckResult, InlineCheckType, Violation};
 use net_traits::request::{
     CorsSettings, CredentialsMode, Destination, Referrer, RequestBuilder, RequestMode,
 };
@@ -258,6 +259,11 @@ impl FetchResponseListener for ScriptContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for ScriptContext {
@@ -286,6 +292,7 @@ fn fetch_a_classic_script(
     url: ServoUrl,
     cors_setting: Option<CorsSettings>,
     integrity_metadata: String,
+    cryptographic_nonce: String,
     character_encoding: &'static Encoding,
 ) {
     let doc = document_from_node(script);
@@ -309,7 +316,8 @@ fn fetch_a_classic_script(
         .pipeline_id(Some(script.global().pipeline_id()))
         .referrer(Some(Referrer::ReferrerUrl(doc.url())))
         .referrer_policy(doc.get_referrer_policy())
-        .integrity_metadata(integrity_metadata);
+        .integrity_metadata(integrity_metadata)
+        .cryptographic_nonce_metadata(cryptographic_nonce);
 
     // TODO: Step 3, Add custom steps to perform fetch
 
@@ -402,7 +410,16 @@ impl HTMLScriptElement {
 
         // TODO: Step 11: nomodule content attribute
 
-        // TODO(#4577): Step 12: CSP.
+        // Step 12.
+        if !element.has_attribute(&local_name!("src")) &&
+            doc.should_elements_inline_type_behavior_be_blocked(
+                element,
+                InlineCheckType::Script,
+                &text,
+            ) == CheckResult::Blocked
+        {
+            return;
+        }
 
         // Step 13.
         let for_attribute = element.get_attribute(&ns!(), &local_name!("for"));
@@ -435,7 +452,8 @@ impl HTMLScriptElement {
 
         // TODO: Step 16: Module script credentials mode.
 
-        // TODO: Step 17: Nonce.
+        // Step 17.
+        let cryptographic_nonce = element.get_string_attribute(&LocalName::from("nonce"));
 
         // Step 18: Integrity metadata.
         let im_attribute = element.get_attribute(&ns!(), &local_name!("integrity"));
@@ -499,6 +517,7 @@ impl HTMLScriptElement {
                 url,
                 cors_setting,
                 integrity_metadata.to_owned(),
+                cryptographic_nonce.into(),
                 encoding,
             );
 
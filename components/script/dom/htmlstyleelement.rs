@@ -22,6 +22,7 @@ use crate::stylesheet_loader::{StylesheetLoader, StylesheetOwner};
 use cssparser::{Parser as CssParser, ParserInput};
 use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
+use net_traits::csp::{CheckResult, InlineCheckType};
 use net_traits::ReferrerPolicy;
 use servo_arc::Arc;
 use std::cell::Cell;
@@ -96,6 +97,18 @@ impl HTMLStyleElement {
         let data = node
             .GetTextContent()
             .expect("Element.textContent must be a string");
+
+        // https://html.spec.whatwg.org/multipage/#update-a-style-block
+        // Step 5.
+        if doc.should_elements_inline_type_behavior_be_blocked(
+            element,
+            InlineCheckType::Style,
+            &data,
+        ) == CheckResult::Blocked
+        {
+            return;
+        }
+
         let url = window.get_url();
         let css_error_reporter = window.css_error_reporter();
         let context = CssParserContext::new_for_cssom(
@@ -153,6 +166,12 @@ impl HTMLStyleElement {
         self.stylesheet.borrow().clone()
     }
 
+    /// The line the text of this element starts at, which the source locations of the rules
+    /// of its stylesheet are relative to.
+    pub fn line_number(&self) -> u32 {
+        self.line_number as u32
+    }
+
     pub fn get_cssom_stylesheet(&self) -> Option<DomRoot<CSSStyleSheet>> {
         self.get_stylesheet().map(|sheet| {
             self.cssom_stylesheet.or_init(|| {
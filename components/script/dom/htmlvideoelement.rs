@@ -26,6 +26,7 @@ use dom_struct::dom_struct;
 use html5ever::{LocalName, Prefix};
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
+use net_traits::csp::Violation;
 use net_traits::image_cache::UsePlaceholder;
 use net_traits::image_cache::{CanRequestImages, ImageCache, ImageOrMetadataAvailable};
 use net_traits::image_cache::{ImageResponse, ImageState, PendingImageId};
@@ -167,7 +168,8 @@ impl HTMLVideoElement {
             .credentials_mode(CredentialsMode::Include)
             .use_url_credentials(true)
             .origin(document.origin().immutable().clone())
-            .pipeline_id(Some(document.global().pipeline_id()));
+            .pipeline_id(Some(document.global().pipeline_id()))
+            .csp_list(document.get_csp_list());
 
         // Step 5.
         // This delay must be independent from the ones created by HTMLMediaElement during
@@ -340,6 +342,11 @@ impl FetchResponseListener for PosterFrameFetchContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for PosterFrameFetchContext {
@@ -0,0 +1,377 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorMethods;
+use crate::dom::bindings::codegen::UnionTypes::IDBObjectStoreOrIDBIndex;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::structuredclone::StructuredCloneData;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::{IDBRequest, RequestSource};
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::indexed_db::{convert_value_to_key, cursor_direction_to_idl, extract_key};
+use crate::indexed_db::{index_keys_for_value, key_to_jsval};
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSContext};
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::Operation;
+use net_traits::indexeddb_thread::{CursorDirection, CursorPosition, CursorRecord};
+use net_traits::indexeddb_thread::{IndexedDBKey, IndexedDBKeyRange, IndexedDBTxnMode};
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct IDBCursor {
+    reflector_: Reflector,
+    object_store: Dom<IDBObjectStore>,
+    /// The index iterated over, if the source of the cursor is an index.
+    index: Option<Dom<IDBIndex>>,
+    direction: CursorDirection,
+    range: IndexedDBKeyRange,
+    key_only: bool,
+    /// The key and primary key of the record the cursor is at.
+    position: DomRefCell<Option<(IndexedDBKey, IndexedDBKey)>>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    key: Heap<JSVal>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    primary_key: Heap<JSVal>,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    value: Heap<JSVal>,
+    request: Dom<IDBRequest>,
+    got_value: Cell<bool>,
+}
+
+impl IDBCursor {
+    pub fn new_inherited(
+        object_store: &IDBObjectStore,
+        index: Option<&IDBIndex>,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+        key_only: bool,
+        request: &IDBRequest,
+    ) -> IDBCursor {
+        IDBCursor {
+            reflector_: Reflector::new(),
+            object_store: Dom::from_ref(object_store),
+            index: index.map(Dom::from_ref),
+            direction: direction,
+            range: range,
+            key_only: key_only,
+            position: DomRefCell::new(None),
+            key: Heap::default(),
+            primary_key: Heap::default(),
+            value: Heap::default(),
+            request: Dom::from_ref(request),
+            got_value: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        object_store: &IDBObjectStore,
+        index: Option<&IDBIndex>,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+        request: &IDBRequest,
+    ) -> DomRoot<IDBCursor> {
+        reflect_dom_object(
+            Box::new(IDBCursor::new_inherited(
+                object_store,
+                index,
+                direction,
+                range,
+                true,
+                request,
+            )),
+            global,
+            IDBCursorBinding::Wrap,
+        )
+    }
+
+    pub fn value(&self) -> JSVal {
+        self.value.get()
+    }
+
+    /// Move the cursor to the record the backend found, returning false if
+    /// it went past the end of its range.
+    #[allow(unsafe_code)]
+    pub unsafe fn set_record(&self, cx: *mut JSContext, record: Option<CursorRecord>) -> bool {
+        let record = match record {
+            Some(record) => record,
+            None => {
+                *self.position.borrow_mut() = None;
+                self.key.set(UndefinedValue());
+                self.primary_key.set(UndefinedValue());
+                self.value.set(UndefinedValue());
+                return false;
+            },
+        };
+        rooted!(in(cx) let mut value = UndefinedValue());
+        key_to_jsval(cx, &record.key, value.handle_mut());
+        self.key.set(value.get());
+        key_to_jsval(cx, &record.primary_key, value.handle_mut());
+        self.primary_key.set(value.get());
+        if let Some(bytes) = record.value {
+            StructuredCloneData::Vector(bytes).read(&self.global(), value.handle_mut());
+            self.value.set(value.get());
+        }
+        *self.position.borrow_mut() = Some((record.key, record.primary_key));
+        self.got_value.set(true);
+        true
+    }
+
+    fn transaction(&self) -> DomRoot<IDBTransaction> {
+        self.object_store.transaction()
+    }
+
+    fn is_source_deleted(&self) -> bool {
+        match self.index {
+            Some(ref index) => index.is_deleted(),
+            None => self.object_store.is_deleted(),
+        }
+    }
+
+    /// The checks shared by the methods that move the cursor.
+    fn check_iterable(&self) -> ErrorResult {
+        if !self.transaction().is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if self.is_source_deleted() || !self.got_value.get() {
+            return Err(Error::InvalidState);
+        }
+        Ok(())
+    }
+
+    /// The checks shared by `update()` and `delete()`.
+    fn check_writable(&self) -> ErrorResult {
+        let transaction = self.transaction();
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if transaction.mode() == IndexedDBTxnMode::Readonly {
+            return Err(Error::ReadOnly);
+        }
+        if self.is_source_deleted() || !self.got_value.get() || self.key_only {
+            return Err(Error::InvalidState);
+        }
+        Ok(())
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#iterate-a-cursor>, run again with the
+    /// request that opened the cursor.
+    fn iterate(
+        &self,
+        target_key: Option<IndexedDBKey>,
+        target_primary_key: Option<IndexedDBKey>,
+        count: u32,
+    ) {
+        self.got_value.set(false);
+        self.request.reset();
+        let operation = Operation::Iterate {
+            store: self.object_store.name(),
+            index: self.index.as_ref().map(|index| index.name()),
+            range: self.range.clone(),
+            direction: self.direction,
+            position: CursorPosition {
+                current: self.position.borrow().clone(),
+                target_key: target_key,
+                target_primary_key: target_primary_key,
+                count: count,
+            },
+            key_only: self.key_only,
+        };
+        self.transaction()
+            .queue_request(&self.request, operation, Some(self), None);
+    }
+
+    fn primary_key(&self) -> Option<IndexedDBKey> {
+        self.position
+            .borrow()
+            .as_ref()
+            .map(|&(_, ref primary_key)| primary_key.clone())
+    }
+}
+
+impl IDBCursorMethods for IDBCursor {
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-source
+    fn Source(&self) -> IDBObjectStoreOrIDBIndex {
+        match self.index {
+            Some(ref index) => IDBObjectStoreOrIDBIndex::IDBIndex(DomRoot::from_ref(&**index)),
+            None => {
+                IDBObjectStoreOrIDBIndex::IDBObjectStore(DomRoot::from_ref(&*self.object_store))
+            },
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-direction
+    fn Direction(&self) -> IDBCursorDirection {
+        cursor_direction_to_idl(self.direction)
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-key
+    unsafe fn Key(&self, _cx: *mut JSContext) -> JSVal {
+        self.key.get()
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-primarykey
+    unsafe fn PrimaryKey(&self, _cx: *mut JSContext) -> JSVal {
+        self.primary_key.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-request
+    fn Request(&self) -> DomRoot<IDBRequest> {
+        DomRoot::from_ref(&*self.request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-advance
+    fn Advance(&self, count: u32) -> ErrorResult {
+        // Step 1.
+        if count == 0 {
+            return Err(Error::Type("The count must not be zero".to_owned()));
+        }
+        // Steps 2-5.
+        self.check_iterable()?;
+        // Steps 6-8.
+        self.iterate(None, None, count);
+        Ok(())
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-continue
+    unsafe fn Continue(&self, cx: *mut JSContext, key: HandleValue) -> ErrorResult {
+        // Steps 1-4.
+        self.check_iterable()?;
+        // Step 5.
+        let key = if key.is_undefined() {
+            None
+        } else {
+            let key = convert_value_to_key(cx, key, &mut vec![])?;
+            let current = self
+                .position
+                .borrow()
+                .as_ref()
+                .map(|&(ref key, _)| key.clone());
+            let valid = match (self.direction, current) {
+                (CursorDirection::Next, Some(ref current)) |
+                (CursorDirection::NextUnique, Some(ref current)) => key > *current,
+                (CursorDirection::Prev, Some(ref current)) |
+                (CursorDirection::PrevUnique, Some(ref current)) => key < *current,
+                (_, None) => true,
+            };
+            if !valid {
+                return Err(Error::Data);
+            }
+            Some(key)
+        };
+        // Steps 6-8.
+        self.iterate(key, None, 1);
+        Ok(())
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-continueprimarykey
+    unsafe fn ContinuePrimaryKey(
+        &self,
+        cx: *mut JSContext,
+        key: HandleValue,
+        primary_key: HandleValue,
+    ) -> ErrorResult {
+        // Steps 1-2.
+        if !self.transaction().is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        if self.is_source_deleted() {
+            return Err(Error::InvalidState);
+        }
+        // Steps 3-4.
+        let unique = match self.direction {
+            CursorDirection::NextUnique | CursorDirection::PrevUnique => true,
+            CursorDirection::Next | CursorDirection::Prev => false,
+        };
+        if self.index.is_none() || unique {
+            return Err(Error::InvalidAccess);
+        }
+        // Step 5.
+        if !self.got_value.get() {
+            return Err(Error::InvalidState);
+        }
+        // Steps 6-7.
+        let key = convert_value_to_key(cx, key, &mut vec![])?;
+        let primary_key = convert_value_to_key(cx, primary_key, &mut vec![])?;
+        // Steps 8-11.
+        if let Some((ref current_key, ref current_primary_key)) = *self.position.borrow() {
+            let forwards = self.direction == CursorDirection::Next;
+            let before = if forwards {
+                key < *current_key || (key == *current_key && primary_key <= *current_primary_key)
+            } else {
+                key > *current_key || (key == *current_key && primary_key >= *current_primary_key)
+            };
+            if before {
+                return Err(Error::Data);
+            }
+        }
+        // Steps 12-14.
+        self.iterate(Some(key), Some(primary_key), 1);
+        Ok(())
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-update
+    unsafe fn Update(
+        &self,
+        cx: *mut JSContext,
+        value: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-6.
+        self.check_writable()?;
+        // Steps 7-8.
+        let clone = StructuredCloneData::write(cx, value)?.move_to_arraybuffer();
+        // Step 9.
+        let primary_key = self.primary_key().ok_or(Error::InvalidState)?;
+        let info = self.object_store.info();
+        if let Some(ref key_path) = info.key_path {
+            match extract_key(cx, value, key_path, false)? {
+                Some(ref key) if *key == primary_key => {},
+                _ => return Err(Error::Data),
+            }
+        }
+        let index_keys = index_keys_for_value(cx, value, &info.indexes)?;
+        // Step 10.
+        let operation = Operation::Put {
+            store: info.name.clone(),
+            key: Some(primary_key),
+            value: clone,
+            index_keys: index_keys,
+            no_overwrite: false,
+        };
+        let transaction = self.transaction();
+        let request = IDBRequest::new(&self.global(), RequestSource::Cursor(self), &transaction);
+        transaction.queue_request(&request, operation, None, None);
+        Ok(request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbcursor-delete
+    fn Delete(&self) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_writable()?;
+        // Steps 6-7.
+        let primary_key = self.primary_key().ok_or(Error::InvalidState)?;
+        let operation = Operation::Delete(
+            self.object_store.name(),
+            IndexedDBKeyRange::only(primary_key),
+        );
+        let transaction = self.transaction();
+        let request = IDBRequest::new(&self.global(), RequestSource::Cursor(self), &transaction);
+        transaction.queue_request(&request, operation, None, None);
+        Ok(request)
+    }
+}
@@ -0,0 +1,46 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBCursorWithValueBinding;
+use crate::dom::bindings::codegen::Bindings::IDBCursorWithValueBinding::IDBCursorWithValueMethods;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::IDBRequest;
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::JSVal;
+use net_traits::indexeddb_thread::{CursorDirection, IndexedDBKeyRange};
+
+#[dom_struct]
+pub struct IDBCursorWithValue {
+    cursor: IDBCursor,
+}
+
+impl IDBCursorWithValue {
+    pub fn new(
+        global: &GlobalScope,
+        object_store: &IDBObjectStore,
+        index: Option<&IDBIndex>,
+        direction: CursorDirection,
+        range: IndexedDBKeyRange,
+        request: &IDBRequest,
+    ) -> DomRoot<IDBCursorWithValue> {
+        let cursor = IDBCursorWithValue {
+            cursor: IDBCursor::new_inherited(object_store, index, direction, range, false, request),
+        };
+        reflect_dom_object(Box::new(cursor), global, IDBCursorWithValueBinding::Wrap)
+    }
+}
+
+impl IDBCursorWithValueMethods for IDBCursorWithValue {
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbcursorwithvalue-value
+    unsafe fn Value(&self, _cx: *mut JSContext) -> JSVal {
+        self.cursor.value()
+    }
+}
@@ -0,0 +1,323 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::IDBDatabaseBinding;
+use crate::dom::bindings::codegen::Bindings::IDBDatabaseBinding::IDBDatabaseMethods;
+use crate::dom::bindings::codegen::Bindings::IDBDatabaseBinding::IDBObjectStoreParameters;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMethods;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMode;
+use crate::dom::bindings::codegen::UnionTypes::StringOrStringSequence;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::dom::idbversionchangeevent::IDBVersionChangeEvent;
+use crate::indexed_db::{backend_error_to_dom_error, is_valid_key_path, key_path_from_idl};
+use dom_struct::dom_struct;
+use net_traits::indexeddb_thread::{ConnectionId, DatabaseInfo, IndexedDBThreadMsg};
+use net_traits::indexeddb_thread::{IndexedDBTxnMode, KeyPath, ObjectStoreInfo, Operation};
+use net_traits::IpcSend;
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct IDBDatabase {
+    eventtarget: EventTarget,
+    connection: ConnectionId,
+    name: DOMString,
+    version: Cell<u64>,
+    /// The schema of the database, as seen by this connection.
+    info: DomRefCell<DatabaseInfo>,
+    /// The version and schema to go back to if the upgrade transaction aborts.
+    upgrade_snapshot: DomRefCell<Option<(u64, DatabaseInfo)>>,
+    close_pending: Cell<bool>,
+    next_transaction_serial: Cell<u64>,
+    upgrade_transaction: MutNullableDom<IDBTransaction>,
+}
+
+impl IDBDatabase {
+    fn new_inherited(connection: ConnectionId, info: DatabaseInfo) -> IDBDatabase {
+        IDBDatabase {
+            eventtarget: EventTarget::new_inherited(),
+            connection: connection,
+            name: DOMString::from(info.name.clone()),
+            version: Cell::new(info.version),
+            info: DomRefCell::new(info),
+            upgrade_snapshot: DomRefCell::new(None),
+            close_pending: Cell::new(false),
+            // Serial 0 is used by the upgrade transaction.
+            next_transaction_serial: Cell::new(1),
+            upgrade_transaction: Default::default(),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        connection: ConnectionId,
+        info: DatabaseInfo,
+    ) -> DomRoot<IDBDatabase> {
+        reflect_dom_object(
+            Box::new(IDBDatabase::new_inherited(connection, info)),
+            global,
+            IDBDatabaseBinding::Wrap,
+        )
+    }
+
+    pub fn connection(&self) -> ConnectionId {
+        self.connection
+    }
+
+    pub fn is_close_pending(&self) -> bool {
+        self.close_pending.get()
+    }
+
+    pub fn object_store_names(&self) -> Vec<DOMString> {
+        let mut names: Vec<DOMString> = self
+            .info
+            .borrow()
+            .object_stores
+            .iter()
+            .map(|store| DOMString::from(store.name.clone()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn object_store_info(&self, name: &str) -> Option<ObjectStoreInfo> {
+        self.info
+            .borrow()
+            .object_stores
+            .iter()
+            .find(|store| store.name == name)
+            .cloned()
+    }
+
+    /// Replace the schema of the object store called `name`, adding it if it
+    /// doesn't exist, or removing it if `info` is `None`.
+    pub fn set_object_store_info(&self, name: &str, info: Option<ObjectStoreInfo>) {
+        let stores = &mut self.info.borrow_mut().object_stores;
+        let position = stores.iter().position(|store| store.name == name);
+        match (position, info) {
+            (Some(position), Some(info)) => stores[position] = info,
+            (Some(position), None) => {
+                stores.remove(position);
+            },
+            (None, Some(info)) => stores.push(info),
+            (None, None) => {},
+        }
+    }
+
+    pub fn upgrade_transaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.upgrade_transaction.get()
+    }
+
+    pub fn start_upgrade(&self, transaction: &IDBTransaction, old_version: u64) {
+        *self.upgrade_snapshot.borrow_mut() = Some((old_version, self.info.borrow().clone()));
+        self.upgrade_transaction.set(Some(transaction));
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#abort-an-upgrade-transaction>
+    pub fn finish_upgrade(&self, aborted: bool) {
+        self.upgrade_transaction.set(None);
+        let snapshot = self.upgrade_snapshot.borrow_mut().take();
+        if aborted {
+            if let Some((version, info)) = snapshot {
+                self.version.set(version);
+                *self.info.borrow_mut() = info;
+            }
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#close-a-database-connection>
+    ///
+    /// The backend waits for the transactions of the connection to finish.
+    pub fn close_connection(&self) {
+        if self.close_pending.get() {
+            return;
+        }
+        self.close_pending.set(true);
+        self.global()
+            .resource_threads()
+            .send(IndexedDBThreadMsg::Close(self.connection))
+            .unwrap();
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#open-a-database>, step 6.2.
+    pub fn version_change(&self, old_version: u64, new_version: Option<u64>) {
+        if self.close_pending.get() {
+            return;
+        }
+        IDBVersionChangeEvent::fire(
+            self.upcast(),
+            atom!("versionchange"),
+            old_version,
+            new_version,
+        );
+    }
+
+    /// The upgrade transaction, if it is running and active, as required by
+    /// the methods that change the schema.
+    pub fn active_upgrade_transaction(&self) -> Fallible<DomRoot<IDBTransaction>> {
+        let transaction = self.upgrade_transaction.get().ok_or(Error::InvalidState)?;
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        Ok(transaction)
+    }
+}
+
+impl IDBDatabaseMethods for IDBDatabase {
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-name
+    fn Name(&self) -> DOMString {
+        self.name.clone()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-version
+    fn Version(&self) -> u64 {
+        self.version.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-objectstorenames
+    fn ObjectStoreNames(&self) -> DomRoot<DOMStringList> {
+        DOMStringList::new(&self.global(), self.object_store_names())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-transaction
+    fn Transaction(
+        &self,
+        store_names: StringOrStringSequence,
+        mode: IDBTransactionMode,
+    ) -> Fallible<DomRoot<IDBTransaction>> {
+        // Steps 1-2.
+        if self.upgrade_transaction.get().is_some() || self.close_pending.get() {
+            return Err(Error::InvalidState);
+        }
+        // Step 3.
+        let mut scope = match store_names {
+            StringOrStringSequence::String(name) => vec![name],
+            StringOrStringSequence::StringSequence(names) => names,
+        };
+        scope.sort();
+        scope.dedup();
+        // Step 4.
+        if scope
+            .iter()
+            .any(|name| self.object_store_info(name).is_none())
+        {
+            return Err(Error::NotFound);
+        }
+        // Step 5.
+        if scope.is_empty() {
+            return Err(Error::InvalidAccess);
+        }
+        // Step 6.
+        let mode = match mode {
+            IDBTransactionMode::Readonly => IndexedDBTxnMode::Readonly,
+            IDBTransactionMode::Readwrite => IndexedDBTxnMode::Readwrite,
+            IDBTransactionMode::Versionchange => {
+                return Err(Error::Type(
+                    "Transactions can't be created in versionchange mode".to_owned(),
+                ));
+            },
+        };
+        // Steps 7-9.
+        let serial = self.next_transaction_serial.get();
+        self.next_transaction_serial.set(serial + 1);
+        Ok(IDBTransaction::new(
+            &self.global(),
+            self,
+            serial,
+            mode,
+            scope,
+        ))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-close
+    fn Close(&self) {
+        self.close_connection();
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-createobjectstore
+    fn CreateObjectStore(
+        &self,
+        name: DOMString,
+        options: &IDBObjectStoreParameters,
+    ) -> Fallible<DomRoot<IDBObjectStore>> {
+        // Steps 1-4.
+        let transaction = self.active_upgrade_transaction()?;
+        // Steps 5-6.
+        let key_path = options.keyPath.as_ref().map(key_path_from_idl);
+        if let Some(ref key_path) = key_path {
+            if !is_valid_key_path(key_path) {
+                return Err(Error::Syntax);
+            }
+        }
+        // Step 7.
+        if self.object_store_info(&name).is_some() {
+            return Err(Error::Constraint);
+        }
+        // Step 8.
+        let auto_increment = options.autoIncrement;
+        match key_path {
+            Some(KeyPath::String(ref path)) if auto_increment && path.is_empty() => {
+                return Err(Error::InvalidAccess);
+            },
+            Some(KeyPath::Sequence(_)) if auto_increment => return Err(Error::InvalidAccess),
+            _ => {},
+        }
+
+        // Steps 9-12.
+        transaction
+            .execute(Operation::CreateObjectStore(
+                name.to_string(),
+                key_path.clone(),
+                auto_increment,
+            ))
+            .map_err(backend_error_to_dom_error)?;
+        let info = ObjectStoreInfo {
+            name: name.to_string(),
+            key_path: key_path,
+            auto_increment: auto_increment,
+            indexes: vec![],
+        };
+        self.set_object_store_info(&name, Some(info));
+        transaction.ObjectStore(name)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-deleteobjectstore
+    fn DeleteObjectStore(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-4.
+        let transaction = self.active_upgrade_transaction()?;
+        // Step 5.
+        if self.object_store_info(&name).is_none() {
+            return Err(Error::NotFound);
+        }
+        // Steps 6-7.
+        transaction
+            .execute(Operation::DeleteObjectStore(name.to_string()))
+            .map_err(backend_error_to_dom_error)?;
+        self.set_object_store_info(&name, None);
+        transaction.forget_object_store(&name);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onclose
+    event_handler!(close, GetOnclose, SetOnclose);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbdatabase-onversionchange
+    event_handler!(versionchange, GetOnversionchange, SetOnversionchange);
+}
@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBFactoryBinding;
+use crate::dom::bindings::codegen::Bindings::IDBFactoryBinding::IDBFactoryMethods;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbopendbrequest::IDBOpenDBRequest;
+use crate::indexed_db::convert_value_to_key;
+use dom_struct::dom_struct;
+use ipc_channel::ipc;
+use js::jsapi::JSContext;
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::IndexedDBThreadMsg;
+use net_traits::IpcSend;
+use servo_url::ImmutableOrigin;
+use std::cmp::Ordering;
+
+#[dom_struct]
+pub struct IDBFactory {
+    reflector_: Reflector,
+}
+
+impl IDBFactory {
+    fn new_inherited() -> IDBFactory {
+        IDBFactory {
+            reflector_: Reflector::new(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<IDBFactory> {
+        reflect_dom_object(
+            Box::new(IDBFactory::new_inherited()),
+            global,
+            IDBFactoryBinding::Wrap,
+        )
+    }
+
+    /// The origin databases are stored for, which must be a tuple origin.
+    fn origin(&self) -> Fallible<ImmutableOrigin> {
+        let origin = self.global().origin().immutable().clone();
+        if !origin.is_tuple() {
+            return Err(Error::Security);
+        }
+        Ok(origin)
+    }
+}
+
+impl IDBFactoryMethods for IDBFactory {
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-open
+    fn Open(&self, name: DOMString, version: Option<u64>) -> Fallible<DomRoot<IDBOpenDBRequest>> {
+        // Step 1.
+        if version == Some(0) {
+            return Err(Error::Type(
+                "The version must be greater than zero".to_owned(),
+            ));
+        }
+        // Steps 2-3.
+        let origin = self.origin()?;
+
+        // Steps 4-5.
+        let request = IDBOpenDBRequest::new(&self.global());
+        let (sender, receiver) = ipc::channel().unwrap();
+        request.route_connection_events(receiver);
+        self.global()
+            .resource_threads()
+            .send(IndexedDBThreadMsg::Open(
+                sender,
+                origin,
+                name.into(),
+                version,
+            ))
+            .unwrap();
+        Ok(request)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-deletedatabase
+    fn DeleteDatabase(&self, name: DOMString) -> Fallible<DomRoot<IDBOpenDBRequest>> {
+        // Steps 1-2.
+        let origin = self.origin()?;
+
+        // Steps 3-4.
+        let request = IDBOpenDBRequest::new(&self.global());
+        let (sender, receiver) = ipc::channel().unwrap();
+        request.route_delete_events(receiver);
+        self.global()
+            .resource_threads()
+            .send(IndexedDBThreadMsg::DeleteDatabase(
+                sender,
+                origin,
+                name.into(),
+            ))
+            .unwrap();
+        Ok(request)
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbfactory-cmp
+    unsafe fn Cmp(
+        &self,
+        cx: *mut JSContext,
+        first: HandleValue,
+        second: HandleValue,
+    ) -> Fallible<i16> {
+        let first = convert_value_to_key(cx, first, &mut vec![])?;
+        let second = convert_value_to_key(cx, second, &mut vec![])?;
+        Ok(match first.cmp(&second) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        })
+    }
+}
@@ -0,0 +1,259 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::Bindings::IDBIndexBinding;
+use crate::dom::bindings::codegen::Bindings::IDBIndexBinding::IDBIndexMethods;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbrequest::{IDBRequest, RequestSource};
+use crate::indexed_db::key_path_to_jsval;
+use crate::indexed_db::{backend_error_to_dom_error, convert_value_to_key_range};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::{IndexInfo, IndexedDBTxnMode, Operation};
+use std::cell::Cell;
+
+#[dom_struct]
+pub struct IDBIndex {
+    reflector_: Reflector,
+    object_store: Dom<IDBObjectStore>,
+    /// The schema of the index, as seen by the transaction.
+    info: DomRefCell<IndexInfo>,
+    deleted: Cell<bool>,
+}
+
+impl IDBIndex {
+    fn new_inherited(object_store: &IDBObjectStore, info: IndexInfo) -> IDBIndex {
+        IDBIndex {
+            reflector_: Reflector::new(),
+            object_store: Dom::from_ref(object_store),
+            info: DomRefCell::new(info),
+            deleted: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        object_store: &IDBObjectStore,
+        info: IndexInfo,
+    ) -> DomRoot<IDBIndex> {
+        reflect_dom_object(
+            Box::new(IDBIndex::new_inherited(object_store, info)),
+            global,
+            IDBIndexBinding::Wrap,
+        )
+    }
+
+    pub fn name(&self) -> String {
+        self.info.borrow().name.clone()
+    }
+
+    pub fn object_store(&self) -> DomRoot<IDBObjectStore> {
+        DomRoot::from_ref(&*self.object_store)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.get() || self.object_store.is_deleted()
+    }
+
+    pub fn mark_deleted(&self) {
+        self.deleted.set(true);
+    }
+
+    fn check_readable(&self) -> ErrorResult {
+        if self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        self.object_store.check_readable()
+    }
+
+    fn make_request(&self, operation: Operation) -> DomRoot<IDBRequest> {
+        self.object_store
+            .make_request(RequestSource::Index(self), operation)
+    }
+}
+
+impl IDBIndexMethods for IDBIndex {
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-name
+    fn Name(&self) -> DOMString {
+        DOMString::from(self.name())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-name
+    fn SetName(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-7.
+        let transaction = self.object_store.transaction();
+        if transaction.mode() != IndexedDBTxnMode::Versionchange || self.is_deleted() {
+            return Err(Error::InvalidState);
+        }
+        if !transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        // Step 8.
+        let old_name = self.Name();
+        if name == old_name {
+            return Ok(());
+        }
+        // Step 9.
+        if self.object_store.index_info(&name).is_some() {
+            return Err(Error::Constraint);
+        }
+        // Steps 10-11.
+        transaction
+            .execute(Operation::RenameIndex(
+                self.object_store.name(),
+                old_name.to_string(),
+                name.to_string(),
+            ))
+            .map_err(backend_error_to_dom_error)?;
+        self.info.borrow_mut().name = name.to_string();
+        self.object_store
+            .set_index_info(&old_name, Some(self.info.borrow().clone()));
+        self.object_store.rename_index(&old_name, name);
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-objectstore
+    fn ObjectStore(&self) -> DomRoot<IDBObjectStore> {
+        self.object_store()
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-keypath
+    unsafe fn KeyPath(&self, cx: *mut JSContext) -> JSVal {
+        rooted!(in(cx) let mut value = UndefinedValue());
+        key_path_to_jsval(cx, &self.info.borrow().key_path, value.handle_mut());
+        value.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-multientry
+    fn MultiEntry(&self) -> bool {
+        self.info.borrow().multi_entry
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-unique
+    fn Unique(&self) -> bool {
+        self.info.borrow().unique
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-get
+    unsafe fn Get(&self, cx: *mut JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, true)?;
+        Ok(self.make_request(Operation::Get(
+            self.object_store.name(),
+            Some(self.name()),
+            range,
+        )))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getkey
+    unsafe fn GetKey(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, true)?;
+        Ok(self.make_request(Operation::GetKey(
+            self.object_store.name(),
+            Some(self.name()),
+            range,
+        )))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getall
+    unsafe fn GetAll(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        let count = count.filter(|count| *count != 0);
+        Ok(self.make_request(Operation::GetAll(
+            self.object_store.name(),
+            Some(self.name()),
+            range,
+            count,
+        )))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-getallkeys
+    unsafe fn GetAllKeys(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        let count = count.filter(|count| *count != 0);
+        Ok(self.make_request(Operation::GetAllKeys(
+            self.object_store.name(),
+            Some(self.name()),
+            range,
+            count,
+        )))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-count
+    unsafe fn Count(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        Ok(self.make_request(Operation::Count(
+            self.object_store.name(),
+            Some(self.name()),
+            range,
+        )))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-opencursor
+    unsafe fn OpenCursor(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        Ok(self
+            .object_store
+            .open_cursor(Some(self), range, direction, false))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbindex-openkeycursor
+    unsafe fn OpenKeyCursor(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        Ok(self
+            .object_store
+            .open_cursor(Some(self), range, direction, true))
+    }
+}
@@ -0,0 +1,157 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::IDBKeyRangeBinding;
+use crate::dom::bindings::codegen::Bindings::IDBKeyRangeBinding::IDBKeyRangeMethods;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::globalscope::GlobalScope;
+use crate::indexed_db::{convert_value_to_key, key_to_jsval};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::{IndexedDBKey, IndexedDBKeyRange};
+
+#[dom_struct]
+pub struct IDBKeyRange {
+    reflector_: Reflector,
+    inner: IndexedDBKeyRange,
+}
+
+impl IDBKeyRange {
+    fn new_inherited(inner: IndexedDBKeyRange) -> IDBKeyRange {
+        IDBKeyRange {
+            reflector_: Reflector::new(),
+            inner: inner,
+        }
+    }
+
+    pub fn new(global: &GlobalScope, inner: IndexedDBKeyRange) -> DomRoot<IDBKeyRange> {
+        reflect_dom_object(
+            Box::new(IDBKeyRange::new_inherited(inner)),
+            global,
+            IDBKeyRangeBinding::Wrap,
+        )
+    }
+
+    pub fn inner(&self) -> &IndexedDBKeyRange {
+        &self.inner
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-only
+    #[allow(unsafe_code)]
+    pub unsafe fn Only(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        value: HandleValue,
+    ) -> Fallible<DomRoot<IDBKeyRange>> {
+        let key = convert_value_to_key(cx, value, &mut vec![])?;
+        Ok(IDBKeyRange::new(global, IndexedDBKeyRange::only(key)))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-lowerbound
+    #[allow(unsafe_code)]
+    pub unsafe fn LowerBound(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        lower: HandleValue,
+        open: bool,
+    ) -> Fallible<DomRoot<IDBKeyRange>> {
+        let lower = convert_value_to_key(cx, lower, &mut vec![])?;
+        let range = IndexedDBKeyRange {
+            lower: Some(lower),
+            upper: None,
+            lower_open: open,
+            upper_open: true,
+        };
+        Ok(IDBKeyRange::new(global, range))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-upperbound
+    #[allow(unsafe_code)]
+    pub unsafe fn UpperBound(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        upper: HandleValue,
+        open: bool,
+    ) -> Fallible<DomRoot<IDBKeyRange>> {
+        let upper = convert_value_to_key(cx, upper, &mut vec![])?;
+        let range = IndexedDBKeyRange {
+            lower: None,
+            upper: Some(upper),
+            lower_open: true,
+            upper_open: open,
+        };
+        Ok(IDBKeyRange::new(global, range))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-bound
+    #[allow(unsafe_code)]
+    pub unsafe fn Bound(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        lower: HandleValue,
+        upper: HandleValue,
+        lower_open: bool,
+        upper_open: bool,
+    ) -> Fallible<DomRoot<IDBKeyRange>> {
+        // Steps 1-4.
+        let lower = convert_value_to_key(cx, lower, &mut vec![])?;
+        let upper = convert_value_to_key(cx, upper, &mut vec![])?;
+        // Step 5.
+        if lower > upper || (lower == upper && (lower_open || upper_open)) {
+            return Err(Error::Data);
+        }
+        // Steps 6-7.
+        let range = IndexedDBKeyRange {
+            lower: Some(lower),
+            upper: Some(upper),
+            lower_open: lower_open,
+            upper_open: upper_open,
+        };
+        Ok(IDBKeyRange::new(global, range))
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe fn bound_to_jsval(cx: *mut JSContext, bound: &Option<IndexedDBKey>) -> JSVal {
+    rooted!(in(cx) let mut value = UndefinedValue());
+    if let Some(ref key) = *bound {
+        key_to_jsval(cx, key, value.handle_mut());
+    }
+    value.get()
+}
+
+impl IDBKeyRangeMethods for IDBKeyRange {
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-lower
+    unsafe fn Lower(&self, cx: *mut JSContext) -> JSVal {
+        bound_to_jsval(cx, &self.inner.lower)
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-upper
+    unsafe fn Upper(&self, cx: *mut JSContext) -> JSVal {
+        bound_to_jsval(cx, &self.inner.upper)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-loweropen
+    fn LowerOpen(&self) -> bool {
+        self.inner.lower_open
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-upperopen
+    fn UpperOpen(&self) -> bool {
+        self.inner.upper_open
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbkeyrange-includes
+    unsafe fn Includes(&self, cx: *mut JSContext, key: HandleValue) -> Fallible<bool> {
+        let key = convert_value_to_key(cx, key, &mut vec![])?;
+        Ok(self.inner.contains(&key))
+    }
+}
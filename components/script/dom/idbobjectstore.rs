@@ -0,0 +1,595 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::Bindings::IDBObjectStoreBinding;
+use crate::dom::bindings::codegen::Bindings::IDBObjectStoreBinding::IDBIndexParameters;
+use crate::dom::bindings::codegen::Bindings::IDBObjectStoreBinding::IDBObjectStoreMethods;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMethods;
+use crate::dom::bindings::codegen::UnionTypes::StringOrStringSequence;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone::StructuredCloneData;
+use crate::dom::domexception::DOMErrorName;
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbcursorwithvalue::IDBCursorWithValue;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbrequest::{IDBRequest, RequestSource};
+use crate::dom::idbtransaction::{IDBTransaction, KeyInjection};
+use crate::indexed_db::key_path_to_jsval;
+use crate::indexed_db::{backend_error_to_dom_error, can_inject_key, convert_value_to_key};
+use crate::indexed_db::{convert_value_to_key_range, cursor_direction_from_idl, extract_key};
+use crate::indexed_db::{index_keys_for_value, is_valid_key_path, key_path_from_idl};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::{JSVal, NullValue, UndefinedValue};
+use js::rust::HandleValue;
+use net_traits::indexeddb_thread::OperationResult;
+use net_traits::indexeddb_thread::{BackendError, CursorPosition, IndexInfo, IndexedDBKeyRange};
+use net_traits::indexeddb_thread::{IndexedDBTxnMode, KeyPath, ObjectStoreInfo, Operation};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::slice;
+
+#[dom_struct]
+pub struct IDBObjectStore {
+    reflector_: Reflector,
+    transaction: Dom<IDBTransaction>,
+    /// The schema of the object store, as seen by the transaction.
+    info: DomRefCell<ObjectStoreInfo>,
+    indexes: DomRefCell<HashMap<DOMString, Dom<IDBIndex>>>,
+    deleted: Cell<bool>,
+}
+
+impl IDBObjectStore {
+    fn new_inherited(transaction: &IDBTransaction, info: ObjectStoreInfo) -> IDBObjectStore {
+        IDBObjectStore {
+            reflector_: Reflector::new(),
+            transaction: Dom::from_ref(transaction),
+            info: DomRefCell::new(info),
+            indexes: DomRefCell::new(HashMap::new()),
+            deleted: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        transaction: &IDBTransaction,
+        info: ObjectStoreInfo,
+    ) -> DomRoot<IDBObjectStore> {
+        reflect_dom_object(
+            Box::new(IDBObjectStore::new_inherited(transaction, info)),
+            global,
+            IDBObjectStoreBinding::Wrap,
+        )
+    }
+
+    pub fn name(&self) -> String {
+        self.info.borrow().name.clone()
+    }
+
+    pub fn info(&self) -> ObjectStoreInfo {
+        self.info.borrow().clone()
+    }
+
+    pub fn transaction(&self) -> DomRoot<IDBTransaction> {
+        DomRoot::from_ref(&*self.transaction)
+    }
+
+    pub fn index_info(&self, name: &str) -> Option<IndexInfo> {
+        self.info
+            .borrow()
+            .indexes
+            .iter()
+            .find(|index| index.name == name)
+            .cloned()
+    }
+
+    /// Replace the schema of the index called `name`, removing it if `info`
+    /// is `None`, and tell the database about the change.
+    pub fn set_index_info(&self, name: &str, info: Option<IndexInfo>) {
+        {
+            let indexes = &mut self.info.borrow_mut().indexes;
+            let position = indexes.iter().position(|index| index.name == name);
+            match (position, info) {
+                (Some(position), Some(info)) => indexes[position] = info,
+                (Some(position), None) => {
+                    indexes.remove(position);
+                },
+                (None, Some(info)) => indexes.push(info),
+                (None, None) => {},
+            }
+        }
+        let info = self.info.borrow().clone();
+        let name = info.name.clone();
+        self.transaction
+            .Db()
+            .set_object_store_info(&name, Some(info));
+    }
+
+    /// Track the renaming of one of the indexes of the object store.
+    pub fn rename_index(&self, old_name: &DOMString, new_name: DOMString) {
+        let mut indexes = self.indexes.borrow_mut();
+        if let Some(index) = indexes.remove(old_name) {
+            indexes.insert(new_name, index);
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.get()
+    }
+
+    pub fn mark_deleted(&self) {
+        self.deleted.set(true);
+        for index in self.indexes.borrow().values() {
+            index.mark_deleted();
+        }
+    }
+
+    /// Check that requests can be made against the object store, or one of
+    /// its indexes.
+    pub fn check_readable(&self) -> ErrorResult {
+        if self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        Ok(())
+    }
+
+    fn check_writable(&self) -> ErrorResult {
+        self.check_readable()?;
+        if self.transaction.mode() == IndexedDBTxnMode::Readonly {
+            return Err(Error::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Check that the schema of the object store can be changed, which is only
+    /// possible in an active upgrade transaction.
+    fn check_upgrading(&self) -> ErrorResult {
+        if self.transaction.mode() != IndexedDBTxnMode::Versionchange || self.deleted.get() {
+            return Err(Error::InvalidState);
+        }
+        if !self.transaction.is_active() {
+            return Err(Error::TransactionInactive);
+        }
+        Ok(())
+    }
+
+    /// Queue a request made against the object store or one of its indexes.
+    pub fn make_request(&self, source: RequestSource, operation: Operation) -> DomRoot<IDBRequest> {
+        let request = IDBRequest::new(&self.global(), source, &self.transaction);
+        self.transaction
+            .queue_request(&request, operation, None, None);
+        request
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#dom-idbobjectstore-opencursor>, and
+    /// the other methods that open cursors, from step 7.
+    pub fn open_cursor(
+        &self,
+        index: Option<&IDBIndex>,
+        range: IndexedDBKeyRange,
+        direction: IDBCursorDirection,
+        key_only: bool,
+    ) -> DomRoot<IDBRequest> {
+        let global = self.global();
+        let source = match index {
+            Some(index) => RequestSource::Index(index),
+            None => RequestSource::ObjectStore(self),
+        };
+        let request = IDBRequest::new(&global, source, &self.transaction);
+        let direction = cursor_direction_from_idl(direction);
+        let cursor = if key_only {
+            IDBCursor::new(&global, self, index, direction, range.clone(), &request)
+        } else {
+            DomRoot::upcast(IDBCursorWithValue::new(
+                &global,
+                self,
+                index,
+                direction,
+                range.clone(),
+                &request,
+            ))
+        };
+        let operation = Operation::Iterate {
+            store: self.name(),
+            index: index.map(|index| index.name()),
+            range: range,
+            direction: direction,
+            position: CursorPosition {
+                current: None,
+                target_key: None,
+                target_primary_key: None,
+                count: 1,
+            },
+            key_only: key_only,
+        };
+        self.transaction
+            .queue_request(&request, operation, Some(&cursor), None);
+        request
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#add-or-put>
+    #[allow(unsafe_code)]
+    unsafe fn put(
+        &self,
+        cx: *mut JSContext,
+        value: HandleValue,
+        key: HandleValue,
+        no_overwrite: bool,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        // Steps 1-5.
+        self.check_writable()?;
+        let info = self.info.borrow().clone();
+        // Steps 6-8.
+        let key = if key.is_undefined() {
+            if info.key_path.is_none() && !info.auto_increment {
+                return Err(Error::Data);
+            }
+            None
+        } else {
+            if info.key_path.is_some() {
+                return Err(Error::Data);
+            }
+            Some(convert_value_to_key(cx, key, &mut vec![])?)
+        };
+        // Steps 9-10.
+        let clone = StructuredCloneData::write(cx, value)?.move_to_arraybuffer();
+
+        // Step 11. The key path is evaluated on the original value, which
+        // gives the same result as evaluating it on the clone.
+        let mut injection = None;
+        let key = match info.key_path {
+            Some(ref key_path) => match extract_key(cx, value, key_path, false)? {
+                Some(key) => Some(key),
+                None => match *key_path {
+                    KeyPath::String(ref path)
+                        if info.auto_increment && can_inject_key(cx, value, path) =>
+                    {
+                        injection = Some(KeyInjection {
+                            key_path: path.clone(),
+                            indexes: info.indexes.clone(),
+                        });
+                        None
+                    },
+                    _ => return Err(Error::Data),
+                },
+            },
+            None => key,
+        };
+        let index_keys = index_keys_for_value(cx, value, &info.indexes)?;
+
+        // Steps 12-13.
+        let operation = Operation::Put {
+            store: info.name.clone(),
+            key: key,
+            value: clone,
+            index_keys: index_keys,
+            no_overwrite: no_overwrite,
+        };
+        let source = RequestSource::ObjectStore(self);
+        let request = IDBRequest::new(&self.global(), source, &self.transaction);
+        self.transaction
+            .queue_request(&request, operation, None, injection);
+        Ok(request)
+    }
+}
+
+impl IDBObjectStoreMethods for IDBObjectStore {
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-name
+    fn Name(&self) -> DOMString {
+        DOMString::from(self.name())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-name
+    fn SetName(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-6.
+        self.check_upgrading()?;
+        // Step 7.
+        let old_name = self.Name();
+        if name == old_name {
+            return Ok(());
+        }
+        // Step 8.
+        let db = self.transaction.Db();
+        if db.object_store_info(&name).is_some() {
+            return Err(Error::Constraint);
+        }
+        // Steps 9-10.
+        self.transaction
+            .execute(Operation::RenameObjectStore(
+                old_name.to_string(),
+                name.to_string(),
+            ))
+            .map_err(backend_error_to_dom_error)?;
+        self.info.borrow_mut().name = name.to_string();
+        db.set_object_store_info(&old_name, Some(self.info.borrow().clone()));
+        self.transaction.rename_object_store(&old_name, name);
+        Ok(())
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-keypath
+    unsafe fn KeyPath(&self, cx: *mut JSContext) -> JSVal {
+        rooted!(in(cx) let mut value = NullValue());
+        if let Some(ref key_path) = self.info.borrow().key_path {
+            key_path_to_jsval(cx, key_path, value.handle_mut());
+        }
+        value.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-indexnames
+    fn IndexNames(&self) -> DomRoot<DOMStringList> {
+        let mut names: Vec<DOMString> = self
+            .info
+            .borrow()
+            .indexes
+            .iter()
+            .map(|index| DOMString::from(index.name.clone()))
+            .collect();
+        names.sort();
+        DOMStringList::new(&self.global(), names)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-transaction
+    fn Transaction(&self) -> DomRoot<IDBTransaction> {
+        self.transaction()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-autoincrement
+    fn AutoIncrement(&self) -> bool {
+        self.info.borrow().auto_increment
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-put
+    unsafe fn Put(
+        &self,
+        cx: *mut JSContext,
+        value: HandleValue,
+        key: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.put(cx, value, key, false)
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-add
+    unsafe fn Add(
+        &self,
+        cx: *mut JSContext,
+        value: HandleValue,
+        key: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.put(cx, value, key, true)
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-delete
+    unsafe fn Delete(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_writable()?;
+        let range = convert_value_to_key_range(cx, query, true)?;
+        let source = RequestSource::ObjectStore(self);
+        Ok(self.make_request(source, Operation::Delete(self.name(), range)))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-clear
+    fn Clear(&self) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_writable()?;
+        let source = RequestSource::ObjectStore(self);
+        Ok(self.make_request(source, Operation::Clear(self.name())))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-get
+    unsafe fn Get(&self, cx: *mut JSContext, query: HandleValue) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, true)?;
+        let source = RequestSource::ObjectStore(self);
+        Ok(self.make_request(source, Operation::Get(self.name(), None, range)))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getkey
+    unsafe fn GetKey(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, true)?;
+        let source = RequestSource::ObjectStore(self);
+        Ok(self.make_request(source, Operation::GetKey(self.name(), None, range)))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getall
+    unsafe fn GetAll(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        let source = RequestSource::ObjectStore(self);
+        let count = count.filter(|count| *count != 0);
+        Ok(self.make_request(source, Operation::GetAll(self.name(), None, range, count)))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-getallkeys
+    unsafe fn GetAllKeys(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        count: Option<u32>,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        let source = RequestSource::ObjectStore(self);
+        let count = count.filter(|count| *count != 0);
+        Ok(self.make_request(
+            source,
+            Operation::GetAllKeys(self.name(), None, range, count),
+        ))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-count
+    unsafe fn Count(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        let source = RequestSource::ObjectStore(self);
+        Ok(self.make_request(source, Operation::Count(self.name(), None, range)))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-opencursor
+    unsafe fn OpenCursor(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        Ok(self.open_cursor(None, range, direction, false))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-openkeycursor
+    unsafe fn OpenKeyCursor(
+        &self,
+        cx: *mut JSContext,
+        query: HandleValue,
+        direction: IDBCursorDirection,
+    ) -> Fallible<DomRoot<IDBRequest>> {
+        self.check_readable()?;
+        let range = convert_value_to_key_range(cx, query, false)?;
+        Ok(self.open_cursor(None, range, direction, true))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-index
+    fn Index(&self, name: DOMString) -> Fallible<DomRoot<IDBIndex>> {
+        // Steps 1-3.
+        if self.deleted.get() || self.transaction.is_finished() {
+            return Err(Error::InvalidState);
+        }
+        // Step 4.
+        let info = self.index_info(&name).ok_or(Error::NotFound)?;
+        // Step 5.
+        let mut indexes = self.indexes.borrow_mut();
+        let index = indexes
+            .entry(name)
+            .or_insert_with(|| Dom::from_ref(&*IDBIndex::new(&self.global(), self, info)));
+        Ok(DomRoot::from_ref(&**index))
+    }
+
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-createindex
+    unsafe fn CreateIndex(
+        &self,
+        cx: *mut JSContext,
+        name: DOMString,
+        key_path: StringOrStringSequence,
+        options: &IDBIndexParameters,
+    ) -> Fallible<DomRoot<IDBIndex>> {
+        // Steps 1-6.
+        self.check_upgrading()?;
+        // Step 7.
+        if self.index_info(&name).is_some() {
+            return Err(Error::Constraint);
+        }
+        // Step 8.
+        let key_path = key_path_from_idl(&key_path);
+        if !is_valid_key_path(&key_path) {
+            return Err(Error::Syntax);
+        }
+        // Steps 9-10.
+        if let KeyPath::Sequence(_) = key_path {
+            if options.multiEntry {
+                return Err(Error::InvalidAccess);
+            }
+        }
+        let info = IndexInfo {
+            name: name.to_string(),
+            key_path: key_path,
+            unique: options.unique,
+            multi_entry: options.multiEntry,
+        };
+
+        // Step 11. The index is populated with the records already in the
+        // object store, whose keys are computed here.
+        let records = match self
+            .transaction
+            .execute(Operation::GetAllRecords(self.name()))
+        {
+            Ok(OperationResult::Records(records)) => records,
+            Ok(_) => return Err(Error::Unknown),
+            Err(error) => return Err(backend_error_to_dom_error(error)),
+        };
+        let global = self.global();
+        let mut entries = vec![];
+        for (primary_key, value) in records {
+            rooted!(in(cx) let mut clone = UndefinedValue());
+            StructuredCloneData::Vector(value).read(&global, clone.handle_mut());
+            let mut keys = index_keys_for_value(cx, clone.handle(), slice::from_ref(&info))?;
+            if let Some((_, keys)) = keys.pop() {
+                entries.push((primary_key, keys));
+            }
+        }
+        let operation = Operation::CreateIndex {
+            store: self.name(),
+            info: info.clone(),
+            entries: entries,
+        };
+        match self.transaction.execute(operation) {
+            Ok(_) => {},
+            // A unique index that the existing records violate aborts the
+            // transaction, as in step 4 of
+            // <https://w3c.github.io/IndexedDB/#dom-idbobjectstore-createindex>.
+            Err(BackendError::Constraint) => {
+                self.transaction.abort(Some(DOMErrorName::ConstraintError));
+            },
+            Err(error) => return Err(backend_error_to_dom_error(error)),
+        }
+        self.set_index_info(&name, Some(info));
+        self.Index(name)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbobjectstore-deleteindex
+    fn DeleteIndex(&self, name: DOMString) -> ErrorResult {
+        // Steps 1-6.
+        self.check_upgrading()?;
+        // Step 7.
+        if self.index_info(&name).is_none() {
+            return Err(Error::NotFound);
+        }
+        // Steps 8-10.
+        self.transaction
+            .execute(Operation::DeleteIndex(self.name(), name.to_string()))
+            .map_err(backend_error_to_dom_error)?;
+        self.set_index_info(&name, None);
+        if let Some(index) = self.indexes.borrow_mut().remove(&name) {
+            index.mark_deleted();
+        }
+        Ok(())
+    }
+}
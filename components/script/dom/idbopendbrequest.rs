@@ -0,0 +1,195 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::enter_realm;
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::IDBOpenDBRequestBinding;
+use crate::dom::bindings::codegen::Bindings::IDBOpenDBRequestBinding::IDBOpenDBRequestMethods;
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::domexception::DOMErrorName;
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbdatabase::IDBDatabase;
+use crate::dom::idbrequest::IDBRequest;
+use crate::dom::idbtransaction::IDBTransaction;
+use crate::dom::idbversionchangeevent::IDBVersionChangeEvent;
+use crate::indexed_db::backend_error_name;
+use crate::task_source::{TaskSource, TaskSourceName};
+use dom_struct::dom_struct;
+use ipc_channel::ipc::IpcReceiver;
+use ipc_channel::router::ROUTER;
+use js::jsval::UndefinedValue;
+use net_traits::indexeddb_thread::{ConnectionEvent, DeleteEvent};
+use serde::{Deserialize, Serialize};
+
+#[dom_struct]
+pub struct IDBOpenDBRequest {
+    request: IDBRequest,
+    /// The connection opened by this request, which is told about later
+    /// version changes.
+    database: MutNullableDom<IDBDatabase>,
+}
+
+impl IDBOpenDBRequest {
+    fn new_inherited() -> IDBOpenDBRequest {
+        IDBOpenDBRequest {
+            request: IDBRequest::new_inherited(),
+            database: Default::default(),
+        }
+    }
+
+    pub fn new(global: &GlobalScope) -> DomRoot<IDBOpenDBRequest> {
+        reflect_dom_object(
+            Box::new(IDBOpenDBRequest::new_inherited()),
+            global,
+            IDBOpenDBRequestBinding::Wrap,
+        )
+    }
+
+    /// Handle the messages the backend sends on `receiver` in tasks.
+    fn route<T>(&self, receiver: IpcReceiver<T>, handler: fn(&IDBOpenDBRequest, T))
+    where
+        T: for<'de> Deserialize<'de> + Serialize + Send + 'static,
+    {
+        let global = self.global();
+        let task_source = global.dom_manipulation_task_source();
+        let canceller = global.task_canceller(TaskSourceName::DOMManipulation);
+        let this = Trusted::new(self);
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |message| {
+                let this = this.clone();
+                let event: T = message.to().unwrap();
+                let _ = task_source.queue_with_canceller(
+                    task!(handle_indexeddb_event: move || {
+                        handler(&this.root(), event);
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+    }
+
+    pub fn route_connection_events(&self, receiver: IpcReceiver<ConnectionEvent>) {
+        self.route(receiver, IDBOpenDBRequest::handle_connection_event);
+    }
+
+    pub fn route_delete_events(&self, receiver: IpcReceiver<DeleteEvent>) {
+        self.route(receiver, IDBOpenDBRequest::handle_delete_event);
+    }
+
+    /// Set the result of the request to a new connection.
+    #[allow(unsafe_code)]
+    fn set_connection(&self, database: &IDBDatabase) {
+        self.database.set(Some(database));
+        let cx = self.global().get_cx();
+        let _ac = enter_realm(self);
+        rooted!(in(cx) let mut result = UndefinedValue());
+        unsafe { database.to_jsval(cx, result.handle_mut()) };
+        self.request.set_result(result.handle());
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#open-a-database>
+    fn handle_connection_event(&self, event: ConnectionEvent) {
+        let global = self.global();
+        match event {
+            // Step 6.3.
+            ConnectionEvent::Blocked {
+                old_version,
+                new_version,
+            } => {
+                IDBVersionChangeEvent::fire(
+                    self.upcast(),
+                    atom!("blocked"),
+                    old_version,
+                    Some(new_version),
+                );
+            },
+            // <https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction>
+            ConnectionEvent::UpgradeNeeded {
+                connection,
+                old_version,
+                info,
+            } => {
+                let new_version = info.version;
+                let database = IDBDatabase::new(&global, connection, info);
+                let transaction = IDBTransaction::new_upgrade(&global, &database, self);
+                database.start_upgrade(&transaction, old_version);
+                self.set_connection(&database);
+                self.request.set_transaction(Some(&transaction));
+
+                transaction.set_active(true);
+                IDBVersionChangeEvent::fire(
+                    self.upcast(),
+                    atom!("upgradeneeded"),
+                    old_version,
+                    Some(new_version),
+                );
+                transaction.set_active(false);
+                transaction.maybe_commit();
+            },
+            ConnectionEvent::Opened { connection, info } => {
+                let database = IDBDatabase::new(&global, connection, info);
+                self.set_connection(&database);
+                self.request.fire_success();
+            },
+            ConnectionEvent::Error(error) => {
+                self.request.set_error(backend_error_name(error));
+                self.request.fire_error();
+            },
+            ConnectionEvent::VersionChange {
+                old_version,
+                new_version,
+            } => {
+                if let Some(database) = self.database.get() {
+                    database.version_change(old_version, new_version);
+                }
+            },
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction>, steps 11-12.
+    pub fn upgrade_finished(&self, aborted: bool) {
+        self.request.set_transaction(None);
+        let closed = self
+            .database
+            .get()
+            .map_or(true, |database| database.is_close_pending());
+        if aborted || closed {
+            if let Some(database) = self.database.get() {
+                database.close_connection();
+            }
+            self.request.set_error(DOMErrorName::AbortError);
+            self.request.fire_error();
+        } else {
+            self.request.fire_success();
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#delete-a-database>
+    fn handle_delete_event(&self, event: DeleteEvent) {
+        match event {
+            DeleteEvent::Blocked { old_version } => {
+                IDBVersionChangeEvent::fire(self.upcast(), atom!("blocked"), old_version, None);
+            },
+            DeleteEvent::Deleted { old_version } => {
+                rooted!(in(self.global().get_cx()) let result = UndefinedValue());
+                self.request.set_result(result.handle());
+                IDBVersionChangeEvent::fire(self.upcast(), atom!("success"), old_version, None);
+            },
+        }
+    }
+}
+
+impl IDBOpenDBRequestMethods for IDBOpenDBRequest {
+    // https://w3c.github.io/IndexedDB/#dom-idbopendbrequest-onblocked
+    event_handler!(blocked, GetOnblocked, SetOnblocked);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbopendbrequest-onupgradeneeded
+    event_handler!(upgradeneeded, GetOnupgradeneeded, SetOnupgradeneeded);
+}
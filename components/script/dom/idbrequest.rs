@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::IDBRequestBinding;
+use crate::dom::bindings::codegen::Bindings::IDBRequestBinding::IDBRequestMethods;
+use crate::dom::bindings::codegen::Bindings::IDBRequestBinding::IDBRequestReadyState;
+use crate::dom::bindings::codegen::UnionTypes::IDBObjectStoreOrIDBIndexOrIDBCursor;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::domexception::{DOMErrorName, DOMException};
+use crate::dom::event::{Event, EventBubbles, EventCancelable, EventStatus};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbindex::IDBIndex;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbtransaction::IDBTransaction;
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSContext};
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use std::cell::Cell;
+
+/// What a request was made against.
+#[derive(Clone, Copy)]
+pub enum RequestSource<'a> {
+    ObjectStore(&'a IDBObjectStore),
+    Index(&'a IDBIndex),
+    Cursor(&'a IDBCursor),
+}
+
+#[must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+enum Source {
+    ObjectStore(Dom<IDBObjectStore>),
+    Index(Dom<IDBIndex>),
+    Cursor(Dom<IDBCursor>),
+}
+
+#[dom_struct]
+pub struct IDBRequest {
+    eventtarget: EventTarget,
+    #[ignore_malloc_size_of = "Defined in rust-mozjs"]
+    result: Heap<JSVal>,
+    error: MutNullableDom<DOMException>,
+    source: DomRefCell<Option<Source>>,
+    transaction: MutNullableDom<IDBTransaction>,
+    done: Cell<bool>,
+}
+
+impl IDBRequest {
+    pub fn new_inherited() -> IDBRequest {
+        IDBRequest {
+            eventtarget: EventTarget::new_inherited(),
+            result: Heap::default(),
+            error: Default::default(),
+            source: DomRefCell::new(None),
+            transaction: Default::default(),
+            done: Cell::new(false),
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        source: RequestSource,
+        transaction: &IDBTransaction,
+    ) -> DomRoot<IDBRequest> {
+        let request = reflect_dom_object(
+            Box::new(IDBRequest::new_inherited()),
+            global,
+            IDBRequestBinding::Wrap,
+        );
+        *request.source.borrow_mut() = Some(match source {
+            RequestSource::ObjectStore(store) => Source::ObjectStore(Dom::from_ref(store)),
+            RequestSource::Index(index) => Source::Index(Dom::from_ref(index)),
+            RequestSource::Cursor(cursor) => Source::Cursor(Dom::from_ref(cursor)),
+        });
+        request.transaction.set(Some(transaction));
+        request
+    }
+
+    pub fn transaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.transaction.get()
+    }
+
+    pub fn set_transaction(&self, transaction: Option<&IDBTransaction>) {
+        self.transaction.set(transaction);
+    }
+
+    /// Mark the request as done with `value` as its result.
+    pub fn set_result(&self, value: HandleValue) {
+        self.result.set(value.get());
+        self.error.set(None);
+        self.done.set(true);
+    }
+
+    /// Mark the request as done, having failed with the given error.
+    pub fn set_error(&self, name: DOMErrorName) {
+        self.result.set(UndefinedValue());
+        self.error
+            .set(Some(&DOMException::new(&self.global(), name)));
+        self.done.set(true);
+    }
+
+    /// Make the request pending again, for a cursor that is iterated.
+    pub fn reset(&self) {
+        self.result.set(UndefinedValue());
+        self.error.set(None);
+        self.done.set(false);
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#fire-a-success-event>
+    pub fn fire_success(&self) {
+        let transaction = self.transaction.get();
+        if let Some(ref transaction) = transaction {
+            transaction.set_active(true);
+        }
+        self.upcast::<EventTarget>().fire_event(atom!("success"));
+        if let Some(ref transaction) = transaction {
+            transaction.set_active(false);
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#fire-an-error-event>
+    ///
+    /// Returns whether the default action of the event was prevented, in which
+    /// case the transaction shouldn't be aborted.
+    pub fn fire_error(&self) -> bool {
+        let transaction = self.transaction.get();
+        if let Some(ref transaction) = transaction {
+            transaction.set_active(true);
+        }
+        let event = Event::new(
+            &self.global(),
+            atom!("error"),
+            EventBubbles::Bubbles,
+            EventCancelable::Cancelable,
+        );
+        let status = event.fire(self.upcast());
+        if let Some(ref transaction) = transaction {
+            transaction.set_active(false);
+        }
+        status == EventStatus::Canceled
+    }
+}
+
+impl IDBRequestMethods for IDBRequest {
+    #[allow(unsafe_code)]
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-result
+    unsafe fn GetResult(&self, _cx: *mut JSContext) -> Fallible<JSVal> {
+        if !self.done.get() {
+            return Err(Error::InvalidState);
+        }
+        Ok(self.result.get())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-error
+    fn GetError(&self) -> Fallible<Option<DomRoot<DOMException>>> {
+        if !self.done.get() {
+            return Err(Error::InvalidState);
+        }
+        Ok(self.error.get())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-source
+    fn GetSource(&self) -> Option<IDBObjectStoreOrIDBIndexOrIDBCursor> {
+        self.source.borrow().as_ref().map(|source| match *source {
+            Source::ObjectStore(ref store) => {
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBObjectStore(DomRoot::from_ref(&**store))
+            },
+            Source::Index(ref index) => {
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBIndex(DomRoot::from_ref(&**index))
+            },
+            Source::Cursor(ref cursor) => {
+                IDBObjectStoreOrIDBIndexOrIDBCursor::IDBCursor(DomRoot::from_ref(&**cursor))
+            },
+        })
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-transaction
+    fn GetTransaction(&self) -> Option<DomRoot<IDBTransaction>> {
+        self.transaction.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-readystate
+    fn ReadyState(&self) -> IDBRequestReadyState {
+        if self.done.get() {
+            IDBRequestReadyState::Done
+        } else {
+            IDBRequestReadyState::Pending
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-onsuccess
+    event_handler!(success, GetOnsuccess, SetOnsuccess);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbrequest-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+}
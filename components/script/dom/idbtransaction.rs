@@ -0,0 +1,622 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::enter_realm;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMethods;
+use crate::dom::bindings::codegen::Bindings::IDBTransactionBinding::IDBTransactionMode;
+use crate::dom::bindings::error::{Error, ErrorResult, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::refcounted::Trusted;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone::StructuredCloneData;
+use crate::dom::bindings::utils::set_dictionary_property;
+use crate::dom::domexception::{DOMErrorName, DOMException};
+use crate::dom::domstringlist::DOMStringList;
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbcursor::IDBCursor;
+use crate::dom::idbdatabase::IDBDatabase;
+use crate::dom::idbobjectstore::IDBObjectStore;
+use crate::dom::idbopendbrequest::IDBOpenDBRequest;
+use crate::dom::idbrequest::IDBRequest;
+use crate::indexed_db::{backend_error_name, index_keys_for_value, inject_key, key_to_jsval};
+use crate::task::TaskCanceller;
+use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
+use crate::task_source::{TaskSource, TaskSourceName};
+use dom_struct::dom_struct;
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use js::jsapi::{HandleValueArray, JSContext, JS_NewArrayObject};
+use js::jsval::{DoubleValue, NullValue, ObjectValue, UndefinedValue};
+use js::rust::MutableHandleValue;
+use net_traits::indexeddb_thread::{BackendResult, IndexInfo, IndexedDBKey};
+use net_traits::indexeddb_thread::{IndexedDBThreadMsg, IndexedDBTxnMode};
+use net_traits::indexeddb_thread::{Operation, OperationResult, TransactionId};
+use net_traits::IpcSend;
+use profile_traits::ipc as ProfiledIpc;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+
+/// <https://w3c.github.io/IndexedDB/#transaction-lifetime-concept>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+enum TransactionState {
+    Active,
+    Inactive,
+    Committing,
+    Finished,
+}
+
+/// How to put a key generated by the backend into the value being stored,
+/// for object stores with a key generator and an in-line key.
+#[derive(JSTraceable, MallocSizeOf)]
+pub struct KeyInjection {
+    pub key_path: String,
+    pub indexes: Vec<IndexInfo>,
+}
+
+/// A request waiting for its operation to be run by the backend.
+#[must_root]
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingRequest {
+    request: Dom<IDBRequest>,
+    #[ignore_malloc_size_of = "Defined in net_traits"]
+    operation: Operation,
+    /// The cursor iterated by the operation, if any.
+    cursor: Option<Dom<IDBCursor>>,
+    injection: Option<KeyInjection>,
+}
+
+#[dom_struct]
+pub struct IDBTransaction {
+    eventtarget: EventTarget,
+    db: Dom<IDBDatabase>,
+    id: TransactionId,
+    mode: IndexedDBTxnMode,
+    /// The names of the object stores in the scope, which for an upgrade
+    /// transaction are those of the database.
+    scope: Vec<DOMString>,
+    state: Cell<TransactionState>,
+    /// Whether the backend has started the transaction, so that its requests
+    /// can be run.
+    started: Cell<bool>,
+    /// Whether a task to run the next request has been queued.
+    processing: Cell<bool>,
+    requests: DomRefCell<VecDeque<PendingRequest>>,
+    error: MutNullableDom<DOMException>,
+    object_stores: DomRefCell<HashMap<DOMString, Dom<IDBObjectStore>>>,
+    /// The request that opened the connection, for an upgrade transaction.
+    open_request: MutNullableDom<IDBOpenDBRequest>,
+}
+
+impl IDBTransaction {
+    fn new_inherited(
+        db: &IDBDatabase,
+        id: TransactionId,
+        mode: IndexedDBTxnMode,
+        scope: Vec<DOMString>,
+        state: TransactionState,
+    ) -> IDBTransaction {
+        IDBTransaction {
+            eventtarget: EventTarget::new_inherited(),
+            db: Dom::from_ref(db),
+            id: id,
+            mode: mode,
+            scope: scope,
+            state: Cell::new(state),
+            started: Cell::new(false),
+            processing: Cell::new(false),
+            requests: DomRefCell::new(VecDeque::new()),
+            error: Default::default(),
+            object_stores: DomRefCell::new(HashMap::new()),
+            open_request: Default::default(),
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#dom-idbdatabase-transaction>, steps 7-9.
+    pub fn new(
+        global: &GlobalScope,
+        db: &IDBDatabase,
+        serial: u64,
+        mode: IndexedDBTxnMode,
+        scope: Vec<DOMString>,
+    ) -> DomRoot<IDBTransaction> {
+        let id = TransactionId {
+            connection: db.connection(),
+            serial: serial,
+        };
+        let transaction = reflect_dom_object(
+            Box::new(IDBTransaction::new_inherited(
+                db,
+                id,
+                mode,
+                scope.clone(),
+                TransactionState::Active,
+            )),
+            global,
+            IDBTransactionBinding::Wrap,
+        );
+
+        // The transaction starts once the backend has scheduled it.
+        let (sender, receiver) = ipc::channel().unwrap();
+        let this = Trusted::new(&*transaction);
+        let (task_source, canceller) = transaction.task_source();
+        ROUTER.add_route(
+            receiver.to_opaque(),
+            Box::new(move |_| {
+                let this = this.clone();
+                let _ = task_source.queue_with_canceller(
+                    task!(start_transaction: move || {
+                        let this = this.root();
+                        this.started.set(true);
+                        this.process_requests();
+                    }),
+                    &canceller,
+                );
+            }),
+        );
+        let scope = scope.into_iter().map(String::from).collect();
+        transaction.send(IndexedDBThreadMsg::CreateTransaction(
+            sender, id, mode, scope,
+        ));
+
+        // Step 8: the transaction becomes inactive when control returns to
+        // the event loop.
+        let this = Trusted::new(&*transaction);
+        let (task_source, canceller) = transaction.task_source();
+        let _ = task_source.queue_with_canceller(
+            task!(deactivate_transaction: move || {
+                let this = this.root();
+                this.set_active(false);
+                this.maybe_commit();
+            }),
+            &canceller,
+        );
+        transaction
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#run-an-upgrade-transaction>, steps 3-5.
+    ///
+    /// The backend has already started the transaction when the connection
+    /// hears about it.
+    pub fn new_upgrade(
+        global: &GlobalScope,
+        db: &IDBDatabase,
+        open_request: &IDBOpenDBRequest,
+    ) -> DomRoot<IDBTransaction> {
+        let id = TransactionId {
+            connection: db.connection(),
+            serial: 0,
+        };
+        let transaction = reflect_dom_object(
+            Box::new(IDBTransaction::new_inherited(
+                db,
+                id,
+                IndexedDBTxnMode::Versionchange,
+                vec![],
+                TransactionState::Inactive,
+            )),
+            global,
+            IDBTransactionBinding::Wrap,
+        );
+        transaction.started.set(true);
+        transaction.open_request.set(Some(open_request));
+        transaction
+    }
+
+    fn task_source(&self) -> (DOMManipulationTaskSource, TaskCanceller) {
+        let global = self.global();
+        (
+            global.dom_manipulation_task_source(),
+            global.task_canceller(TaskSourceName::DOMManipulation),
+        )
+    }
+
+    fn send(&self, message: IndexedDBThreadMsg) {
+        self.global().resource_threads().send(message).unwrap();
+    }
+
+    pub fn mode(&self) -> IndexedDBTxnMode {
+        self.mode
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.get() == TransactionState::Active
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.get() == TransactionState::Finished
+    }
+
+    /// Make the transaction active while an event is dispatched for one of
+    /// its requests, and inactive again afterwards. Transactions that are
+    /// committing or finished are left alone.
+    pub fn set_active(&self, active: bool) {
+        let state = match (self.state.get(), active) {
+            (TransactionState::Inactive, true) => TransactionState::Active,
+            (TransactionState::Active, false) => TransactionState::Inactive,
+            (state, _) => state,
+        };
+        self.state.set(state);
+    }
+
+    /// Run an operation synchronously, for the methods that change the schema
+    /// of the database, which happen in the upgrade transaction.
+    pub fn execute(&self, operation: Operation) -> BackendResult<OperationResult> {
+        let global = self.global();
+        let (sender, receiver) = ProfiledIpc::channel(global.time_profiler_chan().clone()).unwrap();
+        self.send(IndexedDBThreadMsg::Operation(sender, self.id, operation));
+        receiver.recv().unwrap()
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#asynchronously-execute-a-request>
+    pub fn queue_request(
+        &self,
+        request: &IDBRequest,
+        operation: Operation,
+        cursor: Option<&IDBCursor>,
+        injection: Option<KeyInjection>,
+    ) {
+        self.requests.borrow_mut().push_back(PendingRequest {
+            request: Dom::from_ref(request),
+            operation: operation,
+            cursor: cursor.map(Dom::from_ref),
+            injection: injection,
+        });
+        self.process_requests();
+    }
+
+    /// Queue a task to run the next request, unless one is already queued.
+    fn process_requests(&self) {
+        if !self.started.get() ||
+            self.processing.get() ||
+            self.is_finished() ||
+            self.requests.borrow().is_empty()
+        {
+            return;
+        }
+        self.processing.set(true);
+        let this = Trusted::new(self);
+        let (task_source, canceller) = self.task_source();
+        let _ = task_source.queue_with_canceller(
+            task!(process_indexeddb_request: move || {
+                this.root().process_next_request();
+            }),
+            &canceller,
+        );
+    }
+
+    #[allow(unrooted_must_root, unsafe_code)]
+    fn process_next_request(&self) {
+        self.processing.set(false);
+        if self.is_finished() {
+            return;
+        }
+        let pending = match self.requests.borrow_mut().pop_front() {
+            Some(pending) => pending,
+            None => return self.maybe_commit(),
+        };
+        let request = DomRoot::from_ref(&*pending.request);
+        let cursor = pending
+            .cursor
+            .as_ref()
+            .map(|cursor| DomRoot::from_ref(&**cursor));
+
+        let result = match pending.injection {
+            Some(injection) => self.put_with_generated_key(pending.operation, injection),
+            None => self.execute(pending.operation).map_err(backend_error_name),
+        };
+
+        match result {
+            // <https://w3c.github.io/IndexedDB/#fire-a-success-event>
+            Ok(result) => {
+                let global = self.global();
+                let cx = global.get_cx();
+                let _ac = enter_realm(self);
+                rooted!(in(cx) let mut value = UndefinedValue());
+                let cursor = cursor.as_ref().map(|cursor| &**cursor);
+                unsafe { self.result_to_jsval(cx, result, cursor, value.handle_mut()) };
+                request.set_result(value.handle());
+                request.fire_success();
+            },
+            // <https://w3c.github.io/IndexedDB/#fire-an-error-event>
+            Err(name) => {
+                request.set_error(name);
+                if !request.fire_error() {
+                    self.abort(Some(name));
+                }
+            },
+        }
+
+        self.process_requests();
+        self.maybe_commit();
+    }
+
+    /// Store a value in an object store with a key generator and an in-line
+    /// key, as in steps 2-4 of
+    /// <https://w3c.github.io/IndexedDB/#store-a-record-into-an-object-store>.
+    #[allow(unsafe_code)]
+    fn put_with_generated_key(
+        &self,
+        operation: Operation,
+        injection: KeyInjection,
+    ) -> Result<OperationResult, DOMErrorName> {
+        let (store, value, no_overwrite) = match operation {
+            Operation::Put {
+                store,
+                value,
+                no_overwrite,
+                ..
+            } => (store, value, no_overwrite),
+            _ => unreachable!("Only puts have keys injected"),
+        };
+        let key = match self.execute(Operation::GenerateKey(store.clone())) {
+            Ok(OperationResult::Key(Some(key))) => key,
+            Ok(_) => return Err(DOMErrorName::UnknownError),
+            Err(error) => return Err(backend_error_name(error)),
+        };
+
+        let global = self.global();
+        let cx = global.get_cx();
+        let _ac = enter_realm(self);
+        rooted!(in(cx) let mut clone = UndefinedValue());
+        StructuredCloneData::Vector(value).read(&global, clone.handle_mut());
+        let index_keys = unsafe {
+            inject_key(cx, clone.handle(), &key, &injection.key_path)
+                .and_then(|_| index_keys_for_value(cx, clone.handle(), &injection.indexes))
+        }
+        .map_err(|_| DOMErrorName::DataError)?;
+        let value = StructuredCloneData::write(cx, clone.handle())
+            .map_err(|_| DOMErrorName::DataCloneError)?
+            .move_to_arraybuffer();
+
+        self.execute(Operation::Put {
+            store: store,
+            key: Some(key),
+            value: value,
+            index_keys: index_keys,
+            no_overwrite: no_overwrite,
+        })
+        .map_err(backend_error_name)
+    }
+
+    #[allow(unsafe_code)]
+    unsafe fn result_to_jsval(
+        &self,
+        cx: *mut JSContext,
+        result: OperationResult,
+        cursor: Option<&IDBCursor>,
+        mut rval: MutableHandleValue,
+    ) {
+        let global = self.global();
+        match result {
+            OperationResult::Value(Some(value)) => {
+                StructuredCloneData::Vector(value).read(&global, rval)
+            },
+            OperationResult::Value(None) |
+            OperationResult::Key(None) |
+            OperationResult::Records(_) |
+            OperationResult::Undefined => rval.set(UndefinedValue()),
+            OperationResult::Key(Some(key)) => key_to_jsval(cx, &key, rval),
+            OperationResult::Keys(keys) => key_to_jsval(cx, &IndexedDBKey::Array(keys), rval),
+            OperationResult::Values(values) => {
+                rooted!(in(cx) let array = JS_NewArrayObject(cx, &HandleValueArray::new()));
+                for (index, value) in values.into_iter().enumerate() {
+                    rooted!(in(cx) let mut entry = UndefinedValue());
+                    StructuredCloneData::Vector(value).read(&global, entry.handle_mut());
+                    let _ = set_dictionary_property(
+                        cx,
+                        array.handle(),
+                        &index.to_string(),
+                        entry.handle(),
+                    );
+                }
+                rval.set(ObjectValue(array.get()));
+            },
+            OperationResult::Count(count) => rval.set(DoubleValue(count as f64)),
+            OperationResult::Cursor(record) => {
+                let cursor = cursor.expect("Cursor operation without a cursor");
+                if cursor.set_record(cx, record) {
+                    rval.set(ObjectValue(cursor.reflector().get_jsobject().get()));
+                } else {
+                    rval.set(NullValue());
+                }
+            },
+        }
+    }
+
+    /// Commit the transaction once it is inactive and all of its requests
+    /// have run, as described in
+    /// <https://w3c.github.io/IndexedDB/#transaction-lifetime-concept>.
+    pub fn maybe_commit(&self) {
+        let ready = match self.state.get() {
+            TransactionState::Inactive | TransactionState::Committing => true,
+            TransactionState::Active | TransactionState::Finished => false,
+        };
+        if !ready ||
+            !self.started.get() ||
+            self.processing.get() ||
+            !self.requests.borrow().is_empty()
+        {
+            return;
+        }
+        self.commit();
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#commit-a-transaction>
+    fn commit(&self) {
+        self.state.set(TransactionState::Committing);
+        let global = self.global();
+        let (sender, receiver) = ProfiledIpc::channel(global.time_profiler_chan().clone()).unwrap();
+        self.send(IndexedDBThreadMsg::Commit(sender, self.id));
+        if let Err(error) = receiver.recv().unwrap() {
+            return self.abort(Some(backend_error_name(error)));
+        }
+
+        self.state.set(TransactionState::Finished);
+        if self.mode == IndexedDBTxnMode::Versionchange {
+            self.db.finish_upgrade(false);
+        }
+        self.upcast::<EventTarget>().fire_event(atom!("complete"));
+        if let Some(open_request) = self.open_request.get() {
+            open_request.upgrade_finished(false);
+        }
+    }
+
+    /// <https://w3c.github.io/IndexedDB/#abort-a-transaction>
+    pub fn abort(&self, error: Option<DOMErrorName>) {
+        if self.is_finished() {
+            return;
+        }
+        // Steps 1-2.
+        let global = self.global();
+        let (sender, receiver) = ProfiledIpc::channel(global.time_profiler_chan().clone()).unwrap();
+        self.send(IndexedDBThreadMsg::Abort(sender, self.id));
+        receiver.recv().unwrap();
+        // Step 3.
+        self.state.set(TransactionState::Finished);
+        if self.mode == IndexedDBTxnMode::Versionchange {
+            self.db.finish_upgrade(true);
+        }
+        // Step 4.
+        if let Some(name) = error {
+            self.error.set(Some(&DOMException::new(&global, name)));
+        }
+
+        // Step 5.
+        let requests: Vec<_> = self
+            .requests
+            .borrow_mut()
+            .drain(..)
+            .map(|pending| Trusted::new(&*pending.request))
+            .collect();
+        // Step 6.
+        let this = Trusted::new(self);
+        let (task_source, canceller) = self.task_source();
+        let _ = task_source.queue_with_canceller(
+            task!(abort_transaction: move || {
+                for request in requests {
+                    let request = request.root();
+                    request.set_error(DOMErrorName::AbortError);
+                    request.fire_error();
+                }
+                let this = this.root();
+                let event = Event::new(
+                    &this.global(),
+                    atom!("abort"),
+                    EventBubbles::Bubbles,
+                    EventCancelable::NotCancelable,
+                );
+                event.fire(this.upcast());
+                if let Some(open_request) = this.open_request.get() {
+                    open_request.upgrade_finished(true);
+                }
+            }),
+            &canceller,
+        );
+    }
+
+    /// Drop the object store of a deleted object store, so that a new one is
+    /// made if an object store with the same name is created.
+    pub fn forget_object_store(&self, name: &DOMString) {
+        if let Some(store) = self.object_stores.borrow_mut().remove(name) {
+            store.mark_deleted();
+        }
+    }
+
+    /// Track the renaming of one of the object stores of an upgrade
+    /// transaction.
+    pub fn rename_object_store(&self, old_name: &DOMString, new_name: DOMString) {
+        let mut stores = self.object_stores.borrow_mut();
+        if let Some(store) = stores.remove(old_name) {
+            stores.insert(new_name, store);
+        }
+    }
+}
+
+impl IDBTransactionMethods for IDBTransaction {
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-objectstorenames
+    fn ObjectStoreNames(&self) -> DomRoot<DOMStringList> {
+        let names = if self.mode == IndexedDBTxnMode::Versionchange {
+            self.db.object_store_names()
+        } else {
+            self.scope.clone()
+        };
+        DOMStringList::new(&self.global(), names)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-mode
+    fn Mode(&self) -> IDBTransactionMode {
+        match self.mode {
+            IndexedDBTxnMode::Readonly => IDBTransactionMode::Readonly,
+            IndexedDBTxnMode::Readwrite => IDBTransactionMode::Readwrite,
+            IndexedDBTxnMode::Versionchange => IDBTransactionMode::Versionchange,
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-db
+    fn Db(&self) -> DomRoot<IDBDatabase> {
+        DomRoot::from_ref(&*self.db)
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-error
+    fn GetError(&self) -> Option<DomRoot<DOMException>> {
+        self.error.get()
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-objectstore
+    fn ObjectStore(&self, name: DOMString) -> Fallible<DomRoot<IDBObjectStore>> {
+        // Step 1.
+        if self.is_finished() {
+            return Err(Error::InvalidState);
+        }
+        // Step 2.
+        let in_scope = self.mode == IndexedDBTxnMode::Versionchange || self.scope.contains(&name);
+        let info = match self.db.object_store_info(&name) {
+            Some(info) if in_scope => info,
+            _ => return Err(Error::NotFound),
+        };
+        // Step 3.
+        let mut stores = self.object_stores.borrow_mut();
+        let store = stores
+            .entry(name)
+            .or_insert_with(|| Dom::from_ref(&*IDBObjectStore::new(&self.global(), self, info)));
+        Ok(DomRoot::from_ref(&**store))
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-commit
+    fn Commit(&self) -> ErrorResult {
+        if !self.is_active() {
+            return Err(Error::InvalidState);
+        }
+        self.state.set(TransactionState::Committing);
+        self.maybe_commit();
+        Ok(())
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-abort
+    fn Abort(&self) -> ErrorResult {
+        match self.state.get() {
+            TransactionState::Committing | TransactionState::Finished => Err(Error::InvalidState),
+            TransactionState::Active | TransactionState::Inactive => {
+                self.abort(None);
+                Ok(())
+            },
+        }
+    }
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-onabort
+    event_handler!(abort, GetOnabort, SetOnabort);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-oncomplete
+    event_handler!(complete, GetOncomplete, SetOncomplete);
+
+    // https://w3c.github.io/IndexedDB/#dom-idbtransaction-onerror
+    event_handler!(error, GetOnerror, SetOnerror);
+}
@@ -0,0 +1,563 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::IntersectionObserverBinding::{
+    self, IntersectionObserverCallback, IntersectionObserverInit, IntersectionObserverMethods,
+};
+use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
+use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::codegen::UnionTypes::DoubleOrDoubleSequence;
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::document::Document;
+use crate::dom::domrectreadonly::DOMRectReadOnly;
+use crate::dom::element::Element;
+use crate::dom::intersectionobserverentry::IntersectionObserverEntry;
+use crate::dom::node::{Node, ShadowIncluding};
+use crate::dom::window::Window;
+use app_units::Au;
+use cssparser::{Parser, ParserInput, Token};
+use dom_struct::dom_struct;
+use euclid::{Point2D, Rect, Size2D, Vector2D};
+use js::jsapi::{Heap, JSContext};
+use js::jsval::{JSVal, UndefinedValue};
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A component of the root margin of an observer.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+enum LengthPercentage {
+    Px(f32),
+    Percent(f32),
+}
+
+impl LengthPercentage {
+    fn resolve(&self, basis: f64) -> f64 {
+        match *self {
+            LengthPercentage::Px(px) => px as f64,
+            LengthPercentage::Percent(percent) => basis * percent as f64 / 100.,
+        }
+    }
+}
+
+impl fmt::Display for LengthPercentage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LengthPercentage::Px(px) => write!(f, "{}px", px),
+            LengthPercentage::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-rootmargin-slot>
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+struct RootMargin {
+    top: LengthPercentage,
+    right: LengthPercentage,
+    bottom: LengthPercentage,
+    left: LengthPercentage,
+}
+
+impl RootMargin {
+    /// <https://w3c.github.io/IntersectionObserver/#parse-a-root-margin>
+    fn parse(margin: &str) -> Result<RootMargin, ()> {
+        let mut input = ParserInput::new(margin);
+        let mut parser = Parser::new(&mut input);
+        let mut values = vec![];
+        while !parser.is_exhausted() {
+            let value = match *parser.next().map_err(|_| ())? {
+                Token::Dimension {
+                    value, ref unit, ..
+                } if unit.eq_ignore_ascii_case("px") => LengthPercentage::Px(value),
+                Token::Percentage { unit_value, .. } => {
+                    LengthPercentage::Percent(unit_value * 100.)
+                },
+                Token::Number { value, .. } if value == 0. => LengthPercentage::Px(0.),
+                _ => return Err(()),
+            };
+            values.push(value);
+        }
+        let (top, right, bottom, left) = match values[..] {
+            [] => {
+                let zero = LengthPercentage::Px(0.);
+                (zero, zero, zero, zero)
+            },
+            [all] => (all, all, all, all),
+            [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+            [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+            [top, right, bottom, left] => (top, right, bottom, left),
+            _ => return Err(()),
+        };
+        Ok(RootMargin {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+
+    /// Grows `rect` by this margin, resolving percentages against its size.
+    fn apply(&self, rect: Rect<f64>) -> Rect<f64> {
+        let top = self.top.resolve(rect.size.height);
+        let right = self.right.resolve(rect.size.width);
+        let bottom = self.bottom.resolve(rect.size.height);
+        let left = self.left.resolve(rect.size.width);
+        Rect::new(
+            Point2D::new(rect.origin.x - left, rect.origin.y - top),
+            Size2D::new(
+                (rect.size.width + left + right).max(0.),
+                (rect.size.height + top + bottom).max(0.),
+            ),
+        )
+    }
+}
+
+impl fmt::Display for RootMargin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#intersectionobserverregistration>
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct IntersectionObserverRegistration {
+    target: Dom<Element>,
+    /// None until the first update of the observations, the spec's -1.
+    previous_threshold_index: Cell<Option<usize>>,
+    previous_is_intersecting: Cell<bool>,
+}
+
+fn rect_to_px(rect: Rect<Au>) -> Rect<f64> {
+    Rect::new(
+        Point2D::new(rect.origin.x.to_f64_px(), rect.origin.y.to_f64_px()),
+        Size2D::new(rect.size.width.to_f64_px(), rect.size.height.to_f64_px()),
+    )
+}
+
+/// The intersection of two rectangles, which exists even if they only share an edge.
+fn edge_inclusive_intersection(a: &Rect<f64>, b: &Rect<f64>) -> Option<Rect<f64>> {
+    let min_x = a.min_x().max(b.min_x());
+    let min_y = a.min_y().max(b.min_y());
+    let max_x = a.max_x().min(b.max_x());
+    let max_y = a.max_y().min(b.max_y());
+    if max_x < min_x || max_y < min_y {
+        return None;
+    }
+    Some(Rect::new(
+        Point2D::new(min_x, min_y),
+        Size2D::new(max_x - min_x, max_y - min_y),
+    ))
+}
+
+/// Whether the boxes of `node` are positioned relative to the viewport rather than to
+/// a scrolling box, i.e. it is the document or its document element.
+fn is_scrolled_by_viewport(node: &Node) -> bool {
+    node.is::<Document>() ||
+        node.GetParentNode()
+            .map_or(false, |parent| parent.is::<Document>())
+}
+
+/// The sum of the scroll offsets of the ancestors of `node`, other than the viewport.
+fn ancestor_scroll_offset(window: &Window, node: &Node) -> Vector2D<f64> {
+    node.inclusive_ancestors(ShadowIncluding::Yes)
+        .skip(1)
+        .take_while(|ancestor| !is_scrolled_by_viewport(ancestor))
+        .fold(Vector2D::zero(), |offset, ancestor| {
+            let scroll_offset = window.scroll_offset_query(&ancestor);
+            offset + Vector2D::new(scroll_offset.x as f64, scroll_offset.y as f64)
+        })
+}
+
+/// The padding box of `element` in page coordinates, moved by the scroll offsets of its
+/// ancestors, or None if it has no box.
+fn scrolled_padding_box(window: &Window, element: &Element) -> Option<Rect<f64>> {
+    let node = element.upcast::<Node>();
+    let border_box = rect_to_px(node.bounding_content_box()?);
+    let client_rect = window.client_rect_query(node);
+    let padding_box = Rect::new(
+        Point2D::new(
+            border_box.origin.x + client_rect.origin.x as f64,
+            border_box.origin.y + client_rect.origin.y as f64,
+        ),
+        Size2D::new(
+            client_rect.size.width as f64,
+            client_rect.size.height as f64,
+        ),
+    );
+    Some(padding_box.translate(&-ancestor_scroll_offset(window, node)))
+}
+
+/// <https://w3c.github.io/IntersectionObserver/#intersection-observer-interface>
+#[dom_struct]
+pub struct IntersectionObserver {
+    reflector_: Reflector,
+    #[ignore_malloc_size_of = "can't measure Rc values"]
+    callback: Rc<IntersectionObserverCallback>,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-root>
+    ///
+    /// None for the implicit root, which is the viewport of the document of the observer.
+    root: Option<Dom<Element>>,
+    root_margin: RootMargin,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-thresholds>
+    thresholds: Vec<f64>,
+    /// The array returned by the thresholds getter, created on first access.
+    #[ignore_malloc_size_of = "mozjs"]
+    thresholds_array: Heap<JSVal>,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-queuedentries-slot>
+    queued_entries: DomRefCell<Vec<Dom<IntersectionObserverEntry>>>,
+    /// <https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-observationtargets-slot>
+    ///
+    /// The registrations of the targets are kept with the observer rather than on the
+    /// targets themselves.
+    observation_targets: DomRefCell<Vec<IntersectionObserverRegistration>>,
+}
+
+impl IntersectionObserver {
+    fn new_inherited(
+        callback: Rc<IntersectionObserverCallback>,
+        root: Option<&Element>,
+        root_margin: RootMargin,
+        thresholds: Vec<f64>,
+    ) -> IntersectionObserver {
+        IntersectionObserver {
+            reflector_: Reflector::new(),
+            callback,
+            root: root.map(Dom::from_ref),
+            root_margin,
+            thresholds,
+            thresholds_array: Heap::default(),
+            queued_entries: DomRefCell::new(vec![]),
+            observation_targets: DomRefCell::new(vec![]),
+        }
+    }
+
+    fn new(
+        window: &Window,
+        callback: Rc<IntersectionObserverCallback>,
+        root: Option<&Element>,
+        root_margin: RootMargin,
+        thresholds: Vec<f64>,
+    ) -> DomRoot<IntersectionObserver> {
+        reflect_dom_object(
+            Box::new(IntersectionObserver::new_inherited(
+                callback,
+                root,
+                root_margin,
+                thresholds,
+            )),
+            window,
+            IntersectionObserverBinding::Wrap,
+        )
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-intersectionobserver
+    pub fn Constructor(
+        window: &Window,
+        callback: Rc<IntersectionObserverCallback>,
+        options: &IntersectionObserverInit,
+    ) -> Fallible<DomRoot<IntersectionObserver>> {
+        // Step 2-3.
+        let root_margin = RootMargin::parse(&options.rootMargin).map_err(|()| Error::Syntax)?;
+        // Step 4.
+        let mut thresholds: Vec<f64> = match options.threshold {
+            None => vec![0.],
+            Some(DoubleOrDoubleSequence::Double(threshold)) => vec![*threshold],
+            Some(DoubleOrDoubleSequence::DoubleSequence(ref thresholds)) => {
+                thresholds.iter().map(|threshold| **threshold).collect()
+            },
+        };
+        // Step 5.
+        if thresholds
+            .iter()
+            .any(|threshold| *threshold < 0. || *threshold > 1.)
+        {
+            return Err(Error::Range(
+                "Threshold values must be between 0 and 1".to_owned(),
+            ));
+        }
+        // Step 6.
+        thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // Step 7.
+        if thresholds.is_empty() {
+            thresholds.push(0.);
+        }
+        // Step 8.
+        Ok(IntersectionObserver::new(
+            window,
+            callback,
+            options.root.as_ref().map(|root| &**root),
+            root_margin,
+            thresholds,
+        ))
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#intersectionobserver-root-intersection-rectangle>
+    ///
+    /// In page coordinates, or None if the root has no box.
+    fn root_intersection_rectangle(&self, window: &Window) -> Option<Rect<f64>> {
+        let rect = match self.root {
+            None => rect_to_px(window.current_viewport()),
+            Some(ref root) if root.has_content_clip() => scrolled_padding_box(window, root)?,
+            Some(ref root) => {
+                let node = root.upcast::<Node>();
+                rect_to_px(node.bounding_content_box()?)
+                    .translate(&-ancestor_scroll_offset(window, node))
+            },
+        };
+        Some(self.root_margin.apply(rect))
+    }
+
+    /// <https://w3c.github.io/IntersectionObserver/#compute-the-intersection>
+    fn compute_the_intersection(
+        &self,
+        window: &Window,
+        target: &Element,
+        target_rect: Rect<f64>,
+        root_intersection_rect: &Rect<f64>,
+    ) -> Option<Rect<f64>> {
+        // Step 1.
+        let mut intersection_rect = target_rect;
+        // Step 2-3.
+        for ancestor in target
+            .upcast::<Node>()
+            .inclusive_ancestors(ShadowIncluding::Yes)
+            .skip(1)
+        {
+            if is_scrolled_by_viewport(&ancestor) ||
+                self.root
+                    .as_ref()
+                    .map_or(false, |root| &*ancestor == root.upcast())
+            {
+                break;
+            }
+            let element = match DomRoot::downcast::<Element>(ancestor) {
+                Some(element) => element,
+                None => continue,
+            };
+            if element.has_content_clip() {
+                if let Some(clip) = scrolled_padding_box(window, &element) {
+                    intersection_rect = edge_inclusive_intersection(&intersection_rect, &clip)?;
+                }
+            }
+        }
+        // Step 4-6.
+        edge_inclusive_intersection(&intersection_rect, root_intersection_rect)
+    }
+
+    /// Step 2 of <https://w3c.github.io/IntersectionObserver/#update-intersection-observations-algo>,
+    /// for this observer. Returns whether any entries were queued.
+    pub fn update_intersection_observations(&self, document: &Document, time: f64) -> bool {
+        let window = self.global();
+        let window = window.as_window();
+        let viewport_origin = rect_to_px(window.current_viewport()).origin.to_vector();
+        let to_dom_rect = |rect: &Rect<f64>| {
+            let rect = rect.translate(&-viewport_origin);
+            DOMRectReadOnly::new(
+                window.upcast(),
+                rect.origin.x,
+                rect.origin.y,
+                rect.size.width,
+                rect.size.height,
+            )
+        };
+        // Step 2.1.
+        let root_bounds = self.root_intersection_rectangle(window);
+        let mut queued = false;
+        // Step 2.2.
+        for registration in self.observation_targets.borrow().iter() {
+            let target = &*registration.target;
+            let target_node = target.upcast::<Node>();
+            // Step 2.2.1.
+            let mut threshold_index = 0;
+            let mut is_intersecting = false;
+            let mut target_rect = Rect::zero();
+            let mut intersection_rect = Rect::zero();
+            let mut intersection_ratio = 0.;
+            // Step 2.2.2-2.2.3.
+            let is_observable = target_node.is_connected() &&
+                match self.root {
+                    None => *target_node.owner_doc() == *document,
+                    Some(ref root) => target_node
+                        .inclusive_ancestors(ShadowIncluding::Yes)
+                        .skip(1)
+                        .any(|ancestor| &*ancestor == root.upcast()),
+                };
+            let target_box = if is_observable {
+                target_node.bounding_content_box()
+            } else {
+                None
+            };
+            if let (Some(root_bounds), Some(target_box)) = (root_bounds.as_ref(), target_box) {
+                // Step 2.2.4.
+                target_rect =
+                    rect_to_px(target_box).translate(&-ancestor_scroll_offset(window, target_node));
+                // Step 2.2.5.
+                let intersection =
+                    self.compute_the_intersection(window, target, target_rect, root_bounds);
+                // Step 2.2.6.
+                let target_area = target_rect.size.width * target_rect.size.height;
+                // Step 2.2.7.
+                is_intersecting = intersection.is_some();
+                intersection_rect = intersection.unwrap_or_else(Rect::zero);
+                // Step 2.2.8.
+                intersection_ratio = if target_area > 0. {
+                    intersection_rect.size.width * intersection_rect.size.height / target_area
+                } else if is_intersecting {
+                    1.
+                } else {
+                    0.
+                };
+                // Step 2.2.9.
+                threshold_index = self
+                    .thresholds
+                    .iter()
+                    .position(|threshold| *threshold > intersection_ratio)
+                    .unwrap_or(self.thresholds.len());
+            }
+            // Step 2.2.10-2.2.11.
+            if registration.previous_threshold_index.get() != Some(threshold_index) ||
+                registration.previous_is_intersecting.get() != is_intersecting
+            {
+                let root_bounds = root_bounds.as_ref().map(|rect| to_dom_rect(rect));
+                let entry = IntersectionObserverEntry::new(
+                    window,
+                    time,
+                    root_bounds.as_ref().map(|rect| &**rect),
+                    &to_dom_rect(&target_rect),
+                    &to_dom_rect(&intersection_rect),
+                    is_intersecting,
+                    intersection_ratio,
+                    target,
+                );
+                self.queued_entries
+                    .borrow_mut()
+                    .push(Dom::from_ref(&*entry));
+                queued = true;
+            }
+            // Step 2.2.12-2.2.13.
+            registration
+                .previous_threshold_index
+                .set(Some(threshold_index));
+            registration.previous_is_intersecting.set(is_intersecting);
+        }
+        queued
+    }
+
+    /// Step 3 of <https://w3c.github.io/IntersectionObserver/#notify-intersection-observers-algo>,
+    /// for this observer.
+    pub fn notify(&self) {
+        // Step 3.1.
+        if self.queued_entries.borrow().is_empty() {
+            return;
+        }
+        // Step 3.2-3.3.
+        let entries = self.TakeRecords();
+        // Step 3.4.
+        let _ = self
+            .callback
+            .Call_(self, entries, self, ExceptionHandling::Report);
+    }
+}
+
+impl IntersectionObserverMethods for IntersectionObserver {
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-root
+    fn GetRoot(&self) -> Option<DomRoot<Element>> {
+        self.root.as_ref().map(|root| DomRoot::from_ref(&**root))
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-rootmargin
+    fn RootMargin(&self) -> DOMString {
+        DOMString::from(self.root_margin.to_string())
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-thresholds
+    #[allow(unsafe_code)]
+    unsafe fn Thresholds(&self, cx: *mut JSContext) -> JSVal {
+        if self.thresholds_array.get().is_undefined() {
+            rooted!(in(cx) let mut value = UndefinedValue());
+            self.thresholds.to_jsval(cx, value.handle_mut());
+            self.thresholds_array.set(value.get());
+        }
+        self.thresholds_array.get()
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-observe
+    fn Observe(&self, target: &Element) {
+        // Step 1.
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        if observation_targets
+            .iter()
+            .any(|registration| &*registration.target == target)
+        {
+            return;
+        }
+        // Without targets, the observer is not known to its document.
+        if observation_targets.is_empty() {
+            self.global()
+                .as_window()
+                .Document()
+                .add_intersection_observer(self);
+        }
+        // Step 2-4.
+        observation_targets.push(IntersectionObserverRegistration {
+            target: Dom::from_ref(target),
+            previous_threshold_index: Cell::new(None),
+            previous_is_intersecting: Cell::new(false),
+        });
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-unobserve
+    fn Unobserve(&self, target: &Element) {
+        // Step 1-2.
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        let index = observation_targets
+            .iter()
+            .position(|registration| &*registration.target == target);
+        if let Some(index) = index {
+            observation_targets.remove(index);
+            if observation_targets.is_empty() {
+                self.global()
+                    .as_window()
+                    .Document()
+                    .remove_intersection_observer(self);
+            }
+        }
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-disconnect
+    fn Disconnect(&self) {
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        if !observation_targets.is_empty() {
+            observation_targets.clear();
+            self.global()
+                .as_window()
+                .Document()
+                .remove_intersection_observer(self);
+        }
+        // Entries queued for the removed targets will not be delivered.
+        self.queued_entries.borrow_mut().clear();
+    }
+
+    // https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-takerecords
+    fn TakeRecords(&self) -> Vec<DomRoot<IntersectionObserverEntry>> {
+        // Step 1-3.
+        self.queued_entries
+            .borrow_mut()
+            .drain(..)
+            .map(|entry| DomRoot::from_ref(&*entry))
+            .collect()
+    }
+}
@@ -14,10 +14,12 @@ use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::event::Event;
 use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::messageport::MessagePort;
 use crate::dom::windowproxy::WindowProxy;
 use dom_struct::dom_struct;
+use js::conversions::ToJSValConvertible;
 use js::jsapi::{Heap, JSContext, JSObject};
-use js::jsval::JSVal;
+use js::jsval::{JSVal, UndefinedValue};
 use js::rust::HandleValue;
 use servo_atoms::Atom;
 use std::ptr::NonNull;
@@ -30,6 +32,10 @@ pub struct MessageEvent {
     origin: DOMString,
     source: Option<Dom<WindowProxy>>,
     lastEventId: DOMString,
+    ports: Vec<Dom<MessagePort>>,
+    /// The array returned by the `ports` getter, created on first access.
+    #[ignore_malloc_size_of = "mozjs"]
+    ports_array: Heap<JSVal>,
 }
 
 impl MessageEvent {
@@ -40,6 +46,7 @@ impl MessageEvent {
             DOMString::new(),
             None,
             DOMString::new(),
+            vec![],
         )
     }
 
@@ -49,6 +56,7 @@ impl MessageEvent {
         origin: DOMString,
         source: Option<&WindowProxy>,
         lastEventId: DOMString,
+        ports: Vec<DomRoot<MessagePort>>,
     ) -> DomRoot<MessageEvent> {
         let ev = Box::new(MessageEvent {
             event: Event::new_inherited(),
@@ -56,6 +64,8 @@ impl MessageEvent {
             origin: origin,
             source: source.map(Dom::from_ref),
             lastEventId: lastEventId,
+            ports: ports.iter().map(|port| Dom::from_ref(&**port)).collect(),
+            ports_array: Heap::default(),
         });
         let ev = reflect_dom_object(ev, global, MessageEventBinding::Wrap);
         ev.data.set(data.get());
@@ -72,8 +82,9 @@ impl MessageEvent {
         origin: DOMString,
         source: Option<&WindowProxy>,
         lastEventId: DOMString,
+        ports: Vec<DomRoot<MessagePort>>,
     ) -> DomRoot<MessageEvent> {
-        let ev = MessageEvent::new_initialized(global, data, origin, source, lastEventId);
+        let ev = MessageEvent::new_initialized(global, data, origin, source, lastEventId, ports);
         {
             let event = ev.upcast::<Event>();
             event.init_event(type_, bubbles, cancelable);
@@ -99,6 +110,7 @@ impl MessageEvent {
             init.origin.clone(),
             source.as_ref().map(|source| &**source),
             init.lastEventId.clone(),
+            init.ports.clone(),
         );
         Ok(ev)
     }
@@ -111,6 +123,7 @@ impl MessageEvent {
         message: HandleValue,
         origin: Option<&str>,
         source: Option<&WindowProxy>,
+        ports: Vec<DomRoot<MessagePort>>,
     ) {
         let messageevent = MessageEvent::new(
             scope,
@@ -121,6 +134,23 @@ impl MessageEvent {
             DOMString::from(origin.unwrap_or("")),
             source,
             DOMString::new(),
+            ports,
+        );
+        messageevent.upcast::<Event>().fire(target);
+    }
+
+    /// Fire a `messageerror` event, for a message that could not be deserialized.
+    pub fn dispatch_error(target: &EventTarget, scope: &GlobalScope) {
+        let messageevent = MessageEvent::new(
+            scope,
+            atom!("messageerror"),
+            false,
+            false,
+            HandleValue::null(),
+            DOMString::new(),
+            None,
+            DOMString::new(),
+            vec![],
         );
         messageevent.upcast::<Event>().fire(target);
     }
@@ -146,6 +176,22 @@ impl MessageEventMethods for MessageEvent {
             .and_then(|source| NonNull::new(source.reflector().get_jsobject().get()))
     }
 
+    #[allow(unsafe_code)]
+    // https://html.spec.whatwg.org/multipage/#dom-messageevent-ports
+    unsafe fn Ports(&self, cx: *mut JSContext) -> JSVal {
+        if self.ports_array.get().is_undefined() {
+            let ports: Vec<DomRoot<MessagePort>> = self
+                .ports
+                .iter()
+                .map(|port| DomRoot::from_ref(&**port))
+                .collect();
+            rooted!(in(cx) let mut array = UndefinedValue());
+            ports.to_jsval(cx, array.handle_mut());
+            self.ports_array.set(array.get());
+        }
+        self.ports_array.get()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-messageevent-lasteventid
     fn LastEventId(&self) -> DOMString {
         self.lastEventId.clone()
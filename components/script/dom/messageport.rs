@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use crate::dom::bindings::codegen::Bindings::MessagePortBinding::{self, MessagePortMethods};
+use crate::dom::bindings::error::{Error, ErrorResult};
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::structuredclone::{self, StructuredDataHolder};
+use crate::dom::eventtarget::EventTarget;
+use crate::dom::globalscope::GlobalScope;
+use dom_struct::dom_struct;
+use js::jsapi::{JSContext, JSObject};
+use js::rust::{CustomAutoRooterGuard, HandleValue};
+use msg::constellation_msg::{MessagePortId, MessagePortIndex, PipelineNamespaceId};
+use script_traits::transferable::PortMessageTask;
+use std::cell::Cell;
+use std::num::NonZeroU32;
+use std::rc::Rc;
+
+#[dom_struct]
+/// The MessagePort used in the DOM.
+pub struct MessagePort {
+    eventtarget: EventTarget,
+    message_port_id: MessagePortId,
+    detached: Cell<bool>,
+}
+
+impl MessagePort {
+    fn new_inherited(message_port_id: MessagePortId) -> MessagePort {
+        MessagePort {
+            eventtarget: EventTarget::new_inherited(),
+            message_port_id,
+            detached: Cell::new(false),
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#create-a-new-messageport-object>
+    pub fn new(owner: &GlobalScope) -> DomRoot<MessagePort> {
+        let port = reflect_dom_object(
+            Box::new(MessagePort::new_inherited(MessagePortId::new())),
+            owner,
+            MessagePortBinding::Wrap,
+        );
+        owner.track_message_port(&port, None);
+        port
+    }
+
+    /// Create the object of a port that was transferred to `owner`.
+    fn new_transferred(
+        owner: &GlobalScope,
+        message_port_id: MessagePortId,
+    ) -> DomRoot<MessagePort> {
+        reflect_dom_object(
+            Box::new(MessagePort::new_inherited(message_port_id)),
+            owner,
+            MessagePortBinding::Wrap,
+        )
+    }
+
+    pub fn message_port_id(&self) -> &MessagePortId {
+        &self.message_port_id
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#message-ports:transfer-steps>
+    ///
+    /// Returns the id of the port, packed into the extra data of the transfer.
+    pub fn transfer(&self, sc_holder: &mut StructuredDataHolder) -> Result<u64, ()> {
+        if self.detached.get() {
+            return Err(());
+        }
+        let port_impls = match sc_holder {
+            StructuredDataHolder::Write(port_impls) => port_impls,
+            StructuredDataHolder::Read { .. } => return Err(()),
+        };
+
+        // Step 1, the port travels to its new owner with its message queue.
+        let port_impl = self
+            .global()
+            .mark_port_as_transferred(&self.message_port_id)
+            .ok_or(())?;
+        self.detached.set(true);
+        port_impls
+            .get_or_insert_with(Default::default)
+            .insert(self.message_port_id, port_impl);
+
+        let PipelineNamespaceId(namespace_id) = self.message_port_id.namespace_id;
+        let MessagePortIndex(index) = self.message_port_id.index;
+        Ok(((namespace_id as u64) << 32) | index.get() as u64)
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#message-ports:transfer-receiving-steps>
+    pub fn transfer_receive(
+        owner: &GlobalScope,
+        sc_holder: &mut StructuredDataHolder,
+        extra_data: u64,
+    ) -> Result<DomRoot<MessagePort>, ()> {
+        let (message_ports, port_impls) = match sc_holder {
+            StructuredDataHolder::Read {
+                message_ports,
+                port_impls,
+                ..
+            } => (message_ports, port_impls),
+            StructuredDataHolder::Write(_) => return Err(()),
+        };
+
+        let index = NonZeroU32::new(extra_data as u32).ok_or(())?;
+        let id = MessagePortId {
+            namespace_id: PipelineNamespaceId((extra_data >> 32) as u32),
+            index: MessagePortIndex(index),
+        };
+        let port_impl = port_impls
+            .as_mut()
+            .and_then(|port_impls| port_impls.remove(&id))
+            .ok_or(())?;
+
+        let port = MessagePort::new_transferred(owner, id);
+        owner.track_message_port(&port, Some(port_impl));
+        message_ports.push(port.clone());
+        Ok(port)
+    }
+}
+
+impl MessagePortMethods for MessagePort {
+    #[allow(unsafe_code)]
+    // https://html.spec.whatwg.org/multipage/#dom-messageport-postmessage
+    unsafe fn PostMessage(
+        &self,
+        cx: *mut JSContext,
+        message: HandleValue,
+        transfer: CustomAutoRooterGuard<Vec<*mut JSObject>>,
+    ) -> ErrorResult {
+        if self.detached.get() {
+            return Ok(());
+        }
+
+        // Step 2, a port can't be sent through itself.
+        let own_object = self.reflector().get_jsobject().get();
+        if transfer.iter().any(|object| *object == own_object) {
+            return Err(Error::DataClone);
+        }
+
+        // Step 5-6.
+        let data = structuredclone::write(cx, message, Some(transfer))?;
+
+        // Step 7, the global drops the message if the port isn't entangled.
+        let global = self.global();
+        let task = PortMessageTask {
+            origin: global.origin().immutable().clone(),
+            data,
+        };
+        global.post_messageport_msg(self.message_port_id, task);
+        Ok(())
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-messageport-start
+    fn Start(&self) {
+        if self.detached.get() {
+            return;
+        }
+        self.global().start_message_port(&self.message_port_id);
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-messageport-close
+    fn Close(&self) {
+        if self.detached.get() {
+            return;
+        }
+        self.detached.set(true);
+        self.global().close_message_port(&self.message_port_id);
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-messageport-onmessage
+    fn GetOnmessage(&self) -> Option<Rc<EventHandlerNonNull>> {
+        self.upcast::<EventTarget>()
+            .get_event_handler_common("message")
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-messageport-onmessage
+    fn SetOnmessage(&self, listener: Option<Rc<EventHandlerNonNull>>) {
+        self.upcast::<EventTarget>()
+            .set_event_handler_common("message", listener);
+        // Setting onmessage implicitly enables the port message queue.
+        self.Start();
+    }
+
+    // https://html.spec.whatwg.org/multipage/#handler-messageport-onmessageerror
+    event_handler!(messageerror, GetOnmessageerror, SetOnmessageerror);
+}
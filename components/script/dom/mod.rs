@@ -212,6 +212,8 @@ pub mod types {
     include!(concat!(env!("OUT_DIR"), "/build/InterfaceTypes.rs"));
 }
 
+pub mod abortcontroller;
+pub mod abortsignal;
 pub mod abstractworker;
 pub mod abstractworkerglobalscope;
 pub mod activation;
@@ -242,6 +244,9 @@ pub mod bluetoothremotegattdescriptor;
 pub mod bluetoothremotegattserver;
 pub mod bluetoothremotegattservice;
 pub mod bluetoothuuid;
+pub mod broadcastchannel;
+pub mod cache;
+pub mod cachestorage;
 pub mod canvasgradient;
 pub mod canvaspattern;
 pub mod canvasrenderingcontext2d;
@@ -265,6 +270,7 @@ pub mod csskeyframerule;
 pub mod csskeyframesrule;
 pub mod cssmediarule;
 pub mod cssnamespacerule;
+pub mod csspagerule;
 pub mod cssrule;
 pub mod cssrulelist;
 pub mod cssstyledeclaration;
@@ -292,6 +298,7 @@ pub mod dompointreadonly;
 pub mod domquad;
 pub mod domrect;
 pub mod domrectreadonly;
+pub mod domstringlist;
 pub mod domstringmap;
 pub mod domtokenlist;
 pub mod element;
@@ -302,6 +309,7 @@ pub mod eventtarget;
 pub mod extendableevent;
 pub mod extendablemessageevent;
 pub mod fakexrdevice;
+pub mod fetchevent;
 pub mod file;
 pub mod filelist;
 pub mod filereader;
@@ -390,8 +398,21 @@ pub mod htmltrackelement;
 pub mod htmlulistelement;
 pub mod htmlunknownelement;
 pub mod htmlvideoelement;
+pub mod idbcursor;
+pub mod idbcursorwithvalue;
+pub mod idbdatabase;
+pub mod idbfactory;
+pub mod idbindex;
+pub mod idbkeyrange;
+pub mod idbobjectstore;
+pub mod idbopendbrequest;
+pub mod idbrequest;
+pub mod idbtransaction;
+pub mod idbversionchangeevent;
 pub mod imagedata;
 pub mod inputevent;
+pub mod intersectionobserver;
+pub mod intersectionobserverentry;
 pub mod keyboardevent;
 pub mod location;
 pub mod mediadevices;
@@ -401,7 +422,9 @@ pub mod mediaquerylist;
 pub mod mediaquerylistevent;
 pub mod mediastream;
 pub mod mediastreamtrack;
+pub mod messagechannel;
 pub mod messageevent;
+pub mod messageport;
 pub mod mimetype;
 pub mod mimetypearray;
 pub mod mouseevent;
@@ -446,7 +469,13 @@ pub mod promiserejectionevent;
 pub mod radionodelist;
 pub mod range;
 pub mod raredata;
+pub mod readablestream;
+pub mod readablestreamdefaultcontroller;
+pub mod readablestreamdefaultreader;
 pub mod request;
+pub mod resizeobserver;
+pub mod resizeobserverentry;
+pub mod resizeobserversize;
 pub mod response;
 pub mod rtcicecandidate;
 pub mod rtcpeerconnection;
@@ -454,6 +483,7 @@ pub mod rtcpeerconnectioniceevent;
 pub mod rtcsessiondescription;
 pub mod rtctrackevent;
 pub mod screen;
+pub mod securitypolicyviolationevent;
 pub mod serviceworker;
 pub mod serviceworkercontainer;
 pub mod serviceworkerglobalscope;
@@ -463,6 +493,7 @@ pub mod shadowroot;
 pub mod stereopannernode;
 pub mod storage;
 pub mod storageevent;
+pub mod streams;
 pub mod stylepropertymapreadonly;
 pub mod stylesheet;
 pub mod stylesheetlist;
@@ -489,6 +520,8 @@ pub mod touch;
 pub mod touchevent;
 pub mod touchlist;
 pub mod trackevent;
+pub mod transformstream;
+pub mod transformstreamdefaultcontroller;
 pub mod transitionevent;
 pub mod treewalker;
 pub mod uievent;
@@ -537,6 +570,9 @@ pub mod workerlocation;
 pub mod workernavigator;
 pub mod worklet;
 pub mod workletglobalscope;
+pub mod writablestream;
+pub mod writablestreamdefaultcontroller;
+pub mod writablestreamdefaultwriter;
 pub mod xmldocument;
 pub mod xmlhttprequest;
 pub mod xmlhttprequesteventtarget;
@@ -62,10 +62,6 @@ pub struct PerformanceResourceTiming {
 // TODO(#21256): redirect_start
 // TODO(#21257): redirect_end
 // TODO(#21258): fetch_start
-// TODO(#21259): domain_lookup_start
-// TODO(#21260): domain_lookup_end
-// TODO(#21261): connect_start
-// TODO(#21262): connect_end
 impl PerformanceResourceTiming {
     pub fn new_inherited(
         url: ServoUrl,
@@ -119,10 +115,10 @@ impl PerformanceResourceTiming {
             redirect_end: 0.,
             fetch_start: resource_timing.fetch_start as f64,
             domain_lookup_start: resource_timing.domain_lookup_start as f64,
-            domain_lookup_end: 0.,
+            domain_lookup_end: resource_timing.domain_lookup_end as f64,
             connect_start: resource_timing.connect_start as f64,
             connect_end: resource_timing.connect_end as f64,
-            secure_connection_start: 0.,
+            secure_connection_start: resource_timing.secure_connection_start as f64,
             request_start: resource_timing.request_start as f64,
             response_start: resource_timing.response_start as f64,
             response_end: resource_timing.response_end as f64,
@@ -0,0 +1,1136 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::enter_realm;
+use crate::dom::abortsignal::{AbortAlgorithm, AbortSignal};
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::codegen::Bindings::ReadableStreamBinding::{
+    self, ReadableStreamGetReaderOptions, ReadableStreamMethods, ReadableWritablePair,
+    StreamPipeOptions,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::bindings::trace::JSTraceable;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestreamdefaultcontroller::{
+    ReadableStreamDefaultController, UnderlyingSource,
+};
+use crate::dom::readablestreamdefaultreader::{ReadRequest, ReadableStreamDefaultReader};
+use crate::dom::streams::{check_no_type, error_to_jsval, extract_high_water_mark};
+use crate::dom::streams::{mark_as_handled, promise_rejected_with_error, react_to_promise};
+use crate::dom::streams::{rejected_promise, resolved_promise};
+use crate::dom::streams::{transform_to_undefined, wait_for_all};
+use crate::dom::streams::{Reaction, StartAlgorithm, UnderlyingMethod};
+use crate::dom::writablestream::{WritableStream, WritableStreamState};
+use crate::dom::writablestreamdefaultwriter::WritableStreamDefaultWriter;
+use dom_struct::dom_struct;
+use js::jsapi::{HandleValueArray, Heap, JSContext, JSObject, JS_NewArrayObject};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::{HandleValue, MutableHandleValue};
+use js::typedarray::{CreateWith, Uint8Array};
+use malloc_size_of::MallocSizeOf;
+use std::cell::Cell;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#readablestream-state>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum ReadableStreamState {
+    Readable,
+    Closed,
+    Errored,
+}
+
+/// <https://streams.spec.whatwg.org/#rs-class>
+#[dom_struct]
+pub struct ReadableStream {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#readablestream-controller>
+    controller: MutNullableDom<ReadableStreamDefaultController>,
+    /// <https://streams.spec.whatwg.org/#readablestream-disturbed>
+    disturbed: Cell<bool>,
+    /// <https://streams.spec.whatwg.org/#readablestream-reader>
+    reader: MutNullableDom<ReadableStreamDefaultReader>,
+    /// <https://streams.spec.whatwg.org/#readablestream-state>
+    state: Cell<ReadableStreamState>,
+    /// <https://streams.spec.whatwg.org/#readablestream-storederror>
+    #[ignore_malloc_size_of = "mozjs"]
+    stored_error: Heap<JSVal>,
+}
+
+impl ReadableStream {
+    fn new_inherited() -> ReadableStream {
+        ReadableStream {
+            reflector_: Reflector::new(),
+            controller: Default::default(),
+            disturbed: Cell::new(false),
+            reader: Default::default(),
+            state: Cell::new(ReadableStreamState::Readable),
+            stored_error: Heap::default(),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#initialize-readable-stream>
+    fn new(global: &GlobalScope) -> DomRoot<ReadableStream> {
+        reflect_dom_object(
+            Box::new(ReadableStream::new_inherited()),
+            global,
+            ReadableStreamBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#create-readable-stream>
+    #[allow(unrooted_must_root, unsafe_code)]
+    pub fn create(
+        global: &GlobalScope,
+        source: UnderlyingSource,
+        start: StartAlgorithm,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> DomRoot<ReadableStream> {
+        let stream = ReadableStream::new(global);
+        unsafe {
+            ReadableStreamDefaultController::set_up(&stream, source, start, high_water_mark, size)
+                .expect("Only the start method of an underlying source can throw");
+        }
+        stream
+    }
+
+    /// A stream of the given bytes, which is already closed once they are read.
+    pub fn new_from_bytes(global: &GlobalScope, bytes: Vec<u8>) -> DomRoot<ReadableStream> {
+        let stream = ReadableStream::create(
+            global,
+            UnderlyingSource::Native,
+            StartAlgorithm::Nothing,
+            1.,
+            None,
+        );
+        if !bytes.is_empty() {
+            stream.enqueue_native(bytes);
+        }
+        stream.close_native();
+        stream
+    }
+
+    // https://streams.spec.whatwg.org/#rs-constructor
+    #[allow(unsafe_code)]
+    pub unsafe fn Constructor(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        underlying_source: Option<*mut JSObject>,
+        strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<ReadableStream>> {
+        // Step 1.
+        rooted!(in(cx) let source = underlying_source.unwrap_or(ptr::null_mut()));
+        // Step 2.
+        let cancel = UnderlyingMethod::get(cx, source.handle(), "cancel")?;
+        let pull = UnderlyingMethod::get(cx, source.handle(), "pull")?;
+        let start = UnderlyingMethod::get(cx, source.handle(), "start")?;
+        // Step 4. Readable byte streams aren't supported.
+        check_no_type(cx, source.handle(), "type")?;
+        // Step 5.1-5.2.
+        let size = strategy.size.clone();
+        // Step 5.3.
+        let high_water_mark = extract_high_water_mark(strategy, 1.)?;
+        // Step 3.
+        let stream = ReadableStream::new(global);
+        // Step 5.4.
+        let start = match start {
+            Some(ref start) => StartAlgorithm::Method(start),
+            None => StartAlgorithm::Nothing,
+        };
+        ReadableStreamDefaultController::set_up(
+            &stream,
+            UnderlyingSource::Js { pull, cancel },
+            start,
+            high_water_mark,
+            size,
+        )?;
+        Ok(stream)
+    }
+
+    pub fn controller(&self) -> DomRoot<ReadableStreamDefaultController> {
+        self.controller
+            .get()
+            .expect("The controller of a stream is set up with it")
+    }
+
+    pub fn set_controller(&self, controller: &ReadableStreamDefaultController) {
+        self.controller.set(Some(controller));
+    }
+
+    pub fn reader(&self) -> Option<DomRoot<ReadableStreamDefaultReader>> {
+        self.reader.get()
+    }
+
+    pub fn set_reader(&self, reader: Option<&ReadableStreamDefaultReader>) {
+        self.reader.set(reader);
+    }
+
+    pub fn state(&self) -> ReadableStreamState {
+        self.state.get()
+    }
+
+    pub fn get_stored_error(&self, mut rval: MutableHandleValue) {
+        rval.set(self.stored_error.get());
+    }
+
+    pub fn is_disturbed(&self) -> bool {
+        self.disturbed.get()
+    }
+
+    pub fn set_disturbed(&self) {
+        self.disturbed.set(true);
+    }
+
+    /// <https://streams.spec.whatwg.org/#is-readable-stream-locked>
+    pub fn is_locked(&self) -> bool {
+        self.reader.get().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-get-num-read-requests>
+    pub fn num_read_requests(&self) -> usize {
+        self.reader
+            .get()
+            .map_or(0, |reader| reader.num_read_requests())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-add-read-request>
+    pub fn add_read_request(&self, read_request: Box<dyn ReadRequest>) {
+        self.reader
+            .get()
+            .expect("Only a locked stream has read requests")
+            .add_read_request(read_request);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-fulfill-read-request>
+    pub fn fulfill_read_request(&self, chunk: HandleValue, done: bool) {
+        // Step 2-4.
+        let read_request = match self.reader.get() {
+            Some(reader) => reader.take_first_read_request(),
+            None => None,
+        };
+        let read_request = read_request.expect("There is no read request to fulfill");
+        let cx = self.global().get_cx();
+        // Step 5-6.
+        if done {
+            read_request.close_steps(cx);
+        } else {
+            read_request.chunk_steps(cx, chunk);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-cancel>
+    pub fn cancel(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        // Step 1.
+        self.disturbed.set(true);
+        match self.state.get() {
+            // Step 2.
+            ReadableStreamState::Closed => return resolved_promise(&global),
+            // Step 3.
+            ReadableStreamState::Errored => {
+                rooted!(in(global.get_cx()) let mut error = UndefinedValue());
+                self.get_stored_error(error.handle_mut());
+                return rejected_promise(&global, error.handle());
+            },
+            ReadableStreamState::Readable => {},
+        }
+        // Step 4.
+        self.close();
+        // Step 7.
+        let source_cancel_promise = self.controller().cancel_steps(reason);
+        // Step 8.
+        transform_to_undefined(&source_cancel_promise)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-close>
+    pub fn close(&self) {
+        // Step 1-2.
+        assert_eq!(self.state.get(), ReadableStreamState::Readable);
+        self.state.set(ReadableStreamState::Closed);
+        // Step 3-4.
+        let reader = match self.reader.get() {
+            Some(reader) => reader,
+            None => return,
+        };
+        // Step 5.
+        reader.closed_promise().resolve_native(&());
+        // Step 6.
+        let cx = self.global().get_cx();
+        for read_request in reader.take_read_requests() {
+            read_request.close_steps(cx);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-error>
+    pub fn error(&self, e: HandleValue) {
+        // Step 1-3.
+        assert_eq!(self.state.get(), ReadableStreamState::Readable);
+        self.state.set(ReadableStreamState::Errored);
+        self.stored_error.set(e.get());
+        // Step 4-5.
+        let reader = match self.reader.get() {
+            Some(reader) => reader,
+            None => return,
+        };
+        // Step 6-7.
+        let closed_promise = reader.closed_promise();
+        closed_promise.reject_native(&e.get());
+        mark_as_handled(&closed_promise);
+        // Step 8.
+        let cx = self.global().get_cx();
+        for read_request in reader.take_read_requests() {
+            read_request.error_steps(cx, e);
+        }
+    }
+
+    /// Enqueues a chunk of bytes, as a `Uint8Array`, in a stream whose chunks
+    /// come from native code.
+    #[allow(unsafe_code)]
+    pub fn enqueue_native(&self, bytes: Vec<u8>) {
+        let controller = self.controller();
+        if !controller.can_close_or_enqueue() {
+            return;
+        }
+        let cx = self.global().get_cx();
+        let _ac = enter_realm(self);
+        rooted!(in(cx) let mut array = ptr::null_mut::<JSObject>());
+        rooted!(in(cx) let mut chunk = UndefinedValue());
+        unsafe {
+            assert!(Uint8Array::create(cx, CreateWith::Slice(&bytes), array.handle_mut()).is_ok());
+            chunk.set(ObjectValue(array.get()));
+            // There is no size algorithm that could throw.
+            let _ = controller.enqueue(cx, chunk.handle());
+        }
+    }
+
+    /// Closes a stream whose chunks come from native code.
+    pub fn close_native(&self) {
+        self.controller().close();
+    }
+
+    /// Errors a stream whose chunks come from native code.
+    #[allow(unsafe_code)]
+    pub fn error_native(&self, error: Error) {
+        let global = self.global();
+        let cx = global.get_cx();
+        let _ac = enter_realm(self);
+        rooted!(in(cx) let mut e = UndefinedValue());
+        unsafe { error_to_jsval(cx, &global, error, e.handle_mut()) };
+        self.controller().error(e.handle());
+    }
+
+    /// Reads all the chunks of the stream, which must all be `Uint8Array`s,
+    /// and gives their bytes to `consumer`.
+    ///
+    /// <https://fetch.spec.whatwg.org/#body-fully-read>
+    #[allow(unsafe_code)]
+    pub fn read_all_bytes(&self, consumer: Box<dyn BytesConsumer>) {
+        let global = self.global();
+        let reader = match ReadableStreamDefaultReader::acquire(self) {
+            Ok(reader) => reader,
+            Err(error) => {
+                let cx = global.get_cx();
+                let _ac = enter_realm(self);
+                rooted!(in(cx) let mut e = UndefinedValue());
+                unsafe { error_to_jsval(cx, &global, error, e.handle_mut()) };
+                return consumer.failure(e.handle());
+            },
+        };
+        let state = Rc::new(ReadAllBytes {
+            reader: Dom::from_ref(&*reader),
+            bytes: DomRefCell::new(vec![]),
+            consumer,
+            reading: Cell::new(false),
+            read_again: Cell::new(false),
+        });
+        ReadAllBytes::read_loop(&state);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-tee>
+    #[allow(unsafe_code)]
+    pub fn tee(&self) -> Fallible<(DomRoot<ReadableStream>, DomRoot<ReadableStream>)> {
+        let global = self.global();
+        // Step 3.
+        let reader = ReadableStreamDefaultReader::acquire(self)?;
+        // Step 4-11.
+        let state = Rc::new(TeeState {
+            stream: Dom::from_ref(self),
+            reader: Dom::from_ref(&*reader),
+            reading: Cell::new(false),
+            read_again: Cell::new(false),
+            canceled1: Cell::new(false),
+            canceled2: Cell::new(false),
+            reason1: Heap::default(),
+            reason2: Heap::default(),
+            branch1: Default::default(),
+            branch2: Default::default(),
+            cancel_promise: Promise::new(&global),
+        });
+        // Step 12-19.
+        let branch1 = ReadableStream::create(
+            &global,
+            UnderlyingSource::Tee(state.clone(), TeeBranch::First),
+            StartAlgorithm::Nothing,
+            1.,
+            None,
+        );
+        let branch2 = ReadableStream::create(
+            &global,
+            UnderlyingSource::Tee(state.clone(), TeeBranch::Second),
+            StartAlgorithm::Nothing,
+            1.,
+            None,
+        );
+        state.branch1.set(Some(&branch1));
+        state.branch2.set(Some(&branch2));
+        // Step 20.
+        react_to_promise(&reader.closed_promise(), Box::new(TeeReaderClosed(state)));
+        // Step 21.
+        Ok((branch1, branch2))
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-pipe-to>
+    #[allow(unrooted_must_root)]
+    pub fn pipe_to(
+        &self,
+        dest: &WritableStream,
+        prevent_close: bool,
+        prevent_abort: bool,
+        prevent_cancel: bool,
+        signal: Option<&AbortSignal>,
+    ) -> Rc<Promise> {
+        let global = self.global();
+        // Step 6.
+        self.disturbed.set(true);
+        // Step 7-8.
+        let reader = ReadableStreamDefaultReader::acquire(self).expect("The source is locked");
+        let writer = WritableStreamDefaultWriter::acquire(dest).expect("The dest is locked");
+        // Step 9-13.
+        let promise = Promise::new(&global);
+        let state = Rc::new(PipeState {
+            source: Dom::from_ref(self),
+            dest: Dom::from_ref(dest),
+            reader: Dom::from_ref(&*reader),
+            writer: Dom::from_ref(&*writer),
+            prevent_close,
+            prevent_abort,
+            prevent_cancel,
+            signal: signal.map(Dom::from_ref),
+            promise: promise.clone(),
+            shutting_down: Cell::new(false),
+            current_write: DomRefCell::new(None),
+            action: Cell::new(ShutdownAction::None),
+            error: Heap::default(),
+            has_error: Cell::new(false),
+        });
+        // Step 14.
+        if let Some(signal) = signal {
+            // Step 14.2.
+            if signal.aborted() {
+                PipeState::abort(&state);
+                return promise;
+            }
+            // Step 14.3.
+            signal.add_algorithm(AbortAlgorithm::Pipe(state.clone()));
+        }
+        // Step 15.
+        PipeState::start(&state);
+        // Step 16.
+        promise
+    }
+}
+
+impl ReadableStreamMethods for ReadableStream {
+    // https://streams.spec.whatwg.org/#rs-locked
+    fn Locked(&self) -> bool {
+        self.is_locked()
+    }
+
+    // https://streams.spec.whatwg.org/#rs-cancel
+    #[allow(unsafe_code)]
+    unsafe fn Cancel(&self, _cx: *mut JSContext, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+        // Step 2.
+        self.cancel(reason)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-get-reader
+    fn GetReader(
+        &self,
+        options: &ReadableStreamGetReaderOptions,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        // Step 1.
+        if options.mode.is_none() {
+            return ReadableStreamDefaultReader::acquire(self);
+        }
+        // Step 2-3. Only readable byte streams have BYOB readers.
+        Err(Error::Type(
+            "Only readable byte streams have BYOB readers".to_owned(),
+        ))
+    }
+
+    // https://streams.spec.whatwg.org/#rs-pipe-through
+    fn PipeThrough(
+        &self,
+        transform: &ReadableWritablePair,
+        options: &StreamPipeOptions,
+    ) -> Fallible<DomRoot<ReadableStream>> {
+        // Step 1.
+        if self.is_locked() {
+            return Err(Error::Type("The stream is locked".to_owned()));
+        }
+        // Step 2.
+        if transform.writable.is_locked() {
+            return Err(Error::Type("The writable stream is locked".to_owned()));
+        }
+        // Step 3-4.
+        let promise = self.pipe_to(
+            &transform.writable,
+            options.preventClose,
+            options.preventAbort,
+            options.preventCancel,
+            options.signal.as_ref().map(|signal| &**signal),
+        );
+        // Step 5.
+        mark_as_handled(&promise);
+        // Step 6.
+        Ok(DomRoot::from_ref(&*transform.readable))
+    }
+
+    // https://streams.spec.whatwg.org/#rs-pipe-to
+    fn PipeTo(&self, destination: &WritableStream, options: &StreamPipeOptions) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+        // Step 2.
+        if destination.is_locked() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The destination is locked".to_owned()),
+            );
+        }
+        // Step 3-4.
+        self.pipe_to(
+            destination,
+            options.preventClose,
+            options.preventAbort,
+            options.preventCancel,
+            options.signal.as_ref().map(|signal| &**signal),
+        )
+    }
+
+    // https://streams.spec.whatwg.org/#rs-tee
+    fn Tee(&self) -> Fallible<Vec<DomRoot<ReadableStream>>> {
+        let (branch1, branch2) = self.tee()?;
+        Ok(vec![branch1, branch2])
+    }
+}
+
+/// Receives the bytes read with `ReadableStream::read_all_bytes`.
+pub trait BytesConsumer: JSTraceable + MallocSizeOf {
+    fn success(&self, bytes: Vec<u8>);
+    fn failure(&self, error: HandleValue);
+}
+
+/// The read loop of `ReadableStream::read_all_bytes`.
+///
+/// <https://streams.spec.whatwg.org/#read-loop>
+#[derive(JSTraceable, MallocSizeOf)]
+#[allow_unrooted_in_rc]
+#[must_root]
+struct ReadAllBytes {
+    reader: Dom<ReadableStreamDefaultReader>,
+    bytes: DomRefCell<Vec<u8>>,
+    consumer: Box<dyn BytesConsumer>,
+    /// Whether `read_loop` is on the stack, as the chunks that are already
+    /// queued are read synchronously.
+    reading: Cell<bool>,
+    read_again: Cell<bool>,
+}
+
+impl ReadAllBytes {
+    fn read_loop(state: &Rc<ReadAllBytes>) {
+        if state.reading.get() {
+            return state.read_again.set(true);
+        }
+        state.reading.set(true);
+        loop {
+            state.read_again.set(false);
+            state
+                .reader
+                .read(Box::new(ReadAllBytesRequest(state.clone())));
+            if !state.read_again.get() {
+                break;
+            }
+        }
+        state.reading.set(false);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct ReadAllBytesRequest(#[ignore_malloc_size_of = "Rc"] Rc<ReadAllBytes>);
+
+impl ReadRequest for ReadAllBytesRequest {
+    #[allow(unsafe_code)]
+    fn chunk_steps(&self, cx: *mut JSContext, chunk: HandleValue) {
+        let state = &self.0;
+        // Step 1.
+        let appended = chunk.is_object() &&
+            unsafe {
+                typedarray!(in(cx) let array: Uint8Array = chunk.to_object());
+                array
+                    .map(|array| state.bytes.borrow_mut().extend_from_slice(array.as_slice()))
+                    .is_ok()
+            };
+        if !appended {
+            let global = state.reader.global();
+            let _ac = enter_realm(&*state.reader);
+            rooted!(in(cx) let mut e = UndefinedValue());
+            unsafe {
+                error_to_jsval(
+                    cx,
+                    &global,
+                    Error::Type("The chunks of a body must be Uint8Arrays".to_owned()),
+                    e.handle_mut(),
+                )
+            };
+            return state.consumer.failure(e.handle());
+        }
+        // Step 2-3.
+        ReadAllBytes::read_loop(state);
+    }
+
+    fn close_steps(&self, _cx: *mut JSContext) {
+        let bytes = mem::replace(&mut *self.0.bytes.borrow_mut(), vec![]);
+        self.0.consumer.success(bytes);
+    }
+
+    fn error_steps(&self, _cx: *mut JSContext, e: HandleValue) {
+        self.0.consumer.failure(e);
+    }
+}
+
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum TeeBranch {
+    First,
+    Second,
+}
+
+/// What the two branches of a teed stream share.
+///
+/// <https://streams.spec.whatwg.org/#readable-stream-default-tee>
+#[derive(JSTraceable, MallocSizeOf)]
+#[allow_unrooted_in_rc]
+#[must_root]
+pub struct TeeState {
+    stream: Dom<ReadableStream>,
+    reader: Dom<ReadableStreamDefaultReader>,
+    reading: Cell<bool>,
+    read_again: Cell<bool>,
+    canceled1: Cell<bool>,
+    canceled2: Cell<bool>,
+    #[ignore_malloc_size_of = "mozjs"]
+    reason1: Heap<JSVal>,
+    #[ignore_malloc_size_of = "mozjs"]
+    reason2: Heap<JSVal>,
+    branch1: MutNullableDom<ReadableStream>,
+    branch2: MutNullableDom<ReadableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    cancel_promise: Rc<Promise>,
+}
+
+impl TeeState {
+    /// Step 13 of <https://streams.spec.whatwg.org/#readable-stream-default-tee>.
+    pub fn pull(state: &Rc<TeeState>) -> Rc<Promise> {
+        let global = state.stream.global();
+        // Step 13.1.
+        if state.reading.get() {
+            state.read_again.set(true);
+            return resolved_promise(&global);
+        }
+        // Step 13.2.
+        state.reading.set(true);
+        // Step 13.3-13.4.
+        state.reader.read(Box::new(TeeReadRequest(state.clone())));
+        // Step 13.5.
+        resolved_promise(&global)
+    }
+
+    /// Step 14-15 of <https://streams.spec.whatwg.org/#readable-stream-default-tee>.
+    #[allow(unsafe_code)]
+    pub fn cancel(state: &Rc<TeeState>, branch: TeeBranch, reason: HandleValue) -> Rc<Promise> {
+        // Step 14.1-14.2.
+        let other_canceled = match branch {
+            TeeBranch::First => {
+                state.canceled1.set(true);
+                state.reason1.set(reason.get());
+                state.canceled2.get()
+            },
+            TeeBranch::Second => {
+                state.canceled2.set(true);
+                state.reason2.set(reason.get());
+                state.canceled1.get()
+            },
+        };
+        // Step 14.3.
+        if other_canceled {
+            let cx = state.stream.global().get_cx();
+            let _ac = enter_realm(&*state.stream);
+            // Step 14.3.1.
+            let reasons = [state.reason1.get(), state.reason2.get()];
+            rooted!(in(cx) let composite_reason = unsafe {
+                JS_NewArrayObject(cx, &HandleValueArray::from_rooted_slice(&reasons))
+            });
+            rooted!(in(cx) let composite_reason = ObjectValue(composite_reason.get()));
+            // Step 14.3.2.
+            let cancel_result = state.stream.cancel(composite_reason.handle());
+            // Step 14.3.3.
+            state.cancel_promise.resolve_native(&cancel_result);
+        }
+        // Step 14.4.
+        state.cancel_promise.clone()
+    }
+
+    fn branches(&self) -> Vec<DomRoot<ReadableStream>> {
+        let mut branches = vec![];
+        if !self.canceled1.get() {
+            branches.extend(self.branch1.get());
+        }
+        if !self.canceled2.get() {
+            branches.extend(self.branch2.get());
+        }
+        branches
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct TeeReadRequest(#[ignore_malloc_size_of = "Rc"] Rc<TeeState>);
+
+impl ReadRequest for TeeReadRequest {
+    fn chunk_steps(&self, _cx: *mut JSContext, chunk: HandleValue) {
+        // Step 13.3.1. Queue a microtask, as a reaction to a resolved promise.
+        let global = self.0.stream.global();
+        react_to_promise(
+            &resolved_promise(&global),
+            Box::new(TeeChunk {
+                state: self.0.clone(),
+                chunk: Heap::boxed(chunk.get()),
+            }),
+        );
+    }
+
+    fn close_steps(&self, _cx: *mut JSContext) {
+        let state = &self.0;
+        // Step 13.3.2.1.
+        state.reading.set(false);
+        // Step 13.3.2.2-13.3.2.3.
+        for branch in state.branches() {
+            branch.controller().close();
+        }
+        // Step 13.3.2.4.
+        if !state.canceled1.get() || !state.canceled2.get() {
+            state.cancel_promise.resolve_native(&());
+        }
+    }
+
+    fn error_steps(&self, _cx: *mut JSContext, _e: HandleValue) {
+        // Step 13.3.3.1.
+        self.0.reading.set(false);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct TeeChunk {
+    #[ignore_malloc_size_of = "Rc"]
+    state: Rc<TeeState>,
+    #[ignore_malloc_size_of = "mozjs"]
+    chunk: Box<Heap<JSVal>>,
+}
+
+impl Reaction for TeeChunk {
+    #[allow(unsafe_code)]
+    fn fulfilled(&self, cx: *mut JSContext, _value: HandleValue) {
+        let state = &self.state;
+        // Step 13.3.1.1.
+        state.read_again.set(false);
+        // Step 13.3.1.2-13.3.1.5.
+        rooted!(in(cx) let chunk = self.chunk.get());
+        for branch in state.branches() {
+            // The branches have no size algorithm that could throw.
+            let _ = unsafe { branch.controller().enqueue(cx, chunk.handle()) };
+        }
+        // Step 13.3.1.6.
+        state.reading.set(false);
+        // Step 13.3.1.7.
+        if state.read_again.get() {
+            TeeState::pull(state);
+        }
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, _reason: HandleValue) {}
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct TeeReaderClosed(#[ignore_malloc_size_of = "Rc"] Rc<TeeState>);
+
+impl Reaction for TeeReaderClosed {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {}
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        let state = &self.0;
+        // Step 20.1-20.2.
+        for branch in [&state.branch1, &state.branch2].iter() {
+            if let Some(branch) = branch.get() {
+                branch.controller().error(reason);
+            }
+        }
+        // Step 20.3.
+        if !state.canceled1.get() || !state.canceled2.get() {
+            state.cancel_promise.resolve_native(&());
+        }
+    }
+}
+
+/// What to do once a pipe shuts down, before it is finalized.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf, PartialEq)]
+enum ShutdownAction {
+    None,
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-close-with-error-propagation>
+    CloseDest,
+    /// <https://streams.spec.whatwg.org/#writable-stream-abort>
+    AbortDest,
+    /// <https://streams.spec.whatwg.org/#readable-stream-cancel>
+    CancelSource,
+    AbortDestAndCancelSource,
+}
+
+/// The state of an ongoing pipe.
+///
+/// <https://streams.spec.whatwg.org/#readable-stream-pipe-to>
+#[derive(JSTraceable, MallocSizeOf)]
+#[allow_unrooted_in_rc]
+#[must_root]
+pub struct PipeState {
+    source: Dom<ReadableStream>,
+    dest: Dom<WritableStream>,
+    reader: Dom<ReadableStreamDefaultReader>,
+    writer: Dom<WritableStreamDefaultWriter>,
+    prevent_close: bool,
+    prevent_abort: bool,
+    prevent_cancel: bool,
+    signal: Option<Dom<AbortSignal>>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    shutting_down: Cell<bool>,
+    /// The promise of the last write, which settles after all the others.
+    #[ignore_malloc_size_of = "Rc"]
+    current_write: DomRefCell<Option<Rc<Promise>>>,
+    /// The action of the shutdown, and the error the pipe is finalized with,
+    /// while the pending writes are waited for.
+    action: Cell<ShutdownAction>,
+    #[ignore_malloc_size_of = "mozjs"]
+    error: Heap<JSVal>,
+    has_error: Cell<bool>,
+}
+
+impl PipeState {
+    /// Step 15 of <https://streams.spec.whatwg.org/#readable-stream-pipe-to>.
+    fn start(state: &Rc<PipeState>) {
+        let cx = state.source.global().get_cx();
+        react_to_promise(
+            &state.reader.closed_promise(),
+            Box::new(PipeReaction {
+                state: state.clone(),
+                step: PipeStep::SourceClosed,
+            }),
+        );
+        react_to_promise(
+            &state.writer.closed_promise(),
+            Box::new(PipeReaction {
+                state: state.clone(),
+                step: PipeStep::DestClosed,
+            }),
+        );
+        rooted!(in(cx) let mut error = UndefinedValue());
+        // Errors must be propagated forward.
+        if state.source.state() == ReadableStreamState::Errored {
+            state.source.get_stored_error(error.handle_mut());
+            return PipeState::source_errored(state, error.handle());
+        }
+        // Errors must be propagated backward.
+        if state.dest.state() == WritableStreamState::Errored {
+            state.dest.get_stored_error(error.handle_mut());
+            return PipeState::dest_errored(state, error.handle());
+        }
+        // Closing must be propagated forward.
+        if state.source.state() == ReadableStreamState::Closed {
+            return PipeState::source_closed(state);
+        }
+        // Closing must be propagated backward.
+        if state.dest.close_queued_or_in_flight() ||
+            state.dest.state() == WritableStreamState::Closed
+        {
+            return PipeState::dest_closed(state);
+        }
+        PipeState::pipe_loop(state);
+    }
+
+    /// Reads a chunk and writes it once the writer is ready.
+    fn pipe_loop(state: &Rc<PipeState>) {
+        if state.shutting_down.get() {
+            return;
+        }
+        react_to_promise(
+            &state.writer.ready_promise(),
+            Box::new(PipeReaction {
+                state: state.clone(),
+                step: PipeStep::WriterReady,
+            }),
+        );
+    }
+
+    fn source_errored(state: &Rc<PipeState>, error: HandleValue) {
+        if !state.prevent_abort {
+            PipeState::shutdown(state, ShutdownAction::AbortDest, Some(error));
+        } else {
+            PipeState::shutdown(state, ShutdownAction::None, Some(error));
+        }
+    }
+
+    fn dest_errored(state: &Rc<PipeState>, error: HandleValue) {
+        if !state.prevent_cancel {
+            PipeState::shutdown(state, ShutdownAction::CancelSource, Some(error));
+        } else {
+            PipeState::shutdown(state, ShutdownAction::None, Some(error));
+        }
+    }
+
+    fn source_closed(state: &Rc<PipeState>) {
+        if !state.prevent_close {
+            PipeState::shutdown(state, ShutdownAction::CloseDest, None);
+        } else {
+            PipeState::shutdown(state, ShutdownAction::None, None);
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn dest_closed(state: &Rc<PipeState>) {
+        let global = state.source.global();
+        let cx = global.get_cx();
+        let _ac = enter_realm(&*state.source);
+        rooted!(in(cx) let mut dest_closed = UndefinedValue());
+        unsafe {
+            error_to_jsval(
+                cx,
+                &global,
+                Error::Type("The destination is closed".to_owned()),
+                dest_closed.handle_mut(),
+            )
+        };
+        if !state.prevent_cancel {
+            PipeState::shutdown(
+                state,
+                ShutdownAction::CancelSource,
+                Some(dest_closed.handle()),
+            );
+        } else {
+            PipeState::shutdown(state, ShutdownAction::None, Some(dest_closed.handle()));
+        }
+    }
+
+    /// Step 14.1 of <https://streams.spec.whatwg.org/#readable-stream-pipe-to>.
+    #[allow(unsafe_code)]
+    pub fn abort(state: &Rc<PipeState>) {
+        let global = state.source.global();
+        let cx = global.get_cx();
+        let _ac = enter_realm(&*state.source);
+        // Step 14.1.1.
+        rooted!(in(cx) let mut error = UndefinedValue());
+        unsafe { error_to_jsval(cx, &global, Error::Abort, error.handle_mut()) };
+        // Step 14.1.2-14.1.4.
+        let abort_dest =
+            !state.prevent_abort && state.dest.state() == WritableStreamState::Writable;
+        let cancel_source =
+            !state.prevent_cancel && state.source.state() == ReadableStreamState::Readable;
+        let action = match (abort_dest, cancel_source) {
+            (true, true) => ShutdownAction::AbortDestAndCancelSource,
+            (true, false) => ShutdownAction::AbortDest,
+            (false, true) => ShutdownAction::CancelSource,
+            (false, false) => ShutdownAction::None,
+        };
+        // Step 14.1.5.
+        PipeState::shutdown(state, action, Some(error.handle()));
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-pipeTo-shutdown-with-action>, and
+    /// <https://streams.spec.whatwg.org/#rs-pipeTo-shutdown> without an action.
+    fn shutdown(state: &Rc<PipeState>, action: ShutdownAction, error: Option<HandleValue>) {
+        // Step 1.
+        if state.shutting_down.get() {
+            return;
+        }
+        // Step 2.
+        state.shutting_down.set(true);
+        state.action.set(action);
+        if let Some(error) = error {
+            state.error.set(error.get());
+            state.has_error.set(true);
+        }
+        // Step 3.
+        if state.dest.state() == WritableStreamState::Writable &&
+            !state.dest.close_queued_or_in_flight()
+        {
+            let current_write = state.current_write.borrow().clone();
+            if let Some(current_write) = current_write {
+                return react_to_promise(
+                    &current_write,
+                    Box::new(PipeReaction {
+                        state: state.clone(),
+                        step: PipeStep::WritesFinished,
+                    }),
+                );
+            }
+        }
+        PipeState::perform_action(state);
+    }
+
+    /// Step 4-6 of <https://streams.spec.whatwg.org/#rs-pipeTo-shutdown-with-action>.
+    fn perform_action(state: &Rc<PipeState>) {
+        let global = state.source.global();
+        let cx = global.get_cx();
+        rooted!(in(cx) let error = state.error.get());
+        let promise = match state.action.get() {
+            ShutdownAction::None => return PipeState::finalize(state),
+            ShutdownAction::CloseDest => state.writer.close_with_error_propagation(),
+            ShutdownAction::AbortDest => state.dest.abort(error.handle()),
+            ShutdownAction::CancelSource => state.source.cancel(error.handle()),
+            ShutdownAction::AbortDestAndCancelSource => {
+                let actions = [
+                    state.dest.abort(error.handle()),
+                    state.source.cancel(error.handle()),
+                ];
+                wait_for_all(&global, &actions)
+            },
+        };
+        react_to_promise(
+            &promise,
+            Box::new(PipeReaction {
+                state: state.clone(),
+                step: PipeStep::ActionDone,
+            }),
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-pipeTo-finalize>
+    fn finalize(state: &Rc<PipeState>) {
+        // Step 1.
+        state.writer.release();
+        // Step 2.
+        state.reader.release();
+        // Step 3.
+        if let Some(ref signal) = state.signal {
+            signal.remove_algorithm(|algorithm| match *algorithm {
+                AbortAlgorithm::Pipe(ref pipe) => Rc::ptr_eq(pipe, state),
+                _ => false,
+            });
+        }
+        // Step 4-5.
+        if state.has_error.get() {
+            state.promise.reject_native(&state.error.get());
+        } else {
+            state.promise.resolve_native(&());
+        }
+    }
+}
+
+/// The steps of a pipe that wait for a promise.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf)]
+enum PipeStep {
+    /// The closed promise of the reader settled.
+    SourceClosed,
+    /// The closed promise of the writer settled.
+    DestClosed,
+    /// The writer is ready for the next chunk.
+    WriterReady,
+    /// The last write settled, after a shutdown.
+    WritesFinished,
+    /// The action of a shutdown is done.
+    ActionDone,
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct PipeReaction {
+    #[ignore_malloc_size_of = "Rc"]
+    state: Rc<PipeState>,
+    step: PipeStep,
+}
+
+impl Reaction for PipeReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        let state = &self.state;
+        match self.step {
+            PipeStep::SourceClosed => PipeState::source_closed(state),
+            PipeStep::DestClosed => {},
+            PipeStep::WriterReady => {
+                if !state.shutting_down.get() {
+                    state.reader.read(Box::new(PipeReadRequest(state.clone())));
+                }
+            },
+            PipeStep::WritesFinished => PipeState::perform_action(state),
+            PipeStep::ActionDone => PipeState::finalize(state),
+        }
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        let state = &self.state;
+        match self.step {
+            PipeStep::SourceClosed => PipeState::source_errored(state, reason),
+            PipeStep::DestClosed => PipeState::dest_errored(state, reason),
+            // The closed promise of the writer is rejected as well.
+            PipeStep::WriterReady => {},
+            PipeStep::WritesFinished => PipeState::perform_action(state),
+            PipeStep::ActionDone => {
+                state.error.set(reason.get());
+                state.has_error.set(true);
+                PipeState::finalize(state);
+            },
+        }
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct PipeReadRequest(#[ignore_malloc_size_of = "Rc"] Rc<PipeState>);
+
+impl ReadRequest for PipeReadRequest {
+    fn chunk_steps(&self, cx: *mut JSContext, chunk: HandleValue) {
+        let state = &self.0;
+        let write = state.writer.write(cx, chunk);
+        mark_as_handled(&write);
+        *state.current_write.borrow_mut() = Some(write);
+        PipeState::pipe_loop(state);
+    }
+
+    // The closed promise of the reader takes care of these.
+    fn close_steps(&self, _cx: *mut JSContext) {}
+
+    fn error_steps(&self, _cx: *mut JSContext, _e: HandleValue) {}
+}
@@ -0,0 +1,404 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategySize;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultControllerBinding::{
+    self, ReadableStreamDefaultControllerMethods,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState, TeeBranch, TeeState};
+use crate::dom::readablestreamdefaultreader::ReadRequest;
+use crate::dom::response::Response;
+use crate::dom::streams::{chunk_size, object_value, promise_call_or_noop, react_to_promise};
+use crate::dom::streams::{resolved_promise, take_pending_exception, throw_value};
+use crate::dom::streams::{QueueWithSizes, Reaction, StartAlgorithm, UnderlyingMethod};
+use crate::dom::transformstream::TransformStream;
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Where the chunks of a readable stream come from, with the pull and cancel
+/// algorithms of its controller.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub enum UnderlyingSource {
+    /// An underlying source object given to the constructor.
+    Js {
+        pull: Option<UnderlyingMethod>,
+        cancel: Option<UnderlyingMethod>,
+    },
+    /// The body of the response of a fetch, whose chunks are enqueued as they
+    /// are received.
+    Fetch(Dom<Response>),
+    /// A branch of a teed stream.
+    Tee(#[ignore_malloc_size_of = "Rc"] Rc<TeeState>, TeeBranch),
+    /// The readable side of a transform stream.
+    Transform(Dom<TransformStream>),
+    /// A source whose chunks were all enqueued when the stream was created.
+    Native,
+}
+
+/// <https://streams.spec.whatwg.org/#rs-default-controller-class>
+#[dom_struct]
+pub struct ReadableStreamDefaultController {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#readablestreamdefaultcontroller-stream>
+    stream: Dom<ReadableStream>,
+    source: UnderlyingSource,
+    queue: DomRefCell<QueueWithSizes>,
+    started: Cell<bool>,
+    close_requested: Cell<bool>,
+    pull_again: Cell<bool>,
+    pulling: Cell<bool>,
+    strategy_hwm: f64,
+    #[ignore_malloc_size_of = "Rc"]
+    strategy_size: Option<Rc<QueuingStrategySize>>,
+}
+
+impl ReadableStreamDefaultController {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> ReadableStreamDefaultController {
+        ReadableStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            source,
+            queue: Default::default(),
+            started: Cell::new(false),
+            close_requested: Cell::new(false),
+            pull_again: Cell::new(false),
+            pulling: Cell::new(false),
+            strategy_hwm: high_water_mark,
+            strategy_size: size,
+        }
+    }
+
+    #[allow(unrooted_must_root)]
+    fn new(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> DomRoot<ReadableStreamDefaultController> {
+        reflect_dom_object(
+            Box::new(ReadableStreamDefaultController::new_inherited(
+                stream,
+                source,
+                high_water_mark,
+                size,
+            )),
+            global,
+            ReadableStreamDefaultControllerBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-stream-default-controller>
+    ///
+    /// The exception stays pending if the start algorithm throws.
+    #[allow(unrooted_must_root, unsafe_code)]
+    pub unsafe fn set_up(
+        stream: &ReadableStream,
+        source: UnderlyingSource,
+        start: StartAlgorithm,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> Fallible<()> {
+        let global = stream.global();
+        let cx = global.get_cx();
+        // Step 1-6.
+        let controller =
+            ReadableStreamDefaultController::new(&global, stream, source, high_water_mark, size);
+        // Step 7.
+        stream.set_controller(&controller);
+        // Step 8-9.
+        let start_promise = start.run(&global, cx, object_value(&*controller))?;
+        // Step 10-11.
+        react_to_promise(
+            &start_promise,
+            Box::new(StartReaction {
+                controller: Dom::from_ref(&*controller),
+            }),
+        );
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-call-pull-if-needed>
+    fn call_pull_if_needed(&self) {
+        // Step 1-2.
+        if !self.should_call_pull() {
+            return;
+        }
+        // Step 3.
+        if self.pulling.get() {
+            self.pull_again.set(true);
+            return;
+        }
+        // Step 4.
+        self.pulling.set(true);
+        // Step 5.
+        let pull_promise = self.pull_algorithm();
+        // Step 6-7.
+        react_to_promise(
+            &pull_promise,
+            Box::new(PullReaction {
+                controller: Dom::from_ref(self),
+            }),
+        );
+    }
+
+    #[allow(unsafe_code)]
+    fn pull_algorithm(&self) -> Rc<Promise> {
+        let global = self.global();
+        match self.source {
+            UnderlyingSource::Js { ref pull, .. } => unsafe {
+                promise_call_or_noop(pull, &global, global.get_cx(), &[object_value(self)])
+            },
+            UnderlyingSource::Tee(ref state, _) => TeeState::pull(state),
+            UnderlyingSource::Transform(ref stream) => stream.source_pull(),
+            UnderlyingSource::Fetch(_) | UnderlyingSource::Native => resolved_promise(&global),
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn cancel_algorithm(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        match self.source {
+            UnderlyingSource::Js { ref cancel, .. } => unsafe {
+                promise_call_or_noop(cancel, &global, global.get_cx(), &[reason.get()])
+            },
+            UnderlyingSource::Fetch(ref response) => {
+                response.cancel_body();
+                resolved_promise(&global)
+            },
+            UnderlyingSource::Tee(ref state, branch) => TeeState::cancel(state, branch, reason),
+            UnderlyingSource::Transform(ref stream) => {
+                stream.error_writable_and_unblock_write(reason);
+                resolved_promise(&global)
+            },
+            UnderlyingSource::Native => resolved_promise(&global),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-should-call-pull>
+    fn should_call_pull(&self) -> bool {
+        // Step 1-2.
+        if !self.can_close_or_enqueue() {
+            return false;
+        }
+        // Step 3.
+        if !self.started.get() {
+            return false;
+        }
+        // Step 4.
+        if self.stream.is_locked() && self.stream.num_read_requests() > 0 {
+            return true;
+        }
+        // Step 5-8.
+        self.get_desired_size().map_or(false, |size| size > 0.)
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-close>
+    pub fn close(&self) {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return;
+        }
+        // Step 2-3.
+        self.close_requested.set(true);
+        // Step 4.
+        if self.queue.borrow().is_empty() {
+            self.stream.close();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-enqueue>
+    ///
+    /// If the size algorithm throws, the stream is errored and the exception stays
+    /// pending.
+    #[allow(unsafe_code)]
+    pub unsafe fn enqueue(&self, cx: *mut JSContext, chunk: HandleValue) -> Fallible<()> {
+        // Step 1-2.
+        if !self.can_close_or_enqueue() {
+            return Ok(());
+        }
+        // Step 3.
+        if self.stream.is_locked() && self.stream.num_read_requests() > 0 {
+            self.stream.fulfill_read_request(chunk, false);
+        } else {
+            // Step 4.1-4.2.
+            let result = chunk_size(&self.strategy_size, chunk)
+                .and_then(|size| self.queue.borrow_mut().enqueue_value_with_size(chunk, size));
+            if let Err(error) = result {
+                rooted!(in(cx) let mut reason = UndefinedValue());
+                match error {
+                    Error::JSFailed => take_pending_exception(cx, reason.handle_mut()),
+                    error => error.to_jsval(cx, &self.global(), reason.handle_mut()),
+                }
+                self.error(reason.handle());
+                return Err(throw_value(cx, reason.handle()));
+            }
+        }
+        // Step 5.
+        self.call_pull_if_needed();
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-error>
+    pub fn error(&self, e: HandleValue) {
+        // Step 1-2.
+        if self.stream.state() != ReadableStreamState::Readable {
+            return;
+        }
+        // Step 3.
+        self.queue.borrow_mut().reset();
+        // Step 4-5.
+        self.stream.error(e);
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-get-desired-size>
+    pub fn get_desired_size(&self) -> Option<f64> {
+        match self.stream.state() {
+            // Step 2-3.
+            ReadableStreamState::Errored => None,
+            ReadableStreamState::Closed => Some(0.),
+            // Step 4.
+            ReadableStreamState::Readable => {
+                Some(self.strategy_hwm - self.queue.borrow().total_size())
+            },
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-has-backpressure>
+    pub fn has_backpressure(&self) -> bool {
+        !self.should_call_pull()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-controller-can-close-or-enqueue>
+    pub fn can_close_or_enqueue(&self) -> bool {
+        !self.close_requested.get() && self.stream.state() == ReadableStreamState::Readable
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-private-cancel>
+    pub fn cancel_steps(&self, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        self.queue.borrow_mut().reset();
+        // Step 2-4.
+        self.cancel_algorithm(reason)
+    }
+
+    /// <https://streams.spec.whatwg.org/#rs-default-controller-private-pull>
+    pub fn pull_steps(&self, read_request: Box<dyn ReadRequest>) {
+        let cx = self.global().get_cx();
+        // Step 2.
+        if !self.queue.borrow().is_empty() {
+            // Step 2.1.
+            rooted!(in(cx) let mut chunk = UndefinedValue());
+            self.queue.borrow_mut().dequeue_value(chunk.handle_mut());
+            // Step 2.2.
+            if self.close_requested.get() && self.queue.borrow().is_empty() {
+                self.stream.close();
+            } else {
+                // Step 2.3.
+                self.call_pull_if_needed();
+            }
+            // Step 2.4.
+            return read_request.chunk_steps(cx, chunk.handle());
+        }
+        // Step 3.1.
+        self.stream.add_read_request(read_request);
+        // Step 3.2.
+        self.call_pull_if_needed();
+    }
+}
+
+impl ReadableStreamDefaultControllerMethods for ReadableStreamDefaultController {
+    // https://streams.spec.whatwg.org/#rs-default-controller-desired-size
+    fn GetDesiredSize(&self) -> Option<f64> {
+        self.get_desired_size()
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-close
+    fn Close(&self) -> Fallible<()> {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type("The stream cannot be closed".to_owned()));
+        }
+        // Step 2.
+        self.close();
+        Ok(())
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-enqueue
+    #[allow(unsafe_code)]
+    unsafe fn Enqueue(&self, cx: *mut JSContext, chunk: HandleValue) -> Fallible<()> {
+        // Step 1.
+        if !self.can_close_or_enqueue() {
+            return Err(Error::Type("Chunks cannot be enqueued".to_owned()));
+        }
+        // Step 2.
+        self.enqueue(cx, chunk)
+    }
+
+    // https://streams.spec.whatwg.org/#rs-default-controller-error
+    #[allow(unsafe_code)]
+    unsafe fn Error(&self, _cx: *mut JSContext, e: HandleValue) {
+        self.error(e);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct StartReaction {
+    controller: Dom<ReadableStreamDefaultController>,
+}
+
+impl Reaction for StartReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        // Step 10.1-10.3.
+        self.controller.started.set(true);
+        // Step 10.4.
+        self.controller.call_pull_if_needed();
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 11.1.
+        self.controller.error(reason);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct PullReaction {
+    controller: Dom<ReadableStreamDefaultController>,
+}
+
+impl Reaction for PullReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        // Step 6.1.
+        self.controller.pulling.set(false);
+        // Step 6.2.
+        if self.controller.pull_again.get() {
+            self.controller.pull_again.set(false);
+            self.controller.call_pull_if_needed();
+        }
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 7.1.
+        self.controller.error(reason);
+    }
+}
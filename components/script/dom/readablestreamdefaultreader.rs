@@ -0,0 +1,253 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ReadableStreamDefaultReaderBinding::{
+    self, ReadableStreamDefaultReaderMethods, ReadableStreamReadResult,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::trace::JSTraceable;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState};
+use crate::dom::streams::{mark_as_handled, promise_rejected_with_error};
+use crate::dom::streams::{rejected_promise, resolved_promise};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use malloc_size_of::MallocSizeOf;
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#read-request>
+pub trait ReadRequest: JSTraceable + MallocSizeOf {
+    fn chunk_steps(&self, cx: *mut JSContext, chunk: HandleValue);
+    fn close_steps(&self, cx: *mut JSContext);
+    fn error_steps(&self, cx: *mut JSContext, e: HandleValue);
+}
+
+/// <https://streams.spec.whatwg.org/#default-reader-class>
+#[dom_struct]
+pub struct ReadableStreamDefaultReader {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#readablestreamgenericreader-stream>
+    stream: MutNullableDom<ReadableStream>,
+    /// <https://streams.spec.whatwg.org/#readablestreamgenericreader-closedpromise>
+    #[ignore_malloc_size_of = "Rc"]
+    closed_promise: DomRefCell<Rc<Promise>>,
+    /// <https://streams.spec.whatwg.org/#readablestreamdefaultreader-readrequests>
+    read_requests: DomRefCell<VecDeque<Box<dyn ReadRequest>>>,
+}
+
+impl ReadableStreamDefaultReader {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &ReadableStream,
+        closed_promise: Rc<Promise>,
+    ) -> ReadableStreamDefaultReader {
+        ReadableStreamDefaultReader {
+            reflector_: Reflector::new(),
+            stream: MutNullableDom::new(Some(stream)),
+            closed_promise: DomRefCell::new(closed_promise),
+            read_requests: Default::default(),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-readable-stream-default-reader>
+    fn set_up(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        // Step 1.
+        if stream.is_locked() {
+            return Err(Error::Type("The stream is already locked".to_owned()));
+        }
+        // Step 2, https://streams.spec.whatwg.org/#readable-stream-reader-generic-initialize
+        let closed_promise = match stream.state() {
+            // Step 4.
+            ReadableStreamState::Readable => Promise::new(global),
+            // Step 5.
+            ReadableStreamState::Closed => resolved_promise(global),
+            // Step 6.
+            ReadableStreamState::Errored => {
+                rooted!(in(global.get_cx()) let mut error = UndefinedValue());
+                stream.get_stored_error(error.handle_mut());
+                let promise = rejected_promise(global, error.handle());
+                mark_as_handled(&promise);
+                promise
+            },
+        };
+        let reader = reflect_dom_object(
+            Box::new(ReadableStreamDefaultReader::new_inherited(
+                stream,
+                closed_promise,
+            )),
+            global,
+            ReadableStreamDefaultReaderBinding::Wrap,
+        );
+        // Step 3.
+        stream.set_reader(Some(&reader));
+        Ok(reader)
+    }
+
+    /// <https://streams.spec.whatwg.org/#acquire-readable-stream-reader>
+    pub fn acquire(stream: &ReadableStream) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        ReadableStreamDefaultReader::set_up(&stream.global(), stream)
+    }
+
+    // https://streams.spec.whatwg.org/#default-reader-constructor
+    pub fn Constructor(
+        global: &GlobalScope,
+        stream: &ReadableStream,
+    ) -> Fallible<DomRoot<ReadableStreamDefaultReader>> {
+        ReadableStreamDefaultReader::set_up(global, stream)
+    }
+
+    pub fn closed_promise(&self) -> Rc<Promise> {
+        self.closed_promise.borrow().clone()
+    }
+
+    pub fn add_read_request(&self, read_request: Box<dyn ReadRequest>) {
+        self.read_requests.borrow_mut().push_back(read_request);
+    }
+
+    pub fn take_first_read_request(&self) -> Option<Box<dyn ReadRequest>> {
+        self.read_requests.borrow_mut().pop_front()
+    }
+
+    pub fn take_read_requests(&self) -> VecDeque<Box<dyn ReadRequest>> {
+        mem::replace(&mut *self.read_requests.borrow_mut(), VecDeque::new())
+    }
+
+    pub fn num_read_requests(&self) -> usize {
+        self.read_requests.borrow().len()
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-default-reader-read>
+    pub fn read(&self, read_request: Box<dyn ReadRequest>) {
+        let cx = self.global().get_cx();
+        // Step 1.
+        let stream = self.stream.get().expect("The reader was released");
+        // Step 2.
+        stream.set_disturbed();
+        match stream.state() {
+            // Step 3.
+            ReadableStreamState::Closed => read_request.close_steps(cx),
+            // Step 4.
+            ReadableStreamState::Errored => {
+                rooted!(in(cx) let mut error = UndefinedValue());
+                stream.get_stored_error(error.handle_mut());
+                read_request.error_steps(cx, error.handle());
+            },
+            // Step 5.
+            ReadableStreamState::Readable => stream.controller().pull_steps(read_request),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#readable-stream-reader-generic-release>
+    pub fn release(&self) {
+        // Step 1-2.
+        let stream = self.stream.get().expect("The reader was released");
+        let error = Error::Type("The reader was released".to_owned());
+        // Step 3-4.
+        let closed_promise = self.closed_promise();
+        if stream.state() == ReadableStreamState::Readable && !closed_promise.is_fulfilled() {
+            closed_promise.reject_error(error);
+        } else {
+            *self.closed_promise.borrow_mut() = promise_rejected_with_error(&self.global(), error);
+        }
+        // Step 5.
+        mark_as_handled(&self.closed_promise());
+        // Step 6-7.
+        stream.set_reader(None);
+        self.stream.set(None);
+    }
+}
+
+impl ReadableStreamDefaultReaderMethods for ReadableStreamDefaultReader {
+    // https://streams.spec.whatwg.org/#generic-reader-closed
+    fn Closed(&self) -> Rc<Promise> {
+        self.closed_promise()
+    }
+
+    // https://streams.spec.whatwg.org/#generic-reader-cancel
+    #[allow(unsafe_code)]
+    unsafe fn Cancel(&self, _cx: *mut JSContext, reason: HandleValue) -> Rc<Promise> {
+        match self.stream.get() {
+            // Step 1.
+            None => promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The reader was released".to_owned()),
+            ),
+            // Step 2.
+            Some(stream) => stream.cancel(reason),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#default-reader-read
+    fn Read(&self) -> Rc<Promise> {
+        // Step 1.
+        if self.stream.get().is_none() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The reader was released".to_owned()),
+            );
+        }
+        // Step 2.
+        let promise = Promise::new(&self.global());
+        // Step 3-4.
+        self.read(Box::new(PromiseReadRequest {
+            promise: promise.clone(),
+        }));
+        // Step 5.
+        promise
+    }
+
+    // https://streams.spec.whatwg.org/#default-reader-release-lock
+    fn ReleaseLock(&self) -> Fallible<()> {
+        // Step 1.
+        if self.stream.get().is_none() {
+            return Ok(());
+        }
+        // Step 2.
+        if self.num_read_requests() > 0 {
+            return Err(Error::Type(
+                "The reader cannot be released while reads are pending".to_owned(),
+            ));
+        }
+        // Step 3.
+        self.release();
+        Ok(())
+    }
+}
+
+/// The read request of `read()`, which settles a promise with the result.
+#[derive(JSTraceable, MallocSizeOf)]
+struct PromiseReadRequest {
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl ReadRequest for PromiseReadRequest {
+    fn chunk_steps(&self, _cx: *mut JSContext, chunk: HandleValue) {
+        let mut result = ReadableStreamReadResult::empty();
+        result.value.set(chunk.get());
+        result.done = Some(false);
+        self.promise.resolve_native(&result);
+    }
+
+    fn close_steps(&self, _cx: *mut JSContext) {
+        let mut result = ReadableStreamReadResult::empty();
+        result.done = Some(true);
+        self.promise.resolve_native(&result);
+    }
+
+    fn error_steps(&self, _cx: *mut JSContext, e: HandleValue) {
+        self.promise.reject_native(&e.get());
+    }
+}
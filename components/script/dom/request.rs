@@ -2,7 +2,9 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::body::{consume_body, BodyOperations, BodyType};
+use crate::body::{consume_body, consume_body_with_promise, extract_body, BodyOperations};
+use crate::body::{BodyType, ExtractedBody};
+use crate::dom::abortsignal::AbortSignal;
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::HeadersBinding::{HeadersInit, HeadersMethods};
 use crate::dom::bindings::codegen::Bindings::RequestBinding;
@@ -17,16 +19,18 @@ use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestMode;
 use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestRedirect;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
-use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::str::{ByteString, DOMString, USVString};
 use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::headers::{Guard, Headers};
 use crate::dom::promise::Promise;
-use crate::dom::xmlhttprequest::Extractable;
+use crate::dom::readablestream::{BytesConsumer, ReadableStream};
 use dom_struct::dom_struct;
 use http::method::InvalidMethod;
 use http::Method as HttpMethod;
+use js::rust::HandleValue;
+use net_traits::cache_storage_thread::CachedRequest;
 use net_traits::request::CacheMode as NetTraitsRequestCache;
 use net_traits::request::CredentialsMode as NetTraitsRequestCredentials;
 use net_traits::request::Destination as NetTraitsRequestDestination;
@@ -50,10 +54,17 @@ pub struct Request {
     mime_type: DomRefCell<Vec<u8>>,
     #[ignore_malloc_size_of = "Rc"]
     body_promise: DomRefCell<Option<(Rc<Promise>, BodyType)>>,
+    /// <https://fetch.spec.whatwg.org/#request-signal>
+    signal: Dom<AbortSignal>,
+    /// <https://fetch.spec.whatwg.org/#concept-body-stream>
+    ///
+    /// Only created once script asks for it, unless the body was given as a
+    /// stream. Its bytes are all read before the request is sent.
+    body_stream: MutNullableDom<ReadableStream>,
 }
 
 impl Request {
-    fn new_inherited(global: &GlobalScope, url: ServoUrl) -> Request {
+    fn new_inherited(global: &GlobalScope, url: ServoUrl, signal: &AbortSignal) -> Request {
         Request {
             reflector_: Reflector::new(),
             request: DomRefCell::new(net_request_from_global(global, url)),
@@ -61,12 +72,15 @@ impl Request {
             headers: Default::default(),
             mime_type: DomRefCell::new("".to_string().into_bytes()),
             body_promise: DomRefCell::new(None),
+            signal: Dom::from_ref(signal),
+            body_stream: Default::default(),
         }
     }
 
     pub fn new(global: &GlobalScope, url: ServoUrl) -> DomRoot<Request> {
+        let signal = AbortSignal::new(global);
         reflect_dom_object(
-            Box::new(Request::new_inherited(global, url)),
+            Box::new(Request::new_inherited(global, url, &signal)),
             global,
             RequestBinding::Wrap,
         )
@@ -297,6 +311,18 @@ impl Request {
         let r = Request::from_net_request(global, request);
         r.headers.or_init(|| Headers::for_request(&r.global()));
 
+        // The signal of the request follows the one of `init`, or else the one of `input`.
+        let signal = match init.signal {
+            Some(ref signal) => signal.as_ref().map(|signal| DomRoot::from_ref(&**signal)),
+            None => match input {
+                RequestInfo::Request(ref input_request) => Some(input_request.Signal()),
+                RequestInfo::USVString(_) => None,
+            },
+        };
+        if let Some(signal) = signal {
+            r.signal.follow(&signal);
+        }
+
         // Step 27
         let mut headers_copy = r.Headers();
 
@@ -363,16 +389,20 @@ impl Request {
         r.request.borrow_mut().headers = r.Headers().get_headers_list();
 
         // Step 32
-        let mut input_body = if let RequestInfo::Request(ref input_request) = input {
-            let input_request_request = input_request.request.borrow();
-            input_request_request.body.clone()
-        } else {
-            None
-        };
+        let (mut input_body, mut input_stream) =
+            if let RequestInfo::Request(ref input_request) = input {
+                let input_request_request = input_request.request.borrow();
+                (
+                    input_request_request.body.clone(),
+                    input_request.body_stream.get(),
+                )
+            } else {
+                (None, None)
+            };
 
         // Step 33
         if let Some(init_body_option) = init.body.as_ref() {
-            if init_body_option.is_some() || input_body.is_some() {
+            if init_body_option.is_some() || input_body.is_some() || input_stream.is_some() {
                 let req = r.request.borrow();
                 let req_method = &req.method;
                 match *req_method {
@@ -394,9 +424,17 @@ impl Request {
         // Step 34
         if let Some(Some(ref init_body)) = init.body {
             // Step 34.2
-            let extracted_body_tmp = init_body.extract();
-            input_body = Some(extracted_body_tmp.0);
-            let content_type = extracted_body_tmp.1;
+            let (extracted_body, content_type) = extract_body(init_body)?;
+            match extracted_body {
+                ExtractedBody::Bytes(bytes) => {
+                    input_body = Some(bytes);
+                    input_stream = None;
+                },
+                ExtractedBody::Stream(stream) => {
+                    input_body = None;
+                    input_stream = Some(stream);
+                },
+            }
 
             // Step 34.3
             if let Some(contents) = content_type {
@@ -415,13 +453,22 @@ impl Request {
 
         // Step 35
         r.request.borrow_mut().body = input_body;
+        r.body_stream
+            .set(input_stream.as_ref().map(|stream| &**stream));
 
         // Step 36
         let extracted_mime_type = r.Headers().extract_mime_type();
         *r.mime_type.borrow_mut() = extracted_mime_type;
 
         // Step 37
-        // TODO: `ReadableStream` object is not implemented in Servo yet.
+        // The stream of the body of the input moves to the new request, which
+        // leaves the input disturbed.
+        if let RequestInfo::Request(ref input_request) = input {
+            if init.body.is_none() && input_request.body_stream.get().is_some() {
+                input_request.body_stream.set(None);
+                input_request.body_used.set(true);
+            }
+        }
 
         // Step 38
         Ok(r)
@@ -429,9 +476,9 @@ impl Request {
 
     // https://fetch.spec.whatwg.org/#concept-body-locked
     fn locked(&self) -> bool {
-        // TODO: ReadableStream is unimplemented. Just return false
-        // for now.
-        false
+        self.body_stream
+            .get()
+            .map_or(false, |stream| stream.is_locked())
     }
 }
 
@@ -442,6 +489,16 @@ impl Request {
         r
     }
 
+    /// Creates the request object of a request made outside of script, such as the one
+    /// a fetch event is dispatched for or one a cache stored, whose headers are immutable.
+    pub fn new_immutable(global: &GlobalScope, net_request: NetTraitsRequest) -> DomRoot<Request> {
+        let headers = net_request.headers.clone();
+        let r = Request::from_net_request(global, net_request);
+        r.Headers().set_headers(headers);
+        r.Headers().set_guard(Guard::Immutable);
+        r
+    }
+
     fn clone_from(r: &Request) -> Fallible<DomRoot<Request>> {
         let req = r.request.borrow();
         let url = req.url();
@@ -461,12 +518,65 @@ impl Request {
             .Headers()
             .fill(Some(HeadersInit::Headers(r.Headers())))?;
         r_clone.Headers().set_guard(headers_guard);
+        r_clone.signal.follow(&r.signal);
+        if let Some(stream) = r.body_stream.get() {
+            let (branch1, branch2) = stream.tee()?;
+            r.body_stream.set(Some(&branch1));
+            r_clone.body_stream.set(Some(&branch2));
+        }
         Ok(r_clone)
     }
 
     pub fn get_request(&self) -> NetTraitsRequest {
         self.request.borrow().clone()
     }
+
+    /// The stream of the body, if the body was given as one or script asked
+    /// for it; the body of `get_request` is null then.
+    pub fn get_body_stream(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream.get()
+    }
+
+    /// Consumes the body with the bytes read from its stream.
+    #[allow(unrooted_must_root)]
+    fn finish_reading_body(&self, bytes: Vec<u8>) {
+        self.request.borrow_mut().body = Some(bytes);
+        let body_promise = self.body_promise.borrow_mut().take();
+        if let Some((p, body_type)) = body_promise {
+            consume_body_with_promise(self, body_type, &p);
+        }
+    }
+
+    /// The request a `RequestInfo` stands for, for the methods of caches.
+    pub fn from_request_info(
+        global: &GlobalScope,
+        info: RequestInfo,
+    ) -> Fallible<DomRoot<Request>> {
+        match info {
+            RequestInfo::Request(request) => Ok(request),
+            info => Request::Constructor(global, info, RequestInit::empty()),
+        }
+    }
+
+    /// Creates a request object from one a cache stored.
+    pub fn from_cached_request(global: &GlobalScope, cached: CachedRequest) -> DomRoot<Request> {
+        let origin = Origin::Origin(global.origin().immutable().clone());
+        let mut net_request =
+            NetTraitsRequest::new(cached.url, Some(origin), Some(global.pipeline_id()));
+        net_request.method = cached.method;
+        net_request.headers = cached.headers;
+        Request::new_immutable(global, net_request)
+    }
+
+    /// What a cache stores of the request.
+    pub fn to_cached_request(&self) -> CachedRequest {
+        let request = self.request.borrow();
+        CachedRequest {
+            url: request.url(),
+            method: request.method.clone(),
+            headers: self.Headers().get_headers_list(),
+        }
+    }
 }
 
 fn net_request_from_global(global: &GlobalScope, url: ServoUrl) -> NetTraitsRequest {
@@ -515,16 +625,14 @@ fn includes_credentials(input: &ServoUrl) -> bool {
     !input.username().is_empty() || input.password().is_some()
 }
 
-// TODO: `Readable Stream` object is not implemented in Servo yet.
 // https://fetch.spec.whatwg.org/#concept-body-disturbed
-fn request_is_disturbed(_input: &Request) -> bool {
-    false
+fn request_is_disturbed(input: &Request) -> bool {
+    input.BodyUsed()
 }
 
-// TODO: `Readable Stream` object is not implemented in Servo yet.
 // https://fetch.spec.whatwg.org/#concept-body-locked
-fn request_is_locked(_input: &Request) -> bool {
-    false
+fn request_is_locked(input: &Request) -> bool {
+    input.locked()
 }
 
 impl RequestMethods for Request {
@@ -601,9 +709,35 @@ impl RequestMethods for Request {
         DOMString::from_string(r.integrity_metadata.clone())
     }
 
+    // https://fetch.spec.whatwg.org/#dom-request-signal
+    fn Signal(&self) -> DomRoot<AbortSignal> {
+        DomRoot::from_ref(&*self.signal)
+    }
+
+    // https://fetch.spec.whatwg.org/#dom-body-body
+    fn GetBody(&self) -> Option<DomRoot<ReadableStream>> {
+        if let Some(stream) = self.body_stream.get() {
+            return Some(stream);
+        }
+        // A body that was read as a whole has a disturbed stream, with nothing
+        // left to read.
+        if self.body_used.get() {
+            let stream = ReadableStream::new_from_bytes(&self.global(), vec![]);
+            stream.set_disturbed();
+            return Some(stream);
+        }
+        let body = self.request.borrow_mut().body.take()?;
+        let stream = ReadableStream::new_from_bytes(&self.global(), body);
+        self.body_stream.set(Some(&stream));
+        Some(stream)
+    }
+
     // https://fetch.spec.whatwg.org/#dom-body-bodyused
     fn BodyUsed(&self) -> bool {
-        self.body_used.get()
+        self.body_used.get() ||
+            self.body_stream
+                .get()
+                .map_or(false, |stream| stream.is_disturbed())
     }
 
     // https://fetch.spec.whatwg.org/#dom-request-clone
@@ -655,6 +789,11 @@ impl BodyOperations for Request {
         assert!(self.body_promise.borrow().is_none());
         self.body_used.set(true);
         *self.body_promise.borrow_mut() = Some((p.clone(), body_type));
+        if let Some(stream) = self.body_stream.get() {
+            stream.read_all_bytes(Box::new(BodyStreamReader {
+                request: Dom::from_ref(self),
+            }));
+        }
     }
 
     fn is_locked(&self) -> bool {
@@ -663,8 +802,12 @@ impl BodyOperations for Request {
 
     fn take_body(&self) -> Option<Vec<u8>> {
         let mut request = self.request.borrow_mut();
-        let body = request.body.take();
-        Some(body.unwrap_or(vec![]))
+        match request.body.take() {
+            Some(body) => Some(body),
+            // The bytes of a body with a stream are read later on.
+            None if self.body_stream.get().is_some() => None,
+            None => Some(vec![]),
+        }
     }
 
     fn get_mime_type(&self) -> Ref<Vec<u8>> {
@@ -672,6 +815,26 @@ impl BodyOperations for Request {
     }
 }
 
+/// Reads the whole stream of a body that is consumed.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct BodyStreamReader {
+    request: Dom<Request>,
+}
+
+impl BytesConsumer for BodyStreamReader {
+    fn success(&self, bytes: Vec<u8>) {
+        self.request.finish_reading_body(bytes);
+    }
+
+    fn failure(&self, error: HandleValue) {
+        let body_promise = self.request.body_promise.borrow_mut().take();
+        if let Some((p, _)) = body_promise {
+            p.reject_native(&error.get());
+        }
+    }
+}
+
 impl Into<NetTraitsRequestCache> for RequestCache {
     fn into(self) -> NetTraitsRequestCache {
         match self {
@@ -726,6 +889,7 @@ impl Into<NetTraitsRequestDestination> for RequestDestination {
             RequestDestination::Document => NetTraitsRequestDestination::Document,
             RequestDestination::Embed => NetTraitsRequestDestination::Embed,
             RequestDestination::Font => NetTraitsRequestDestination::Font,
+            RequestDestination::Iframe => NetTraitsRequestDestination::IFrame,
             RequestDestination::Image => NetTraitsRequestDestination::Image,
             RequestDestination::Manifest => NetTraitsRequestDestination::Manifest,
             RequestDestination::Object => NetTraitsRequestDestination::Object,
@@ -749,6 +913,7 @@ impl Into<RequestDestination> for NetTraitsRequestDestination {
             NetTraitsRequestDestination::Document => RequestDestination::Document,
             NetTraitsRequestDestination::Embed => RequestDestination::Embed,
             NetTraitsRequestDestination::Font => RequestDestination::Font,
+            NetTraitsRequestDestination::IFrame => RequestDestination::Iframe,
             NetTraitsRequestDestination::Image => RequestDestination::Image,
             NetTraitsRequestDestination::Manifest => RequestDestination::Manifest,
             NetTraitsRequestDestination::Object => RequestDestination::Object,
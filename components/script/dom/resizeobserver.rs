@@ -0,0 +1,294 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::ResizeObserverBinding::{
+    self, ResizeObserverBoxOptions, ResizeObserverCallback, ResizeObserverMethods,
+    ResizeObserverOptions,
+};
+use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::element::Element;
+use crate::dom::node::{Node, ShadowIncluding};
+use crate::dom::resizeobserverentry::ResizeObserverEntry;
+use crate::dom::resizeobserversize::ResizeObserverSizeImpl;
+use crate::dom::window::Window;
+use dom_struct::dom_struct;
+use script_layout_interface::rpc::BoxSizes;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Where an observation is, after the active observations of its document
+/// were last gathered.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf, PartialEq)]
+enum ObservationState {
+    Inactive,
+    /// In the active targets of its observer.
+    Active,
+    /// In the skipped targets of its observer.
+    Skipped,
+}
+
+/// <https://drafts.csswg.org/resize-observer/#resize-observation-interface>
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct ResizeObservation {
+    target: Dom<Element>,
+    observed_box: ResizeObserverBoxOptions,
+    last_reported_size: Cell<ResizeObserverSizeImpl>,
+    state: Cell<ObservationState>,
+}
+
+impl ResizeObservation {
+    fn new(target: &Element, observed_box: ResizeObserverBoxOptions) -> ResizeObservation {
+        ResizeObservation {
+            target: Dom::from_ref(target),
+            observed_box,
+            last_reported_size: Cell::new(ResizeObserverSizeImpl {
+                inline_size: 0.,
+                block_size: 0.,
+            }),
+            state: Cell::new(ObservationState::Inactive),
+        }
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobservation-isactive>
+    fn is_active(&self, window: &Window) -> bool {
+        let sizes = window.box_sizes_query(self.target.upcast());
+        let device_pixel_ratio = window.device_pixel_ratio().get() as f64;
+        let current_size =
+            calculate_box_size(sizes.as_ref(), self.observed_box, device_pixel_ratio);
+        current_size != self.last_reported_size.get()
+    }
+}
+
+/// <https://drafts.csswg.org/resize-observer/#calculate-box-size>
+///
+/// `sizes` are the sizes of the boxes of the target, or None if it has no box.
+pub fn calculate_box_size(
+    sizes: Option<&BoxSizes>,
+    observed_box: ResizeObserverBoxOptions,
+    device_pixel_ratio: f64,
+) -> ResizeObserverSizeImpl {
+    let sizes = match sizes {
+        Some(sizes) => sizes,
+        None => {
+            return ResizeObserverSizeImpl {
+                inline_size: 0.,
+                block_size: 0.,
+            };
+        },
+    };
+    let size = match observed_box {
+        ResizeObserverBoxOptions::Border_box => sizes.border_box,
+        ResizeObserverBoxOptions::Content_box |
+        ResizeObserverBoxOptions::Device_pixel_content_box => sizes.content_box.size,
+    };
+    let (inline_size, block_size) = if sizes.vertical_writing_mode {
+        (size.height.to_f64_px(), size.width.to_f64_px())
+    } else {
+        (size.width.to_f64_px(), size.height.to_f64_px())
+    };
+    match observed_box {
+        ResizeObserverBoxOptions::Device_pixel_content_box => ResizeObserverSizeImpl {
+            inline_size: (inline_size * device_pixel_ratio).round(),
+            block_size: (block_size * device_pixel_ratio).round(),
+        },
+        _ => ResizeObserverSizeImpl {
+            inline_size,
+            block_size,
+        },
+    }
+}
+
+/// <https://drafts.csswg.org/resize-observer/#calculate-depth-for-node>
+fn calculate_depth_for_node(target: &Element) -> usize {
+    target
+        .upcast::<Node>()
+        .inclusive_ancestors(ShadowIncluding::Yes)
+        .count()
+}
+
+/// <https://drafts.csswg.org/resize-observer/#resize-observer-interface>
+#[dom_struct]
+pub struct ResizeObserver {
+    reflector_: Reflector,
+    #[ignore_malloc_size_of = "can't measure Rc values"]
+    callback: Rc<ResizeObserverCallback>,
+    /// <https://drafts.csswg.org/resize-observer/#dom-resizeobserver-observationtargets-slot>
+    ///
+    /// Whether an observation is in the active or skipped targets of the
+    /// observer is kept with the observation.
+    observation_targets: DomRefCell<Vec<ResizeObservation>>,
+}
+
+impl ResizeObserver {
+    fn new_inherited(callback: Rc<ResizeObserverCallback>) -> ResizeObserver {
+        ResizeObserver {
+            reflector_: Reflector::new(),
+            callback,
+            observation_targets: DomRefCell::new(vec![]),
+        }
+    }
+
+    fn new(window: &Window, callback: Rc<ResizeObserverCallback>) -> DomRoot<ResizeObserver> {
+        reflect_dom_object(
+            Box::new(ResizeObserver::new_inherited(callback)),
+            window,
+            ResizeObserverBinding::Wrap,
+        )
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-resizeobserver
+    pub fn Constructor(
+        window: &Window,
+        callback: Rc<ResizeObserverCallback>,
+    ) -> Fallible<DomRoot<ResizeObserver>> {
+        Ok(ResizeObserver::new(window, callback))
+    }
+
+    /// Steps 2.2-2.3 of <https://drafts.csswg.org/resize-observer/#gather-active-observations-h>,
+    /// for this observer.
+    pub fn gather_active_observations_at_depth(&self, depth: usize) {
+        let window = self.global();
+        let window = window.as_window();
+        for observation in self.observation_targets.borrow().iter() {
+            let state = if !observation.is_active(window) {
+                ObservationState::Inactive
+            } else if calculate_depth_for_node(&observation.target) > depth {
+                ObservationState::Active
+            } else {
+                ObservationState::Skipped
+            };
+            observation.state.set(state);
+        }
+    }
+
+    pub fn has_active_observations(&self) -> bool {
+        self.observation_targets
+            .borrow()
+            .iter()
+            .any(|observation| observation.state.get() == ObservationState::Active)
+    }
+
+    pub fn has_skipped_observations(&self) -> bool {
+        self.observation_targets
+            .borrow()
+            .iter()
+            .any(|observation| observation.state.get() == ObservationState::Skipped)
+    }
+
+    /// Step 2 of <https://drafts.csswg.org/resize-observer/#broadcast-active-observations>,
+    /// for this observer.
+    pub fn broadcast_active_observations(&self, shallowest_target_depth: &mut usize) {
+        // Step 2.1.
+        if !self.has_active_observations() {
+            return;
+        }
+        let window = self.global();
+        let window = window.as_window();
+        let device_pixel_ratio = window.device_pixel_ratio().get() as f64;
+        // Step 2.2.
+        let mut entries = vec![];
+        // Step 2.3.
+        for observation in self.observation_targets.borrow().iter() {
+            if observation.state.get() != ObservationState::Active {
+                continue;
+            }
+            // Step 2.5, done ahead of the callback which may change the targets.
+            observation.state.set(ObservationState::Inactive);
+            // Step 2.3.1-2.3.2.
+            let sizes = window.box_sizes_query(observation.target.upcast());
+            entries.push(ResizeObserverEntry::new(
+                window,
+                &observation.target,
+                sizes.as_ref(),
+            ));
+            // Step 2.3.3.
+            observation.last_reported_size.set(calculate_box_size(
+                sizes.as_ref(),
+                observation.observed_box,
+                device_pixel_ratio,
+            ));
+            // Step 2.3.4-2.3.5.
+            let target_depth = calculate_depth_for_node(&observation.target);
+            if target_depth < *shallowest_target_depth {
+                *shallowest_target_depth = target_depth;
+            }
+        }
+        // Step 2.4.
+        let _ = self
+            .callback
+            .Call_(self, entries, self, ExceptionHandling::Report);
+    }
+
+    /// Stops observing the target of the observation at `index`.
+    fn remove_observation(&self, index: usize) {
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        observation_targets.remove(index);
+        // Without targets, the observer has nothing left to be notified about.
+        if observation_targets.is_empty() {
+            self.global()
+                .as_window()
+                .Document()
+                .remove_resize_observer(self);
+        }
+    }
+}
+
+impl ResizeObserverMethods for ResizeObserver {
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-observe
+    fn Observe(&self, target: &Element, options: &ResizeObserverOptions) {
+        // Step 1.
+        let index = self
+            .observation_targets
+            .borrow()
+            .iter()
+            .position(|observation| &*observation.target == target);
+        if let Some(index) = index {
+            self.remove_observation(index);
+        }
+        // Step 2-3.
+        let observation = ResizeObservation::new(target, options.box_);
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        if observation_targets.is_empty() {
+            self.global()
+                .as_window()
+                .Document()
+                .add_resize_observer(self);
+        }
+        observation_targets.push(observation);
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-unobserve
+    fn Unobserve(&self, target: &Element) {
+        // Step 1-2.
+        let index = self
+            .observation_targets
+            .borrow()
+            .iter()
+            .position(|observation| &*observation.target == target);
+        // Step 3.
+        if let Some(index) = index {
+            self.remove_observation(index);
+        }
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserver-disconnect
+    fn Disconnect(&self) {
+        // Step 1-3.
+        let mut observation_targets = self.observation_targets.borrow_mut();
+        if !observation_targets.is_empty() {
+            observation_targets.clear();
+            self.global()
+                .as_window()
+                .Document()
+                .remove_resize_observer(self);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::ResizeObserverBinding::ResizeObserverBoxOptions;
+use crate::dom::bindings::codegen::Bindings::ResizeObserverEntryBinding::{
+    self, ResizeObserverEntryMethods,
+};
+use crate::dom::bindings::conversions::ToJSValConvertible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::domrectreadonly::DOMRectReadOnly;
+use crate::dom::element::Element;
+use crate::dom::resizeobserver::calculate_box_size;
+use crate::dom::resizeobserversize::ResizeObserverSize;
+use crate::dom::window::Window;
+use dom_struct::dom_struct;
+use euclid::Rect;
+use js::jsapi::{Heap, JSContext};
+use js::jsval::{JSVal, UndefinedValue};
+use script_layout_interface::rpc::BoxSizes;
+
+/// <https://drafts.csswg.org/resize-observer/#resize-observer-entry-interface>
+#[dom_struct]
+pub struct ResizeObserverEntry {
+    reflector_: Reflector,
+    target: Dom<Element>,
+    content_rect: Dom<DOMRectReadOnly>,
+    border_box_size: Dom<ResizeObserverSize>,
+    content_box_size: Dom<ResizeObserverSize>,
+    device_pixel_content_box_size: Dom<ResizeObserverSize>,
+    /// The arrays returned by the size getters, created on first access.
+    #[ignore_malloc_size_of = "mozjs"]
+    border_box_size_array: Heap<JSVal>,
+    #[ignore_malloc_size_of = "mozjs"]
+    content_box_size_array: Heap<JSVal>,
+    #[ignore_malloc_size_of = "mozjs"]
+    device_pixel_content_box_size_array: Heap<JSVal>,
+}
+
+impl ResizeObserverEntry {
+    fn new_inherited(
+        target: &Element,
+        content_rect: &DOMRectReadOnly,
+        border_box_size: &ResizeObserverSize,
+        content_box_size: &ResizeObserverSize,
+        device_pixel_content_box_size: &ResizeObserverSize,
+    ) -> ResizeObserverEntry {
+        ResizeObserverEntry {
+            reflector_: Reflector::new(),
+            target: Dom::from_ref(target),
+            content_rect: Dom::from_ref(content_rect),
+            border_box_size: Dom::from_ref(border_box_size),
+            content_box_size: Dom::from_ref(content_box_size),
+            device_pixel_content_box_size: Dom::from_ref(device_pixel_content_box_size),
+            border_box_size_array: Heap::default(),
+            content_box_size_array: Heap::default(),
+            device_pixel_content_box_size_array: Heap::default(),
+        }
+    }
+
+    /// <https://drafts.csswg.org/resize-observer/#create-and-populate-a-resizeobserverentry>
+    ///
+    /// `sizes` are the sizes of the boxes of the target, or None if it has no box.
+    pub fn new(
+        window: &Window,
+        target: &Element,
+        sizes: Option<&BoxSizes>,
+    ) -> DomRoot<ResizeObserverEntry> {
+        let device_pixel_ratio = window.device_pixel_ratio().get() as f64;
+        let size = |observed_box| {
+            ResizeObserverSize::new(
+                window,
+                calculate_box_size(sizes, observed_box, device_pixel_ratio),
+            )
+        };
+        // Step 3-5.
+        let border_box_size = size(ResizeObserverBoxOptions::Border_box);
+        let content_box_size = size(ResizeObserverBoxOptions::Content_box);
+        let device_pixel_content_box_size =
+            size(ResizeObserverBoxOptions::Device_pixel_content_box);
+        // Step 6-8.
+        let content_rect = sizes.map_or_else(Rect::zero, |sizes| sizes.content_box);
+        let content_rect = DOMRectReadOnly::new(
+            window.upcast(),
+            content_rect.origin.x.to_f64_px(),
+            content_rect.origin.y.to_f64_px(),
+            content_rect.size.width.to_f64_px(),
+            content_rect.size.height.to_f64_px(),
+        );
+        reflect_dom_object(
+            Box::new(ResizeObserverEntry::new_inherited(
+                target,
+                &content_rect,
+                &border_box_size,
+                &content_box_size,
+                &device_pixel_content_box_size,
+            )),
+            window,
+            ResizeObserverEntryBinding::Wrap,
+        )
+    }
+}
+
+/// Returns the array of `size` cached in `array`, creating it on first access.
+#[allow(unsafe_code)]
+unsafe fn size_array(cx: *mut JSContext, size: &ResizeObserverSize, array: &Heap<JSVal>) -> JSVal {
+    if array.get().is_undefined() {
+        rooted!(in(cx) let mut value = UndefinedValue());
+        vec![DomRoot::from_ref(size)].to_jsval(cx, value.handle_mut());
+        array.set(value.get());
+    }
+    array.get()
+}
+
+impl ResizeObserverEntryMethods for ResizeObserverEntry {
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-target
+    fn Target(&self) -> DomRoot<Element> {
+        DomRoot::from_ref(&*self.target)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-contentrect
+    fn ContentRect(&self) -> DomRoot<DOMRectReadOnly> {
+        DomRoot::from_ref(&*self.content_rect)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-borderboxsize
+    #[allow(unsafe_code)]
+    unsafe fn BorderBoxSize(&self, cx: *mut JSContext) -> JSVal {
+        size_array(cx, &self.border_box_size, &self.border_box_size_array)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-contentboxsize
+    #[allow(unsafe_code)]
+    unsafe fn ContentBoxSize(&self, cx: *mut JSContext) -> JSVal {
+        size_array(cx, &self.content_box_size, &self.content_box_size_array)
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserverentry-devicepixelcontentboxsize
+    #[allow(unsafe_code)]
+    unsafe fn DevicePixelContentBoxSize(&self, cx: *mut JSContext) -> JSVal {
+        size_array(
+            cx,
+            &self.device_pixel_content_box_size,
+            &self.device_pixel_content_box_size_array,
+        )
+    }
+}
@@ -0,0 +1,54 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::ResizeObserverSizeBinding::{
+    self, ResizeObserverSizeMethods,
+};
+use crate::dom::bindings::reflector::{reflect_dom_object, Reflector};
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::window::Window;
+use dom_struct::dom_struct;
+
+/// The size of a box, in the writing mode of its element.
+#[derive(Clone, Copy, JSTraceable, MallocSizeOf, PartialEq)]
+pub struct ResizeObserverSizeImpl {
+    pub inline_size: f64,
+    pub block_size: f64,
+}
+
+/// <https://drafts.csswg.org/resize-observer/#resizeobserversize>
+#[dom_struct]
+pub struct ResizeObserverSize {
+    reflector_: Reflector,
+    size_impl: ResizeObserverSizeImpl,
+}
+
+impl ResizeObserverSize {
+    fn new_inherited(size_impl: ResizeObserverSizeImpl) -> ResizeObserverSize {
+        ResizeObserverSize {
+            reflector_: Reflector::new(),
+            size_impl,
+        }
+    }
+
+    pub fn new(window: &Window, size_impl: ResizeObserverSizeImpl) -> DomRoot<ResizeObserverSize> {
+        reflect_dom_object(
+            Box::new(ResizeObserverSize::new_inherited(size_impl)),
+            window,
+            ResizeObserverSizeBinding::Wrap,
+        )
+    }
+}
+
+impl ResizeObserverSizeMethods for ResizeObserverSize {
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserversize-inlinesize
+    fn InlineSize(&self) -> f64 {
+        self.size_impl.inline_size
+    }
+
+    // https://drafts.csswg.org/resize-observer/#dom-resizeobserversize-blocksize
+    fn BlockSize(&self) -> f64 {
+        self.size_impl.block_size
+    }
+}
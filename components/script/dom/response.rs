@@ -2,27 +2,36 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::body::{consume_body, consume_body_with_promise, BodyOperations, BodyType};
+use crate::body::{consume_body, consume_body_with_promise, extract_body, BodyOperations};
+use crate::body::{BodyType, ExtractedBody};
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::HeadersBinding::{HeadersInit, HeadersMethods};
 use crate::dom::bindings::codegen::Bindings::ResponseBinding;
 use crate::dom::bindings::codegen::Bindings::ResponseBinding::{
-    ResponseMethods, ResponseType as DOMResponseType,
+    ResponseBodyInit, ResponseMethods, ResponseType as DOMResponseType,
 };
-use crate::dom::bindings::codegen::Bindings::XMLHttpRequestBinding::BodyInit;
 use crate::dom::bindings::error::{Error, Fallible};
 use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
-use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::str::{ByteString, USVString};
+use crate::dom::bindings::trace::JSTraceable;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::headers::{is_obs_text, is_vchar};
 use crate::dom::headers::{Guard, Headers};
 use crate::dom::promise::Promise;
-use crate::dom::xmlhttprequest::Extractable;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
+use crate::dom::readablestream::{BytesConsumer, ReadableStream};
+use crate::dom::readablestreamdefaultcontroller::UnderlyingSource;
+use crate::dom::streams::StartAlgorithm;
 use dom_struct::dom_struct;
 use http::header::HeaderMap as HyperHeaders;
 use hyper::StatusCode;
 use hyper_serde::Serde;
+use js::jsapi::JSContext;
+use js::rust::HandleValue;
+use js::typedarray::ArrayBuffer;
+use malloc_size_of::MallocSizeOf;
+use net_traits::cache_storage_thread::CachedResponse;
 use net_traits::response::ResponseBody as NetTraitsResponseBody;
 use servo_url::ServoUrl;
 use std::cell::{Cell, Ref};
@@ -48,6 +57,18 @@ pub struct Response {
     body: DomRefCell<NetTraitsResponseBody>,
     #[ignore_malloc_size_of = "Rc"]
     body_promise: DomRefCell<Option<(Rc<Promise>, BodyType)>>,
+    /// Whether the fetch was aborted before the whole body was received.
+    body_aborted: Cell<bool>,
+    /// <https://fetch.spec.whatwg.org/#concept-body-stream>
+    ///
+    /// Only created once script asks for it, or when the response is cloned.
+    body_stream: MutNullableDom<ReadableStream>,
+    /// The stream the rest of the body of a fetch is enqueued in as it is
+    /// received, once the body has a stream.
+    fetch_stream: MutNullableDom<ReadableStream>,
+    /// Whether the stream of the body of a fetch was cancelled, which
+    /// terminates the fetch.
+    body_cancelled: Cell<bool>,
 }
 
 impl Response {
@@ -64,6 +85,10 @@ impl Response {
             url_list: DomRefCell::new(vec![]),
             body: DomRefCell::new(NetTraitsResponseBody::Empty),
             body_promise: DomRefCell::new(None),
+            body_aborted: Cell::new(false),
+            body_stream: Default::default(),
+            fetch_stream: Default::default(),
+            body_cancelled: Cell::new(false),
         }
     }
 
@@ -78,7 +103,7 @@ impl Response {
 
     pub fn Constructor(
         global: &GlobalScope,
-        body: Option<BodyInit>,
+        body: Option<ResponseBodyInit>,
         init: &ResponseBinding::ResponseInit,
     ) -> Fallible<DomRoot<Response>> {
         // Step 1
@@ -125,8 +150,13 @@ impl Response {
             };
 
             // Step 7.3
-            let (extracted_body, content_type) = body.extract();
-            *r.body.borrow_mut() = NetTraitsResponseBody::Done(extracted_body);
+            let (extracted_body, content_type) = extract_body(body)?;
+            match extracted_body {
+                ExtractedBody::Bytes(bytes) => {
+                    *r.body.borrow_mut() = NetTraitsResponseBody::Done(bytes);
+                },
+                ExtractedBody::Stream(stream) => r.body_stream.set(Some(&stream)),
+            }
 
             // Step 7.4
             if let Some(content_type_contents) = content_type {
@@ -210,9 +240,50 @@ impl Response {
 
     // https://fetch.spec.whatwg.org/#concept-body-locked
     fn locked(&self) -> bool {
-        // TODO: ReadableStream is unimplemented. Just return false
-        // for now.
-        false
+        self.body_stream
+            .get()
+            .map_or(false, |stream| stream.is_locked())
+    }
+
+    /// The stream of the body, which is created from the bytes received so far
+    /// if it doesn't exist yet.
+    fn body_stream(&self) -> Option<DomRoot<ReadableStream>> {
+        if let Some(stream) = self.body_stream.get() {
+            return Some(stream);
+        }
+        let global = self.global();
+        // A body that was read as a whole has a disturbed stream, with nothing
+        // left to read.
+        if self.body_used.get() {
+            let stream = ReadableStream::new_from_bytes(&global, vec![]);
+            stream.set_disturbed();
+            return Some(stream);
+        }
+        let body = mem::replace(&mut *self.body.borrow_mut(), NetTraitsResponseBody::Empty);
+        let stream = match body {
+            NetTraitsResponseBody::Empty => return None,
+            NetTraitsResponseBody::Done(bytes) => ReadableStream::new_from_bytes(&global, bytes),
+            NetTraitsResponseBody::Receiving(bytes) => {
+                let stream = ReadableStream::create(
+                    &global,
+                    UnderlyingSource::Fetch(Dom::from_ref(self)),
+                    StartAlgorithm::Nothing,
+                    1.,
+                    None,
+                );
+                if !bytes.is_empty() {
+                    stream.enqueue_native(bytes);
+                }
+                if self.body_aborted.get() {
+                    stream.error_native(Error::Abort);
+                } else {
+                    self.fetch_stream.set(Some(&stream));
+                }
+                stream
+            },
+        };
+        self.body_stream.set(Some(&stream));
+        Some(stream)
     }
 }
 
@@ -224,7 +295,15 @@ impl BodyOperations for Response {
     fn set_body_promise(&self, p: &Rc<Promise>, body_type: BodyType) {
         assert!(self.body_promise.borrow().is_none());
         self.body_used.set(true);
+        if self.body_aborted.get() {
+            return p.reject_error(Error::Abort);
+        }
         *self.body_promise.borrow_mut() = Some((p.clone(), body_type));
+        if let Some(stream) = self.body_stream.get() {
+            stream.read_all_bytes(Box::new(BodyStreamReader {
+                response: Dom::from_ref(self),
+            }));
+        }
     }
 
     fn is_locked(&self) -> bool {
@@ -235,6 +314,9 @@ impl BodyOperations for Response {
         let body = mem::replace(&mut *self.body.borrow_mut(), NetTraitsResponseBody::Empty);
         match body {
             NetTraitsResponseBody::Done(bytes) => Some(bytes),
+            // Only the responses of fetches, and the bodies with a stream, get
+            // their bytes later on.
+            NetTraitsResponseBody::Empty if self.body_stream.get().is_none() => Some(vec![]),
             body => {
                 mem::replace(&mut *self.body.borrow_mut(), body);
                 None
@@ -326,7 +408,7 @@ impl ResponseMethods for Response {
     // https://fetch.spec.whatwg.org/#dom-response-clone
     fn Clone(&self) -> Fallible<DomRoot<Response>> {
         // Step 1
-        if self.is_locked() || self.body_used.get() {
+        if self.is_locked() || self.BodyUsed() {
             return Err(Error::Type("cannot clone a disturbed response".to_string()));
         }
 
@@ -346,7 +428,17 @@ impl ResponseMethods for Response {
         *new_response.url.borrow_mut() = self.url.borrow().clone();
         *new_response.url_list.borrow_mut() = self.url_list.borrow().clone();
 
-        if *self.body.borrow() != NetTraitsResponseBody::Empty {
+        // The body is teed if it has a stream, or is still being received.
+        let receiving = match *self.body.borrow() {
+            NetTraitsResponseBody::Receiving(_) => true,
+            _ => false,
+        };
+        if receiving || self.body_stream.get().is_some() {
+            let stream = self.body_stream().expect("The body isn't null");
+            let (branch1, branch2) = stream.tee()?;
+            self.body_stream.set(Some(&branch1));
+            new_response.body_stream.set(Some(&branch2));
+        } else if *self.body.borrow() != NetTraitsResponseBody::Empty {
             *new_response.body.borrow_mut() = self.body.borrow().clone();
         }
 
@@ -357,9 +449,17 @@ impl ResponseMethods for Response {
         Ok(new_response)
     }
 
+    // https://fetch.spec.whatwg.org/#dom-body-body
+    fn GetBody(&self) -> Option<DomRoot<ReadableStream>> {
+        self.body_stream()
+    }
+
     // https://fetch.spec.whatwg.org/#dom-body-bodyused
     fn BodyUsed(&self) -> bool {
-        self.body_used.get()
+        self.body_used.get() ||
+            self.body_stream
+                .get()
+                .map_or(false, |stream| stream.is_disturbed())
     }
 
     // https://fetch.spec.whatwg.org/#dom-body-text
@@ -405,6 +505,9 @@ impl Response {
     }
 
     pub fn set_raw_status(&self, status: Option<(u16, Vec<u8>)>) {
+        *self.status.borrow_mut() = status
+            .as_ref()
+            .and_then(|&(code, _)| StatusCode::from_u16(code).ok());
         *self.raw_status.borrow_mut() = status;
     }
 
@@ -412,6 +515,12 @@ impl Response {
         *self.url.borrow_mut() = Some(final_url);
     }
 
+    /// Marks the body as not received yet, for the response of a fetch,
+    /// until `finish` is called with it.
+    pub fn start_receiving_body(&self) {
+        *self.body.borrow_mut() = NetTraitsResponseBody::Receiving(vec![]);
+    }
+
     #[allow(unrooted_must_root)]
     pub fn finish(&self, body: Vec<u8>) {
         *self.body.borrow_mut() = NetTraitsResponseBody::Done(body);
@@ -419,4 +528,129 @@ impl Response {
             consume_body_with_promise(self, body_type, &p);
         }
     }
+
+    /// Receives a chunk of the body of the response of a fetch.
+    pub fn receive_body_chunk(&self, mut chunk: Vec<u8>) {
+        if let Some(stream) = self.fetch_stream.get() {
+            return stream.enqueue_native(chunk);
+        }
+        if let NetTraitsResponseBody::Receiving(ref mut bytes) = *self.body.borrow_mut() {
+            bytes.append(&mut chunk);
+        }
+    }
+
+    /// Finishes the body of the response of a fetch, once all of it was received.
+    pub fn finish_receiving_body(&self) {
+        if let Some(stream) = self.fetch_stream.take() {
+            return stream.close_native();
+        }
+        let body = mem::replace(&mut *self.body.borrow_mut(), NetTraitsResponseBody::Empty);
+        match body {
+            NetTraitsResponseBody::Receiving(bytes) => self.finish(bytes),
+            body => *self.body.borrow_mut() = body,
+        }
+    }
+
+    /// Terminates the fetch of the response once its body stream is cancelled.
+    pub fn cancel_body(&self) {
+        self.fetch_stream.set(None);
+        self.body_cancelled.set(true);
+    }
+
+    pub fn is_body_cancelled(&self) -> bool {
+        self.body_cancelled.get()
+    }
+
+    /// Errors the body of the response of a fetch that was aborted before
+    /// the whole body was received.
+    #[allow(unrooted_must_root)]
+    pub fn abort_body(&self) {
+        if let Some(stream) = self.fetch_stream.take() {
+            return stream.error_native(Error::Abort);
+        }
+        match *self.body.borrow() {
+            NetTraitsResponseBody::Receiving(_) => self.body_aborted.set(true),
+            _ => return,
+        }
+        if let Some((p, _)) = self.body_promise.borrow_mut().take() {
+            p.reject_error(Error::Abort);
+        }
+    }
+
+    /// Creates a response from the one a cache stored.
+    pub fn from_cached_response(global: &GlobalScope, cached: CachedResponse) -> DomRoot<Response> {
+        let r = Response::new(global);
+        r.Headers().set_headers(cached.headers);
+        r.Headers().set_guard(Guard::Immutable);
+        *r.mime_type.borrow_mut() = r.Headers().extract_mime_type();
+        r.set_raw_status(Some(cached.status));
+        if let Some(url) = cached.url {
+            r.set_final_url(url);
+        }
+        *r.body.borrow_mut() = NetTraitsResponseBody::Done(cached.body);
+        r
+    }
+
+    /// What a cache stores of the response, given its whole body.
+    pub fn to_cached_response(&self, body: Vec<u8>) -> CachedResponse {
+        CachedResponse {
+            url: self.url.borrow().clone(),
+            status: self.raw_status.borrow().clone().unwrap_or((0, vec![])),
+            headers: self.Headers().get_headers_list(),
+            body: body,
+        }
+    }
+
+    /// Reads the whole body, which must not be used yet, for the consumers outside of
+    /// script: a service worker responding to a fetch, or a cache storing the response.
+    pub fn read_body(&self, consumer: Box<dyn BodyConsumer>) {
+        let promise = consume_body(self, BodyType::ArrayBuffer);
+        let handler = PromiseNativeHandler::new(
+            &self.global(),
+            Some(Box::new(ArrayBufferHandler { consumer: consumer })),
+            None,
+        );
+        promise.append_native_handler(&handler);
+    }
+}
+
+/// Receives the whole body of a response read with `Response::read_body`.
+pub trait BodyConsumer: JSTraceable + MallocSizeOf {
+    fn consume(&self, body: Vec<u8>);
+}
+
+/// Reads the whole stream of a body that is consumed.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct BodyStreamReader {
+    response: Dom<Response>,
+}
+
+impl BytesConsumer for BodyStreamReader {
+    fn success(&self, bytes: Vec<u8>) {
+        self.response.finish(bytes);
+    }
+
+    fn failure(&self, error: HandleValue) {
+        let body_promise = self.response.body_promise.borrow_mut().take();
+        if let Some((p, _)) = body_promise {
+            p.reject_native(&error.get());
+        }
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct ArrayBufferHandler {
+    consumer: Box<dyn BodyConsumer>,
+}
+
+impl Callback for ArrayBufferHandler {
+    #[allow(unsafe_code)]
+    fn callback(&self, cx: *mut JSContext, v: HandleValue) {
+        let body = unsafe {
+            typedarray!(in(cx) let array_buffer: ArrayBuffer = v.get().to_object());
+            array_buffer.map_or(vec![], |array_buffer| array_buffer.as_slice().to_vec())
+        };
+        self.consumer.consume(body);
+    }
 }
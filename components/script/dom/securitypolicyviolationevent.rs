@@ -0,0 +1,157 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use crate::dom::bindings::codegen::Bindings::SecurityPolicyViolationEventBinding;
+use crate::dom::bindings::codegen::Bindings::SecurityPolicyViolationEventBinding::{
+    SecurityPolicyViolationEventDisposition, SecurityPolicyViolationEventInit,
+    SecurityPolicyViolationEventMethods,
+};
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::reflector::reflect_dom_object;
+use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::str::{DOMString, USVString};
+use crate::dom::event::{Event, EventBubbles, EventCancelable};
+use crate::dom::globalscope::GlobalScope;
+use dom_struct::dom_struct;
+use servo_atoms::Atom;
+
+// https://w3c.github.io/webappsec-csp/#securitypolicyviolationevent
+#[dom_struct]
+pub struct SecurityPolicyViolationEvent {
+    event: Event,
+    document_uri: USVString,
+    referrer: USVString,
+    blocked_uri: USVString,
+    effective_directive: DOMString,
+    violated_directive: DOMString,
+    original_policy: DOMString,
+    source_file: USVString,
+    sample: DOMString,
+    disposition: SecurityPolicyViolationEventDisposition,
+    status_code: u16,
+    line_number: u32,
+    column_number: u32,
+}
+
+impl SecurityPolicyViolationEvent {
+    fn new_inherited(init: &SecurityPolicyViolationEventInit) -> SecurityPolicyViolationEvent {
+        SecurityPolicyViolationEvent {
+            event: Event::new_inherited(),
+            document_uri: init.documentURI.clone(),
+            referrer: init.referrer.clone(),
+            blocked_uri: init.blockedURI.clone(),
+            effective_directive: init.effectiveDirective.clone(),
+            violated_directive: init.violatedDirective.clone(),
+            original_policy: init.originalPolicy.clone(),
+            source_file: init.sourceFile.clone(),
+            sample: init.sample.clone(),
+            disposition: init.disposition,
+            status_code: init.statusCode,
+            line_number: init.lineNumber,
+            column_number: init.columnNumber,
+        }
+    }
+
+    pub fn new(
+        global: &GlobalScope,
+        type_: Atom,
+        bubbles: EventBubbles,
+        cancelable: EventCancelable,
+        init: &SecurityPolicyViolationEventInit,
+    ) -> DomRoot<SecurityPolicyViolationEvent> {
+        let ev = reflect_dom_object(
+            Box::new(SecurityPolicyViolationEvent::new_inherited(init)),
+            global,
+            SecurityPolicyViolationEventBinding::Wrap,
+        );
+        {
+            let event = ev.upcast::<Event>();
+            event.init_event(type_, bool::from(bubbles), bool::from(cancelable));
+        }
+        ev
+    }
+
+    pub fn Constructor(
+        global: &GlobalScope,
+        type_: DOMString,
+        init: &SecurityPolicyViolationEventInit,
+    ) -> Fallible<DomRoot<SecurityPolicyViolationEvent>> {
+        Ok(SecurityPolicyViolationEvent::new(
+            global,
+            Atom::from(type_),
+            EventBubbles::from(init.parent.bubbles),
+            EventCancelable::from(init.parent.cancelable),
+            init,
+        ))
+    }
+}
+
+impl SecurityPolicyViolationEventMethods for SecurityPolicyViolationEvent {
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-documenturi
+    fn DocumentURI(&self) -> USVString {
+        self.document_uri.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-referrer
+    fn Referrer(&self) -> USVString {
+        self.referrer.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-blockeduri
+    fn BlockedURI(&self) -> USVString {
+        self.blocked_uri.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-effectivedirective
+    fn EffectiveDirective(&self) -> DOMString {
+        self.effective_directive.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-violateddirective
+    fn ViolatedDirective(&self) -> DOMString {
+        self.violated_directive.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-originalpolicy
+    fn OriginalPolicy(&self) -> DOMString {
+        self.original_policy.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-sourcefile
+    fn SourceFile(&self) -> USVString {
+        self.source_file.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-sample
+    fn Sample(&self) -> DOMString {
+        self.sample.clone()
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-disposition
+    fn Disposition(&self) -> SecurityPolicyViolationEventDisposition {
+        self.disposition
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-statuscode
+    fn StatusCode(&self) -> u16 {
+        self.status_code
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-linenumber
+    fn LineNumber(&self) -> u32 {
+        self.line_number
+    }
+
+    // https://w3c.github.io/webappsec-csp/#dom-securitypolicyviolationevent-columnnumber
+    fn ColumnNumber(&self) -> u32 {
+        self.column_number
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-istrusted
+    fn IsTrusted(&self) -> bool {
+        self.event.IsTrusted()
+    }
+}
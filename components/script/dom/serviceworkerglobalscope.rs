@@ -12,14 +12,18 @@ use crate::dom::bindings::codegen::Bindings::WorkerBinding::WorkerType;
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::root::{DomRoot, RootCollection, ThreadLocalStackRoots};
 use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::structuredclone;
 use crate::dom::dedicatedworkerglobalscope::AutoWorkerReset;
 use crate::dom::event::Event;
 use crate::dom::eventtarget::EventTarget;
 use crate::dom::extendableevent::ExtendableEvent;
 use crate::dom::extendablemessageevent::ExtendableMessageEvent;
+use crate::dom::fetchevent::FetchEvent;
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::messageevent::MessageEvent;
+use crate::dom::request::Request;
 use crate::dom::worker::TrustedWorkerAddress;
-use crate::dom::workerglobalscope::WorkerGlobalScope;
+use crate::dom::workerglobalscope::{install_worker_pipeline_namespace, WorkerGlobalScope};
 use crate::fetch::load_whole_resource;
 use crate::script_runtime::{new_rt_and_cx, CommonScriptMsg, Runtime, ScriptChan};
 use crate::task_queue::{QueuedTask, QueuedTaskConversion, TaskQueue};
@@ -37,9 +41,12 @@ use net_traits::{CustomResponseMediator, IpcSend};
 use script_traits::{
     ScopeThings, ServiceWorkerMsg, TimerEvent, WorkerGlobalScopeInit, WorkerScriptLoadOrigin,
 };
+use servo_atoms::Atom;
 use servo_config::pref;
 use servo_rand::random;
 use servo_url::ServoUrl;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use style::thread_state::{self, ThreadState};
@@ -210,7 +217,7 @@ impl ServiceWorkerGlobalScope {
                 runtime,
                 from_devtools_receiver,
                 timer_event_chan,
-                None,
+                Some(Arc::new(AtomicBool::new(false))),
             ),
             task_queue: TaskQueue::new(receiver, own_sender.clone()),
             timer_event_port: timer_event_port,
@@ -258,6 +265,7 @@ impl ServiceWorkerGlobalScope {
         devtools_receiver: IpcReceiver<DevtoolScriptControlMsg>,
         swmanager_sender: IpcSender<ServiceWorkerMsg>,
         scope_url: ServoUrl,
+        install: bool,
     ) {
         let ScopeThings {
             script_url,
@@ -267,15 +275,12 @@ impl ServiceWorkerGlobalScope {
         } = scope_things;
 
         let serialized_worker_url = script_url.to_string();
-        let origin = GlobalScope::current()
-            .expect("No current global object")
-            .origin()
-            .immutable()
-            .clone();
+        let origin = init.origin.clone();
         thread::Builder::new()
             .name(format!("ServiceWorker for {}", serialized_worker_url))
             .spawn(move || {
                 thread_state::initialize(ThreadState::SCRIPT | ThreadState::IN_WORKER);
+                install_worker_pipeline_namespace(&init.script_to_constellation_chan);
                 let roots = RootCollection::new();
                 let _stack_roots = ThreadLocalStackRoots::new(&roots);
 
@@ -297,20 +302,6 @@ impl ServiceWorkerGlobalScope {
                     .referrer_policy(referrer_policy)
                     .origin(origin);
 
-                let (url, source) = match load_whole_resource(
-                    request,
-                    &init.resource_threads.sender(),
-                    &GlobalScope::current().expect("No current global object"),
-                ) {
-                    Err(_) => {
-                        println!("error loading script {}", serialized_worker_url);
-                        return;
-                    },
-                    Ok((metadata, bytes)) => {
-                        (metadata.final_url, String::from_utf8(bytes).unwrap())
-                    },
-                };
-
                 let runtime = new_rt_and_cx();
 
                 let (devtools_mpsc_chan, devtools_mpsc_port) = unbounded();
@@ -321,7 +312,7 @@ impl ServiceWorkerGlobalScope {
                 let (timer_chan, timer_port) = unbounded();
                 let global = ServiceWorkerGlobalScope::new(
                     init,
-                    url,
+                    script_url,
                     devtools_mpsc_port,
                     runtime,
                     own_sender,
@@ -332,6 +323,27 @@ impl ServiceWorkerGlobalScope {
                     scope_url,
                 );
                 let scope = global.upcast::<WorkerGlobalScope>();
+                let global_scope = global.upcast::<GlobalScope>();
+
+                let (metadata, bytes) = match load_whole_resource(
+                    request,
+                    &global_scope.resource_threads().sender(),
+                    &global_scope,
+                ) {
+                    Err(_) => {
+                        error!("error loading script {}", serialized_worker_url);
+                        if install {
+                            let _ = global
+                                .swmanager_sender
+                                .send(ServiceWorkerMsg::InstallFailed(global.scope_url.clone()));
+                        }
+                        return;
+                    },
+                    Ok((metadata, bytes)) => (metadata, bytes),
+                };
+                scope.initialize_csp_list(&metadata, None);
+                scope.set_url(metadata.final_url);
+                let source = String::from_utf8_lossy(&bytes);
 
                 unsafe {
                     // Handle interrupt requests
@@ -349,7 +361,9 @@ impl ServiceWorkerGlobalScope {
                     })
                     .expect("Thread spawning failed");
 
-                global.dispatch_activate();
+                if install {
+                    global.dispatch_lifecycle_event(atom!("install"));
+                }
                 let reporter_name = format!("service-worker-reporter-{}", random::<u64>());
                 scope
                     .upcast::<GlobalScope>()
@@ -369,6 +383,7 @@ impl ServiceWorkerGlobalScope {
                         scope.script_chan(),
                         CommonScriptMsg::CollectReports,
                     );
+                scope.upcast::<GlobalScope>().remove_web_messaging_infra();
             })
             .expect("Thread spawning failed");
     }
@@ -412,18 +427,23 @@ impl ServiceWorkerGlobalScope {
                 let target = self.upcast();
                 let _ac = enter_realm(&*scope);
                 rooted!(in(scope.get_cx()) let mut message = UndefinedValue());
-                data.read(scope.upcast(), message.handle_mut());
+                if structuredclone::read(scope.upcast(), data, message.handle_mut()).is_err() {
+                    return MessageEvent::dispatch_error(target, scope.upcast());
+                }
                 ExtendableMessageEvent::dispatch_jsval(target, scope.upcast(), message.handle());
             },
             CommonWorker(WorkerScriptMsg::Common(msg)) => {
                 self.upcast::<WorkerGlobalScope>().process_event(msg);
             },
             Response(mediator) => {
-                // TODO XXXcreativcoder This will eventually use a FetchEvent interface to fire event
-                // when we have the Request and Response dom api's implemented
-                // https://w3c.github.io/ServiceWorker/#fetchevent-interface
-                self.upcast::<EventTarget>().fire_event(atom!("fetch"));
-                let _ = mediator.response_chan.send(None);
+                // https://w3c.github.io/ServiceWorker/#handle-fetch
+                let scope = self.upcast::<WorkerGlobalScope>();
+                let _ac = enter_realm(&*scope);
+                let request = Request::new_immutable(scope.upcast(), mediator.request.build());
+                let event = FetchEvent::new_for_fetch(self, &request, mediator.response_chan);
+                event.upcast::<Event>().set_trusted(true);
+                self.upcast::<EventTarget>().dispatch_event(event.upcast());
+                event.respond_if_not_entered();
             },
             WakeUp => {},
         }
@@ -435,10 +455,38 @@ impl ServiceWorkerGlobalScope {
         })
     }
 
-    fn dispatch_activate(&self) {
-        let event = ExtendableEvent::new(self, atom!("activate"), false, false);
-        let event = (&*event).upcast::<Event>();
-        self.upcast::<EventTarget>().dispatch_event(event);
+    /// Dispatches the install or activate event, whose lifetime promises may delay
+    /// the next step of the lifecycle until they settle.
+    fn dispatch_lifecycle_event(&self, type_: Atom) {
+        let event = ExtendableEvent::new(self, type_, false, false);
+        event.upcast::<Event>().set_trusted(true);
+        self.upcast::<EventTarget>().dispatch_event(event.upcast());
+        if !event.has_pending_promises() {
+            self.extendable_event_settled(&event);
+        }
+    }
+
+    /// Continues the lifecycle of the service worker once the install or activate event
+    /// is handled.
+    /// <https://w3c.github.io/ServiceWorker/#installation-algorithm>
+    /// <https://w3c.github.io/ServiceWorker/#activation-algorithm>
+    pub fn extendable_event_settled(&self, event: &ExtendableEvent) {
+        let event_type = event.upcast::<Event>().type_();
+        if event_type == atom!("install") {
+            if event.rejected() {
+                let _ = self
+                    .swmanager_sender
+                    .send(ServiceWorkerMsg::InstallFailed(self.scope_url.clone()));
+                self.upcast::<WorkerGlobalScope>().close();
+                return;
+            }
+            self.dispatch_lifecycle_event(atom!("activate"));
+        } else if event_type == atom!("activate") {
+            // The rejection of an activate event doesn't prevent the activation.
+            let _ = self
+                .swmanager_sender
+                .send(ServiceWorkerMsg::Activated(self.scope_url.clone()));
+        }
     }
 }
 
@@ -458,4 +506,13 @@ impl ServiceWorkerGlobalScopeMethods for ServiceWorkerGlobalScope {
 
     // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onmessageerror
     event_handler!(messageerror, GetOnmessageerror, SetOnmessageerror);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-oninstall
+    event_handler!(install, GetOninstall, SetOninstall);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onactivate
+    event_handler!(activate, GetOnactivate, SetOnactivate);
+
+    // https://w3c.github.io/ServiceWorker/#dom-serviceworkerglobalscope-onfetch
+    event_handler!(fetch, GetOnfetch, SetOnfetch);
 }
@@ -46,6 +46,7 @@ use html5ever::{Attribute, ExpandedName, LocalName, QualName};
 use hyper_serde::Serde;
 use mime::{self, Mime};
 use msg::constellation_msg::PipelineId;
+use net_traits::csp::Violation;
 use net_traits::{FetchMetadata, FetchResponseListener, Metadata, NetworkError};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use profile_traits::time::{
@@ -846,6 +847,14 @@ impl FetchResponseListener for ParserContext {
             .performance()
             .queue_entry(performance_entry.upcast::<PerformanceEntry>(), true);
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let parser = match self.parser.as_ref() {
+            Some(parser) => parser.root(),
+            None => return,
+        };
+        parser.document.global().report_csp_violations(violations);
+    }
 }
 
 impl PreInvoke for ParserContext {}
@@ -0,0 +1,471 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The abstract operations that readable, writable and transform streams share.
+//!
+//! <https://streams.spec.whatwg.org/>
+
+use crate::dom::bindings::callback::ExceptionHandling;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::conversions::{ConversionResult, FromJSValConvertible};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::DomObject;
+use crate::dom::bindings::settings_stack::AutoEntryScript;
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::trace::JSTraceable;
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::promisenativehandler::{Callback, PromiseNativeHandler};
+use js::conversions::StringificationBehavior;
+use js::jsapi::{HandleValueArray, Heap, IsCallable, JSAutoRealm, JSContext, JSObject};
+use js::jsapi::{JS_ClearPendingException, JS_IsExceptionPending};
+use js::jsval::{JSVal, ObjectValue, UndefinedValue};
+use js::rust::wrappers::{Call, JS_GetPendingException, JS_GetProperty, JS_SetPendingException};
+use js::rust::{HandleObject, HandleValue, MutableHandleValue};
+use malloc_size_of::MallocSizeOf;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::rc::Rc;
+
+/// A value in the queue of a controller.
+#[derive(JSTraceable, MallocSizeOf)]
+enum QueueEntry {
+    Chunk(#[ignore_malloc_size_of = "mozjs"] Box<Heap<JSVal>>, f64),
+    /// <https://streams.spec.whatwg.org/#close-sentinel>
+    Close,
+}
+
+/// <https://streams.spec.whatwg.org/#queue-with-sizes>
+#[derive(Default, JSTraceable, MallocSizeOf)]
+pub struct QueueWithSizes {
+    queue: VecDeque<QueueEntry>,
+    /// <https://streams.spec.whatwg.org/#readablestreamdefaultcontroller-queuetotalsize>
+    total_size: f64,
+}
+
+impl QueueWithSizes {
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn total_size(&self) -> f64 {
+        self.total_size
+    }
+
+    /// <https://streams.spec.whatwg.org/#enqueue-value-with-size>
+    pub fn enqueue_value_with_size(&mut self, value: HandleValue, size: f64) -> Fallible<()> {
+        // Step 2-3.
+        if !size.is_finite() || size < 0. {
+            return Err(Error::Range(
+                "The size of a chunk must be a finite, non-negative number".to_owned(),
+            ));
+        }
+        // Step 4-5.
+        self.queue
+            .push_back(QueueEntry::Chunk(Heap::boxed(value.get()), size));
+        self.total_size += size;
+        Ok(())
+    }
+
+    /// Enqueues the close sentinel of a writable stream, with a size of 0.
+    pub fn enqueue_close_sentinel(&mut self) {
+        self.queue.push_back(QueueEntry::Close);
+    }
+
+    /// <https://streams.spec.whatwg.org/#dequeue-value>
+    ///
+    /// The close sentinel dequeues as undefined.
+    pub fn dequeue_value(&mut self, mut rval: MutableHandleValue) {
+        // Step 2-3.
+        match self.queue.pop_front() {
+            Some(QueueEntry::Chunk(value, size)) => {
+                // Step 4-5.
+                self.total_size = (self.total_size - size).max(0.);
+                // Step 6.
+                rval.set(value.get());
+            },
+            Some(QueueEntry::Close) | None => rval.set(UndefinedValue()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#peek-queue-value>
+    ///
+    /// Returns false, leaving `rval` alone, if the value is the close sentinel.
+    pub fn peek_queue_value(&self, mut rval: MutableHandleValue) -> bool {
+        match self.queue.front() {
+            Some(&QueueEntry::Chunk(ref value, _)) => {
+                rval.set(value.get());
+                true
+            },
+            Some(&QueueEntry::Close) | None => false,
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#reset-queue>
+    pub fn reset(&mut self) {
+        self.queue.clear();
+        self.total_size = 0.;
+    }
+}
+
+/// <https://streams.spec.whatwg.org/#validate-and-normalize-high-water-mark>
+pub fn extract_high_water_mark(strategy: &QueuingStrategy, default: f64) -> Fallible<f64> {
+    // Step 1.
+    let high_water_mark = match strategy.highWaterMark {
+        Some(high_water_mark) => high_water_mark,
+        None => return Ok(default),
+    };
+    // Step 2-3.
+    if high_water_mark.is_nan() || high_water_mark < 0. {
+        return Err(Error::Range(
+            "The high water mark must be a non-negative number".to_owned(),
+        ));
+    }
+    // Step 4.
+    Ok(high_water_mark)
+}
+
+/// The size algorithm of a stream, from the size function of its strategy.
+///
+/// <https://streams.spec.whatwg.org/#make-size-algorithm-from-size-function>
+pub fn chunk_size(size: &Option<Rc<QueuingStrategySize>>, chunk: HandleValue) -> Fallible<f64> {
+    match *size {
+        // The exception stays pending if it throws.
+        Some(ref size) => size.Call__(chunk, ExceptionHandling::Rethrow),
+        None => Ok(1.),
+    }
+}
+
+/// Takes the pending exception of `cx`, if there is one, to use it as the
+/// reason to error a stream or reject a promise.
+#[allow(unsafe_code)]
+pub unsafe fn take_pending_exception(cx: *mut JSContext, mut rval: MutableHandleValue) {
+    rval.set(UndefinedValue());
+    if JS_IsExceptionPending(cx) {
+        if !JS_GetPendingException(cx, rval) {
+            rval.set(UndefinedValue());
+        }
+        JS_ClearPendingException(cx);
+    }
+}
+
+/// Throws `value`, for an operation that fails with `Error::JSFailed`.
+#[allow(unsafe_code)]
+pub unsafe fn throw_value(cx: *mut JSContext, value: HandleValue) -> Error {
+    JS_SetPendingException(cx, value);
+    Error::JSFailed
+}
+
+/// Converts an error to the JS value that would be thrown for it.
+#[allow(unsafe_code)]
+pub unsafe fn error_to_jsval(
+    cx: *mut JSContext,
+    global: &GlobalScope,
+    error: Error,
+    rval: MutableHandleValue,
+) {
+    if let Error::JSFailed = error {
+        return take_pending_exception(cx, rval);
+    }
+    let _ac = JSAutoRealm::new(cx, global.reflector().get_jsobject().get());
+    error.to_jsval(cx, global, rval);
+}
+
+/// A method of an underlying source, sink or transformer, which the stream
+/// algorithms call with the underlying object as `this`.
+///
+/// <https://streams.spec.whatwg.org/#create-algorithm-from-underlying-method>
+#[derive(JSTraceable, MallocSizeOf)]
+pub struct UnderlyingMethod {
+    #[ignore_malloc_size_of = "mozjs"]
+    object: Box<Heap<*mut JSObject>>,
+    #[ignore_malloc_size_of = "mozjs"]
+    method: Box<Heap<*mut JSObject>>,
+}
+
+impl UnderlyingMethod {
+    /// Looks up the method `name` of `object`, which is either undefined or
+    /// callable.
+    #[allow(unsafe_code)]
+    pub unsafe fn get(
+        cx: *mut JSContext,
+        object: HandleObject,
+        name: &str,
+    ) -> Fallible<Option<UnderlyingMethod>> {
+        if object.is_null() {
+            return Ok(None);
+        }
+        rooted!(in(cx) let mut method = UndefinedValue());
+        let name = CString::new(name).unwrap();
+        if !JS_GetProperty(cx, object, name.as_ptr(), method.handle_mut()) {
+            return Err(Error::JSFailed);
+        }
+        if method.is_undefined() {
+            return Ok(None);
+        }
+        if !method.is_object() || !IsCallable(method.to_object()) {
+            return Err(Error::Type(format!(
+                "{} of the underlying object is not callable",
+                name.to_string_lossy()
+            )));
+        }
+        Ok(Some(UnderlyingMethod {
+            object: Heap::boxed(object.get()),
+            method: Heap::boxed(method.to_object()),
+        }))
+    }
+
+    /// Calls the method. The exception stays pending if it throws.
+    #[allow(unsafe_code)]
+    pub unsafe fn call(
+        &self,
+        cx: *mut JSContext,
+        args: &[JSVal],
+        rval: MutableHandleValue,
+    ) -> Fallible<()> {
+        let global = GlobalScope::from_object(self.method.get());
+        let _aes = AutoEntryScript::new(&global);
+        let _ac = JSAutoRealm::new(cx, self.method.get());
+        rooted!(in(cx) let this = ObjectValue(self.object.get()));
+        rooted!(in(cx) let method = ObjectValue(self.method.get()));
+        let args = HandleValueArray::from_rooted_slice(args);
+        if !Call(cx, this.handle(), method.handle(), &args, rval) {
+            return Err(Error::JSFailed);
+        }
+        Ok(())
+    }
+
+    /// Calls the method, and returns a promise for its result, which is rejected
+    /// if it throws.
+    #[allow(unsafe_code)]
+    pub unsafe fn promise_call(
+        &self,
+        global: &GlobalScope,
+        cx: *mut JSContext,
+        args: &[JSVal],
+    ) -> Rc<Promise> {
+        rooted!(in(cx) let mut result = UndefinedValue());
+        if self.call(cx, args, result.handle_mut()).is_err() {
+            take_pending_exception(cx, result.handle_mut());
+            return Promise::new_rejected(global, cx, result.handle()).unwrap();
+        }
+        Promise::new_resolved(global, cx, result.handle()).unwrap()
+    }
+}
+
+/// Calls an optional method of an underlying source, sink or transformer and
+/// returns a promise for its result; a missing method does nothing.
+#[allow(unsafe_code)]
+pub unsafe fn promise_call_or_noop(
+    method: &Option<UnderlyingMethod>,
+    global: &GlobalScope,
+    cx: *mut JSContext,
+    args: &[JSVal],
+) -> Rc<Promise> {
+    match *method {
+        Some(ref method) => method.promise_call(global, cx, args),
+        None => resolved_promise(global),
+    }
+}
+
+/// The start algorithm of a stream, which runs when its controller is set up.
+pub enum StartAlgorithm<'a> {
+    /// The stream has nothing to start.
+    Nothing,
+    /// Call the start method of the underlying object with the controller.
+    Method(&'a UnderlyingMethod),
+    /// Wait for a promise, like the start promise of a transform stream.
+    Promise(&'a Promise),
+}
+
+impl<'a> StartAlgorithm<'a> {
+    /// Runs the algorithm, and returns the promise its result is resolved with.
+    /// The exception stays pending if the start method throws.
+    #[allow(unsafe_code)]
+    pub unsafe fn run(
+        &self,
+        global: &GlobalScope,
+        cx: *mut JSContext,
+        controller: JSVal,
+    ) -> Fallible<Rc<Promise>> {
+        match *self {
+            StartAlgorithm::Nothing => Ok(resolved_promise(global)),
+            StartAlgorithm::Method(method) => {
+                rooted!(in(cx) let controller = controller);
+                rooted!(in(cx) let mut result = UndefinedValue());
+                method.call(cx, &[controller.get()], result.handle_mut())?;
+                Ok(Promise::new_resolved(global, cx, result.handle()).unwrap())
+            },
+            StartAlgorithm::Promise(promise) => Ok(promise.duplicate()),
+        }
+    }
+}
+
+/// Checks the `type` member of an underlying source, sink or transformer,
+/// none of which may be given a type yet.
+#[allow(unsafe_code)]
+pub unsafe fn check_no_type(cx: *mut JSContext, object: HandleObject, name: &str) -> Fallible<()> {
+    if object.is_null() {
+        return Ok(());
+    }
+    rooted!(in(cx) let mut value = UndefinedValue());
+    let c_name = CString::new(name).unwrap();
+    if !JS_GetProperty(cx, object, c_name.as_ptr(), value.handle_mut()) {
+        return Err(Error::JSFailed);
+    }
+    if value.is_undefined() {
+        return Ok(());
+    }
+    match DOMString::from_jsval(cx, value.handle(), StringificationBehavior::Default) {
+        Ok(ConversionResult::Success(ref type_)) if &**type_ == "bytes" && name == "type" => Err(
+            Error::Range("Readable byte streams are not supported".to_owned()),
+        ),
+        Ok(_) => Err(Error::Range(format!("Invalid {}", name))),
+        Err(()) => Err(Error::JSFailed),
+    }
+}
+
+/// A promise resolved with undefined.
+pub fn resolved_promise(global: &GlobalScope) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    promise.resolve_native(&());
+    promise
+}
+
+/// A promise rejected with `reason`.
+pub fn rejected_promise(global: &GlobalScope, reason: HandleValue) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    promise.reject_native(&reason.get());
+    promise
+}
+
+/// A promise rejected with the exception for `error`.
+pub fn promise_rejected_with_error(global: &GlobalScope, error: Error) -> Rc<Promise> {
+    let promise = Promise::new(global);
+    promise.reject_error(error);
+    promise
+}
+
+/// Sets the [[PromiseIsHandled]] flag of a promise, so that rejecting it isn't
+/// reported: a promise with a reaction is handled.
+pub fn mark_as_handled(promise: &Promise) {
+    let handler = PromiseNativeHandler::new(&promise.global(), None, None);
+    promise.append_native_handler(&handler);
+}
+
+/// A promise that settles like `promise`, but is fulfilled with undefined.
+pub fn transform_to_undefined(promise: &Promise) -> Rc<Promise> {
+    let result = Promise::new(&promise.global());
+    react_to_promise(promise, Box::new(SettleWithUndefined(result.clone())));
+    result
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct SettleWithUndefined(#[ignore_malloc_size_of = "Rc"] Rc<Promise>);
+
+impl Reaction for SettleWithUndefined {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        self.0.resolve_native(&());
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        self.0.reject_native(&reason.get());
+    }
+}
+
+/// A promise that is fulfilled once all of `promises` are, or rejected as soon as
+/// one of them is.
+///
+/// <https://heycam.github.io/webidl/#wait-for-all>
+pub fn wait_for_all(global: &GlobalScope, promises: &[Rc<Promise>]) -> Rc<Promise> {
+    let result = Promise::new(global);
+    if promises.is_empty() {
+        result.resolve_native(&());
+        return result;
+    }
+    let waiting = Rc::new(WaitForAll {
+        remaining: Cell::new(promises.len()),
+        promise: result.clone(),
+    });
+    for promise in promises {
+        react_to_promise(promise, Box::new(WaitForOne(waiting.clone())));
+    }
+    result
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct WaitForAll {
+    remaining: Cell<usize>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct WaitForOne(#[ignore_malloc_size_of = "Rc"] Rc<WaitForAll>);
+
+impl Reaction for WaitForOne {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        let remaining = self.0.remaining.get() - 1;
+        self.0.remaining.set(remaining);
+        if remaining == 0 {
+            self.0.promise.resolve_native(&());
+        }
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        if !self.0.promise.is_fulfilled() {
+            self.0.promise.reject_native(&reason.get());
+        }
+    }
+}
+
+/// Steps to run upon the fulfillment or the rejection of a promise.
+///
+/// <https://heycam.github.io/webidl/#upon-fulfillment>
+pub trait Reaction: JSTraceable + MallocSizeOf {
+    fn fulfilled(&self, cx: *mut JSContext, value: HandleValue);
+    fn rejected(&self, cx: *mut JSContext, reason: HandleValue);
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+struct ReactionCallback {
+    #[ignore_malloc_size_of = "Rc"]
+    reaction: Rc<Box<dyn Reaction>>,
+    fulfilled: bool,
+}
+
+impl Callback for ReactionCallback {
+    fn callback(&self, cx: *mut JSContext, v: HandleValue) {
+        if self.fulfilled {
+            self.reaction.fulfilled(cx, v)
+        } else {
+            self.reaction.rejected(cx, v)
+        }
+    }
+}
+
+/// Runs `reaction` once `promise` is settled.
+pub fn react_to_promise(promise: &Promise, reaction: Box<dyn Reaction>) {
+    let reaction = Rc::new(reaction);
+    let handler = PromiseNativeHandler::new(
+        &promise.global(),
+        Some(Box::new(ReactionCallback {
+            reaction: reaction.clone(),
+            fulfilled: true,
+        })),
+        Some(Box::new(ReactionCallback {
+            reaction,
+            fulfilled: false,
+        })),
+    );
+    promise.append_native_handler(&handler);
+}
+
+/// The JS value of a DOM object, to pass it to an underlying method.
+pub fn object_value<T: DomObject>(object: &T) -> JSVal {
+    ObjectValue(object.reflector().get_jsobject().get())
+}
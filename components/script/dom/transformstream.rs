@@ -0,0 +1,312 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategy;
+use crate::dom::bindings::codegen::Bindings::TransformStreamBinding::{
+    self, TransformStreamMethods,
+};
+use crate::dom::bindings::error::Fallible;
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::readablestream::{ReadableStream, ReadableStreamState};
+use crate::dom::readablestreamdefaultcontroller::UnderlyingSource;
+use crate::dom::streams::{check_no_type, extract_high_water_mark, object_value};
+use crate::dom::streams::{react_to_promise, resolved_promise};
+use crate::dom::streams::{Reaction, StartAlgorithm, UnderlyingMethod};
+use crate::dom::transformstreamdefaultcontroller::TransformStreamDefaultController;
+use crate::dom::writablestream::{WritableStream, WritableStreamState};
+use crate::dom::writablestreamdefaultcontroller::UnderlyingSink;
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSContext, JSObject};
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::HandleValue;
+use std::cell::Cell;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#ts-class>
+#[dom_struct]
+pub struct TransformStream {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#transformstream-backpressure>
+    backpressure: Cell<bool>,
+    /// <https://streams.spec.whatwg.org/#transformstream-backpressurechangepromise>
+    #[ignore_malloc_size_of = "Rc"]
+    backpressure_change_promise: DomRefCell<Option<Rc<Promise>>>,
+    /// <https://streams.spec.whatwg.org/#transformstream-controller>
+    controller: MutNullableDom<TransformStreamDefaultController>,
+    /// <https://streams.spec.whatwg.org/#transformstream-readable>
+    readable: MutNullableDom<ReadableStream>,
+    /// <https://streams.spec.whatwg.org/#transformstream-writable>
+    writable: MutNullableDom<WritableStream>,
+}
+
+impl TransformStream {
+    fn new_inherited() -> TransformStream {
+        TransformStream {
+            reflector_: Reflector::new(),
+            backpressure: Cell::new(false),
+            backpressure_change_promise: DomRefCell::new(None),
+            controller: Default::default(),
+            readable: Default::default(),
+            writable: Default::default(),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#ts-constructor
+    #[allow(unsafe_code)]
+    pub unsafe fn Constructor(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        transformer: Option<*mut JSObject>,
+        writable_strategy: &QueuingStrategy,
+        readable_strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<TransformStream>> {
+        // Step 1.
+        rooted!(in(cx) let transformer = transformer.unwrap_or(ptr::null_mut()));
+        // Step 2.
+        let flush = UnderlyingMethod::get(cx, transformer.handle(), "flush")?;
+        // Step 3.
+        check_no_type(cx, transformer.handle(), "readableType")?;
+        let start = UnderlyingMethod::get(cx, transformer.handle(), "start")?;
+        let transform = UnderlyingMethod::get(cx, transformer.handle(), "transform")?;
+        // Step 4.
+        check_no_type(cx, transformer.handle(), "writableType")?;
+        // Step 5-6.
+        let readable_high_water_mark = extract_high_water_mark(readable_strategy, 0.)?;
+        let readable_size = readable_strategy.size.clone();
+        // Step 7-8.
+        let writable_high_water_mark = extract_high_water_mark(writable_strategy, 1.)?;
+        let writable_size = writable_strategy.size.clone();
+        // Step 9.
+        let start_promise = Promise::new(global);
+        // Step 10, https://streams.spec.whatwg.org/#initialize-transform-stream
+        let stream = reflect_dom_object(
+            Box::new(TransformStream::new_inherited()),
+            global,
+            TransformStreamBinding::Wrap,
+        );
+        let writable = WritableStream::create(
+            global,
+            UnderlyingSink::Transform(Dom::from_ref(&*stream)),
+            StartAlgorithm::Promise(&start_promise),
+            writable_high_water_mark,
+            writable_size,
+        );
+        stream.writable.set(Some(&writable));
+        let readable = ReadableStream::create(
+            global,
+            UnderlyingSource::Transform(Dom::from_ref(&*stream)),
+            StartAlgorithm::Promise(&start_promise),
+            readable_high_water_mark,
+            readable_size,
+        );
+        stream.readable.set(Some(&readable));
+        stream.set_backpressure(true);
+        // Step 11.
+        let controller = TransformStreamDefaultController::new(global, &stream, transform, flush);
+        stream.controller.set(Some(&controller));
+        // Step 12.
+        rooted!(in(cx) let mut result = UndefinedValue());
+        if let Some(ref start) = start {
+            start.call(cx, &[object_value(&*controller)], result.handle_mut())?;
+        }
+        start_promise.resolve_native(&result.get());
+        Ok(stream)
+    }
+
+    fn controller(&self) -> DomRoot<TransformStreamDefaultController> {
+        self.controller
+            .get()
+            .expect("The controller of a stream is set up with it")
+    }
+
+    pub fn readable(&self) -> DomRoot<ReadableStream> {
+        self.readable
+            .get()
+            .expect("The readable side of a stream is set up with it")
+    }
+
+    pub fn writable(&self) -> DomRoot<WritableStream> {
+        self.writable
+            .get()
+            .expect("The writable side of a stream is set up with it")
+    }
+
+    pub fn backpressure(&self) -> bool {
+        self.backpressure.get()
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-error>
+    pub fn error(&self, e: HandleValue) {
+        // Step 1.
+        self.readable().controller().error(e);
+        // Step 2.
+        self.error_writable_and_unblock_write(e);
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-error-writable-and-unblock-write>
+    pub fn error_writable_and_unblock_write(&self, e: HandleValue) {
+        // Step 2.
+        self.writable().controller().error_if_needed(e);
+        // Step 3.
+        if self.backpressure.get() {
+            self.set_backpressure(false);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-set-backpressure>
+    pub fn set_backpressure(&self, backpressure: bool) {
+        // Step 2.
+        let promise = self.backpressure_change_promise.borrow_mut().take();
+        if let Some(promise) = promise {
+            promise.resolve_native(&());
+        }
+        // Step 3.
+        *self.backpressure_change_promise.borrow_mut() = Some(Promise::new(&self.global()));
+        // Step 4.
+        self.backpressure.set(backpressure);
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-write-algorithm>
+    pub fn sink_write(&self, chunk: HandleValue) -> Rc<Promise> {
+        // Step 3.
+        if self.backpressure.get() {
+            // Step 3.1.
+            let promise = self
+                .backpressure_change_promise
+                .borrow()
+                .clone()
+                .expect("The backpressure change promise is set up with the stream");
+            // Step 3.2.
+            let result = Promise::new(&self.global());
+            react_to_promise(
+                &promise,
+                Box::new(BackpressureChangeReaction {
+                    stream: Dom::from_ref(self),
+                    chunk: Heap::boxed(chunk.get()),
+                    promise: result.clone(),
+                }),
+            );
+            return result;
+        }
+        // Step 4.
+        self.controller().perform_transform(chunk)
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-abort-algorithm>
+    pub fn sink_abort(&self, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        self.error(reason);
+        // Step 2.
+        resolved_promise(&self.global())
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-sink-close-algorithm>
+    pub fn sink_close(&self) -> Rc<Promise> {
+        // Step 3.
+        let flush_promise = self.controller().flush();
+        // Step 5.
+        let result = Promise::new(&self.global());
+        react_to_promise(
+            &flush_promise,
+            Box::new(FlushReaction {
+                stream: Dom::from_ref(self),
+                promise: result.clone(),
+            }),
+        );
+        result
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-source-pull>
+    pub fn source_pull(&self) -> Rc<Promise> {
+        // Step 2.
+        self.set_backpressure(false);
+        // Step 3.
+        self.backpressure_change_promise
+            .borrow()
+            .clone()
+            .expect("The backpressure change promise is set up with the stream")
+    }
+}
+
+impl TransformStreamMethods for TransformStream {
+    // https://streams.spec.whatwg.org/#ts-readable
+    fn Readable(&self) -> DomRoot<ReadableStream> {
+        self.readable()
+    }
+
+    // https://streams.spec.whatwg.org/#ts-writable
+    fn Writable(&self) -> DomRoot<WritableStream> {
+        self.writable()
+    }
+}
+
+/// Step 3.2 of <https://streams.spec.whatwg.org/#transform-stream-default-sink-write-algorithm>.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct BackpressureChangeReaction {
+    stream: Dom<TransformStream>,
+    #[ignore_malloc_size_of = "mozjs"]
+    chunk: Box<Heap<JSVal>>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl Reaction for BackpressureChangeReaction {
+    fn fulfilled(&self, cx: *mut JSContext, _value: HandleValue) {
+        // Step 3.2.1-3.2.3.
+        let writable = self.stream.writable();
+        if writable.state() == WritableStreamState::Erroring {
+            rooted!(in(cx) let mut stored_error = UndefinedValue());
+            writable.get_stored_error(stored_error.handle_mut());
+            return self.promise.reject_native(&stored_error.get());
+        }
+        // Step 3.2.4-3.2.5.
+        rooted!(in(cx) let chunk = self.chunk.get());
+        let transform_promise = self.stream.controller().perform_transform(chunk.handle());
+        self.promise.resolve_native(&transform_promise);
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        self.promise.reject_native(&reason.get());
+    }
+}
+
+/// Step 5 of <https://streams.spec.whatwg.org/#transform-stream-default-sink-close-algorithm>.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct FlushReaction {
+    stream: Dom<TransformStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl Reaction for FlushReaction {
+    fn fulfilled(&self, cx: *mut JSContext, _value: HandleValue) {
+        let readable = self.stream.readable();
+        // Step 5.1.1.
+        if readable.state() == ReadableStreamState::Errored {
+            rooted!(in(cx) let mut stored_error = UndefinedValue());
+            readable.get_stored_error(stored_error.handle_mut());
+            return self.promise.reject_native(&stored_error.get());
+        }
+        // Step 5.1.2-5.1.3.
+        readable.controller().close();
+        self.promise.resolve_native(&());
+    }
+
+    fn rejected(&self, cx: *mut JSContext, reason: HandleValue) {
+        // Step 5.2.1.
+        self.stream.error(reason);
+        // Step 5.2.2.
+        let readable = self.stream.readable();
+        rooted!(in(cx) let mut stored_error = UndefinedValue());
+        readable.get_stored_error(stored_error.handle_mut());
+        self.promise.reject_native(&stored_error.get());
+    }
+}
@@ -0,0 +1,220 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::enter_realm;
+use crate::dom::bindings::codegen::Bindings::TransformStreamDefaultControllerBinding::{
+    self, TransformStreamDefaultControllerMethods,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::streams::{error_to_jsval, object_value, promise_call_or_noop};
+use crate::dom::streams::{react_to_promise, take_pending_exception};
+use crate::dom::streams::{throw_value, Reaction, UnderlyingMethod};
+use crate::dom::transformstream::TransformStream;
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#ts-default-controller-class>
+#[dom_struct]
+pub struct TransformStreamDefaultController {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#transformstreamdefaultcontroller-stream>
+    stream: Dom<TransformStream>,
+    /// The transform method of the transformer, or None to enqueue chunks as
+    /// they are.
+    transform: Option<UnderlyingMethod>,
+    flush: Option<UnderlyingMethod>,
+}
+
+impl TransformStreamDefaultController {
+    fn new_inherited(
+        stream: &TransformStream,
+        transform: Option<UnderlyingMethod>,
+        flush: Option<UnderlyingMethod>,
+    ) -> TransformStreamDefaultController {
+        TransformStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            transform,
+            flush,
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-transform-stream-default-controller-from-transformer>
+    pub fn new(
+        global: &GlobalScope,
+        stream: &TransformStream,
+        transform: Option<UnderlyingMethod>,
+        flush: Option<UnderlyingMethod>,
+    ) -> DomRoot<TransformStreamDefaultController> {
+        reflect_dom_object(
+            Box::new(TransformStreamDefaultController::new_inherited(
+                stream, transform, flush,
+            )),
+            global,
+            TransformStreamDefaultControllerBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-enqueue>
+    ///
+    /// The exception stays pending if the chunk cannot be enqueued.
+    #[allow(unsafe_code)]
+    unsafe fn enqueue(&self, cx: *mut JSContext, chunk: HandleValue) -> Fallible<()> {
+        // Step 2.
+        let readable = self.stream.readable();
+        let readable_controller = readable.controller();
+        // Step 3.
+        if !readable_controller.can_close_or_enqueue() {
+            return Err(Error::Type(
+                "The readable side of the stream cannot be enqueued to".to_owned(),
+            ));
+        }
+        // Step 4-5.
+        if readable_controller.enqueue(cx, chunk).is_err() {
+            // Step 5.1.
+            rooted!(in(cx) let mut reason = UndefinedValue());
+            take_pending_exception(cx, reason.handle_mut());
+            self.stream
+                .error_writable_and_unblock_write(reason.handle());
+            // Step 5.2.
+            rooted!(in(cx) let mut stored_error = UndefinedValue());
+            readable.get_stored_error(stored_error.handle_mut());
+            return Err(throw_value(cx, stored_error.handle()));
+        }
+        // Step 6-7.
+        let backpressure = readable_controller.has_backpressure();
+        if backpressure != self.stream.backpressure() {
+            // Step 7.1-7.2.
+            self.stream.set_backpressure(true);
+        }
+        Ok(())
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-perform-transform>
+    pub fn perform_transform(&self, chunk: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        let transform_promise = self.transform_algorithm(chunk);
+        // Step 2.
+        let result = Promise::new(&self.global());
+        react_to_promise(
+            &transform_promise,
+            Box::new(TransformReaction {
+                stream: Dom::from_ref(&*self.stream),
+                promise: result.clone(),
+            }),
+        );
+        result
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-transform-stream-default-controller-from-transformer>
+    #[allow(unsafe_code)]
+    fn transform_algorithm(&self, chunk: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        let cx = global.get_cx();
+        match self.transform {
+            // Step 4.
+            Some(ref transform) => unsafe {
+                transform.promise_call(&global, cx, &[chunk.get(), object_value(self)])
+            },
+            // Step 2.
+            None => unsafe {
+                let _ac = enter_realm(self);
+                let promise = Promise::new(&global);
+                match self.enqueue(cx, chunk) {
+                    Ok(()) => promise.resolve_native(&()),
+                    Err(error) => {
+                        rooted!(in(cx) let mut reason = UndefinedValue());
+                        error_to_jsval(cx, &global, error, reason.handle_mut());
+                        promise.reject_native(&reason.get());
+                    },
+                }
+                promise
+            },
+        }
+    }
+
+    /// The flush algorithm of the transformer, which resolves with undefined if
+    /// there is none.
+    #[allow(unsafe_code)]
+    pub fn flush(&self) -> Rc<Promise> {
+        let global = self.global();
+        unsafe {
+            promise_call_or_noop(&self.flush, &global, global.get_cx(), &[object_value(self)])
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#transform-stream-default-controller-terminate>
+    #[allow(unsafe_code)]
+    fn terminate(&self) {
+        let global = self.global();
+        let cx = global.get_cx();
+        // Step 2-3.
+        self.stream.readable().controller().close();
+        // Step 4.
+        rooted!(in(cx) let mut error = UndefinedValue());
+        unsafe {
+            error_to_jsval(
+                cx,
+                &global,
+                Error::Type("The transform stream was terminated".to_owned()),
+                error.handle_mut(),
+            )
+        };
+        // Step 5.
+        self.stream.error_writable_and_unblock_write(error.handle());
+    }
+}
+
+impl TransformStreamDefaultControllerMethods for TransformStreamDefaultController {
+    // https://streams.spec.whatwg.org/#ts-default-controller-desired-size
+    fn GetDesiredSize(&self) -> Option<f64> {
+        self.stream.readable().controller().get_desired_size()
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-enqueue
+    #[allow(unsafe_code)]
+    unsafe fn Enqueue(&self, cx: *mut JSContext, chunk: HandleValue) -> Fallible<()> {
+        self.enqueue(cx, chunk)
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-error
+    #[allow(unsafe_code)]
+    unsafe fn Error(&self, _cx: *mut JSContext, reason: HandleValue) {
+        self.stream.error(reason);
+    }
+
+    // https://streams.spec.whatwg.org/#ts-default-controller-terminate
+    fn Terminate(&self) {
+        self.terminate();
+    }
+}
+
+/// Step 2 of <https://streams.spec.whatwg.org/#transform-stream-default-controller-perform-transform>.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct TransformReaction {
+    stream: Dom<TransformStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl Reaction for TransformReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        self.promise.resolve_native(&());
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 2.1.
+        self.stream.error(reason);
+        // Step 2.2.
+        self.promise.reject_native(&reason.get());
+    }
+}
@@ -3,6 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::compartments::InCompartment;
+use crate::devtools::DevtoolsMutation;
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::DocumentBinding::{
     DocumentMethods, DocumentReadyState,
@@ -24,11 +25,12 @@ use crate::dom::bindings::refcounted::Trusted;
 use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
 use crate::dom::bindings::str::{DOMString, USVString};
-use crate::dom::bindings::structuredclone::StructuredCloneData;
+use crate::dom::bindings::structuredclone;
 use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::bindings::utils::{GlobalStaticData, WindowProxyHandler};
 use crate::dom::bindings::weakref::DOMTracker;
 use crate::dom::bluetooth::BluetoothExtraPermissionData;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::cssstyledeclaration::{CSSModificationAccess, CSSStyleDeclaration, CSSStyleOwner};
 use crate::dom::customelementregistry::CustomElementRegistry;
@@ -39,6 +41,7 @@ use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::hashchangeevent::HashChangeEvent;
 use crate::dom::history::History;
+use crate::dom::idbfactory::IDBFactory;
 use crate::dom::location::Location;
 use crate::dom::mediaquerylist::{MediaQueryList, MediaQueryListMatchState};
 use crate::dom::mediaquerylistevent::MediaQueryListEvent;
@@ -55,7 +58,7 @@ use crate::dom::worklet::Worklet;
 use crate::dom::workletglobalscope::WorkletGlobalScopeType;
 use crate::fetch;
 use crate::layout_image::fetch_image_for_layout;
-use crate::microtask::MicrotaskQueue;
+use crate::microtask::{Microtask, MicrotaskQueue};
 use crate::script_runtime::{
     CommonScriptMsg, Runtime, ScriptChan, ScriptPort, ScriptThreadEventCategory,
 };
@@ -79,12 +82,13 @@ use ipc_channel::ipc::{channel, IpcSender};
 use ipc_channel::router::ROUTER;
 use js::jsapi::JSAutoRealm;
 use js::jsapi::JSContext;
+use js::jsapi::JSObject;
 use js::jsapi::JSPROP_ENUMERATE;
 use js::jsapi::{GCReason, JS_GC};
 use js::jsval::JSVal;
 use js::jsval::UndefinedValue;
 use js::rust::wrappers::JS_DefineProperty;
-use js::rust::HandleValue;
+use js::rust::{CustomAutoRooterGuard, HandleValue};
 use media::WindowGLContext;
 use msg::constellation_msg::PipelineId;
 use net_traits::image_cache::{ImageCache, ImageResponder, ImageResponse};
@@ -97,13 +101,15 @@ use profile_traits::ipc as ProfiledIpc;
 use profile_traits::mem::ProfilerChan as MemProfilerChan;
 use profile_traits::time::{ProfilerChan as TimeProfilerChan, ProfilerMsg};
 use script_layout_interface::message::{Msg, QueryMsg, Reflow, ReflowGoal, ScriptReflow};
+use script_layout_interface::rpc::{BoxSizes, BoxSizesResponse};
 use script_layout_interface::rpc::{ContentBoxResponse, ContentBoxesResponse, LayoutRPC};
 use script_layout_interface::rpc::{
     NodeScrollIdResponse, ResolvedStyleResponse, TextIndexResponse,
 };
 use script_layout_interface::{PendingImageState, TrustedNodeAddress};
+use script_traits::transferable::StructuredSerializedData;
 use script_traits::webdriver_msg::{WebDriverJSError, WebDriverJSResult};
-use script_traits::{ConstellationControlMsg, DocumentState, LoadData};
+use script_traits::{ConstellationControlMsg, DocumentState, LoadData, PrintSettings};
 use script_traits::{ScriptMsg, ScriptToConstellationChan, ScrollState, TimerEvent, TimerEventId};
 use script_traits::{TimerSchedulerMsg, WindowSizeData, WindowSizeType};
 use selectors::attr::CaseSensitivity;
@@ -166,6 +172,7 @@ pub enum ReflowReason {
     IFrameLoadEvent,
     MissingExplicitReflow,
     ElementStateChanged,
+    Print,
 }
 
 #[dom_struct]
@@ -197,6 +204,10 @@ pub struct Window {
     devtools_markers: DomRefCell<HashSet<TimelineMarkerType>>,
     #[ignore_malloc_size_of = "channels are hard"]
     devtools_marker_sender: DomRefCell<Option<IpcSender<Option<TimelineMarker>>>>,
+    /// Whether devtools want to be told of the mutations of the document.
+    devtools_wants_mutations: Cell<bool>,
+    /// The mutations of the document devtools were not told of yet.
+    devtools_mutations: DomRefCell<Vec<DevtoolsMutation>>,
 
     /// Pending resize event, if any.
     resize_event: Cell<Option<(WindowSizeData, WindowSizeType)>>,
@@ -595,6 +606,24 @@ impl WindowMethods for Window {
         receiver.recv().unwrap();
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-confirm
+    fn Confirm(&self, s: DOMString) -> bool {
+        let (sender, receiver) =
+            ProfiledIpc::channel(self.global().time_profiler_chan().clone()).unwrap();
+        let msg = EmbedderMsg::Confirm(s.to_string(), sender);
+        self.send_to_embedder(msg);
+        receiver.recv().unwrap()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-prompt
+    fn Prompt(&self, message: DOMString, default: DOMString) -> Option<DOMString> {
+        let (sender, receiver) =
+            ProfiledIpc::channel(self.global().time_profiler_chan().clone()).unwrap();
+        let msg = EmbedderMsg::Prompt(message.to_string(), default.to_string(), sender);
+        self.send_to_embedder(msg);
+        receiver.recv().unwrap().map(DOMString::from)
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-window-stop
     fn Stop(&self) {
         // TODO: Cancel ongoing navigation.
@@ -709,6 +738,16 @@ impl WindowMethods for Window {
         self.upcast::<GlobalScope>().crypto()
     }
 
+    // https://w3c.github.io/IndexedDB/#dom-windoworworkerglobalscope-indexeddb
+    fn IndexedDB(&self) -> DomRoot<IDBFactory> {
+        self.upcast::<GlobalScope>().indexed_db()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#self-caches
+    fn Caches(&self) -> DomRoot<CacheStorage> {
+        self.upcast::<GlobalScope>().caches()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-frameelement
     fn GetFrameElement(&self) -> Option<DomRoot<Element>> {
         // Steps 1-3.
@@ -909,6 +948,7 @@ impl WindowMethods for Window {
         cx: *mut JSContext,
         message: HandleValue,
         origin: DOMString,
+        transfer: CustomAutoRooterGuard<Vec<*mut JSObject>>,
     ) -> ErrorResult {
         let source_global = GlobalScope::incumbent().expect("no incumbent global??");
         let source = source_global.as_window();
@@ -924,8 +964,7 @@ impl WindowMethods for Window {
         };
 
         // Step 1-2, 6-8.
-        // TODO(#12717): Should implement the `transfer` argument.
-        let data = StructuredCloneData::write(cx, message)?;
+        let data = structuredclone::write(cx, message, Some(transfer))?;
 
         // Step 9.
         self.post_message(origin, &*source.window_proxy(), data);
@@ -1279,6 +1318,9 @@ impl Window {
             custom_elements.teardown();
         }
 
+        // Stop receiving messages for the ports and broadcast channels of this window.
+        self.upcast::<GlobalScope>().remove_web_messaging_infra();
+
         // The above code may not catch all DOM objects (e.g. DOM
         // objects removed from the tree that haven't been collected
         // yet). There should not be any such DOM nodes with layout
@@ -1623,6 +1665,14 @@ impl Window {
         )
     }
 
+    /// Lay the document out and print it on pages, as a PDF document.
+    pub fn print_to_pdf(&self, settings: PrintSettings) -> Option<Vec<u8>> {
+        self.reflow(ReflowGoal::Full, ReflowReason::Print);
+        let (sender, receiver) = channel().ok()?;
+        self.layout_chan.send(Msg::Print(settings, sender)).ok()?;
+        receiver.recv().ok()?
+    }
+
     pub fn layout(&self) -> &dyn LayoutRPC {
         &*self.layout_rpc
     }
@@ -1643,6 +1693,14 @@ impl Window {
         rects
     }
 
+    pub fn box_sizes_query(&self, node: &Node) -> Option<BoxSizes> {
+        if !self.layout_reflow(QueryMsg::BoxSizesQuery(node.to_opaque())) {
+            return None;
+        }
+        let BoxSizesResponse(sizes) = self.layout_rpc.box_sizes();
+        sizes
+    }
+
     pub fn client_rect_query(&self, node: &Node) -> Rect<i32> {
         if !self.layout_reflow(QueryMsg::NodeGeometryQuery(node.to_opaque())) {
             return Rect::zero();
@@ -1980,6 +2038,32 @@ impl Window {
         }
     }
 
+    pub fn devtools_wants_mutations(&self) -> bool {
+        self.devtools_wants_mutations.get()
+    }
+
+    pub fn set_devtools_wants_mutations(&self, value: bool) {
+        self.devtools_wants_mutations.set(value);
+        if !value {
+            self.devtools_mutations.borrow_mut().clear();
+        }
+    }
+
+    /// Queue a mutation of the document to tell devtools of at the next microtask checkpoint.
+    pub fn queue_devtools_mutation(&self, mutation: DevtoolsMutation) {
+        let mut mutations = self.devtools_mutations.borrow_mut();
+        if mutations.is_empty() {
+            ScriptThread::enqueue_microtask(Microtask::NotifyDevtoolsOfMutations(
+                self.upcast::<GlobalScope>().pipeline_id(),
+            ));
+        }
+        mutations.push(mutation);
+    }
+
+    pub fn take_devtools_mutations(&self) -> Vec<DevtoolsMutation> {
+        mem::replace(&mut *self.devtools_mutations.borrow_mut(), vec![])
+    }
+
     pub fn set_webdriver_script_chan(&self, chan: Option<IpcSender<WebDriverJSResult>>) {
         *self.webdriver_script_chan.borrow_mut() = chan;
     }
@@ -2151,6 +2235,8 @@ impl Window {
             current_state: Cell::new(WindowState::Alive),
             devtools_marker_sender: Default::default(),
             devtools_markers: Default::default(),
+            devtools_wants_mutations: Default::default(),
+            devtools_mutations: Default::default(),
             webdriver_script_chan: Default::default(),
             error_reporter,
             scroll_offsets: Default::default(),
@@ -2217,6 +2303,7 @@ fn debug_reflow_events(id: PipelineId, reflow_goal: &ReflowGoal, reason: &Reflow
         ReflowGoal::LayoutQuery(ref query_msg, _) => match query_msg {
             &QueryMsg::ContentBoxQuery(_n) => "\tContentBoxQuery",
             &QueryMsg::ContentBoxesQuery(_n) => "\tContentBoxesQuery",
+            &QueryMsg::BoxSizesQuery(_n) => "\tBoxSizesQuery",
             &QueryMsg::NodesFromPointQuery(..) => "\tNodesFromPointQuery",
             &QueryMsg::NodeGeometryQuery(_n) => "\tNodeGeometryQuery",
             &QueryMsg::NodeScrollGeometryQuery(_n) => "\tNodeScrollGeometryQuery",
@@ -2250,6 +2337,7 @@ fn debug_reflow_events(id: PipelineId, reflow_goal: &ReflowGoal, reason: &Reflow
         ReflowReason::IFrameLoadEvent => "\tIFrameLoadEvent",
         ReflowReason::MissingExplicitReflow => "\tMissingExplicitReflow",
         ReflowReason::ElementStateChanged => "\tElementStateChanged",
+        ReflowReason::Print => "\tPrint",
     });
 
     println!("{}", debug_msg);
@@ -2261,7 +2349,7 @@ impl Window {
         &self,
         target_origin: Option<ImmutableOrigin>,
         source: &WindowProxy,
-        serialize_with_transfer_result: StructuredCloneData,
+        serialize_with_transfer_result: StructuredSerializedData,
     ) {
         let this = Trusted::new(self);
         let source = Trusted::new(source);
@@ -2281,15 +2369,18 @@ impl Window {
             let obj = this.reflector().get_jsobject();
             let _ac = JSAutoRealm::new(cx, obj.get());
             rooted!(in(cx) let mut message_clone = UndefinedValue());
-            serialize_with_transfer_result.read(
+            let ports = match structuredclone::read(
                 this.upcast(),
+                serialize_with_transfer_result,
                 message_clone.handle_mut(),
-            );
-
-            // Step 7.6.
-            // TODO: MessagePort array.
+            ) {
+                Ok(ports) => ports,
+                Err(()) => {
+                    return MessageEvent::dispatch_error(this.upcast(), this.upcast());
+                },
+            };
 
-            // Step 7.7.
+            // Step 7.6-7.7.
             // TODO(#12719): Set the other attributes.
             MessageEvent::dispatch_jsval(
                 this.upcast(),
@@ -2297,6 +2388,7 @@ impl Window {
                 message_clone.handle(),
                 None,
                 Some(&*source),
+                ports,
             );
         });
         // FIXME(nox): Why are errors silenced here?
@@ -16,9 +16,11 @@ use crate::dom::bindings::root::{DomRoot, MutNullableDom};
 use crate::dom::bindings::settings_stack::AutoEntryScript;
 use crate::dom::bindings::str::{DOMString, USVString};
 use crate::dom::bindings::trace::RootedTraceableBox;
+use crate::dom::cachestorage::CacheStorage;
 use crate::dom::crypto::Crypto;
 use crate::dom::dedicatedworkerglobalscope::DedicatedWorkerGlobalScope;
 use crate::dom::globalscope::GlobalScope;
+use crate::dom::idbfactory::IDBFactory;
 use crate::dom::performance::Performance;
 use crate::dom::promise::Promise;
 use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
@@ -32,23 +34,25 @@ use crate::task_source::dom_manipulation::DOMManipulationTaskSource;
 use crate::task_source::file_reading::FileReadingTaskSource;
 use crate::task_source::networking::NetworkingTaskSource;
 use crate::task_source::performance_timeline::PerformanceTimelineTaskSource;
+use crate::task_source::port_message::PortMessageQueue;
 use crate::task_source::remote_event::RemoteEventTaskSource;
 use crate::task_source::websocket::WebsocketTaskSource;
 use crate::timers::{IsInterval, TimerCallback};
 use crossbeam_channel::Receiver;
 use devtools_traits::{DevtoolScriptControlMsg, WorkerId};
 use dom_struct::dom_struct;
-use ipc_channel::ipc::IpcSender;
+use ipc_channel::ipc::{self, IpcSender};
 use js::jsapi::{JSAutoRealm, JSContext};
 use js::jsval::UndefinedValue;
 use js::panic::maybe_resume_unwind;
 use js::rust::{HandleValue, ParentRuntime};
-use msg::constellation_msg::PipelineId;
+use msg::constellation_msg::{PipelineId, PipelineNamespace};
+use net_traits::csp::CspList;
 use net_traits::request::{
     CredentialsMode, Destination, ParserMetadata, RequestBuilder as NetRequestInit,
 };
-use net_traits::IpcSend;
-use script_traits::WorkerGlobalScopeInit;
+use net_traits::{IpcSend, Metadata};
+use script_traits::{ScriptMsg, ScriptToConstellationChan, WorkerGlobalScopeInit};
 use script_traits::{TimerEvent, TimerEventId};
 use servo_url::{MutableOrigin, ServoUrl};
 use std::cell::Ref;
@@ -80,6 +84,17 @@ pub fn prepare_workerscope_init(
     init
 }
 
+/// Install a pipeline namespace on the current worker thread, so that the worker
+/// can generate ids of its own, like the ones of the message ports it creates.
+pub fn install_worker_pipeline_namespace(script_to_constellation_chan: &ScriptToConstellationChan) {
+    let (sender, receiver) = ipc::channel().expect("ipc channel failure");
+    let _ = script_to_constellation_chan.send(ScriptMsg::NewPipelineNamespace(sender));
+    match receiver.recv() {
+        Ok(namespace_id) => PipelineNamespace::install(namespace_id),
+        Err(e) => warn!("Worker didn't get a pipeline namespace ({:?}).", e),
+    }
+}
+
 // https://html.spec.whatwg.org/multipage/#the-workerglobalscope-common-interface
 #[dom_struct]
 pub struct WorkerGlobalScope {
@@ -90,6 +105,8 @@ pub struct WorkerGlobalScope {
 
     worker_id: WorkerId,
     worker_url: DomRefCell<ServoUrl>,
+    /// <https://w3c.github.io/webappsec-csp/#initialize-global-object-csp>
+    csp_list: DomRefCell<Option<CspList>>,
     #[ignore_malloc_size_of = "Arc"]
     closing: Option<Arc<AtomicBool>>,
     #[ignore_malloc_size_of = "Defined in js"]
@@ -141,6 +158,7 @@ impl WorkerGlobalScope {
             worker_name,
             worker_type,
             worker_url: DomRefCell::new(worker_url),
+            csp_list: DomRefCell::new(None),
             closing,
             runtime,
             location: Default::default(),
@@ -184,6 +202,25 @@ impl WorkerGlobalScope {
         *self.worker_url.borrow_mut() = url;
     }
 
+    /// Set the policies of this worker from the response of its script,
+    /// or from those of its owner if the script was loaded from a local URL.
+    /// <https://w3c.github.io/webappsec-csp/#initialize-global-object-csp>
+    pub fn initialize_csp_list(&self, metadata: &Metadata, owner_csp_list: Option<CspList>) {
+        let csp_list = match metadata.final_url.scheme() {
+            "about" | "blob" | "data" | "filesystem" => owner_csp_list,
+            _ => metadata
+                .headers
+                .as_ref()
+                .map(|h| CspList::parse_response_headers(h))
+                .filter(|csp_list| !csp_list.is_empty()),
+        };
+        *self.csp_list.borrow_mut() = csp_list;
+    }
+
+    pub fn get_csp_list(&self) -> Option<CspList> {
+        self.csp_list.borrow().clone()
+    }
+
     pub fn get_worker_id(&self) -> WorkerId {
         self.worker_id.clone()
     }
@@ -278,6 +315,16 @@ impl WorkerGlobalScopeMethods for WorkerGlobalScope {
         self.upcast::<GlobalScope>().crypto()
     }
 
+    // https://w3c.github.io/IndexedDB/#dom-windoworworkerglobalscope-indexeddb
+    fn IndexedDB(&self) -> DomRoot<IDBFactory> {
+        self.upcast::<GlobalScope>().indexed_db()
+    }
+
+    // https://w3c.github.io/ServiceWorker/#self-caches
+    fn Caches(&self) -> DomRoot<CacheStorage> {
+        self.upcast::<GlobalScope>().caches()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-windowbase64-btoa
     fn Btoa(&self, btoa: DOMString) -> Fallible<DOMString> {
         base64_btoa(btoa)
@@ -465,6 +512,10 @@ impl WorkerGlobalScope {
         WebsocketTaskSource(self.script_chan(), self.pipeline_id())
     }
 
+    pub fn port_message_queue(&self) -> PortMessageQueue {
+        PortMessageQueue(self.script_chan(), self.pipeline_id())
+    }
+
     pub fn new_script_pair(&self) -> (Box<dyn ScriptChan + Send>, Box<dyn ScriptPort + Send>) {
         let dedicated = self.downcast::<DedicatedWorkerGlobalScope>();
         if let Some(dedicated) = dedicated {
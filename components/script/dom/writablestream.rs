@@ -0,0 +1,535 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::{
+    QueuingStrategy, QueuingStrategySize,
+};
+use crate::dom::bindings::codegen::Bindings::WritableStreamBinding::{self, WritableStreamMethods};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::streams::{check_no_type, extract_high_water_mark, mark_as_handled};
+use crate::dom::streams::{promise_rejected_with_error, react_to_promise, resolved_promise};
+use crate::dom::streams::{Reaction, StartAlgorithm, UnderlyingMethod};
+use crate::dom::writablestreamdefaultcontroller::{
+    UnderlyingSink, WritableStreamDefaultController,
+};
+use crate::dom::writablestreamdefaultwriter::WritableStreamDefaultWriter;
+use dom_struct::dom_struct;
+use js::jsapi::{Heap, JSContext, JSObject};
+use js::jsval::{JSVal, UndefinedValue};
+use js::rust::{HandleValue, MutableHandleValue};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#writablestream-state>
+#[derive(Clone, Copy, Debug, JSTraceable, MallocSizeOf, PartialEq)]
+pub enum WritableStreamState {
+    Writable,
+    Closed,
+    Erroring,
+    Errored,
+}
+
+/// <https://streams.spec.whatwg.org/#pending-abort-request>
+#[derive(JSTraceable, MallocSizeOf)]
+struct PendingAbortRequest {
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+    #[ignore_malloc_size_of = "mozjs"]
+    reason: Box<Heap<JSVal>>,
+    was_already_erroring: bool,
+}
+
+/// <https://streams.spec.whatwg.org/#ws-class>
+#[dom_struct]
+pub struct WritableStream {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#writablestream-backpressure>
+    backpressure: Cell<bool>,
+    /// <https://streams.spec.whatwg.org/#writablestream-closerequest>
+    #[ignore_malloc_size_of = "Rc"]
+    close_request: DomRefCell<Option<Rc<Promise>>>,
+    /// <https://streams.spec.whatwg.org/#writablestream-controller>
+    controller: MutNullableDom<WritableStreamDefaultController>,
+    /// <https://streams.spec.whatwg.org/#writablestream-inflightwriterequest>
+    #[ignore_malloc_size_of = "Rc"]
+    in_flight_write_request: DomRefCell<Option<Rc<Promise>>>,
+    /// <https://streams.spec.whatwg.org/#writablestream-inflightcloserequest>
+    #[ignore_malloc_size_of = "Rc"]
+    in_flight_close_request: DomRefCell<Option<Rc<Promise>>>,
+    /// <https://streams.spec.whatwg.org/#writablestream-pendingabortrequest>
+    pending_abort_request: DomRefCell<Option<PendingAbortRequest>>,
+    /// <https://streams.spec.whatwg.org/#writablestream-state>
+    state: Cell<WritableStreamState>,
+    /// <https://streams.spec.whatwg.org/#writablestream-storederror>
+    #[ignore_malloc_size_of = "mozjs"]
+    stored_error: Heap<JSVal>,
+    /// <https://streams.spec.whatwg.org/#writablestream-writer>
+    writer: MutNullableDom<WritableStreamDefaultWriter>,
+    /// <https://streams.spec.whatwg.org/#writablestream-writerequests>
+    #[ignore_malloc_size_of = "Rc"]
+    write_requests: DomRefCell<VecDeque<Rc<Promise>>>,
+}
+
+impl WritableStream {
+    fn new_inherited() -> WritableStream {
+        WritableStream {
+            reflector_: Reflector::new(),
+            backpressure: Cell::new(false),
+            close_request: DomRefCell::new(None),
+            controller: Default::default(),
+            in_flight_write_request: DomRefCell::new(None),
+            in_flight_close_request: DomRefCell::new(None),
+            pending_abort_request: DomRefCell::new(None),
+            state: Cell::new(WritableStreamState::Writable),
+            stored_error: Heap::default(),
+            writer: Default::default(),
+            write_requests: Default::default(),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#initialize-writable-stream>
+    fn new(global: &GlobalScope) -> DomRoot<WritableStream> {
+        reflect_dom_object(
+            Box::new(WritableStream::new_inherited()),
+            global,
+            WritableStreamBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#create-writable-stream>
+    #[allow(unrooted_must_root, unsafe_code)]
+    pub fn create(
+        global: &GlobalScope,
+        sink: UnderlyingSink,
+        start: StartAlgorithm,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> DomRoot<WritableStream> {
+        let stream = WritableStream::new(global);
+        unsafe {
+            WritableStreamDefaultController::set_up(&stream, sink, start, high_water_mark, size)
+                .expect("Only the start method of an underlying sink can throw");
+        }
+        stream
+    }
+
+    // https://streams.spec.whatwg.org/#ws-constructor
+    #[allow(unsafe_code)]
+    pub unsafe fn Constructor(
+        cx: *mut JSContext,
+        global: &GlobalScope,
+        underlying_sink: Option<*mut JSObject>,
+        strategy: &QueuingStrategy,
+    ) -> Fallible<DomRoot<WritableStream>> {
+        // Step 1.
+        rooted!(in(cx) let sink = underlying_sink.unwrap_or(ptr::null_mut()));
+        // Step 2.
+        let abort = UnderlyingMethod::get(cx, sink.handle(), "abort")?;
+        let close = UnderlyingMethod::get(cx, sink.handle(), "close")?;
+        let start = UnderlyingMethod::get(cx, sink.handle(), "start")?;
+        // Step 3.
+        check_no_type(cx, sink.handle(), "type")?;
+        let write = UnderlyingMethod::get(cx, sink.handle(), "write")?;
+        // Step 5.
+        let size = strategy.size.clone();
+        // Step 6.
+        let high_water_mark = extract_high_water_mark(strategy, 1.)?;
+        // Step 4.
+        let stream = WritableStream::new(global);
+        // Step 7.
+        let start = match start {
+            Some(ref start) => StartAlgorithm::Method(start),
+            None => StartAlgorithm::Nothing,
+        };
+        WritableStreamDefaultController::set_up(
+            &stream,
+            UnderlyingSink::Js {
+                abort,
+                close,
+                write,
+            },
+            start,
+            high_water_mark,
+            size,
+        )?;
+        Ok(stream)
+    }
+
+    pub fn controller(&self) -> DomRoot<WritableStreamDefaultController> {
+        self.controller
+            .get()
+            .expect("The controller of a stream is set up with it")
+    }
+
+    pub fn set_controller(&self, controller: &WritableStreamDefaultController) {
+        self.controller.set(Some(controller));
+    }
+
+    pub fn writer(&self) -> Option<DomRoot<WritableStreamDefaultWriter>> {
+        self.writer.get()
+    }
+
+    pub fn set_writer(&self, writer: Option<&WritableStreamDefaultWriter>) {
+        self.writer.set(writer);
+    }
+
+    pub fn state(&self) -> WritableStreamState {
+        self.state.get()
+    }
+
+    pub fn get_stored_error(&self, mut rval: MutableHandleValue) {
+        rval.set(self.stored_error.get());
+    }
+
+    pub fn backpressure(&self) -> bool {
+        self.backpressure.get()
+    }
+
+    /// <https://streams.spec.whatwg.org/#is-writable-stream-locked>
+    pub fn is_locked(&self) -> bool {
+        self.writer.get().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-abort>
+    pub fn abort(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        // Step 1-4.
+        match self.state.get() {
+            WritableStreamState::Closed | WritableStreamState::Errored => {
+                return resolved_promise(&global);
+            },
+            _ => {},
+        }
+        // Step 5.
+        if let Some(ref request) = *self.pending_abort_request.borrow() {
+            return request.promise.clone();
+        }
+        // Step 6-7.
+        let was_already_erroring = self.state.get() == WritableStreamState::Erroring;
+        rooted!(in(global.get_cx()) let reason = if was_already_erroring {
+            UndefinedValue()
+        } else {
+            reason.get()
+        });
+        // Step 8.
+        let promise = Promise::new(&global);
+        // Step 9.
+        *self.pending_abort_request.borrow_mut() = Some(PendingAbortRequest {
+            promise: promise.clone(),
+            reason: Heap::boxed(reason.get()),
+            was_already_erroring,
+        });
+        // Step 10.
+        if !was_already_erroring {
+            self.start_erroring(reason.handle());
+        }
+        // Step 11.
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-close>
+    pub fn close(&self) -> Rc<Promise> {
+        let global = self.global();
+        // Step 1-2.
+        match self.state.get() {
+            WritableStreamState::Closed | WritableStreamState::Errored => {
+                return promise_rejected_with_error(
+                    &global,
+                    Error::Type("The stream is closed or errored".to_owned()),
+                );
+            },
+            _ => {},
+        }
+        // Step 3-5.
+        let promise = Promise::new(&global);
+        // Step 6.
+        *self.close_request.borrow_mut() = Some(promise.clone());
+        // Step 7-8.
+        if let Some(writer) = self.writer.get() {
+            if self.backpressure.get() && self.state.get() == WritableStreamState::Writable {
+                writer.ready_promise().resolve_native(&());
+            }
+        }
+        // Step 9.
+        self.controller().close();
+        // Step 10.
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-add-write-request>
+    pub fn add_write_request(&self) -> Rc<Promise> {
+        // Step 3.
+        let promise = Promise::new(&self.global());
+        // Step 4.
+        self.write_requests.borrow_mut().push_back(promise.clone());
+        // Step 5.
+        promise
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-close-queued-or-in-flight>
+    pub fn close_queued_or_in_flight(&self) -> bool {
+        self.close_request.borrow().is_some() || self.in_flight_close_request.borrow().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-deal-with-rejection>
+    pub fn deal_with_rejection(&self, error: HandleValue) {
+        // Step 1-2.
+        if self.state.get() == WritableStreamState::Writable {
+            return self.start_erroring(error);
+        }
+        // Step 3-4.
+        self.finish_erroring();
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-start-erroring>
+    pub fn start_erroring(&self, reason: HandleValue) {
+        // Step 1-3.
+        let controller = self.controller();
+        // Step 4-5.
+        self.state.set(WritableStreamState::Erroring);
+        self.stored_error.set(reason.get());
+        // Step 6-7.
+        if let Some(writer) = self.writer.get() {
+            writer.ensure_ready_promise_rejected(reason);
+        }
+        // Step 8.
+        if !self.has_operation_marked_in_flight() && controller.started() {
+            self.finish_erroring();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-erroring>
+    pub fn finish_erroring(&self) {
+        let cx = self.global().get_cx();
+        // Step 1-3.
+        self.state.set(WritableStreamState::Errored);
+        // Step 4.
+        self.controller().error_steps();
+        // Step 5-7.
+        rooted!(in(cx) let stored_error = self.stored_error.get());
+        let write_requests = mem::replace(&mut *self.write_requests.borrow_mut(), VecDeque::new());
+        for write_request in write_requests {
+            write_request.reject_native(&stored_error.get());
+        }
+        // Step 8-9.
+        let abort_request = self.pending_abort_request.borrow_mut().take();
+        let abort_request = match abort_request {
+            Some(abort_request) => abort_request,
+            None => return self.reject_close_and_closed_promise_if_needed(),
+        };
+        // Step 10-11.
+        if abort_request.was_already_erroring {
+            abort_request.promise.reject_native(&stored_error.get());
+            return self.reject_close_and_closed_promise_if_needed();
+        }
+        // Step 12.
+        rooted!(in(cx) let reason = abort_request.reason.get());
+        let promise = self.controller().abort_steps(reason.handle());
+        // Step 13-14.
+        react_to_promise(
+            &promise,
+            Box::new(AbortReaction {
+                stream: Dom::from_ref(self),
+                promise: abort_request.promise,
+            }),
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-write>
+    pub fn finish_in_flight_write(&self) {
+        // Step 1-3.
+        let request = self.in_flight_write_request.borrow_mut().take();
+        request
+            .expect("There is no write in flight")
+            .resolve_native(&());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-write-with-error>
+    pub fn finish_in_flight_write_with_error(&self, error: HandleValue) {
+        // Step 1-3.
+        let request = self.in_flight_write_request.borrow_mut().take();
+        request
+            .expect("There is no write in flight")
+            .reject_native(&error.get());
+        // Step 4-5.
+        self.deal_with_rejection(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-close>
+    pub fn finish_in_flight_close(&self) {
+        // Step 1-3.
+        let request = self.in_flight_close_request.borrow_mut().take();
+        request
+            .expect("There is no close in flight")
+            .resolve_native(&());
+        // Step 4-6.
+        if self.state.get() == WritableStreamState::Erroring {
+            // Step 6.1.
+            self.stored_error.set(UndefinedValue());
+            // Step 6.2.
+            let abort_request = self.pending_abort_request.borrow_mut().take();
+            if let Some(abort_request) = abort_request {
+                abort_request.promise.resolve_native(&());
+            }
+        }
+        // Step 7.
+        self.state.set(WritableStreamState::Closed);
+        // Step 8-9.
+        if let Some(writer) = self.writer.get() {
+            writer.closed_promise().resolve_native(&());
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-finish-in-flight-close-with-error>
+    pub fn finish_in_flight_close_with_error(&self, error: HandleValue) {
+        // Step 1-3.
+        let request = self.in_flight_close_request.borrow_mut().take();
+        request
+            .expect("There is no close in flight")
+            .reject_native(&error.get());
+        // Step 4-5.
+        let abort_request = self.pending_abort_request.borrow_mut().take();
+        if let Some(abort_request) = abort_request {
+            abort_request.promise.reject_native(&error.get());
+        }
+        // Step 6.
+        self.deal_with_rejection(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-has-operation-marked-in-flight>
+    pub fn has_operation_marked_in_flight(&self) -> bool {
+        self.in_flight_write_request.borrow().is_some() ||
+            self.in_flight_close_request.borrow().is_some()
+    }
+
+    pub fn has_in_flight_write_request(&self) -> bool {
+        self.in_flight_write_request.borrow().is_some()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-mark-close-request-in-flight>
+    pub fn mark_close_request_in_flight(&self) {
+        let request = self.close_request.borrow_mut().take();
+        *self.in_flight_close_request.borrow_mut() = request;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-mark-first-write-request-in-flight>
+    pub fn mark_first_write_request_in_flight(&self) {
+        let request = self.write_requests.borrow_mut().pop_front();
+        *self.in_flight_write_request.borrow_mut() = request;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-reject-close-and-closed-promise-if-needed>
+    fn reject_close_and_closed_promise_if_needed(&self) {
+        let cx = self.global().get_cx();
+        rooted!(in(cx) let stored_error = self.stored_error.get());
+        // Step 2.
+        let close_request = self.close_request.borrow_mut().take();
+        if let Some(close_request) = close_request {
+            close_request.reject_native(&stored_error.get());
+        }
+        // Step 3-4.
+        if let Some(writer) = self.writer.get() {
+            let closed_promise = writer.closed_promise();
+            closed_promise.reject_native(&stored_error.get());
+            mark_as_handled(&closed_promise);
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-update-backpressure>
+    pub fn update_backpressure(&self, backpressure: bool) {
+        // Step 3-4.
+        if let Some(writer) = self.writer.get() {
+            if backpressure != self.backpressure.get() {
+                if backpressure {
+                    // Step 4.1.
+                    writer.set_ready_promise(Promise::new(&self.global()));
+                } else {
+                    // Step 4.2.
+                    writer.ready_promise().resolve_native(&());
+                }
+            }
+        }
+        // Step 5.
+        self.backpressure.set(backpressure);
+    }
+}
+
+impl WritableStreamMethods for WritableStream {
+    // https://streams.spec.whatwg.org/#ws-locked
+    fn Locked(&self) -> bool {
+        self.is_locked()
+    }
+
+    // https://streams.spec.whatwg.org/#ws-abort
+    #[allow(unsafe_code)]
+    unsafe fn Abort(&self, _cx: *mut JSContext, reason: HandleValue) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+        // Step 2.
+        self.abort(reason)
+    }
+
+    // https://streams.spec.whatwg.org/#ws-close
+    fn Close(&self) -> Rc<Promise> {
+        // Step 1.
+        if self.is_locked() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is locked".to_owned()),
+            );
+        }
+        // Step 2.
+        if self.close_queued_or_in_flight() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is already closing".to_owned()),
+            );
+        }
+        // Step 3.
+        self.close()
+    }
+
+    // https://streams.spec.whatwg.org/#ws-get-writer
+    fn GetWriter(&self) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        WritableStreamDefaultWriter::acquire(self)
+    }
+}
+
+/// Step 13-14 of <https://streams.spec.whatwg.org/#writable-stream-finish-erroring>.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct AbortReaction {
+    stream: Dom<WritableStream>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl Reaction for AbortReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        // Step 13.1.
+        self.promise.resolve_native(&());
+        // Step 13.2.
+        self.stream.reject_close_and_closed_promise_if_needed();
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 14.1.
+        self.promise.reject_native(&reason.get());
+        // Step 14.2.
+        self.stream.reject_close_and_closed_promise_if_needed();
+    }
+}
@@ -0,0 +1,401 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::QueuingStrategyBinding::QueuingStrategySize;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultControllerBinding::{
+    self, WritableStreamDefaultControllerMethods,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{Dom, DomRoot};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::streams::UnderlyingMethod;
+use crate::dom::streams::{chunk_size, error_to_jsval, object_value, promise_call_or_noop};
+use crate::dom::streams::{react_to_promise, QueueWithSizes, Reaction, StartAlgorithm};
+use crate::dom::transformstream::TransformStream;
+use crate::dom::writablestream::{WritableStream, WritableStreamState};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Where the chunks written to a writable stream go, with the write, close and
+/// abort algorithms of its controller.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+pub enum UnderlyingSink {
+    /// An underlying sink object given to the constructor.
+    Js {
+        abort: Option<UnderlyingMethod>,
+        close: Option<UnderlyingMethod>,
+        write: Option<UnderlyingMethod>,
+    },
+    /// The writable side of a transform stream.
+    Transform(Dom<TransformStream>),
+}
+
+/// <https://streams.spec.whatwg.org/#ws-default-controller-class>
+#[dom_struct]
+pub struct WritableStreamDefaultController {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#writablestreamdefaultcontroller-stream>
+    stream: Dom<WritableStream>,
+    sink: UnderlyingSink,
+    queue: DomRefCell<QueueWithSizes>,
+    started: Cell<bool>,
+    strategy_hwm: f64,
+    #[ignore_malloc_size_of = "Rc"]
+    strategy_size: Option<Rc<QueuingStrategySize>>,
+}
+
+impl WritableStreamDefaultController {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &WritableStream,
+        sink: UnderlyingSink,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> WritableStreamDefaultController {
+        WritableStreamDefaultController {
+            reflector_: Reflector::new(),
+            stream: Dom::from_ref(stream),
+            sink,
+            queue: Default::default(),
+            started: Cell::new(false),
+            strategy_hwm: high_water_mark,
+            strategy_size: size,
+        }
+    }
+
+    #[allow(unrooted_must_root)]
+    fn new(
+        global: &GlobalScope,
+        stream: &WritableStream,
+        sink: UnderlyingSink,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> DomRoot<WritableStreamDefaultController> {
+        reflect_dom_object(
+            Box::new(WritableStreamDefaultController::new_inherited(
+                stream,
+                sink,
+                high_water_mark,
+                size,
+            )),
+            global,
+            WritableStreamDefaultControllerBinding::Wrap,
+        )
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-writable-stream-default-controller>
+    ///
+    /// The exception stays pending if the start algorithm throws.
+    #[allow(unrooted_must_root, unsafe_code)]
+    pub unsafe fn set_up(
+        stream: &WritableStream,
+        sink: UnderlyingSink,
+        start: StartAlgorithm,
+        high_water_mark: f64,
+        size: Option<Rc<QueuingStrategySize>>,
+    ) -> Fallible<()> {
+        let global = stream.global();
+        let cx = global.get_cx();
+        // Step 1-14.
+        let controller =
+            WritableStreamDefaultController::new(&global, stream, sink, high_water_mark, size);
+        stream.set_controller(&controller);
+        // Step 15-16.
+        stream.update_backpressure(controller.get_backpressure());
+        // Step 17-18.
+        let start_promise = start.run(&global, cx, object_value(&*controller))?;
+        // Step 19-20.
+        react_to_promise(
+            &start_promise,
+            Box::new(StartReaction {
+                controller: Dom::from_ref(&*controller),
+            }),
+        );
+        Ok(())
+    }
+
+    pub fn started(&self) -> bool {
+        self.started.get()
+    }
+
+    #[allow(unsafe_code)]
+    fn write_algorithm(&self, chunk: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        match self.sink {
+            UnderlyingSink::Js { ref write, .. } => unsafe {
+                promise_call_or_noop(
+                    write,
+                    &global,
+                    global.get_cx(),
+                    &[chunk.get(), object_value(self)],
+                )
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_write(chunk),
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn close_algorithm(&self) -> Rc<Promise> {
+        let global = self.global();
+        match self.sink {
+            UnderlyingSink::Js { ref close, .. } => unsafe {
+                promise_call_or_noop(close, &global, global.get_cx(), &[])
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_close(),
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn abort_algorithm(&self, reason: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        match self.sink {
+            UnderlyingSink::Js { ref abort, .. } => unsafe {
+                promise_call_or_noop(abort, &global, global.get_cx(), &[reason.get()])
+            },
+            UnderlyingSink::Transform(ref stream) => stream.sink_abort(reason),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-advance-queue-if-needed>
+    fn advance_queue_if_needed(&self) {
+        let cx = self.global().get_cx();
+        // Step 2.
+        if !self.started.get() {
+            return;
+        }
+        // Step 3.
+        if self.stream.has_in_flight_write_request() {
+            return;
+        }
+        // Step 4-6.
+        if self.stream.state() == WritableStreamState::Erroring {
+            return self.stream.finish_erroring();
+        }
+        // Step 7.
+        if self.queue.borrow().is_empty() {
+            return;
+        }
+        // Step 8-10.
+        rooted!(in(cx) let mut value = UndefinedValue());
+        if self.queue.borrow().peek_queue_value(value.handle_mut()) {
+            self.process_write(value.handle());
+        } else {
+            self.process_close();
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-close>
+    pub fn close(&self) {
+        // Step 1.
+        self.queue.borrow_mut().enqueue_close_sentinel();
+        // Step 2.
+        self.advance_queue_if_needed();
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-error>
+    fn error(&self, error: HandleValue) {
+        // Step 1-4.
+        self.stream.start_erroring(error);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-error-if-needed>
+    pub fn error_if_needed(&self, error: HandleValue) {
+        if self.stream.state() == WritableStreamState::Writable {
+            self.error(error);
+        }
+    }
+
+    /// Reports an error of the size algorithm or of enqueueing a chunk.
+    #[allow(unsafe_code)]
+    fn error_if_needed_with(&self, cx: *mut JSContext, error: Error) {
+        rooted!(in(cx) let mut reason = UndefinedValue());
+        unsafe { error_to_jsval(cx, &self.global(), error, reason.handle_mut()) };
+        self.error_if_needed(reason.handle());
+    }
+
+    /// <https://streams.spec.whatwg.org/#ws-default-controller-private-error>
+    pub fn error_steps(&self) {
+        self.queue.borrow_mut().reset();
+    }
+
+    /// <https://streams.spec.whatwg.org/#ws-default-controller-private-abort>
+    pub fn abort_steps(&self, reason: HandleValue) -> Rc<Promise> {
+        self.abort_algorithm(reason)
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-backpressure>
+    fn get_backpressure(&self) -> bool {
+        self.get_desired_size() <= 0.
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-chunk-size>
+    pub fn get_chunk_size(&self, cx: *mut JSContext, chunk: HandleValue) -> f64 {
+        match chunk_size(&self.strategy_size, chunk) {
+            Ok(size) => size,
+            // Step 2.
+            Err(error) => {
+                self.error_if_needed_with(cx, error);
+                1.
+            },
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-get-desired-size>
+    pub fn get_desired_size(&self) -> f64 {
+        self.strategy_hwm - self.queue.borrow().total_size()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-process-close>
+    fn process_close(&self) {
+        let cx = self.global().get_cx();
+        // Step 2.
+        self.stream.mark_close_request_in_flight();
+        // Step 3-4.
+        rooted!(in(cx) let mut sentinel = UndefinedValue());
+        self.queue.borrow_mut().dequeue_value(sentinel.handle_mut());
+        // Step 5-6.
+        let promise = self.close_algorithm();
+        // Step 7-8.
+        react_to_promise(
+            &promise,
+            Box::new(CloseReaction {
+                controller: Dom::from_ref(self),
+            }),
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-process-write>
+    fn process_write(&self, chunk: HandleValue) {
+        // Step 2.
+        self.stream.mark_first_write_request_in_flight();
+        // Step 3.
+        let promise = self.write_algorithm(chunk);
+        // Step 4-5.
+        react_to_promise(
+            &promise,
+            Box::new(WriteReaction {
+                controller: Dom::from_ref(self),
+            }),
+        );
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-controller-write>
+    pub fn write(&self, chunk: HandleValue, chunk_size: f64) {
+        let cx = self.global().get_cx();
+        // Step 1-2.
+        let result = self
+            .queue
+            .borrow_mut()
+            .enqueue_value_with_size(chunk, chunk_size);
+        if let Err(error) = result {
+            return self.error_if_needed_with(cx, error);
+        }
+        // Step 3-4.
+        self.update_backpressure_if_writable();
+        // Step 5.
+        self.advance_queue_if_needed();
+    }
+
+    fn update_backpressure_if_writable(&self) {
+        if !self.stream.close_queued_or_in_flight() &&
+            self.stream.state() == WritableStreamState::Writable
+        {
+            self.stream.update_backpressure(self.get_backpressure());
+        }
+    }
+}
+
+impl WritableStreamDefaultControllerMethods for WritableStreamDefaultController {
+    // https://streams.spec.whatwg.org/#ws-default-controller-error
+    #[allow(unsafe_code)]
+    unsafe fn Error(&self, _cx: *mut JSContext, e: HandleValue) {
+        // Step 1-2.
+        if self.stream.state() != WritableStreamState::Writable {
+            return;
+        }
+        // Step 3.
+        self.error(e);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct StartReaction {
+    controller: Dom<WritableStreamDefaultController>,
+}
+
+impl Reaction for StartReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        // Step 19.1-19.2.
+        self.controller.started.set(true);
+        // Step 19.3.
+        self.controller.advance_queue_if_needed();
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 20.1-20.2.
+        self.controller.started.set(true);
+        // Step 20.3.
+        self.controller.stream.deal_with_rejection(reason);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct CloseReaction {
+    controller: Dom<WritableStreamDefaultController>,
+}
+
+impl Reaction for CloseReaction {
+    fn fulfilled(&self, _cx: *mut JSContext, _value: HandleValue) {
+        // Step 7.1.
+        self.controller.stream.finish_in_flight_close();
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 8.1.
+        self.controller
+            .stream
+            .finish_in_flight_close_with_error(reason);
+    }
+}
+
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct WriteReaction {
+    controller: Dom<WritableStreamDefaultController>,
+}
+
+impl Reaction for WriteReaction {
+    fn fulfilled(&self, cx: *mut JSContext, _value: HandleValue) {
+        let controller = &self.controller;
+        // Step 4.1.
+        controller.stream.finish_in_flight_write();
+        // Step 4.2-4.4.
+        rooted!(in(cx) let mut chunk = UndefinedValue());
+        controller
+            .queue
+            .borrow_mut()
+            .dequeue_value(chunk.handle_mut());
+        // Step 4.5.
+        controller.update_backpressure_if_writable();
+        // Step 4.6.
+        controller.advance_queue_if_needed();
+    }
+
+    fn rejected(&self, _cx: *mut JSContext, reason: HandleValue) {
+        // Step 5.1-5.2.
+        self.controller
+            .stream
+            .finish_in_flight_write_with_error(reason);
+    }
+}
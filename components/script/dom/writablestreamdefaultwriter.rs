@@ -0,0 +1,345 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::compartments::enter_realm;
+use crate::dom::bindings::cell::DomRefCell;
+use crate::dom::bindings::codegen::Bindings::WritableStreamDefaultWriterBinding::{
+    self, WritableStreamDefaultWriterMethods,
+};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::reflector::{reflect_dom_object, DomObject, Reflector};
+use crate::dom::bindings::root::{DomRoot, MutNullableDom};
+use crate::dom::globalscope::GlobalScope;
+use crate::dom::promise::Promise;
+use crate::dom::streams::{mark_as_handled, promise_rejected_with_error};
+use crate::dom::streams::{rejected_promise, resolved_promise};
+use crate::dom::writablestream::{WritableStream, WritableStreamState};
+use dom_struct::dom_struct;
+use js::jsapi::JSContext;
+use js::jsval::UndefinedValue;
+use js::rust::HandleValue;
+use std::rc::Rc;
+
+/// <https://streams.spec.whatwg.org/#default-writer-class>
+#[dom_struct]
+pub struct WritableStreamDefaultWriter {
+    reflector_: Reflector,
+    /// <https://streams.spec.whatwg.org/#writablestreamdefaultwriter-closedpromise>
+    #[ignore_malloc_size_of = "Rc"]
+    closed_promise: DomRefCell<Rc<Promise>>,
+    /// <https://streams.spec.whatwg.org/#writablestreamdefaultwriter-readypromise>
+    #[ignore_malloc_size_of = "Rc"]
+    ready_promise: DomRefCell<Rc<Promise>>,
+    /// <https://streams.spec.whatwg.org/#writablestreamdefaultwriter-stream>
+    stream: MutNullableDom<WritableStream>,
+}
+
+impl WritableStreamDefaultWriter {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(
+        stream: &WritableStream,
+        ready_promise: Rc<Promise>,
+        closed_promise: Rc<Promise>,
+    ) -> WritableStreamDefaultWriter {
+        WritableStreamDefaultWriter {
+            reflector_: Reflector::new(),
+            closed_promise: DomRefCell::new(closed_promise),
+            ready_promise: DomRefCell::new(ready_promise),
+            stream: MutNullableDom::new(Some(stream)),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#set-up-writable-stream-default-writer>
+    fn set_up(
+        global: &GlobalScope,
+        stream: &WritableStream,
+    ) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        // Step 1.
+        if stream.is_locked() {
+            return Err(Error::Type("The stream is already locked".to_owned()));
+        }
+        rooted!(in(global.get_cx()) let mut stored_error = UndefinedValue());
+        stream.get_stored_error(stored_error.handle_mut());
+        let rejected_with_stored_error = || {
+            let promise = rejected_promise(global, stored_error.handle());
+            mark_as_handled(&promise);
+            promise
+        };
+        // Step 4.
+        let (ready_promise, closed_promise) = match stream.state() {
+            // Step 5.
+            WritableStreamState::Writable => {
+                let ready_promise = if !stream.close_queued_or_in_flight() && stream.backpressure()
+                {
+                    Promise::new(global)
+                } else {
+                    resolved_promise(global)
+                };
+                (ready_promise, Promise::new(global))
+            },
+            // Step 6.
+            WritableStreamState::Erroring => (rejected_with_stored_error(), Promise::new(global)),
+            // Step 7.
+            WritableStreamState::Closed => (resolved_promise(global), resolved_promise(global)),
+            // Step 8.
+            WritableStreamState::Errored => {
+                (rejected_with_stored_error(), rejected_with_stored_error())
+            },
+        };
+        // Step 2.
+        let writer = reflect_dom_object(
+            Box::new(WritableStreamDefaultWriter::new_inherited(
+                stream,
+                ready_promise,
+                closed_promise,
+            )),
+            global,
+            WritableStreamDefaultWriterBinding::Wrap,
+        );
+        // Step 3.
+        stream.set_writer(Some(&writer));
+        Ok(writer)
+    }
+
+    /// <https://streams.spec.whatwg.org/#acquire-writable-stream-default-writer>
+    pub fn acquire(stream: &WritableStream) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        WritableStreamDefaultWriter::set_up(&stream.global(), stream)
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-constructor
+    pub fn Constructor(
+        global: &GlobalScope,
+        stream: &WritableStream,
+    ) -> Fallible<DomRoot<WritableStreamDefaultWriter>> {
+        WritableStreamDefaultWriter::set_up(global, stream)
+    }
+
+    pub fn closed_promise(&self) -> Rc<Promise> {
+        self.closed_promise.borrow().clone()
+    }
+
+    pub fn ready_promise(&self) -> Rc<Promise> {
+        self.ready_promise.borrow().clone()
+    }
+
+    #[allow(unrooted_must_root)]
+    pub fn set_ready_promise(&self, promise: Rc<Promise>) {
+        *self.ready_promise.borrow_mut() = promise;
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-close-with-error-propagation>
+    pub fn close_with_error_propagation(&self) -> Rc<Promise> {
+        let global = self.global();
+        // Step 1-2.
+        let stream = self.stream.get().expect("The writer was released");
+        // Step 3-4.
+        if stream.close_queued_or_in_flight() || stream.state() == WritableStreamState::Closed {
+            return resolved_promise(&global);
+        }
+        // Step 5.
+        if stream.state() == WritableStreamState::Errored {
+            rooted!(in(global.get_cx()) let mut stored_error = UndefinedValue());
+            stream.get_stored_error(stored_error.handle_mut());
+            return rejected_promise(&global, stored_error.handle());
+        }
+        // Step 6-7.
+        stream.close()
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-ensure-closed-promise-rejected>
+    fn ensure_closed_promise_rejected(&self, error: HandleValue) {
+        let closed_promise = self.closed_promise();
+        if !closed_promise.is_fulfilled() {
+            // Step 1.
+            closed_promise.reject_native(&error.get());
+        } else {
+            // Step 2.
+            *self.closed_promise.borrow_mut() = rejected_promise(&self.global(), error);
+        }
+        // Step 3.
+        mark_as_handled(&self.closed_promise());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-ensure-ready-promise-rejected>
+    pub fn ensure_ready_promise_rejected(&self, error: HandleValue) {
+        let ready_promise = self.ready_promise();
+        if !ready_promise.is_fulfilled() {
+            // Step 1.
+            ready_promise.reject_native(&error.get());
+        } else {
+            // Step 2.
+            *self.ready_promise.borrow_mut() = rejected_promise(&self.global(), error);
+        }
+        // Step 3.
+        mark_as_handled(&self.ready_promise());
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-get-desired-size>
+    fn get_desired_size(&self, stream: &WritableStream) -> Option<f64> {
+        match stream.state() {
+            // Step 2-3.
+            WritableStreamState::Errored | WritableStreamState::Erroring => None,
+            // Step 4.
+            WritableStreamState::Closed => Some(0.),
+            // Step 5.
+            WritableStreamState::Writable => Some(stream.controller().get_desired_size()),
+        }
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-release>
+    #[allow(unsafe_code)]
+    pub fn release(&self) {
+        let global = self.global();
+        let cx = global.get_cx();
+        // Step 1-2.
+        let stream = self.stream.get().expect("The writer was released");
+        // Step 3.
+        rooted!(in(cx) let mut released_error = UndefinedValue());
+        {
+            let _ac = enter_realm(self);
+            unsafe {
+                Error::Type("The writer was released".to_owned()).to_jsval(
+                    cx,
+                    &global,
+                    released_error.handle_mut(),
+                )
+            };
+        }
+        // Step 4.
+        self.ensure_ready_promise_rejected(released_error.handle());
+        // Step 5.
+        self.ensure_closed_promise_rejected(released_error.handle());
+        // Step 6-7.
+        stream.set_writer(None);
+        self.stream.set(None);
+    }
+
+    /// <https://streams.spec.whatwg.org/#writable-stream-default-writer-write>
+    pub fn write(&self, cx: *mut JSContext, chunk: HandleValue) -> Rc<Promise> {
+        let global = self.global();
+        // Step 1-2.
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return promise_rejected_with_error(
+                    &global,
+                    Error::Type("The writer was released".to_owned()),
+                );
+            },
+        };
+        let controller = stream.controller();
+        // Step 3.
+        let chunk_size = controller.get_chunk_size(cx, chunk);
+        // Step 4.
+        if self.stream.get().map_or(true, |current| current != stream) {
+            return promise_rejected_with_error(
+                &global,
+                Error::Type("The writer was released".to_owned()),
+            );
+        }
+        // Step 5-6.
+        match stream.state() {
+            WritableStreamState::Errored | WritableStreamState::Erroring => {
+                rooted!(in(cx) let mut stored_error = UndefinedValue());
+                stream.get_stored_error(stored_error.handle_mut());
+                return rejected_promise(&global, stored_error.handle());
+            },
+            // Step 7.
+            WritableStreamState::Closed => {
+                return promise_rejected_with_error(
+                    &global,
+                    Error::Type("The stream is closed".to_owned()),
+                );
+            },
+            WritableStreamState::Writable => {},
+        }
+        if stream.close_queued_or_in_flight() {
+            return promise_rejected_with_error(
+                &global,
+                Error::Type("The stream is closing".to_owned()),
+            );
+        }
+        // Step 9-10.
+        let promise = stream.add_write_request();
+        // Step 11.
+        controller.write(chunk, chunk_size);
+        // Step 12.
+        promise
+    }
+}
+
+impl WritableStreamDefaultWriterMethods for WritableStreamDefaultWriter {
+    // https://streams.spec.whatwg.org/#default-writer-closed
+    fn Closed(&self) -> Rc<Promise> {
+        self.closed_promise()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-desired-size
+    fn GetDesiredSize(&self) -> Fallible<Option<f64>> {
+        match self.stream.get() {
+            // Step 1.
+            None => Err(Error::Type("The writer was released".to_owned())),
+            // Step 2.
+            Some(stream) => Ok(self.get_desired_size(&stream)),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-ready
+    fn Ready(&self) -> Rc<Promise> {
+        self.ready_promise()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-abort
+    #[allow(unsafe_code)]
+    unsafe fn Abort(&self, _cx: *mut JSContext, reason: HandleValue) -> Rc<Promise> {
+        match self.stream.get() {
+            // Step 1.
+            None => promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The writer was released".to_owned()),
+            ),
+            // Step 2.
+            Some(stream) => stream.abort(reason),
+        }
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-close
+    fn Close(&self) -> Rc<Promise> {
+        // Step 1-2.
+        let stream = match self.stream.get() {
+            Some(stream) => stream,
+            None => {
+                return promise_rejected_with_error(
+                    &self.global(),
+                    Error::Type("The writer was released".to_owned()),
+                );
+            },
+        };
+        // Step 3.
+        if stream.close_queued_or_in_flight() {
+            return promise_rejected_with_error(
+                &self.global(),
+                Error::Type("The stream is already closing".to_owned()),
+            );
+        }
+        // Step 4.
+        stream.close()
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-release-lock
+    fn ReleaseLock(&self) {
+        // Step 1-2.
+        if self.stream.get().is_none() {
+            return;
+        }
+        // Step 3-4.
+        self.release();
+    }
+
+    // https://streams.spec.whatwg.org/#default-writer-write
+    #[allow(unsafe_code)]
+    unsafe fn Write(&self, cx: *mut JSContext, chunk: HandleValue) -> Rc<Promise> {
+        self.write(cx, chunk)
+    }
+}
@@ -58,6 +58,7 @@ use js::jsval::{JSVal, NullValue, UndefinedValue};
 use js::rust::wrappers::JS_ParseJSON;
 use js::typedarray::{ArrayBuffer, CreateWith};
 use mime::{self, Mime, Name};
+use net_traits::csp::Violation;
 use net_traits::request::{CredentialsMode, Destination, Referrer, RequestBuilder, RequestMode};
 use net_traits::trim_http_whitespace;
 use net_traits::CoreResourceMsg::Fetch;
@@ -280,6 +281,11 @@ impl XMLHttpRequest {
             fn submit_resource_timing(&mut self) {
                 network_listener::submit_timing(self)
             }
+
+            fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+                let global = &self.resource_timing_global();
+                global.report_csp_violations(violations);
+            }
         }
 
         impl ResourceTimingListener for XHRContext {
@@ -657,7 +663,8 @@ impl XMLHttpRequestMethods for XMLHttpRequest {
                     .map(|referrer_url| Referrer::ReferrerUrl(referrer_url)),
             )
             .referrer_policy(self.referrer_policy.clone())
-            .pipeline_id(Some(self.global().pipeline_id()));
+            .pipeline_id(Some(self.global().pipeline_id()))
+            .csp_list(self.global().get_csp_list());
 
         // step 4 (second half)
         match extracted_or_serialized {
@@ -3,20 +3,23 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::compartments::{enter_realm, InCompartment};
+use crate::dom::abortsignal::AbortAlgorithm;
 use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInfo;
 use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestInit;
+use crate::dom::bindings::codegen::Bindings::RequestBinding::RequestMethods;
 use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseBinding::ResponseMethods;
 use crate::dom::bindings::codegen::Bindings::ResponseBinding::ResponseType as DOMResponseType;
 use crate::dom::bindings::error::Error;
 use crate::dom::bindings::inheritance::Castable;
 use crate::dom::bindings::refcounted::{Trusted, TrustedPromise};
 use crate::dom::bindings::reflector::DomObject;
-use crate::dom::bindings::root::DomRoot;
+use crate::dom::bindings::root::{Dom, DomRoot};
 use crate::dom::bindings::trace::RootedTraceableBox;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::headers::Guard;
 use crate::dom::performanceresourcetiming::InitiatorType;
 use crate::dom::promise::Promise;
+use crate::dom::readablestream::BytesConsumer;
 use crate::dom::request::Request;
 use crate::dom::response::Response;
 use crate::dom::serviceworkerglobalscope::ServiceWorkerGlobalScope;
@@ -26,6 +29,8 @@ use crate::network_listener::{
 use crate::task_source::TaskSourceName;
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
+use js::rust::HandleValue;
+use net_traits::csp::Violation;
 use net_traits::request::RequestBuilder;
 use net_traits::request::{Request as NetTraitsRequest, ServiceWorkersMode};
 use net_traits::CoreResourceMsg::Fetch as NetTraitsFetch;
@@ -34,15 +39,47 @@ use net_traits::{FetchChannels, FetchResponseListener, NetworkError};
 use net_traits::{FetchMetadata, FilteredMetadata, Metadata};
 use net_traits::{ResourceFetchTiming, ResourceTimingType};
 use servo_url::ServoUrl;
-use std::mem;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 struct FetchContext {
     fetch_promise: Option<TrustedPromise>,
     response_object: Trusted<Response>,
-    body: Vec<u8>,
     resource_timing: ResourceFetchTiming,
+    /// Terminates the fetch in the resource thread if its signal aborts it,
+    /// or the stream of its body is cancelled.
+    canceller: FetchCanceller,
+    /// Whether the signal of the request aborted the fetch, or the stream of
+    /// its body was cancelled.
+    locally_aborted: bool,
+}
+
+/// What the signal of the request of a fetch needs to abort it.
+pub struct FetchAbortHandle(Arc<Mutex<FetchContext>>);
+
+unsafe_no_jsmanaged_fields!(FetchAbortHandle);
+
+impl FetchAbortHandle {
+    /// <https://fetch.spec.whatwg.org/#abort-fetch>
+    pub fn abort(&self) {
+        let mut context = self.0.lock().unwrap();
+        context.locally_aborted = true;
+
+        let response = context.response_object.root();
+        let _ac = enter_realm(&*response);
+        // Step 1
+        if let Some(promise) = context.fetch_promise.take() {
+            promise.root().reject_error(Error::Abort);
+        }
+
+        // Steps 2-3 have nothing to do, as the stream of the body of the
+        // request is read as a whole before the fetch starts.
+        // Steps 4-6
+        response.abort_body();
+
+        // Terminate the ongoing fetch.
+        context.canceller.cancel();
+    }
 }
 
 /// RAII fetch canceller object. By default initialized to not having a canceller
@@ -120,10 +157,14 @@ fn request_init_from_request(request: NetTraitsRequest) -> RequestBuilder {
         referrer: Some(request.referrer.clone()),
         referrer_policy: request.referrer_policy,
         pipeline_id: request.pipeline_id,
+        top_level_browsing_context_id: request.top_level_browsing_context_id,
         redirect_mode: request.redirect_mode,
         integrity_metadata: "".to_owned(),
         url_list: vec![],
         parser_metadata: request.parser_metadata,
+        cryptographic_nonce_metadata: request.cryptographic_nonce_metadata.clone(),
+        csp_list: request.csp_list.clone(),
+        has_trustworthy_ancestor_origin: request.has_trustworthy_ancestor_origin,
     }
 }
 
@@ -135,23 +176,56 @@ pub fn Fetch(
     init: RootedTraceableBox<RequestInit>,
     comp: InCompartment,
 ) -> Rc<Promise> {
-    let core_resource_thread = global.core_resource_thread();
-
     // Step 1
     let promise = Promise::new_in_current_compartment(global, comp);
-    let response = Response::new(global);
 
     // Step 2
-    let request = match Request::Constructor(global, input, init) {
+    let request_object = match Request::Constructor(global, input, init) {
         Err(e) => {
             promise.reject_error(e);
             return promise;
         },
-        Ok(r) => r.get_request(),
+        Ok(r) => r,
     };
+
+    // A body given as a stream is read as a whole before the request is sent.
+    match request_object.get_body_stream() {
+        Some(stream) => stream.read_all_bytes(Box::new(RequestBodyReader {
+            request: Dom::from_ref(&*request_object),
+            promise: promise.clone(),
+        })),
+        None => start_fetch(global, &request_object, None, &promise),
+    }
+
+    promise
+}
+
+/// Sends the request of a fetch, with `body` as its body if it was read from a stream.
+#[allow(unrooted_must_root)]
+fn start_fetch(
+    global: &GlobalScope,
+    request_object: &Request,
+    body: Option<Vec<u8>>,
+    promise: &Rc<Promise>,
+) {
+    let core_resource_thread = global.core_resource_thread();
+    let response = Response::new(global);
+    response.start_receiving_body();
+
+    let request = request_object.get_request();
     let timing_type = request.timing_type();
 
+    // The fetch doesn't even start if the signal of the request is already aborted.
+    let signal = request_object.Signal();
+    if signal.aborted() {
+        return promise.reject_error(Error::Abort);
+    }
+
     let mut request_init = request_init_from_request(request);
+    request_init.csp_list = global.get_csp_list();
+    if body.is_some() {
+        request_init.body = body;
+    }
 
     // Step 3
     if global.downcast::<ServiceWorkerGlobalScope>().is_some() {
@@ -163,12 +237,18 @@ pub fn Fetch(
 
     // Step 5
     let (action_sender, action_receiver) = ipc::channel().unwrap();
+    let mut canceller = FetchCanceller::new();
+    let cancel_receiver = canceller.initialize();
     let fetch_context = Arc::new(Mutex::new(FetchContext {
         fetch_promise: Some(TrustedPromise::new(promise.clone())),
         response_object: Trusted::new(&*response),
-        body: vec![],
         resource_timing: ResourceFetchTiming::new(timing_type),
+        canceller,
+        locally_aborted: false,
     }));
+    signal.add_algorithm(AbortAlgorithm::Fetch(FetchAbortHandle(
+        fetch_context.clone(),
+    )));
     let listener = NetworkListener {
         context: fetch_context,
         task_source: global.networking_task_source(),
@@ -184,11 +264,33 @@ pub fn Fetch(
     core_resource_thread
         .send(NetTraitsFetch(
             request_init,
-            FetchChannels::ResponseMsg(action_sender, None),
+            FetchChannels::ResponseMsg(action_sender, Some(cancel_receiver)),
         ))
         .unwrap();
+}
 
-    promise
+/// Reads the stream of the body of the request of a fetch, before sending it.
+#[derive(JSTraceable, MallocSizeOf)]
+#[must_root]
+struct RequestBodyReader {
+    request: Dom<Request>,
+    #[ignore_malloc_size_of = "Rc"]
+    promise: Rc<Promise>,
+}
+
+impl BytesConsumer for RequestBodyReader {
+    fn success(&self, bytes: Vec<u8>) {
+        start_fetch(
+            &self.request.global(),
+            &self.request,
+            Some(bytes),
+            &self.promise,
+        );
+    }
+
+    fn failure(&self, error: HandleValue) {
+        self.promise.reject_native(&error.get());
+    }
 }
 
 impl PreInvoke for FetchContext {}
@@ -204,6 +306,9 @@ impl FetchResponseListener for FetchContext {
 
     #[allow(unrooted_must_root)]
     fn process_response(&mut self, fetch_metadata: Result<FetchMetadata, NetworkError>) {
+        if self.locally_aborted {
+            return;
+        }
         let promise = self
             .fetch_promise
             .take()
@@ -252,14 +357,30 @@ impl FetchResponseListener for FetchContext {
         self.fetch_promise = Some(TrustedPromise::new(promise));
     }
 
-    fn process_response_chunk(&mut self, mut chunk: Vec<u8>) {
-        self.body.append(&mut chunk);
+    fn process_response_chunk(&mut self, chunk: Vec<u8>) {
+        if self.locally_aborted {
+            return;
+        }
+        let response = self.response_object.root();
+        // Nothing reads the rest of the body once its stream is cancelled.
+        if response.is_body_cancelled() {
+            self.locally_aborted = true;
+            self.canceller.cancel();
+            return;
+        }
+        let _ac = enter_realm(&*response);
+        response.receive_body_chunk(chunk);
     }
 
     fn process_response_eof(&mut self, _response: Result<ResourceFetchTiming, NetworkError>) {
+        if self.locally_aborted {
+            return;
+        }
+        // The fetch is over, there is nothing left to cancel.
+        self.canceller.ignore();
         let response = self.response_object.root();
         let _ac = enter_realm(&*response);
-        response.finish(mem::replace(&mut self.body, vec![]));
+        response.finish_receiving_body();
         // TODO
         // ... trailerObject is not supported in Servo yet.
     }
@@ -279,6 +400,11 @@ impl FetchResponseListener for FetchContext {
             _ => {},
         };
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for FetchContext {
@@ -308,6 +434,7 @@ pub fn load_whole_resource(
 ) -> Result<(Metadata, Vec<u8>), NetworkError> {
     let (action_sender, action_receiver) = ipc::channel().unwrap();
     let url = request.url.clone();
+    let request = request.csp_list(global.get_csp_list());
     core_resource_thread
         .send(CoreResourceMsg::Fetch(
             request,
@@ -327,6 +454,9 @@ pub fn load_whole_resource(
                 })
             },
             FetchResponseMsg::ProcessResponseChunk(data) => buf.extend_from_slice(&data),
+            FetchResponseMsg::ProcessCspViolations(violations) => {
+                global.report_csp_violations(violations)
+            },
             FetchResponseMsg::ProcessResponseEOF(Ok(_)) => {
                 let metadata = metadata.unwrap();
                 if let Some(timing) = &metadata.timing {
@@ -0,0 +1,457 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The parts of IndexedDB that deal with JavaScript values: converting them
+//! to and from keys, and evaluating key paths on them. The backend only ever
+//! sees keys and structured clones, so all of this happens in script.
+
+use crate::dom::bindings::codegen::Bindings::IDBCursorBinding::IDBCursorDirection;
+use crate::dom::bindings::codegen::UnionTypes::StringOrStringSequence;
+use crate::dom::bindings::conversions::{root_from_object, ToJSValConvertible};
+use crate::dom::bindings::conversions::{ConversionResult, FromJSValConvertible};
+use crate::dom::bindings::error::{Error, Fallible};
+use crate::dom::bindings::str::DOMString;
+use crate::dom::bindings::utils::{get_dictionary_property, set_dictionary_property};
+use crate::dom::domexception::DOMErrorName;
+use crate::dom::idbkeyrange::IDBKeyRange;
+use js::conversions::StringificationBehavior;
+use js::jsapi::{ClippedTime, HandleValueArray, JSContext, JSObject};
+use js::jsapi::{JS_NewArrayObject, JS_NewPlainObject, NewDateObject};
+use js::jsval::{DoubleValue, ObjectValue, UndefinedValue};
+use js::rust::wrappers::{DateGetMsecSinceEpoch, JS_IsArrayObject, ObjectIsDate};
+use js::rust::{HandleObject, HandleValue, MutableHandleValue};
+use js::typedarray::{ArrayBuffer, ArrayBufferView, CreateWith};
+use net_traits::indexeddb_thread::{BackendError, CursorDirection, IndexInfo, IndexKeys};
+use net_traits::indexeddb_thread::{IndexedDBKey, IndexedDBKeyRange, KeyPath};
+use std::ptr;
+
+/// The exception a failed backend operation is reported with.
+pub fn backend_error_to_dom_error(error: BackendError) -> Error {
+    match error {
+        BackendError::Constraint => Error::Constraint,
+        BackendError::Data => Error::Data,
+        BackendError::NotFound => Error::NotFound,
+        BackendError::Quota => Error::QuotaExceeded,
+        BackendError::Version => Error::Version,
+        BackendError::Unknown => Error::Unknown,
+    }
+}
+
+/// The name of the `DOMException` a request that failed in the backend is
+/// given as its error.
+pub fn backend_error_name(error: BackendError) -> DOMErrorName {
+    match error {
+        BackendError::Constraint => DOMErrorName::ConstraintError,
+        BackendError::Data => DOMErrorName::DataError,
+        BackendError::NotFound => DOMErrorName::NotFoundError,
+        BackendError::Quota => DOMErrorName::QuotaExceededError,
+        BackendError::Version => DOMErrorName::VersionError,
+        BackendError::Unknown => DOMErrorName::UnknownError,
+    }
+}
+
+pub fn cursor_direction_from_idl(direction: IDBCursorDirection) -> CursorDirection {
+    match direction {
+        IDBCursorDirection::Next => CursorDirection::Next,
+        IDBCursorDirection::Nextunique => CursorDirection::NextUnique,
+        IDBCursorDirection::Prev => CursorDirection::Prev,
+        IDBCursorDirection::Prevunique => CursorDirection::PrevUnique,
+    }
+}
+
+pub fn cursor_direction_to_idl(direction: CursorDirection) -> IDBCursorDirection {
+    match direction {
+        CursorDirection::Next => IDBCursorDirection::Next,
+        CursorDirection::NextUnique => IDBCursorDirection::Nextunique,
+        CursorDirection::Prev => IDBCursorDirection::Prev,
+        CursorDirection::PrevUnique => IDBCursorDirection::Prevunique,
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-key>
+///
+/// Invalid keys are reported as `Error::Data`.
+#[allow(unsafe_code)]
+pub unsafe fn convert_value_to_key(
+    cx: *mut JSContext,
+    input: HandleValue,
+    seen: &mut Vec<*mut JSObject>,
+) -> Fallible<IndexedDBKey> {
+    if input.is_number() {
+        let number = input.to_number();
+        if number.is_nan() {
+            return Err(Error::Data);
+        }
+        return Ok(IndexedDBKey::Number(number));
+    }
+
+    if input.is_string() {
+        return match DOMString::from_jsval(cx, input, StringificationBehavior::Default) {
+            Ok(ConversionResult::Success(string)) => Ok(IndexedDBKey::String(string.into())),
+            _ => Err(Error::JSFailed),
+        };
+    }
+
+    if !input.is_object() {
+        return Err(Error::Data);
+    }
+    rooted!(in(cx) let object = input.to_object());
+
+    let mut is_date = false;
+    if !ObjectIsDate(cx, object.handle(), &mut is_date) {
+        return Err(Error::JSFailed);
+    }
+    if is_date {
+        let mut time = 0.;
+        if !DateGetMsecSinceEpoch(cx, object.handle(), &mut time) {
+            return Err(Error::JSFailed);
+        }
+        if time.is_nan() {
+            return Err(Error::Data);
+        }
+        return Ok(IndexedDBKey::Date(time));
+    }
+
+    typedarray!(in(cx) let buffer: ArrayBuffer = object.get());
+    if let Ok(buffer) = buffer {
+        return Ok(IndexedDBKey::Binary(buffer.as_slice().to_vec()));
+    }
+    typedarray!(in(cx) let view: ArrayBufferView = object.get());
+    if let Ok(view) = view {
+        return Ok(IndexedDBKey::Binary(view.as_slice().to_vec()));
+    }
+
+    let mut is_array = false;
+    if !JS_IsArrayObject(cx, input, &mut is_array) {
+        return Err(Error::JSFailed);
+    }
+    if !is_array {
+        return Err(Error::Data);
+    }
+    if seen.contains(&object.get()) {
+        return Err(Error::Data);
+    }
+    seen.push(object.get());
+    let length = array_length(cx, object.handle())?;
+    let mut keys = Vec::with_capacity(length as usize);
+    for index in 0..length {
+        rooted!(in(cx) let mut entry = UndefinedValue());
+        // Holes in the array are not valid keys.
+        match get_dictionary_property(cx, object.handle(), &index.to_string(), entry.handle_mut()) {
+            Ok(true) => {},
+            Ok(false) => return Err(Error::Data),
+            Err(()) => return Err(Error::JSFailed),
+        }
+        keys.push(convert_value_to_key(cx, entry.handle(), seen)?);
+    }
+    Ok(IndexedDBKey::Array(keys))
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-multientry-key>
+#[allow(unsafe_code)]
+unsafe fn convert_value_to_multi_entry_key(
+    cx: *mut JSContext,
+    input: HandleValue,
+) -> Fallible<IndexedDBKey> {
+    let mut is_array = false;
+    if !JS_IsArrayObject(cx, input, &mut is_array) {
+        return Err(Error::JSFailed);
+    }
+    if !is_array {
+        return convert_value_to_key(cx, input, &mut vec![]);
+    }
+
+    rooted!(in(cx) let object = input.to_object());
+    let length = array_length(cx, object.handle())?;
+    let mut keys = vec![];
+    for index in 0..length {
+        rooted!(in(cx) let mut entry = UndefinedValue());
+        match get_dictionary_property(cx, object.handle(), &index.to_string(), entry.handle_mut()) {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(()) => return Err(Error::JSFailed),
+        }
+        let mut seen = vec![object.get()];
+        match convert_value_to_key(cx, entry.handle(), &mut seen) {
+            Ok(key) => keys.push(key),
+            // Invalid keys are skipped.
+            Err(Error::Data) => {},
+            Err(error) => return Err(error),
+        }
+    }
+    keys.sort();
+    keys.dedup();
+    Ok(IndexedDBKey::Array(keys))
+}
+
+#[allow(unsafe_code)]
+unsafe fn array_length(cx: *mut JSContext, array: HandleObject) -> Fallible<u32> {
+    rooted!(in(cx) let mut length = UndefinedValue());
+    match get_dictionary_property(cx, array, "length", length.handle_mut()) {
+        Ok(true) if length.is_number() => Ok(length.to_number() as u32),
+        Ok(_) => Ok(0),
+        Err(()) => Err(Error::JSFailed),
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-key-to-a-value>
+#[allow(unsafe_code)]
+pub unsafe fn key_to_jsval(cx: *mut JSContext, key: &IndexedDBKey, mut rval: MutableHandleValue) {
+    match *key {
+        IndexedDBKey::Number(number) => rval.set(DoubleValue(number)),
+        IndexedDBKey::Date(time) => {
+            rooted!(in(cx) let date = NewDateObject(cx, ClippedTime { t: time }));
+            rval.set(ObjectValue(date.get()));
+        },
+        IndexedDBKey::String(ref string) => DOMString::from(string.clone()).to_jsval(cx, rval),
+        IndexedDBKey::Binary(ref bytes) => {
+            rooted!(in(cx) let mut buffer = ptr::null_mut::<JSObject>());
+            assert!(ArrayBuffer::create(cx, CreateWith::Slice(bytes), buffer.handle_mut()).is_ok());
+            rval.set(ObjectValue(buffer.get()));
+        },
+        IndexedDBKey::Array(ref keys) => {
+            rooted!(in(cx) let array = JS_NewArrayObject(cx, &HandleValueArray::new()));
+            for (index, key) in keys.iter().enumerate() {
+                rooted!(in(cx) let mut entry = UndefinedValue());
+                key_to_jsval(cx, key, entry.handle_mut());
+                let _ =
+                    set_dictionary_property(cx, array.handle(), &index.to_string(), entry.handle());
+            }
+            rval.set(ObjectValue(array.get()));
+        },
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#convert-a-value-to-a-key-range>
+#[allow(unsafe_code)]
+pub unsafe fn convert_value_to_key_range(
+    cx: *mut JSContext,
+    input: HandleValue,
+    null_disallowed: bool,
+) -> Fallible<IndexedDBKeyRange> {
+    // Step 1.
+    if input.is_object() {
+        if let Ok(range) = root_from_object::<IDBKeyRange>(input.to_object(), cx) {
+            return Ok(range.inner().clone());
+        }
+    }
+    // Step 2.
+    if input.is_undefined() || input.is_null() {
+        if null_disallowed {
+            return Err(Error::Data);
+        }
+        return Ok(IndexedDBKeyRange::unbounded());
+    }
+    // Steps 3-5.
+    let key = convert_value_to_key(cx, input, &mut vec![])?;
+    Ok(IndexedDBKeyRange::only(key))
+}
+
+pub fn key_path_from_idl(key_path: &StringOrStringSequence) -> KeyPath {
+    match *key_path {
+        StringOrStringSequence::String(ref path) => KeyPath::String(path.to_string()),
+        StringOrStringSequence::StringSequence(ref paths) => {
+            KeyPath::Sequence(paths.iter().map(|path| path.to_string()).collect())
+        },
+    }
+}
+
+#[allow(unsafe_code)]
+pub unsafe fn key_path_to_jsval(cx: *mut JSContext, key_path: &KeyPath, rval: MutableHandleValue) {
+    match *key_path {
+        KeyPath::String(ref path) => DOMString::from(path.clone()).to_jsval(cx, rval),
+        KeyPath::Sequence(ref paths) => paths
+            .iter()
+            .map(|path| DOMString::from(path.clone()))
+            .collect::<Vec<_>>()
+            .to_jsval(cx, rval),
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#valid-key-path>
+pub fn is_valid_key_path(key_path: &KeyPath) -> bool {
+    fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(c) if c.is_alphabetic() || c == '$' || c == '_' => {},
+            _ => return false,
+        }
+        chars.all(|c| c.is_alphanumeric() || c == '$' || c == '_')
+    }
+    fn is_valid(path: &str) -> bool {
+        path.is_empty() || path.split('.').all(is_identifier)
+    }
+
+    match *key_path {
+        KeyPath::String(ref path) => is_valid(path),
+        KeyPath::Sequence(ref paths) => {
+            !paths.is_empty() && paths.iter().all(|path| is_valid(path))
+        },
+    }
+}
+
+/// <https://w3c.github.io/IndexedDB/#evaluate-a-key-path-on-a-value>
+///
+/// Returns whether the key path resolved to a value.
+#[allow(unsafe_code)]
+pub unsafe fn evaluate_key_path_on_value(
+    cx: *mut JSContext,
+    value: HandleValue,
+    key_path: &KeyPath,
+    mut rval: MutableHandleValue,
+) -> Fallible<bool> {
+    match *key_path {
+        KeyPath::String(ref path) => evaluate_path(cx, value, path, rval),
+        // Step 1.
+        KeyPath::Sequence(ref paths) => {
+            rooted!(in(cx) let array = JS_NewArrayObject(cx, &HandleValueArray::new()));
+            for (index, path) in paths.iter().enumerate() {
+                rooted!(in(cx) let mut key = UndefinedValue());
+                if !evaluate_path(cx, value, path, key.handle_mut())? {
+                    return Ok(false);
+                }
+                let _ =
+                    set_dictionary_property(cx, array.handle(), &index.to_string(), key.handle());
+            }
+            rval.set(ObjectValue(array.get()));
+            Ok(true)
+        },
+    }
+}
+
+/// Steps 2-5 of <https://w3c.github.io/IndexedDB/#evaluate-a-key-path-on-a-value>.
+#[allow(unsafe_code)]
+unsafe fn evaluate_path(
+    cx: *mut JSContext,
+    value: HandleValue,
+    path: &str,
+    mut rval: MutableHandleValue,
+) -> Fallible<bool> {
+    rval.set(value.get());
+    if path.is_empty() {
+        return Ok(true);
+    }
+    for identifier in path.split('.') {
+        rooted!(in(cx) let current = rval.get());
+        if current.is_string() && identifier == "length" {
+            let length =
+                match DOMString::from_jsval(cx, current.handle(), StringificationBehavior::Default)
+                {
+                    Ok(ConversionResult::Success(string)) => string.encode_utf16().count(),
+                    _ => return Err(Error::JSFailed),
+                };
+            rval.set(DoubleValue(length as f64));
+            continue;
+        }
+        // TODO: the attributes of Blob and File objects.
+        if !current.is_object() {
+            return Ok(false);
+        }
+        rooted!(in(cx) let object = current.to_object());
+        rooted!(in(cx) let mut property = UndefinedValue());
+        match get_dictionary_property(cx, object.handle(), identifier, property.handle_mut()) {
+            Ok(true) => rval.set(property.get()),
+            Ok(false) => return Ok(false),
+            Err(()) => return Err(Error::JSFailed),
+        }
+    }
+    Ok(true)
+}
+
+/// <https://w3c.github.io/IndexedDB/#extract-a-key-from-a-value-using-a-key-path>
+///
+/// Returns `None` if the key path doesn't resolve, and `Error::Data` if it
+/// resolves to an invalid key.
+#[allow(unsafe_code)]
+pub unsafe fn extract_key(
+    cx: *mut JSContext,
+    value: HandleValue,
+    key_path: &KeyPath,
+    multi_entry: bool,
+) -> Fallible<Option<IndexedDBKey>> {
+    rooted!(in(cx) let mut result = UndefinedValue());
+    if !evaluate_key_path_on_value(cx, value, key_path, result.handle_mut())? {
+        return Ok(None);
+    }
+    let key = if multi_entry {
+        convert_value_to_multi_entry_key(cx, result.handle())?
+    } else {
+        convert_value_to_key(cx, result.handle(), &mut vec![])?
+    };
+    Ok(Some(key))
+}
+
+/// The keys `value` has in each index, leaving out the indexes it has no
+/// valid key for, as in step 5 of
+/// <https://w3c.github.io/IndexedDB/#store-a-record-into-an-object-store>.
+#[allow(unsafe_code)]
+pub unsafe fn index_keys_for_value(
+    cx: *mut JSContext,
+    value: HandleValue,
+    indexes: &[IndexInfo],
+) -> Fallible<IndexKeys> {
+    let mut index_keys = vec![];
+    for index in indexes {
+        let keys = match extract_key(cx, value, &index.key_path, index.multi_entry) {
+            Ok(Some(IndexedDBKey::Array(keys))) if index.multi_entry => keys,
+            Ok(Some(key)) => vec![key],
+            Ok(None) | Err(Error::Data) => continue,
+            Err(error) => return Err(error),
+        };
+        if !keys.is_empty() {
+            index_keys.push((index.name.clone(), keys));
+        }
+    }
+    Ok(index_keys)
+}
+
+/// <https://w3c.github.io/IndexedDB/#check-that-a-key-could-be-injected-into-a-value>
+#[allow(unsafe_code)]
+pub unsafe fn can_inject_key(cx: *mut JSContext, value: HandleValue, key_path: &str) -> bool {
+    let mut identifiers: Vec<&str> = key_path.split('.').collect();
+    identifiers.pop();
+    rooted!(in(cx) let mut current = value.get());
+    for identifier in identifiers {
+        if !current.is_object() {
+            return false;
+        }
+        rooted!(in(cx) let object = current.to_object());
+        rooted!(in(cx) let mut property = UndefinedValue());
+        match get_dictionary_property(cx, object.handle(), identifier, property.handle_mut()) {
+            Ok(true) => current.set(property.get()),
+            _ => return true,
+        }
+    }
+    current.is_object()
+}
+
+/// <https://w3c.github.io/IndexedDB/#inject-a-key-into-a-value-using-a-key-path>
+#[allow(unsafe_code)]
+pub unsafe fn inject_key(
+    cx: *mut JSContext,
+    value: HandleValue,
+    key: &IndexedDBKey,
+    key_path: &str,
+) -> Fallible<()> {
+    let mut identifiers: Vec<&str> = key_path.split('.').collect();
+    let last = identifiers.pop().unwrap_or("");
+    rooted!(in(cx) let mut current = value.to_object());
+    for identifier in identifiers {
+        rooted!(in(cx) let mut property = UndefinedValue());
+        match get_dictionary_property(cx, current.handle(), identifier, property.handle_mut()) {
+            Ok(true) => {},
+            Ok(false) => {
+                property.set(ObjectValue(JS_NewPlainObject(cx)));
+                set_dictionary_property(cx, current.handle(), identifier, property.handle())
+                    .map_err(|()| Error::JSFailed)?;
+            },
+            Err(()) => return Err(Error::JSFailed),
+        }
+        current.set(property.to_object());
+    }
+    rooted!(in(cx) let mut key_value = UndefinedValue());
+    key_to_jsval(cx, key, key_value.handle_mut());
+    set_dictionary_property(cx, current.handle(), last, key_value.handle())
+        .map_err(|()| Error::JSFailed)
+}
@@ -17,6 +17,7 @@ use crate::dom::performanceresourcetiming::InitiatorType;
 use crate::network_listener::{self, NetworkListener, PreInvoke, ResourceTimingListener};
 use ipc_channel::ipc;
 use ipc_channel::router::ROUTER;
+use net_traits::csp::Violation;
 use net_traits::image_cache::{ImageCache, PendingImageId};
 use net_traits::request::{Destination, RequestBuilder as FetchRequestInit};
 use net_traits::{FetchMetadata, FetchResponseListener, FetchResponseMsg, NetworkError};
@@ -61,6 +62,11 @@ impl FetchResponseListener for LayoutImageContext {
     fn submit_resource_timing(&mut self) {
         network_listener::submit_timing(self)
     }
+
+    fn process_csp_violations(&mut self, violations: Vec<Violation>) {
+        let global = &self.resource_timing_global();
+        global.report_csp_violations(violations);
+    }
 }
 
 impl ResourceTimingListener for LayoutImageContext {
@@ -113,7 +119,8 @@ pub fn fetch_image_for_layout(
     let request = FetchRequestInit::new(url)
         .origin(document.origin().immutable().clone())
         .destination(Destination::Image)
-        .pipeline_id(Some(document.global().pipeline_id()));
+        .pipeline_id(Some(document.global().pipeline_id()))
+        .csp_list(document.get_csp_list());
 
     // Layout image loads do not delay the document load event.
     document
@@ -51,6 +51,7 @@ extern crate style;
 mod task;
 mod body;
 pub mod clipboard_provider;
+mod debugger;
 mod devtools;
 pub mod document_loader;
 #[macro_use]
@@ -58,6 +59,7 @@ mod dom;
 mod compartments;
 pub mod fetch;
 mod image_listener;
+mod indexed_db;
 mod layout_image;
 mod mem;
 mod microtask;
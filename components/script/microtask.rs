@@ -6,6 +6,7 @@
 //! microtask queues. It is up to implementations of event loops to store a queue and
 //! perform checkpoints at appropriate times, as well as enqueue microtasks as required.
 
+use crate::devtools;
 use crate::dom::bindings::callback::ExceptionHandling;
 use crate::dom::bindings::cell::DomRefCell;
 use crate::dom::bindings::codegen::Bindings::PromiseBinding::PromiseJobCallback;
@@ -38,6 +39,7 @@ pub enum Microtask {
     ImageElement(ImageElementMicrotask),
     CustomElementReaction,
     NotifyMutationObservers,
+    NotifyDevtoolsOfMutations(PipelineId),
 }
 
 pub trait MicrotaskRunnable {
@@ -107,6 +109,11 @@ impl MicrotaskQueue {
                     Microtask::NotifyMutationObservers => {
                         MutationObserver::notify_mutation_observers();
                     },
+                    Microtask::NotifyDevtoolsOfMutations(pipeline) => {
+                        if let Some(target) = target_provider(pipeline) {
+                            devtools::notify_mutations(target.as_window());
+                        }
+                    },
                 }
             }
         }
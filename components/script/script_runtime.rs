@@ -36,6 +36,7 @@ use js::jsapi::{
     JSJitCompilerOption, JS_SetOffthreadIonCompilationEnabled, JS_SetParallelParsingEnabled,
 };
 use js::jsapi::{JSObject, PromiseRejectionHandlingState, SetPreserveWrapperCallback};
+use js::jsapi::{JSSecurityCallbacks, JS_SetSecurityCallbacks};
 use js::jsapi::{SetJobQueue, SetProcessBuildIdOp, SetPromiseRejectionTrackerCallback};
 use js::panic::wrap_panic;
 use js::rust::wrappers::{GetPromiseIsHandled, GetPromiseResult};
@@ -190,6 +191,24 @@ unsafe extern "C" fn enqueue_promise_job(
     )
 }
 
+static SECURITY_CALLBACKS: JSSecurityCallbacks = JSSecurityCallbacks {
+    contentSecurityPolicyAllows: Some(content_security_policy_allows),
+    subsumes: None,
+};
+
+/// SM callback deciding whether strings may be compiled into code, e.g. by `eval`.
+/// <https://w3c.github.io/webappsec-csp/#can-compile-strings>
+#[allow(unsafe_code)]
+unsafe extern "C" fn content_security_policy_allows(cx: *mut JSContext) -> bool {
+    wrap_panic(
+        AssertUnwindSafe(|| {
+            let global = GlobalScope::from_context(cx);
+            global.is_js_evaluation_allowed()
+        }),
+        false,
+    )
+}
+
 #[allow(unsafe_code, unrooted_must_root)]
 /// https://html.spec.whatwg.org/multipage/#the-hostpromiserejectiontracker-implementation
 unsafe extern "C" fn promise_rejection_tracker(
@@ -416,6 +435,7 @@ unsafe fn new_rt_and_cx_with_parent(parent: Option<ParentRuntime>) -> Runtime {
     );
     SetJobQueue(cx, job_queue);
     SetPromiseRejectionTrackerCallback(cx, Some(promise_rejection_tracker), ptr::null_mut());
+    JS_SetSecurityCallbacks(cx, &SECURITY_CALLBACKS);
 
     set_gc_zeal_options(cx);
 
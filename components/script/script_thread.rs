@@ -18,6 +18,7 @@
 //! loop.
 
 use crate::compartments::enter_realm;
+use crate::debugger::ScriptDebugger;
 use crate::devtools;
 use crate::document_loader::DocumentLoader;
 use crate::dom::bindings::cell::DomRefCell;
@@ -37,7 +38,6 @@ use crate::dom::bindings::reflector::DomObject;
 use crate::dom::bindings::root::ThreadLocalStackRoots;
 use crate::dom::bindings::root::{Dom, DomRoot, MutNullableDom, RootCollection};
 use crate::dom::bindings::str::DOMString;
-use crate::dom::bindings::structuredclone::StructuredCloneData;
 use crate::dom::bindings::trace::JSTraceable;
 use crate::dom::bindings::utils::WRAP_CALLBACKS;
 use crate::dom::customelementregistry::{
@@ -80,6 +80,7 @@ use crate::task_source::history_traversal::HistoryTraversalTaskSource;
 use crate::task_source::media_element::MediaElementTaskSource;
 use crate::task_source::networking::NetworkingTaskSource;
 use crate::task_source::performance_timeline::PerformanceTimelineTaskSource;
+use crate::task_source::port_message::PortMessageQueue;
 use crate::task_source::remote_event::RemoteEventTaskSource;
 use crate::task_source::user_interaction::UserInteractionTaskSource;
 use crate::task_source::websocket::WebsocketTaskSource;
@@ -89,7 +90,7 @@ use bluetooth_traits::BluetoothRequest;
 use canvas_traits::webgl::WebGLPipeline;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use devtools_traits::CSSError;
-use devtools_traits::{DevtoolScriptControlMsg, DevtoolsPageInfo};
+use devtools_traits::{DevtoolScriptControlMsg, DevtoolsPageInfo, PauseInfo, ResumeLimit};
 use devtools_traits::{ScriptToDevtoolsControlMsg, WorkerId};
 use embedder_traits::EmbedderMsg;
 use euclid::{Point2D, Rect, Vector2D};
@@ -112,6 +113,7 @@ use msg::constellation_msg::{
 use msg::constellation_msg::{BrowsingContextId, HistoryStateId, PipelineId};
 use msg::constellation_msg::{HangAnnotation, MonitoredComponentId, MonitoredComponentType};
 use msg::constellation_msg::{PipelineNamespace, TopLevelBrowsingContextId};
+use net_traits::csp::{CheckResult, CspList, InlineCheckType};
 use net_traits::image_cache::{ImageCache, PendingImageResponse};
 use net_traits::request::{CredentialsMode, Destination, RedirectMode, RequestBuilder};
 use net_traits::storage_thread::StorageType;
@@ -123,6 +125,7 @@ use net_traits::{
 use profile_traits::mem::{self as profile_mem, OpaqueSender, ReportsChan};
 use profile_traits::time::{self as profile_time, profile, ProfilerCategory};
 use script_layout_interface::message::{self, LayoutThreadInit, Msg, ReflowGoal};
+use script_traits::transferable::StructuredSerializedData;
 use script_traits::webdriver_msg::WebDriverScriptCommand;
 use script_traits::CompositorEvent::{
     CompositionEvent, KeyboardEvent, MouseButtonEvent, MouseMoveEvent, ResizeEvent, TouchEvent,
@@ -564,6 +567,8 @@ pub struct ScriptThread {
 
     performance_timeline_task_sender: Box<dyn ScriptChan>,
 
+    port_message_sender: Box<dyn ScriptChan>,
+
     remote_event_task_sender: Box<dyn ScriptChan>,
 
     /// A channel to hand out to threads that need to respond to a message from the script thread.
@@ -596,6 +601,8 @@ pub struct ScriptThread {
     /// no such server exists.
     devtools_port: Receiver<DevtoolScriptControlMsg>,
     devtools_sender: IpcSender<DevtoolScriptControlMsg>,
+    /// The debugger of the windows of this thread, if there is a devtools server.
+    debugger: Option<ScriptDebugger>,
 
     /// The JavaScript runtime.
     js_runtime: Rc<Runtime>,
@@ -1140,6 +1147,10 @@ impl ScriptThread {
         let (ipc_devtools_sender, ipc_devtools_receiver) = ipc::channel().unwrap();
         let devtools_port =
             ROUTER.route_ipc_receiver_to_new_crossbeam_receiver(ipc_devtools_receiver);
+        let debugger = state
+            .devtools_chan
+            .as_ref()
+            .map(|_| ScriptDebugger::new(cx));
 
         let (timer_event_chan, timer_event_port) = unbounded();
 
@@ -1185,6 +1196,7 @@ impl ScriptThread {
             networking_task_sender: boxed_script_sender.clone(),
             file_reading_task_sender: boxed_script_sender.clone(),
             performance_timeline_task_sender: boxed_script_sender.clone(),
+            port_message_sender: boxed_script_sender.clone(),
             remote_event_task_sender: boxed_script_sender.clone(),
 
             history_traversal_task_sender: chan.clone(),
@@ -1198,6 +1210,7 @@ impl ScriptThread {
             devtools_chan: state.devtools_chan,
             devtools_port: devtools_port,
             devtools_sender: ipc_devtools_sender,
+            debugger,
 
             microtask_queue: runtime.microtask_queue.clone(),
 
@@ -1461,11 +1474,21 @@ impl ScriptThread {
         // TODO(gw): In the future we could probably batch other types of reflows
         // into this loop too, but for now it's only images.
         debug!("Issuing batched reflows.");
-        for (_, document) in self.documents.borrow().iter() {
+        // Observer callbacks run script, which may add or remove documents.
+        let documents: Vec<_> = self
+            .documents
+            .borrow()
+            .iter()
+            .map(|(_, document)| document)
+            .collect();
+        for document in documents {
             // Step 13
             if !document.is_fully_active() {
                 continue;
             }
+            // https://drafts.csswg.org/resize-observer/#html-event-loop
+            document.update_resize_observations();
+
             let window = document.window();
             let pending_reflows = window.get_pending_reflow_count();
             if pending_reflows > 0 {
@@ -1477,6 +1500,9 @@ impl ScriptThread {
                 // minimize unnecessary work.
                 window.reflow(ReflowGoal::Full, ReflowReason::MissingExplicitReflow);
             }
+
+            // https://w3c.github.io/IntersectionObserver/#event-loop
+            document.update_intersection_observations();
         }
 
         true
@@ -1550,6 +1576,7 @@ impl ScriptThread {
                 match *inner_msg {
                     StopDelayingLoadEventsMode(id) => Some(id),
                     NavigationResponse(id, _) => Some(id),
+                    ReportCspViolations(id, _) => Some(id),
                     AttachLayout(ref new_layout_info) => Some(new_layout_info.new_pipeline_id),
                     Resize(id, ..) => Some(id),
                     ResizeInactive(id, ..) => Some(id),
@@ -1703,6 +1730,11 @@ impl ScriptThread {
                     _ => unreachable!(),
                 };
             },
+            ConstellationControlMsg::ReportCspViolations(id, violations) => {
+                if let Some(document) = self.documents.borrow().find_document(id) {
+                    document.global().report_csp_violations(violations);
+                }
+            },
             ConstellationControlMsg::Navigate(
                 parent_pipeline_id,
                 browsing_context_id,
@@ -1896,6 +1928,30 @@ impl ScriptThread {
             DevtoolScriptControlMsg::ModifyAttribute(id, node_id, modifications) => {
                 devtools::handle_modify_attribute(&*documents, id, node_id, modifications)
             },
+            DevtoolScriptControlMsg::InsertBefore(id, node_id, parent_id, sibling_id) => {
+                devtools::handle_insert_before(&*documents, id, node_id, parent_id, sibling_id)
+            },
+            DevtoolScriptControlMsg::RemoveNode(id, node_id, reply) => {
+                devtools::handle_remove_node(&*documents, id, node_id, reply)
+            },
+            DevtoolScriptControlMsg::GetOuterHTML(id, node_id, reply) => {
+                devtools::handle_get_outer_html(&*documents, id, node_id, reply)
+            },
+            DevtoolScriptControlMsg::SetOuterHTML(id, node_id, html) => {
+                devtools::handle_set_outer_html(&*documents, id, node_id, html)
+            },
+            DevtoolScriptControlMsg::SetNodeValue(id, node_id, value) => {
+                devtools::handle_set_node_value(&*documents, id, node_id, value)
+            },
+            DevtoolScriptControlMsg::WantsMutationNotifications(id, to_send) => {
+                devtools::handle_wants_mutation_notifications(&*documents, id, to_send)
+            },
+            DevtoolScriptControlMsg::GetAppliedRules(id, node_id, reply) => {
+                devtools::handle_get_applied_rules(&*documents, id, node_id, reply)
+            },
+            DevtoolScriptControlMsg::ModifyRule(id, rule_id, modifications, reply) => {
+                devtools::handle_modify_rule(&*documents, id, rule_id, modifications, reply)
+            },
             DevtoolScriptControlMsg::WantsLiveNotifications(id, to_send) => match documents
                 .find_window(id)
             {
@@ -1912,9 +1968,62 @@ impl ScriptThread {
                 devtools::handle_request_animation_frame(&*documents, id, name)
             },
             DevtoolScriptControlMsg::Reload(id) => devtools::handle_reload(&*documents, id),
+            DevtoolScriptControlMsg::GetStyleSheets(id, reply) => {
+                devtools::handle_get_stylesheets(&*documents, id, reply)
+            },
+            DevtoolScriptControlMsg::GetStyleSheetText(id, sheet_id, reply) => {
+                devtools::handle_get_stylesheet_text(&*documents, id, sheet_id, reply)
+            },
+            DevtoolScriptControlMsg::GetStyleSheetRules(id, sheet_id, reply) => {
+                devtools::handle_get_stylesheet_rules(&*documents, id, sheet_id, reply)
+            },
+            DevtoolScriptControlMsg::UpdateStyleSheet(id, sheet_id, text, reply) => {
+                devtools::handle_update_stylesheet(&*documents, id, sheet_id, text, reply)
+            },
+            DevtoolScriptControlMsg::ToggleStyleSheetDisabled(id, sheet_id, reply) => {
+                devtools::handle_toggle_stylesheet_disabled(&*documents, id, sheet_id, reply)
+            },
+            msg @ DevtoolScriptControlMsg::ListSources(..) |
+            msg @ DevtoolScriptControlMsg::GetSourceText(..) |
+            msg @ DevtoolScriptControlMsg::SetBreakpoint(..) |
+            msg @ DevtoolScriptControlMsg::RemoveBreakpoint(..) |
+            msg @ DevtoolScriptControlMsg::PauseOnExceptions(..) |
+            msg @ DevtoolScriptControlMsg::Interrupt(..) |
+            msg @ DevtoolScriptControlMsg::Resume(..) |
+            msg @ DevtoolScriptControlMsg::GetFrames(..) |
+            msg @ DevtoolScriptControlMsg::GetEnvironments(..) |
+            msg @ DevtoolScriptControlMsg::GetObjectProperties(..) => {
+                if let Some(ref debugger) = self.debugger {
+                    debugger.handle_msg(msg);
+                }
+            },
         }
     }
 
+    /// Called by the debugger when script pauses in the given pipeline. Until devtools
+    /// resume it, nothing but devtools messages are handled, so that the page stays as
+    /// the debugger shows it.
+    pub fn pause_for_debugger(pipeline_id: PipelineId, pause: PauseInfo) -> Option<ResumeLimit> {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            let script_thread = unsafe { &*root.get().unwrap() };
+            let chan = script_thread.devtools_chan.as_ref()?;
+            chan.send(ScriptToDevtoolsControlMsg::DebuggerPaused(
+                pipeline_id,
+                pause,
+            ))
+            .unwrap();
+            while let Ok(msg) = script_thread.devtools_port.recv() {
+                match msg {
+                    DevtoolScriptControlMsg::Resume(id, limit) if id == pipeline_id => {
+                        return limit;
+                    },
+                    msg => script_thread.handle_msg_from_devtools(msg),
+                }
+            }
+            None
+        })
+    }
+
     fn handle_msg_from_image_cache(&self, (id, response): (PipelineId, PendingImageResponse)) {
         let window = self.documents.borrow().find_window(id);
         if let Some(ref window) = window {
@@ -1995,6 +2104,9 @@ impl ScriptThread {
             WebDriverScriptCommand::GetPageSource(reply) => {
                 webdriver_handlers::handle_get_page_source(&*documents, pipeline_id, reply)
             },
+            WebDriverScriptCommand::Print(settings, reply) => {
+                webdriver_handlers::handle_print(&*documents, pipeline_id, settings, reply)
+            },
             WebDriverScriptCommand::GetCookies(reply) => {
                 webdriver_handlers::handle_get_cookies(&*documents, pipeline_id, reply)
             },
@@ -2019,6 +2131,28 @@ impl ScriptThread {
             WebDriverScriptCommand::GetElementRect(node_id, reply) => {
                 webdriver_handlers::handle_get_rect(&*documents, pipeline_id, node_id, reply)
             },
+            WebDriverScriptCommand::GetElementInViewCenterPoint(node_id, reply) => {
+                webdriver_handlers::handle_get_element_in_view_center_point(
+                    &*documents,
+                    pipeline_id,
+                    node_id,
+                    reply,
+                )
+            },
+            WebDriverScriptCommand::ScrollIntoView(node_id, reply) => {
+                webdriver_handlers::handle_scroll_into_view(
+                    &*documents,
+                    pipeline_id,
+                    node_id,
+                    reply,
+                )
+            },
+            WebDriverScriptCommand::ElementClick(node_id, reply) => {
+                webdriver_handlers::handle_element_click(&*documents, pipeline_id, node_id, reply)
+            },
+            WebDriverScriptCommand::ElementClear(node_id, reply) => {
+                webdriver_handlers::handle_element_clear(&*documents, pipeline_id, node_id, reply)
+            },
             WebDriverScriptCommand::GetElementText(node_id, reply) => {
                 webdriver_handlers::handle_get_text(&*documents, pipeline_id, node_id, reply)
             },
@@ -2288,7 +2422,7 @@ impl ScriptThread {
         source_pipeline_id: PipelineId,
         source_browsing_context: TopLevelBrowsingContextId,
         origin: Option<ImmutableOrigin>,
-        data: Vec<u8>,
+        data: StructuredSerializedData,
     ) {
         match { self.documents.borrow().find_window(pipeline_id) } {
             None => return warn!("postMessage after target pipeline {} closed.", pipeline_id),
@@ -2310,7 +2444,7 @@ impl ScriptThread {
                     Some(source) => source,
                 };
                 // FIXME(#22512): enqueues a task; unnecessary delay.
-                window.post_message(origin, &*source, StructuredCloneData::Vector(data))
+                window.post_message(origin, &*source, data)
             },
         }
     }
@@ -2561,6 +2695,10 @@ impl ScriptThread {
         WebsocketTaskSource(self.remote_event_task_sender.clone(), pipeline_id)
     }
 
+    pub fn port_message_queue(&self, pipeline_id: PipelineId) -> PortMessageQueue {
+        PortMessageQueue(self.port_message_sender.clone(), pipeline_id)
+    }
+
     /// Handles a request for the window title.
     fn handle_get_title_msg(&self, pipeline_id: PipelineId) {
         let document = match { self.documents.borrow().find_document(pipeline_id) } {
@@ -2627,6 +2765,9 @@ impl ScriptThread {
             if discard_bc == DiscardBrowsingContext::Yes {
                 window.window_proxy().discard_browsing_context();
             }
+            if let Some(ref debugger) = self.debugger {
+                debugger.remove_debuggee(id);
+            }
             window.clear_js_runtime();
         }
 
@@ -2946,6 +3087,7 @@ impl ScriptThread {
             self.networking_task_source(incomplete.pipeline_id),
             self.performance_timeline_task_source(incomplete.pipeline_id)
                 .clone(),
+            self.port_message_queue(incomplete.pipeline_id),
             self.user_interaction_task_source(incomplete.pipeline_id),
             self.remote_event_task_source(incomplete.pipeline_id),
             self.websocket_task_source(incomplete.pipeline_id),
@@ -3049,6 +3191,12 @@ impl ScriptThread {
             .and_then(|h| h.typed_get::<ReferrerPolicyHeader>())
             .map(ReferrerPolicy::from);
 
+        let csp_list = metadata
+            .headers
+            .as_ref()
+            .map(|h| CspList::parse_response_headers(h))
+            .filter(|csp_list| !csp_list.is_empty());
+
         let document = Document::new(
             &window,
             HasBrowsingContext::Yes,
@@ -3065,6 +3213,7 @@ impl ScriptThread {
             incomplete.canceller,
         );
         document.set_ready_state(DocumentReadyState::Loading);
+        document.set_csp_list(csp_list);
 
         self.documents
             .borrow_mut()
@@ -3098,6 +3247,9 @@ impl ScriptThread {
             final_url.clone(),
             (incomplete.pipeline_id, None),
         );
+        if let Some(ref debugger) = self.debugger {
+            debugger.add_debuggee(incomplete.pipeline_id, window.upcast());
+        }
 
         let parse_input = DOMString::new();
 
@@ -3366,7 +3518,16 @@ impl ScriptThread {
         if is_javascript {
             let window = self.documents.borrow().find_window(parent_pipeline_id);
             if let Some(window) = window {
-                ScriptThread::eval_js_url(window.upcast::<GlobalScope>(), &mut load_data);
+                // https://w3c.github.io/webappsec-csp/#should-block-navigation-request
+                let global = window.upcast::<GlobalScope>();
+                if global.should_inline_behavior_be_blocked(
+                    InlineCheckType::Navigation,
+                    load_data.url.as_str(),
+                ) == CheckResult::Blocked
+                {
+                    return;
+                }
+                ScriptThread::eval_js_url(global, &mut load_data);
             }
         }
 
@@ -3469,9 +3630,14 @@ impl ScriptThread {
     /// argument until a notification is received that the fetch is complete.
     fn pre_page_load(&self, mut incomplete: InProgressLoad, load_data: LoadData) {
         let id = incomplete.pipeline_id.clone();
+        // https://fetch.spec.whatwg.org/#concept-request-destination
+        let destination = match incomplete.parent_info {
+            Some(_) => Destination::IFrame,
+            None => Destination::Document,
+        };
         let req_init = RequestBuilder::new(load_data.url.clone())
             .method(load_data.method)
-            .destination(Destination::Document)
+            .destination(destination)
             .credentials_mode(CredentialsMode::Include)
             .use_url_credentials(true)
             .pipeline_id(Some(id))
@@ -3480,7 +3646,8 @@ impl ScriptThread {
             .headers(load_data.headers)
             .body(load_data.data)
             .redirect_mode(RedirectMode::Manual)
-            .origin(incomplete.origin.immutable().clone());
+            .origin(incomplete.origin.immutable().clone())
+            .csp_list(load_data.csp_list);
 
         let context = ParserContext::new(id, load_data.url);
         self.incomplete_parser_contexts
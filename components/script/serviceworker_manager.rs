@@ -6,20 +6,25 @@
 //! It also stores an active workers map, which holds descriptors of running service workers.
 //! If an active service worker timeouts, then it removes the descriptor entry from its
 //! active_workers map
+//!
+//! Once the service worker of a scope is activated, the documents navigated to in that scope
+//! become its clients: it is asked to respond to their navigation and subresource requests.
 
 use crate::dom::abstractworker::WorkerScriptMsg;
-use crate::dom::bindings::structuredclone::StructuredCloneData;
 use crate::dom::serviceworkerglobalscope::{ServiceWorkerGlobalScope, ServiceWorkerScriptMsg};
 use crate::dom::serviceworkerregistration::longest_prefix_match;
 use crossbeam_channel::{unbounded, Receiver, RecvError, Sender};
 use devtools_traits::{DevtoolsPageInfo, ScriptToDevtoolsControlMsg};
 use ipc_channel::ipc::{self, IpcSender};
 use ipc_channel::router::ROUTER;
+use msg::constellation_msg::PipelineId;
+use net_traits::request::Destination;
 use net_traits::{CoreResourceMsg, CustomResponseMediator};
+use script_traits::transferable::StructuredSerializedData;
 use script_traits::{DOMMessage, SWManagerMsg, SWManagerSenders, ScopeThings, ServiceWorkerMsg};
 use servo_config::pref;
 use servo_url::ServoUrl;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::thread;
 
 enum Message {
@@ -32,12 +37,18 @@ pub struct ServiceWorkerManager {
     registered_workers: HashMap<ServoUrl, ScopeThings>,
     // map of active service worker descriptors
     active_workers: HashMap<ServoUrl, Sender<ServiceWorkerScriptMsg>>,
+    // scopes whose service worker was installed and activated
+    activated_scopes: HashSet<ServoUrl>,
+    // the scope of the service worker controlling each client
+    controlled_clients: HashMap<PipelineId, ServoUrl>,
     // own sender to send messages here
     own_sender: IpcSender<ServiceWorkerMsg>,
     // receiver to receive messages from constellation
     own_port: Receiver<ServiceWorkerMsg>,
     // to receive resource messages
     resource_receiver: Receiver<CustomResponseMediator>,
+    // to tell the resource thread which requests to send here
+    resource_sender: IpcSender<CoreResourceMsg>,
 }
 
 impl ServiceWorkerManager {
@@ -45,13 +56,17 @@ impl ServiceWorkerManager {
         own_sender: IpcSender<ServiceWorkerMsg>,
         from_constellation_receiver: Receiver<ServiceWorkerMsg>,
         resource_port: Receiver<CustomResponseMediator>,
+        resource_sender: IpcSender<CoreResourceMsg>,
     ) -> ServiceWorkerManager {
         ServiceWorkerManager {
             registered_workers: HashMap::new(),
             active_workers: HashMap::new(),
+            activated_scopes: HashSet::new(),
+            controlled_clients: HashMap::new(),
             own_sender: own_sender,
             own_port: from_constellation_receiver,
             resource_receiver: resource_port,
+            resource_sender: resource_sender,
         }
     }
 
@@ -67,28 +82,60 @@ impl ServiceWorkerManager {
         let _ = sw_senders
             .swmanager_sender
             .send(SWManagerMsg::OwnSender(own_sender.clone()));
+        let resource_sender = sw_senders.resource_sender;
         thread::Builder::new()
             .name("ServiceWorkerManager".to_owned())
             .spawn(move || {
-                ServiceWorkerManager::new(own_sender, from_constellation, resource_port)
-                    .handle_message();
+                ServiceWorkerManager::new(
+                    own_sender,
+                    from_constellation,
+                    resource_port,
+                    resource_sender,
+                )
+                .handle_message();
             })
             .expect("Thread spawning failed");
     }
 
+    /// <https://w3c.github.io/ServiceWorker/#scope-match-algorithm>, among the scopes
+    /// whose service worker is activated.
     pub fn get_matching_scope(&self, load_url: &ServoUrl) -> Option<ServoUrl> {
-        for scope in self.registered_workers.keys() {
-            if longest_prefix_match(&scope, load_url) {
-                return Some(scope.clone());
+        self.activated_scopes
+            .iter()
+            .filter(|scope| longest_prefix_match(scope, load_url))
+            .max_by_key(|scope| scope.path().len())
+            .cloned()
+    }
+
+    /// The scope of the service worker a request should be handled by, if any. A navigation
+    /// makes the document it creates a client of the service worker of its scope, while a
+    /// subresource request is handled by the service worker of the client it was made by.
+    fn get_controlling_scope(&mut self, mediator: &CustomResponseMediator) -> Option<ServoUrl> {
+        let pipeline_id = mediator.request.pipeline_id?;
+        let destination = mediator.request.destination;
+        if destination == Destination::Document || destination == Destination::IFrame {
+            match self.get_matching_scope(&mediator.request.url) {
+                Some(scope) => {
+                    self.controlled_clients.insert(pipeline_id, scope.clone());
+                    Some(scope)
+                },
+                None => {
+                    self.controlled_clients.remove(&pipeline_id);
+                    None
+                },
             }
+        } else {
+            self.controlled_clients.get(&pipeline_id).cloned()
         }
-        None
     }
 
     pub fn wakeup_serviceworker(
         &mut self,
         scope_url: ServoUrl,
     ) -> Option<Sender<ServiceWorkerScriptMsg>> {
+        // A service worker that was never activated runs its install and activate
+        // events before anything else.
+        let install = !self.activated_scopes.contains(&scope_url);
         let scope_things = self.registered_workers.get(&scope_url);
         if let Some(scope_things) = scope_things {
             let (sender, receiver) = unbounded();
@@ -112,6 +159,7 @@ impl ServiceWorkerManager {
                 devtools_receiver,
                 self.own_sender.clone(),
                 scope_url.clone(),
+                install,
             );
             // We store the activated worker
             self.active_workers.insert(scope_url, sender.clone());
@@ -136,7 +184,7 @@ impl ServiceWorkerManager {
 
     fn forward_message(&self, msg: DOMMessage, sender: &Sender<ServiceWorkerScriptMsg>) {
         let DOMMessage(data) = msg;
-        let data = StructuredCloneData::Vector(data);
+        let data = StructuredSerializedData::new(data);
         let _ = sender.send(ServiceWorkerScriptMsg::CommonWorker(
             WorkerScriptMsg::DOMMessage(data),
         ));
@@ -148,10 +196,30 @@ impl ServiceWorkerManager {
                 if self.registered_workers.contains_key(&scope) {
                     warn!("ScopeThings for {:?} already stored in SW-Manager", scope);
                 } else {
-                    self.registered_workers.insert(scope, scope_things);
+                    self.registered_workers.insert(scope.clone(), scope_things);
+                    // https://w3c.github.io/ServiceWorker/#installation-algorithm
+                    let _ = self.wakeup_serviceworker(scope);
                 }
                 true
             },
+            ServiceWorkerMsg::Activated(scope) => {
+                self.activated_scopes.insert(scope);
+                let origins = self
+                    .activated_scopes
+                    .iter()
+                    .map(|scope| scope.origin())
+                    .collect();
+                let _ = self
+                    .resource_sender
+                    .send(CoreResourceMsg::ServiceWorkerOrigins(origins));
+                true
+            },
+            ServiceWorkerMsg::InstallFailed(scope) => {
+                warn!("ServiceWorker for {:?} failed to install", scope);
+                self.registered_workers.remove(&scope);
+                self.active_workers.remove(&scope);
+                true
+            },
             ServiceWorkerMsg::Timeout(scope) => {
                 if self.active_workers.contains_key(&scope) {
                     let _ = self.active_workers.remove(&scope);
@@ -178,7 +246,7 @@ impl ServiceWorkerManager {
 
     fn handle_message_from_resource(&mut self, mediator: CustomResponseMediator) -> bool {
         if serviceworker_enabled() {
-            if let Some(scope) = self.get_matching_scope(&mediator.load_url) {
+            if let Some(scope) = self.get_controlling_scope(&mediator) {
                 if self.active_workers.contains_key(&scope) {
                     if let Some(sender) = self.active_workers.get(&scope) {
                         let _ = sender.send(ServiceWorkerScriptMsg::Response(mediator));
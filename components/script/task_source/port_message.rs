@@ -0,0 +1,34 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::script_runtime::{CommonScriptMsg, ScriptChan, ScriptThreadEventCategory};
+use crate::task::{TaskCanceller, TaskOnce};
+use crate::task_source::{TaskSource, TaskSourceName};
+use msg::constellation_msg::PipelineId;
+
+/// <https://html.spec.whatwg.org/multipage/#posted-message-task-source>
+#[derive(JSTraceable)]
+pub struct PortMessageQueue(pub Box<dyn ScriptChan + Send + 'static>, pub PipelineId);
+
+impl Clone for PortMessageQueue {
+    fn clone(&self) -> PortMessageQueue {
+        PortMessageQueue(self.0.clone(), self.1.clone())
+    }
+}
+
+impl TaskSource for PortMessageQueue {
+    const NAME: TaskSourceName = TaskSourceName::PortMessage;
+
+    fn queue_with_canceller<T>(&self, task: T, canceller: &TaskCanceller) -> Result<(), ()>
+    where
+        T: TaskOnce + 'static,
+    {
+        self.0.send(CommonScriptMsg::Task(
+            ScriptThreadEventCategory::DomEvent,
+            Box::new(canceller.wrap_task(task)),
+            Some(self.1),
+            PortMessageQueue::NAME,
+        ))
+    }
+}
@@ -516,10 +516,13 @@ impl JsTimerTask {
         match self.callback {
             InternalTimerCallback::StringTimerCallback(ref code_str) => {
                 let global = this.global();
-                let cx = global.get_cx();
-                rooted!(in(cx) let mut rval = UndefinedValue());
+                // Compiling the string is subject to the same checks as `eval`.
+                if global.is_js_evaluation_allowed() {
+                    let cx = global.get_cx();
+                    rooted!(in(cx) let mut rval = UndefinedValue());
 
-                global.evaluate_js_on_global_with_result(code_str, rval.handle_mut());
+                    global.evaluate_js_on_global_with_result(code_str, rval.handle_mut());
+                }
             },
             InternalTimerCallback::FunctionTimerCallback(ref function, ref arguments) => {
                 let arguments = self.collect_heap_args(arguments);
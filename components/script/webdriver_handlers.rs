@@ -3,27 +3,32 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::dom::bindings::codegen::Bindings::CSSStyleDeclarationBinding::CSSStyleDeclarationMethods;
+use crate::dom::bindings::codegen::Bindings::DOMRectBinding::DOMRectMethods;
 use crate::dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use crate::dom::bindings::codegen::Bindings::ElementBinding::ElementMethods;
 use crate::dom::bindings::codegen::Bindings::HTMLElementBinding::HTMLElementMethods;
 use crate::dom::bindings::codegen::Bindings::HTMLInputElementBinding::HTMLInputElementMethods;
 use crate::dom::bindings::codegen::Bindings::HTMLOptionElementBinding::HTMLOptionElementMethods;
+use crate::dom::bindings::codegen::Bindings::HTMLTextAreaElementBinding::HTMLTextAreaElementMethods;
 use crate::dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
-use crate::dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use crate::dom::bindings::codegen::Bindings::WindowBinding::{ScrollBehavior, WindowMethods};
 use crate::dom::bindings::codegen::Bindings::XMLSerializerBinding::XMLSerializerMethods;
 use crate::dom::bindings::conversions::{
     ConversionResult, FromJSValConvertible, StringificationBehavior,
 };
 use crate::dom::bindings::inheritance::Castable;
+use crate::dom::bindings::num::Finite;
 use crate::dom::bindings::root::DomRoot;
 use crate::dom::bindings::str::DOMString;
 use crate::dom::element::Element;
+use crate::dom::eventtarget::EventTarget;
 use crate::dom::globalscope::GlobalScope;
 use crate::dom::htmlelement::HTMLElement;
 use crate::dom::htmliframeelement::HTMLIFrameElement;
-use crate::dom::htmlinputelement::HTMLInputElement;
+use crate::dom::htmlinputelement::{HTMLInputElement, InputType};
 use crate::dom::htmloptionelement::HTMLOptionElement;
-use crate::dom::node::{window_from_node, Node, ShadowIncluding};
+use crate::dom::htmltextareaelement::HTMLTextAreaElement;
+use crate::dom::node::{document_from_node, window_from_node, Node, ShadowIncluding};
 use crate::dom::window::Window;
 use crate::dom::xmlserializer::XMLSerializer;
 use crate::script_thread::Documents;
@@ -39,10 +44,11 @@ use msg::constellation_msg::PipelineId;
 use net_traits::CookieSource::{NonHTTP, HTTP};
 use net_traits::CoreResourceMsg::{DeleteCookies, GetCookiesDataForUrl, SetCookieForUrl};
 use net_traits::IpcSend;
-use script_traits::webdriver_msg::WebDriverCookieError;
+use script_traits::webdriver_msg::{WebDriverCookieError, WebDriverInteractionError};
 use script_traits::webdriver_msg::{
     WebDriverFrameId, WebDriverJSError, WebDriverJSResult, WebDriverJSValue,
 };
+use script_traits::PrintSettings;
 use servo_url::ServoUrl;
 
 fn find_node_by_unique_id(
@@ -308,6 +314,22 @@ pub fn handle_get_page_source(
         .unwrap();
 }
 
+pub fn handle_print(
+    documents: &Documents,
+    pipeline: PipelineId,
+    settings: PrintSettings,
+    reply: IpcSender<Result<Vec<u8>, ()>>,
+) {
+    reply
+        .send(
+            documents
+                .find_window(pipeline)
+                .and_then(|window| window.print_to_pdf(settings))
+                .ok_or(()),
+        )
+        .unwrap();
+}
+
 pub fn handle_get_cookies(
     documents: &Documents,
     pipeline: PipelineId,
@@ -482,6 +504,216 @@ pub fn handle_get_rect(
         .unwrap();
 }
 
+/// https://w3c.github.io/webdriver/#dfn-in-view-center-point, in CSS pixels relative to the
+/// viewport. `None` if the element has no client rects or is out of the viewport.
+fn in_view_center_point(element: &Element) -> Option<(i64, i64)> {
+    // Step 1
+    let rect = element.GetClientRects().into_iter().next()?;
+    let window = window_from_node(element);
+    // Steps 2 - 5
+    let left = rect.X().min(rect.X() + rect.Width()).max(0.0);
+    let right = (rect.X().max(rect.X() + rect.Width())).min(window.InnerWidth() as f64);
+    let top = rect.Y().min(rect.Y() + rect.Height()).max(0.0);
+    let bottom = (rect.Y().max(rect.Y() + rect.Height())).min(window.InnerHeight() as f64);
+    if left > right || top > bottom {
+        return None;
+    }
+    // Steps 6 - 8
+    Some((
+        ((left + right) / 2.0).floor() as i64,
+        ((top + bottom) / 2.0).floor() as i64,
+    ))
+}
+
+/// Scroll the viewport as `element.scrollIntoView({block: "end", inline: "nearest"})`
+/// would, and return the bounding client rect of the element then.
+///
+/// https://w3c.github.io/webdriver/#dfn-scrolls-into-view
+fn scroll_into_view(element: &Element) -> Rect<f64> {
+    let window = window_from_node(element);
+    let rect = element.GetBoundingClientRect();
+    let (x, y, width, height) = (rect.X(), rect.Y(), rect.Width(), rect.Height());
+    let viewport_width = window.InnerWidth() as f64;
+    let viewport_height = window.InnerHeight() as f64;
+
+    let dx = if x < 0.0 {
+        x
+    } else if x + width > viewport_width {
+        (x + width - viewport_width).min(x)
+    } else {
+        0.0
+    };
+    let dy = y + height - viewport_height;
+    window.scroll(
+        window.ScrollX() as f64 + dx,
+        window.ScrollY() as f64 + dy,
+        ScrollBehavior::Instant,
+    );
+
+    let rect = element.GetBoundingClientRect();
+    Rect::new(
+        Point2D::new(rect.X(), rect.Y()),
+        Size2D::new(rect.Width(), rect.Height()),
+    )
+}
+
+pub fn handle_get_element_in_view_center_point(
+    documents: &Documents,
+    pipeline: PipelineId,
+    element_id: String,
+    reply: IpcSender<Result<Option<(i64, i64)>, ()>>,
+) {
+    reply
+        .send(
+            match find_node_by_unique_id(documents, pipeline, element_id) {
+                Some(node) => match node.downcast::<Element>() {
+                    Some(element) => Ok(in_view_center_point(element)),
+                    None => Err(()),
+                },
+                None => Err(()),
+            },
+        )
+        .unwrap();
+}
+
+pub fn handle_scroll_into_view(
+    documents: &Documents,
+    pipeline: PipelineId,
+    element_id: String,
+    reply: IpcSender<Result<Rect<f64>, ()>>,
+) {
+    reply
+        .send(
+            match find_node_by_unique_id(documents, pipeline, element_id) {
+                Some(node) => match node.downcast::<Element>() {
+                    Some(element) => Ok(scroll_into_view(element)),
+                    None => Err(()),
+                },
+                None => Err(()),
+            },
+        )
+        .unwrap();
+}
+
+/// The point where the element is to be clicked, once scrolled into view.
+///
+/// https://w3c.github.io/webdriver/#element-click
+pub fn handle_element_click(
+    documents: &Documents,
+    pipeline: PipelineId,
+    element_id: String,
+    reply: IpcSender<Result<(i64, i64), WebDriverInteractionError>>,
+) {
+    reply
+        .send(
+            match find_node_by_unique_id(documents, pipeline, element_id) {
+                Some(node) => match node.downcast::<Element>() {
+                    Some(element) => element_click_point(element),
+                    None => Err(WebDriverInteractionError::StaleElement),
+                },
+                None => Err(WebDriverInteractionError::StaleElement),
+            },
+        )
+        .unwrap();
+}
+
+fn element_click_point(element: &Element) -> Result<(i64, i64), WebDriverInteractionError> {
+    // Step 4
+    if let Some(input) = element.downcast::<HTMLInputElement>() {
+        if input.input_type() == InputType::File {
+            return Err(WebDriverInteractionError::InvalidArgument);
+        }
+    }
+
+    // Step 5
+    scroll_into_view(element);
+
+    // Steps 6 - 7
+    let (x, y) = in_view_center_point(element).ok_or(WebDriverInteractionError::NotInteractable)?;
+
+    // Step 8
+    let document = document_from_node(element);
+    let elements = document.ElementsFromPoint(Finite::wrap(x as f64), Finite::wrap(y as f64));
+    match elements.first() {
+        Some(hit)
+            if element
+                .upcast::<Node>()
+                .is_inclusive_ancestor_of(hit.upcast()) =>
+        {
+            Ok((x, y))
+        },
+        _ => Err(WebDriverInteractionError::ClickIntercepted),
+    }
+}
+
+/// https://w3c.github.io/webdriver/#element-clear
+pub fn handle_element_clear(
+    documents: &Documents,
+    pipeline: PipelineId,
+    element_id: String,
+    reply: IpcSender<Result<(), WebDriverInteractionError>>,
+) {
+    reply
+        .send(
+            match find_node_by_unique_id(documents, pipeline, element_id) {
+                Some(node) => match node.downcast::<Element>() {
+                    Some(element) => clear_element(element),
+                    None => Err(WebDriverInteractionError::StaleElement),
+                },
+                None => Err(WebDriverInteractionError::StaleElement),
+            },
+        )
+        .unwrap();
+}
+
+fn clear_element(element: &Element) -> Result<(), WebDriverInteractionError> {
+    let input = element.downcast::<HTMLInputElement>();
+    let textarea = element.downcast::<HTMLTextAreaElement>();
+
+    // Step 4
+    // TODO: content editable elements, once they can be edited.
+    let editable = match input {
+        Some(input) => match input.input_type() {
+            InputType::Button |
+            InputType::Checkbox |
+            InputType::Hidden |
+            InputType::Image |
+            InputType::Radio |
+            InputType::Reset |
+            InputType::Submit => false,
+            _ => true,
+        },
+        None => textarea.is_some(),
+    };
+    let read_only = input.map_or(false, |input| input.ReadOnly()) ||
+        textarea.map_or(false, |textarea| textarea.ReadOnly());
+    if !editable || read_only || element.disabled_state() {
+        return Err(WebDriverInteractionError::InvalidElementState);
+    }
+
+    // Steps 5 - 7
+    scroll_into_view(element);
+    if in_view_center_point(element).is_none() {
+        return Err(WebDriverInteractionError::NotInteractable);
+    }
+
+    // Step 8
+    // https://w3c.github.io/webdriver/#dfn-clear-a-resettable-element
+    let html_element = element.downcast::<HTMLElement>().unwrap();
+    html_element.Focus();
+    if let Some(input) = input {
+        let _ = input.SetValue(DOMString::new());
+    }
+    if let Some(textarea) = textarea {
+        textarea.SetValue(DOMString::new());
+    }
+    let target = element.upcast::<EventTarget>();
+    target.fire_bubbling_event(atom!("input"));
+    target.fire_bubbling_event(atom!("change"));
+    html_element.Blur();
+    Ok(())
+}
+
 pub fn handle_get_text(
     documents: &Documents,
     pipeline: PipelineId,
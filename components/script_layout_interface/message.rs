@@ -15,7 +15,7 @@ use net_traits::image_cache::ImageCache;
 use profile_traits::mem::ReportsChan;
 use script_traits::Painter;
 use script_traits::{ConstellationControlMsg, LayoutControlMsg, LayoutMsg as ConstellationMsg};
-use script_traits::{ScrollState, UntrustedNodeAddress, WindowSizeData};
+use script_traits::{PrintSettings, ScrollState, UntrustedNodeAddress, WindowSizeData};
 use servo_arc::Arc as ServoArc;
 use servo_atoms::Atom;
 use servo_url::ServoUrl;
@@ -103,6 +103,9 @@ pub enum Msg {
 
     /// Request the current number of animations that are running.
     GetRunningAnimations(IpcSender<usize>),
+
+    /// Requests the document laid out on pages, as a PDF document.
+    Print(PrintSettings, IpcSender<Option<Vec<u8>>>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -115,6 +118,7 @@ pub enum NodesFromPointQueryType {
 pub enum QueryMsg {
     ContentBoxQuery(OpaqueNode),
     ContentBoxesQuery(OpaqueNode),
+    BoxSizesQuery(OpaqueNode),
     NodeGeometryQuery(OpaqueNode),
     NodeScrollGeometryQuery(OpaqueNode),
     OffsetParentQuery(OpaqueNode),
@@ -150,6 +154,7 @@ impl ReflowGoal {
                 QueryMsg::ElementInnerTextQuery(_) => true,
                 QueryMsg::ContentBoxQuery(_) |
                 QueryMsg::ContentBoxesQuery(_) |
+                QueryMsg::BoxSizesQuery(_) |
                 QueryMsg::NodeGeometryQuery(_) |
                 QueryMsg::NodeScrollGeometryQuery(_) |
                 QueryMsg::NodeScrollIdQuery(_) |
@@ -171,6 +176,7 @@ impl ReflowGoal {
                 QueryMsg::ElementInnerTextQuery(_) => true,
                 QueryMsg::ContentBoxQuery(_) |
                 QueryMsg::ContentBoxesQuery(_) |
+                QueryMsg::BoxSizesQuery(_) |
                 QueryMsg::NodeGeometryQuery(_) |
                 QueryMsg::NodeScrollGeometryQuery(_) |
                 QueryMsg::NodeScrollIdQuery(_) |
@@ -3,7 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use app_units::Au;
-use euclid::Rect;
+use euclid::{Rect, Size2D};
 use script_traits::UntrustedNodeAddress;
 use servo_arc::Arc;
 use style::properties::ComputedValues;
@@ -22,6 +22,8 @@ pub trait LayoutRPC {
     fn content_box(&self) -> ContentBoxResponse;
     /// Requests the dimensions of all the content boxes, as in the `getClientRects()` call.
     fn content_boxes(&self) -> ContentBoxesResponse;
+    /// Requests the sizes of the border and content boxes of this node, as in `ResizeObserver`.
+    fn box_sizes(&self) -> BoxSizesResponse;
     /// Requests the geometry of this node. Used by APIs such as `clientTop`.
     fn node_geometry(&self) -> NodeGeometryResponse;
     /// Requests the scroll geometry of this node. Used by APIs such as `scrollTop`.
@@ -45,6 +47,19 @@ pub struct ContentBoxResponse(pub Option<Rect<Au>>);
 
 pub struct ContentBoxesResponse(pub Vec<Rect<Au>>);
 
+pub struct BoxSizesResponse(pub Option<BoxSizes>);
+
+/// The sizes of the first box of a node.
+#[derive(Clone, Copy, Debug)]
+pub struct BoxSizes {
+    pub border_box: Size2D<Au>,
+    /// The content box, relative to the padding box.
+    pub content_box: Rect<Au>,
+    /// Whether the writing mode of the box is vertical, so that its inline
+    /// size is its height.
+    pub vertical_writing_mode: bool,
+}
+
 pub struct NodeGeometryResponse {
     pub client_rect: Rect<i32>,
 }
@@ -17,18 +17,24 @@ extern crate malloc_size_of_derive;
 extern crate serde;
 
 mod script_msg;
+pub mod transferable;
 pub mod webdriver_msg;
 
-use crate::webdriver_msg::{LoadStatus, WebDriverScriptCommand};
+use crate::transferable::StructuredSerializedData;
+use crate::webdriver_msg::{LoadStatus, WebDriverScriptCommand, WebDriverUserPromptAction};
+use crate::webdriver_msg::{WebDriverBrowsingContextEvent, WebDriverWindowState};
+use crate::webdriver_msg::{WebDriverUserPromptError, WebDriverUserPromptType};
 use bluetooth_traits::BluetoothRequest;
 use canvas_traits::webgl::WebGLPipeline;
+use cookie::Cookie;
 use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use devtools_traits::{DevtoolScriptControlMsg, ScriptToDevtoolsControlMsg, WorkerId};
-use embedder_traits::Cursor;
-use euclid::{Length, Point2D, Rect, TypedScale, TypedSize2D, Vector2D};
+use embedder_traits::{Cursor, DownloadId};
+use euclid::{Length, Point2D, Rect, TypedScale, TypedSideOffsets2D, TypedSize2D, Vector2D};
 use gfx_traits::Epoch;
 use http::HeaderMap;
 use hyper::Method;
+use hyper_serde::Serde;
 use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use ipc_channel::Error as IpcError;
 use keyboard_types::webdriver::Event as WebDriverInputEvent;
@@ -38,6 +44,7 @@ use media::WindowGLContext;
 use msg::constellation_msg::BackgroundHangMonitorRegister;
 use msg::constellation_msg::{BrowsingContextId, HistoryStateId, PipelineId};
 use msg::constellation_msg::{PipelineNamespaceId, TopLevelBrowsingContextId, TraversalDirection};
+use net_traits::csp::{CspList, Violation};
 use net_traits::image::base::Image;
 use net_traits::image_cache::ImageCache;
 use net_traits::request::Referrer;
@@ -116,6 +123,8 @@ pub enum LayoutControlMsg {
     GetWebFontLoadState(IpcSender<bool>),
     /// Send the paint time for a specific epoch to the layout thread.
     PaintMetric(Epoch, u64),
+    /// Requests the document laid out on pages, as a PDF document.
+    Print(PrintSettings, IpcSender<Option<Vec<u8>>>),
 }
 
 /// can be passed to `LoadUrl` to load a page with GET/POST
@@ -146,6 +155,9 @@ pub struct LoadData {
     pub referrer: Option<Referrer>,
     /// The referrer policy.
     pub referrer_policy: Option<ReferrerPolicy>,
+    /// The policies of the document which initiated the load, if any,
+    /// which the navigation request is checked against.
+    pub csp_list: Option<CspList>,
 }
 
 /// The result of evaluating a javascript scheme url.
@@ -175,6 +187,7 @@ impl LoadData {
             js_eval_result: None,
             referrer: referrer,
             referrer_policy: referrer_policy,
+            csp_list: None,
         }
     }
 }
@@ -259,6 +272,9 @@ pub enum ConstellationControlMsg {
     /// Sends the final response to script thread for fetching after all redirections
     /// have been resolved
     NavigationResponse(PipelineId, FetchResponseMsg),
+    /// Report violations of the policies of a document, found while navigating
+    /// one of its nested browsing contexts.
+    ReportCspViolations(PipelineId, Vec<Violation>),
     /// Gives a channel and ID to a layout thread, as well as the ID of that layout's parent
     AttachLayout(NewLayoutInfo),
     /// Window resized.  Sends a DOM event eventually, but first we combine events.
@@ -302,7 +318,7 @@ pub enum ConstellationControlMsg {
         /// The expected origin of the target.
         target_origin: Option<ImmutableOrigin>,
         /// The data to be posted.
-        data: Vec<u8>,
+        data: StructuredSerializedData,
     },
     /// Updates the current pipeline ID of a given iframe.
     /// First PipelineId is for the parent, second is the new PipelineId for the frame.
@@ -364,6 +380,7 @@ impl fmt::Debug for ConstellationControlMsg {
         let variant = match *self {
             StopDelayingLoadEventsMode(..) => "StopDelayingLoadsEventMode",
             NavigationResponse(..) => "NavigationResponse",
+            ReportCspViolations(..) => "ReportCspViolations",
             AttachLayout(..) => "AttachLayout",
             Resize(..) => "Resize",
             ResizeInactive(..) => "ResizeInactive",
@@ -743,6 +760,37 @@ pub struct WindowSizeData {
     pub device_pixel_ratio: TypedScale<f32, CSSPixel, DevicePixel>,
 }
 
+/// How to print a document to PDF, as asked by WebDriver or on the command line.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PrintSettings {
+    /// The size of the pages.
+    pub page_size: TypedSize2D<f32, CSSPixel>,
+    /// The margins of the pages, or `None` to use those of the `@page` rules of the document.
+    pub margins: Option<TypedSideOffsets2D<f32, CSSPixel>>,
+    /// How much to scale the document by on the pages.
+    pub scale: f32,
+    /// Whether to scale the document down further when it is wider than the pages.
+    pub shrink_to_fit: bool,
+    /// Whether to print background colors and images.
+    pub background: bool,
+    /// The pages to print as inclusive ranges counting from 1, or all of them if empty.
+    pub page_ranges: Vec<(usize, usize)>,
+}
+
+impl Default for PrintSettings {
+    /// The defaults of <https://w3c.github.io/webdriver/#print-page>, on US Letter pages.
+    fn default() -> PrintSettings {
+        PrintSettings {
+            page_size: TypedSize2D::new(816., 1056.),
+            margins: None,
+            scale: 1.,
+            shrink_to_fit: true,
+            background: false,
+            page_ranges: vec![],
+        }
+    }
+}
+
 /// The type of window size change.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, MallocSizeOf, PartialEq, Serialize)]
 pub enum WindowSizeType {
@@ -774,6 +822,49 @@ pub enum WebDriverCommandMsg {
     ),
     /// Take a screenshot of the window.
     TakeScreenshot(TopLevelBrowsingContextId, IpcSender<Option<Image>>),
+    /// Act as if the given mouse button was pressed, released or clicked at the given point
+    /// of the viewport, in CSS pixels.
+    MouseButtonAction(MouseEventType, MouseButton, f32, f32),
+    /// Act as if the mouse was moved to the given point of the viewport, in CSS pixels.
+    MouseMoveAction(f32, f32),
+    /// Act as if the mouse wheel was turned by the given delta at the given point of the
+    /// viewport, in CSS pixels.
+    WheelScrollAction(WheelDelta, f32, f32),
+    /// Get the type and message of the user prompt currently open in the top-level
+    /// browsing context with the given ID, if any.
+    GetUserPrompt(
+        TopLevelBrowsingContextId,
+        IpcSender<Option<(WebDriverUserPromptType, Option<String>)>>,
+    ),
+    /// Accept or dismiss the user prompt currently open in the top-level browsing context
+    /// with the given ID.
+    HandleUserPrompt(
+        TopLevelBrowsingContextId,
+        WebDriverUserPromptAction,
+        IpcSender<Result<(), WebDriverUserPromptError>>,
+    ),
+    /// Set the text of the `window.prompt()` dialog currently open in the top-level
+    /// browsing context with the given ID.
+    SendUserPromptText(
+        TopLevelBrowsingContextId,
+        String,
+        IpcSender<Result<(), WebDriverUserPromptError>>,
+    ),
+    /// Get the IDs of all the open top-level browsing contexts.
+    GetTopLevelBrowsingContexts(IpcSender<Vec<TopLevelBrowsingContextId>>),
+    /// Open a new top-level browsing context on about:blank, replying with its ID.
+    NewWindow(IpcSender<TopLevelBrowsingContextId>),
+    /// Close the top-level browsing context with the given ID.
+    CloseWindow(TopLevelBrowsingContextId, IpcSender<()>),
+    /// Maximize, minimize, make fullscreen or restore the window, replying with the new
+    /// window size once it has been resized.
+    SetWindowState(
+        TopLevelBrowsingContextId,
+        WebDriverWindowState,
+        IpcSender<WindowSizeData>,
+    ),
+    /// Report what happens to browsing contexts from now on to the given channel.
+    SubscribeToBrowsingContextEvents(IpcSender<WebDriverBrowsingContextEvent>),
 }
 
 /// Messages to the constellation.
@@ -834,6 +925,24 @@ pub enum ConstellationMsg {
     DisableProfiler,
     /// Request to exit from fullscreen mode
     ExitFullScreen(TopLevelBrowsingContextId),
+    /// Retrieve the cookies of the given domain and its subdomains, or all cookies.
+    GetCookies(Option<String>, IpcSender<Vec<Serde<Cookie<'static>>>>),
+    /// Store a cookie, as if it had been set by a response from the given URL.
+    AddCookie(ServoUrl, Serde<Cookie<'static>>),
+    /// Remove the cookies of the given domain and its subdomains, or all cookies.
+    DeleteCookies(Option<String>),
+    /// Create a named ephemeral session, whose state is never written to disk.
+    NewEphemeralSession(String),
+    /// Create a new top level browsing context in the named ephemeral session.
+    NewBrowserInEphemeralSession(ServoUrl, TopLevelBrowsingContextId, String),
+    /// Close all browsers of the named ephemeral session, and discard all of its state.
+    DiscardEphemeralSession(String),
+    /// Stop fetching a download, keeping what was received so far.
+    PauseDownload(DownloadId),
+    /// Fetch the rest of a paused download.
+    ResumeDownload(DownloadId),
+    /// Stop a download, and remove what was received so far.
+    CancelDownload(DownloadId),
 }
 
 impl fmt::Debug for ConstellationMsg {
@@ -864,6 +973,15 @@ impl fmt::Debug for ConstellationMsg {
             EnableProfiler(..) => "EnableProfiler",
             DisableProfiler => "DisableProfiler",
             ExitFullScreen(..) => "ExitFullScreen",
+            GetCookies(..) => "GetCookies",
+            AddCookie(..) => "AddCookie",
+            DeleteCookies(..) => "DeleteCookies",
+            NewEphemeralSession(..) => "NewEphemeralSession",
+            NewBrowserInEphemeralSession(..) => "NewBrowserInEphemeralSession",
+            DiscardEphemeralSession(..) => "DiscardEphemeralSession",
+            PauseDownload(..) => "PauseDownload",
+            ResumeDownload(..) => "ResumeDownload",
+            CancelDownload(..) => "CancelDownload",
         };
         write!(formatter, "ConstellationMsg::{}", variant)
     }
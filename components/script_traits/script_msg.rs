@@ -2,6 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::transferable::{BroadcastMsg, MessagePortMsg};
+use crate::transferable::{PortMessageTask, StructuredSerializedData};
 use crate::AnimationState;
 use crate::AuxiliaryBrowsingContextLoadInfo;
 use crate::DocumentState;
@@ -18,8 +20,9 @@ use embedder_traits::EmbedderMsg;
 use euclid::{Size2D, TypedSize2D};
 use gfx_traits::Epoch;
 use ipc_channel::ipc::{IpcReceiver, IpcSender};
+use msg::constellation_msg::{BroadcastChannelRouterId, MessagePortId, MessagePortRouterId};
 use msg::constellation_msg::{BrowsingContextId, PipelineId, TopLevelBrowsingContextId};
-use msg::constellation_msg::{HistoryStateId, TraversalDirection};
+use msg::constellation_msg::{HistoryStateId, PipelineNamespaceId, TraversalDirection};
 use net_traits::request::RequestBuilder;
 use net_traits::storage_thread::StorageType;
 use net_traits::CoreResourceMsg;
@@ -155,7 +158,7 @@ pub enum ScriptMsg {
         /// The expected origin of the target.
         target_origin: Option<ImmutableOrigin>,
         /// The data to be posted.
-        data: Vec<u8>,
+        data: StructuredSerializedData,
     },
     /// Inform the constellation that a fragment was navigated to and whether or not it was a replacement navigation.
     NavigatedToFragment(ServoUrl, bool),
@@ -208,6 +211,38 @@ pub enum ScriptMsg {
     GetScreenSize(IpcSender<(DeviceIntSize)>),
     /// Get the available screen size (pixel)
     GetScreenAvailSize(IpcSender<(DeviceIntSize)>),
+    /// Request a new pipeline namespace, for a worker thread.
+    NewPipelineNamespace(IpcSender<PipelineNamespaceId>),
+    /// A global has started managing message ports.
+    NewMessagePortRouter(MessagePortRouterId, IpcSender<MessagePortMsg>),
+    /// A global has stopped managing message ports.
+    RemoveMessagePortRouter(MessagePortRouterId),
+    /// A new message port was created by a global.
+    NewMessagePort(MessagePortRouterId, MessagePortId),
+    /// Two message ports were entangled.
+    EntanglePorts(MessagePortId, MessagePortId),
+    /// A message port was shipped to another global as part of a transfer.
+    MessagePortShipped(MessagePortId),
+    /// A global has received a transferred port, and now manages it.
+    CompleteMessagePortTransfer(MessagePortRouterId, MessagePortId),
+    /// A message port was closed, or its global went away.
+    RemoveMessagePort(MessagePortId),
+    /// Route a message to the global currently managing a port.
+    RerouteMessagePort(MessagePortId, PortMessageTask),
+    /// A global has started managing broadcast channels.
+    NewBroadcastChannelRouter(
+        BroadcastChannelRouterId,
+        IpcSender<BroadcastMsg>,
+        ImmutableOrigin,
+    ),
+    /// A global has stopped managing broadcast channels.
+    RemoveBroadcastChannelRouter(BroadcastChannelRouterId, ImmutableOrigin),
+    /// A global has a BroadcastChannel with a name it didn't have before.
+    NewBroadcastChannelNameInRouter(BroadcastChannelRouterId, String, ImmutableOrigin),
+    /// A global no longer has any BroadcastChannel with a given name.
+    RemoveBroadcastChannelNameInRouter(BroadcastChannelRouterId, String, ImmutableOrigin),
+    /// Broadcast a message to the same-origin channels of other globals.
+    ScheduleBroadcast(BroadcastChannelRouterId, BroadcastMsg),
 }
 
 impl fmt::Debug for ScriptMsg {
@@ -250,6 +285,20 @@ impl fmt::Debug for ScriptMsg {
             GetClientWindow(..) => "GetClientWindow",
             GetScreenSize(..) => "GetScreenSize",
             GetScreenAvailSize(..) => "GetScreenAvailSize",
+            NewPipelineNamespace(..) => "NewPipelineNamespace",
+            NewMessagePortRouter(..) => "NewMessagePortRouter",
+            RemoveMessagePortRouter(..) => "RemoveMessagePortRouter",
+            NewMessagePort(..) => "NewMessagePort",
+            EntanglePorts(..) => "EntanglePorts",
+            MessagePortShipped(..) => "MessagePortShipped",
+            CompleteMessagePortTransfer(..) => "CompleteMessagePortTransfer",
+            RemoveMessagePort(..) => "RemoveMessagePort",
+            RerouteMessagePort(..) => "RerouteMessagePort",
+            NewBroadcastChannelRouter(..) => "NewBroadcastChannelRouter",
+            RemoveBroadcastChannelRouter(..) => "RemoveBroadcastChannelRouter",
+            NewBroadcastChannelNameInRouter(..) => "NewBroadcastChannelNameInRouter",
+            RemoveBroadcastChannelNameInRouter(..) => "RemoveBroadcastChannelNameInRouter",
+            ScheduleBroadcast(..) => "ScheduleBroadcast",
         };
         write!(formatter, "ScriptMsg::{}", variant)
     }
@@ -289,6 +338,11 @@ pub enum ServiceWorkerMsg {
     RegisterServiceWorker(ScopeThings, ServoUrl),
     /// Timeout message sent by active service workers
     Timeout(ServoUrl),
+    /// Sent by a service worker once its activate event was handled, after which
+    /// it controls the clients of its scope
+    Activated(ServoUrl),
+    /// Sent by a service worker whose install event failed, to drop its registration
+    InstallFailed(ServoUrl),
     /// Message sent by constellation to forward to a running service worker
     ForwardDOMMessage(DOMMessage, ServoUrl),
     /// Exit the service worker manager
@@ -0,0 +1,175 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! This module contains the data that travels along with structured clones
+//! of transferable objects, and the messages used to route it between globals.
+//!
+//! <https://html.spec.whatwg.org/multipage/#transferable-objects>
+
+use msg::constellation_msg::MessagePortId;
+use servo_url::ImmutableOrigin;
+use std::collections::{HashMap, VecDeque};
+
+/// The result of a structured serialization, together with the
+/// message ports that were transferred along with it.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct StructuredSerializedData {
+    /// The bytes written by the structured clone algorithm.
+    pub serialized: Vec<u8>,
+    /// The ports transferred with this data, if any.
+    pub ports: Option<HashMap<MessagePortId, MessagePortImpl>>,
+}
+
+impl StructuredSerializedData {
+    /// Wrap data that was serialized without a transfer list.
+    pub fn new(serialized: Vec<u8>) -> StructuredSerializedData {
+        StructuredSerializedData {
+            serialized,
+            ports: None,
+        }
+    }
+}
+
+/// A message sent through a MessagePort.
+///
+/// <https://html.spec.whatwg.org/multipage/#port-message-queue>
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct PortMessageTask {
+    /// The origin of the global that posted the message.
+    pub origin: ImmutableOrigin,
+    /// The serialized message, along with any ports transferred with it.
+    pub data: StructuredSerializedData,
+}
+
+/// The part of a MessagePort that survives being transferred to another global.
+#[derive(Clone, Debug, Deserialize, MallocSizeOf, Serialize)]
+pub struct MessagePortImpl {
+    /// The id of this port.
+    message_port_id: MessagePortId,
+    /// The id of the port this one is entangled with, if any.
+    entangled_port: Option<MessagePortId>,
+    /// <https://html.spec.whatwg.org/multipage/#port-message-queue>
+    message_buffer: VecDeque<PortMessageTask>,
+    /// Whether the port message queue has been enabled.
+    enabled: bool,
+    /// Whether the port is currently being transferred to another global,
+    /// in which case the messages it receives are buffered.
+    transfer_pending: bool,
+    /// <https://html.spec.whatwg.org/multipage/#detached>
+    detached: bool,
+}
+
+impl MessagePortImpl {
+    /// Create a new, disabled, port.
+    pub fn new(message_port_id: MessagePortId) -> MessagePortImpl {
+        MessagePortImpl {
+            message_port_id,
+            entangled_port: None,
+            message_buffer: VecDeque::new(),
+            enabled: false,
+            transfer_pending: false,
+            detached: false,
+        }
+    }
+
+    /// Get the id of this port.
+    pub fn message_port_id(&self) -> &MessagePortId {
+        &self.message_port_id
+    }
+
+    /// Get the id of the port this one is entangled with, if any.
+    pub fn entangled_port_id(&self) -> Option<MessagePortId> {
+        self.entangled_port
+    }
+
+    /// Entangle this port with another one.
+    pub fn entangle(&mut self, other_id: MessagePortId) {
+        self.entangled_port = Some(other_id);
+    }
+
+    /// Disentangle this port from its counterpart.
+    pub fn disentangle(&mut self) {
+        self.entangled_port = None;
+    }
+
+    /// Whether the port message queue has been enabled.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Mark this port as being shipped to another global, which detaches it
+    /// from the current one. The port message queue travels with it.
+    pub fn set_transfer_pending(&mut self) {
+        self.transfer_pending = true;
+    }
+
+    /// Complete a transfer, prepending the messages buffered elsewhere
+    /// while the port was in transit to the ones received locally.
+    pub fn complete_transfer(&mut self, mut tasks: VecDeque<PortMessageTask>) {
+        self.transfer_pending = false;
+        tasks.append(&mut self.message_buffer);
+        self.message_buffer = tasks;
+    }
+
+    /// Handle an incoming message, returning it if it should be dispatched now.
+    pub fn handle_incoming(&mut self, task: PortMessageTask) -> Option<PortMessageTask> {
+        if self.detached {
+            return None;
+        }
+        if self.enabled && !self.transfer_pending {
+            return Some(task);
+        }
+        self.message_buffer.push_back(task);
+        None
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-messageport-start>
+    ///
+    /// Returns the messages that were buffered while the queue was disabled,
+    /// unless the port is still in transit.
+    pub fn start(&mut self) -> Option<VecDeque<PortMessageTask>> {
+        self.enabled = true;
+        if self.transfer_pending {
+            return None;
+        }
+        Some(self.message_buffer.drain(..).collect())
+    }
+
+    /// Take the buffered messages of an enabled port whose transfer just completed.
+    pub fn take_pending_messages(&mut self) -> Option<VecDeque<PortMessageTask>> {
+        if !self.enabled || self.transfer_pending {
+            return None;
+        }
+        Some(self.message_buffer.drain(..).collect())
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/#dom-messageport-close>
+    pub fn close(&mut self) {
+        self.detached = true;
+        self.message_buffer.clear();
+    }
+}
+
+/// Messages sent by the constellation to the router of a global managing message ports.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum MessagePortMsg {
+    /// Complete the transfer of a port to this global, handing over the
+    /// messages that were buffered while it was in transit.
+    CompleteTransfer(MessagePortId, VecDeque<PortMessageTask>),
+    /// A message for a port managed by this global.
+    NewTask(MessagePortId, PortMessageTask),
+}
+
+/// A message posted on a BroadcastChannel.
+///
+/// <https://html.spec.whatwg.org/multipage/#dom-broadcastchannel-postmessage>
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BroadcastMsg {
+    /// The origin of the global that posted the message.
+    pub origin: ImmutableOrigin,
+    /// The name of the channel the message was posted on.
+    pub channel_name: String,
+    /// The serialized message.
+    pub data: StructuredSerializedData,
+}
@@ -4,6 +4,7 @@
 
 #![allow(missing_docs)]
 
+use crate::PrintSettings;
 use cookie::Cookie;
 use euclid::Rect;
 use hyper_serde::Serde;
@@ -35,6 +36,13 @@ pub enum WebDriverScriptCommand {
     GetElementAttribute(String, String, IpcSender<Result<Option<String>, ()>>),
     GetElementCSS(String, String, IpcSender<Result<String, ()>>),
     GetElementRect(String, IpcSender<Result<Rect<f64>, ()>>),
+    GetElementInViewCenterPoint(String, IpcSender<Result<Option<(i64, i64)>, ()>>),
+    ScrollIntoView(String, IpcSender<Result<Rect<f64>, ()>>),
+    ElementClick(
+        String,
+        IpcSender<Result<(i64, i64), WebDriverInteractionError>>,
+    ),
+    ElementClear(String, IpcSender<Result<(), WebDriverInteractionError>>),
     GetElementTagName(String, IpcSender<Result<String, ()>>),
     GetElementText(String, IpcSender<Result<String, ()>>),
     GetBrowsingContextId(WebDriverFrameId, IpcSender<Result<BrowsingContextId, ()>>),
@@ -43,6 +51,7 @@ pub enum WebDriverScriptCommand {
     IsEnabled(String, IpcSender<Result<bool, ()>>),
     IsSelected(String, IpcSender<Result<bool, ()>>),
     GetTitle(IpcSender<String>),
+    Print(PrintSettings, IpcSender<Result<Vec<u8>, ()>>),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -51,6 +60,46 @@ pub enum WebDriverCookieError {
     UnableToSetCookie,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WebDriverInteractionError {
+    StaleElement,
+    InvalidArgument,
+    InvalidElementState,
+    NotInteractable,
+    ClickIntercepted,
+}
+
+/// https://w3c.github.io/webdriver/#dfn-user-prompt
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum WebDriverUserPromptType {
+    Alert,
+    Confirm,
+    Prompt,
+    BeforeUnload,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum WebDriverUserPromptAction {
+    Accept,
+    Dismiss,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WebDriverUserPromptError {
+    NoSuchAlert,
+    NotInteractable,
+    UnsupportedOperation,
+}
+
+/// https://w3c.github.io/webdriver/#dfn-window-state
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum WebDriverWindowState {
+    Normal,
+    Maximized,
+    Minimized,
+    Fullscreen,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum WebDriverJSValue {
     Undefined,
@@ -71,6 +120,18 @@ pub enum WebDriverJSError {
 
 pub type WebDriverJSResult = Result<WebDriverJSValue, WebDriverJSError>;
 
+/// What happens to browsing contexts, as reported by the constellation to WebDriver BiDi.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WebDriverBrowsingContextEvent {
+    /// A browsing context was created, nested in the given parent if any, with its
+    /// initial URL.
+    ContextCreated(BrowsingContextId, Option<BrowsingContextId>, ServoUrl),
+    /// A browsing context started navigating to the given URL.
+    NavigationStarted(BrowsingContextId, ServoUrl),
+    /// The document of a browsing context finished loading.
+    Load(BrowsingContextId, ServoUrl),
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum WebDriverFrameId {
     Short(u16),
@@ -62,6 +62,25 @@ fn webdriver(port: u16, constellation: Sender<ConstellationMsg>) {
 #[cfg(not(feature = "webdriver"))]
 fn webdriver(_port: u16, _constellation: Sender<ConstellationMsg>) {}
 
+#[cfg(feature = "webdriver")]
+fn webdriver_bidi(
+    port: u16,
+    constellation: Sender<ConstellationMsg>,
+    devtools_receiver: Receiver<DevtoolsControlMsg>,
+    devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+) {
+    webdriver_server::start_bidi_server(port, constellation, devtools_receiver, devtools_chan);
+}
+
+#[cfg(not(feature = "webdriver"))]
+fn webdriver_bidi(
+    _port: u16,
+    _constellation: Sender<ConstellationMsg>,
+    _devtools_receiver: Receiver<DevtoolsControlMsg>,
+    _devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+) {
+}
+
 use bluetooth::BluetoothThreadFactory;
 use bluetooth_traits::BluetoothRequest;
 use canvas::gl_context::{CloneableDispatcher, GLContextFactory};
@@ -81,7 +100,8 @@ use compositing::{CompositingReason, IOCompositor, ShutdownState};
 use constellation::content_process_sandbox_profile;
 use constellation::{Constellation, InitialConstellationState, UnprivilegedPipelineContent};
 use constellation::{FromCompositorLogger, FromScriptLogger};
-use crossbeam_channel::{unbounded, Sender};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use devtools_traits::DevtoolsControlMsg;
 use embedder_traits::{EmbedderMsg, EmbedderProxy, EmbedderReceiver, EventLoopWaker};
 use env_logger::Builder as EnvLoggerBuilder;
 use euclid::TypedSize2D;
@@ -284,6 +304,16 @@ where
         let debugger_chan = opts.debugger_port.map(|port| debugger::start_server(port));
         let devtools_chan = opts.devtools_port.map(|port| devtools::start_server(port));
 
+        // WebDriver BiDi reports the console messages and network events that are sent to
+        // devtools, so it sees them first and passes them on.
+        let (devtools_chan, webdriver_bidi_devtools) = match opts.webdriver_bidi_port {
+            Some(_) if cfg!(feature = "webdriver") => {
+                let (sender, receiver) = unbounded();
+                (Some(sender), Some((receiver, devtools_chan)))
+            },
+            _ => (devtools_chan, None),
+        };
+
         let coordinates = window.get_coordinates();
 
         let (mut webrender, webrender_api_sender) = {
@@ -401,6 +431,16 @@ where
             if let Some(port) = opts.webdriver_port {
                 webdriver(port, constellation_chan.clone());
             }
+            if let (Some(port), Some((devtools_receiver, devtools_chan))) =
+                (opts.webdriver_bidi_port, webdriver_bidi_devtools)
+            {
+                webdriver_bidi(
+                    port,
+                    constellation_chan.clone(),
+                    devtools_receiver,
+                    devtools_chan,
+                );
+            }
         }
 
         // The compositor coordinates with the client window to create the final
@@ -551,6 +591,78 @@ where
                 self.compositor.capture_webrender();
             },
 
+            WindowEvent::GetCookies(domain, sender) => {
+                let msg = ConstellationMsg::GetCookies(domain, sender);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending GetCookies to constellation failed ({:?}).", e);
+                }
+            },
+
+            WindowEvent::AddCookie(url, cookie) => {
+                let msg = ConstellationMsg::AddCookie(url, cookie);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending AddCookie to constellation failed ({:?}).", e);
+                }
+            },
+
+            WindowEvent::DeleteCookies(domain) => {
+                let msg = ConstellationMsg::DeleteCookies(domain);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending DeleteCookies to constellation failed ({:?}).", e);
+                }
+            },
+
+            WindowEvent::NewEphemeralSession(name) => {
+                let msg = ConstellationMsg::NewEphemeralSession(name);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending NewEphemeralSession message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
+
+            WindowEvent::NewBrowserInEphemeralSession(url, browser_id, name) => {
+                let msg = ConstellationMsg::NewBrowserInEphemeralSession(url, browser_id, name);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending NewBrowserInEphemeralSession message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
+
+            WindowEvent::DiscardEphemeralSession(name) => {
+                let msg = ConstellationMsg::DiscardEphemeralSession(name);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!(
+                        "Sending DiscardEphemeralSession message to constellation failed ({:?}).",
+                        e
+                    );
+                }
+            },
+
+            WindowEvent::PauseDownload(id) => {
+                let msg = ConstellationMsg::PauseDownload(id);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending PauseDownload to constellation failed ({:?}).", e);
+                }
+            },
+
+            WindowEvent::ResumeDownload(id) => {
+                let msg = ConstellationMsg::ResumeDownload(id);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending ResumeDownload to constellation failed ({:?}).", e);
+                }
+            },
+
+            WindowEvent::CancelDownload(id) => {
+                let msg = ConstellationMsg::CancelDownload(id);
+                if let Err(e) = self.constellation_chan.send(msg) {
+                    warn!("Sending CancelDownload to constellation failed ({:?}).", e);
+                }
+            },
+
             WindowEvent::NewBrowser(url, browser_id) => {
                 let msg = ConstellationMsg::NewBrowser(url, browser_id);
                 if let Err(e) = self.constellation_chan.send(msg) {
@@ -696,7 +808,7 @@ fn create_constellation(
     time_profiler_chan: time::ProfilerChan,
     mem_profiler_chan: mem::ProfilerChan,
     debugger_chan: Option<debugger::Sender>,
-    devtools_chan: Option<Sender<devtools_traits::DevtoolsControlMsg>>,
+    devtools_chan: Option<Sender<DevtoolsControlMsg>>,
     webrender: &mut webrender::Renderer,
     webrender_document: webrender_api::DocumentId,
     webrender_api_sender: webrender_api::RenderApiSender,
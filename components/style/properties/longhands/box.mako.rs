@@ -449,7 +449,6 @@ ${helpers.predefined_type(
     "BreakBetween",
     "computed::BreakBetween::Auto",
     needs_context=False,
-    products="gecko",
     spec="https://drafts.csswg.org/css-break/#propdef-break-after",
     animation_value_type="discrete",
 )}
@@ -459,7 +458,6 @@ ${helpers.predefined_type(
     "BreakBetween",
     "computed::BreakBetween::Auto",
     needs_context=False,
-    products="gecko",
     spec="https://drafts.csswg.org/css-break/#propdef-break-before",
     animation_value_type="discrete",
 )}
@@ -469,7 +467,6 @@ ${helpers.predefined_type(
     "BreakWithin",
     "computed::BreakWithin::Auto",
     needs_context=False,
-    products="gecko",
     alias="page-break-inside",
     spec="https://drafts.csswg.org/css-break/#propdef-break-inside",
     animation_value_type="discrete",
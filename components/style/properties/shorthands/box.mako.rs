@@ -316,7 +316,6 @@ ${helpers.two_properties_shorthand(
 
 <%helpers:shorthand
     name="page-break-before"
-    products="gecko"
     flags="SHORTHAND_IN_GETCS IS_LEGACY_SHORTHAND"
     sub_properties="break-before"
     spec="https://drafts.csswg.org/css2/page.html#propdef-page-break-before"
@@ -340,7 +339,6 @@ ${helpers.two_properties_shorthand(
 
 <%helpers:shorthand
     name="page-break-after"
-    products="gecko"
     flags="SHORTHAND_IN_GETCS IS_LEGACY_SHORTHAND"
     sub_properties="break-after"
     spec="https://drafts.csswg.org/css2/page.html#propdef-page-break-after"
@@ -29,8 +29,8 @@ use crate::stylesheets::viewport_rule::{self, MaybeNew, ViewportRule};
 use crate::stylesheets::StyleRule;
 use crate::stylesheets::StylesheetInDocument;
 #[cfg(feature = "gecko")]
-use crate::stylesheets::{CounterStyleRule, FontFaceRule, FontFeatureValuesRule, PageRule};
-use crate::stylesheets::{CssRule, Origin, OriginSet, PerOrigin, PerOriginIter};
+use crate::stylesheets::{CounterStyleRule, FontFaceRule, FontFeatureValuesRule};
+use crate::stylesheets::{CssRule, Origin, OriginSet, PageRule, PerOrigin, PerOriginIter};
 use crate::thread_state::{self, ThreadState};
 use crate::{Atom, LocalName, Namespace, WeakAtom};
 use fallible::FallibleVec;
@@ -1403,7 +1403,7 @@ pub struct ExtraStyleData {
     pub counter_styles: PrecomputedHashMap<Atom, Arc<Locked<CounterStyleRule>>>,
 
     /// A map of effective page rules.
-    #[cfg(feature = "gecko")]
+    #[cfg_attr(feature = "servo", ignore_malloc_size_of = "Arc")]
     pub pages: Vec<Arc<Locked<PageRule>>>,
 }
 
@@ -1433,22 +1433,22 @@ impl ExtraStyleData {
         let name = rule.read_with(guard).name().0.clone();
         self.counter_styles.insert(name, rule.clone());
     }
+}
 
+impl ExtraStyleData {
     /// Add the given @page rule.
     fn add_page(&mut self, rule: &Arc<Locked<PageRule>>) {
         self.pages.push(rule.clone());
     }
-}
 
-impl ExtraStyleData {
     fn clear(&mut self) {
         #[cfg(feature = "gecko")]
         {
             self.font_faces.clear();
             self.font_feature_values.clear();
             self.counter_styles.clear();
-            self.pages.clear();
         }
+        self.pages.clear();
     }
 }
 
@@ -2107,7 +2107,6 @@ impl CascadeData {
                 CssRule::CounterStyle(ref rule) => {
                     self.extra_data.add_counter_style(guard, rule);
                 },
-                #[cfg(feature = "gecko")]
                 CssRule::Page(ref rule) => {
                     self.extra_data.add_page(rule);
                 },
@@ -0,0 +1,648 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The input sources of a session and the dispatching of their actions, as in
+//! https://w3c.github.io/webdriver/#actions.
+
+use crate::Handler;
+use ipc_channel::ipc;
+use keyboard_types::webdriver::{Event as WebDriverInputEvent, KeyInputState};
+use keyboard_types::KeyboardEvent;
+use script_traits::webdriver_msg::WebDriverScriptCommand;
+use script_traits::{ConstellationMsg, MouseButton, MouseEventType, WebDriverCommandMsg};
+use script_traits::{WheelDelta, WheelMode};
+use std::collections::HashMap;
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use webdriver::actions::{ActionSequence, ActionsType, GeneralAction, KeyAction, KeyActionItem};
+use webdriver::actions::{NullActionItem, PointerAction, PointerActionItem, PointerMoveAction};
+use webdriver::actions::{PointerOrigin, PointerType};
+use webdriver::common::WebElement;
+use webdriver::error::{ErrorStatus, WebDriverError, WebDriverResult};
+
+/// The interval between the steps of the pointer moves and scrolls lasting some time, in
+/// milliseconds.
+const INTERPOLATION_INTERVAL: u64 = 17;
+
+/// https://w3c.github.io/webdriver/#dfn-input-source-state
+pub(crate) enum InputSourceState {
+    Null,
+    Key(KeyInputState),
+    Pointer(PointerInputState),
+    Wheel,
+}
+
+/// https://w3c.github.io/webdriver/#dfn-pointer-input-state, for mice, the only pointers
+/// supported.
+pub(crate) struct PointerInputState {
+    /// The pressed buttons, with where they were pressed.
+    pressed: HashMap<u64, (f64, f64)>,
+    x: f64,
+    y: f64,
+}
+
+/// An action undoing a past one, as found in the input cancel list.
+pub(crate) enum CancelAction {
+    KeyUp(char),
+    PointerUp(u64),
+}
+
+/// The actions of a wheel input source, which the webdriver crate does not know of.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum WheelActionSequence {
+    #[serde(rename = "wheel")]
+    Wheel {
+        id: String,
+        actions: Vec<WheelActionItem>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum WheelActionItem {
+    #[serde(rename = "pause")]
+    Pause { duration: Option<u64> },
+    #[serde(rename = "scroll")]
+    Scroll(WheelScrollAction),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WheelScrollAction {
+    duration: Option<u64>,
+    origin: Option<PointerOrigin>,
+    x: i64,
+    y: i64,
+    delta_x: i64,
+    delta_y: i64,
+}
+
+/// The actions of an input source, of any type.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ServoActionSequence {
+    Wheel(WheelActionSequence),
+    Standard(ActionSequence),
+}
+
+/// The actions of an input source, as given to Perform Actions or to its counterpart
+/// supporting wheels.
+#[derive(Clone, Copy)]
+pub(crate) enum InputSequence<'a> {
+    Standard(&'a ActionSequence),
+    Wheel(&'a WheelActionSequence),
+}
+
+impl<'a> From<&'a ServoActionSequence> for InputSequence<'a> {
+    fn from(sequence: &'a ServoActionSequence) -> InputSequence<'a> {
+        match *sequence {
+            ServoActionSequence::Wheel(ref sequence) => InputSequence::Wheel(sequence),
+            ServoActionSequence::Standard(ref sequence) => InputSequence::Standard(sequence),
+        }
+    }
+}
+
+/// An input source, as found in an action sequence.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Null,
+    Key,
+    Pointer(&'a PointerType),
+    Wheel,
+}
+
+/// An action, in the tick it is dispatched in.
+enum Action<'a> {
+    Pause(Option<u64>),
+    KeyDown(char),
+    KeyUp(char),
+    Pointer(&'a PointerAction),
+    Wheel(&'a WheelScrollAction),
+}
+
+impl<'a> Action<'a> {
+    /// The time the tick of this action has to last at least.
+    ///
+    /// https://w3c.github.io/webdriver/#dfn-computing-the-tick-duration
+    fn duration(&self) -> u64 {
+        let duration = match *self {
+            Action::Pause(duration) => duration,
+            Action::Pointer(&PointerAction::Move(ref action)) => action.duration,
+            Action::Wheel(action) => action.duration,
+            _ => None,
+        };
+        duration.unwrap_or(0)
+    }
+}
+
+impl<'a> From<&'a GeneralAction> for Action<'a> {
+    fn from(action: &'a GeneralAction) -> Action<'a> {
+        match *action {
+            GeneralAction::Pause(ref action) => Action::Pause(action.duration),
+        }
+    }
+}
+
+/// The key of a key action.
+///
+/// https://w3c.github.io/webdriver/#dfn-process-a-key-action
+fn key_action_value(value: &str) -> WebDriverResult<char> {
+    value.chars().next().ok_or_else(|| {
+        WebDriverError::new(
+            ErrorStatus::InvalidArgument,
+            "The value of a key action must be a key",
+        )
+    })
+}
+
+/// The id, the kind and the actions of the input source of the given sequence.
+///
+/// https://w3c.github.io/webdriver/#dfn-process-an-input-source-action-sequence
+fn process_action_sequence(
+    sequence: InputSequence,
+) -> WebDriverResult<(&str, Source, Vec<Action>)> {
+    let sequence = match sequence {
+        InputSequence::Wheel(&WheelActionSequence::Wheel {
+            ref id,
+            ref actions,
+        }) => {
+            let actions: Vec<Action> = actions
+                .iter()
+                .map(|action| match *action {
+                    WheelActionItem::Pause { duration } => Action::Pause(duration),
+                    WheelActionItem::Scroll(ref action) => Action::Wheel(action),
+                })
+                .collect();
+            return Ok((id, Source::Wheel, actions));
+        },
+        InputSequence::Standard(sequence) => sequence,
+    };
+    match sequence.actions {
+        ActionsType::Null { ref actions } => {
+            let actions: Vec<Action> = actions
+                .iter()
+                .map(|action| match *action {
+                    NullActionItem::General(ref action) => action.into(),
+                })
+                .collect();
+            Ok((&sequence.id, Source::Null, actions))
+        },
+        ActionsType::Key { ref actions } => {
+            let actions = actions
+                .iter()
+                .map(|action| match *action {
+                    KeyActionItem::General(ref action) => Ok(action.into()),
+                    KeyActionItem::Key(KeyAction::Down(ref action)) => {
+                        Ok(Action::KeyDown(key_action_value(&action.value)?))
+                    },
+                    KeyActionItem::Key(KeyAction::Up(ref action)) => {
+                        Ok(Action::KeyUp(key_action_value(&action.value)?))
+                    },
+                })
+                .collect::<WebDriverResult<Vec<Action>>>()?;
+            Ok((&sequence.id, Source::Key, actions))
+        },
+        ActionsType::Pointer {
+            ref parameters,
+            ref actions,
+        } => {
+            let actions: Vec<Action> = actions
+                .iter()
+                .map(|action| match *action {
+                    PointerActionItem::General(ref action) => action.into(),
+                    PointerActionItem::Pointer(ref action) => Action::Pointer(action),
+                })
+                .collect();
+            Ok((
+                &sequence.id,
+                Source::Pointer(&parameters.pointer_type),
+                actions,
+            ))
+        },
+    }
+}
+
+fn mouse_button(button: u64) -> Option<MouseButton> {
+    match button {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    }
+}
+
+impl Handler {
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-actions
+    pub(crate) fn dispatch_actions(&mut self, sequences: &[InputSequence]) -> WebDriverResult<()> {
+        // https://w3c.github.io/webdriver/#dfn-extract-an-action-sequence
+        let mut actions_by_tick: Vec<Vec<(&str, Action)>> = vec![];
+        for &sequence in sequences {
+            let (id, source, actions) = process_action_sequence(sequence)?;
+            self.ensure_input_source(id, source)?;
+            for (tick, action) in actions.into_iter().enumerate() {
+                if actions_by_tick.len() == tick {
+                    actions_by_tick.push(vec![]);
+                }
+                actions_by_tick[tick].push((id, action));
+            }
+        }
+
+        for tick_actions in actions_by_tick {
+            let tick_start = Instant::now();
+            let tick_duration = tick_actions
+                .iter()
+                .map(|&(_, ref action)| action.duration())
+                .max()
+                .unwrap_or(0);
+            for (id, action) in tick_actions {
+                self.dispatch_action(id, action, tick_start, tick_duration)?;
+            }
+            let tick_duration = Duration::from_millis(tick_duration);
+            let elapsed = tick_start.elapsed();
+            if elapsed < tick_duration {
+                thread::sleep(tick_duration - elapsed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the actions of the input cancel list and forget the input sources.
+    ///
+    /// https://w3c.github.io/webdriver/#release-actions
+    pub(crate) fn release_actions(&mut self) -> WebDriverResult<()> {
+        let input_cancel_list = mem::replace(&mut self.session_mut()?.input_cancel_list, vec![]);
+        for (id, action) in input_cancel_list.into_iter().rev() {
+            match action {
+                CancelAction::KeyUp(raw_key) => self.dispatch_keyup_action(&id, raw_key)?,
+                CancelAction::PointerUp(button) => self.dispatch_pointerup_action(&id, button)?,
+            }
+        }
+        self.session_mut()?.input_state_table.clear();
+        Ok(())
+    }
+
+    /// Create the state of the given input source if it is new, or make sure it is of the
+    /// same kind as before otherwise.
+    fn ensure_input_source(&mut self, id: &str, source: Source) -> WebDriverResult<()> {
+        if let Source::Pointer(pointer_type) = source {
+            match *pointer_type {
+                PointerType::Mouse => {},
+                _ => {
+                    return Err(WebDriverError::new(
+                        ErrorStatus::UnsupportedOperation,
+                        "Only mouse pointers are supported",
+                    ));
+                },
+            }
+        }
+        let input_state_table = &mut self.session_mut()?.input_state_table;
+        if let Some(state) = input_state_table.get(id) {
+            return match (state, source) {
+                (&InputSourceState::Null, Source::Null) |
+                (&InputSourceState::Key(_), Source::Key) |
+                (&InputSourceState::Pointer(_), Source::Pointer(_)) |
+                (&InputSourceState::Wheel, Source::Wheel) => Ok(()),
+                _ => Err(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    format!("Input source {} was given actions of another type", id),
+                )),
+            };
+        }
+        let state = match source {
+            Source::Null => InputSourceState::Null,
+            Source::Key => InputSourceState::Key(KeyInputState::new()),
+            Source::Pointer(_) => InputSourceState::Pointer(PointerInputState {
+                pressed: HashMap::new(),
+                x: 0.0,
+                y: 0.0,
+            }),
+            Source::Wheel => InputSourceState::Wheel,
+        };
+        input_state_table.insert(id.to_owned(), state);
+        Ok(())
+    }
+
+    fn dispatch_action(
+        &mut self,
+        id: &str,
+        action: Action,
+        tick_start: Instant,
+        tick_duration: u64,
+    ) -> WebDriverResult<()> {
+        match action {
+            // The tick lasts as long as needed.
+            Action::Pause(_) => Ok(()),
+            Action::KeyDown(raw_key) => self.dispatch_keydown_action(id, raw_key),
+            Action::KeyUp(raw_key) => self.dispatch_keyup_action(id, raw_key),
+            Action::Pointer(&PointerAction::Down(ref action)) => {
+                self.dispatch_pointerdown_action(id, action.button)
+            },
+            Action::Pointer(&PointerAction::Up(ref action)) => {
+                self.dispatch_pointerup_action(id, action.button)
+            },
+            Action::Pointer(&PointerAction::Move(ref action)) => {
+                self.dispatch_pointermove_action(id, action, tick_start, tick_duration)
+            },
+            // Mice cannot be cancelled.
+            Action::Pointer(&PointerAction::Cancel) => Ok(()),
+            Action::Wheel(action) => self.dispatch_scroll_action(action, tick_start, tick_duration),
+        }
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-keydown-action
+    fn dispatch_keydown_action(&mut self, id: &str, raw_key: char) -> WebDriverResult<()> {
+        let event = self.key_input_state(id)?.dispatch_keydown(raw_key);
+        self.session_mut()?
+            .input_cancel_list
+            .push((id.to_owned(), CancelAction::KeyUp(raw_key)));
+        self.send_keyboard_event(event)
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-keyup-action
+    fn dispatch_keyup_action(&mut self, id: &str, raw_key: char) -> WebDriverResult<()> {
+        match self.key_input_state(id)?.dispatch_keyup(raw_key) {
+            Some(event) => self.send_keyboard_event(event),
+            // The key was not pressed.
+            None => Ok(()),
+        }
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-pointerdown-action
+    fn dispatch_pointerdown_action(&mut self, id: &str, button: u64) -> WebDriverResult<()> {
+        let state = self.pointer_input_state(id)?;
+        let (x, y) = (state.x, state.y);
+        if state.pressed.contains_key(&button) {
+            return Ok(());
+        }
+        state.pressed.insert(button, (x, y));
+        self.session_mut()?
+            .input_cancel_list
+            .push((id.to_owned(), CancelAction::PointerUp(button)));
+        self.send_mouse_button_event(MouseEventType::MouseDown, button, x, y)
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-pointerup-action
+    fn dispatch_pointerup_action(&mut self, id: &str, button: u64) -> WebDriverResult<()> {
+        let state = self.pointer_input_state(id)?;
+        let (x, y) = (state.x, state.y);
+        let pressed_at = match state.pressed.remove(&button) {
+            Some(pressed_at) => pressed_at,
+            None => return Ok(()),
+        };
+        self.send_mouse_button_event(MouseEventType::MouseUp, button, x, y)?;
+        // Like windowing systems, click when the button is released where it was pressed.
+        if pressed_at == (x, y) {
+            self.send_mouse_button_event(MouseEventType::Click, button, x, y)?;
+        }
+        Ok(())
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-pointermove-action
+    fn dispatch_pointermove_action(
+        &mut self,
+        id: &str,
+        action: &PointerMoveAction,
+        tick_start: Instant,
+        tick_duration: u64,
+    ) -> WebDriverResult<()> {
+        // Steps 1 - 2
+        let x_offset = action.x.unwrap_or(0) as f64;
+        let y_offset = action.y.unwrap_or(0) as f64;
+
+        // Steps 3 - 4
+        let state = self.pointer_input_state(id)?;
+        let start = (state.x, state.y);
+
+        // Steps 5 - 6
+        let (x, y) = match action.origin {
+            PointerOrigin::Viewport => (x_offset, y_offset),
+            PointerOrigin::Pointer => (start.0 + x_offset, start.1 + y_offset),
+            PointerOrigin::Element(ref element) => {
+                let (x, y) = self.element_in_view_center_point(element)?;
+                (x + x_offset, y + y_offset)
+            },
+        };
+
+        // Step 7
+        self.check_in_viewport(x, y)?;
+
+        // Steps 8 - 10
+        let duration = action.duration.unwrap_or(tick_duration);
+        self.interpolate(start, (x, y), tick_start, duration, |handler, point| {
+            handler.move_pointer(id, point)
+        })
+    }
+
+    fn move_pointer(&mut self, id: &str, (x, y): (f64, f64)) -> WebDriverResult<()> {
+        let state = self.pointer_input_state(id)?;
+        if (state.x, state.y) == (x, y) {
+            return Ok(());
+        }
+        state.x = x;
+        state.y = y;
+        let cmd_msg = WebDriverCommandMsg::MouseMoveAction(x as f32, y as f32);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        Ok(())
+    }
+
+    /// Click at the given point of the viewport with the primary button of a new mouse.
+    ///
+    /// https://w3c.github.io/webdriver/#element-click
+    pub(crate) fn click(&mut self, x: f64, y: f64) -> WebDriverResult<()> {
+        // Steps 9.1 - 9.3
+        let id = Uuid::new_v4().to_string();
+        self.ensure_input_source(&id, Source::Pointer(&PointerType::Mouse))?;
+
+        // Steps 9.4 - 9.11
+        self.move_pointer(&id, (x, y))?;
+        self.dispatch_pointerdown_action(&id, 0)?;
+        self.dispatch_pointerup_action(&id, 0)?;
+
+        // Step 9.12
+        let session = self.session_mut()?;
+        session.input_state_table.remove(&id);
+        session
+            .input_cancel_list
+            .retain(|&(ref source_id, _)| *source_id != id);
+        Ok(())
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-dispatch-a-scroll-action
+    fn dispatch_scroll_action(
+        &mut self,
+        action: &WheelScrollAction,
+        tick_start: Instant,
+        tick_duration: u64,
+    ) -> WebDriverResult<()> {
+        // Steps 1 - 2
+        let x_offset = action.x as f64;
+        let y_offset = action.y as f64;
+
+        // Steps 3 - 5
+        let (x, y) = match action.origin {
+            None | Some(PointerOrigin::Viewport) => (x_offset, y_offset),
+            Some(PointerOrigin::Element(ref element)) => {
+                let (x, y) = self.element_in_view_center_point(element)?;
+                (x + x_offset, y + y_offset)
+            },
+            Some(PointerOrigin::Pointer) => {
+                return Err(WebDriverError::new(
+                    ErrorStatus::InvalidArgument,
+                    "Scrolls cannot be relative to the pointer",
+                ));
+            },
+        };
+
+        // Step 6
+        self.check_in_viewport(x, y)?;
+
+        // Steps 7 - 10
+        let duration = action.duration.unwrap_or(tick_duration);
+        let delta = (action.delta_x as f64, action.delta_y as f64);
+        let mut scrolled = (0.0, 0.0);
+        self.interpolate(
+            (0.0, 0.0),
+            delta,
+            tick_start,
+            duration,
+            |handler, current| {
+                if current == scrolled {
+                    return Ok(());
+                }
+                let delta = WheelDelta {
+                    x: current.0 - scrolled.0,
+                    y: current.1 - scrolled.1,
+                    z: 0.0,
+                    mode: WheelMode::DeltaPixel,
+                };
+                scrolled = current;
+                let cmd_msg = WebDriverCommandMsg::WheelScrollAction(delta, x as f32, y as f32);
+                handler
+                    .constellation_chan
+                    .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+                    .unwrap();
+                Ok(())
+            },
+        )
+    }
+
+    /// Go from `start` to `target` in as many steps as there is time for in the given
+    /// duration since the start of the tick, ending with `target`.
+    ///
+    /// https://w3c.github.io/webdriver/#dfn-perform-a-pointer-move
+    fn interpolate<F>(
+        &mut self,
+        start: (f64, f64),
+        target: (f64, f64),
+        tick_start: Instant,
+        duration: u64,
+        mut step: F,
+    ) -> WebDriverResult<()>
+    where
+        F: FnMut(&mut Handler, (f64, f64)) -> WebDriverResult<()>,
+    {
+        loop {
+            let elapsed = tick_start.elapsed().as_millis() as u64;
+            if elapsed >= duration {
+                return step(self, target);
+            }
+            let ratio = elapsed as f64 / duration as f64;
+            step(
+                self,
+                (
+                    (ratio * (target.0 - start.0) + start.0).floor(),
+                    (ratio * (target.1 - start.1) + start.1).floor(),
+                ),
+            )?;
+            thread::sleep(Duration::from_millis(INTERPOLATION_INTERVAL));
+        }
+    }
+
+    fn key_input_state(&mut self, id: &str) -> WebDriverResult<&mut KeyInputState> {
+        match self.session_mut()?.input_state_table.get_mut(id) {
+            Some(&mut InputSourceState::Key(ref mut state)) => Ok(state),
+            _ => unreachable!("Key actions of another input source"),
+        }
+    }
+
+    fn pointer_input_state(&mut self, id: &str) -> WebDriverResult<&mut PointerInputState> {
+        match self.session_mut()?.input_state_table.get_mut(id) {
+            Some(&mut InputSourceState::Pointer(ref mut state)) => Ok(state),
+            _ => unreachable!("Pointer actions of another input source"),
+        }
+    }
+
+    fn send_keyboard_event(&self, event: KeyboardEvent) -> WebDriverResult<()> {
+        let browsing_context_id = self.session()?.browsing_context_id;
+        let cmd_msg = WebDriverCommandMsg::SendKeys(
+            browsing_context_id,
+            vec![WebDriverInputEvent::Keyboard(event)],
+        );
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        Ok(())
+    }
+
+    fn send_mouse_button_event(
+        &self,
+        event_type: MouseEventType,
+        button: u64,
+        x: f64,
+        y: f64,
+    ) -> WebDriverResult<()> {
+        // Other buttons cannot be pressed as far as pages are concerned.
+        let button = match mouse_button(button) {
+            Some(button) => button,
+            None => return Ok(()),
+        };
+        let cmd_msg =
+            WebDriverCommandMsg::MouseButtonAction(event_type, button, x as f32, y as f32);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        Ok(())
+    }
+
+    /// https://w3c.github.io/webdriver/#dfn-in-view-center-point
+    fn element_in_view_center_point(&self, element: &WebElement) -> WebDriverResult<(f64, f64)> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd = WebDriverScriptCommand::GetElementInViewCenterPoint(element.id.clone(), sender);
+        self.browsing_context_script_command(cmd)?;
+        match receiver.recv().unwrap() {
+            Ok(Some((x, y))) => Ok((x as f64, y as f64)),
+            Ok(None) => Err(WebDriverError::new(
+                ErrorStatus::MoveTargetOutOfBounds,
+                "Element has no client rects",
+            )),
+            Err(_) => Err(WebDriverError::new(
+                ErrorStatus::StaleElementReference,
+                "Unable to find element in document",
+            )),
+        }
+    }
+
+    /// Fail with `move target out of bounds` unless the given point is in the viewport.
+    fn check_in_viewport(&self, x: f64, y: f64) -> WebDriverResult<()> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        let cmd_msg = WebDriverCommandMsg::GetWindowSize(top_level_browsing_context_id, sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        let viewport = receiver.recv().unwrap().initial_viewport;
+        if x < 0.0 || y < 0.0 || x > viewport.width as f64 || y > viewport.height as f64 {
+            return Err(WebDriverError::new(
+                ErrorStatus::MoveTargetOutOfBounds,
+                format!("({}, {}) is out of the viewport", x, y),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1143 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A WebSocket server speaking the WebDriver BiDi protocol, which streams events to its
+//! clients instead of having them poll.
+//! https://w3c.github.io/webdriver-bidi/
+
+use crate::browsing_context_handle;
+use crate::capabilities::ServoCapabilities;
+use crossbeam_channel::{select, Receiver, Sender};
+use devtools_traits::ScriptToDevtoolsControlMsg;
+use devtools_traits::{ChromeToDevtoolsControlMsg, ConsoleMessage, DevtoolsControlMsg};
+use devtools_traits::{HttpRequest, HttpResponse, HttpResponseEnd, LogLevel, NetworkEvent};
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use msg::constellation_msg::{BrowsingContextId, PipelineId};
+use script_traits::webdriver_msg::{WebDriverBrowsingContextEvent, WebDriverJSError};
+use script_traits::webdriver_msg::{WebDriverJSValue, WebDriverScriptCommand};
+use script_traits::{ConstellationMsg, WebDriverCommandMsg};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+use ws::{self, Builder, CloseCode, Handshake};
+
+/// The events clients can subscribe to, by module.
+const EVENTS: &[(&str, &[&str])] = &[
+    (
+        "browsingContext",
+        &[
+            "browsingContext.contextCreated",
+            "browsingContext.navigationStarted",
+            "browsingContext.load",
+        ],
+    ),
+    ("log", &["log.entryAdded"]),
+    (
+        "network",
+        &[
+            "network.beforeRequestSent",
+            "network.responseStarted",
+            "network.responseCompleted",
+        ],
+    ),
+];
+
+/// Start the WebDriver BiDi server on the given port. It reports the console messages and
+/// network events received on `devtools_receiver`, before passing them on to devtools.
+pub fn start_bidi_server(
+    port: u16,
+    constellation_chan: Sender<ConstellationMsg>,
+    devtools_receiver: Receiver<DevtoolsControlMsg>,
+    devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+) {
+    let (event_sender, event_receiver) = ipc::channel().unwrap();
+    let cmd_msg = WebDriverCommandMsg::SubscribeToBrowsingContextEvents(event_sender);
+    constellation_chan
+        .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+        .unwrap();
+    let event_receiver = ROUTER.route_ipc_receiver_to_new_crossbeam_receiver(event_receiver);
+
+    let server = Arc::new(Mutex::new(Server {
+        clients: HashMap::new(),
+        parents: HashMap::new(),
+        requests: HashMap::new(),
+    }));
+
+    let events_server = server.clone();
+    let events_constellation_chan = constellation_chan.clone();
+    thread::Builder::new()
+        .name("WebdriverBidiEvents".to_owned())
+        .spawn(move || {
+            report_events(
+                events_server,
+                events_constellation_chan,
+                event_receiver,
+                devtools_receiver,
+                devtools_chan,
+            )
+        })
+        .expect("Thread spawning failed");
+
+    thread::Builder::new()
+        .name("WebdriverBidiServer".to_owned())
+        .spawn(move || {
+            let socket = Builder::new()
+                .build(|sender: ws::Sender| Connection {
+                    sender,
+                    server: server.clone(),
+                    constellation_chan: constellation_chan.clone(),
+                })
+                .unwrap();
+            match socket.listen(("0.0.0.0", port)) {
+                Ok(_) => info!("WebDriver BiDi server stopped"),
+                Err(e) => panic!("Unable to start WebDriver BiDi server ({})", e),
+            }
+        })
+        .expect("Thread spawning failed");
+}
+
+/// The state the connections and the event reporting share.
+struct Server {
+    clients: HashMap<u32, Client>,
+    /// The parent of the nested browsing contexts seen so far, to tell the top-level
+    /// browsing context of the ones events are about.
+    parents: HashMap<BrowsingContextId, BrowsingContextId>,
+    /// The browsing context and the data of the requests that are still in flight, by
+    /// request id.
+    requests: HashMap<String, (Option<BrowsingContextId>, Value, Option<Value>)>,
+}
+
+struct Client {
+    sender: ws::Sender,
+    /// https://w3c.github.io/webdriver-bidi/#session, if the client started one.
+    session_id: Option<String>,
+    subscriptions: Vec<Subscription>,
+}
+
+/// https://w3c.github.io/webdriver-bidi/#subscriptions
+#[derive(PartialEq)]
+struct Subscription {
+    event: &'static str,
+    /// The top-level browsing contexts the subscription is restricted to, if any.
+    contexts: Option<Vec<BrowsingContextId>>,
+}
+
+impl Subscription {
+    /// Whether the subscription is for the given event, about the given top-level browsing
+    /// context if any.
+    fn matches(&self, method: &str, top_level: Option<BrowsingContextId>) -> bool {
+        self.event == method &&
+            match (&self.contexts, top_level) {
+                (None, _) => true,
+                (Some(contexts), Some(top_level)) => contexts.contains(&top_level),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+impl Server {
+    fn top_level_browsing_context(
+        &self,
+        mut browsing_context_id: BrowsingContextId,
+    ) -> BrowsingContextId {
+        while let Some(parent) = self.parents.get(&browsing_context_id) {
+            browsing_context_id = *parent;
+        }
+        browsing_context_id
+    }
+
+    /// https://w3c.github.io/webdriver-bidi/#emit-an-event
+    fn emit(&self, method: &str, context: Option<BrowsingContextId>, params: Value) {
+        let top_level = context.map(|context| self.top_level_browsing_context(context));
+        let message = json!({
+            "type": "event",
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        for client in self.clients.values() {
+            if client.session_id.is_none() {
+                continue;
+            }
+            let subscribed = client
+                .subscriptions
+                .iter()
+                .any(|subscription| subscription.matches(method, top_level));
+            if subscribed {
+                if let Err(e) = client.sender.send(message.clone()) {
+                    warn!("Failed to send WebDriver BiDi event ({}).", e);
+                }
+            }
+        }
+    }
+
+    fn handle_browsing_context_event(&mut self, event: WebDriverBrowsingContextEvent) {
+        match event {
+            WebDriverBrowsingContextEvent::ContextCreated(context, parent, url) => {
+                if let Some(parent) = parent {
+                    self.parents.insert(context, parent);
+                }
+                let params = json!({
+                    "context": browsing_context_handle(context),
+                    "url": url.as_str(),
+                    "children": null,
+                    "parent": parent.map(browsing_context_handle),
+                    "userContext": "default",
+                });
+                self.emit("browsingContext.contextCreated", Some(context), params);
+            },
+            WebDriverBrowsingContextEvent::NavigationStarted(context, url) => {
+                let params = navigation_info(context, url.as_str());
+                self.emit("browsingContext.navigationStarted", Some(context), params);
+            },
+            WebDriverBrowsingContextEvent::Load(context, url) => {
+                let params = navigation_info(context, url.as_str());
+                self.emit("browsingContext.load", Some(context), params);
+            },
+        }
+    }
+
+    /// https://w3c.github.io/webdriver-bidi/#event-log-entryAdded
+    fn handle_console_message(&self, context: Option<BrowsingContextId>, message: &ConsoleMessage) {
+        let (level, method) = match message.logLevel {
+            LogLevel::Log => ("info", "log"),
+            LogLevel::Debug => ("debug", "debug"),
+            LogLevel::Info => ("info", "info"),
+            LogLevel::Warn => ("warn", "warn"),
+            LogLevel::Error => ("error", "error"),
+        };
+        let params = json!({
+            "type": "console",
+            "method": method,
+            "level": level,
+            "source": {
+                "realm": "",
+                "context": context.map(browsing_context_handle),
+            },
+            "text": message.message,
+            "timestamp": timestamp(),
+            "args": [{ "type": "string", "value": message.message }],
+            "stackTrace": {
+                "callFrames": [{
+                    "url": message.filename,
+                    "functionName": "",
+                    "lineNumber": message.lineNumber,
+                    "columnNumber": message.columnNumber,
+                }],
+            },
+        });
+        self.emit("log.entryAdded", context, params);
+    }
+
+    /// https://w3c.github.io/webdriver-bidi/#module-network
+    fn handle_network_event(
+        &mut self,
+        context: Option<BrowsingContextId>,
+        request_id: String,
+        event: &NetworkEvent,
+    ) {
+        match *event {
+            NetworkEvent::HttpRequest(ref request) => {
+                let request_data = request_data(&request_id, request);
+                let params = network_event_params(context, &request_data, None);
+                self.emit("network.beforeRequestSent", context, params);
+                self.requests
+                    .insert(request_id, (context, request_data, None));
+            },
+            NetworkEvent::HttpResponse(ref response) => {
+                let (context, params) = match self.requests.get_mut(&request_id) {
+                    Some((context, request_data, response_data)) => {
+                        let data = response_data_from(request_data, response);
+                        let params = network_event_params(*context, request_data, Some(&data));
+                        *response_data = Some(data);
+                        (*context, params)
+                    },
+                    None => return,
+                };
+                self.emit("network.responseStarted", context, params);
+            },
+            NetworkEvent::HttpResponseEnd(ref end) => {
+                let (context, request_data, response_data) = match self.requests.remove(&request_id)
+                {
+                    Some(request) => request,
+                    None => return,
+                };
+                let mut response_data = response_data.unwrap_or_else(|| json!({}));
+                complete_response_data(&mut response_data, end);
+                let params = network_event_params(context, &request_data, Some(&response_data));
+                self.emit("network.responseCompleted", context, params);
+            },
+        }
+    }
+}
+
+/// Pass the events of the constellation and the messages for devtools on to the clients.
+fn report_events(
+    server: Arc<Mutex<Server>>,
+    constellation_chan: Sender<ConstellationMsg>,
+    event_receiver: Receiver<WebDriverBrowsingContextEvent>,
+    devtools_receiver: Receiver<DevtoolsControlMsg>,
+    devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+) {
+    loop {
+        select! {
+            recv(event_receiver) -> event => {
+                match event {
+                    Ok(event) => server.lock().unwrap().handle_browsing_context_event(event),
+                    Err(_) => break,
+                }
+            }
+            recv(devtools_receiver) -> msg => {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                };
+                match msg {
+                    DevtoolsControlMsg::FromScript(
+                        ScriptToDevtoolsControlMsg::ConsoleAPI(pipeline_id, ref message, _),
+                    ) => {
+                        let context = browsing_context_of(&constellation_chan, pipeline_id);
+                        server.lock().unwrap().handle_console_message(context, message);
+                    },
+                    DevtoolsControlMsg::FromChrome(
+                        ChromeToDevtoolsControlMsg::NetworkEvent(ref request_id, ref event),
+                    ) => {
+                        let pipeline_id = match *event {
+                            NetworkEvent::HttpRequest(ref request) => request.pipeline_id,
+                            NetworkEvent::HttpResponse(ref response) => response.pipeline_id,
+                            NetworkEvent::HttpResponseEnd(ref end) => end.pipeline_id,
+                        };
+                        let context = browsing_context_of(&constellation_chan, pipeline_id);
+                        server
+                            .lock()
+                            .unwrap()
+                            .handle_network_event(context, request_id.clone(), event);
+                    },
+                    _ => {},
+                }
+                if let Some(ref devtools_chan) = devtools_chan {
+                    let _ = devtools_chan.send(msg);
+                }
+            }
+        }
+    }
+}
+
+fn browsing_context_of(
+    constellation_chan: &Sender<ConstellationMsg>,
+    pipeline_id: PipelineId,
+) -> Option<BrowsingContextId> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    constellation_chan
+        .send(ConstellationMsg::GetBrowsingContext(pipeline_id, sender))
+        .unwrap();
+    receiver.recv().unwrap_or(None)
+}
+
+/// The number of milliseconds since the epoch.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() * 1000 + u64::from(duration.subsec_millis()))
+        .unwrap_or(0)
+}
+
+/// https://w3c.github.io/webdriver-bidi/#type-browsingContext-NavigationInfo
+fn navigation_info(context: BrowsingContextId, url: &str) -> Value {
+    json!({
+        "context": browsing_context_handle(context),
+        "navigation": null,
+        "timestamp": timestamp(),
+        "url": url,
+    })
+}
+
+fn headers_data<'a>(headers: impl Iterator<Item = (&'a str, &'a [u8])>) -> Vec<Value> {
+    headers
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": { "type": "string", "value": String::from_utf8_lossy(value) },
+            })
+        })
+        .collect()
+}
+
+/// https://w3c.github.io/webdriver-bidi/#type-network-RequestData
+fn request_data(request_id: &str, request: &HttpRequest) -> Value {
+    let timings = &request.timings;
+    // The time stamp of requests is in seconds.
+    let request_time = request.timeStamp as u64 * 1000;
+    let dns_start = request_time + timings.blocked;
+    let connect_start = dns_start + timings.dns;
+    let request_start = connect_start + timings.connect + timings.ssl;
+    let response_start = request_start + timings.send + timings.wait;
+    json!({
+        "request": request_id,
+        "url": request.url.as_str(),
+        "method": request.method.as_str(),
+        "headers": headers_data(
+            request
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_bytes())),
+        ),
+        "cookies": [],
+        "headersSize": 0,
+        "bodySize": request.body.as_ref().map(|body| body.len()),
+        "timings": {
+            "timeOrigin": request_time,
+            "requestTime": request_time,
+            "redirectStart": 0,
+            "redirectEnd": 0,
+            "fetchStart": request_time,
+            "dnsStart": dns_start,
+            "dnsEnd": connect_start,
+            "connectStart": connect_start,
+            "connectEnd": request_start,
+            "tlsStart": connect_start + timings.connect,
+            "requestStart": request_start,
+            "responseStart": response_start,
+            "responseEnd": response_start + timings.receive,
+        },
+    })
+}
+
+/// https://w3c.github.io/webdriver-bidi/#type-network-ResponseData
+fn response_data_from(request_data: &Value, response: &HttpResponse) -> Value {
+    let headers = match response.headers {
+        Some(ref headers) => headers_data(
+            headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_bytes())),
+        ),
+        None => vec![],
+    };
+    let mime_type = response
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("content-type"))
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default();
+    let (status, status_text) = match response.status {
+        Some((status, ref text)) => (status, String::from_utf8_lossy(text).into_owned()),
+        None => (0, String::new()),
+    };
+    json!({
+        "url": request_data["url"],
+        "protocol": response.http_version,
+        "status": status,
+        "statusText": status_text,
+        "fromCache": false,
+        "headers": headers,
+        "mimeType": mime_type,
+        "bytesReceived": 0,
+        "headersSize": null,
+        "bodySize": null,
+        "content": { "size": 0 },
+    })
+}
+
+fn complete_response_data(response_data: &mut Value, end: &HttpResponseEnd) {
+    response_data["bytesReceived"] = json!(end.transferred_size);
+    response_data["bodySize"] = json!(end.transferred_size);
+    response_data["content"] = json!({ "size": end.body_size });
+}
+
+/// https://w3c.github.io/webdriver-bidi/#type-network-BaseParameters, along with the
+/// response for the events that come after it.
+fn network_event_params(
+    context: Option<BrowsingContextId>,
+    request_data: &Value,
+    response_data: Option<&Value>,
+) -> Value {
+    let mut params = json!({
+        "context": context.map(browsing_context_handle),
+        "isBlocked": false,
+        "navigation": null,
+        "redirectCount": 0,
+        "request": request_data,
+        "timestamp": timestamp(),
+    });
+    match response_data {
+        Some(response_data) => params["response"] = response_data.clone(),
+        None => params["initiator"] = json!({ "type": "other" }),
+    }
+    params
+}
+
+/// https://w3c.github.io/webdriver-bidi/#type-script-RemoteValue
+fn remote_value(value: WebDriverJSValue) -> Value {
+    match value {
+        WebDriverJSValue::Undefined => json!({ "type": "undefined" }),
+        WebDriverJSValue::Null => json!({ "type": "null" }),
+        WebDriverJSValue::Boolean(value) => json!({ "type": "boolean", "value": value }),
+        WebDriverJSValue::Number(value) => {
+            let value = if value.is_nan() {
+                json!("NaN")
+            } else if value == 0.0 && value.is_sign_negative() {
+                json!("-0")
+            } else if value.is_infinite() && value > 0.0 {
+                json!("Infinity")
+            } else if value.is_infinite() {
+                json!("-Infinity")
+            } else {
+                json!(value)
+            };
+            json!({ "type": "number", "value": value })
+        },
+        WebDriverJSValue::String(value) => json!({ "type": "string", "value": value }),
+    }
+}
+
+/// https://w3c.github.io/webdriver-bidi/#errors
+#[derive(Debug)]
+struct BidiError {
+    error: &'static str,
+    message: String,
+}
+
+impl BidiError {
+    fn new<S: Into<String>>(error: &'static str, message: S) -> BidiError {
+        BidiError {
+            error,
+            message: message.into(),
+        }
+    }
+}
+
+type BidiResult = Result<Value, BidiError>;
+
+/// A connection to a client, which runs the commands it sends.
+struct Connection {
+    sender: ws::Sender,
+    server: Arc<Mutex<Server>>,
+    constellation_chan: Sender<ConstellationMsg>,
+}
+
+impl ws::Handler for Connection {
+    fn on_open(&mut self, _: Handshake) -> ws::Result<()> {
+        debug!("WebDriver BiDi connection opened.");
+        self.server.lock().unwrap().clients.insert(
+            self.sender.connection_id(),
+            Client {
+                sender: self.sender.clone(),
+                session_id: None,
+                subscriptions: vec![],
+            },
+        );
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: CloseCode, _: &str) {
+        debug!("WebDriver BiDi connection closed.");
+        self.server
+            .lock()
+            .unwrap()
+            .clients
+            .remove(&self.sender.connection_id());
+    }
+
+    fn on_message(&mut self, message: ws::Message) -> ws::Result<()> {
+        let command: Value = match serde_json::from_str(message.as_text()?) {
+            Ok(command) => command,
+            Err(e) => {
+                return self.send_error(None, BidiError::new("invalid argument", e.to_string()));
+            },
+        };
+        let id = match command["id"].as_u64() {
+            Some(id) => id,
+            None => {
+                return self.send_error(None, BidiError::new("invalid argument", "Missing id"));
+            },
+        };
+        let method = match command["method"].as_str() {
+            Some(method) => method.to_owned(),
+            None => {
+                let error = BidiError::new("invalid argument", "Missing method");
+                return self.send_error(Some(id), error);
+            },
+        };
+        let params = match command.get("params") {
+            Some(params) if params.is_object() => params.clone(),
+            Some(_) => {
+                let error = BidiError::new("invalid argument", "Params must be an object");
+                return self.send_error(Some(id), error);
+            },
+            None => json!({}),
+        };
+
+        let result = match &*method {
+            "session.status" => self.handle_status(),
+            "session.new" => self.handle_new_session(),
+            "session.end" => self.in_session().and_then(|_| self.handle_end_session()),
+            "session.subscribe" => self
+                .in_session()
+                .and_then(|_| self.handle_subscribe(&params)),
+            "session.unsubscribe" => self
+                .in_session()
+                .and_then(|_| self.handle_unsubscribe(&params)),
+            "script.evaluate" => {
+                if let Err(error) = self.in_session() {
+                    return self.send_error(Some(id), error);
+                }
+                // Scripts may take a while, don't keep other clients waiting meanwhile.
+                let sender = self.sender.clone();
+                let constellation_chan = self.constellation_chan.clone();
+                thread::Builder::new()
+                    .name("WebdriverBidiEvaluate".to_owned())
+                    .spawn(move || {
+                        let result = evaluate(&constellation_chan, &params);
+                        let _ = send_result(&sender, id, result);
+                    })
+                    .expect("Thread spawning failed");
+                return Ok(());
+            },
+            _ => Err(BidiError::new(
+                "unknown command",
+                format!("Unknown command: {}", method),
+            )),
+        };
+        send_result(&self.sender, id, result)
+    }
+}
+
+impl Connection {
+    fn send_error(&self, id: Option<u64>, error: BidiError) -> ws::Result<()> {
+        send_result_for(&self.sender, id, Err(error))
+    }
+
+    fn in_session(&self) -> Result<(), BidiError> {
+        let server = self.server.lock().unwrap();
+        match server.clients.get(&self.sender.connection_id()) {
+            Some(client) if client.session_id.is_some() => Ok(()),
+            _ => Err(BidiError::new(
+                "invalid session id",
+                "No session was started",
+            )),
+        }
+    }
+
+    fn with_client<T>(&self, f: impl FnOnce(&mut Client) -> T) -> T {
+        let mut server = self.server.lock().unwrap();
+        let client = server
+            .clients
+            .get_mut(&self.sender.connection_id())
+            .expect("Message from a closed connection");
+        f(client)
+    }
+
+    // https://w3c.github.io/webdriver-bidi/#command-session-status
+    fn handle_status(&self) -> BidiResult {
+        let ready = self.with_client(|client| client.session_id.is_none());
+        Ok(json!({
+            "ready": ready,
+            "message": if ready { "" } else { "Session already started" },
+        }))
+    }
+
+    // https://w3c.github.io/webdriver-bidi/#command-session-new
+    fn handle_new_session(&self) -> BidiResult {
+        let session_id = Uuid::new_v4().to_string();
+        let started = self.with_client(|client| {
+            if client.session_id.is_some() {
+                return false;
+            }
+            client.session_id = Some(session_id.clone());
+            true
+        });
+        if !started {
+            return Err(BidiError::new(
+                "session not created",
+                "Session already started",
+            ));
+        }
+        let capabilities = ServoCapabilities::new();
+        Ok(json!({
+            "sessionId": session_id,
+            "capabilities": {
+                "acceptInsecureCerts": capabilities.accept_insecure_certs,
+                "browserName": capabilities.browser_name,
+                "browserVersion": capabilities.browser_version,
+                "platformName": capabilities
+                    .platform_name
+                    .unwrap_or_else(|| "unknown".to_owned()),
+                "setWindowRect": capabilities.set_window_rect,
+                "proxy": {},
+            },
+        }))
+    }
+
+    // https://w3c.github.io/webdriver-bidi/#command-session-end
+    fn handle_end_session(&self) -> BidiResult {
+        self.with_client(|client| {
+            client.session_id = None;
+            client.subscriptions.clear();
+        });
+        Ok(json!({}))
+    }
+
+    /// The events and top-level browsing contexts subscription commands are about.
+    fn subscription_params(
+        &self,
+        params: &Value,
+    ) -> Result<(Vec<&'static str>, Option<Vec<BrowsingContextId>>), BidiError> {
+        let events = subscribed_events(&params["events"])?;
+
+        let contexts = match params.get("contexts") {
+            None => None,
+            Some(handles) => {
+                let handles = handles.as_array().ok_or_else(|| {
+                    BidiError::new("invalid argument", "Contexts must be an array")
+                })?;
+                let mut contexts = vec![];
+                for handle in handles {
+                    let context = handle
+                        .as_str()
+                        .and_then(|handle| self.browsing_context_from_handle(handle))
+                        .ok_or_else(|| BidiError::new("no such frame", "Unknown context"))?;
+                    contexts.push(
+                        self.server
+                            .lock()
+                            .unwrap()
+                            .top_level_browsing_context(context),
+                    );
+                }
+                Some(contexts)
+            },
+        };
+        Ok((events, contexts))
+    }
+
+    fn browsing_context_from_handle(&self, handle: &str) -> Option<BrowsingContextId> {
+        browsing_context_from_handle(&self.constellation_chan, handle).or_else(|| {
+            self.server
+                .lock()
+                .unwrap()
+                .parents
+                .keys()
+                .cloned()
+                .find(|context| browsing_context_handle(*context) == handle)
+        })
+    }
+
+    // https://w3c.github.io/webdriver-bidi/#command-session-subscribe
+    fn handle_subscribe(&self, params: &Value) -> BidiResult {
+        let (events, contexts) = self.subscription_params(params)?;
+        self.with_client(|client| {
+            for event in events {
+                let subscription = Subscription {
+                    event,
+                    contexts: contexts.clone(),
+                };
+                if !client.subscriptions.contains(&subscription) {
+                    client.subscriptions.push(subscription);
+                }
+            }
+        });
+        Ok(json!({}))
+    }
+
+    // https://w3c.github.io/webdriver-bidi/#command-session-unsubscribe
+    fn handle_unsubscribe(&self, params: &Value) -> BidiResult {
+        let (events, contexts) = self.subscription_params(params)?;
+        self.with_client(|client| {
+            client.subscriptions.retain(|subscription| {
+                !(events.contains(&subscription.event) && subscription.contexts == contexts)
+            });
+        });
+        Ok(json!({}))
+    }
+}
+
+/// The events the given event and module names of subscription commands stand for.
+fn subscribed_events(names: &Value) -> Result<Vec<&'static str>, BidiError> {
+    let names = names
+        .as_array()
+        .ok_or_else(|| BidiError::new("invalid argument", "Missing events"))?;
+    let mut events = vec![];
+    for name in names {
+        let name = name
+            .as_str()
+            .ok_or_else(|| BidiError::new("invalid argument", "Events must be strings"))?;
+        let module_events = EVENTS
+            .iter()
+            .find(|(module, module_events)| *module == name || module_events.contains(&name))
+            .map(|(module, module_events)| {
+                if *module == name {
+                    module_events.to_vec()
+                } else {
+                    module_events
+                        .iter()
+                        .cloned()
+                        .filter(|event| *event == name)
+                        .collect()
+                }
+            })
+            .ok_or_else(|| {
+                BidiError::new("invalid argument", format!("Unknown event: {}", name))
+            })?;
+        events.extend(module_events);
+    }
+    Ok(events)
+}
+
+/// Find an open top-level browsing context from its handle.
+fn browsing_context_from_handle(
+    constellation_chan: &Sender<ConstellationMsg>,
+    handle: &str,
+) -> Option<BrowsingContextId> {
+    let (sender, receiver) = ipc::channel().unwrap();
+    let cmd_msg = WebDriverCommandMsg::GetTopLevelBrowsingContexts(sender);
+    constellation_chan
+        .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+        .unwrap();
+    receiver
+        .recv()
+        .unwrap_or_default()
+        .into_iter()
+        .map(BrowsingContextId::from)
+        .find(|context| browsing_context_handle(*context) == handle)
+}
+
+// https://w3c.github.io/webdriver-bidi/#command-script-evaluate
+fn evaluate(constellation_chan: &Sender<ConstellationMsg>, params: &Value) -> BidiResult {
+    let expression = params["expression"]
+        .as_str()
+        .ok_or_else(|| BidiError::new("invalid argument", "Missing expression"))?;
+    let handle = params["target"]["context"]
+        .as_str()
+        .ok_or_else(|| BidiError::new("invalid argument", "Only contexts can be targeted"))?;
+    let await_promise = params["awaitPromise"]
+        .as_bool()
+        .ok_or_else(|| BidiError::new("invalid argument", "Missing awaitPromise"))?;
+    let context = browsing_context_from_handle(constellation_chan, handle)
+        .ok_or_else(|| BidiError::new("no such frame", "Unknown context"))?;
+
+    let (sender, receiver) = ipc::channel().unwrap();
+    let command = if await_promise {
+        // The script thread can't tell fulfilled promises from rejected ones yet, so
+        // both report their value.
+        let script = format!(
+            "Promise.resolve(eval({})).then(window.webdriverCallback, window.webdriverCallback)",
+            Value::from(expression),
+        );
+        WebDriverScriptCommand::ExecuteAsyncScript(script, sender)
+    } else {
+        WebDriverScriptCommand::ExecuteScript(expression.to_owned(), sender)
+    };
+    let cmd_msg = WebDriverCommandMsg::ScriptCommand(context, command);
+    constellation_chan
+        .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+        .unwrap();
+
+    let result = match receiver.recv().unwrap() {
+        Ok(value) => remote_value(value),
+        // Objects can't be serialized yet, but they can be told apart from the rest.
+        Err(WebDriverJSError::UnknownType) => json!({ "type": "object" }),
+        Err(WebDriverJSError::BrowsingContextNotFound) => {
+            return Err(BidiError::new("no such frame", "Unknown context"));
+        },
+        Err(WebDriverJSError::Timeout) => {
+            return Err(BidiError::new("unknown error", "Script timed out"));
+        },
+    };
+    Ok(json!({
+        "type": "success",
+        "result": result,
+        "realm": handle,
+    }))
+}
+
+fn send_result(sender: &ws::Sender, id: u64, result: BidiResult) -> ws::Result<()> {
+    send_result_for(sender, Some(id), result)
+}
+
+/// https://w3c.github.io/webdriver-bidi/#respond-with-an-error
+fn send_result_for(sender: &ws::Sender, id: Option<u64>, result: BidiResult) -> ws::Result<()> {
+    let message = match result {
+        Ok(result) => json!({
+            "type": "success",
+            "id": id,
+            "result": result,
+        }),
+        Err(error) => json!({
+            "type": "error",
+            "id": id,
+            "error": error.error,
+            "message": error.message,
+        }),
+    };
+    sender.send(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{complete_response_data, evaluate, network_event_params, remote_value};
+    use super::{subscribed_events, Server, Subscription};
+    use crate::browsing_context_handle;
+    use crossbeam_channel::{unbounded, Sender};
+    use devtools_traits::HttpResponseEnd;
+    use msg::constellation_msg::{BrowsingContextId, BrowsingContextIndex};
+    use msg::constellation_msg::{TopLevelBrowsingContextId, TEST_NAMESPACE, TEST_PIPELINE_ID};
+    use script_traits::webdriver_msg::{WebDriverBrowsingContextEvent, WebDriverJSValue};
+    use script_traits::webdriver_msg::{WebDriverJSResult, WebDriverScriptCommand};
+    use script_traits::{ConstellationMsg, WebDriverCommandMsg};
+    use serde_json::json;
+    use servo_url::ServoUrl;
+    use std::collections::HashMap;
+    use std::f64;
+    use std::num::NonZeroU32;
+    use std::thread::{self, JoinHandle};
+
+    fn browsing_context(index: u32) -> BrowsingContextId {
+        BrowsingContextId {
+            namespace_id: TEST_NAMESPACE,
+            index: BrowsingContextIndex(NonZeroU32::new(index).unwrap()),
+        }
+    }
+
+    #[test]
+    fn subscribed_events_expands_modules() {
+        assert_eq!(
+            subscribed_events(&json!(["log", "network.responseStarted"])).unwrap(),
+            vec!["log.entryAdded", "network.responseStarted"]
+        );
+        assert_eq!(
+            subscribed_events(&json!(["browsingContext"])).unwrap(),
+            vec![
+                "browsingContext.contextCreated",
+                "browsingContext.navigationStarted",
+                "browsingContext.load",
+            ]
+        );
+    }
+
+    #[test]
+    fn subscribed_events_rejects_unknown_events() {
+        let error = subscribed_events(&json!(["log.unknown"])).unwrap_err();
+        assert_eq!(error.error, "invalid argument");
+        let error = subscribed_events(&json!([1])).unwrap_err();
+        assert_eq!(error.error, "invalid argument");
+        let error = subscribed_events(&json!(null)).unwrap_err();
+        assert_eq!(error.error, "invalid argument");
+    }
+
+    #[test]
+    fn subscriptions_match_their_event_and_contexts() {
+        let everywhere = Subscription {
+            event: "log.entryAdded",
+            contexts: None,
+        };
+        assert!(everywhere.matches("log.entryAdded", Some(browsing_context(1))));
+        assert!(everywhere.matches("log.entryAdded", None));
+        assert!(!everywhere.matches("browsingContext.load", None));
+
+        let restricted = Subscription {
+            event: "log.entryAdded",
+            contexts: Some(vec![browsing_context(1)]),
+        };
+        assert!(restricted.matches("log.entryAdded", Some(browsing_context(1))));
+        assert!(!restricted.matches("log.entryAdded", Some(browsing_context(2))));
+        assert!(!restricted.matches("log.entryAdded", None));
+    }
+
+    #[test]
+    fn nested_browsing_contexts_belong_to_their_top_level_one() {
+        let mut server = Server {
+            clients: HashMap::new(),
+            parents: HashMap::new(),
+            requests: HashMap::new(),
+        };
+        let url = ServoUrl::parse("about:blank").unwrap();
+        let events = vec![
+            (browsing_context(1), None),
+            (browsing_context(2), Some(browsing_context(1))),
+            (browsing_context(3), Some(browsing_context(2))),
+        ];
+        for (context, parent) in events {
+            server.handle_browsing_context_event(WebDriverBrowsingContextEvent::ContextCreated(
+                context,
+                parent,
+                url.clone(),
+            ));
+        }
+        for index in 1..4 {
+            assert_eq!(
+                server.top_level_browsing_context(browsing_context(index)),
+                browsing_context(1)
+            );
+        }
+    }
+
+    #[test]
+    fn remote_values() {
+        assert_eq!(
+            remote_value(WebDriverJSValue::Undefined),
+            json!({ "type": "undefined" })
+        );
+        assert_eq!(
+            remote_value(WebDriverJSValue::Boolean(true)),
+            json!({ "type": "boolean", "value": true })
+        );
+        assert_eq!(
+            remote_value(WebDriverJSValue::String("a".to_owned())),
+            json!({ "type": "string", "value": "a" })
+        );
+        let numbers = vec![
+            (1.5, json!(1.5)),
+            (f64::NAN, json!("NaN")),
+            (-0.0, json!("-0")),
+            (f64::INFINITY, json!("Infinity")),
+            (f64::NEG_INFINITY, json!("-Infinity")),
+        ];
+        for (number, value) in numbers {
+            assert_eq!(
+                remote_value(WebDriverJSValue::Number(number)),
+                json!({ "type": "number", "value": value })
+            );
+        }
+    }
+
+    #[test]
+    fn network_event_params_have_the_response_once_there_is_one() {
+        let request_data = json!({ "request": "1", "url": "http://example.com/" });
+        let params = network_event_params(Some(browsing_context(1)), &request_data, None);
+        assert_eq!(
+            params["context"],
+            browsing_context_handle(browsing_context(1))
+        );
+        assert_eq!(params["request"], request_data);
+        assert_eq!(params["initiator"], json!({ "type": "other" }));
+        assert!(params.get("response").is_none());
+
+        let mut response_data = json!({ "status": 200 });
+        complete_response_data(
+            &mut response_data,
+            &HttpResponseEnd {
+                body: None,
+                body_size: 100,
+                transferred_size: 120,
+                receive_time: 0,
+                pipeline_id: TEST_PIPELINE_ID,
+            },
+        );
+        let params = network_event_params(None, &request_data, Some(&response_data));
+        assert_eq!(params["context"], json!(null));
+        assert!(params.get("initiator").is_none());
+        assert_eq!(params["response"]["status"], 200);
+        assert_eq!(params["response"]["bytesReceived"], 120);
+        assert_eq!(params["response"]["content"], json!({ "size": 100 }));
+    }
+
+    /// Answer the commands `evaluate` sends, as the constellation would with a single
+    /// top-level browsing context whose scripts give the given result. The thread ends with
+    /// the script that was run, if any.
+    fn constellation(
+        result: WebDriverJSResult,
+    ) -> (Sender<ConstellationMsg>, JoinHandle<Option<String>>) {
+        let (constellation_chan, constellation_port) = unbounded();
+        let constellation = thread::spawn(move || {
+            let mut result = Some(result);
+            loop {
+                let command = match constellation_port.recv() {
+                    Ok(ConstellationMsg::WebDriverCommand(command)) => command,
+                    Ok(msg) => panic!("unexpected message {:?}", msg),
+                    Err(_) => return None,
+                };
+                match command {
+                    WebDriverCommandMsg::GetTopLevelBrowsingContexts(reply) => reply
+                        .send(vec![TopLevelBrowsingContextId(browsing_context(1))])
+                        .unwrap(),
+                    WebDriverCommandMsg::ScriptCommand(context, command) => {
+                        assert_eq!(context, browsing_context(1));
+                        let (script, reply) = match command {
+                            WebDriverScriptCommand::ExecuteScript(script, reply) => (script, reply),
+                            WebDriverScriptCommand::ExecuteAsyncScript(script, reply) => {
+                                (format!("async {}", script), reply)
+                            },
+                            command => panic!("unexpected command {:?}", command),
+                        };
+                        reply.send(result.take().unwrap()).unwrap();
+                        return Some(script);
+                    },
+                    command => panic!("unexpected command {:?}", command),
+                }
+            }
+        });
+        (constellation_chan, constellation)
+    }
+
+    #[test]
+    fn evaluate_runs_the_expression_in_the_context() {
+        let (constellation_chan, constellation) = constellation(Ok(WebDriverJSValue::Number(2.0)));
+        let handle = browsing_context_handle(browsing_context(1));
+        let params = json!({
+            "expression": "1 + 1",
+            "target": { "context": handle },
+            "awaitPromise": false,
+        });
+        let result = evaluate(&constellation_chan, &params).unwrap();
+        assert_eq!(constellation.join().unwrap().unwrap(), "1 + 1");
+        assert_eq!(
+            result,
+            json!({
+                "type": "success",
+                "result": { "type": "number", "value": 2.0 },
+                "realm": handle,
+            })
+        );
+    }
+
+    #[test]
+    fn evaluate_awaits_promises() {
+        let (constellation_chan, constellation) =
+            constellation(Ok(WebDriverJSValue::String("a".to_owned())));
+        let params = json!({
+            "expression": "Promise.resolve(\"a\")",
+            "target": { "context": browsing_context_handle(browsing_context(1)) },
+            "awaitPromise": true,
+        });
+        let result = evaluate(&constellation_chan, &params).unwrap();
+        assert_eq!(
+            constellation.join().unwrap().unwrap(),
+            "async Promise.resolve(eval(\"Promise.resolve(\\\"a\\\")\"))\
+             .then(window.webdriverCallback, window.webdriverCallback)"
+        );
+        assert_eq!(result["result"], json!({ "type": "string", "value": "a" }));
+    }
+
+    #[test]
+    fn evaluate_rejects_invalid_params() {
+        let (constellation_chan, _constellation_port) = unbounded();
+        let handle = browsing_context_handle(browsing_context(1));
+        let params = vec![
+            json!({ "target": { "context": handle }, "awaitPromise": false }),
+            json!({ "expression": "1", "target": { "realm": "" }, "awaitPromise": false }),
+            json!({ "expression": "1", "target": { "context": handle } }),
+        ];
+        for params in params {
+            let error = evaluate(&constellation_chan, &params).unwrap_err();
+            assert_eq!(error.error, "invalid argument");
+        }
+    }
+
+    #[test]
+    fn evaluate_rejects_unknown_contexts() {
+        let (constellation_chan, constellation) = constellation(Ok(WebDriverJSValue::Undefined));
+        let params = json!({
+            "expression": "1",
+            "target": { "context": browsing_context_handle(browsing_context(2)) },
+            "awaitPromise": false,
+        });
+        let error = evaluate(&constellation_chan, &params).unwrap_err();
+        assert_eq!(error.error, "no such frame");
+        drop(constellation_chan);
+        assert_eq!(constellation.join().unwrap(), None);
+    }
+}
@@ -3,6 +3,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use serde_json::{Map, Value};
+use std::str::FromStr;
 use webdriver::capabilities::{BrowserCapabilities, Capabilities};
 use webdriver::error::WebDriverResult;
 
@@ -15,6 +16,7 @@ pub struct ServoCapabilities {
     pub strict_file_interactability: bool,
     pub accept_proxy: bool,
     pub accept_custom: bool,
+    pub unhandled_prompt_behavior: UnhandledPromptBehavior,
 }
 
 impl ServoCapabilities {
@@ -28,6 +30,44 @@ impl ServoCapabilities {
             strict_file_interactability: false,
             accept_proxy: false,
             accept_custom: false,
+            unhandled_prompt_behavior: UnhandledPromptBehavior::DismissAndNotify,
+        }
+    }
+}
+
+/// https://w3c.github.io/webdriver/#dfn-unhandled-prompt-behavior
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnhandledPromptBehavior {
+    Dismiss,
+    Accept,
+    DismissAndNotify,
+    AcceptAndNotify,
+    Ignore,
+}
+
+impl FromStr for UnhandledPromptBehavior {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<UnhandledPromptBehavior, ()> {
+        match value {
+            "dismiss" => Ok(UnhandledPromptBehavior::Dismiss),
+            "accept" => Ok(UnhandledPromptBehavior::Accept),
+            "dismiss and notify" => Ok(UnhandledPromptBehavior::DismissAndNotify),
+            "accept and notify" => Ok(UnhandledPromptBehavior::AcceptAndNotify),
+            "ignore" => Ok(UnhandledPromptBehavior::Ignore),
+            _ => Err(()),
+        }
+    }
+}
+
+impl UnhandledPromptBehavior {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            UnhandledPromptBehavior::Dismiss => "dismiss",
+            UnhandledPromptBehavior::Accept => "accept",
+            UnhandledPromptBehavior::DismissAndNotify => "dismiss and notify",
+            UnhandledPromptBehavior::AcceptAndNotify => "accept and notify",
+            UnhandledPromptBehavior::Ignore => "ignore",
         }
     }
 }
@@ -13,52 +13,64 @@ extern crate serde;
 #[macro_use]
 extern crate serde_json;
 
+mod actions;
+mod bidi;
 mod capabilities;
 
+use crate::actions::{CancelAction, InputSequence, InputSourceState, ServoActionSequence};
+pub use crate::bidi::start_bidi_server;
 use base64;
-use capabilities::ServoCapabilities;
+use capabilities::{ServoCapabilities, UnhandledPromptBehavior};
 use crossbeam_channel::Sender;
-use euclid::TypedSize2D;
+use euclid::{Point2D, Rect, Size2D, TypedSideOffsets2D, TypedSize2D};
 use hyper::Method;
 use image::{DynamicImage, ImageFormat, RgbImage};
 use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
 use keyboard_types::webdriver::send_keys;
-use msg::constellation_msg::{BrowsingContextId, TopLevelBrowsingContextId, TraversalDirection};
+use msg::constellation_msg::{BrowsingContextId, BrowsingContextIndex, PipelineNamespaceId};
+use msg::constellation_msg::{TopLevelBrowsingContextId, TraversalDirection};
 use pixels::PixelFormat;
 use regex::Captures;
+use script_traits::webdriver_msg::WebDriverInteractionError;
 use script_traits::webdriver_msg::{LoadStatus, WebDriverCookieError, WebDriverFrameId};
 use script_traits::webdriver_msg::{
     WebDriverJSError, WebDriverJSResult, WebDriverJSValue, WebDriverScriptCommand,
 };
-use script_traits::{ConstellationMsg, LoadData, WebDriverCommandMsg};
+use script_traits::webdriver_msg::{WebDriverUserPromptAction, WebDriverUserPromptError};
+use script_traits::webdriver_msg::{WebDriverUserPromptType, WebDriverWindowState};
+use script_traits::{
+    ConstellationMsg, LoadData, PrintSettings, WebDriverCommandMsg, WindowSizeData,
+};
 use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
 use serde::ser::{Serialize, Serializer};
 use serde_json::{json, Value};
 use servo_config::{prefs, prefs::PrefValue};
 use servo_url::ServoUrl;
 use std::borrow::ToOwned;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::net::{SocketAddr, SocketAddrV4};
 use std::thread;
 use std::time::Duration;
 use uuid::Uuid;
 use webdriver::capabilities::{Capabilities, CapabilitiesMatching};
-use webdriver::command::SwitchToWindowParameters;
+use webdriver::command::{ActionsParameters, NewWindowParameters, SwitchToWindowParameters};
 use webdriver::command::{
     AddCookieParameters, GetParameters, JavascriptCommandParameters, LocatorParameters,
 };
 use webdriver::command::{
     NewSessionParameters, SendKeysParameters, SwitchToFrameParameters, TimeoutsParameters,
 };
+use webdriver::command::{PrintOrientation, PrintPageRange, PrintParameters};
 use webdriver::command::{
     WebDriverCommand, WebDriverExtensionCommand, WebDriverMessage, WindowRectParameters,
 };
 use webdriver::common::{Cookie, Date, LocatorStrategy, WebElement};
 use webdriver::error::{ErrorStatus, WebDriverError, WebDriverResult};
 use webdriver::httpapi::WebDriverExtensionRoute;
-use webdriver::response::{CookieResponse, CookiesResponse};
-use webdriver::response::{ElementRectResponse, NewSessionResponse, ValueResponse};
+use webdriver::response::ValueResponse;
+use webdriver::response::{CloseWindowResponse, CookieResponse, CookiesResponse};
+use webdriver::response::{ElementRectResponse, NewSessionResponse, NewWindowResponse};
 use webdriver::response::{TimeoutsResponse, WebDriverResponse, WindowRectResponse};
 use webdriver::server::{self, Session, WebDriverHandler};
 
@@ -79,9 +91,62 @@ fn extension_routes() -> Vec<(Method, &'static str, ServoExtensionRoute)> {
             "/session/{sessionId}/servo/prefs/reset",
             ServoExtensionRoute::ResetPrefs,
         ),
+        (
+            Method::POST,
+            "/session/{sessionId}/servo/actions",
+            ServoExtensionRoute::PerformActions,
+        ),
     ];
 }
 
+fn interaction_error_to_webdriver_error(error: WebDriverInteractionError) -> WebDriverError {
+    match error {
+        WebDriverInteractionError::StaleElement => WebDriverError::new(
+            ErrorStatus::StaleElementReference,
+            "Unable to find element in document",
+        ),
+        WebDriverInteractionError::InvalidArgument => WebDriverError::new(
+            ErrorStatus::InvalidArgument,
+            "Element cannot be interacted with like that",
+        ),
+        WebDriverInteractionError::InvalidElementState => {
+            WebDriverError::new(ErrorStatus::InvalidElementState, "Element is not editable")
+        },
+        WebDriverInteractionError::NotInteractable => WebDriverError::new(
+            ErrorStatus::ElementNotInteractable,
+            "Element is not in view",
+        ),
+        WebDriverInteractionError::ClickIntercepted => WebDriverError::new(
+            ErrorStatus::ElementClickIntercepted,
+            "Element is obscured by another element",
+        ),
+    }
+}
+
+/// https://w3c.github.io/webdriver/#dfn-window-handle, which WebDriver BiDi also uses as the
+/// id of the browsing context.
+pub(crate) fn browsing_context_handle(browsing_context_id: BrowsingContextId) -> String {
+    let PipelineNamespaceId(namespace_id) = browsing_context_id.namespace_id;
+    let BrowsingContextIndex(index) = browsing_context_id.index;
+    format!("{}-{}", namespace_id, index.get())
+}
+
+fn user_prompt_error_to_webdriver_error(error: WebDriverUserPromptError) -> WebDriverError {
+    match error {
+        WebDriverUserPromptError::NoSuchAlert => {
+            WebDriverError::new(ErrorStatus::NoSuchAlert, "No user prompt is open")
+        },
+        WebDriverUserPromptError::NotInteractable => WebDriverError::new(
+            ErrorStatus::ElementNotInteractable,
+            "User prompt does not accept text",
+        ),
+        WebDriverUserPromptError::UnsupportedOperation => WebDriverError::new(
+            ErrorStatus::UnsupportedOperation,
+            "User prompt does not accept text",
+        ),
+    }
+}
+
 fn cookie_msg_to_cookie(cookie: cookie::Cookie) -> Cookie {
     Cookie {
         name: cookie.name().to_owned(),
@@ -96,6 +161,39 @@ fn cookie_msg_to_cookie(cookie: cookie::Cookie) -> Cookie {
     }
 }
 
+/// The first and last pages of a page range, numbered from 1, where a missing bound of a
+/// range reaches to the first or last page.
+///
+/// https://w3c.github.io/webdriver/#dfn-parse-a-page-range
+fn parse_page_range(range: &PrintPageRange) -> Option<(usize, usize)> {
+    let (first, last) = match *range {
+        PrintPageRange::Integer(page) => (page as usize, page as usize),
+        PrintPageRange::Range(ref range) => {
+            let mut bounds = range.splitn(2, '-').map(str::trim);
+            let first = bounds.next()?;
+            let last = match bounds.next() {
+                Some(last) => last,
+                None => first,
+            };
+            let first = if first.is_empty() {
+                1
+            } else {
+                first.parse().ok()?
+            };
+            let last = if last.is_empty() {
+                usize::max_value()
+            } else {
+                last.parse().ok()?
+            };
+            (first, last)
+        },
+    };
+    if first == 0 || first > last {
+        return None;
+    }
+    Some((first, last))
+}
+
 pub fn start_server(port: u16, constellation_chan: Sender<ConstellationMsg>) {
     let handler = Handler::new(constellation_chan);
     thread::Builder::new()
@@ -130,7 +228,13 @@ struct WebDriverSession {
     page_loading_strategy: String,
     secure_tls: bool,
     strict_file_interactability: bool,
-    unhandled_prompt_behavior: String,
+    unhandled_prompt_behavior: UnhandledPromptBehavior,
+
+    /// https://w3c.github.io/webdriver/#dfn-input-state-table
+    input_state_table: HashMap<String, InputSourceState>,
+    /// https://w3c.github.io/webdriver/#dfn-input-cancel-list, with the ids of the input
+    /// sources.
+    input_cancel_list: Vec<(String, CancelAction)>,
 }
 
 impl WebDriverSession {
@@ -150,7 +254,10 @@ impl WebDriverSession {
             page_loading_strategy: "normal".to_string(),
             secure_tls: true,
             strict_file_interactability: false,
-            unhandled_prompt_behavior: "dismiss and notify".to_string(),
+            unhandled_prompt_behavior: UnhandledPromptBehavior::DismissAndNotify,
+
+            input_state_table: HashMap::new(),
+            input_cancel_list: vec![],
         }
     }
 }
@@ -166,6 +273,7 @@ enum ServoExtensionRoute {
     GetPrefs,
     SetPrefs,
     ResetPrefs,
+    PerformActions,
 }
 
 impl WebDriverExtensionRoute for ServoExtensionRoute {
@@ -189,6 +297,10 @@ impl WebDriverExtensionRoute for ServoExtensionRoute {
                 let parameters: GetPrefsParameters = serde_json::from_value(body_data.clone())?;
                 ServoExtensionCommand::ResetPrefs(parameters)
             },
+            ServoExtensionRoute::PerformActions => {
+                let parameters: ServoActionsParameters = serde_json::from_value(body_data.clone())?;
+                ServoExtensionCommand::PerformActions(parameters)
+            },
         };
         Ok(WebDriverCommand::Extension(command))
     }
@@ -199,6 +311,7 @@ enum ServoExtensionCommand {
     GetPrefs(GetPrefsParameters),
     SetPrefs(SetPrefsParameters),
     ResetPrefs(GetPrefsParameters),
+    PerformActions(ServoActionsParameters),
 }
 
 impl WebDriverExtensionCommand for ServoExtensionCommand {
@@ -207,6 +320,7 @@ impl WebDriverExtensionCommand for ServoExtensionCommand {
             ServoExtensionCommand::GetPrefs(ref x) => serde_json::to_value(x).ok(),
             ServoExtensionCommand::SetPrefs(ref x) => serde_json::to_value(x).ok(),
             ServoExtensionCommand::ResetPrefs(ref x) => serde_json::to_value(x).ok(),
+            ServoExtensionCommand::PerformActions(ref x) => serde_json::to_value(x).ok(),
         }
     }
 }
@@ -311,6 +425,12 @@ struct SetPrefsParameters {
     prefs: Vec<(String, WebDriverPrefValue)>,
 }
 
+/// The parameters of Perform Actions, whose sequences can also be of wheel input sources.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct ServoActionsParameters {
+    actions: Vec<Value>,
+}
+
 fn map_to_vec<'de, D>(de: D) -> Result<Vec<(String, WebDriverPrefValue)>, D::Error>
 where
     D: Deserializer<'de>,
@@ -492,13 +612,20 @@ impl Handler {
 
                     match processed.get("unhandledPromptBehavior") {
                         Some(unhandled_prompt_behavior) => {
-                            session.unhandled_prompt_behavior =
-                                unhandled_prompt_behavior.to_string()
+                            session.unhandled_prompt_behavior = unhandled_prompt_behavior
+                                .as_str()
+                                .and_then(|behavior| behavior.parse().ok())
+                                .ok_or(WebDriverError::new(
+                                    ErrorStatus::InvalidArgument,
+                                    "Invalid unhandledPromptBehavior",
+                                ))?
                         },
                         None => {
+                            session.unhandled_prompt_behavior =
+                                servo_capabilities.unhandled_prompt_behavior;
                             processed.insert(
                                 "unhandledPromptBehavior".to_string(),
-                                json!(session.unhandled_prompt_behavior),
+                                json!(session.unhandled_prompt_behavior.as_str()),
                             );
                         },
                     }
@@ -614,12 +741,19 @@ impl Handler {
             let _ = sender.send(LoadStatus::LoadTimeout);
         });
 
-        // wait to get a load event
-        match receiver.recv().unwrap() {
-            LoadStatus::LoadComplete => Ok(WebDriverResponse::Void),
-            LoadStatus::LoadTimeout => {
-                Err(WebDriverError::new(ErrorStatus::Timeout, "Load timed out"))
-            },
+        // wait to get a load event, unless a user prompt opened by the page blocks it
+        loop {
+            match receiver.try_recv() {
+                Ok(LoadStatus::LoadComplete) => return Ok(WebDriverResponse::Void),
+                Ok(LoadStatus::LoadTimeout) => {
+                    return Err(WebDriverError::new(ErrorStatus::Timeout, "Load timed out"));
+                },
+                Err(_) => {},
+            }
+            if self.user_prompt()?.is_some() {
+                return Ok(WebDriverResponse::Void);
+            }
+            thread::sleep(Duration::from_millis(10));
         }
     }
 
@@ -679,6 +813,40 @@ impl Handler {
             .send(ConstellationMsg::WebDriverCommand(cmd_msg))
             .unwrap();
 
+        self.wait_for_window_size(top_level_browsing_context_id, sender, receiver)
+    }
+
+    // https://w3c.github.io/webdriver/#maximize-window
+    // https://w3c.github.io/webdriver/#minimize-window
+    // https://w3c.github.io/webdriver/#fullscreen-window
+    fn handle_set_window_state(
+        &self,
+        state: WebDriverWindowState,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        self.verify_top_level_browsing_context_is_open(top_level_browsing_context_id)?;
+        let cmd_msg = WebDriverCommandMsg::SetWindowState(
+            top_level_browsing_context_id,
+            state,
+            sender.clone(),
+        );
+
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+
+        self.wait_for_window_size(top_level_browsing_context_id, sender, receiver)
+    }
+
+    /// Wait for the window to be resized, replying with the current window size if that
+    /// doesn't happen in time.
+    fn wait_for_window_size(
+        &self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
+        sender: IpcSender<WindowSizeData>,
+        receiver: IpcReceiver<WindowSizeData>,
+    ) -> WebDriverResult<WebDriverResponse> {
         let timeout = self.resize_timeout;
         let constellation_chan = self.constellation_chan.clone();
         thread::spawn(move || {
@@ -780,26 +948,109 @@ impl Handler {
         )))
     }
 
+    fn top_level_browsing_context_ids(&self) -> Vec<TopLevelBrowsingContextId> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd_msg = WebDriverCommandMsg::GetTopLevelBrowsingContexts(sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        receiver.recv().unwrap()
+    }
+
+    fn verify_top_level_browsing_context_is_open(
+        &self,
+        top_level_browsing_context_id: TopLevelBrowsingContextId,
+    ) -> WebDriverResult<()> {
+        if self
+            .top_level_browsing_context_ids()
+            .contains(&top_level_browsing_context_id)
+        {
+            Ok(())
+        } else {
+            Err(WebDriverError::new(
+                ErrorStatus::NoSuchWindow,
+                "No such window",
+            ))
+        }
+    }
+
+    fn window_handles(&self) -> Vec<(TopLevelBrowsingContextId, String)> {
+        self.top_level_browsing_context_ids()
+            .into_iter()
+            .map(|id| (id, browsing_context_handle(BrowsingContextId::from(id))))
+            .collect()
+    }
+
+    // https://w3c.github.io/webdriver/#get-window-handle
     fn handle_window_handle(&self) -> WebDriverResult<WebDriverResponse> {
-        // For now we assume there's only one window so just use the session
-        // id as the window id
-        let handle = self.session.as_ref().unwrap().id.to_string();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        self.verify_top_level_browsing_context_is_open(top_level_browsing_context_id)?;
+        let handle =
+            browsing_context_handle(BrowsingContextId::from(top_level_browsing_context_id));
         Ok(WebDriverResponse::Generic(ValueResponse(
             serde_json::to_value(handle)?,
         )))
     }
 
+    // https://w3c.github.io/webdriver/#get-window-handles
     fn handle_window_handles(&self) -> WebDriverResult<WebDriverResponse> {
-        // For now we assume there's only one window so just use the session
-        // id as the window id
-        let handles = vec![serde_json::to_value(
-            self.session.as_ref().unwrap().id.to_string(),
-        )?];
+        let handles: Vec<String> = self
+            .window_handles()
+            .into_iter()
+            .map(|(_, handle)| handle)
+            .collect();
         Ok(WebDriverResponse::Generic(ValueResponse(
             serde_json::to_value(handles)?,
         )))
     }
 
+    // https://w3c.github.io/webdriver/#new-window
+    fn handle_new_window(
+        &self,
+        _parameters: &NewWindowParameters,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let current_top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        self.verify_top_level_browsing_context_is_open(current_top_level_browsing_context_id)?;
+
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd_msg = WebDriverCommandMsg::NewWindow(sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        let top_level_browsing_context_id = receiver.recv().unwrap();
+        let handle =
+            browsing_context_handle(BrowsingContextId::from(top_level_browsing_context_id));
+        // Servo has no notion of windows apart from tabs.
+        Ok(WebDriverResponse::NewWindow(NewWindowResponse {
+            handle,
+            typ: "tab".to_string(),
+        }))
+    }
+
+    // https://w3c.github.io/webdriver/#close-window
+    fn handle_close_window(&mut self) -> WebDriverResult<WebDriverResponse> {
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        self.verify_top_level_browsing_context_is_open(top_level_browsing_context_id)?;
+
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd_msg = WebDriverCommandMsg::CloseWindow(top_level_browsing_context_id, sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        receiver.recv().unwrap();
+
+        let handles: Vec<String> = self
+            .window_handles()
+            .into_iter()
+            .map(|(_, handle)| handle)
+            .collect();
+        // The session ends along with its last window.
+        if handles.is_empty() {
+            self.session = None;
+        }
+        Ok(WebDriverResponse::CloseWindow(CloseWindowResponse(handles)))
+    }
+
     fn handle_find_element(
         &self,
         parameters: &LocatorParameters,
@@ -858,16 +1109,30 @@ impl Handler {
         &mut self,
         parameters: &SwitchToWindowParameters,
     ) -> WebDriverResult<WebDriverResponse> {
-        // For now we assume there is only one window which has the current
-        // session's id as window id
-        if parameters.handle == self.session.as_ref().unwrap().id.to_string() {
-            Ok(WebDriverResponse::Void)
-        } else {
-            Err(WebDriverError::new(
-                ErrorStatus::NoSuchWindow,
-                "No such window",
+        let top_level_browsing_context_id = match self
+            .window_handles()
+            .into_iter()
+            .find(|(_, handle)| *handle == parameters.handle)
+        {
+            Some((id, _)) => id,
+            None => {
+                return Err(WebDriverError::new(
+                    ErrorStatus::NoSuchWindow,
+                    "No such window",
+                ));
+            },
+        };
+
+        let session = self.session_mut()?;
+        session.top_level_browsing_context_id = top_level_browsing_context_id;
+        session.browsing_context_id = BrowsingContextId::from(top_level_browsing_context_id);
+        // Show the browser, as if the user had selected it.
+        self.constellation_chan
+            .send(ConstellationMsg::SelectBrowser(
+                top_level_browsing_context_id,
             ))
-        }
+            .unwrap();
+        Ok(WebDriverResponse::Void)
     }
 
     fn switch_to_frame(
@@ -1180,9 +1445,102 @@ impl Handler {
         }
     }
 
+    fn user_prompt(&self) -> WebDriverResult<Option<(WebDriverUserPromptType, Option<String>)>> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        let cmd_msg = WebDriverCommandMsg::GetUserPrompt(top_level_browsing_context_id, sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        Ok(receiver.recv().unwrap())
+    }
+
+    fn handle_user_prompt(&self, action: WebDriverUserPromptAction) -> WebDriverResult<()> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        let cmd_msg =
+            WebDriverCommandMsg::HandleUserPrompt(top_level_browsing_context_id, action, sender);
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        receiver
+            .recv()
+            .unwrap()
+            .map_err(user_prompt_error_to_webdriver_error)
+    }
+
+    // https://w3c.github.io/webdriver/#dfn-handle-any-user-prompts
+    fn handle_any_user_prompts(&self) -> WebDriverResult<()> {
+        let message = match self.user_prompt()? {
+            Some((_, message)) => message.unwrap_or_default(),
+            None => return Ok(()),
+        };
+        let (action, notify) = match self.session()?.unhandled_prompt_behavior {
+            UnhandledPromptBehavior::Dismiss => (Some(WebDriverUserPromptAction::Dismiss), false),
+            UnhandledPromptBehavior::Accept => (Some(WebDriverUserPromptAction::Accept), false),
+            UnhandledPromptBehavior::DismissAndNotify => {
+                (Some(WebDriverUserPromptAction::Dismiss), true)
+            },
+            UnhandledPromptBehavior::AcceptAndNotify => {
+                (Some(WebDriverUserPromptAction::Accept), true)
+            },
+            UnhandledPromptBehavior::Ignore => (None, true),
+        };
+        if let Some(action) = action {
+            self.handle_user_prompt(action)?;
+        }
+        if notify {
+            return Err(WebDriverError::new(
+                ErrorStatus::UnexpectedAlertOpen,
+                format!("Unexpected user prompt: {}", message),
+            ));
+        }
+        Ok(())
+    }
+
     // https://w3c.github.io/webdriver/#dismiss-alert
-    fn handle_dismiss_alert(&mut self) -> WebDriverResult<WebDriverResponse> {
-        // Since user prompts are not yet implement this will always succeed
+    fn handle_dismiss_alert(&self) -> WebDriverResult<WebDriverResponse> {
+        self.handle_user_prompt(WebDriverUserPromptAction::Dismiss)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    // https://w3c.github.io/webdriver/#accept-alert
+    fn handle_accept_alert(&self) -> WebDriverResult<WebDriverResponse> {
+        self.handle_user_prompt(WebDriverUserPromptAction::Accept)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    // https://w3c.github.io/webdriver/#get-alert-text
+    fn handle_get_alert_text(&self) -> WebDriverResult<WebDriverResponse> {
+        match self.user_prompt()? {
+            Some((_, message)) => Ok(WebDriverResponse::Generic(ValueResponse(
+                serde_json::to_value(message)?,
+            ))),
+            None => Err(user_prompt_error_to_webdriver_error(
+                WebDriverUserPromptError::NoSuchAlert,
+            )),
+        }
+    }
+
+    // https://w3c.github.io/webdriver/#send-alert-text
+    fn handle_send_alert_text(
+        &self,
+        params: &SendKeysParameters,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let top_level_browsing_context_id = self.session()?.top_level_browsing_context_id;
+        let cmd_msg = WebDriverCommandMsg::SendUserPromptText(
+            top_level_browsing_context_id,
+            params.text.clone(),
+            sender,
+        );
+        self.constellation_chan
+            .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+            .unwrap();
+        receiver
+            .recv()
+            .unwrap()
+            .map_err(user_prompt_error_to_webdriver_error)?;
         Ok(WebDriverResponse::Void)
     }
 
@@ -1255,8 +1613,7 @@ impl Handler {
         let (sender, receiver) = ipc::channel().unwrap();
         let command = WebDriverScriptCommand::ExecuteScript(script, sender);
         self.browsing_context_script_command(command)?;
-        let result = receiver.recv().unwrap();
-        self.postprocess_js_result(result)
+        self.wait_for_js_result(receiver)
     }
 
     fn handle_execute_async_script(
@@ -1279,8 +1636,29 @@ impl Handler {
         let (sender, receiver) = ipc::channel().unwrap();
         let command = WebDriverScriptCommand::ExecuteAsyncScript(script, sender);
         self.browsing_context_script_command(command)?;
-        let result = receiver.recv().unwrap();
-        self.postprocess_js_result(result)
+        self.wait_for_js_result(receiver)
+    }
+
+    /// Wait for the result of a script, or for it to open a user prompt, in which case the
+    /// result is null.
+    /// https://w3c.github.io/webdriver/#dfn-execute-a-function-body
+    fn wait_for_js_result(
+        &self,
+        receiver: IpcReceiver<WebDriverJSResult>,
+    ) -> WebDriverResult<WebDriverResponse> {
+        loop {
+            if let Ok(result) = receiver.try_recv() {
+                return self.postprocess_js_result(result);
+            }
+            if self.user_prompt()?.is_some() {
+                // The script thread replies once the prompt is handled.
+                thread::spawn(move || {
+                    let _ = receiver.recv();
+                });
+                return Ok(WebDriverResponse::Generic(ValueResponse(Value::Null)));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
     }
 
     fn postprocess_js_result(
@@ -1339,7 +1717,63 @@ impl Handler {
         Ok(WebDriverResponse::Void)
     }
 
-    fn handle_take_screenshot(&self) -> WebDriverResult<WebDriverResponse> {
+    fn handle_element_click(&mut self, element: &WebElement) -> WebDriverResult<WebDriverResponse> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd = WebDriverScriptCommand::ElementClick(element.id.clone(), sender);
+        self.browsing_context_script_command(cmd)?;
+        let (x, y) = receiver
+            .recv()
+            .unwrap()
+            .map_err(interaction_error_to_webdriver_error)?;
+        self.click(x as f64, y as f64)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    fn handle_element_clear(&self, element: &WebElement) -> WebDriverResult<WebDriverResponse> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd = WebDriverScriptCommand::ElementClear(element.id.clone(), sender);
+        self.browsing_context_script_command(cmd)?;
+        receiver
+            .recv()
+            .unwrap()
+            .map_err(interaction_error_to_webdriver_error)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    fn handle_perform_actions(
+        &mut self,
+        parameters: &ActionsParameters,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let sequences: Vec<_> = parameters
+            .actions
+            .iter()
+            .map(InputSequence::Standard)
+            .collect();
+        self.dispatch_actions(&sequences)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    fn handle_servo_perform_actions(
+        &mut self,
+        parameters: &ServoActionsParameters,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let sequences = parameters
+            .actions
+            .iter()
+            .map(|sequence| serde_json::from_value(sequence.clone()))
+            .collect::<Result<Vec<ServoActionSequence>, _>>()?;
+        let sequences: Vec<_> = sequences.iter().map(InputSequence::from).collect();
+        self.dispatch_actions(&sequences)?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    fn handle_release_actions(&mut self) -> WebDriverResult<WebDriverResponse> {
+        self.release_actions()?;
+        Ok(WebDriverResponse::Void)
+    }
+
+    /// A base64 encoded PNG of the viewport, or of the given rect of it, in CSS pixels.
+    fn take_screenshot(&self, rect: Option<Rect<f64>>) -> WebDriverResult<String> {
         let mut img = None;
         let top_level_id = self.session()?.top_level_browsing_context_id;
 
@@ -1378,18 +1812,109 @@ impl Handler {
             "Unexpected screenshot pixel format"
         );
         let rgb = RgbImage::from_raw(img.width, img.height, img.bytes.to_vec()).unwrap();
+        let mut image = DynamicImage::ImageRgb8(rgb);
+
+        if let Some(rect) = rect {
+            // Unlike the rect, the screenshot is in device pixels.
+            let (sender, receiver) = ipc::channel().unwrap();
+            let cmd_msg = WebDriverCommandMsg::GetWindowSize(top_level_id, sender);
+            self.constellation_chan
+                .send(ConstellationMsg::WebDriverCommand(cmd_msg))
+                .unwrap();
+            let scale = receiver.recv().unwrap().device_pixel_ratio.get() as f64;
+            let bounds = Rect::new(
+                Point2D::zero(),
+                Size2D::new(img.width as f64, img.height as f64),
+            );
+            let rect = rect
+                .scale(scale, scale)
+                .round_out()
+                .intersection(&bounds)
+                .unwrap_or(Rect::zero());
+            image = image.crop(
+                rect.origin.x as u32,
+                rect.origin.y as u32,
+                rect.size.width as u32,
+                rect.size.height as u32,
+            );
+        }
 
         let mut png_data = Vec::new();
-        DynamicImage::ImageRgb8(rgb)
-            .write_to(&mut png_data, ImageFormat::PNG)
-            .unwrap();
+        image.write_to(&mut png_data, ImageFormat::PNG).unwrap();
+
+        Ok(base64::encode(&png_data))
+    }
 
-        let encoded = base64::encode(&png_data);
+    fn handle_take_screenshot(&self) -> WebDriverResult<WebDriverResponse> {
+        let encoded = self.take_screenshot(None)?;
+        Ok(WebDriverResponse::Generic(ValueResponse(
+            serde_json::to_value(encoded)?,
+        )))
+    }
+
+    fn handle_take_element_screenshot(
+        &self,
+        element: &WebElement,
+    ) -> WebDriverResult<WebDriverResponse> {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd = WebDriverScriptCommand::ScrollIntoView(element.id.clone(), sender);
+        self.browsing_context_script_command(cmd)?;
+        let rect = receiver.recv().unwrap().or_else(|_| {
+            Err(WebDriverError::new(
+                ErrorStatus::StaleElementReference,
+                "Unable to find element in document",
+            ))
+        })?;
+        let encoded = self.take_screenshot(Some(rect))?;
         Ok(WebDriverResponse::Generic(ValueResponse(
             serde_json::to_value(encoded)?,
         )))
     }
 
+    /// https://w3c.github.io/webdriver/#print-page
+    fn handle_print(&self, parameters: &PrintParameters) -> WebDriverResult<WebDriverResponse> {
+        // Pages and margins are given in centimetres.
+        let px = |cm: f64| (cm * 96. / 2.54) as f32;
+        let (width, height) = match parameters.orientation {
+            PrintOrientation::Portrait => (parameters.page.width, parameters.page.height),
+            PrintOrientation::Landscape => (parameters.page.height, parameters.page.width),
+        };
+        let margin = &parameters.margin;
+        let mut page_ranges = vec![];
+        for range in &parameters.page_ranges {
+            page_ranges.push(parse_page_range(range).ok_or_else(|| {
+                WebDriverError::new(ErrorStatus::InvalidArgument, "Invalid page range")
+            })?);
+        }
+        let settings = PrintSettings {
+            page_size: TypedSize2D::new(px(width), px(height)),
+            margins: Some(TypedSideOffsets2D::new(
+                px(margin.top),
+                px(margin.right),
+                px(margin.bottom),
+                px(margin.left),
+            )),
+            scale: parameters.scale as f32,
+            shrink_to_fit: parameters.shrink_to_fit,
+            background: parameters.background,
+            page_ranges,
+        };
+
+        let (sender, receiver) = ipc::channel().unwrap();
+        let cmd = WebDriverScriptCommand::Print(settings, sender);
+        self.top_level_script_command(cmd)?;
+
+        match receiver.recv().unwrap() {
+            Ok(pdf) => Ok(WebDriverResponse::Generic(ValueResponse(
+                serde_json::to_value(base64::encode(&pdf))?,
+            ))),
+            Err(_) => Err(WebDriverError::new(
+                ErrorStatus::UnknownError,
+                "Unable to print page",
+            )),
+        }
+    }
+
     fn handle_get_prefs(
         &self,
         parameters: &GetPrefsParameters,
@@ -1467,6 +1992,26 @@ impl WebDriverHandler<ServoExtensionRoute> for Handler {
             },
         }
 
+        // Commands that interact with the page can't run while a user prompt is open.
+        match msg.command {
+            WebDriverCommand::NewSession(_) |
+            WebDriverCommand::DeleteSession |
+            WebDriverCommand::Status |
+            WebDriverCommand::GetTimeouts |
+            WebDriverCommand::SetTimeouts(_) |
+            WebDriverCommand::GetWindowHandle |
+            WebDriverCommand::GetWindowHandles |
+            WebDriverCommand::SwitchToWindow(_) |
+            WebDriverCommand::DismissAlert |
+            WebDriverCommand::AcceptAlert |
+            WebDriverCommand::GetAlertText |
+            WebDriverCommand::SendAlertText(_) |
+            WebDriverCommand::Extension(ServoExtensionCommand::GetPrefs(_)) |
+            WebDriverCommand::Extension(ServoExtensionCommand::SetPrefs(_)) |
+            WebDriverCommand::Extension(ServoExtensionCommand::ResetPrefs(_)) => {},
+            _ => self.handle_any_user_prompts()?,
+        }
+
         match msg.command {
             WebDriverCommand::NewSession(ref parameters) => self.handle_new_session(parameters),
             WebDriverCommand::DeleteSession => self.handle_delete_session(),
@@ -1484,6 +2029,17 @@ impl WebDriverHandler<ServoExtensionRoute> for Handler {
             WebDriverCommand::GetTitle => self.handle_title(),
             WebDriverCommand::GetWindowHandle => self.handle_window_handle(),
             WebDriverCommand::GetWindowHandles => self.handle_window_handles(),
+            WebDriverCommand::NewWindow(ref parameters) => self.handle_new_window(parameters),
+            WebDriverCommand::CloseWindow => self.handle_close_window(),
+            WebDriverCommand::MaximizeWindow => {
+                self.handle_set_window_state(WebDriverWindowState::Maximized)
+            },
+            WebDriverCommand::MinimizeWindow => {
+                self.handle_set_window_state(WebDriverWindowState::Minimized)
+            },
+            WebDriverCommand::FullscreenWindow => {
+                self.handle_set_window_state(WebDriverWindowState::Fullscreen)
+            },
             WebDriverCommand::SwitchToFrame(ref parameters) => {
                 self.handle_switch_to_frame(parameters)
             },
@@ -1520,14 +2076,28 @@ impl WebDriverHandler<ServoExtensionRoute> for Handler {
                 self.handle_element_send_keys(element, keys)
             },
             WebDriverCommand::DismissAlert => self.handle_dismiss_alert(),
+            WebDriverCommand::AcceptAlert => self.handle_accept_alert(),
+            WebDriverCommand::GetAlertText => self.handle_get_alert_text(),
+            WebDriverCommand::SendAlertText(ref x) => self.handle_send_alert_text(x),
             WebDriverCommand::DeleteCookies => self.handle_delete_cookies(),
             WebDriverCommand::GetTimeouts => self.handle_get_timeouts(),
             WebDriverCommand::SetTimeouts(ref x) => self.handle_set_timeouts(x),
             WebDriverCommand::TakeScreenshot => self.handle_take_screenshot(),
+            WebDriverCommand::TakeElementScreenshot(ref element) => {
+                self.handle_take_element_screenshot(element)
+            },
+            WebDriverCommand::Print(ref x) => self.handle_print(x),
+            WebDriverCommand::ElementClick(ref element) => self.handle_element_click(element),
+            WebDriverCommand::ElementClear(ref element) => self.handle_element_clear(element),
+            WebDriverCommand::PerformActions(ref x) => self.handle_perform_actions(x),
+            WebDriverCommand::ReleaseActions => self.handle_release_actions(),
             WebDriverCommand::Extension(ref extension) => match *extension {
                 ServoExtensionCommand::GetPrefs(ref x) => self.handle_get_prefs(x),
                 ServoExtensionCommand::SetPrefs(ref x) => self.handle_set_prefs(x),
                 ServoExtensionCommand::ResetPrefs(ref x) => self.handle_reset_prefs(x),
+                ServoExtensionCommand::PerformActions(ref x) => {
+                    self.handle_servo_perform_actions(x)
+                },
             },
             _ => Err(WebDriverError::new(
                 ErrorStatus::UnsupportedOperation,
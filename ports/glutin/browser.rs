@@ -7,7 +7,7 @@ use crate::window_trait::{WindowPortsMethods, LINE_HEIGHT};
 use euclid::{TypedPoint2D, TypedVector2D};
 use keyboard_types::{Key, KeyboardEvent, Modifiers, ShortcutMatcher};
 use servo::compositing::windowing::{WebRenderDebugOption, WindowEvent};
-use servo::embedder_traits::{EmbedderMsg, FilterPattern};
+use servo::embedder_traits::{Credentials, CredentialsRequest, EmbedderMsg, FilterPattern};
 use servo::msg::constellation_msg::TopLevelBrowsingContextId as BrowserId;
 use servo::msg::constellation_msg::TraversalDirection;
 use servo::net_traits::pub_domains::is_reg_domain;
@@ -24,7 +24,7 @@ use std::mem;
 use std::rc::Rc;
 use std::thread;
 use std::time::Duration;
-use tinyfiledialogs::{self, MessageBoxIcon};
+use tinyfiledialogs::{self, MessageBoxIcon, OkCancel};
 
 pub struct Browser<Window: WindowPortsMethods + ?Sized> {
     current_url: Option<ServoUrl>,
@@ -320,6 +320,47 @@ where
                             .push(WindowEvent::SendError(browser_id, reason));
                     }
                 },
+                EmbedderMsg::Confirm(message, sender) => {
+                    let confirmed = if opts::get().headless {
+                        true
+                    } else {
+                        thread::Builder::new()
+                            .name("display confirm dialog".to_owned())
+                            .spawn(move || {
+                                tinyfiledialogs::message_box_ok_cancel(
+                                    "Confirm",
+                                    &message,
+                                    MessageBoxIcon::Question,
+                                    OkCancel::Cancel,
+                                ) == OkCancel::Ok
+                            })
+                            .unwrap()
+                            .join()
+                            .expect("Thread spawning failed")
+                    };
+                    if let Err(e) = sender.send(confirmed) {
+                        let reason = format!("Failed to send Confirm response: {}", e);
+                        self.event_queue
+                            .push(WindowEvent::SendError(browser_id, reason));
+                    }
+                },
+                EmbedderMsg::Prompt(message, default, sender) => {
+                    let input = if opts::get().headless {
+                        Some(default)
+                    } else {
+                        thread::Builder::new()
+                            .name("display prompt dialog".to_owned())
+                            .spawn(move || tinyfiledialogs::input_box("Prompt", &message, &default))
+                            .unwrap()
+                            .join()
+                            .expect("Thread spawning failed")
+                    };
+                    if let Err(e) = sender.send(input) {
+                        let reason = format!("Failed to send Prompt response: {}", e);
+                        self.event_queue
+                            .push(WindowEvent::SendError(browser_id, reason));
+                    }
+                },
                 EmbedderMsg::AllowUnload(sender) => {
                     // Always allow unload for now.
                     if let Err(e) = sender.send(true) {
@@ -392,6 +433,12 @@ where
                 EmbedderMsg::SetFullscreenState(state) => {
                     self.window.set_fullscreen(state);
                 },
+                EmbedderMsg::SetMaximizedState(state) => {
+                    self.window.set_maximized(state);
+                },
+                EmbedderMsg::SetMinimizedState(state) => {
+                    self.window.set_minimized(state);
+                },
                 EmbedderMsg::LoadStart => {
                     self.loading_state = Some(LoadingState::Connecting);
                 },
@@ -399,8 +446,14 @@ where
                     self.loading_state = Some(LoadingState::Loaded);
                 },
                 EmbedderMsg::CloseBrowser => {
-                    // TODO: close the appropriate "tab".
-                    let _ = self.browsers.pop();
+                    match browser_id.and_then(|id| self.browsers.iter().position(|b| *b == id)) {
+                        Some(index) => {
+                            self.browsers.remove(index);
+                        },
+                        None => {
+                            let _ = self.browsers.pop();
+                        },
+                    }
                     if let Some(prev_browser_id) = self.browsers.last() {
                         self.browser_id = Some(*prev_browser_id);
                         self.event_queue
@@ -447,6 +500,39 @@ where
                         error!("Failed to store profile: {}", e);
                     }
                 },
+                EmbedderMsg::CookiesChanged(domain, changes) => {
+                    for change in changes {
+                        info!(
+                            "Cookie {} {} for {}{} of {} ({:?})",
+                            change.name,
+                            if change.removed { "removed" } else { "set" },
+                            change.domain,
+                            change.path,
+                            domain,
+                            browser_id,
+                        );
+                    }
+                },
+                EmbedderMsg::PromptCredentials(request, sender) => {
+                    let credentials = if opts::get().headless {
+                        None
+                    } else {
+                        prompt_credentials(request)
+                    };
+                    if let Err(e) = sender.send(credentials) {
+                        let reason = format!("Failed to send PromptCredentials response: {}", e);
+                        self.event_queue.push(WindowEvent::SendError(None, reason));
+                    };
+                },
+                EmbedderMsg::DownloadStarted(id, url, path) => {
+                    info!("Downloading {} to {} ({:?})", url, path.display(), id);
+                },
+                EmbedderMsg::DownloadProgress(id, received, total) => {
+                    debug!("Download {:?}: {} of {:?} bytes", id, received, total);
+                },
+                EmbedderMsg::DownloadFinished(id, outcome) => {
+                    info!("Download {:?} finished: {:?}", id, outcome);
+                },
             }
         }
     }
@@ -518,6 +604,31 @@ fn get_selected_files(patterns: Vec<FilterPattern>, multiple_files: bool) -> Opt
         .expect("Thread spawning failed")
 }
 
+fn prompt_credentials(request: CredentialsRequest) -> Option<Credentials> {
+    let title = if request.for_proxy {
+        "Proxy authentication required"
+    } else {
+        "Authentication required"
+    };
+    thread::Builder::new()
+        .name(title.to_owned())
+        .spawn(move || {
+            let message = format!(
+                "{} is requesting a user name and password. The site says: \"{}\"",
+                request.origin, request.realm
+            );
+            let user_name = tinyfiledialogs::input_box(title, &message, "")?;
+            let password = tinyfiledialogs::password_box(title, "Password:")?;
+            Some(Credentials {
+                user_name,
+                password,
+            })
+        })
+        .unwrap()
+        .join()
+        .expect("Thread spawning failed")
+}
+
 fn sanitize_url(request: &str) -> Option<ServoUrl> {
     let request = request.trim();
     ServoUrl::parse(&request)
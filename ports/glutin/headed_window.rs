@@ -319,12 +319,30 @@ impl WindowPortsMethods for Window {
 
     fn set_fullscreen(&self, state: bool) {
         if self.fullscreen.get() != state {
-            self.gl_context.borrow_mut().window()
-                .set_fullscreen(Some(self.primary_monitor.clone()));
+            let monitor = if state {
+                Some(self.primary_monitor.clone())
+            } else {
+                None
+            };
+            self.gl_context.borrow_mut().window().set_fullscreen(monitor);
         }
         self.fullscreen.set(state);
     }
 
+    fn set_maximized(&self, state: bool) {
+        self.gl_context.borrow_mut().window().set_maximized(state);
+    }
+
+    fn set_minimized(&self, state: bool) {
+        // winit can't minimize windows yet, hiding the window is the closest we get.
+        let context = self.gl_context.borrow_mut();
+        if state {
+            context.window().hide();
+        } else {
+            context.window().show();
+        }
+    }
+
     fn get_fullscreen(&self) -> bool {
         return self.fullscreen.get();
     }
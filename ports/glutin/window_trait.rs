@@ -25,5 +25,7 @@ pub trait WindowPortsMethods: WindowMethods {
     fn set_inner_size(&self, _size: DeviceIntSize) {}
     fn set_position(&self, _point: DeviceIntPoint) {}
     fn set_fullscreen(&self, _state: bool) {}
+    fn set_maximized(&self, _state: bool) {}
+    fn set_minimized(&self, _state: bool) {}
     fn set_cursor(&self, _cursor: Cursor) {}
 }
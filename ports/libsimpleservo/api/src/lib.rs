@@ -516,6 +516,9 @@ impl ServoGlue {
                 EmbedderMsg::GetSelectedBluetoothDevice(_, sender) => {
                     let _ = sender.send(None);
                 },
+                EmbedderMsg::PromptCredentials(_, sender) => {
+                    let _ = sender.send(None);
+                },
                 EmbedderMsg::AllowUnload(sender) => {
                     let _ = sender.send(true);
                 },
@@ -523,6 +526,14 @@ impl ServoGlue {
                     info!("Alert: {}", message);
                     let _ = sender.send(());
                 },
+                EmbedderMsg::Confirm(message, sender) => {
+                    info!("Confirm: {}", message);
+                    let _ = sender.send(true);
+                },
+                EmbedderMsg::Prompt(message, default, sender) => {
+                    info!("Prompt: {}", message);
+                    let _ = sender.send(Some(default));
+                },
                 EmbedderMsg::AllowOpeningBrowser(response_chan) => {
                     // Note: would be a place to handle pop-ups config.
                     // see Step 7 of #the-rules-for-choosing-a-browsing-context-given-a-browsing-context-name
@@ -546,8 +557,14 @@ impl ServoGlue {
                     self.callbacks.host_callbacks.set_clipboard_contents(text);
                 },
                 EmbedderMsg::CloseBrowser => {
-                    // TODO: close the appropriate "tab".
-                    let _ = self.browsers.pop();
+                    match browser_id.and_then(|id| self.browsers.iter().position(|b| *b == id)) {
+                        Some(index) => {
+                            self.browsers.remove(index);
+                        },
+                        None => {
+                            let _ = self.browsers.pop();
+                        },
+                    }
                     if let Some(prev_browser_id) = self.browsers.last() {
                         self.browser_id = Some(*prev_browser_id);
                         self.events
@@ -568,10 +585,16 @@ impl ServoGlue {
                 EmbedderMsg::NewFavicon(..) |
                 EmbedderMsg::HeadParsed |
                 EmbedderMsg::SetFullscreenState(..) |
+                EmbedderMsg::SetMaximizedState(..) |
+                EmbedderMsg::SetMinimizedState(..) |
                 EmbedderMsg::ShowIME(..) |
                 EmbedderMsg::HideIME |
                 EmbedderMsg::Panic(..) |
-                EmbedderMsg::ReportProfile(..) => {},
+                EmbedderMsg::ReportProfile(..) |
+                EmbedderMsg::CookiesChanged(..) |
+                EmbedderMsg::DownloadStarted(..) |
+                EmbedderMsg::DownloadProgress(..) |
+                EmbedderMsg::DownloadFinished(..) => {},
             }
         }
         Ok(())
@@ -695,6 +718,7 @@ impl ResourceReaderMethods for ResourceReaderInstance {
             },
             Resource::QuirksModeCSS => &include_bytes!("../../../../resources/quirks-mode.css")[..],
             Resource::RippyPNG => &include_bytes!("../../../../resources/rippy.png")[..],
+            Resource::DebuggerJS => &include_bytes!("../../../../resources/debugger.js")[..],
             Resource::DomainList => &include_bytes!("../../../../resources/public_domains.txt")[..],
             Resource::BluetoothBlocklist => {
                 &include_bytes!("../../../../resources/gatt_blocklist.txt")[..]
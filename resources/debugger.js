@@ -0,0 +1,396 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+// The script debugger of a script thread, run in a global of its own with SpiderMonkey's
+// Debugger API. The script thread calls `addDebuggee` and `removeDebuggee` as windows come
+// and go, and `handleRequest` with the requests of the devtools server. Values are exchanged
+// as JSON, in the shapes serde gives the types of the `devtools_traits` crate.
+//
+// The thread defines `pauseThread(pipeline, pauseInfo)`, which only returns once the devtools
+// server resumes the pipeline, with the `ResumeLimit` to pause at next, or null.
+
+"use strict";
+
+const dbg = new Debugger();
+
+// The global of each debuggee pipeline, and the other way around.
+const debuggees = new Map();
+const pipelines = new Map();
+
+// The breakpoints, by URL and line, with the handler set in the scripts of that line.
+const breakpoints = [];
+
+// The sources the scripts of each pipeline came from, by id, and the other way around.
+const sources = new Map();
+const sourceIds = new Map();
+
+let nextId = 0;
+let pauseOnExceptions = false;
+let lastException = undefined;
+const interrupted = new Set();
+
+// The state of the current pause: its stack, youngest frame first, and the objects the
+// devtools server was given ids for, which live until the pipeline resumes.
+let pausedPipeline = null;
+let pausedFrames = [];
+let pauseObjects = new Map();
+let pauseObjectIds = new Map();
+
+// The frames with stepping handlers set.
+let steppingFrames = [];
+
+function addDebuggee(pipeline, global) {
+    const debuggee = dbg.addDebuggee(global);
+    debuggees.set(pipeline, debuggee);
+    pipelines.set(debuggee, pipeline);
+    // This finds the scripts of nested functions as well.
+    for (const script of dbg.findScripts({ global: debuggee })) {
+        addScript(script, false);
+    }
+}
+
+function removeDebuggee(pipeline) {
+    const debuggee = debuggees.get(pipeline);
+    if (!debuggee) {
+        return;
+    }
+    dbg.removeDebuggee(debuggee);
+    debuggees.delete(pipeline);
+    pipelines.delete(debuggee);
+    interrupted.delete(pipeline);
+    for (const [id, entry] of sources) {
+        if (entry.pipeline === pipeline) {
+            sources.delete(id);
+            sourceIds.delete(entry.source);
+        }
+    }
+}
+
+function pipelineOf(frame) {
+    return frame.script ? pipelines.get(frame.script.global) : undefined;
+}
+
+dbg.onNewScript = function(script) {
+    addScript(script, true);
+};
+
+function addScript(script, withChildren) {
+    const pipeline = pipelines.get(script.global);
+    if (pipeline === undefined) {
+        return;
+    }
+    sourceInfo(script.source, pipeline);
+    for (const breakpoint of breakpoints) {
+        if (script.url === breakpoint.url) {
+            setBreakpointIn(script, breakpoint, withChildren);
+        }
+    }
+}
+
+function setBreakpointIn(script, breakpoint, withChildren) {
+    if (script.startLine <= breakpoint.line &&
+        breakpoint.line < script.startLine + script.lineCount) {
+        for (const offset of script.getLineOffsets(breakpoint.line)) {
+            script.setBreakpoint(offset, breakpoint.handler);
+        }
+    }
+    if (withChildren) {
+        for (const child of script.getChildScripts()) {
+            setBreakpointIn(child, breakpoint, true);
+        }
+    }
+}
+
+function sourceInfo(source, pipeline) {
+    let id = sourceIds.get(source);
+    if (id === undefined) {
+        id = `${pipeline}/source${nextId++}`;
+        sourceIds.set(source, id);
+        sources.set(id, { source, pipeline });
+    }
+    return {
+        id,
+        url: source.url || "",
+        introduction_type: source.introductionType || null,
+    };
+}
+
+function grip(value) {
+    switch (typeof value) {
+        case "undefined":
+            return "VoidValue";
+        case "boolean":
+            return { BooleanValue: value };
+        case "number":
+            // JSON has no representation for the numbers that are not finite.
+            return Number.isFinite(value) ? { NumberValue: value } : { StringValue: String(value) };
+        case "string":
+            return { StringValue: value };
+        case "object":
+            if (value === null) {
+                return "NullValue";
+            }
+            if (value instanceof Debugger.Object) {
+                let id = pauseObjectIds.get(value);
+                if (id === undefined) {
+                    id = `${pausedPipeline}/object${nextId++}`;
+                    pauseObjectIds.set(value, id);
+                    pauseObjects.set(id, value);
+                }
+                return { ActorValue: { class: value.class, uuid: id } };
+            }
+            // The binding is uninitialized or optimized out.
+            return "VoidValue";
+        default:
+            return { StringValue: String(value) };
+    }
+}
+
+function calleeName(callee) {
+    return callee ? (callee.displayName || callee.name || null) : null;
+}
+
+function frameInfo(frame, depth) {
+    const pipeline = pipelineOf(frame);
+    const location = frame.script ? frame.script.getOffsetLocation(frame.offset) : null;
+    return {
+        depth,
+        type_: frame.type,
+        callee: calleeName(frame.callee),
+        this: grip(frame.this),
+        source: frame.script ? sourceInfo(frame.script.source, pipeline) : null,
+        line: location ? location.lineNumber : 0,
+        column: location ? location.columnNumber : 0,
+    };
+}
+
+function pause(frame, why) {
+    const pipeline = pipelineOf(frame);
+    if (pipeline === undefined) {
+        return undefined;
+    }
+    clearStepping();
+    pausedPipeline = pipeline;
+    pausedFrames = [];
+    for (let older = frame; older; older = older.older) {
+        pausedFrames.push(older);
+    }
+    const info = { why: typeof why === "function" ? why() : why, frame: frameInfo(frame, 0) };
+    const limit = JSON.parse(pauseThread(pipeline, JSON.stringify(info)));
+    pausedPipeline = null;
+    pausedFrames = [];
+    pauseObjects = new Map();
+    pauseObjectIds = new Map();
+    if (limit && frame.live) {
+        setResumeLimit(frame, limit);
+    }
+    return undefined;
+}
+
+// Pause at the next line, other than the line `start.frame` resumed at.
+function pauseOnStep(start) {
+    return function() {
+        const location = this.script.getOffsetLocation(this.offset);
+        if (!location.isEntryPoint) {
+            return undefined;
+        }
+        if (this === start.frame && location.lineNumber === start.line) {
+            return undefined;
+        }
+        return pause(this, "ResumeLimit");
+    };
+}
+
+function setStepping(frame, onStep, onPop) {
+    frame.onStep = onStep;
+    frame.onPop = onPop;
+    steppingFrames.push(frame);
+}
+
+function setResumeLimit(frame, limit) {
+    const start = {
+        frame,
+        line: frame.script.getOffsetLocation(frame.offset).lineNumber,
+    };
+    const onStep = pauseOnStep(start);
+    // Once the frame returns, pause at the next line of its caller.
+    const onPop = function() {
+        const older = this.older;
+        if (older && pipelineOf(older) !== undefined) {
+            setStepping(older, pauseOnStep({ frame: null, line: 0 }), undefined);
+        }
+        return undefined;
+    };
+    setStepping(frame, limit === "StepOut" ? undefined : onStep, onPop);
+    if (limit === "StepIn") {
+        dbg.onEnterFrame = function(entered) {
+            setStepping(entered, pauseOnStep({ frame: null, line: 0 }), onPop);
+        };
+    }
+}
+
+function clearStepping() {
+    for (const frame of steppingFrames) {
+        if (frame.live) {
+            frame.onStep = undefined;
+            frame.onPop = undefined;
+        }
+    }
+    steppingFrames = [];
+    dbg.onEnterFrame = interrupted.size ? onInterrupt : undefined;
+}
+
+function onInterrupt(frame) {
+    const pipeline = pipelineOf(frame);
+    if (!interrupted.has(pipeline)) {
+        return undefined;
+    }
+    interrupted.delete(pipeline);
+    // Pause at the first line of the frame rather than before it starts.
+    setStepping(frame, pauseOnStepAs("Interrupted"), undefined);
+    return undefined;
+}
+
+function pauseOnStepAs(why) {
+    return function() {
+        if (!this.script.getOffsetLocation(this.offset).isEntryPoint) {
+            return undefined;
+        }
+        return pause(this, why);
+    };
+}
+
+dbg.onDebuggerStatement = function(frame) {
+    return pause(frame, "DebuggerStatement");
+};
+
+dbg.onExceptionUnwind = function(frame, value) {
+    // An exception unwinds through every frame up to its handler, but only pauses once.
+    if (!pauseOnExceptions || value === lastException) {
+        return undefined;
+    }
+    lastException = value;
+    return pause(frame, () => ({ Exception: grip(value) }));
+};
+
+const requests = {
+    removeDebuggee,
+
+    listSources(pipeline) {
+        const list = [];
+        for (const [id, entry] of sources) {
+            if (entry.pipeline === pipeline) {
+                list.push(sourceInfo(entry.source, pipeline));
+            }
+        }
+        return list;
+    },
+
+    sourceText(pipeline, id) {
+        const entry = sources.get(id);
+        return entry && entry.pipeline === pipeline ? entry.source.text : null;
+    },
+
+    setBreakpoint(pipeline, location) {
+        if (breakpoints.some(b => b.url === location.url && b.line === location.line)) {
+            return;
+        }
+        const breakpoint = {
+            url: location.url,
+            line: location.line,
+            handler: {
+                hit(frame) {
+                    return pause(frame, "Breakpoint");
+                },
+            },
+        };
+        breakpoints.push(breakpoint);
+        // This finds the scripts of nested functions as well.
+        for (const script of dbg.findScripts({ url: location.url, line: location.line })) {
+            setBreakpointIn(script, breakpoint, false);
+        }
+    },
+
+    removeBreakpoint(pipeline, location) {
+        const index = breakpoints.findIndex(
+            b => b.url === location.url && b.line === location.line
+        );
+        if (index !== -1) {
+            dbg.clearBreakpoint(breakpoints[index].handler);
+            breakpoints.splice(index, 1);
+        }
+    },
+
+    pauseOnExceptions(pipeline, enabled) {
+        pauseOnExceptions = enabled;
+        lastException = undefined;
+    },
+
+    interrupt(pipeline) {
+        interrupted.add(pipeline);
+        if (!steppingFrames.length) {
+            dbg.onEnterFrame = onInterrupt;
+        }
+    },
+
+    frames(pipeline) {
+        if (pipeline !== pausedPipeline) {
+            return [];
+        }
+        return pausedFrames.map(frameInfo);
+    },
+
+    environments(pipeline, depth) {
+        const frame = pipeline === pausedPipeline ? pausedFrames[depth] : undefined;
+        if (!frame || !frame.live || !frame.environment) {
+            return [];
+        }
+        const list = [];
+        for (let env = frame.environment; env; env = env.parent) {
+            if (env.type === "declarative") {
+                list.push({
+                    type_: env.callee ? "function" : "block",
+                    callee: calleeName(env.callee),
+                    bindings: env.names().map(name => [name, grip(env.getVariable(name))]),
+                    object: null,
+                });
+            } else {
+                list.push({
+                    type_: env.type,
+                    callee: null,
+                    bindings: [],
+                    object: grip(env.object),
+                });
+            }
+        }
+        return list;
+    },
+
+    objectProperties(pipeline, id) {
+        const object = pauseObjects.get(id);
+        if (!object) {
+            return null;
+        }
+        const properties = [];
+        for (const name of object.getOwnPropertyNames()) {
+            const descriptor = object.getOwnPropertyDescriptor(name);
+            if (!descriptor) {
+                continue;
+            }
+            properties.push({
+                name,
+                value: grip(descriptor.value),
+                enumerable: descriptor.enumerable,
+                writable: !!descriptor.writable,
+                configurable: descriptor.configurable,
+            });
+        }
+        return { prototype: grip(object.proto), properties };
+    },
+};
+
+function handleRequest(name, args) {
+    const result = requests[name](...JSON.parse(args));
+    return JSON.stringify(result === undefined ? null : result);
+}
@@ -7,7 +7,10 @@ importScripts("interfaces.js");
 
 // IMPORTANT: Do not change the list below without review from a DOM peer!
 test_interfaces([
+  "AbortController",
+  "AbortSignal",
   "Blob",
+  "BroadcastChannel",
   "CanvasGradient",
   "CanvasPattern",
   "CloseEvent",
@@ -21,6 +24,7 @@ test_interfaces([
   "CustomEvent",
   "DedicatedWorkerGlobalScope",
   "DOMException",
+  "DOMStringList",
   "ErrorEvent",
   "Event",
   "EventSource",
@@ -32,8 +36,21 @@ test_interfaces([
   "FormData",
   "Headers",
   "History",
+  "IDBCursor",
+  "IDBCursorWithValue",
+  "IDBDatabase",
+  "IDBFactory",
+  "IDBIndex",
+  "IDBKeyRange",
+  "IDBObjectStore",
+  "IDBOpenDBRequest",
+  "IDBRequest",
+  "IDBTransaction",
+  "IDBVersionChangeEvent",
   "ImageData",
+  "MessageChannel",
   "MessageEvent",
+  "MessagePort",
   "Performance",
   "PerformanceEntry",
   "PerformanceMark",
@@ -44,10 +61,16 @@ test_interfaces([
   "PerformanceResourceTiming",
   "ProgressEvent",
   "PromiseRejectionEvent",
+  "ReadableStream",
+  "ReadableStreamDefaultController",
+  "ReadableStreamDefaultReader",
   "Request",
   "Response",
+  "SecurityPolicyViolationEvent",
   "TextDecoder",
   "TextEncoder",
+  "TransformStream",
+  "TransformStreamDefaultController",
   "URL",
   "URLSearchParams",
   "WebSocket",
@@ -55,6 +78,9 @@ test_interfaces([
   "WorkerGlobalScope",
   "WorkerLocation",
   "WorkerNavigator",
+  "WritableStream",
+  "WritableStreamDefaultController",
+  "WritableStreamDefaultWriter",
   "XMLHttpRequest",
   "XMLHttpRequestEventTarget",
   "XMLHttpRequestUpload",
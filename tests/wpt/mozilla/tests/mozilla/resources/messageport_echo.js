@@ -0,0 +1,6 @@
+onmessage = function(e) {
+  var port = e.ports[0];
+  port.onmessage = function(e) {
+    port.postMessage(e.data + " back");
+  };
+};